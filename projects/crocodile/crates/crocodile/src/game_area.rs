@@ -7,6 +7,17 @@ use super::{GRID_HEIGHT, GRID_WIDTH, TILE_LAYER, TILE_SIZE};
 #[derive(Component)]
 struct GameTile;
 
+/// World space rectangle covering the centers of every tile on the board
+pub fn board_bounds() -> Rect {
+    let mut bounds = Rect::from_center_size(coords_to_pixel(0, 0), Vec2::ZERO);
+    for r in 0..GRID_WIDTH + 1 {
+        for c in 0..GRID_HEIGHT + 1 {
+            bounds = bounds.union_point(coords_to_pixel(r, c));
+        }
+    }
+    bounds
+}
+
 pub fn setup_tiles_square(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
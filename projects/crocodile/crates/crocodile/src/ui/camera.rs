@@ -0,0 +1,314 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use simulation::gamestate::Team;
+
+use crate::{game_area::board_bounds, sim_wrapper::SimStateResource, TILE_SIZE};
+
+use super::{
+    controls::{controls_closed, Control, Keybindings},
+    sprite::MainCamera,
+    to_world,
+};
+
+/// World units panned per second when zoomed all the way out, panning slows
+/// down as the camera zooms in so the board moves at the same speed on screen
+const PAN_SPEED: f32 = 300.0;
+/// Distance from the window edge, in logical pixels, where the cursor scrolls the camera
+const EDGE_SCROLL_MARGIN: f32 = 10.0;
+/// Fraction the view shrinks by for each line scrolled
+const ZOOM_STEP: f32 = 0.1;
+/// Pixel scroll events, e.g. from touchpads, are counted as a line every this many pixels
+const PIXELS_PER_LINE: f32 = 20.0;
+const MIN_ZOOM: f32 = 0.2;
+/// At this scale the whole board fits in the window
+const MAX_ZOOM: f32 = 1.0;
+
+const MINIMAP_WIDTH: f32 = 160.0;
+const MINIMAP_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.7);
+const MINIMAP_DOT_SIZE: f32 = 4.0;
+const PLAYERS_COLOR: Color = Color::srgb(0.2, 0.4, 1.0);
+const NPCS_COLOR: Color = Color::srgb(1.0, 0.2, 0.2);
+
+pub(super) struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BoardBounds(board_bounds()))
+            .add_systems(Startup, setup_minimap)
+            .add_systems(
+                Update,
+                (
+                    (pan_camera, zoom_camera).run_if(controls_closed),
+                    clamp_camera,
+                    update_minimap_viewport,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, update_minimap_units);
+    }
+}
+
+/// Tile centers of the board, see [`board_bounds`]
+#[derive(Resource)]
+struct BoardBounds(Rect);
+
+impl BoardBounds {
+    /// Area shown on the minimap, the whole of every tile
+    fn minimap_area(&self) -> Rect {
+        self.0.inflate(TILE_SIZE as f32 / 2.0)
+    }
+}
+
+#[derive(Component)]
+struct Minimap;
+
+#[derive(Component)]
+struct MinimapUnit;
+
+/// Outline of the part of the board the camera can see
+#[derive(Component)]
+struct MinimapViewport;
+
+fn pan_camera(
+    time: Res<Time>,
+    bindings: Res<Keybindings>,
+    input: Res<ButtonInput<KeyCode>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let mut dir = Vec2::ZERO;
+    for (control, control_dir) in [
+        (Control::PanUp, Vec2::Y),
+        (Control::PanDown, Vec2::NEG_Y),
+        (Control::PanLeft, Vec2::NEG_X),
+        (Control::PanRight, Vec2::X),
+    ] {
+        if input.pressed(bindings.key(control)) {
+            dir += control_dir;
+        }
+    }
+
+    if let Some((cursor, window)) = q_window
+        .get_single()
+        .ok()
+        .and_then(|w| Some((w.cursor_position()?, w)))
+    {
+        dir += edge_scroll(cursor, window.size());
+    }
+
+    if dir == Vec2::ZERO {
+        return;
+    }
+
+    let (mut transform, projection) = q_camera.single_mut();
+    let delta = dir.normalize() * PAN_SPEED * projection.scale * time.delta_secs();
+    transform.translation += delta.extend(0.0);
+}
+
+/// Direction to pan for the cursor at `cursor` in a window of `size`, window
+/// coordinates start at the top left so the y axis is flipped
+fn edge_scroll(cursor: Vec2, size: Vec2) -> Vec2 {
+    let mut dir = Vec2::ZERO;
+    if cursor.x < EDGE_SCROLL_MARGIN {
+        dir.x -= 1.0;
+    }
+    if cursor.x > size.x - EDGE_SCROLL_MARGIN {
+        dir.x += 1.0;
+    }
+    if cursor.y < EDGE_SCROLL_MARGIN {
+        dir.y += 1.0;
+    }
+    if cursor.y > size.y - EDGE_SCROLL_MARGIN {
+        dir.y -= 1.0;
+    }
+    dir
+}
+
+fn zoom_camera(
+    mut ev_wheel: EventReader<MouseWheel>,
+    mut q_camera: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    let lines: f32 = ev_wheel
+        .read()
+        .map(|ev| match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    if lines == 0.0 {
+        return;
+    }
+
+    let mut projection = q_camera.single_mut();
+    projection.scale = zoom(projection.scale, lines);
+}
+
+/// Scale after scrolling `lines`, scrolling up zooms in
+fn zoom(scale: f32, lines: f32) -> f32 {
+    (scale * (1.0 - ZOOM_STEP).powf(lines)).clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+/// Keeps the center of the view over the board
+fn clamp_camera(bounds: Res<BoardBounds>, mut q_camera: Query<&mut Transform, With<MainCamera>>) {
+    for mut transform in &mut q_camera {
+        let center = transform.translation.truncate();
+        let clamped = center.clamp(bounds.0.min, bounds.0.max);
+        // only write when needed so change detection still works downstream
+        if clamped != center {
+            transform.translation = clamped.extend(transform.translation.z);
+        }
+    }
+}
+
+fn setup_minimap(mut commands: Commands, bounds: Res<BoardBounds>) {
+    let area = bounds.minimap_area();
+    commands
+        .spawn((
+            Minimap,
+            Node {
+                position_type: PositionType::Absolute,
+                // bottom right of the game area, next to the right panel
+                right: Val::Px(410.),
+                bottom: Val::Px(10.),
+                width: Val::Px(MINIMAP_WIDTH),
+                height: Val::Px(MINIMAP_WIDTH * area.height() / area.width()),
+                border: UiRect::all(Val::Px(2.)),
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            BackgroundColor(MINIMAP_BACKGROUND),
+            BorderColor(Color::WHITE),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                MinimapViewport,
+                Node {
+                    position_type: PositionType::Absolute,
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                BorderColor(Color::WHITE),
+                // draw over the unit dots
+                ZIndex(1),
+            ));
+        });
+}
+
+/// Position of `world` on the minimap, as percentages from its bottom left corner
+fn to_minimap(area: Rect, world: Vec2) -> Vec2 {
+    (world - area.min) / area.size() * 100.0
+}
+
+/// Redraws a dot for each model in its team's color whenever the sim changes
+fn update_minimap_units(
+    mut commands: Commands,
+    sim: Res<SimStateResource>,
+    bounds: Res<BoardBounds>,
+    minimap: Query<Entity, With<Minimap>>,
+    old_units: Query<Entity, With<MinimapUnit>>,
+) {
+    if !sim.is_changed() {
+        return;
+    }
+
+    for e in &old_units {
+        commands.entity(e).despawn_recursive();
+    }
+
+    let Ok(minimap) = minimap.get_single() else {
+        return;
+    };
+
+    let area = bounds.minimap_area();
+    commands.entity(minimap).with_children(|parent| {
+        for (id, loc, _) in sim.0.sprites() {
+            let color = match sim.0.get_model_team(id) {
+                Team::Players => PLAYERS_COLOR,
+                Team::NPCs => NPCS_COLOR,
+            };
+            let pos = to_minimap(area, to_world(&loc));
+            parent.spawn((
+                MinimapUnit,
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(pos.x),
+                    bottom: Val::Percent(pos.y),
+                    width: Val::Px(MINIMAP_DOT_SIZE),
+                    height: Val::Px(MINIMAP_DOT_SIZE),
+                    // center the dot on the model
+                    margin: UiRect {
+                        left: Val::Px(-MINIMAP_DOT_SIZE / 2.0),
+                        bottom: Val::Px(-MINIMAP_DOT_SIZE / 2.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                BackgroundColor(color),
+            ));
+        }
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn update_minimap_viewport(
+    bounds: Res<BoardBounds>,
+    q_camera: Query<
+        (&Transform, &OrthographicProjection),
+        (
+            With<MainCamera>,
+            Or<(Changed<Transform>, Changed<OrthographicProjection>)>,
+        ),
+    >,
+    mut q_viewport: Query<&mut Node, With<MinimapViewport>>,
+) {
+    let Ok((transform, projection)) = q_camera.get_single() else {
+        return;
+    };
+
+    let area = bounds.minimap_area();
+    let view = Rect::from_center_size(transform.translation.truncate(), projection.area.size())
+        .intersect(area);
+    let min = to_minimap(area, view.min);
+    let max = to_minimap(area, view.max);
+    for mut node in &mut q_viewport {
+        node.left = Val::Percent(min.x);
+        node.bottom = Val::Percent(min.y);
+        node.width = Val::Percent(max.x - min.x);
+        node.height = Val::Percent(max.y - min.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec2, Rect};
+
+    use super::{edge_scroll, to_minimap, zoom, MAX_ZOOM, MIN_ZOOM};
+
+    #[test]
+    fn test_zoom_is_clamped() {
+        assert!(zoom(1.0, 1.0) < 1.0);
+        assert_eq!(zoom(1.0, -1.0), MAX_ZOOM);
+        assert_eq!(zoom(1.0, 100.0), MIN_ZOOM);
+        // zooming in and back out returns to the same scale
+        assert!((zoom(zoom(0.5, 3.0), -3.0) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_edge_scroll() {
+        let size = vec2(800.0, 600.0);
+        assert_eq!(edge_scroll(vec2(400.0, 300.0), size), vec2(0.0, 0.0));
+        // top left corner pans up and left
+        assert_eq!(edge_scroll(vec2(0.0, 0.0), size), vec2(-1.0, 1.0));
+        assert_eq!(edge_scroll(vec2(799.0, 599.0), size), vec2(1.0, -1.0));
+    }
+
+    #[test]
+    fn test_to_minimap() {
+        let area = Rect::new(-10.0, 0.0, 10.0, 40.0);
+        assert_eq!(to_minimap(area, vec2(-10.0, 0.0)), vec2(0.0, 0.0));
+        assert_eq!(to_minimap(area, vec2(0.0, 30.0)), vec2(50.0, 75.0));
+    }
+}
@@ -0,0 +1,545 @@
+use std::{collections::HashMap, fs};
+
+use bevy::{color::palettes::css::GREEN, prelude::*};
+use simulation::gamestate::Action;
+
+use crate::{sim_wrapper::SimStateResource, PlayState, NORMAL_BUTTON};
+
+use super::{highlight_moves, right_panel::ActionButton, ActionEvent, SelectedModel};
+
+/// Keybindings are loaded from and saved to this file, relative to where the game is run
+const CONTROLS_PATH: &str = "controls.json";
+
+/// Keys that can be bound to a control, stored in the config file by their debug name
+const BINDABLE_KEYS: [KeyCode; 52] = {
+    use KeyCode::*;
+    [
+        KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM, KeyN, KeyO,
+        KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ, Digit0, Digit1, Digit2,
+        Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9, Space, Tab, Enter, Backspace,
+        ArrowUp, ArrowDown, ArrowLeft, ArrowRight, F1, F2, F3, F4, F5, F6, F7, F8,
+    ]
+};
+
+pub(super) struct ControlsPlugin;
+
+impl bevy::app::Plugin for ControlsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(Keybindings::load())
+            .init_resource::<ControlsScreen>()
+            .init_resource::<FocusedWeapon>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        toggle_controls_screen,
+                        remap_button_click,
+                        remap_key,
+                        update_controls_screen,
+                    )
+                        .chain(),
+                    highlight_focused_weapon,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    end_phase.run_if(control_just_pressed(Control::EndPhase)),
+                    (cycle_units, highlight_moves)
+                        .chain()
+                        .run_if(control_just_pressed(Control::CycleUnits)),
+                    cycle_weapons.run_if(control_just_pressed(Control::CycleWeapons)),
+                    use_weapon.run_if(control_just_pressed(Control::UseWeapon)),
+                    undo.run_if(control_just_pressed(Control::Undo)),
+                )
+                    .run_if(in_state(PlayState::Waiting)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+    EndPhase,
+    CycleUnits,
+    CycleWeapons,
+    UseWeapon,
+    Undo,
+    ShowControls,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+}
+
+impl Control {
+    const ALL: [Control; 10] = [
+        Control::EndPhase,
+        Control::CycleUnits,
+        Control::CycleWeapons,
+        Control::UseWeapon,
+        Control::Undo,
+        Control::ShowControls,
+        Control::PanUp,
+        Control::PanDown,
+        Control::PanLeft,
+        Control::PanRight,
+    ];
+
+    /// Name of the control in the config file
+    fn name(&self) -> &'static str {
+        match self {
+            Control::EndPhase => "end_phase",
+            Control::CycleUnits => "cycle_units",
+            Control::CycleWeapons => "cycle_weapons",
+            Control::UseWeapon => "use_weapon",
+            Control::Undo => "undo",
+            Control::ShowControls => "show_controls",
+            Control::PanUp => "pan_up",
+            Control::PanDown => "pan_down",
+            Control::PanLeft => "pan_left",
+            Control::PanRight => "pan_right",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Control::EndPhase => "End phase",
+            Control::CycleUnits => "Next unit",
+            Control::CycleWeapons => "Next weapon",
+            Control::UseWeapon => "Use weapon",
+            Control::Undo => "Undo",
+            Control::ShowControls => "Show controls",
+            Control::PanUp => "Pan up",
+            Control::PanDown => "Pan down",
+            Control::PanLeft => "Pan left",
+            Control::PanRight => "Pan right",
+        }
+    }
+
+    fn default_key(&self) -> KeyCode {
+        match self {
+            Control::EndPhase => KeyCode::KeyE,
+            Control::CycleUnits => KeyCode::Tab,
+            Control::CycleWeapons => KeyCode::KeyQ,
+            Control::UseWeapon => KeyCode::Enter,
+            Control::Undo => KeyCode::KeyZ,
+            Control::ShowControls => KeyCode::F1,
+            Control::PanUp => KeyCode::KeyW,
+            Control::PanDown => KeyCode::KeyS,
+            Control::PanLeft => KeyCode::KeyA,
+            Control::PanRight => KeyCode::KeyD,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Keybindings(HashMap<Control, KeyCode>);
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self(Control::ALL.iter().map(|c| (*c, c.default_key())).collect())
+    }
+}
+
+impl Keybindings {
+    /// Loads the bindings from [`CONTROLS_PATH`], any missing or invalid
+    /// bindings use the default keys
+    pub fn load() -> Self {
+        match fs::read_to_string(CONTROLS_PATH) {
+            Ok(s) => Self::parse(&s),
+            Err(_) => {
+                debug!("no controls file found, using default keybindings");
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        let mut bindings = Self::default();
+        let config = match json::parse(s) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!(
+                    "failed to parse controls file, using default keybindings: {}",
+                    e
+                );
+                return bindings;
+            }
+        };
+
+        for control in Control::ALL {
+            let Some(name) = config[control.name()].as_str() else {
+                continue;
+            };
+            match key_from_name(name) {
+                Some(key) => bindings.set(control, key),
+                None => warn!("unknown key {} for {}", name, control.name()),
+            }
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = fs::write(CONTROLS_PATH, self.to_json()) {
+            warn!("failed to save controls: {}", e);
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let mut config = json::JsonValue::new_object();
+        for control in Control::ALL {
+            config[control.name()] = key_name(self.key(control)).into();
+        }
+        config.pretty(2)
+    }
+
+    pub fn key(&self, control: Control) -> KeyCode {
+        self.0[&control]
+    }
+
+    /// Binds `key` to `control`, if another control was using the key it
+    /// swaps to the old key of `control` so no two controls share a key
+    pub fn set(&mut self, control: Control, key: KeyCode) {
+        let old = self.key(control);
+        if let Some(other) = Control::ALL.into_iter().find(|c| self.key(*c) == key) {
+            self.0.insert(other, old);
+        }
+        self.0.insert(control, key);
+    }
+}
+
+fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.into_iter().find(|k| key_name(*k) == name)
+}
+
+/// Run condition for when the key bound to `control` is pressed, keys are
+/// ignored while the controls screen is open
+fn control_just_pressed(
+    control: Control,
+) -> impl FnMut(Res<Keybindings>, Res<ButtonInput<KeyCode>>, Res<ControlsScreen>) -> bool + Clone {
+    move |bindings: Res<Keybindings>,
+          input: Res<ButtonInput<KeyCode>>,
+          screen: Res<ControlsScreen>| {
+        !screen.open && input.just_pressed(bindings.key(control))
+    }
+}
+
+/// Run condition for when the controls screen is closed, for systems that
+/// read held keys rather than using [`control_just_pressed`]
+pub(super) fn controls_closed(screen: Res<ControlsScreen>) -> bool {
+    !screen.open
+}
+
+#[derive(Resource, Default)]
+pub(super) struct ControlsScreen {
+    open: bool,
+    /// Control waiting on a key press to be rebound
+    remapping: Option<Control>,
+}
+
+/// Weapon selected with the keyboard, used when the use weapon key is pressed
+#[derive(Resource, Default)]
+struct FocusedWeapon(Option<Action>);
+
+#[derive(Component)]
+struct ControlsScreenRoot;
+
+#[derive(Component)]
+#[require(Button)]
+struct RemapButton(Control);
+
+fn end_phase(mut ev_action: EventWriter<ActionEvent>, sim: Res<SimStateResource>) {
+    let mut legal_actions = Vec::new();
+    sim.0.legal_actions(&mut legal_actions);
+    if legal_actions.contains(&Action::EndPhase) {
+        ev_action.send(ActionEvent {
+            action: Action::EndPhase,
+        });
+    }
+}
+
+/// Selects the next model that has a legal action
+fn cycle_units(sim: Res<SimStateResource>, mut selected: ResMut<SelectedModel>) {
+    let mut legal_actions = Vec::new();
+    sim.0.legal_actions(&mut legal_actions);
+
+    let can_act = sim
+        .0
+        .sprites()
+        .into_iter()
+        .map(|(id, _, _)| id)
+        .filter(|id| {
+            legal_actions.iter().any(|a| match a {
+                Action::Move { id: a_id, .. }
+                | Action::Charge { id: a_id, .. }
+                | Action::RemoveModel { id: a_id } => a_id == id,
+                Action::UseWeapon { from, .. } => *from == sim.0.get_model_unit(*id),
+                _ => false,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if can_act.is_empty() {
+        return;
+    }
+
+    let next = match can_act.iter().position(|id| *id == selected.0) {
+        Some(i) => can_act[(i + 1) % can_act.len()],
+        None => can_act[0],
+    };
+    debug!("cycling selection to: {:?}", next);
+    selected.0 = next;
+}
+
+/// Focuses the next weapon the selected model's unit can use
+fn cycle_weapons(
+    sim: Res<SimStateResource>,
+    selected: Res<SelectedModel>,
+    mut focused: ResMut<FocusedWeapon>,
+) {
+    let mut legal_actions = Vec::new();
+    sim.0.legal_actions(&mut legal_actions);
+
+    let unit = sim.0.get_model_unit(selected.0);
+    let weapons = legal_actions
+        .into_iter()
+        .filter(|a| matches!(a, Action::UseWeapon { from, .. } if *from == unit))
+        .collect::<Vec<_>>();
+
+    if weapons.is_empty() {
+        focused.0 = None;
+        return;
+    }
+
+    focused.0 = match focused.0.and_then(|f| weapons.iter().position(|a| *a == f)) {
+        Some(i) => Some(weapons[(i + 1) % weapons.len()]),
+        None => Some(weapons[0]),
+    };
+}
+
+fn use_weapon(
+    mut ev_action: EventWriter<ActionEvent>,
+    sim: Res<SimStateResource>,
+    mut focused: ResMut<FocusedWeapon>,
+) {
+    let Some(action) = focused.0.take() else {
+        return;
+    };
+
+    let mut legal_actions = Vec::new();
+    sim.0.legal_actions(&mut legal_actions);
+    if legal_actions.contains(&action) {
+        ev_action.send(ActionEvent { action });
+    }
+}
+
+fn undo(mut next_state: ResMut<NextState<PlayState>>, mut sim: ResMut<SimStateResource>) {
+    if !sim.0.can_undo() {
+        return;
+    }
+    debug!("undoing last action");
+    sim.0.undo();
+    next_state.set(PlayState::Processing);
+}
+
+/// Outline the action button of the focused weapon
+fn highlight_focused_weapon(
+    mut focused: ResMut<FocusedWeapon>,
+    sim: Res<SimStateResource>,
+    selected: Res<SelectedModel>,
+    mut buttons: Query<(&ActionButton, &Interaction, &mut BorderColor)>,
+) {
+    // focus doesn't carry over once the legal actions change
+    if sim.is_changed() || selected.is_changed() {
+        focused.0 = None;
+    }
+
+    for (button, interaction, mut border) in &mut buttons {
+        if *interaction != Interaction::None {
+            continue;
+        }
+        border.0 = if Some(button.0) == focused.0 {
+            GREEN.into()
+        } else {
+            Color::BLACK
+        };
+    }
+}
+
+fn toggle_controls_screen(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<Keybindings>,
+    mut screen: ResMut<ControlsScreen>,
+    root: Query<Entity, With<ControlsScreenRoot>>,
+) {
+    // keys pressed while remapping are handled by remap_key
+    if screen.remapping.is_some() {
+        return;
+    }
+
+    let toggle = input.just_pressed(bindings.key(Control::ShowControls));
+    let close = screen.open && input.just_pressed(KeyCode::Escape);
+    if !toggle && !close {
+        return;
+    }
+
+    screen.open = !screen.open;
+    if !screen.open {
+        for e in &root {
+            commands.entity(e).despawn_recursive();
+        }
+        return;
+    }
+
+    commands
+        .spawn((
+            ControlsScreenRoot,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+            // draw over the side panels
+            GlobalZIndex(1),
+        ))
+        .with_children(|parent| {
+            parent.spawn(Text::new("Controls"));
+            parent.spawn(Text::new("Click a key to remap it, Escape to close"));
+
+            for control in Control::ALL {
+                parent
+                    .spawn(Node {
+                        width: Val::Px(400.),
+                        justify_content: JustifyContent::SpaceBetween,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(Text::new(control.label()));
+                        parent
+                            .spawn((
+                                RemapButton(control),
+                                Node {
+                                    width: Val::Px(150.),
+                                    border: UiRect::all(Val::Px(5.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(NORMAL_BUTTON),
+                                BorderColor(Color::BLACK),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text(key_name(bindings.key(control))),
+                                    TextFont {
+                                        font: Default::default(),
+                                        font_size: 25.0,
+                                        ..default()
+                                    },
+                                    TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                                ));
+                            });
+                    });
+            }
+        });
+}
+
+fn remap_button_click(
+    interaction_query: Query<(&Interaction, &RemapButton), Changed<Interaction>>,
+    mut screen: ResMut<ControlsScreen>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            debug!("remapping {:?}", button.0);
+            screen.remapping = Some(button.0);
+        }
+    }
+}
+
+/// Binds the next key pressed to the control being remapped, escape cancels
+fn remap_key(
+    input: Res<ButtonInput<KeyCode>>,
+    mut screen: ResMut<ControlsScreen>,
+    mut bindings: ResMut<Keybindings>,
+) {
+    let Some(control) = screen.remapping else {
+        return;
+    };
+
+    if input.just_pressed(KeyCode::Escape) {
+        screen.remapping = None;
+        return;
+    }
+
+    let Some(key) = input.get_just_pressed().find(|k| BINDABLE_KEYS.contains(k)) else {
+        return;
+    };
+
+    bindings.set(control, *key);
+    bindings.save();
+    screen.remapping = None;
+}
+
+/// Keeps the key labels in sync with the bindings
+fn update_controls_screen(
+    screen: Res<ControlsScreen>,
+    bindings: Res<Keybindings>,
+    buttons: Query<(&RemapButton, &Children)>,
+    mut text: Query<&mut Text>,
+) {
+    if !screen.is_changed() && !bindings.is_changed() {
+        return;
+    }
+
+    for (button, children) in &buttons {
+        let label = if screen.remapping == Some(button.0) {
+            "Press a key...".to_string()
+        } else {
+            key_name(bindings.key(button.0))
+        };
+
+        for child in children {
+            if let Ok(mut text) = text.get_mut(*child) {
+                text.0 = label.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::keyboard::KeyCode;
+
+    use super::{Control, Keybindings};
+
+    #[test]
+    fn test_keybindings_round_trip() {
+        let mut bindings = Keybindings::default();
+        bindings.set(Control::EndPhase, KeyCode::Space);
+        assert_eq!(Keybindings::parse(&bindings.to_json()), bindings);
+
+        // unknown keys and bad files fall back to the defaults
+        let parsed = Keybindings::parse(r#"{"undo": "NotAKey"}"#);
+        assert_eq!(parsed.key(Control::Undo), Control::Undo.default_key());
+        assert_eq!(Keybindings::parse("not json"), Keybindings::default());
+    }
+
+    #[test]
+    fn test_keybindings_swap_on_conflict() {
+        let mut bindings = Keybindings::default();
+        bindings.set(Control::EndPhase, Control::Undo.default_key());
+        assert_eq!(bindings.key(Control::EndPhase), Control::Undo.default_key());
+        assert_eq!(bindings.key(Control::Undo), Control::EndPhase.default_key());
+    }
+}
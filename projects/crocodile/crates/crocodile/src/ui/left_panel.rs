@@ -82,7 +82,7 @@ fn undo_button_click(
     mut sim: ResMut<SimStateResource>,
 ) {
     for interaction in &interaction_query {
-        if *interaction == Interaction::Pressed {
+        if *interaction == Interaction::Pressed && sim.0.can_undo() {
             debug!("undoing last action");
             sim.0.undo();
             next_state.set(PlayState::Processing);
@@ -1,9 +1,11 @@
 use animation::animate_sprite;
 use bevy::{input::common_conditions::*, math::vec2, prelude::*, window::PrimaryWindow};
+use camera::CameraPlugin;
 use character::{
     cleanup_resolution_text, spawn_character, weapon_resolution, CharacterSpawnEvent,
     WeaponResolutionEvent,
 };
+use controls::ControlsPlugin;
 use left_panel::LeftPanelPlugin;
 use right_panel::{setup_right_panel, RightPanelPlugin};
 use simulation::gamestate::{spatial::SimCoords, Action, ModelId, Phase};
@@ -17,7 +19,9 @@ use crate::{
 };
 
 pub mod animation;
+mod camera;
 pub mod character;
+mod controls;
 mod left_panel;
 mod right_panel;
 pub mod sprite;
@@ -31,7 +35,12 @@ impl Plugin for UIPlugin {
         app.add_event::<CharacterSpawnEvent>();
         app.add_event::<WeaponResolutionEvent>();
 
-        app.add_plugins((LeftPanelPlugin, RightPanelPlugin));
+        app.add_plugins((
+            LeftPanelPlugin,
+            RightPanelPlugin,
+            ControlsPlugin,
+            CameraPlugin,
+        ));
 
         app.add_systems(
             Startup,
@@ -37,7 +37,7 @@ struct ActionButtonParent;
 struct ActionInfoParent;
 
 #[derive(Component)]
-struct ActionButton(Action);
+pub(super) struct ActionButton(pub(super) Action);
 
 #[derive(Component)]
 struct SuccessInfoParent;
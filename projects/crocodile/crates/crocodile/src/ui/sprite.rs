@@ -79,12 +79,14 @@ pub struct MainCamera;
 pub(super) fn setup_camera(mut commands: Commands) {
     commands.spawn((
         Camera2d {},
-        Projection::Orthographic(OrthographicProjection {
+        // set the projection Camera2d requires rather than adding a Projection,
+        // the camera controls zoom by changing its scale
+        OrthographicProjection {
             scaling_mode: ScalingMode::FixedVertical {
                 viewport_height: (GRID_HEIGHT * TILE_SIZE + TILE_SIZE) as f32,
             },
             ..OrthographicProjection::default_2d()
-        }),
+        },
         Transform::from_xyz(
             ((GRID_WIDTH + 1) * TILE_SIZE / 2) as f32,
             ((GRID_HEIGHT + 1) * TILE_SIZE / 2) as f32,
@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use simulation::gamestate::{Phase, SimState};
+
+/// Random actions played from the start of the default encounter to find the
+/// mid-game states, deep enough to reach every phase
+const MAX_GENERATIONS: usize = 200;
+
+/// The first non-chance state of each phase reached by random playouts of the
+/// default encounter, so the units have moved off their starting positions
+fn mid_game_states() -> Vec<(Phase, SimState)> {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+    let mut states: Vec<(Phase, SimState)> = Vec::new();
+    let mut actions = Vec::new();
+    // nothing has moved during the first movement phase
+    let mut started = false;
+
+    let mut gs = SimState::default();
+    for _ in 0..MAX_GENERATIONS {
+        if gs.is_terminal() {
+            break;
+        }
+
+        let a = if gs.is_chance_node() {
+            gs.chance_outcomes().sample(&mut rng)
+        } else {
+            let phase = gs.phase();
+            started |= phase != Phase::Movement;
+            if started && !states.iter().any(|(p, _)| *p == phase) {
+                states.push((phase, gs.clone()));
+            }
+            gs.legal_actions(&mut actions);
+            *actions.choose(&mut rng).unwrap()
+        };
+        gs.apply(a);
+    }
+
+    states
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let states = mid_game_states();
+    let mut actions = Vec::new();
+
+    // search depth is limited by how fast nodes can be expanded, one call per
+    // element
+    let mut group = c.benchmark_group("legal_actions");
+    group.throughput(Throughput::Elements(1));
+    for (phase, gs) in &states {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{phase:?}")),
+            gs,
+            |b, gs| b.iter(|| gs.legal_actions(&mut actions)),
+        );
+    }
+    group.finish();
+
+    // each iteration applies and undoes every legal action of the state
+    let mut group = c.benchmark_group("apply_undo");
+    for (phase, gs) in &states {
+        gs.legal_actions(&mut actions);
+        group.throughput(Throughput::Elements(actions.len() as u64));
+        let mut gs = gs.clone();
+        group.bench_function(BenchmarkId::from_parameter(format!("{phase:?}")), |b| {
+            b.iter(|| {
+                for a in &actions {
+                    gs.apply(*a);
+                    gs.undo();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
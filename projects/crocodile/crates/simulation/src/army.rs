@@ -0,0 +1,295 @@
+use core::fmt::Display;
+use std::collections::HashMap;
+
+use crate::{
+    gamestate::{spatial::SimCoords, SimState, Team, WORLD_SIZE},
+    info::{insert_unit, ModelType, Weapon},
+};
+
+/// A unit in an army list, every model in the unit has the same weapons
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitEntry {
+    pub model: ModelType,
+    pub team: Team,
+    /// Starting location of each model
+    pub locs: Vec<SimCoords>,
+    pub weapons: Vec<Weapon>,
+}
+
+impl UnitEntry {
+    /// Creates a unit equipped with the default weapons for the model
+    pub fn new(model: ModelType, team: Team, locs: Vec<SimCoords>) -> Self {
+        Self {
+            model,
+            team,
+            locs,
+            weapons: model.default_weapons(),
+        }
+    }
+
+    pub fn with_weapons(mut self, weapons: Vec<Weapon>) -> Self {
+        self.weapons = weapons;
+        self
+    }
+
+    pub fn points(&self) -> u32 {
+        let per_model = self.model.points() + self.weapons.iter().map(|w| w.points()).sum::<u32>();
+        per_model * self.locs.len() as u32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArmyError {
+    /// A team's units cost more than the point cap
+    OverPointCap { team: Team, points: u32, cap: u32 },
+    /// The unit at `unit` in the list has too few or too many models
+    InvalidUnitSize {
+        unit: usize,
+        model: ModelType,
+        size: usize,
+    },
+    /// The unit at `unit` is equipped with a weapon its models can't use
+    InvalidWeapon {
+        unit: usize,
+        model: ModelType,
+        weapon: Weapon,
+    },
+    /// The model is placed outside of the world
+    OutOfBounds { unit: usize, loc: SimCoords },
+    /// More than one model is placed at the same location
+    OverlappingModels {
+        first_unit: usize,
+        second_unit: usize,
+        loc: SimCoords,
+    },
+}
+
+impl Display for ArmyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ArmyError::OverPointCap { team, points, cap } => {
+                write!(
+                    f,
+                    "{team} army costs {points} points, over the {cap} point cap"
+                )
+            }
+            ArmyError::InvalidUnitSize { unit, model, size } => {
+                let range = model.unit_size();
+                write!(
+                    f,
+                    "unit {unit} has {size} {model} models, must have {} to {}",
+                    range.start(),
+                    range.end()
+                )
+            }
+            ArmyError::InvalidWeapon {
+                unit,
+                model,
+                weapon,
+            } => write!(f, "unit {unit}: {model} models can't use {weapon}"),
+            ArmyError::OutOfBounds { unit, loc } => {
+                write!(f, "unit {unit} has a model outside the world at {loc:?}")
+            }
+            ArmyError::OverlappingModels {
+                first_unit,
+                second_unit,
+                loc,
+            } => write!(
+                f,
+                "units {first_unit} and {second_unit} both have a model at {loc:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArmyError {}
+
+/// Builds army lists for both teams and validates them before creating the
+/// [`SimState`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArmyBuilder {
+    /// Maximum points for each team's army
+    point_cap: u32,
+    units: Vec<UnitEntry>,
+}
+
+impl ArmyBuilder {
+    pub fn new(point_cap: u32) -> Self {
+        Self {
+            point_cap,
+            units: Vec::new(),
+        }
+    }
+
+    pub fn add_unit(mut self, unit: UnitEntry) -> Self {
+        self.units.push(unit);
+        self
+    }
+
+    pub fn units(&self) -> &[UnitEntry] {
+        &self.units
+    }
+
+    pub fn point_cap(&self) -> u32 {
+        self.point_cap
+    }
+
+    /// Total points of a team's army
+    pub fn points(&self, team: Team) -> u32 {
+        self.units
+            .iter()
+            .filter(|u| u.team == team)
+            .map(|u| u.points())
+            .sum()
+    }
+
+    /// Returns every problem with the army lists, so they can all be shown at once
+    pub fn validate(&self) -> Result<(), Vec<ArmyError>> {
+        let mut errors = Vec::new();
+
+        for team in [Team::Players, Team::NPCs] {
+            let points = self.points(team);
+            if points > self.point_cap {
+                errors.push(ArmyError::OverPointCap {
+                    team,
+                    points,
+                    cap: self.point_cap,
+                });
+            }
+        }
+
+        let mut occupied = HashMap::new();
+        for (i, unit) in self.units.iter().enumerate() {
+            if !unit.model.unit_size().contains(&unit.locs.len()) {
+                errors.push(ArmyError::InvalidUnitSize {
+                    unit: i,
+                    model: unit.model,
+                    size: unit.locs.len(),
+                });
+            }
+
+            for &weapon in &unit.weapons {
+                if !unit.model.allowed_weapons().contains(&weapon) {
+                    errors.push(ArmyError::InvalidWeapon {
+                        unit: i,
+                        model: unit.model,
+                        weapon,
+                    });
+                }
+            }
+
+            for &loc in &unit.locs {
+                if loc.x >= WORLD_SIZE || loc.y >= WORLD_SIZE {
+                    errors.push(ArmyError::OutOfBounds { unit: i, loc });
+                } else if let Some(&first_unit) = occupied.get(&loc) {
+                    errors.push(ArmyError::OverlappingModels {
+                        first_unit,
+                        second_unit: i,
+                        loc,
+                    });
+                } else {
+                    occupied.insert(loc, i);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Validates the army lists and creates the starting state with the units
+    pub fn build(self) -> Result<SimState, Vec<ArmyError>> {
+        self.validate()?;
+
+        let mut gs = SimState::new();
+        for unit in self.units {
+            insert_unit(&mut gs, unit.model, unit.locs, unit.team, unit.weapons);
+        }
+        Ok(gs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gamestate::{spatial::sc, Team},
+        info::{ModelType, Weapon},
+    };
+
+    use super::{ArmyBuilder, ArmyError, UnitEntry};
+
+    #[test]
+    fn test_army_points() {
+        let marines = UnitEntry::new(
+            ModelType::SpaceMarine,
+            Team::Players,
+            vec![sc(1, 1), sc(2, 1)],
+        );
+        assert_eq!(marines.points(), 28);
+        let marines = marines.with_weapons(vec![Weapon::Flamer]);
+        assert_eq!(marines.points(), 38);
+
+        let builder = ArmyBuilder::new(100)
+            .add_unit(marines)
+            .add_unit(UnitEntry::new(
+                ModelType::NecronWarrior,
+                Team::NPCs,
+                vec![sc(1, 15)],
+            ));
+        assert_eq!(builder.points(Team::Players), 38);
+        assert_eq!(builder.points(Team::NPCs), 10);
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn test_army_validation() {
+        let builder = ArmyBuilder::new(20)
+            .add_unit(UnitEntry::new(
+                ModelType::SpaceMarine,
+                Team::Players,
+                vec![sc(1, 1), sc(2, 1)],
+            ))
+            .add_unit(
+                UnitEntry::new(
+                    ModelType::NecronWarrior,
+                    Team::NPCs,
+                    vec![sc(2, 1), sc(1, 30)],
+                )
+                .with_weapons(vec![Weapon::Boltgun]),
+            )
+            .add_unit(UnitEntry::new(ModelType::NecronWarrior, Team::NPCs, vec![]));
+
+        assert_eq!(
+            builder.validate(),
+            Err(vec![
+                ArmyError::OverPointCap {
+                    team: Team::Players,
+                    points: 28,
+                    cap: 20
+                },
+                ArmyError::InvalidWeapon {
+                    unit: 1,
+                    model: ModelType::NecronWarrior,
+                    weapon: Weapon::Boltgun
+                },
+                ArmyError::OverlappingModels {
+                    first_unit: 0,
+                    second_unit: 1,
+                    loc: sc(2, 1)
+                },
+                ArmyError::OutOfBounds {
+                    unit: 1,
+                    loc: sc(1, 30)
+                },
+                ArmyError::InvalidUnitSize {
+                    unit: 2,
+                    model: ModelType::NecronWarrior,
+                    size: 0
+                },
+            ])
+        );
+    }
+}
@@ -25,7 +25,7 @@ mod tests;
 mod utils;
 mod weapons;
 
-const WORLD_SIZE: usize = 20;
+pub(crate) const WORLD_SIZE: usize = 20;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
 pub enum Team {
@@ -188,6 +188,11 @@ pub enum ActionResult {
         team: Team,
         value: bool,
     },
+    /// Mark if a model charged this turn, these fight first
+    SetCharged {
+        id: ModelId,
+        value: bool,
+    },
 
     // UI only results
     Hit {
@@ -253,6 +258,8 @@ pub(super) struct Model {
     base_stats: ModelStats,
     remaining_actions: usize,
     charge_movement: u8,
+    /// Charged this turn, cleared at the end of the fight phase
+    charged: bool,
     team: Team,
     weapons: Arsenal,
 }
@@ -382,6 +389,11 @@ impl SimState {
         !self.pending_chance_action.is_empty()
     }
 
+    /// true if there is an action to undo
+    pub fn can_undo(&self) -> bool {
+        self.generation > 0
+    }
+
     /// undo the last action
     pub fn undo(&mut self) {
         if self.generation == 0 {
@@ -449,6 +461,7 @@ impl SimState {
                     self.ended_fight_phase.set(team, !value)
                 }
                 ActionResult::SetActiveFightTeam(team) => self.active_fight_team = team.enemy(),
+                ActionResult::SetCharged { id, value } => self.get_model_mut(id).charged = !value,
 
                 // UI only
                 ActionResult::Hit { id: _ } => {}
@@ -660,30 +673,24 @@ impl SimState {
         }
     }
 
+    /// Units with a model that charged this turn fight before the team's other
+    /// units
     fn legal_actions_fight(&self, actions: &mut Vec<Action>, team: Team) {
         let enemy_team = team.enemy();
 
+        let charged_units: HashSet<UnitId> = team_models!(self, team)
+            .filter(|m| m.charged)
+            .map(|m| m.unit)
+            .collect();
         for model in team_models!(self, team) {
-            for weapon in model.weapons.available_melee() {
-                let range = 1;
+            if charged_units.contains(&model.unit) {
+                self.push_fight_actions(actions, model, enemy_team);
+            }
+        }
 
-                for enemy in team_models!(self, enemy_team) {
-                    if self
-                        .get_loc(model.id)
-                        .unwrap()
-                        .dist(&self.get_loc(enemy.id).unwrap())
-                        <= range as usize
-                    {
-                        let action = Action::UseWeapon {
-                            from: model.unit,
-                            to: enemy.unit,
-                            weapon: *weapon,
-                        };
-                        if !actions.contains(&action) {
-                            actions.push(action);
-                        }
-                    }
-                }
+        if actions.is_empty() {
+            for model in team_models!(self, team) {
+                self.push_fight_actions(actions, model, enemy_team);
             }
         }
 
@@ -692,6 +699,31 @@ impl SimState {
         }
     }
 
+    /// Adds an attack against each enemy unit in melee range of `model`
+    fn push_fight_actions(&self, actions: &mut Vec<Action>, model: &Model, enemy_team: Team) {
+        for weapon in model.weapons.available_melee() {
+            let range = 1;
+
+            for enemy in team_models!(self, enemy_team) {
+                if self
+                    .get_loc(model.id)
+                    .unwrap()
+                    .dist(&self.get_loc(enemy.id).unwrap())
+                    <= range as usize
+                {
+                    let action = Action::UseWeapon {
+                        from: model.unit,
+                        to: enemy.unit,
+                        weapon: *weapon,
+                    };
+                    if !actions.contains(&action) {
+                        actions.push(action);
+                    }
+                }
+            }
+        }
+    }
+
     fn chance_outcomes_shoot(
         &self,
         from: UnitId,
@@ -777,6 +809,7 @@ impl SimState {
                     self.ended_fight_phase.set(team, value)
                 }
                 ActionResult::SetActiveFightTeam(team) => self.active_fight_team = team,
+                ActionResult::SetCharged { id, value } => self.get_model_mut(id).charged = value,
 
                 // UI only results
                 ActionResult::Hit { id: _ } => {}
@@ -812,6 +845,7 @@ impl SimState {
             sprite,
             weapons: Arsenal::from_vec(ranged_weapons),
             charge_movement: 0,
+            charged: false,
         };
 
         self.models.push(entity);
@@ -830,6 +864,11 @@ impl SimState {
     fn generate_results_charge(&mut self, id: ModelId, from: SimCoords, to: SimCoords) {
         let distance = to.dist(&from);
 
+        if !self.get_model(id).charged {
+            self.queued_results
+                .push(ActionResult::SetCharged { id, value: true });
+        }
+
         self.queued_results
             .push(ActionResult::Move { from, to, id });
         self.queued_results.push(ActionResult::SpendCharge {
@@ -917,6 +956,14 @@ impl SimState {
                 }
             }
 
+            // charges only give priority for the turn they're made in
+            for model in self.models.iter().filter(|m| m.charged) {
+                self.queued_results.push(ActionResult::SetCharged {
+                    id: model.id,
+                    value: false,
+                });
+            }
+
             // reset the finished fight tracker
             self.queued_results.push(ActionResult::SetFinishedFight {
                 team: self.cur_team().enemy(),
@@ -1060,6 +1107,10 @@ impl SimState {
         self.get_model(id).unit
     }
 
+    pub fn get_model_team(&self, id: ModelId) -> Team {
+        self.get_model(id).team
+    }
+
     pub fn sprites(&self) -> Vec<(ModelId, SimCoords, ModelSprite)> {
         self.models
             .iter()
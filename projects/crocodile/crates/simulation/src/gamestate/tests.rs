@@ -422,6 +422,79 @@ fn test_fight_phase() {
     assert_eq!(gs.cur_team(), Team::NPCs);
 }
 
+#[test]
+fn test_fight_phase_charge_priority() {
+    let mut gs = SimState::new();
+    insert_space_marine_unit(&mut gs, vec![sc(1, 10)], Team::Players);
+    insert_space_marine_unit(&mut gs, vec![sc(1, 13)], Team::Players);
+    insert_necron_warrior_unit(&mut gs, vec![sc(2, 10)], Team::NPCs);
+    insert_necron_warrior_unit(&mut gs, vec![sc(1, 16)], Team::NPCs);
+    gs.set_phase(Phase::Charge, Team::Players);
+    gs.apply(Action::RollResult { num_success: 4 });
+    gs.apply(Action::RollResult { num_success: 4 });
+
+    let charge = Action::Charge {
+        id: ModelId(1),
+        from: sc(1, 13),
+        to: sc(1, 15),
+    };
+    let mut actions = Vec::new();
+    gs.legal_actions(&mut actions);
+    assert!(actions.contains(&charge));
+    let before_charge = gs.clone();
+    gs.apply(charge);
+    assert!(gs.get_model(ModelId(1)).charged);
+    gs.undo();
+    assert_eq!(gs, before_charge);
+    gs.apply(charge);
+    gs.apply(Action::EndPhase);
+
+    // the unit that charged fights before the one that was already engaged
+    assert_eq!(gs.phase(), Phase::Fight);
+    assert_eq!(gs.cur_team(), Team::Players);
+    gs.legal_actions(&mut actions);
+    assert_eq!(
+        actions,
+        vec![Action::UseWeapon {
+            from: UnitId(2),
+            to: UnitId(4),
+            weapon: Weapon::SpaceMarineCloseCombatWeapon,
+        }]
+    );
+    gs.apply(actions[0]);
+    gs.apply(Action::RollResult { num_success: 0 });
+
+    gs.legal_actions(&mut actions);
+    gs.apply(actions[0]);
+    gs.apply(Action::RollResult { num_success: 0 });
+
+    // once the chargers have fought the others can
+    assert_eq!(gs.cur_team(), Team::Players);
+    gs.legal_actions(&mut actions);
+    assert_eq!(
+        actions,
+        vec![Action::UseWeapon {
+            from: UnitId(1),
+            to: UnitId(3),
+            weapon: Weapon::SpaceMarineCloseCombatWeapon,
+        }]
+    );
+
+    while gs.phase() == Phase::Fight {
+        gs.legal_actions(&mut actions);
+        gs.apply(actions[0]);
+        if gs.is_chance_node() {
+            gs.apply(Action::RollResult { num_success: 0 });
+        }
+    }
+    assert!(gs.models.iter().all(|m| !m.charged));
+
+    // undoing the end of the fight phase restores the charge
+    gs.undo();
+    assert_eq!(gs.phase(), Phase::Fight);
+    assert!(gs.get_model(ModelId(1)).charged);
+}
+
 #[test]
 fn test_undo() {
     let mut start_state = SimState::new();
@@ -436,6 +509,8 @@ fn test_undo() {
         Team::NPCs,
     );
 
+    assert!(!start_state.can_undo());
+
     let mut rng: StdRng = SeedableRng::seed_from_u64(42);
     let mut actions = Vec::new();
     let mut index = 0;
@@ -460,6 +535,7 @@ fn test_undo() {
             };
 
             state.apply(a);
+            assert!(state.can_undo());
             state.undo();
             assert_eq!(
                 state,
@@ -1,4 +1,4 @@
-use core::{fmt::Display, write};
+use core::{fmt::Display, ops::RangeInclusive, write};
 
 use crate::{
     gamestate::{spatial::SimCoords, SimState, Team, UnitType},
@@ -6,6 +6,23 @@ use crate::{
 };
 
 pub fn insert_space_marine_unit(gs: &mut SimState, locs: Vec<SimCoords>, team: Team) {
+    let model = ModelType::SpaceMarine;
+    insert_unit(gs, model, locs, team, model.default_weapons());
+}
+
+pub fn insert_necron_warrior_unit(gs: &mut SimState, locs: Vec<SimCoords>, team: Team) {
+    let model = ModelType::NecronWarrior;
+    insert_unit(gs, model, locs, team, model.default_weapons());
+}
+
+/// Inserts a unit with a model at each location, every model has the same weapons
+pub fn insert_unit(
+    gs: &mut SimState,
+    model: ModelType,
+    locs: Vec<SimCoords>,
+    team: Team,
+    weapons: Vec<Weapon>,
+) {
     for (i, l) in locs.into_iter().enumerate() {
         let unit_type = if i == 0 {
             UnitType::NewUnit
@@ -13,49 +30,99 @@ pub fn insert_space_marine_unit(gs: &mut SimState, locs: Vec<SimCoords>, team: T
             UnitType::LastUnit
         };
 
-        // https://wahapedia.ru/wh40k10ed/factions/space-marines/datasheets.html#Tactical-Squad
         gs.insert_model(
-            ModelSprite::Knight,
+            model.sprite(),
             l,
             team,
             unit_type,
-            ModelStats {
+            model.stats(),
+            weapons.clone(),
+        );
+    }
+}
+
+/// The datasheets models can be built from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ModelType {
+    // https://wahapedia.ru/wh40k10ed/factions/space-marines/datasheets.html#Tactical-Squad
+    SpaceMarine,
+    // https://wahapedia.ru/wh40k10ed/factions/necrons/Necron-Warriors
+    NecronWarrior,
+}
+
+impl ModelType {
+    pub fn stats(&self) -> ModelStats {
+        match self {
+            ModelType::SpaceMarine => ModelStats {
                 movement: 6,
                 wound: 2,
                 toughness: 4,
                 save: 3,
             },
-            vec![
+            ModelType::NecronWarrior => ModelStats {
+                movement: 5,
+                wound: 1,
+                toughness: 4,
+                save: 4,
+            },
+        }
+    }
+
+    pub fn sprite(&self) -> ModelSprite {
+        match self {
+            ModelType::SpaceMarine => ModelSprite::Knight,
+            ModelType::NecronWarrior => ModelSprite::Skeleton,
+        }
+    }
+
+    /// Points cost of each model, not including its weapons
+    pub fn points(&self) -> u32 {
+        match self {
+            ModelType::SpaceMarine => 14,
+            ModelType::NecronWarrior => 10,
+        }
+    }
+
+    /// Minimum and maximum number of models in a unit
+    pub fn unit_size(&self) -> RangeInclusive<usize> {
+        match self {
+            ModelType::SpaceMarine => 1..=10,
+            ModelType::NecronWarrior => 1..=20,
+        }
+    }
+
+    pub fn default_weapons(&self) -> Vec<Weapon> {
+        match self {
+            ModelType::SpaceMarine => vec![
                 Weapon::BoltPistol,
                 Weapon::Boltgun,
                 Weapon::SpaceMarineCloseCombatWeapon,
             ],
-        );
+            ModelType::NecronWarrior => vec![Weapon::GaussFlayer, Weapon::NecronCloseCombatWeapon],
+        }
     }
-}
 
-pub fn insert_necron_warrior_unit(gs: &mut SimState, locs: Vec<SimCoords>, team: Team) {
-    for (i, l) in locs.into_iter().enumerate() {
-        let unit_type = if i == 0 {
-            UnitType::NewUnit
-        } else {
-            UnitType::LastUnit
-        };
+    /// Weapons the models can be equipped with
+    pub fn allowed_weapons(&self) -> &[Weapon] {
+        match self {
+            ModelType::SpaceMarine => &[
+                Weapon::BoltPistol,
+                Weapon::Boltgun,
+                Weapon::Flamer,
+                Weapon::MissleLauncherFrag,
+                Weapon::SpaceMarineCloseCombatWeapon,
+            ],
+            ModelType::NecronWarrior => &[Weapon::GaussFlayer, Weapon::NecronCloseCombatWeapon],
+        }
+    }
+}
 
-        // https://wahapedia.ru/wh40k10ed/factions/necrons/Necron-Warriors
-        gs.insert_model(
-            ModelSprite::Skeleton,
-            l,
-            team,
-            unit_type,
-            ModelStats {
-                movement: 5,
-                wound: 1,
-                toughness: 4,
-                save: 4,
-            },
-            vec![Weapon::GaussFlayer, Weapon::NecronCloseCombatWeapon],
-        );
+impl Display for ModelType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ModelType::SpaceMarine => "Space Marine",
+            ModelType::NecronWarrior => "Necron Warrior",
+        })
     }
 }
 
@@ -187,6 +254,19 @@ impl Weapon {
             },
         }
     }
+
+    /// Points cost of equipping a model with the weapon
+    pub fn points(&self) -> u32 {
+        match self {
+            Weapon::Flamer => 5,
+            Weapon::MissleLauncherFrag => 10,
+            Weapon::BoltPistol
+            | Weapon::Boltgun
+            | Weapon::GaussFlayer
+            | Weapon::SpaceMarineCloseCombatWeapon
+            | Weapon::NecronCloseCombatWeapon => 0,
+        }
+    }
 }
 
 impl Display for Weapon {
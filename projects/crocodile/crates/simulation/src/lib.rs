@@ -2,6 +2,7 @@
 #![feature(let_chains)]
 
 pub mod ai;
+pub mod army;
 pub mod gamestate;
 pub mod info;
 
@@ -0,0 +1,53 @@
+//! Training throughput and node store lock contention of CFRES on the bower
+//! subgame for different thread counts and merge epochs
+
+use std::time::Instant;
+
+use card_platypus::{
+    algorithms::cfres::{self, CFRES},
+    metrics::{node_store_lock_contended, node_store_locks},
+};
+use games::gamestates::euchre::{actions::Card, util::generate_face_up_deals};
+use rand::SeedableRng;
+
+const THREADS: [usize; 3] = [8, 16, 32];
+/// An epoch of 1 merges after every iteration, close to locking on every node
+const EPOCH_LENS: [usize; 3] = [1, 16, 64];
+const ITERATIONS: usize = 2_000;
+
+pub fn main() {
+    cfres::feature::enable(cfres::feature::LinearCFR);
+
+    println!("| threads | epoch | iterations/s | locks | contended |");
+    println!("|---|---|---|---|---|");
+    for threads in THREADS {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        for epoch_len in EPOCH_LENS {
+            let mut alg = CFRES::new_euchre(SeedableRng::seed_from_u64(42), 0, None)
+                .with_epoch_len(epoch_len);
+            alg.set_game_generator(|| generate_face_up_deals(Card::JS));
+
+            let locks = node_store_locks::read();
+            let contended = node_store_lock_contended::read();
+            let start = Instant::now();
+            pool.install(|| alg.train(ITERATIONS));
+            let elapsed = start.elapsed();
+
+            let locks = node_store_locks::read() - locks;
+            let contended = node_store_lock_contended::read() - contended;
+            println!(
+                "| {} | {} | {:.1} | {} | {} ({:.1}%) |",
+                threads,
+                epoch_len,
+                ITERATIONS as f64 / elapsed.as_secs_f64(),
+                locks,
+                contended,
+                100.0 * contended as f64 / locks.max(1) as f64
+            );
+        }
+    }
+}
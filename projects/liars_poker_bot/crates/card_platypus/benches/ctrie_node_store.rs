@@ -0,0 +1,125 @@
+//! Read-modify-write throughput of the CTrie and DashMap as the CFRES node
+//! store for different thread counts, and the cost of iterating a snapshot
+//! while training threads are writing
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use card_platypus::{algorithms::cfres::InfoState, collections::ctrie::CTrie};
+use dashmap::DashMap;
+use games::{
+    istate::{IStateKey, NormalizedAction},
+    Action,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+const THREADS: [usize; 2] = [16, 32];
+const KEYS: usize = 100_000;
+const UPDATES: usize = 2_000_000;
+
+trait NodeStore: Sync {
+    fn name(&self) -> &'static str;
+    fn update(&self, key: IStateKey, regret: f32);
+    fn iter_count(&self) -> usize;
+}
+
+impl NodeStore for CTrie<IStateKey, InfoState> {
+    fn name(&self) -> &'static str {
+        "ctrie"
+    }
+
+    /// Not atomic, concurrent updates to the same key can be lost, which is
+    /// acceptable for sampled regret updates
+    fn update(&self, key: IStateKey, regret: f32) {
+        let mut info = self.get(&key).unwrap_or_else(new_info_state);
+        info.regrets[0] += regret;
+        self.insert(key, info);
+    }
+
+    fn iter_count(&self) -> usize {
+        self.iter().count()
+    }
+}
+
+impl NodeStore for DashMap<IStateKey, InfoState> {
+    fn name(&self) -> &'static str {
+        "dashmap"
+    }
+
+    fn update(&self, key: IStateKey, regret: f32) {
+        self.entry(key).or_insert_with(new_info_state).regrets[0] += regret;
+    }
+
+    fn iter_count(&self) -> usize {
+        self.iter().count()
+    }
+}
+
+pub fn main() {
+    let keys = (0..KEYS).map(key).collect::<Vec<_>>();
+
+    println!("| store | threads | updates/s | iteration during writes |");
+    println!("|---|---|---|---|");
+    for threads in THREADS {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        let ctrie = CTrie::new();
+        run(&pool, threads, &keys, &ctrie);
+        let dashmap = DashMap::new();
+        run(&pool, threads, &keys, &dashmap);
+    }
+}
+
+fn run(pool: &rayon::ThreadPool, threads: usize, keys: &[IStateKey], store: &impl NodeStore) {
+    let done = AtomicBool::new(false);
+    let mut elapsed = Duration::ZERO;
+    let mut iter_time = Duration::ZERO;
+
+    thread::scope(|s| {
+        // iterates the store the same way saving a checkpoint during training would
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                let start = Instant::now();
+                store.iter_count();
+                iter_time = iter_time.max(start.elapsed());
+            }
+        });
+
+        let start = Instant::now();
+        pool.install(|| {
+            (0..UPDATES).into_par_iter().for_each_init(
+                || StdRng::seed_from_u64(rayon::current_thread_index().unwrap_or(0) as u64),
+                |rng, _| store.update(keys[rng.gen_range(0..keys.len())], rng.gen()),
+            )
+        });
+        elapsed = start.elapsed();
+        done.store(true, Ordering::Relaxed);
+    });
+
+    println!(
+        "| {} | {} | {:.0} | {:?} |",
+        store.name(),
+        threads,
+        UPDATES as f64 / elapsed.as_secs_f64(),
+        iter_time
+    );
+}
+
+fn key(i: usize) -> IStateKey {
+    let mut k = IStateKey::default();
+    for b in (i as u32).to_le_bytes() {
+        k.push(Action(b));
+    }
+    k
+}
+
+fn new_info_state() -> InfoState {
+    InfoState::new((0..3).map(NormalizedAction::new_from_id).collect())
+}
@@ -0,0 +1,91 @@
+//! Compares batched regret updates on the cpu and gpu for infostates from the
+//! bower subgame, run with `cargo bench --features gpu --bench gpu_regret_updates`
+
+use std::time::{Duration, Instant};
+
+use burn::backend::Wgpu;
+use card_platypus::algorithms::{
+    cfres::InfoState,
+    gpu::{CpuRegretUpdater, GpuRegretUpdater, RegretUpdater, SlotValues},
+};
+use games::{
+    actions,
+    gamestates::euchre::{actions::Card, util::generate_face_up_deals_with_rng},
+    istate::NormalizedAction,
+    GameState,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+const BATCH_SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+const REPETITIONS: u32 = 10;
+
+pub fn main() {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+    let max_batch = *BATCH_SIZES.iter().max().unwrap();
+    let infostates = bower_infostates(max_batch, &mut rng);
+    let deltas = (0..max_batch)
+        .map(|_| random_slots(&mut rng))
+        .collect::<Vec<_>>();
+
+    let cpu = CpuRegretUpdater;
+    let gpu = GpuRegretUpdater::<Wgpu>::new(Default::default());
+    // the first call compiles the shaders
+    run(&gpu, &mut infostates[..1].to_vec(), &deltas[..1]);
+
+    println!("| infostates | cpu | gpu | speedup |");
+    println!("|---|---|---|---|");
+    for n in BATCH_SIZES {
+        let cpu_time = time(&cpu, &infostates[..n], &deltas[..n]);
+        let gpu_time = time(&gpu, &infostates[..n], &deltas[..n]);
+        println!(
+            "| {} | {:?} | {:?} | {:.2}x |",
+            n,
+            cpu_time,
+            gpu_time,
+            cpu_time.as_secs_f64() / gpu_time.as_secs_f64()
+        );
+    }
+}
+
+/// Average time for one round of regret matching and updates
+fn time(updater: &impl RegretUpdater, infostates: &[InfoState], deltas: &[SlotValues]) -> Duration {
+    let mut infostates = infostates.to_vec();
+    let start = Instant::now();
+    for _ in 0..REPETITIONS {
+        run(updater, &mut infostates, deltas);
+    }
+    start.elapsed() / REPETITIONS
+}
+
+fn run(updater: &impl RegretUpdater, infostates: &mut [InfoState], deltas: &[SlotValues]) {
+    let policy = updater.regret_matching(infostates);
+    updater.update(infostates, deltas, &policy, 0.99);
+}
+
+/// Infostates with the action counts of decisions in random bower subgame
+/// hands, and random regrets
+fn bower_infostates(n: usize, rng: &mut StdRng) -> Vec<InfoState> {
+    let mut infostates = Vec::with_capacity(n);
+    while infostates.len() < n {
+        let mut gs = generate_face_up_deals_with_rng(Card::JS, rng);
+        while !gs.is_terminal() && infostates.len() < n {
+            let actions = actions!(gs);
+            if actions.len() > 1 {
+                let mut is =
+                    InfoState::new(actions.iter().map(|&a| NormalizedAction::new(a)).collect());
+                is.regrets
+                    .iter_mut()
+                    .for_each(|r| *r = rng.gen_range(-1.0..1.0));
+                infostates.push(is);
+            }
+            gs.apply_action(*actions.choose(rng).unwrap());
+        }
+    }
+    infostates
+}
+
+fn random_slots(rng: &mut StdRng) -> SlotValues {
+    let mut slots = [0.0; 6];
+    slots.iter_mut().for_each(|s| *s = rng.gen_range(-1.0..1.0));
+    slots
+}
@@ -0,0 +1,97 @@
+//! Memory used by euchre istate keys stored as bytes and packed to 5 bits per
+//! action, and the cost of indexing each of them with a phf
+
+use std::time::Instant;
+
+use boomphf::Mphf;
+use card_platypus::alloc::tracking::{self, TrackingAllocator};
+use games::{
+    gamestates::euchre::Euchre,
+    istate::{IStateKey, PackedIStateKey},
+    GameState,
+};
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rustc_hash::FxHashMap;
+
+#[global_allocator]
+static ALLOC: TrackingAllocator = TrackingAllocator;
+
+const GAMES: usize = 50_000;
+const GAMMA: f64 = 1.7;
+
+pub fn main() {
+    let keys = istate_keys(GAMES);
+    println!("collected {} istate keys from {GAMES} games", keys.len());
+    println!(
+        "key size: IStateKey {} bytes, PackedIStateKey {} bytes",
+        std::mem::size_of::<IStateKey>(),
+        std::mem::size_of::<PackedIStateKey>()
+    );
+
+    track_memory("vec: IStateKey", || keys.clone());
+    track_memory("vec: PackedIStateKey", || {
+        keys.iter().map(PackedIStateKey::from).collect_vec()
+    });
+    track_memory("hashmap: IStateKey", || {
+        keys.iter()
+            .enumerate()
+            .map(|(i, k)| (*k, i))
+            .collect::<FxHashMap<_, _>>()
+    });
+    track_memory("hashmap: PackedIStateKey", || {
+        keys.iter()
+            .enumerate()
+            .map(|(i, k)| (PackedIStateKey::from(k), i))
+            .collect::<FxHashMap<_, _>>()
+    });
+
+    let phf = Mphf::new(GAMMA, &keys);
+    let packed_phf = Mphf::new(GAMMA, &keys.iter().map(PackedIStateKey::from).collect_vec());
+    time_lookups("phf lookup: IStateKey", &keys, |k| phf.try_hash(k));
+    // includes packing the key, as the node store does for each lookup
+    time_lookups("phf lookup: PackedIStateKey", &keys, |k| {
+        packed_phf.try_hash(&PackedIStateKey::from(k))
+    });
+}
+
+/// Istate keys of each decision in random games, with duplicates removed
+fn istate_keys(games: usize) -> Vec<IStateKey> {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+    let mut actions = Vec::new();
+    let mut keys = Vec::new();
+
+    for _ in 0..games {
+        let mut gs = Euchre::new_state();
+        while !gs.is_terminal() {
+            if !gs.is_chance_node() {
+                keys.push(gs.istate_key(gs.cur_player()));
+            }
+            gs.legal_actions(&mut actions);
+            gs.apply_action(*actions.choose(&mut rng).unwrap());
+        }
+    }
+
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+fn track_memory<T>(name: &str, f: impl FnOnce() -> T) {
+    tracking::reset();
+    let t = f();
+    let stats = tracking::stats();
+    println!("{name}: {} bytes, {} peak", stats.diff, stats.peak);
+    drop(t);
+}
+
+fn time_lookups(name: &str, keys: &[IStateKey], f: impl Fn(&IStateKey) -> Option<u64>) {
+    let start = Instant::now();
+    let found = keys.iter().filter_map(f).count();
+    let elapsed = start.elapsed();
+    assert_eq!(found, keys.len());
+    println!(
+        "{name}: {:?} per lookup",
+        elapsed / keys.len().try_into().unwrap()
+    );
+}
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use card_platypus::algorithms::{
     ismcts::Evaluator,
-    open_hand_solver::{OpenHandSolver, Optimizations},
+    open_hand_solver::{OpenHandSolver, Optimizations, DEFAULT_TT_CAPACITY},
     pimcts::PIMCTSBot,
 };
 use criterion::{criterion_group, criterion_main, Criterion};
@@ -24,6 +24,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         use_transposition_table: false,
         isometric_transposition: false,
         max_depth_for_tt: 255,
+        tt_capacity: DEFAULT_TT_CAPACITY,
+        use_killer_moves: false,
         action_processor: |_: &EuchreGameState, _: &mut Vec<Action>| {},
         can_early_terminate: |_: &EuchreGameState| false,
     });
@@ -36,6 +38,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         use_transposition_table: true,
         isometric_transposition: false,
         max_depth_for_tt: 255,
+        tt_capacity: DEFAULT_TT_CAPACITY,
+        use_killer_moves: false,
         action_processor: |_: &EuchreGameState, _: &mut Vec<Action>| {},
         can_early_terminate: |_: &EuchreGameState| false,
     });
@@ -48,6 +52,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         use_transposition_table: true,
         isometric_transposition: true,
         max_depth_for_tt: 255,
+        tt_capacity: DEFAULT_TT_CAPACITY,
+        use_killer_moves: false,
         action_processor: |_: &EuchreGameState, _: &mut Vec<Action>| {},
         can_early_terminate: |_: &EuchreGameState| false,
     });
@@ -59,6 +65,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         use_transposition_table: true,
         isometric_transposition: true,
         max_depth_for_tt: 255,
+        tt_capacity: DEFAULT_TT_CAPACITY,
+        use_killer_moves: false,
         action_processor: |_: &EuchreGameState, _: &mut Vec<Action>| {},
         can_early_terminate: euchre_early_terminate,
     });
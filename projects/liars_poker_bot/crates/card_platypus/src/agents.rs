@@ -1,22 +1,83 @@
-use games::{Action, GameState};
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf, str::FromStr};
 
-use rand::{
-    rngs::{StdRng, ThreadRng},
-    seq::SliceRandom,
-    thread_rng,
+use anyhow::{bail, Context};
+use games::{
+    actions,
+    gamestates::euchre::{actions::EAction, EPhase, EuchreGameState},
+    Action, GameState,
 };
 
-use crate::policy::Policy;
+use log::{info, warn};
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, SeedableRng};
+
+use crate::{
+    algorithms::{
+        cfres::CFRES,
+        distill::{BidTree, DistilledBidAgent},
+        ismcts::{Evaluator, RandomRolloutEvaluator},
+        open_hand_solver::OpenHandSolver,
+        pimcts::PIMCTSBot,
+    },
+    policy::Policy,
+};
+
+/// Worlds searched for each move by a pimcts agent built from a spec
+pub const DEFAULT_PIMCTS_WORLDS: usize = 50;
+/// Random playouts of each world for pimcts agents with the `rollout` solver
+pub const DEFAULT_ROLLOUTS: usize = 10;
 
 pub trait Agent<T: GameState> {
     fn step(&mut self, s: &T) -> Action;
     fn get_name(&self) -> String {
         std::any::type_name::<Self>().to_string()
     }
+
+    /// Picks an action like [`Agent::step`], along with how hard the choice
+    /// was, see [`decision_difficulty`]. None if the agent can't tell
+    fn step_with_difficulty(&mut self, s: &T) -> (Action, Option<f64>) {
+        (self.step(s), None)
+    }
+}
+
+impl<T: GameState, A: Agent<T> + ?Sized> Agent<T> for Box<A> {
+    fn step(&mut self, s: &T) -> Action {
+        (**self).step(s)
+    }
+
+    fn step_with_difficulty(&mut self, s: &T) -> (Action, Option<f64>) {
+        (**self).step_with_difficulty(s)
+    }
+
+    fn get_name(&self) -> String {
+        (**self).get_name()
+    }
+}
+
+/// How close the best two of an agent's action values or probabilities are,
+/// from 0 when one action is clearly best to 1 when they're tied
+///
+/// The difficulty halves for every `scale` the best action is ahead by. A
+/// forced move has no difficulty.
+pub fn decision_difficulty(values: impl IntoIterator<Item = f64>, scale: f64) -> f64 {
+    let mut best = f64::NEG_INFINITY;
+    let mut second = f64::NEG_INFINITY;
+    for v in values {
+        if v > best {
+            second = best;
+            best = v;
+        } else if v > second {
+            second = v;
+        }
+    }
+
+    if second == f64::NEG_INFINITY {
+        return 0.0;
+    }
+    0.5f64.powf((best - second) / scale)
 }
 
 pub struct RandomAgent {
-    pub rng: ThreadRng,
+    pub rng: StdRng,
 }
 
 impl Default for RandomAgent {
@@ -27,7 +88,9 @@ impl Default for RandomAgent {
 
 impl RandomAgent {
     pub fn new() -> Self {
-        Self { rng: thread_rng() }
+        Self {
+            rng: StdRng::from_rng(thread_rng()).unwrap(),
+        }
     }
 }
 
@@ -88,6 +151,34 @@ impl<T: GameState> Agent<T> for RecordedAgent {
     }
 }
 
+/// Euchre agent that takes every trick its team can win and only calls trump
+/// when it's forced to, an easy opponent for people learning the game
+#[derive(Default, Clone)]
+pub struct GreedyAgent {}
+
+impl Agent<EuchreGameState> for GreedyAgent {
+    fn step(&mut self, s: &EuchreGameState) -> Action {
+        let actions = actions!(s);
+        let pass = Action::from(EAction::Pass);
+        let choice = match s.phase() {
+            EPhase::Play => {
+                let team = s.cur_player() % 2;
+                actions
+                    .iter()
+                    .find(|a| s.trick_winner_with(EAction::from(**a).card()) % 2 == team)
+            }
+            EPhase::Pickup | EPhase::ChooseTrump => actions.iter().find(|a| **a == pass),
+            _ => None,
+        };
+
+        *choice.unwrap_or(&actions[0])
+    }
+
+    fn get_name(&self) -> String {
+        "GreedyAgent".to_string()
+    }
+}
+
 pub struct PolicyAgent<T> {
     pub policy: T,
     rng: StdRng,
@@ -109,6 +200,266 @@ impl<G: GameState, T: Policy<G>> Agent<G> for PolicyAgent<T> {
     }
 }
 
+/// Plays the best action for the real deal, an upper bound for what an agent
+/// can achieve with perfect information
+pub struct OpenHandAgent {
+    solver: OpenHandSolver<EuchreGameState>,
+}
+
+impl Default for OpenHandAgent {
+    fn default() -> Self {
+        Self {
+            solver: OpenHandSolver::new_euchre(),
+        }
+    }
+}
+
+impl Agent<EuchreGameState> for OpenHandAgent {
+    fn step(&mut self, s: &EuchreGameState) -> Action {
+        let player = s.cur_player();
+        let mut gs = s.clone();
+        let mut best = None;
+        for a in actions!(s) {
+            gs.apply_action(a);
+            let v = self.solver.evaluate_player(&gs, player);
+            gs.undo();
+            if best.map_or(true, |(_, best_v)| v > best_v) {
+                best = Some((a, v));
+            }
+        }
+        best.unwrap().0
+    }
+}
+
+/// An agent name and its parameters, e.g. `pimcts(n=50,solver=euchre)`, see
+/// [`agent_from_spec`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentSpec {
+    pub name: String,
+    pub params: BTreeMap<String, String>,
+}
+
+impl FromStr for AgentSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, params) = match s.split_once('(') {
+            Some((name, rest)) => {
+                let Some(params) = rest.strip_suffix(')') else {
+                    bail!("agent spec is missing a closing parenthesis: {}", s);
+                };
+                (name.trim(), params)
+            }
+            None => (s, ""),
+        };
+        if name.is_empty() {
+            bail!("agent spec has no agent name: {}", s);
+        }
+
+        let mut spec = AgentSpec {
+            name: name.to_string(),
+            params: BTreeMap::new(),
+        };
+        for param in params.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((key, value)) = param.split_once('=') else {
+                bail!("expected key=value for agent parameter: {}", param);
+            };
+            let key = key.trim().to_string();
+            if spec
+                .params
+                .insert(key.clone(), value.trim().to_string())
+                .is_some()
+            {
+                bail!("agent parameter {} is set twice: {}", key, s);
+            }
+        }
+        Ok(spec)
+    }
+}
+
+impl AgentSpec {
+    /// Removes and parses a parameter, so parameters left over at the end
+    /// weren't used by the agent
+    fn take<T: FromStr>(&mut self, key: &str) -> anyhow::Result<Option<T>>
+    where
+        T::Err: Display,
+    {
+        let Some(value) = self.params.remove(key) else {
+            return Ok(None);
+        };
+        match value.parse() {
+            Ok(v) => Ok(Some(v)),
+            Err(e) => bail!("invalid {} for {} agent: {}: {}", key, self.name, value, e),
+        }
+    }
+}
+
+/// Builds a euchre agent from a spec, so agent lineups can be given in config
+/// files and on the command line
+///
+/// A spec is an agent name with optional `key=value` parameters in
+/// parentheses, values can't contain commas or parentheses:
+/// * `random`, `first` and `greedy`
+/// * `pimcts(n=50,solver=euchre)` searches `n` worlds a move. The solver is
+///   `euchre` for the open hand solver with the euchre optimizations and
+///   tablebase, `openhand` for the plain open hand solver, or `rollout` for
+///   `rollouts` random playouts
+/// * `cfr(path=/var/lib/card_platypus/infostate.baseline,max_cards=0)` loads
+///   trained weights
+/// * `openhand` cheats by solving the real deal
+/// * `distilled(path=bid_tree.json,n=8)` bids with a tree from the
+///   `distill-bid-policy` command and plays cards like a pimcts agent with `n`
+///   worlds
+///
+/// Every agent also takes a `seed` for repeatable play.
+pub fn agent_from_spec(spec: &str) -> anyhow::Result<Box<dyn Agent<EuchreGameState> + Send>> {
+    let mut spec: AgentSpec = spec.parse()?;
+    let rng = match spec.take("seed")? {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(thread_rng()).unwrap(),
+    };
+
+    let agent: Box<dyn Agent<EuchreGameState> + Send> = match spec.name.as_str() {
+        "random" => Box::new(RandomAgent { rng }),
+        "first" => Box::new(AlwaysFirstAgent::new()),
+        "greedy" => Box::<GreedyAgent>::default(),
+        "openhand" => Box::<OpenHandAgent>::default(),
+        "pimcts" => {
+            let n = spec.take("n")?.unwrap_or(DEFAULT_PIMCTS_WORLDS);
+            let solver: String = spec.take("solver")?.unwrap_or_else(|| "euchre".to_string());
+            match solver.as_str() {
+                "euchre" => Box::new(PIMCTSBot::new(n, OpenHandSolver::new_euchre(), rng)),
+                "openhand" => Box::new(PIMCTSBot::new(n, OpenHandSolver::default(), rng)),
+                "rollout" => {
+                    let rollouts = spec.take("rollouts")?.unwrap_or(DEFAULT_ROLLOUTS);
+                    Box::new(PIMCTSBot::new(
+                        n,
+                        RandomRolloutEvaluator::new(rollouts),
+                        rng,
+                    ))
+                }
+                _ => bail!("unknown pimcts solver: {}", solver),
+            }
+        }
+        "cfr" => {
+            let path: PathBuf = spec
+                .take("path")?
+                .context("cfr agents need the path of their weights")?;
+            let max_cards = spec.take("max_cards")?.unwrap_or(0);
+            let agent = CFRES::new_euchre(rng, max_cards, Some(&path));
+            let n = agent.num_info_states();
+            if n == 0 {
+                warn!("no istates loaded for cfr agent from {:?}", path);
+            }
+            info!("loaded cfr agent: {} istates", n);
+            Box::new(agent)
+        }
+        "distilled" => {
+            let path: PathBuf = spec
+                .take("path")?
+                .context("distilled agents need the path of their bid tree")?;
+            let tree = BidTree::load(&path)?;
+            info!("loaded bid tree with {} rules", tree.num_leaves());
+            let n = spec.take("n")?.unwrap_or(DEFAULT_PIMCTS_WORLDS);
+            let play = PIMCTSBot::new(n, OpenHandSolver::new_euchre(), rng);
+            Box::new(DistilledBidAgent::new(tree, play))
+        }
+        name => bail!("unknown agent: {}", name),
+    };
+
+    if let Some(key) = spec.params.keys().next() {
+        bail!("unknown parameter for {} agent: {}", spec.name, key);
+    }
+    Ok(agent)
+}
+
 pub trait Seedable {
     fn set_seed(&mut self, seed: u64);
 }
+
+impl Seedable for RandomAgent {
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = SeedableRng::seed_from_u64(seed);
+    }
+}
+
+impl<T: Seedable> Seedable for PolicyAgent<T> {
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = SeedableRng::seed_from_u64(seed);
+        self.policy.set_seed(seed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use games::gamestates::euchre::{actions::EAction, EuchreGameState};
+
+    use super::{agent_from_spec, decision_difficulty, Agent, AgentSpec, GreedyAgent};
+
+    #[test]
+    fn test_greedy_agent() {
+        let mut agent = GreedyAgent::default();
+
+        // takes the trick rather than following with the Td
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|QdKd");
+        assert_eq!(EAction::from(agent.step(&gs)), EAction::AD);
+
+        // doesn't call trump
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|");
+        assert_eq!(EAction::from(agent.step(&gs)), EAction::Pass);
+
+        // unless stuck as the dealer
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|PPPPPPP");
+        assert_ne!(EAction::from(agent.step(&gs)), EAction::Pass);
+    }
+
+    #[test]
+    fn test_agent_spec() {
+        let spec: AgentSpec = " pimcts( n=50, solver=euchre )".parse().unwrap();
+        assert_eq!(spec.name, "pimcts");
+        assert_eq!(spec.params["n"], "50");
+        assert_eq!(spec.params["solver"], "euchre");
+        assert!("greedy".parse::<AgentSpec>().unwrap().params.is_empty());
+
+        assert!("pimcts(n=50".parse::<AgentSpec>().is_err());
+        assert!("pimcts(n)".parse::<AgentSpec>().is_err());
+        assert!("pimcts(n=1,n=2)".parse::<AgentSpec>().is_err());
+        assert!("(n=1)".parse::<AgentSpec>().is_err());
+    }
+
+    #[test]
+    fn test_agent_from_spec() {
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|QdKd");
+        for spec in [
+            "random(seed=1)",
+            "first",
+            "greedy",
+            "pimcts(n=2,solver=openhand,seed=1)",
+            "pimcts(n=2,solver=rollout,rollouts=2)",
+        ] {
+            let mut agent = agent_from_spec(spec).unwrap();
+            let a = agent.step(&gs);
+            assert!(gs.explain_illegal(a).is_none(), "{}", spec);
+        }
+        let mut greedy = agent_from_spec("greedy").unwrap();
+        assert_eq!(EAction::from(greedy.step(&gs)), EAction::AD);
+
+        assert!(agent_from_spec("pimcts(n=lots)").is_err());
+        assert!(agent_from_spec("pimcts(solver=magic)").is_err());
+        // rollouts only apply to the rollout solver
+        assert!(agent_from_spec("pimcts(n=2,solver=openhand,rollouts=2)").is_err());
+        assert!(agent_from_spec("cfr").is_err());
+        assert!(agent_from_spec("distilled").is_err());
+        assert!(agent_from_spec("distilled(path=/no/such/tree.json)").is_err());
+        assert!(agent_from_spec("oracle").is_err());
+    }
+
+    #[test]
+    fn test_decision_difficulty() {
+        assert_eq!(decision_difficulty([1.0], 0.5), 0.0);
+        assert_eq!(decision_difficulty([1.0, 0.0, 1.0], 0.5), 1.0);
+        assert_eq!(decision_difficulty([0.5, 1.0, -2.0], 0.5), 0.5);
+        assert_eq!(decision_difficulty([2.0, 1.0], 0.5), 0.25);
+    }
+}
@@ -0,0 +1,206 @@
+use dyn_clone::DynClone;
+use games::{
+    gamestates::euchre::{actions::EAction, EPhase, EuchreGameState},
+    istate::{IStateKey, NormalizedIstate},
+    Action,
+};
+
+/// Number of cards in a euchre hand, they are the first actions of an istate
+const HAND_SIZE: usize = 5;
+const SUIT_MASK: u32 = 0b111111;
+const JACK: u32 = 0b100;
+const ACE: u32 = 0b100000;
+
+/// Maps infostates to a coarser key so similar istates share their values
+///
+/// Unlike an istate normalizer, abstractions are lossy. Every istate that maps
+/// to the same abstract key shares regrets and strategy, so the normalized
+/// actions must be the same for the whole bucket. This also means the
+/// abstraction has imperfect recall, an istate in a later round may not know
+/// which bucket it was in earlier.
+pub trait Abstraction<G>: Sync + Send + DynClone {
+    /// Returns the key to store the normalized istate under, or None if the
+    /// istate isn't abstracted and should be stored as is
+    fn abstract_key(&self, istate: &NormalizedIstate, gs: &G) -> Option<IStateKey>;
+}
+dyn_clone::clone_trait_object!(<G>Abstraction<G>);
+
+#[derive(Default, Clone)]
+pub struct NoAbstraction;
+
+impl<G> Abstraction<G> for NoAbstraction {
+    fn abstract_key(&self, _: &NormalizedIstate, _: &G) -> Option<IStateKey> {
+        None
+    }
+}
+
+/// Buckets euchre bidding istates by the strength of the hand for each
+/// possible trump suit
+///
+/// The hand is replaced by, for each trump suit: the number of trump, which
+/// bowers are held, the number of off suit aces and the number of off suit
+/// voids. The face up card and the bids so far are kept exactly, so the seat
+/// and available actions are the same for the bucket.
+///
+/// The discard and play phases aren't abstracted. The hand can't be bucketed
+/// for the discard as the actions are the cards in it.
+#[derive(Default, Clone)]
+pub struct EuchreBiddingAbstraction;
+
+impl Abstraction<EuchreGameState> for EuchreBiddingAbstraction {
+    fn abstract_key(&self, istate: &NormalizedIstate, gs: &EuchreGameState) -> Option<IStateKey> {
+        if !matches!(gs.phase(), EPhase::Pickup | EPhase::ChooseTrump) {
+            return None;
+        }
+
+        let istate = istate.get();
+        let hand = istate[..HAND_SIZE]
+            .iter()
+            .fold(0, |acc, &a| acc | EAction::from(a).card().mask());
+
+        let mut key = IStateKey::default();
+        for trump in 0..4 {
+            let s = hand_strength(hand, trump);
+            key.push(Action(s.trump));
+            key.push(Action(s.bowers));
+            key.push(Action(s.off_aces));
+            key.push(Action(s.voids));
+        }
+
+        for &a in &istate[HAND_SIZE..] {
+            key.push(a);
+        }
+
+        Some(key)
+    }
+}
+
+struct HandStrength {
+    trump: u8,
+    /// Bit 1 for the right bower, bit 0 for the left
+    bowers: u8,
+    off_aces: u8,
+    voids: u8,
+}
+
+/// Strength of a hand, as a card mask, if `trump` is the index of the trump suit
+fn hand_strength(hand: u32, trump: usize) -> HandStrength {
+    let suit = |s: usize| (hand >> (s * 8)) & SUIT_MASK;
+    // suits of the same color are next to each other
+    let left_suit = trump ^ 1;
+
+    let right = suit(trump) & JACK != 0;
+    let left = suit(left_suit) & JACK != 0;
+
+    let mut off_aces = 0;
+    let mut voids = 0;
+    for s in (0..4).filter(|&s| s != trump) {
+        let mut cards = suit(s);
+        if s == left_suit {
+            cards &= !JACK;
+        }
+        if cards & ACE != 0 {
+            off_aces += 1;
+        }
+        if cards == 0 {
+            voids += 1;
+        }
+    }
+
+    HandStrength {
+        trump: (suit(trump).count_ones() + left as u32) as u8,
+        bowers: (right as u8) << 1 | left as u8,
+        off_aces,
+        voids,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use games::{
+        gamestates::euchre::{
+            actions::{Card, EAction},
+            ismorphic::EuchreNormalizer,
+            Euchre, EuchreGameState,
+        },
+        istate::IStateNormalizer,
+        GameState,
+    };
+
+    use super::{hand_strength, Abstraction, EuchreBiddingAbstraction};
+
+    fn abstract_key(gs: &EuchreGameState) -> Option<games::istate::IStateKey> {
+        let istate =
+            EuchreNormalizer::default().normalize_istate(&gs.istate_key(gs.cur_player()), gs);
+        EuchreBiddingAbstraction.abstract_key(&istate, gs)
+    }
+
+    fn deal(hand: [Card; 5], face_up: Card) -> EuchreGameState {
+        let others = [
+            Card::NC,
+            Card::TC,
+            Card::QC,
+            Card::KC,
+            Card::NH,
+            Card::TH,
+            Card::JH,
+            Card::QH,
+            Card::AH,
+            Card::ND,
+            Card::TD,
+            Card::JD,
+            Card::QD,
+            Card::KD,
+            Card::AD,
+        ];
+        let mut gs = Euchre::new_state();
+        for c in hand.iter().chain(others.iter()).chain([face_up].iter()) {
+            gs.apply_action(EAction::from(*c).into());
+        }
+        gs
+    }
+
+    #[test]
+    fn test_hand_strength() {
+        use Card::*;
+        let hand = [JS, JC, AS, AH, NS].iter().fold(0, |acc, c| acc | c.mask());
+
+        // spades trump
+        let s = hand_strength(hand, 0);
+        assert_eq!(s.trump, 4);
+        assert_eq!(s.bowers, 0b11);
+        assert_eq!(s.off_aces, 1);
+        assert_eq!(s.voids, 2);
+
+        // hearts trump, both black jacks are off suit
+        let s = hand_strength(hand, 2);
+        assert_eq!(s.trump, 1);
+        assert_eq!(s.bowers, 0);
+        assert_eq!(s.off_aces, 1);
+        assert_eq!(s.voids, 1);
+    }
+
+    #[test]
+    fn test_euchre_bidding_abstraction() {
+        use Card::*;
+        // same trump, bowers and off suit aces for every suit, only the 9 of
+        // trump is swapped for the ten
+        let a = deal([JS, AS, NS, AC, KH], QS);
+        let b = deal([JS, AS, TS, AC, KH], QS);
+        assert!(abstract_key(&a).is_some());
+        assert_eq!(abstract_key(&a), abstract_key(&b));
+
+        // a stronger hand is in a different bucket
+        let c = deal([JS, AS, JC, AC, KH], QS);
+        assert_ne!(abstract_key(&a), abstract_key(&c));
+
+        // the play phase isn't abstracted
+        let mut gs = a.clone();
+        while !matches!(gs.phase(), games::gamestates::euchre::EPhase::Play) {
+            let mut actions = Vec::new();
+            gs.legal_actions(&mut actions);
+            gs.apply_action(actions[0]);
+        }
+        assert_eq!(abstract_key(&gs), None);
+    }
+}
@@ -1,11 +1,13 @@
 use std::{
-    path::Path,
+    fs,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, MutexGuard, TryLockError,
     },
 };
 
+use anyhow::Context;
 use bytemuck::{Pod, Zeroable};
 use dashmap::DashMap;
 use dyn_clone::DynClone;
@@ -17,24 +19,37 @@ use games::{
         },
         kuhn_poker::{KPGameState, KuhnPoker},
     },
-    istate::{IStateKey, IStateNormalizer, NoOpNormalizer, NormalizedAction, NormalizedIstate},
+    istate::{IStateKey, IStateNormalizer, NoOpNormalizer, NormalizedAction},
     resample::ResampleFromInfoState,
     Action, GameState, Player,
 };
 use itertools::Itertools;
 use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
 use rayon::prelude::*;
+use rustc_hash::FxHashMap;
 
 use serde::{Deserialize, Serialize};
 use tinyvec::ArrayVec;
 
 use crate::{
-    agents::{Agent, Seedable},
-    algorithms::{ismcts::Evaluator, open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot},
+    agents::{decision_difficulty, Agent, Seedable},
+    algorithms::{
+        abstraction::{Abstraction, NoAbstraction},
+        ismcts::Evaluator,
+        open_hand_solver::OpenHandSolver,
+        pimcts::{best_action, PIMCTSBot, DIFFICULTY_VALUE_SCALE},
+        undo_check::with_action,
+    },
     alloc::Pool,
-    collections::{actionlist::ActionList, actionvec::ActionVec},
+    collections::{
+        actionlist::ActionList,
+        actionvec::ActionVec,
+        ctrie::CTrie,
+        strategywindow::{StrategyWindow, WindowShard},
+    },
     counter,
-    database::NodeStore,
+    database::{NodeStore, ShardStats, WalConfig},
+    metrics::{node_store_lock_contended, node_store_locks, play_cache_hits, play_cache_misses},
     policy::Policy,
 };
 
@@ -48,12 +63,30 @@ use features::features;
 const LINEAR_CFR_CUTOFF: usize = 1_000_000;
 type Weight = f32;
 
+/// File the strategy window is saved to in the infostate directory
+const STRATEGY_WINDOW_NAME: &str = "strategy_window";
+
+/// File the infostates merged since the last save are checkpointed to in the
+/// infostate directory
+const CHECKPOINT_NAME: &str = "checkpoint";
+
+/// Iterations each training thread runs before merging its updates into the
+/// shared node store
+const DEFAULT_EPOCH_LEN: usize = 64;
+
+/// Gap in probability between the policy's best two actions that halves the
+/// difficulty of a decision, see [`decision_difficulty`]
+const DIFFICULTY_PROBABILITY_SCALE: f64 = 0.25;
+
 counter!(nodes_touched);
 
 features! {
     pub mod feature {
         const LinearCFR = 0b01000000,
-        const SingleThread = 0b00100000
+        const SingleThread = 0b00100000,
+        // Floor regrets at 0 and average the strategy on the update player's
+        // nodes, as in CFR+
+        const RegretMatchingPlus = 0b00010000
     }
 }
 
@@ -62,10 +95,13 @@ enum AverageType {
     _Full,
     #[default]
     Simple,
+    /// Average on the update player's nodes at the same time as the regrets
+    /// are updated, weighted by reach and linearly by iteration
+    Simultaneous,
 }
 
 /// Number of actions we can store in a given "slot" of the database
-const MAX_ACTIONS_PER_SLOT: usize = 6;
+pub(crate) const MAX_ACTIONS_PER_SLOT: usize = 6;
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct InfoState {
@@ -131,8 +167,40 @@ pub struct CFRES<G> {
     /// determine if we are at the max depth and should use the rollout
     depth_checker: Box<dyn DepthChecker<G>>,
     normalizer: Box<dyn IStateNormalizer<G>>,
+    abstraction: Box<dyn Abstraction<G>>,
     play_bot: PIMCTSBot<G, OpenHandSolver<G>>,
     evaluator: OpenHandSolver<G>,
+    /// If set, play uses the average strategy of only the most recent iterations
+    strategy_window: Option<Arc<Mutex<StrategyWindow>>>,
+    /// Window updates made by this training thread that haven't been merged
+    /// into the strategy window yet
+    window_shard: Option<WindowShard>,
+    /// Used to sample actions from the average strategy during play
+    play_rng: StdRng,
+    /// Updates made by this training thread that haven't been merged into the
+    /// node store yet
+    local: FxHashMap<NodeKey, LocalNode>,
+    epoch_len: usize,
+    checkpoints: Option<Checkpoints>,
+    /// Play weights of the istates primed by [`CFRES::prime`], keyed by the
+    /// normalized istate and shared by clones
+    play_cache: Arc<DashMap<IStateKey, ActionVec<f64>>>,
+}
+
+/// Infostates merged into the node store since the last save, written to
+/// `path` by [`CFRES::checkpoint`]
+#[derive(Clone)]
+struct Checkpoints {
+    path: PathBuf,
+    infostates: Arc<CTrie<NodeKey, InfoState>>,
+}
+
+/// An infostate as updated by a single training thread
+#[derive(Clone)]
+struct LocalNode {
+    /// Value in the node store when first read, None if it wasn't stored
+    base: Option<InfoState>,
+    value: InfoState,
 }
 
 impl<G> CFRES<G> {
@@ -144,12 +212,110 @@ impl<G> CFRES<G> {
     pub fn iterations(&self) -> usize {
         self.iteration.load(Ordering::Relaxed)
     }
+
+    /// Play with the average strategy over only the last `window` iterations
+    ///
+    /// Useful for continual training against nonstationary opponents, where
+    /// the full average is dominated by old iterations. The window is saved
+    /// next to the infostates and restored if it covers the same number of
+    /// iterations.
+    pub fn with_strategy_window(mut self, window: usize) -> Self {
+        let strategy_window = match self.strategy_window_path() {
+            Some(path) => StrategyWindow::load_or_new(&path, window),
+            None => StrategyWindow::new(window),
+        };
+        self.window_shard = Some(strategy_window.shard());
+        self.strategy_window = Some(Arc::new(Mutex::new(strategy_window)));
+        self
+    }
+
+    /// Store the values of abstracted istates under their abstract key
+    ///
+    /// Values trained with a different abstraction won't be found, the
+    /// abstract keys are only valid for the abstraction that created them.
+    pub fn with_abstraction(mut self, abstraction: Box<dyn Abstraction<G>>) -> Self {
+        self.abstraction = abstraction;
+        self
+    }
+
+    /// Number of iterations each training thread runs before merging its
+    /// updates into the shared node store
+    ///
+    /// Threads don't see each other's updates until they're merged, longer
+    /// epochs reduce lock contention at the cost of training on staler values.
+    pub fn with_epoch_len(mut self, epoch_len: usize) -> Self {
+        assert!(epoch_len > 0, "epoch must be at least 1 iteration");
+        self.epoch_len = epoch_len;
+        self
+    }
+
+    /// Log the node store's writes between saves, so a crash loses at most a
+    /// batch of updates rather than everything since the last save
+    ///
+    /// Writes logged before an earlier crash are replayed into the store.
+    /// Needs a store saved to disk.
+    pub fn with_wal(self, config: WalConfig) -> anyhow::Result<Self> {
+        self.infostates.lock().unwrap().enable_wal(config)?;
+        Ok(self)
+    }
+
+    /// Keep the infostates merged since the last save in a [`CTrie`], so
+    /// [`CFRES::checkpoint`] can write them out while training carries on
+    ///
+    /// A checkpoint left by a run that stopped before saving is replayed into
+    /// the node store, so training resumes from it. Needs a store saved to disk.
+    pub fn with_checkpoints(mut self) -> anyhow::Result<Self> {
+        let path = self
+            .infostates
+            .lock()
+            .unwrap()
+            .path()
+            .map(|p| p.join(CHECKPOINT_NAME))
+            .context("checkpoints need a node store saved to disk")?;
+
+        let infostates = if path.exists() {
+            let checkpoint: CTrie<NodeKey, InfoState> = CTrie::load_checkpoint(&path)?;
+            let mut store = self.lock_infostates();
+            for (key, value) in checkpoint.iter() {
+                key.put_into(&mut store, &value);
+            }
+            checkpoint
+        } else {
+            CTrie::new()
+        };
+        self.checkpoints = Some(Checkpoints {
+            path,
+            infostates: Arc::new(infostates),
+        });
+        Ok(self)
+    }
+
+    /// Writes the infostates merged since the last save to the checkpoint,
+    /// does nothing without [`CFRES::with_checkpoints`]
+    ///
+    /// Only a snapshot of them is written, so unlike [`CFRES::save`] it doesn't
+    /// lock the node store and training threads can keep merging.
+    pub fn checkpoint(&self) -> anyhow::Result<()> {
+        if let Some(checkpoints) = &self.checkpoints {
+            checkpoints.infostates.checkpoint(&checkpoints.path)?;
+        }
+        Ok(())
+    }
+
+    fn strategy_window_path(&self) -> Option<PathBuf> {
+        self.infostates
+            .lock()
+            .unwrap()
+            .path()
+            .map(|p| p.join(STRATEGY_WINDOW_NAME))
+    }
 }
 
 impl<G> Seedable for CFRES<G> {
-    /// Sets the seed for the evaluator, it doesn't change the seed used for training
+    /// Sets the seed used for play, it doesn't change the seed used for training
     fn set_seed(&mut self, seed: u64) {
         self.play_bot.set_seed(seed);
+        self.play_rng = SeedableRng::seed_from_u64(seed);
     }
 }
 
@@ -186,12 +352,32 @@ impl CFRES<EuchreGameState> {
             iteration: Arc::new(AtomicUsize::new(0)),
             evaluator: OpenHandSolver::new_euchre(),
             normalizer,
+            abstraction: Box::<NoAbstraction>::default(),
+            strategy_window: None,
+            window_shard: None,
+            play_rng: StdRng::from_rng(thread_rng()).unwrap(),
+            local: FxHashMap::default(),
+            epoch_len: DEFAULT_EPOCH_LEN,
+            checkpoints: None,
+            play_cache: Arc::default(),
         }
     }
 
     pub fn set_game_generator(&mut self, game_generator: fn() -> EuchreGameState) {
         self.game_generator = game_generator;
     }
+
+    /// Only use the trained policy for bidding, play is left to the PIMCTS
+    /// bot and the play shards of the node store are dropped to save memory
+    pub fn with_bid_policy_only(self) -> anyhow::Result<Self> {
+        self.lock_infostates().drop_shards_from(1)?;
+        Ok(Self {
+            depth_checker: Box::new(EuchreDepthChecker {
+                max_cards_played: 0,
+            }),
+            ..self
+        })
+    }
 }
 
 impl CFRES<KPGameState> {
@@ -211,7 +397,15 @@ impl CFRES<KPGameState> {
             ),
             evaluator: OpenHandSolver::default(),
             normalizer: Box::<NoOpNormalizer>::default(),
+            abstraction: Box::<NoAbstraction>::default(),
             iteration: Arc::new(AtomicUsize::new(0)),
+            strategy_window: None,
+            window_shard: None,
+            play_rng: StdRng::from_rng(thread_rng()).unwrap(),
+            local: FxHashMap::default(),
+            epoch_len: DEFAULT_EPOCH_LEN,
+            checkpoints: None,
+            play_cache: Arc::default(),
         }
     }
 }
@@ -234,7 +428,15 @@ impl CFRES<BluffGameState> {
             ),
             evaluator: OpenHandSolver::default(),
             normalizer: Box::<NoOpNormalizer>::default(),
+            abstraction: Box::<NoAbstraction>::default(),
             iteration: Arc::new(AtomicUsize::new(0)),
+            strategy_window: None,
+            window_shard: None,
+            play_rng: StdRng::from_rng(thread_rng()).unwrap(),
+            local: FxHashMap::default(),
+            epoch_len: DEFAULT_EPOCH_LEN,
+            checkpoints: None,
+            play_cache: Arc::default(),
         }
     }
 }
@@ -242,21 +444,123 @@ impl CFRES<BluffGameState> {
 impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
     pub fn train(&mut self, n: usize) {
         if feature::is_enabled(feature::SingleThread) {
-            for _ in 0..n {
-                self.iteration();
-            }
+            self.train_epochs(n);
         } else {
-            (0..n)
-                .into_par_iter()
-                .for_each(|_| self.clone().iteration())
+            // each thread keeps its own updates and merges them into the
+            // node store every epoch, rather than locking it on every node
+            let threads = rayon::current_num_threads();
+            let alg = &*self;
+            (0..threads).into_par_iter().for_each(|t| {
+                let iterations = n / threads + usize::from(t < n % threads);
+                alg.clone().train_epochs(iterations);
+            });
         }
 
         self.play_bot.reset();
         self.evaluator.reset();
+        // the primed weights are stale once the policy changes
+        self.play_cache.clear();
+    }
+
+    fn train_epochs(&mut self, n: usize) {
+        for i in 1..=n {
+            self.iteration();
+            if i % self.epoch_len == 0 {
+                self.merge_local();
+            }
+        }
+        self.merge_local();
     }
 
     pub fn save(&self) -> anyhow::Result<()> {
-        self.infostates.lock().unwrap().commit()
+        let mut infostates = self.lock_infostates();
+        infostates.commit()?;
+        // merges hold the node store lock, so everything in the checkpoint
+        // was just committed
+        if let Some(checkpoints) = &self.checkpoints {
+            for (key, _) in checkpoints.infostates.iter() {
+                checkpoints.infostates.remove(&key);
+            }
+            if checkpoints.path.exists() {
+                fs::remove_file(&checkpoints.path).context("failed to remove checkpoint")?;
+            }
+        }
+        drop(infostates);
+
+        if let (Some(window), Some(path)) = (&self.strategy_window, self.strategy_window_path()) {
+            window.lock().unwrap().save(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Caches the play weights of common opening istates, so the first moves
+    /// after startup don't wait on disk reads or rollouts
+    ///
+    /// Follows the most likely line of play for `n_deals` random deals, up to
+    /// and including the first move made by the rollout bot, and caches the
+    /// policy or rollout values of every istate on the way. The cache is keyed
+    /// by the normalized istate, so deals with the same hand shape around the
+    /// face-up card share an entry and the common ones are computed once.
+    /// Clones of the agent share the cache, training clears it.
+    ///
+    /// Returns the number of istates cached.
+    pub fn prime(&mut self, n_deals: usize) -> usize
+    where
+        G: Send,
+    {
+        let mut rng = thread_rng();
+        let mut actions = self.vector_pool.detach();
+        let mut primed = 0;
+
+        for _ in 0..n_deals {
+            let mut gs = (self.game_generator)();
+            while gs.is_chance_node() {
+                gs.legal_actions(&mut actions);
+                gs.apply_action(*actions.choose(&mut rng).unwrap());
+            }
+            primed += self.prime_line(gs);
+        }
+
+        self.vector_pool.attach(actions);
+        primed
+    }
+
+    /// Caches the play weights along the most likely line from `gs`, see
+    /// [`CFRES::prime`]
+    fn prime_line(&mut self, mut gs: G) -> usize
+    where
+        G: Send,
+    {
+        let mut primed = 0;
+        while !gs.is_terminal() && !gs.is_chance_node() {
+            let istate = self.play_cache_key(&gs);
+            let cached = self
+                .play_cache
+                .get(&istate)
+                .map(|cached| self.denormalize_weights(&cached, &gs));
+            let weights = match cached {
+                Some(weights) => weights,
+                None => {
+                    let weights = self.compute_play_weights(&gs);
+                    let normalized = self.normalize_weights(&weights, &gs);
+                    self.play_cache.insert(istate, normalized);
+                    primed += 1;
+                    weights
+                }
+            };
+
+            if self.depth_checker.is_max_depth(&gs) {
+                break;
+            }
+
+            let (a, _) = weights
+                .to_vec()
+                .into_iter()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap();
+            gs.apply_action(a);
+        }
+        primed
     }
 
     /// Performs one iteration of external sampling.
@@ -271,7 +575,7 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
 
         let num_players = (self.game_generator)().num_players();
         for player in 0..num_players {
-            self.update_regrets(&mut (self.game_generator)(), player, 0);
+            self.update_regrets(&mut (self.game_generator)(), player, 1.0, 0);
         }
         if matches!(self.average_type, AverageType::_Full) {
             let reach_probs = vec![1.0; num_players];
@@ -284,12 +588,19 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
     /// Args:
     ///     state: the game state to run from
     ///     player: the player to update regrets for
+    ///     reach: probability of the update player playing to this state
     ///
     /// Returns:
     ///     value: is the value of the state in the game
     ///     obtained as the weighted average of the values
     ///     of the children
-    fn update_regrets(&mut self, gs: &mut G, player: Player, _depth: usize) -> Weight {
+    fn update_regrets(
+        &mut self,
+        gs: &mut G,
+        player: Player,
+        reach: Weight,
+        _depth: usize,
+    ) -> Weight {
         if gs.is_terminal() {
             return gs.evaluate(player) as Weight;
         }
@@ -303,10 +614,9 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
             actions.clear();
             self.vector_pool.attach(actions);
 
-            gs.apply_action(outcome);
-            let value = self.update_regrets(gs, player, _depth + 1);
-            gs.undo();
-            return value;
+            return with_action(gs, outcome, |gs| {
+                self.update_regrets(gs, player, reach, _depth + 1)
+            });
         }
 
         // If we're at max depth, do the rollout
@@ -315,17 +625,15 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
         }
 
         let cur_player = gs.cur_player();
-        let info_state_key = self
-            .normalizer
-            .normalize_istate(&gs.istate_key(cur_player), gs);
+        let info_state_key = self.node_key(gs);
         let mut actions = self.vector_pool.detach();
         gs.legal_actions(&mut actions);
 
         // don't store anything if only 1 valid action
         if actions.len() == 1 {
-            gs.apply_action(actions[0]);
-            let v = self.update_regrets(gs, player, _depth + 1);
-            gs.undo();
+            let v = with_action(gs, actions[0], |gs| {
+                self.update_regrets(gs, player, reach, _depth + 1)
+            });
             actions.clear();
             self.vector_pool.attach(actions);
             return v;
@@ -341,7 +649,7 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
         {
             let normalizer = self.normalizer.clone();
             let infostate_info = self
-                .lookup_entry(&info_state_key)
+                .lookup_entry_mut(&info_state_key)
                 .unwrap_or_else(|| InfoState::new(normalized_actions.clone()));
             let regrets = infostate_info
                 .regrets()
@@ -366,15 +674,15 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
                 .choose_weighted(&mut thread_rng(), |a| a.1)
                 .expect("error choosing weighted action")
                 .0;
-            gs.apply_action(a);
-            value = self.update_regrets(gs, player, _depth + 1);
-            gs.undo();
+            value = with_action(gs, a, |gs| {
+                self.update_regrets(gs, player, reach, _depth + 1)
+            });
         } else {
             // walk over all actions at my node
             for &a in actions.iter() {
-                gs.apply_action(a);
-                child_values[a] = self.update_regrets(gs, player, _depth + 1);
-                gs.undo();
+                child_values[a] = with_action(gs, a, |gs| {
+                    self.update_regrets(gs, player, reach * policy[a], _depth + 1)
+                });
                 value += policy[a] * child_values[a];
             }
         }
@@ -384,7 +692,7 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
             let iteration = self.iteration.load(Ordering::SeqCst);
             let normalizer = self.normalizer.clone();
             let mut infostate_info = self
-                .lookup_entry(&info_state_key)
+                .lookup_entry_mut(&info_state_key)
                 .unwrap_or_else(|| InfoState::new(normalized_actions.clone()));
             for &a in actions.iter() {
                 let norm_a = normalizer.normalize_action(a, gs);
@@ -403,16 +711,43 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
         // which reduces to the standard rule in 2 players.
         //
         // We adapt this slightly for euchre where it alternates what team the players are on
+        //
+        // Simultaneous averaging is done on the update player's node instead, so
+        // the average is updated at the same time as the regrets
         let cur_team = cur_player % 2;
         let player_team = player % 2;
-        if matches!(self.average_type, AverageType::Simple) && cur_team != player_team {
+        let average_weight = match self.average_type() {
+            AverageType::Simple if cur_team != player_team => Some(1.0),
+            AverageType::Simultaneous if cur_player == player => {
+                Some(reach * self.iteration.load(Ordering::SeqCst) as Weight)
+            }
+            _ => None,
+        };
+        if let Some(weight) = average_weight {
             let normalizer = self.normalizer.clone();
             let mut infostate_info = self
-                .lookup_entry(&info_state_key)
+                .lookup_entry_mut(&info_state_key)
                 .unwrap_or_else(|| InfoState::new(normalized_actions.clone()));
             for &action in actions.iter() {
                 let norm_a = normalizer.normalize_action(action, gs);
-                add_avstrat(&mut infostate_info, norm_a, policy[action]);
+                add_avstrat(&mut infostate_info, norm_a, weight * policy[action]);
+            }
+
+            let iteration = self.iteration.load(Ordering::SeqCst);
+            if let Some(shard) = &mut self.window_shard {
+                for &action in actions.iter() {
+                    let norm_a = normalizer.normalize_action(action, gs);
+                    let idx = infostate_info
+                        .actions
+                        .index(norm_a)
+                        .expect("couldn't find action");
+                    shard.add(
+                        info_state_key.get(),
+                        iteration,
+                        idx,
+                        weight * policy[action],
+                    );
+                }
             }
 
             self.put_entry(&info_state_key, infostate_info);
@@ -430,22 +765,191 @@ impl<G: GameState + ResampleFromInfoState + Sync> CFRES<G> {
     }
 
     pub fn num_info_states(&self) -> usize {
-        self.infostates.lock().unwrap().len()
+        self.lock_infostates().len()
     }
 
     pub fn indexer_size(&self) -> usize {
-        self.infostates.lock().unwrap().indexer_len()
+        self.lock_infostates().indexer_len()
+    }
+
+    /// Accesses of each node store shard, None for dropped shards
+    pub fn shard_stats(&self) -> Vec<Option<ShardStats>> {
+        self.lock_infostates().shard_stats()
+    }
+
+    fn average_type(&self) -> AverageType {
+        if feature::is_enabled(feature::RegretMatchingPlus) {
+            AverageType::Simultaneous
+        } else {
+            self.average_type.clone()
+        }
+    }
+}
+
+/// Key the values of an infostate are stored under
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum NodeKey {
+    /// A normalized istate
+    IState(IStateKey),
+    /// Shared by every istate in the bucket of an abstraction
+    Abstract(IStateKey),
+}
+
+impl NodeKey {
+    fn get(&self) -> IStateKey {
+        match self {
+            NodeKey::IState(k) | NodeKey::Abstract(k) => *k,
+        }
+    }
+
+    fn get_from(&self, store: &NodeStore) -> Option<InfoState> {
+        match self {
+            NodeKey::IState(k) => store.get(k),
+            NodeKey::Abstract(k) => store.get_abstract(k),
+        }
+    }
+
+    fn put_into(&self, store: &mut NodeStore, v: &InfoState) {
+        match self {
+            NodeKey::IState(k) => store.put(k, v),
+            NodeKey::Abstract(k) => store.put_abstract(k, v),
+        }
     }
 }
 
 impl<G> CFRES<G> {
+    fn node_key(&self, gs: &G) -> NodeKey
+    where
+        G: GameState,
+    {
+        let istate = self
+            .normalizer
+            .normalize_istate(&gs.istate_key(gs.cur_player()), gs);
+        match self.abstraction.abstract_key(&istate, gs) {
+            Some(k) => NodeKey::Abstract(k),
+            None => NodeKey::IState(istate.get()),
+        }
+    }
+
+    /// Locks the node store, counting how often another thread holds it
+    ///
     /// Can deadlock if we hold onto handle
-    fn lookup_entry(&self, key: &NormalizedIstate) -> Option<InfoState> {
-        self.infostates.lock().unwrap().get(&key.get())
+    fn lock_infostates(&self) -> MutexGuard<'_, NodeStore> {
+        node_store_locks::increment();
+        match self.infostates.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::WouldBlock) => {
+                node_store_lock_contended::increment();
+                self.infostates.lock().unwrap()
+            }
+            Err(TryLockError::Poisoned(e)) => panic!("node store lock poisoned: {}", e),
+        }
+    }
+
+    /// Returns this thread's value for the infostate, falling back to the
+    /// node store if it hasn't been updated this epoch
+    fn lookup_entry(&self, key: &NodeKey) -> Option<InfoState> {
+        match self.local.get(key) {
+            Some(node) => Some(node.value),
+            None => key.get_from(&self.lock_infostates()),
+        }
+    }
+
+    /// Same as [`CFRES::lookup_entry`] but keeps values read from the node
+    /// store for the rest of the epoch so they only need to be locked once
+    fn lookup_entry_mut(&mut self, key: &NodeKey) -> Option<InfoState> {
+        if let Some(node) = self.local.get(key) {
+            return Some(node.value);
+        }
+
+        let value = key.get_from(&self.lock_infostates())?;
+        self.local.insert(
+            *key,
+            LocalNode {
+                base: Some(value),
+                value,
+            },
+        );
+        Some(value)
+    }
+
+    /// Updates this thread's value, it's written to the node store on the
+    /// next merge
+    fn put_entry(&mut self, key: &NodeKey, v: InfoState) {
+        self.local
+            .entry(*key)
+            .and_modify(|node| node.value = v)
+            .or_insert(LocalNode {
+                base: None,
+                value: v,
+            });
+    }
+
+    /// Adds the changes this thread made since the last merge to the node store,
+    /// and its strategy window updates to the window
+    ///
+    /// Other threads may have updated the same infostates since they were read,
+    /// so the difference from the value that was read is added rather than
+    /// overwriting the stored value.
+    fn merge_local(&mut self) {
+        if let (Some(window), Some(shard)) = (&self.strategy_window, &mut self.window_shard) {
+            let iteration = self.iteration.load(Ordering::SeqCst);
+            window.lock().unwrap().merge(shard, iteration);
+        }
+        if self.local.is_empty() {
+            return;
+        }
+
+        let rm_plus = feature::is_enabled(feature::RegretMatchingPlus);
+        let local = std::mem::take(&mut self.local);
+        let mut infostates = self.lock_infostates();
+        for (key, node) in local {
+            let merged = match key.get_from(&infostates) {
+                Some(mut current) => {
+                    let base = node
+                        .base
+                        .unwrap_or_else(|| InfoState::new(node.value.actions.to_vec()));
+                    for i in 0..current.regrets.len() {
+                        current.regrets[i] += node.value.regrets[i] - base.regrets[i];
+                        current.avg_strategy[i] +=
+                            node.value.avg_strategy[i] - base.avg_strategy[i];
+                        if rm_plus {
+                            current.regrets[i] = current.regrets[i].max(0.0);
+                        }
+                    }
+                    current.last_iteration = current.last_iteration.max(node.value.last_iteration);
+                    current
+                }
+                None => node.value,
+            };
+            key.put_into(&mut infostates, &merged);
+            if let Some(checkpoints) = &self.checkpoints {
+                checkpoints.infostates.insert(key, merged);
+            }
+        }
     }
 
-    fn put_entry(&self, key: &NormalizedIstate, v: InfoState) {
-        self.infostates.lock().unwrap().put(&key.get(), &v);
+    /// Returns the average strategy over the window, None if there is no window
+    /// or the infostate wasn't visited in it
+    fn window_strategy(
+        &self,
+        key: &NodeKey,
+        infostate: &InfoState,
+    ) -> Option<Vec<(NormalizedAction, Weight)>> {
+        let sums = self
+            .strategy_window
+            .as_ref()?
+            .lock()
+            .unwrap()
+            .strategy_sums(&key.get())?;
+        Some(
+            infostate
+                .actions
+                .to_vec()
+                .into_iter()
+                .zip(sums)
+                .collect_vec(),
+        )
     }
 }
 
@@ -508,6 +1012,9 @@ fn add_regret(
         .index(action)
         .expect("couldn't find action");
     infostate.regrets[idx] += amount;
+    if feature::is_enabled(feature::RegretMatchingPlus) {
+        infostate.regrets[idx] = infostate.regrets[idx].max(0.0);
+    }
 }
 
 fn add_avstrat(infostate: &mut InfoState, action: NormalizedAction, amount: Weight) {
@@ -524,27 +1031,58 @@ impl<G: GameState + ResampleFromInfoState + Send> Policy<G> for CFRES<G> {
     /// If the policy is not defined for the provided state, a uniform
     /// random policy is returned.
     fn action_probabilities(&mut self, gs: &G) -> ActionVec<f64> {
-        let player = gs.cur_player();
+        let weights = self.play_weights(gs);
+        if !self.depth_checker.is_max_depth(gs) {
+            return weights;
+        }
+
+        // the rollout bot plays its best action
+        let values = weights.to_vec();
+        let mut policy = ActionVec::new(weights.actions());
+        policy[best_action(&values)] = 1.0;
+        policy
+    }
+}
 
+impl<G: GameState + ResampleFromInfoState + Send> CFRES<G> {
+    /// Returns the average policy, or the rollout bot's action values past the
+    /// max depth, served from the primed istates if possible
+    fn play_weights(&mut self, gs: &G) -> ActionVec<f64> {
+        let istate = self.play_cache_key(gs);
+        if let Some(cached) = self.play_cache.get(&istate) {
+            play_cache_hits::increment();
+            return self.denormalize_weights(&cached, gs);
+        }
+
+        play_cache_misses::increment();
+        self.compute_play_weights(gs)
+    }
+
+    fn compute_play_weights(&mut self, gs: &G) -> ActionVec<f64> {
         if self.depth_checker.is_max_depth(gs) {
-            return self.play_bot.action_probabilities(gs);
+            let values = self.play_bot.action_values(gs);
+            let actions = values.iter().map(|(a, _)| *a).collect_vec();
+            let mut weights = ActionVec::new(&actions);
+            for (a, v) in values {
+                weights[a] = v;
+            }
+            return weights;
         }
 
         let mut actions = self.vector_pool.detach();
         gs.legal_actions(&mut actions);
-        let info_state_key = self.normalizer.normalize_istate(&gs.istate_key(player), gs);
+        let info_state_key = self.node_key(gs);
 
         let mut policy = ActionVec::new(&actions);
 
         {
             let retrieved_infostate = self.lookup_entry(&info_state_key);
             if let Some(retrieved_infostate) = retrieved_infostate {
-                let policy_sum: f64 = retrieved_infostate
-                    .avg_strategy()
-                    .iter()
-                    .map(|(_, v)| *v as f64)
-                    .sum();
-                for (norm_a, s) in retrieved_infostate.avg_strategy() {
+                let avg_strategy = self
+                    .window_strategy(&info_state_key, &retrieved_infostate)
+                    .unwrap_or_else(|| retrieved_infostate.avg_strategy());
+                let policy_sum: f64 = avg_strategy.iter().map(|(_, v)| *v as f64).sum();
+                for (norm_a, s) in avg_strategy {
                     let a = self.normalizer.denormalize_action(norm_a, gs);
                     policy[a] = s as f64 / policy_sum;
                 }
@@ -561,14 +1099,55 @@ impl<G: GameState + ResampleFromInfoState + Send> Policy<G> for CFRES<G> {
     }
 }
 
+impl<G: GameState> CFRES<G> {
+    fn play_cache_key(&self, gs: &G) -> IStateKey {
+        self.normalizer
+            .normalize_istate(&gs.istate_key(gs.cur_player()), gs)
+            .get()
+    }
+
+    fn normalize_weights(&self, weights: &ActionVec<f64>, gs: &G) -> ActionVec<f64> {
+        weights.map_actions(|a| self.normalizer.normalize_action(a, gs).get())
+    }
+
+    fn denormalize_weights(&self, normalized: &ActionVec<f64>, gs: &G) -> ActionVec<f64> {
+        normalized.map_actions(|a| {
+            self.normalizer
+                .denormalize_action(NormalizedAction::new(a), gs)
+        })
+    }
+}
+
 impl<G: GameState + ResampleFromInfoState + Send> Agent<G> for CFRES<G> {
     fn step(&mut self, s: &G) -> Action {
         let action_weights = self.action_probabilities(s).to_vec();
         action_weights
-            .choose_weighted(&mut thread_rng(), |item| item.1)
+            .choose_weighted(&mut self.play_rng, |item| item.1)
             .unwrap()
             .0
     }
+
+    /// Difficulty from the spread of the policy, or of the play bot's action
+    /// values past the max depth
+    fn step_with_difficulty(&mut self, s: &G) -> (Action, Option<f64>) {
+        if self.depth_checker.is_max_depth(s) {
+            let values = self.play_weights(s).to_vec();
+            let difficulty =
+                decision_difficulty(values.iter().map(|(_, v)| *v), DIFFICULTY_VALUE_SCALE);
+            return (best_action(&values), Some(difficulty));
+        }
+
+        let action_weights = self.action_probabilities(s).to_vec();
+        let difficulty = decision_difficulty(
+            action_weights.iter().map(|(_, p)| *p),
+            DIFFICULTY_PROBABILITY_SCALE,
+        );
+        let a = action_weights
+            .choose_weighted(&mut self.play_rng, |item| item.1)
+            .unwrap()
+            .0;
+        (a, Some(difficulty))
+    }
 }
 
 pub trait DepthChecker<G>: Sync + Send + DynClone {
@@ -598,7 +1177,29 @@ impl DepthChecker<EuchreGameState> for EuchreDepthChecker {
 #[cfg(test)]
 mod tests {
 
-    use super::{feature, CFRES};
+    use approx::assert_relative_eq;
+    use std::{fs, path::Path};
+
+    use games::{
+        actions,
+        gamestates::{
+            euchre::{Euchre, EuchreGameState},
+            kuhn_poker::KuhnPoker,
+        },
+        istate::NormalizedAction,
+        GameState,
+    };
+    use itertools::Itertools;
+    use rand::SeedableRng;
+
+    use crate::{
+        agents::Agent,
+        algorithms::{open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot},
+        metrics::play_cache_hits,
+        policy::Policy,
+    };
+
+    use super::{feature, InfoState, CFRES, CHECKPOINT_NAME};
 
     #[test]
     fn cfres_train_test() {
@@ -607,4 +1208,157 @@ mod tests {
         let mut alg = CFRES::new_kp();
         alg.train(10);
     }
+
+    #[test]
+    fn cfres_strategy_window_test() {
+        let mut alg = CFRES::new_kp().with_strategy_window(100);
+        alg.train(1000);
+
+        let mut gs = KuhnPoker::new_state();
+        while gs.is_chance_node() {
+            gs.apply_action(actions!(gs)[0]);
+        }
+        let policy = alg.action_probabilities(&gs);
+        let total: f64 = policy.to_vec().iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+
+        // only the last 100 of the 1000 iterations count towards the window,
+        // the full average has all of them
+        let key = alg.node_key(&gs);
+        let full: f32 = alg
+            .lookup_entry(&key)
+            .unwrap()
+            .avg_strategy()
+            .iter()
+            .map(|(_, w)| w)
+            .sum();
+        let window: f32 = alg
+            .strategy_window
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .strategy_sums(&key.get())
+            .unwrap()
+            .iter()
+            .sum();
+        assert!(
+            window > 0.0 && window < 0.2 * full,
+            "window {} full {}",
+            window,
+            full
+        );
+    }
+
+    #[test]
+    fn cfres_prime_test() {
+        let mut alg = CFRES::new_kp();
+        alg.train(100);
+
+        // deals share istates, kuhn poker only has 12 of them
+        let primed = alg.prime(10);
+        assert!(primed > 0 && primed <= 12);
+        assert_eq!(alg.play_cache.len(), primed);
+        // priming doesn't train the policy
+        assert_eq!(alg.iterations(), 100);
+
+        // the primed policy is stale once trained
+        alg.train(1);
+        assert!(alg.play_cache.is_empty());
+    }
+
+    #[test]
+    fn cfres_prime_play_bot_test() {
+        // the rollout bot plays from the first card
+        let mut alg = CFRES::new_euchre(SeedableRng::seed_from_u64(42), 0, None);
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|");
+        assert_eq!(alg.prime_line(gs.clone()), 1);
+        let primed = alg.action_probabilities(&gs);
+
+        // a bot without rollouts can't value the actions, so they must come
+        // from the cache
+        alg.play_bot = PIMCTSBot::new(
+            0,
+            OpenHandSolver::new_euchre(),
+            SeedableRng::seed_from_u64(0),
+        );
+        let hits = play_cache_hits::read();
+        let values = alg.play_weights(&gs).to_vec();
+        assert!(values.iter().all(|(_, v)| v.is_finite()));
+        assert_eq!(alg.action_probabilities(&gs), primed);
+        let (a, _) = alg.step_with_difficulty(&gs);
+        assert_eq!(primed[a], 1.0);
+        assert!(play_cache_hits::read() >= hits + 3);
+
+        // clones share the cache
+        let mut clone = alg.clone();
+        assert_eq!(clone.action_probabilities(&gs), primed);
+    }
+
+    #[test]
+    fn cfres_merge_local_test() {
+        let alg = CFRES::new_kp();
+        let mut gs = KuhnPoker::new_state();
+        while gs.is_chance_node() {
+            gs.apply_action(actions!(gs)[0]);
+        }
+        let key = alg.node_key(&gs);
+        let actions = actions!(gs)
+            .into_iter()
+            .map(NormalizedAction::new)
+            .collect_vec();
+        let initial = InfoState::new(actions.clone());
+
+        // neither thread sees the other's update until they're merged
+        let mut threads = [alg.clone(), alg.clone()];
+        for t in threads.iter_mut() {
+            let mut is = t.lookup_entry_mut(&key).unwrap_or(initial);
+            is.regrets[0] += 1.0;
+            t.put_entry(&key, is);
+        }
+        assert!(alg.lookup_entry(&key).is_none());
+
+        threads.iter_mut().for_each(|t| t.merge_local());
+        let merged = alg.lookup_entry(&key).unwrap();
+        assert_relative_eq!(merged.regrets[0], initial.regrets[0] + 2.0);
+        assert_relative_eq!(merged.regrets[1], initial.regrets[1]);
+    }
+
+    #[test]
+    fn cfres_checkpoint_test() {
+        let open = |dir: &Path| {
+            CFRES::new_euchre(SeedableRng::seed_from_u64(0), 0, Some(dir))
+                .with_checkpoints()
+                .unwrap()
+        };
+        let mut gs = Euchre::new_state();
+        while gs.is_chance_node() {
+            gs.apply_action(actions!(gs)[0]);
+        }
+        let mut value = InfoState::new(Vec::new());
+        value.last_iteration = 42;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut alg = open(dir.path());
+        let key = alg.node_key(&gs);
+        alg.put_entry(&key, value);
+        alg.merge_local();
+        alg.checkpoint().unwrap();
+
+        // a store that never saw the update, so it can only come from the
+        // checkpoint
+        let resumed = tempfile::tempdir().unwrap();
+        let path = resumed.path().join(CHECKPOINT_NAME);
+        fs::copy(dir.path().join(CHECKPOINT_NAME), &path).unwrap();
+        let alg = open(resumed.path());
+        assert_eq!(alg.lookup_entry(&key).unwrap().last_iteration, 42);
+
+        // saving commits the update, so the checkpoint isn't needed
+        alg.save().unwrap();
+        assert!(!path.exists());
+        assert!(alg.checkpoints.as_ref().unwrap().infostates.is_empty());
+        drop(alg);
+        let alg = open(resumed.path());
+        assert_eq!(alg.lookup_entry(&key).unwrap().last_iteration, 42);
+    }
 }
@@ -0,0 +1,668 @@
+//! Distills a bid policy into a shallow decision tree over hand strength
+//! features
+//!
+//! The trained policies are millions of opaque infostate weights. A tree of a
+//! few `feature >= threshold` rules is something a person can read and argue
+//! with, and it can play as an opponent whose bids can be explained. Fidelity
+//! is how often the tree makes the same call as the source policy.
+//!
+//! Trees only decide whether to call trump. When choosing trump in the second
+//! round, calling means naming the strongest legal suit, see
+//! [`BidFeatures::candidate_suit`].
+
+use std::{fmt::Display, fs, path::Path};
+
+use anyhow::Context;
+use games::{
+    actions,
+    gamestates::euchre::{
+        actions::{Card, EAction, Suit},
+        EPhase, Euchre, EuchreGameState,
+    },
+    Action, GameState,
+};
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::{agents::Agent, policy::Policy};
+
+/// Seat of the dealer, the first bid is made by the seat after them
+const DEALER: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BidChoice {
+    Pass,
+    Call,
+}
+
+impl Display for BidChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BidChoice::Pass => f.write_str("pass"),
+            BidChoice::Call => f.write_str("call"),
+        }
+    }
+}
+
+/// Hand strength features a bid is made from, all relative to the suit that
+/// would be called
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BidFeature {
+    /// 1 when deciding on the face up card, 2 when naming trump
+    Round,
+    /// 0 is the first to bid and 3 is the dealer
+    Seat,
+    /// Cards that would be trump, including the left bower
+    Trumps,
+    RightBower,
+    LeftBower,
+    /// Aces that wouldn't be trump
+    OffAces,
+    /// Suits held once the left bower counts as trump
+    Suits,
+    /// 1 if the dealer picking up the face up card is on the bidder's team, -1
+    /// if they're an opponent and 0 when naming trump
+    FaceUpToTeam,
+}
+
+pub const BID_FEATURES: [BidFeature; 8] = [
+    BidFeature::Round,
+    BidFeature::Seat,
+    BidFeature::Trumps,
+    BidFeature::RightBower,
+    BidFeature::LeftBower,
+    BidFeature::OffAces,
+    BidFeature::Suits,
+    BidFeature::FaceUpToTeam,
+];
+
+impl Display for BidFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BidFeature::Round => "round",
+            BidFeature::Seat => "seat",
+            BidFeature::Trumps => "trumps",
+            BidFeature::RightBower => "right_bower",
+            BidFeature::LeftBower => "left_bower",
+            BidFeature::OffAces => "off_aces",
+            BidFeature::Suits => "suits",
+            BidFeature::FaceUpToTeam => "face_up_to_team",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BidFeatures([i8; BID_FEATURES.len()]);
+
+impl BidFeatures {
+    /// Returns None outside of the bidding phases
+    pub fn new(gs: &EuchreGameState) -> Option<Self> {
+        let round = match gs.phase() {
+            EPhase::Pickup => 1,
+            EPhase::ChooseTrump => 2,
+            _ => return None,
+        };
+        let seat = gs.cur_player();
+        let trump = Self::candidate_suit(gs)?;
+        let hand = gs.get_hand(seat);
+
+        let mut values = [0; BID_FEATURES.len()];
+        for (f, v) in BID_FEATURES.iter().zip(values.iter_mut()) {
+            *v = match f {
+                BidFeature::Round => round,
+                BidFeature::Seat => seat as i8,
+                BidFeature::Trumps => hand
+                    .iter()
+                    .filter(|c| effective_suit(**c, trump) == trump)
+                    .count() as i8,
+                BidFeature::RightBower => hand.contains(&jack(trump)) as i8,
+                BidFeature::LeftBower => hand.contains(&jack(same_color(trump))) as i8,
+                BidFeature::OffAces => hand
+                    .iter()
+                    .filter(|c| is_ace(**c) && c.suit() != trump)
+                    .count() as i8,
+                BidFeature::Suits => hand
+                    .iter()
+                    .map(|c| effective_suit(*c, trump))
+                    .unique()
+                    .count() as i8,
+                BidFeature::FaceUpToTeam => match round {
+                    1 if seat % 2 == DEALER % 2 => 1,
+                    1 => -1,
+                    _ => 0,
+                },
+            };
+        }
+        Some(Self(values))
+    }
+
+    pub fn get(&self, f: BidFeature) -> i8 {
+        self.0[f as usize]
+    }
+
+    /// The suit a call would make trump: the face up card's suit in the first
+    /// round, otherwise the legal suit the bidder holds the most trump in,
+    /// with the bowers as tie breakers
+    pub fn candidate_suit(gs: &EuchreGameState) -> Option<Suit> {
+        match gs.phase() {
+            EPhase::Pickup => gs.face_up().map(|c| c.suit()),
+            EPhase::ChooseTrump => {
+                let hand = gs.get_hand(gs.cur_player());
+                actions!(gs)
+                    .into_iter()
+                    .filter_map(|a| call_suit(EAction::from(a)))
+                    .max_by_key(|s| {
+                        (
+                            hand.iter()
+                                .filter(|c| effective_suit(**c, *s) == *s)
+                                .count(),
+                            hand.contains(&jack(*s)),
+                            hand.contains(&jack(same_color(*s))),
+                        )
+                    })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A bid state from the source policy, labeled with its more likely choice
+#[derive(Debug, Clone, Copy)]
+pub struct BidSample {
+    pub features: BidFeatures,
+    pub choice: BidChoice,
+    /// Probability the source policy calls trump
+    pub call_probability: f64,
+    /// If the policy's most likely call in the second round is the candidate
+    /// suit, always true in the first round
+    pub suit_agrees: bool,
+}
+
+/// Deals `num_deals` hands and bids them with `policy`, returning a sample
+/// for each bid that isn't forced
+///
+/// Bids are sampled from the policy so later bids see the histories the
+/// policy actually produces.
+pub fn sample_bids<P: Policy<EuchreGameState>>(
+    policy: &mut P,
+    num_deals: usize,
+    rng: &mut StdRng,
+) -> Vec<BidSample> {
+    let mut samples = Vec::new();
+    for _ in 0..num_deals {
+        let mut gs = Euchre::new_state();
+        while gs.is_chance_node() {
+            let a = *actions!(gs).choose(rng).unwrap();
+            gs.apply_action(a);
+        }
+
+        while matches!(gs.phase(), EPhase::Pickup | EPhase::ChooseTrump) {
+            let probs = policy.action_probabilities(&gs).to_vec();
+            if probs.len() > 1 {
+                let features = BidFeatures::new(&gs).unwrap();
+                samples.push(label(&gs, features, &probs));
+            }
+
+            let a = probs.choose_weighted(rng, |x| x.1).unwrap().0;
+            gs.apply_action(a);
+        }
+    }
+    samples
+}
+
+fn label(gs: &EuchreGameState, features: BidFeatures, probs: &[(Action, f64)]) -> BidSample {
+    let pass: Action = EAction::Pass.into();
+    let call_probability = probs
+        .iter()
+        .filter(|(a, _)| *a != pass)
+        .map(|(_, p)| p)
+        .sum::<f64>();
+    let policy_suit = probs
+        .iter()
+        .filter_map(|(a, p)| call_suit(EAction::from(*a)).map(|s| (s, p)))
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(s, _)| s);
+
+    BidSample {
+        features,
+        choice: if call_probability > 0.5 {
+            BidChoice::Call
+        } else {
+            BidChoice::Pass
+        },
+        call_probability,
+        suit_agrees: policy_suit.is_none_or(|s| Some(s) == BidFeatures::candidate_suit(gs)),
+    }
+}
+
+/// How closely a tree matches the policy its samples came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fidelity {
+    /// Fraction of bids where the tree makes the policy's more likely choice
+    pub agreement: f64,
+    /// Average probability the policy gives to the tree's choice
+    pub expected_agreement: f64,
+    /// Fraction of second round calls where the policy's most likely suit is
+    /// the one the tree would name
+    pub suit_agreement: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum BidTree {
+    Leaf {
+        choice: BidChoice,
+        /// Training samples that reached the leaf
+        samples: usize,
+        /// Fraction of those samples where the policy made the same choice
+        purity: f64,
+    },
+    Split {
+        feature: BidFeature,
+        /// Samples with `feature >= threshold` go to `above`
+        threshold: i8,
+        below: Box<BidTree>,
+        above: Box<BidTree>,
+    },
+}
+
+impl BidTree {
+    /// Fits a tree of at most `max_depth` splits to the samples, each leaf
+    /// keeps at least `min_leaf` samples
+    pub fn fit(samples: &[BidSample], max_depth: usize, min_leaf: usize) -> Self {
+        let samples = samples.iter().collect_vec();
+        Self::fit_node(&samples, max_depth, min_leaf.max(1))
+    }
+
+    fn fit_node(samples: &[&BidSample], depth: usize, min_leaf: usize) -> Self {
+        let leaf = Self::leaf(samples);
+        if depth == 0 || samples.len() < 2 * min_leaf || gini(samples) == 0.0 {
+            return leaf;
+        }
+
+        let mut best: Option<(f64, BidFeature, i8)> = None;
+        for f in BID_FEATURES {
+            let values = samples.iter().map(|s| s.features.get(f)).unique().sorted();
+            // splitting at the lowest value puts everything above
+            for t in values.skip(1) {
+                let (above, below): (Vec<_>, Vec<_>) =
+                    samples.iter().partition(|s| s.features.get(f) >= t);
+                if above.len() < min_leaf || below.len() < min_leaf {
+                    continue;
+                }
+                let n = samples.len() as f64;
+                let impurity =
+                    gini(&above) * above.len() as f64 / n + gini(&below) * below.len() as f64 / n;
+                if best.is_none_or(|(b, _, _)| impurity < b) {
+                    best = Some((impurity, f, t));
+                }
+            }
+        }
+
+        let Some((impurity, feature, threshold)) = best else {
+            return leaf;
+        };
+        if impurity >= gini(samples) {
+            return leaf;
+        }
+
+        let (above, below): (Vec<_>, Vec<_>) = samples
+            .iter()
+            .partition(|s| s.features.get(feature) >= threshold);
+        let below = Self::fit_node(&below, depth - 1, min_leaf);
+        let above = Self::fit_node(&above, depth - 1, min_leaf);
+
+        // a split that makes the same choice either way isn't worth reading
+        if let (Some(b), Some(a)) = (below.leaf_choice(), above.leaf_choice()) {
+            if a == b {
+                return leaf;
+            }
+        }
+        BidTree::Split {
+            feature,
+            threshold,
+            below: Box::new(below),
+            above: Box::new(above),
+        }
+    }
+
+    fn leaf(samples: &[&BidSample]) -> Self {
+        let calls = samples
+            .iter()
+            .filter(|s| s.choice == BidChoice::Call)
+            .count();
+        let (choice, agree) = if calls * 2 > samples.len() {
+            (BidChoice::Call, calls)
+        } else {
+            (BidChoice::Pass, samples.len() - calls)
+        };
+        BidTree::Leaf {
+            choice,
+            samples: samples.len(),
+            purity: agree as f64 / samples.len().max(1) as f64,
+        }
+    }
+
+    fn leaf_choice(&self) -> Option<BidChoice> {
+        match self {
+            BidTree::Leaf { choice, .. } => Some(*choice),
+            BidTree::Split { .. } => None,
+        }
+    }
+
+    pub fn decide(&self, features: &BidFeatures) -> BidChoice {
+        match self {
+            BidTree::Leaf { choice, .. } => *choice,
+            BidTree::Split {
+                feature,
+                threshold,
+                below,
+                above,
+            } => match features.get(*feature) >= *threshold {
+                true => above.decide(features),
+                false => below.decide(features),
+            },
+        }
+    }
+
+    pub fn fidelity(&self, samples: &[BidSample]) -> Fidelity {
+        let n = samples.len().max(1) as f64;
+        let mut agreement = 0.0;
+        let mut expected_agreement = 0.0;
+        let mut round_two_calls = 0;
+        let mut suit_agrees = 0;
+        for s in samples {
+            let choice = self.decide(&s.features);
+            if choice == s.choice {
+                agreement += 1.0;
+            }
+            expected_agreement += match choice {
+                BidChoice::Call => s.call_probability,
+                BidChoice::Pass => 1.0 - s.call_probability,
+            };
+            if choice == BidChoice::Call && s.features.get(BidFeature::Round) == 2 {
+                round_two_calls += 1;
+                suit_agrees += s.suit_agrees as usize;
+            }
+        }
+
+        Fidelity {
+            agreement: agreement / n,
+            expected_agreement: expected_agreement / n,
+            suit_agreement: suit_agrees as f64 / round_two_calls.max(1) as f64,
+        }
+    }
+
+    /// Number of rules a reader has to follow, one per leaf
+    pub fn num_leaves(&self) -> usize {
+        match self {
+            BidTree::Leaf { .. } => 1,
+            BidTree::Split { below, above, .. } => below.num_leaves() + above.num_leaves(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write bid tree to {:?}", path))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("failed to read bid tree from {:?}", path))?;
+        Ok(serde_json::from_str(&s)?)
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = "    ".repeat(depth);
+        match self {
+            BidTree::Leaf {
+                choice,
+                samples,
+                purity,
+            } => writeln!(
+                f,
+                "{}{} ({:.0}% of {} bids)",
+                indent,
+                choice,
+                purity * 100.0,
+                samples
+            ),
+            BidTree::Split {
+                feature,
+                threshold,
+                below,
+                above,
+            } => {
+                writeln!(f, "{}if {} >= {}:", indent, feature, threshold)?;
+                above.fmt_indented(f, depth + 1)?;
+                writeln!(f, "{}else:", indent)?;
+                below.fmt_indented(f, depth + 1)
+            }
+        }
+    }
+}
+
+/// Writes the tree as nested if/else rules, e.g.
+///
+/// ```text
+/// if trumps >= 3:
+///     call (91% of 2400 bids)
+/// else:
+///     pass (97% of 8100 bids)
+/// ```
+impl Display for BidTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Bids with a distilled tree and leaves the rest of the hand to `play`
+pub struct DistilledBidAgent<A> {
+    tree: BidTree,
+    play: A,
+}
+
+impl<A> DistilledBidAgent<A> {
+    pub fn new(tree: BidTree, play: A) -> Self {
+        Self { tree, play }
+    }
+}
+
+impl<A: Agent<EuchreGameState>> Agent<EuchreGameState> for DistilledBidAgent<A> {
+    fn step(&mut self, s: &EuchreGameState) -> Action {
+        let Some(features) = BidFeatures::new(s) else {
+            return self.play.step(s);
+        };
+        let pass = EAction::Pass.into();
+        let legal = actions!(s);
+        // the dealer can't pass in the second round
+        if self.tree.decide(&features) == BidChoice::Pass && legal.contains(&pass) {
+            return pass;
+        }
+
+        match s.phase() {
+            EPhase::Pickup => EAction::Pickup.into(),
+            _ => suit_call(BidFeatures::candidate_suit(s).expect("no suit to call")).into(),
+        }
+    }
+
+    fn get_name(&self) -> String {
+        "DistilledBidAgent".to_string()
+    }
+}
+
+/// Gini impurity of the policy's choices, 0 when they all agree
+fn gini(samples: &[&BidSample]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let calls = samples
+        .iter()
+        .filter(|s| s.choice == BidChoice::Call)
+        .count() as f64;
+    let p = calls / samples.len() as f64;
+    2.0 * p * (1.0 - p)
+}
+
+fn call_suit(a: EAction) -> Option<Suit> {
+    match a {
+        EAction::Spades => Some(Suit::Spades),
+        EAction::Clubs => Some(Suit::Clubs),
+        EAction::Hearts => Some(Suit::Hearts),
+        EAction::Diamonds => Some(Suit::Diamonds),
+        _ => None,
+    }
+}
+
+fn suit_call(suit: Suit) -> EAction {
+    match suit {
+        Suit::Spades => EAction::Spades,
+        Suit::Clubs => EAction::Clubs,
+        Suit::Hearts => EAction::Hearts,
+        Suit::Diamonds => EAction::Diamonds,
+    }
+}
+
+fn same_color(suit: Suit) -> Suit {
+    match suit {
+        Suit::Spades => Suit::Clubs,
+        Suit::Clubs => Suit::Spades,
+        Suit::Hearts => Suit::Diamonds,
+        Suit::Diamonds => Suit::Hearts,
+    }
+}
+
+fn jack(suit: Suit) -> Card {
+    Card::JS.to_suit(suit)
+}
+
+fn is_ace(c: Card) -> bool {
+    matches!(c, Card::AS | Card::AC | Card::AH | Card::AD)
+}
+
+/// The suit a card follows, the left bower is part of trump
+fn effective_suit(c: Card, trump: Suit) -> Suit {
+    if c == jack(same_color(trump)) {
+        trump
+    } else {
+        c.suit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use games::{
+        actions,
+        gamestates::euchre::{
+            actions::{EAction, Suit},
+            EPhase, EuchreGameState,
+        },
+        Action, GameState,
+    };
+    use rand::SeedableRng;
+
+    use crate::{
+        agents::{Agent, GreedyAgent},
+        collections::actionvec::ActionVec,
+        policy::Policy,
+    };
+
+    use super::{
+        sample_bids, suit_call, BidChoice, BidFeature, BidFeatures, BidTree, DistilledBidAgent,
+    };
+
+    /// Calls with three or more trump, or two with the right bower, 90% of
+    /// the time
+    struct RulePolicy;
+
+    impl Policy<EuchreGameState> for RulePolicy {
+        fn action_probabilities(&mut self, gs: &EuchreGameState) -> ActionVec<f64> {
+            let actions = actions!(gs);
+            let mut probs = ActionVec::new(&actions);
+            let f = BidFeatures::new(gs).unwrap();
+            let call = f.get(BidFeature::Trumps) >= 3
+                || (f.get(BidFeature::Trumps) == 2 && f.get(BidFeature::RightBower) == 1);
+            let pass = Action::from(EAction::Pass);
+            let a = match (call || !actions.contains(&pass), gs.phase()) {
+                (false, _) => pass,
+                (true, EPhase::Pickup) => EAction::Pickup.into(),
+                (true, _) => suit_call(BidFeatures::candidate_suit(gs).unwrap()).into(),
+            };
+
+            for other in actions.iter() {
+                probs[*other] = 0.1 / (actions.len() - 1).max(1) as f64;
+            }
+            probs[a] = if actions.len() > 1 { 0.9 } else { 1.0 };
+            probs
+        }
+    }
+
+    #[test]
+    fn test_bid_features() {
+        let gs = EuchreGameState::from("JsJc9sAhAd|QcThJhKhKd|Ac9dAsTdQh|9cKc9hKsQs|Ts|");
+        let f = BidFeatures::new(&gs).unwrap();
+        assert_eq!(f.get(BidFeature::Round), 1);
+        assert_eq!(f.get(BidFeature::Seat), 0);
+        assert_eq!(f.get(BidFeature::Trumps), 3);
+        assert_eq!(f.get(BidFeature::RightBower), 1);
+        assert_eq!(f.get(BidFeature::LeftBower), 1);
+        assert_eq!(f.get(BidFeature::OffAces), 2);
+        assert_eq!(f.get(BidFeature::Suits), 3);
+        assert_eq!(f.get(BidFeature::FaceUpToTeam), -1);
+
+        // second player names trump, the face up suit isn't a candidate
+        let gs = EuchreGameState::from("JsJc9sAhAd|QcThJhKhKd|Ac9dAsTdQh|9cKc9hKsQs|Ts|PPPPP");
+        assert_eq!(BidFeatures::candidate_suit(&gs), Some(Suit::Hearts));
+        let f = BidFeatures::new(&gs).unwrap();
+        assert_eq!(f.get(BidFeature::Round), 2);
+        assert_eq!(f.get(BidFeature::Seat), 1);
+        assert_eq!(f.get(BidFeature::FaceUpToTeam), 0);
+        assert_eq!(f.get(BidFeature::Trumps), 3);
+        assert_eq!(f.get(BidFeature::LeftBower), 0);
+    }
+
+    #[test]
+    fn test_distill_bid_policy() {
+        let mut rng = SeedableRng::seed_from_u64(42);
+        let train = sample_bids(&mut RulePolicy, 2000, &mut rng);
+        let test = sample_bids(&mut RulePolicy, 500, &mut rng);
+        assert!(train.iter().any(|s| s.choice == BidChoice::Call));
+
+        let tree = BidTree::fit(&train, 3, 10);
+        let fidelity = tree.fidelity(&test);
+        assert!(fidelity.agreement > 0.97, "{:?}\n{}", fidelity, tree);
+        assert!(fidelity.expected_agreement > 0.85, "{:?}", fidelity);
+        assert_eq!(fidelity.suit_agreement, 1.0);
+        assert!(tree.to_string().starts_with("if trumps >= "), "{}", tree);
+
+        // a stump can't express the bower rule
+        let stump = BidTree::fit(&train, 1, 10);
+        assert_eq!(stump.num_leaves(), 2);
+        assert!(stump.fidelity(&test).agreement <= fidelity.agreement);
+
+        let path = std::env::temp_dir().join("test_distill_bid_policy.json");
+        tree.save(&path).unwrap();
+        assert_eq!(BidTree::load(&path).unwrap(), tree);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_distilled_bid_agent() {
+        let call = BidTree::Leaf {
+            choice: BidChoice::Call,
+            samples: 0,
+            purity: 1.0,
+        };
+        let mut agent = DistilledBidAgent::new(call, GreedyAgent::default());
+
+        let gs = EuchreGameState::from("JsJc9sAhAd|QcThJhKhKd|Ac9dAsTdQh|9cKc9hKsQs|Ts|");
+        assert_eq!(EAction::from(agent.step(&gs)), EAction::Pickup);
+        let gs = EuchreGameState::from("JsJc9sAhAd|QcThJhKhKd|Ac9dAsTdQh|9cKc9hKsQs|Ts|PPPPP");
+        assert_eq!(EAction::from(agent.step(&gs)), EAction::Hearts);
+
+        // card play is left to the other agent
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|QdKd");
+        assert_eq!(EAction::from(agent.step(&gs)), EAction::AD);
+    }
+}
@@ -1,15 +1,22 @@
+use std::collections::HashMap;
+
 use games::{
     actions,
     gamestates::kuhn_poker::{KPAction, KPGameState},
-    Action, GameState,
+    istate::IStateKey,
+    Action, GameState, Player,
 };
 use itertools::Itertools;
+use rand::{rngs::StdRng, seq::SliceRandom};
 
 use crate::{
     algorithms::tabular_best_response::TabularBestResponse, collections::actionvec::ActionVec,
     policy::Policy,
 };
 
+/// Number of states sent to the policy at once when building a [`TabularPolicy`]
+const BATCH_SIZE: usize = 1024;
+
 pub struct ExploitabilityData {
     pub nash_conv: f64,
     pub player_improvements: Vec<f64>,
@@ -70,13 +77,15 @@ pub fn exploitability<G: GameState, P: Policy<G>>(
         panic!("only support 2 players");
     }
 
+    let mut policy = TabularPolicy::new(&root_state, policy);
+
     let mut best_response_values = Vec::new();
     for p in 0..root_state.num_players() {
-        let mut br = TabularBestResponse::new(policy, &root_state, p, 0.0);
+        let mut br = TabularBestResponse::new(&mut policy, &root_state, p, 0.0);
         best_response_values.push(br.value(&mut root_state.clone()));
     }
 
-    let on_policy_values = state_values(&mut root_state, num_players, policy);
+    let on_policy_values = state_values(&mut root_state, num_players, &mut policy);
     let mut player_improvements = Vec::new();
     for i in 0..on_policy_values.len() {
         player_improvements.push(best_response_values[i] - on_policy_values[i]);
@@ -90,6 +99,138 @@ pub fn exploitability<G: GameState, P: Policy<G>>(
     }
 }
 
+/// Estimates the exploitability of `policy` in games too large to walk with
+/// [`exploitability`], using Local Best Response
+///
+/// The players are split into two teams by `p % 2`, for two player games each
+/// team is a single player. For each team, `responder` plays every seat of the
+/// team against `policy` on `num_games` sampled deals, and the improvement is
+/// how much more the team scores than when `policy` plays its seats on the same
+/// deals.
+///
+/// The responder is usually a [`PIMCTSBot`](super::pimcts::PIMCTSBot) with an
+/// [`OpenHandSolver`](super::open_hand_solver::OpenHandSolver), which greedily
+/// picks the action with the best open hand value over worlds sampled from its
+/// istate. As long as the responder only uses its istate, no strategy does
+/// better than a best response, so `nash_conv` is a lower bound on the true
+/// value, up to sampling noise.
+///
+/// See https://arxiv.org/abs/1612.07547 for Local Best Response.
+pub fn local_best_response<G: GameState, P: Policy<G>, R: Policy<G>>(
+    game_generator: fn() -> G,
+    policy: &mut P,
+    responder: &mut R,
+    num_games: usize,
+    rng: &mut StdRng,
+) -> ExploitabilityData {
+    let mut player_improvements = vec![0.0; 2];
+
+    for _ in 0..num_games {
+        // all the playouts for a game share the deal
+        let mut deal = (game_generator)();
+        while deal.is_chance_node() {
+            let a = *actions!(deal).choose(rng).unwrap();
+            deal.apply_action(a);
+        }
+
+        let on_policy = playout(deal.clone(), policy, responder, None, rng);
+        for (team, improvement) in player_improvements.iter_mut().enumerate() {
+            let responded = playout(deal.clone(), policy, responder, Some(team), rng);
+            *improvement += responded.evaluate(team) - on_policy.evaluate(team);
+        }
+    }
+
+    player_improvements
+        .iter_mut()
+        .for_each(|x| *x /= num_games as f64);
+    let nash_conv = player_improvements.iter().sum();
+
+    ExploitabilityData {
+        nash_conv,
+        player_improvements,
+    }
+}
+
+/// Plays the game to the end, `responder` plays the seats of `responding_team`
+/// and `policy` plays all others
+fn playout<G: GameState, P: Policy<G>, R: Policy<G>>(
+    mut gs: G,
+    policy: &mut P,
+    responder: &mut R,
+    responding_team: Option<usize>,
+    rng: &mut StdRng,
+) -> G {
+    while !gs.is_terminal() {
+        let a = if gs.is_chance_node() {
+            *actions!(gs).choose(rng).unwrap()
+        } else if Some(gs.cur_player() % 2) == responding_team {
+            sample_action(&responder.action_probabilities(&gs), rng)
+        } else {
+            sample_action(&policy.action_probabilities(&gs), rng)
+        };
+        gs.apply_action(a);
+    }
+    gs
+}
+
+fn sample_action(probs: &ActionVec<f64>, rng: &mut StdRng) -> Action {
+    probs.to_vec().choose_weighted(rng, |(_, p)| *p).unwrap().0
+}
+
+/// A policy evaluated for every infostate in the game up front
+///
+/// Exploitability queries the policy at every decision node, collecting the
+/// infostates first lets the policy evaluate them in batches.
+struct TabularPolicy {
+    probs: HashMap<(Player, IStateKey), ActionVec<f64>>,
+}
+
+impl TabularPolicy {
+    fn new<G: GameState, P: Policy<G>>(root_state: &G, policy: &mut P) -> Self {
+        let mut states = HashMap::new();
+        decision_states(&mut root_state.clone(), &mut states);
+
+        let (keys, states): (Vec<_>, Vec<_>) = states.into_iter().unzip();
+        let mut probs = HashMap::with_capacity(keys.len());
+        for (keys, states) in keys.chunks(BATCH_SIZE).zip(states.chunks(BATCH_SIZE)) {
+            probs.extend(
+                keys.iter()
+                    .copied()
+                    .zip(policy.action_probabilities_batch(states)),
+            );
+        }
+
+        Self { probs }
+    }
+}
+
+impl<G: GameState> Policy<G> for TabularPolicy {
+    fn action_probabilities(&mut self, gs: &G) -> ActionVec<f64> {
+        let player = gs.cur_player();
+        self.probs[&(player, gs.istate_key(player))].clone()
+    }
+}
+
+/// Collects a state for every infostate where a player makes a decision
+fn decision_states<G: GameState>(state: &mut G, states: &mut HashMap<(Player, IStateKey), G>) {
+    if state.is_terminal() {
+        return;
+    }
+
+    if !state.is_chance_node() {
+        let player = state.cur_player();
+        states
+            .entry((player, state.istate_key(player)))
+            .or_insert_with(|| state.clone());
+    }
+
+    for a in actions!(state) {
+        state.apply_action(a);
+        decision_states(state, states);
+        state.undo();
+    }
+}
+
 /// Policy for the nash equialibrium strategy of kuhn poker
 ///
 /// https://en.wikipedia.org/wiki/Kuhn_poker
@@ -127,12 +268,28 @@ impl Policy<KPGameState> for KuhnPokerNashPolicy {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use approx::assert_relative_eq;
-    use games::gamestates::kuhn_poker::{KPAction, KuhnPoker};
+    use games::{
+        actions,
+        gamestates::{
+            kuhn_poker::{KPAction, KPGameState, KuhnPoker},
+            tictactoe::{TTTGameState, TicTacToe},
+        },
+        istate::IStateKey,
+        GameState,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
 
     use crate::{
-        algorithms::exploitability::{exploitability, KuhnPokerNashPolicy},
-        policy::{AlwaysPolicy, UniformRandomPolicy},
+        algorithms::{
+            exploitability::{exploitability, local_best_response, KuhnPokerNashPolicy},
+            open_hand_solver::OpenHandSolver,
+            pimcts::PIMCTSBot,
+        },
+        collections::actionvec::ActionVec,
+        policy::{AlwaysPolicy, Policy, UniformRandomPolicy},
     };
 
     #[test]
@@ -171,4 +328,135 @@ mod tests {
         let data = exploitability(|| (KuhnPoker::game().new)(), &mut policy);
         assert_relative_eq!(data.nash_conv, 0.0);
     }
+
+    #[test]
+    fn test_local_best_response() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        let mut responder = PIMCTSBot::new(20, OpenHandSolver::default(), rng.clone());
+
+        // can't exploit the nash policy, and should find some of the uniform
+        // policy's 11/12 exploitability. Open hand values ignore that the
+        // opponent can't see our card, so it doesn't find all of it
+        let mut policy = KuhnPokerNashPolicy::default();
+        let nash = local_best_response(
+            || (KuhnPoker::game().new)(),
+            &mut policy,
+            &mut responder,
+            5000,
+            &mut rng,
+        );
+        assert!(nash.nash_conv.abs() < 0.1, "{}", nash.nash_conv);
+
+        let mut policy = UniformRandomPolicy::new();
+        let uniform = local_best_response(
+            || (KuhnPoker::game().new)(),
+            &mut policy,
+            &mut responder,
+            5000,
+            &mut rng,
+        );
+        assert!(
+            uniform.nash_conv > 0.25 && uniform.nash_conv < 11.0 / 12.0 + 0.1,
+            "{}",
+            uniform.nash_conv
+        );
+        assert_eq!(uniform.player_improvements.len(), 2);
+    }
+
+    /// Counts how the wrapped policy is queried
+    #[derive(Default)]
+    struct CountingPolicy {
+        policy: KuhnPokerNashPolicy,
+        single_calls: usize,
+        batched_states: usize,
+    }
+
+    impl Policy<KPGameState> for CountingPolicy {
+        fn action_probabilities(&mut self, gs: &KPGameState) -> ActionVec<f64> {
+            self.single_calls += 1;
+            self.policy.action_probabilities(gs)
+        }
+
+        fn action_probabilities_batch(&mut self, states: &[KPGameState]) -> Vec<ActionVec<f64>> {
+            self.batched_states += states.len();
+            states
+                .iter()
+                .map(|gs| self.policy.action_probabilities(gs))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_exploitability_batches_istates() {
+        let mut policy = CountingPolicy::default();
+        let data = exploitability(|| (KuhnPoker::game().new)(), &mut policy);
+        assert_relative_eq!(data.nash_conv, 0.0);
+
+        // each of the 12 kuhn poker infostates is evaluated once
+        assert_eq!(policy.single_calls, 0);
+        assert_eq!(policy.batched_states, 12);
+    }
+
+    /// Plays uniformly between the moves with the best minimax value
+    #[derive(Default)]
+    struct MinimaxPolicy {
+        /// Value of a board for the player about to move
+        values: HashMap<IStateKey, f64>,
+    }
+
+    impl MinimaxPolicy {
+        fn value(&mut self, gs: &mut TTTGameState) -> f64 {
+            if gs.is_terminal() {
+                return gs.evaluate(gs.cur_player());
+            }
+
+            let key = gs.istate_key(gs.cur_player());
+            if let Some(v) = self.values.get(&key) {
+                return *v;
+            }
+
+            let mut best = f64::NEG_INFINITY;
+            for a in actions!(gs) {
+                gs.apply_action(a);
+                best = best.max(-self.value(gs));
+                gs.undo();
+            }
+            self.values.insert(key, best);
+            best
+        }
+    }
+
+    impl Policy<TTTGameState> for MinimaxPolicy {
+        fn action_probabilities(&mut self, gs: &TTTGameState) -> ActionVec<f64> {
+            let actions = actions!(gs);
+            let mut gs = gs.clone();
+            let values = actions
+                .iter()
+                .map(|&a| {
+                    gs.apply_action(a);
+                    let v = -self.value(&mut gs);
+                    gs.undo();
+                    v
+                })
+                .collect::<Vec<_>>();
+
+            let best = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let num_best = values.iter().filter(|&&v| v == best).count();
+            let mut probs = ActionVec::new(&actions);
+            for (&a, &v) in actions.iter().zip(&values) {
+                if v == best {
+                    probs[a] = 1.0 / num_best as f64;
+                }
+            }
+            probs
+        }
+    }
+
+    #[test]
+    fn test_tictactoe_exploitability() {
+        // perfect play can't be improved on
+        let mut policy = MinimaxPolicy::default();
+        let data = exploitability(TicTacToe::new_state, &mut policy);
+        assert_relative_eq!(data.nash_conv, 0.0);
+    }
 }
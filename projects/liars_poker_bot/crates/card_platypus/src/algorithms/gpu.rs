@@ -0,0 +1,242 @@
+//! Experimental batched regret updates on the gpu
+//!
+//! CFRES updates one infostate at a time as it walks the tree. This prototype
+//! instead collects the updates for many infostates and does the regret
+//! matching and regret / strategy sum updates for all of them in one set of
+//! tensor operations. [`CpuRegretUpdater`] does the same batched updates on
+//! the cpu so the two can be compared, see the `gpu_regret_updates` benchmark.
+
+use burn::{
+    prelude::Backend,
+    tensor::{Tensor, TensorData},
+};
+
+use super::cfres::{InfoState, MAX_ACTIONS_PER_SLOT};
+
+/// Regret or strategy values for each action slot of an infostate
+pub type SlotValues = [f32; MAX_ACTIONS_PER_SLOT];
+
+/// Batched updates for a set of infostates
+pub trait RegretUpdater {
+    /// Returns the regret matching policy for each infostate, unused action
+    /// slots are 0
+    fn regret_matching(&self, infostates: &[InfoState]) -> Vec<SlotValues>;
+
+    /// Discounts the regrets by `discount` and then adds the deltas to the
+    /// regrets and strategy sums of each infostate
+    fn update(
+        &self,
+        infostates: &mut [InfoState],
+        regret_deltas: &[SlotValues],
+        strategy_deltas: &[SlotValues],
+        discount: f32,
+    );
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct CpuRegretUpdater;
+
+impl RegretUpdater for CpuRegretUpdater {
+    fn regret_matching(&self, infostates: &[InfoState]) -> Vec<SlotValues> {
+        infostates
+            .iter()
+            .map(|is| {
+                let n = is.regrets.len();
+                let sum_pos: f32 = is.regrets.iter().map(|r| r.max(0.0)).sum();
+                let mut policy = [0.0; MAX_ACTIONS_PER_SLOT];
+                for (p, r) in policy.iter_mut().zip(is.regrets.iter()) {
+                    *p = if sum_pos > 0.0 {
+                        r.max(0.0) / sum_pos
+                    } else {
+                        1.0 / n as f32
+                    };
+                }
+                policy
+            })
+            .collect()
+    }
+
+    fn update(
+        &self,
+        infostates: &mut [InfoState],
+        regret_deltas: &[SlotValues],
+        strategy_deltas: &[SlotValues],
+        discount: f32,
+    ) {
+        for ((is, rd), sd) in infostates
+            .iter_mut()
+            .zip(regret_deltas)
+            .zip(strategy_deltas)
+        {
+            for (r, d) in is.regrets.iter_mut().zip(rd) {
+                *r = *r * discount + d;
+            }
+            for (s, d) in is.avg_strategy.iter_mut().zip(sd) {
+                *s += d;
+            }
+        }
+    }
+}
+
+/// Does the batched updates with burn tensors on `B`, e.g. `burn::backend::Wgpu`
+pub struct GpuRegretUpdater<B: Backend> {
+    device: B::Device,
+}
+
+impl<B: Backend> GpuRegretUpdater<B> {
+    pub fn new(device: B::Device) -> Self {
+        Self { device }
+    }
+
+    /// Uploads `values` as a `[n, MAX_ACTIONS_PER_SLOT]` tensor
+    fn upload(&self, values: Vec<f32>) -> Tensor<B, 2> {
+        let n = values.len() / MAX_ACTIONS_PER_SLOT;
+        Tensor::from_data(
+            TensorData::new(values, [n, MAX_ACTIONS_PER_SLOT]),
+            &self.device,
+        )
+    }
+
+    /// 1.0 for the action slots in use by each infostate, 0.0 otherwise
+    fn action_mask(&self, infostates: &[InfoState]) -> Tensor<B, 2> {
+        self.upload(
+            infostates
+                .iter()
+                .flat_map(|is| {
+                    (0..MAX_ACTIONS_PER_SLOT).map(|i| (i < is.regrets.len()) as u8 as f32)
+                })
+                .collect(),
+        )
+    }
+
+    fn regrets(&self, infostates: &[InfoState]) -> Tensor<B, 2> {
+        self.upload(infostates.iter().flat_map(|is| pad(&is.regrets)).collect())
+    }
+}
+
+impl<B: Backend> RegretUpdater for GpuRegretUpdater<B> {
+    fn regret_matching(&self, infostates: &[InfoState]) -> Vec<SlotValues> {
+        if infostates.is_empty() {
+            return Vec::new();
+        }
+
+        let mask = self.action_mask(infostates);
+        let positive = self.regrets(infostates).clamp_min(0.0).mul(mask.clone());
+        let sum = positive
+            .clone()
+            .sum_dim(1)
+            .repeat_dim(1, MAX_ACTIONS_PER_SLOT);
+        let uniform = mask
+            .clone()
+            .div(mask.sum_dim(1).repeat_dim(1, MAX_ACTIONS_PER_SLOT));
+
+        // avoid dividing by 0 for the rows that fall back to uniform
+        let no_regret = sum.clone().lower_equal_elem(0.0);
+        let policy = positive
+            .div(sum.clamp_min(f32::MIN_POSITIVE))
+            .mask_where(no_regret, uniform);
+
+        download(policy)
+    }
+
+    fn update(
+        &self,
+        infostates: &mut [InfoState],
+        regret_deltas: &[SlotValues],
+        strategy_deltas: &[SlotValues],
+        discount: f32,
+    ) {
+        if infostates.is_empty() {
+            return;
+        }
+
+        let mask = self.action_mask(infostates);
+        let regrets = self
+            .regrets(infostates)
+            .mul_scalar(discount)
+            .add(self.upload(regret_deltas.concat()).mul(mask.clone()));
+        let strategy = self
+            .upload(
+                infostates
+                    .iter()
+                    .flat_map(|is| pad(&is.avg_strategy))
+                    .collect(),
+            )
+            .add(self.upload(strategy_deltas.concat()).mul(mask));
+
+        for ((is, r), s) in infostates
+            .iter_mut()
+            .zip(download(regrets))
+            .zip(download(strategy))
+        {
+            let n = is.regrets.len();
+            is.regrets.copy_from_slice(&r[..n]);
+            is.avg_strategy.copy_from_slice(&s[..n]);
+        }
+    }
+}
+
+fn pad(values: &[f32]) -> SlotValues {
+    let mut padded = [0.0; MAX_ACTIONS_PER_SLOT];
+    padded[..values.len()].copy_from_slice(values);
+    padded
+}
+
+fn download<B: Backend>(t: Tensor<B, 2>) -> Vec<SlotValues> {
+    t.into_data()
+        .to_vec::<f32>()
+        .expect("failed to read tensor")
+        .chunks_exact(MAX_ACTIONS_PER_SLOT)
+        .map(|c| c.try_into().unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use burn::backend::NdArray;
+    use games::{istate::NormalizedAction, Action};
+
+    use crate::algorithms::cfres::InfoState;
+
+    use super::{CpuRegretUpdater, GpuRegretUpdater, RegretUpdater};
+
+    fn infostate(regrets: &[f32]) -> InfoState {
+        let actions = (0..regrets.len())
+            .map(|a| NormalizedAction::new(Action(a as u8)))
+            .collect();
+        let mut is = InfoState::new(actions);
+        is.regrets.copy_from_slice(regrets);
+        is
+    }
+
+    #[test]
+    fn test_gpu_matches_cpu() {
+        let infostates = vec![
+            infostate(&[1.0, 3.0]),
+            infostate(&[-1.0, -2.0, 0.0]),
+            infostate(&[0.5, -0.5, 2.0, 1.0, 0.0, 4.0]),
+        ];
+        let regret_deltas = vec![[1.0; 6], [0.5; 6], [-1.0; 6]];
+        let strategy_deltas = vec![[0.25; 6], [1.0; 6], [2.0; 6]];
+
+        let cpu = CpuRegretUpdater;
+        // ndarray uses the same tensor code as wgpu without needing a gpu
+        let gpu = GpuRegretUpdater::<NdArray>::new(Default::default());
+
+        let cpu_policy = cpu.regret_matching(&infostates);
+        let gpu_policy = gpu.regret_matching(&infostates);
+        assert_relative_eq!(cpu_policy.concat()[..], gpu_policy.concat()[..]);
+        assert_relative_eq!(cpu_policy[1][..3], [1.0 / 3.0; 3]);
+
+        let mut cpu_infostates = infostates.clone();
+        let mut gpu_infostates = infostates;
+        cpu.update(&mut cpu_infostates, &regret_deltas, &strategy_deltas, 0.5);
+        gpu.update(&mut gpu_infostates, &regret_deltas, &strategy_deltas, 0.5);
+        for (c, g) in cpu_infostates.iter().zip(&gpu_infostates) {
+            assert_eq!(c.regrets.len(), g.regrets.len());
+            assert_relative_eq!(c.regrets[..], g.regrets[..]);
+            assert_relative_eq!(c.avg_strategy[..], g.avg_strategy[..]);
+        }
+    }
+}
@@ -1,6 +1,11 @@
+pub mod abstraction;
 pub mod cfres;
+pub mod distill;
 pub mod exploitability;
+#[cfg(feature = "gpu")]
+pub mod gpu;
 pub mod ismcts;
 pub mod open_hand_solver;
 pub mod pimcts;
 pub mod tabular_best_response;
+pub mod undo_check;
@@ -4,7 +4,6 @@ use std::{
     sync::Arc,
 };
 
-use dashmap::DashMap;
 use games::{
     actions,
     gamestates::euchre::{
@@ -15,17 +14,26 @@ use games::{
 };
 use log::trace;
 
-use crate::{alloc::Pool, collections::actionvec::ActionVec};
+use crate::{
+    alloc::Pool,
+    collections::{actionvec::ActionVec, transpositiontable::TranspositionTable},
+    database::tablebase::default_euchre_tablebase,
+};
 
 use super::ismcts::Evaluator;
 
 pub const DEFAULT_MAX_TT_DEPTH: u8 = 255;
+/// Maximum number of entries kept in the transposition table
+pub const DEFAULT_TT_CAPACITY: usize = 1 << 20;
 
 #[derive(Copy, Clone)]
 pub struct Optimizations<G> {
     pub use_transposition_table: bool,
     pub isometric_transposition: bool,
     pub max_depth_for_tt: u8,
+    pub tt_capacity: usize,
+    /// Evaluate the last move that caused a cutoff at the same depth first
+    pub use_killer_moves: bool,
     /// Function that can filter or re-order moves for evaluation.
     ///
     /// For example, it could filter down to a single move, or it could
@@ -42,6 +50,8 @@ impl<G> Default for Optimizations<G> {
             use_transposition_table: true,
             isometric_transposition: true,
             max_depth_for_tt: DEFAULT_MAX_TT_DEPTH,
+            tt_capacity: DEFAULT_TT_CAPACITY,
+            use_killer_moves: true,
             action_processor: |_: &G, _: &mut Vec<Action>| {},
             can_early_terminate: |_: &G| false,
         }
@@ -54,12 +64,20 @@ impl Optimizations<EuchreGameState> {
             use_transposition_table: true,
             isometric_transposition: true,
             max_depth_for_tt: DEFAULT_MAX_TT_DEPTH,
+            tt_capacity: DEFAULT_TT_CAPACITY,
+            use_killer_moves: true,
             action_processor: process_euchre_actions,
             can_early_terminate: euchre_early_terminate,
         }
     }
 }
 
+/// Exact values for positions near the end of a game, e.g. an endgame tablebase
+pub trait EndgameTable<G>: Send + Sync {
+    /// Returns the value of `gs` for `maximizing_team` if the position is in the table
+    fn evaluate(&self, gs: &G, maximizing_team: Team) -> Option<f64>;
+}
+
 /// Rollout solver that assumes perfect information by playing against open hands
 ///
 /// This is an adaption of a double dummy solver for bridge
@@ -78,6 +96,12 @@ impl<G: Clone> OpenHandSolver<G> {
         }
     }
 
+    /// Use `table` to look up positions rather than searching them
+    pub fn with_endgame_table(mut self, table: Arc<dyn EndgameTable<G>>) -> Self {
+        self.cache.endgame_table = Some(table);
+        self
+    }
+
     pub fn new_without_cache() -> Self {
         let optimizations = Optimizations {
             use_transposition_table: false,
@@ -92,12 +116,18 @@ impl<G: Clone> OpenHandSolver<G> {
 }
 
 impl OpenHandSolver<EuchreGameState> {
+    /// Euchre solver, uses the endgame tablebase from the default location if it exists
     pub fn new_euchre() -> Self {
         let optimizations = Optimizations::new_euchre();
 
-        Self {
+        let solver = Self {
             cache: AlphaBetaCache::new(optimizations.clone()),
             optimizations,
+        };
+
+        match default_euchre_tablebase() {
+            Some(t) => solver.with_endgame_table(t),
+            None => solver,
         }
     }
 }
@@ -221,7 +251,11 @@ type TranspositionKey = (Team, u64);
 #[derive(Clone)]
 struct AlphaBetaCache<G> {
     vec_pool: Pool<Vec<Action>>,
-    transposition_table: Arc<DashMap<TranspositionKey, AlphaBetaResult>>,
+    transposition_table: Arc<TranspositionTable<TranspositionKey, AlphaBetaResult>>,
+    endgame_table: Option<Arc<dyn EndgameTable<G>>>,
+    /// The last action to cause a cutoff at each depth, these are often
+    /// good moves in sibling positions as well
+    killer_moves: Vec<Option<Action>>,
     optimizations: Optimizations<G>,
 }
 
@@ -229,10 +263,28 @@ impl<G> AlphaBetaCache<G> {
     fn new(optimizations: Optimizations<G>) -> Self {
         Self {
             vec_pool: Pool::new(|| Vec::with_capacity(5)),
-            transposition_table: Arc::new(DashMap::new()),
+            transposition_table: Arc::new(TranspositionTable::new(optimizations.tt_capacity)),
+            endgame_table: None,
+            killer_moves: Vec::new(),
             optimizations,
         }
     }
+
+    fn killer_move(&self, depth: u8) -> Option<Action> {
+        self.killer_moves.get(depth as usize).copied().flatten()
+    }
+
+    fn set_killer_move(&mut self, depth: u8, action: Action) {
+        if !self.optimizations.use_killer_moves {
+            return;
+        }
+
+        let depth = depth as usize;
+        if self.killer_moves.len() <= depth {
+            self.killer_moves.resize(depth + 1, None);
+        }
+        self.killer_moves[depth] = Some(action);
+    }
 }
 
 impl<G: GameState> AlphaBetaCache<G> {
@@ -243,10 +295,7 @@ impl<G: GameState> AlphaBetaCache<G> {
 
         let k = self.get_game_key(gs);
         if let Some(k) = k {
-            self.transposition_table
-                .get(&(maximizing_team, k))
-                .as_deref()
-                .copied()
+            self.transposition_table.get(&(maximizing_team, k))
         } else {
             None
         }
@@ -262,7 +311,8 @@ impl<G: GameState> AlphaBetaCache<G> {
         // Check if the game wants to store this state
         let k = self.get_game_key(gs);
         if let Some(k) = k {
-            self.transposition_table.insert((maximizing_team, k), v);
+            self.transposition_table
+                .insert((maximizing_team, k), v, depth);
         }
     }
 
@@ -311,6 +361,14 @@ fn alpha_beta<G: GameState>(
         return (v, None);
     }
 
+    if let Some(v) = cache
+        .endgame_table
+        .as_ref()
+        .and_then(|t| t.evaluate(gs, maximizing_team))
+    {
+        return (v, None);
+    }
+
     // if the game is decided, just play the first action until the game
     // is actually terminal, get the value, get the score, and then undo the actions
     if (optimizations.can_early_terminate)(gs) {
@@ -358,6 +416,12 @@ fn alpha_beta<G: GameState>(
         todo!("add support for chance nodes")
     }
 
+    if let Some(killer) = cache.killer_move(depth) {
+        if let Some(idx) = actions.iter().position(|&a| a == killer) {
+            actions[..=idx].rotate_right(1);
+        }
+    }
+
     let player = gs.cur_player();
     let mut best_action = None;
     let team: Team = player.into();
@@ -383,6 +447,7 @@ fn alpha_beta<G: GameState>(
             }
             alpha = alpha.max(value);
             if value >= beta {
+                cache.set_killer_move(depth, *a);
                 break; // Beta cut-off
             }
         }
@@ -407,6 +472,7 @@ fn alpha_beta<G: GameState>(
             }
             beta = beta.min(value);
             if value <= alpha {
+                cache.set_killer_move(depth, *a);
                 break;
             }
         }
@@ -5,7 +5,7 @@ use itertools::Itertools;
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 
 use crate::{
-    agents::{Agent, Seedable},
+    agents::{decision_difficulty, Agent, Seedable},
     collections::actionvec::ActionVec,
     policy::Policy,
 };
@@ -13,6 +13,10 @@ use rayon::prelude::*;
 
 use super::ismcts::Evaluator;
 
+/// Gap in expected points between the best two actions that halves the
+/// difficulty of a decision, see [`decision_difficulty`]
+pub(super) const DIFFICULTY_VALUE_SCALE: f64 = 0.25;
+
 #[derive(Clone)]
 pub struct PIMCTSBot<G, E> {
     n_rollouts: usize,
@@ -52,6 +56,22 @@ impl<G: GameState + ResampleFromInfoState + Send, E: Evaluator<G> + Clone + Sync
     pub fn reset(&mut self) {
         self.solver.reset();
     }
+
+    /// Returns the expected value of each legal action for the current
+    /// player, every action is evaluated on the same sampled worlds
+    pub fn action_values(&mut self, gs: &G) -> Vec<(Action, f64)> {
+        let actions = actions!(gs);
+        let player = gs.cur_player();
+        let mut worlds = get_worlds(gs, self.n_rollouts, &mut self.rng);
+
+        let mut values = Vec::with_capacity(actions.len());
+        for a in actions {
+            worlds.iter_mut().for_each(|w| w.apply_action(a));
+            values.push((a, self.evaluate_with_worlds(player, worlds.clone())));
+            worlds.iter_mut().for_each(|w| w.undo());
+        }
+        values
+    }
 }
 
 fn evaluate_with_solver<G: GameState + Send, E: Evaluator<G>>(
@@ -95,37 +115,27 @@ impl<G: GameState + ResampleFromInfoState + Send, E: Evaluator<G> + Clone + Sync
     for PIMCTSBot<G, E>
 {
     fn action_probabilities(&mut self, gs: &G) -> ActionVec<f64> {
-        let mut values = Vec::new();
-        let actions = actions!(gs);
-        let player = gs.cur_player();
-
-        // Use the same set of worlds for all evaluations
-        let mut worlds = get_worlds(gs, self.n_rollouts, &mut self.rng);
-
-        for a in actions.clone() {
-            worlds.iter_mut().map(|w| w.apply_action(a)).collect_vec();
-            let v = self.evaluate_with_worlds(player, worlds.clone());
-            values.push(v);
-            worlds.iter_mut().map(|w| w.undo()).collect_vec();
-        }
+        let values = self.action_values(gs);
+        let actions = values.iter().map(|(a, _)| *a).collect_vec();
 
         let mut probs = ActionVec::new(&actions);
-        let index_of_max = values
-            .iter()
-            .enumerate()
-            // since our other algorithms take the first max element, we reverse the order so max by
-            // also returns the first element
-            .rev()
-            .max_by(|(_, a), (_, b)| a.total_cmp(b))
-            .map(|(index, _)| index)
-            .unwrap();
-
-        probs[actions[index_of_max]] = 1.0;
+        probs[best_action(&values)] = 1.0;
 
         probs
     }
 }
 
+pub(super) fn best_action(values: &[(Action, f64)]) -> Action {
+    values
+        .iter()
+        // since our other algorithms take the first max element, we reverse the order so max by
+        // also returns the first element
+        .rev()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(a, _)| *a)
+        .unwrap()
+}
+
 pub(super) fn get_worlds<G: GameState + ResampleFromInfoState>(
     gs: &G,
     n: usize,
@@ -154,18 +164,32 @@ impl<G: GameState + ResampleFromInfoState + Send, E: Evaluator<G> + Clone + Sync
             .unwrap()
             .0
     }
+
+    /// Difficulty from the spread of the action values, close values mean
+    /// the sampled worlds disagree on the best action
+    fn step_with_difficulty(&mut self, s: &G) -> (Action, Option<f64>) {
+        let values = self.action_values(s);
+        let difficulty =
+            decision_difficulty(values.iter().map(|(_, v)| *v), DIFFICULTY_VALUE_SCALE);
+        (best_action(&values), Some(difficulty))
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use games::gamestates::{
-        euchre::EuchreGameState,
-        kuhn_poker::{KPAction, KuhnPoker},
+    use games::{
+        actions,
+        gamestates::{
+            euchre::EuchreGameState,
+            kuhn_poker::{KPAction, KuhnPoker},
+        },
+        GameState,
     };
     use rand::SeedableRng;
 
     use crate::{
+        agents::Agent,
         algorithms::{ismcts::Evaluator, open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot},
         policy::Policy,
     };
@@ -219,4 +243,33 @@ mod tests {
             assert_eq!(policy.action_probabilities(&gs), result);
         }
     }
+
+    #[test]
+    fn test_pimcts_difficulty() {
+        let gs = EuchreGameState::from(
+            "JsQs9hKhAh|TcQcKcThAd|9cJc9sAsQh|KsJh9dJdQd|Kd|PPPT|Ks|JsThAsJh|JdQsAd9c|Qd",
+        );
+
+        let mut policy = PIMCTSBot::new(
+            10,
+            OpenHandSolver::default(),
+            SeedableRng::seed_from_u64(42),
+        );
+        let probs = policy.action_probabilities(&gs);
+        let mut agent = PIMCTSBot::new(
+            10,
+            OpenHandSolver::default(),
+            SeedableRng::seed_from_u64(42),
+        );
+        let (a, difficulty) = agent.step_with_difficulty(&gs);
+        assert_eq!(probs[a], 1.0);
+        assert!((0.0..=1.0).contains(&difficulty.unwrap()));
+
+        // play on until a move is forced
+        let mut gs = gs;
+        while actions!(gs).len() > 1 {
+            gs.apply_action(actions!(gs)[0]);
+        }
+        assert_eq!(agent.step_with_difficulty(&gs).1, Some(0.0));
+    }
 }
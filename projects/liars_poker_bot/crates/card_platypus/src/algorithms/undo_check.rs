@@ -0,0 +1,121 @@
+use games::{Action, GameState};
+
+/// Applies `a` to `gs`, runs `f` on the child and undoes `a`
+///
+/// With the `check-undo` feature this is [`checked_with_action`], so traversals
+/// can be checked for undo bugs while a new game is being developed.
+#[inline]
+pub fn with_action<G: GameState, T>(gs: &mut G, a: Action, f: impl FnOnce(&mut G) -> T) -> T {
+    #[cfg(feature = "check-undo")]
+    {
+        checked_with_action(gs, a, f)
+    }
+
+    #[cfg(not(feature = "check-undo"))]
+    {
+        gs.apply_action(a);
+        let v = f(gs);
+        gs.undo();
+        v
+    }
+}
+
+/// Like [`with_action`], but steps a cloned copy of the state in lockstep and
+/// panics if the undo based state ever differs from it
+///
+/// The child is checked against a clone with `a` applied before and after `f`
+/// runs, so `f` must leave the child as it found it, and the state is checked
+/// against a clone of the parent once `a` is undone. Undo bugs otherwise show
+/// up as silently corrupted regrets long after the node they happened at.
+pub fn checked_with_action<G: GameState, T>(
+    gs: &mut G,
+    a: Action,
+    f: impl FnOnce(&mut G) -> T,
+) -> T {
+    let parent = gs.clone();
+    let mut child = parent.clone();
+    child.apply_action(a);
+
+    gs.apply_action(a);
+    assert_same_state(&child, gs, "applying", a);
+    let v = f(gs);
+    assert_same_state(&child, gs, "traversing the child of", a);
+    gs.undo();
+    assert_same_state(&parent, gs, "undoing", a);
+    v
+}
+
+/// Panics if the states differ in any field or in how they can be played
+fn assert_same_state<G: GameState>(expected: &G, actual: &G, step: &str, a: Action) {
+    // debug output has every field, including the ones only undo touches
+    let expected_debug = format!("{:?}", expected);
+    let actual_debug = format!("{:?}", actual);
+    assert_eq!(
+        expected_debug, actual_debug,
+        "state differs from its clone after {} {:?}\nexpected: {}\nactual:   {}",
+        step, a, expected, actual
+    );
+
+    assert_eq!(
+        expected.key(),
+        actual.key(),
+        "key differs from its clone after {} {:?}",
+        step,
+        a
+    );
+    assert_eq!(expected.is_terminal(), actual.is_terminal());
+    if !expected.is_terminal() {
+        let mut expected_actions = Vec::new();
+        let mut actual_actions = Vec::new();
+        expected.legal_actions(&mut expected_actions);
+        actual.legal_actions(&mut actual_actions);
+        assert_eq!(
+            expected_actions, actual_actions,
+            "legal actions differ from its clone after {} {:?}",
+            step, a
+        );
+        assert_eq!(expected.cur_player(), actual.cur_player());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use games::{
+        actions,
+        gamestates::{euchre::Euchre, kuhn_poker::KuhnPoker},
+        GameState,
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use super::checked_with_action;
+
+    /// Plays random actions to the end of the game, checking every undo
+    fn random_playout<G: GameState>(gs: &mut G, rng: &mut StdRng) -> usize {
+        if gs.is_terminal() {
+            return 0;
+        }
+        let a = *actions!(gs).choose(rng).unwrap();
+        checked_with_action(gs, a, |gs| random_playout(gs, rng) + 1)
+    }
+
+    #[test]
+    fn test_checked_with_action() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            assert!(random_playout(&mut Euchre::new_state(), &mut rng) > 0);
+            assert!(random_playout(&mut KuhnPoker::new_state(), &mut rng) > 0);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "state differs from its clone")]
+    fn test_checked_with_action_catches_missing_undo() {
+        let mut gs = KuhnPoker::new_state();
+        let a = actions!(gs)[0];
+        checked_with_action(&mut gs, a, |gs| {
+            // a child that forgets to undo its own action
+            let b = actions!(gs)[0];
+            gs.apply_action(b);
+        });
+    }
+}
@@ -64,6 +64,14 @@ impl<T: Default + Clone> ActionVec<T> {
     pub fn actions(&self) -> &Vec<Action> {
         &self.actions
     }
+
+    /// Returns the same values indexed by the mapped actions
+    pub(crate) fn map_actions(&self, f: impl Fn(Action) -> Action) -> Self {
+        Self {
+            data: self.data.clone(),
+            actions: self.actions.iter().map(|a| f(*a)).collect(),
+        }
+    }
 }
 
 impl<T: Default + Clone> Index<Action> for ActionVec<T> {
@@ -0,0 +1,1098 @@
+//! Lock-free concurrent hash trie with constant time snapshots
+//!
+//! Implementation of the Ctrie from "Concurrent Tries with Efficient
+//! Non-Blocking Snapshots" (Prokopec et al. 2012):
+//! https://aleksandar-prokopec.com/resources/docs/ctries-snapshot.pdf
+//!
+//! The paper relies on a garbage collector. Here every node is reference
+//! counted and the mutable pointers are [`ArcSwap`]s, so a node is freed once
+//! no other node or in flight operation can reach it.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    fs::{self, OpenOptions},
+    hash::{BuildHasher, Hash},
+    io::{BufReader, BufWriter},
+    marker::PhantomData,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Context;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use serde::{
+    de::{DeserializeOwned, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// Bits of the hash used at each level of the trie
+const W: u32 = 5;
+const HASH_BITS: u32 = 64;
+
+/// Identifies which snapshot an inode belongs to, inodes from an older
+/// generation are copied before they're modified
+type Gen = u64;
+
+static NEXT_GEN: AtomicU64 = AtomicU64::new(0);
+
+fn new_gen() -> Gen {
+    NEXT_GEN.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The operation raced with another one and must start again from the root
+struct Restart;
+
+struct SNode<K, V> {
+    key: K,
+    value: V,
+    hash: u64,
+}
+
+impl<K: Eq, V> SNode<K, V> {
+    fn matches(&self, key: &K, hash: u64) -> bool {
+        self.hash == hash && self.key == *key
+    }
+}
+
+enum Branch<K, V> {
+    I(Arc<INode<K, V>>),
+    S(Arc<SNode<K, V>>),
+}
+
+impl<K, V> Clone for Branch<K, V> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::I(i) => Self::I(i.clone()),
+            Self::S(s) => Self::S(s.clone()),
+        }
+    }
+}
+
+struct CNode<K, V> {
+    bitmap: u32,
+    array: Vec<Branch<K, V>>,
+    gen: Gen,
+}
+
+impl<K, V> CNode<K, V> {
+    fn updated_at(&self, pos: usize, branch: Branch<K, V>, gen: Gen) -> Self {
+        let mut array = self.array.clone();
+        array[pos] = branch;
+        Self {
+            bitmap: self.bitmap,
+            array,
+            gen,
+        }
+    }
+
+    fn removed_at(&self, pos: usize, flag: u32, gen: Gen) -> Self {
+        let mut array = self.array.clone();
+        array.remove(pos);
+        Self {
+            bitmap: self.bitmap ^ flag,
+            array,
+            gen,
+        }
+    }
+
+    fn inserted_at(&self, pos: usize, flag: u32, branch: Branch<K, V>, gen: Gen) -> Self {
+        let mut array = self.array.clone();
+        array.insert(pos, branch);
+        Self {
+            bitmap: self.bitmap | flag,
+            array,
+            gen,
+        }
+    }
+
+    /// Copy of the node with the inodes copied to `gen`
+    fn renewed<S>(&self, gen: Gen, ct: &CTrie<K, V, S>) -> Self {
+        let array = self
+            .array
+            .iter()
+            .map(|b| match b {
+                Branch::I(i) => Branch::I(Arc::new(i.copy_to_gen(gen, ct))),
+                Branch::S(s) => Branch::S(s.clone()),
+            })
+            .collect();
+        Self {
+            bitmap: self.bitmap,
+            array,
+            gen,
+        }
+    }
+
+    /// A cnode with a single key below the root is entombed so the parent
+    /// can pull the key up a level
+    fn into_contracted(self, lev: u32) -> MainKind<K, V> {
+        if self.array.len() == 1 && lev > 0 {
+            if let Branch::S(s) = &self.array[0] {
+                return MainKind::T(s.clone());
+            }
+        }
+        MainKind::C(self)
+    }
+
+    /// Replaces the entombed children with their key
+    fn to_compressed<S>(&self, ct: &CTrie<K, V, S>, lev: u32, gen: Gen) -> MainKind<K, V> {
+        let array = self
+            .array
+            .iter()
+            .map(|b| match b {
+                Branch::I(i) => match &i.gcas_read(ct).kind {
+                    MainKind::T(s) => Branch::S(s.clone()),
+                    _ => Branch::I(i.clone()),
+                },
+                Branch::S(s) => Branch::S(s.clone()),
+            })
+            .collect();
+        Self {
+            bitmap: self.bitmap,
+            array,
+            gen,
+        }
+        .into_contracted(lev)
+    }
+
+    /// Node holding two keys that collided at the level above
+    fn dual(x: Arc<SNode<K, V>>, y: Arc<SNode<K, V>>, lev: u32, gen: Gen) -> MainKind<K, V> {
+        if lev >= HASH_BITS {
+            return MainKind::L(vec![x, y]);
+        }
+
+        let xidx = (x.hash >> lev) & 0x1f;
+        let yidx = (y.hash >> lev) & 0x1f;
+        let bitmap = (1 << xidx) | (1 << yidx);
+        let array = if xidx == yidx {
+            let sub = INode::new(MainNode::new(Self::dual(x, y, lev + W, gen)), gen);
+            vec![Branch::I(Arc::new(sub))]
+        } else if xidx < yidx {
+            vec![Branch::S(x), Branch::S(y)]
+        } else {
+            vec![Branch::S(y), Branch::S(x)]
+        };
+
+        MainKind::C(Self { bitmap, array, gen })
+    }
+}
+
+enum MainKind<K, V> {
+    C(CNode<K, V>),
+    /// Tombed key of a removed cnode, waiting to be compressed into the parent
+    T(Arc<SNode<K, V>>),
+    /// Keys whose hashes fully collide
+    L(Vec<Arc<SNode<K, V>>>),
+}
+
+struct MainNode<K, V> {
+    kind: MainKind<K, V>,
+    /// Set while the node is a GCAS proposal that isn't committed yet
+    prev: ArcSwapOption<Prev<K, V>>,
+}
+
+impl<K, V> MainNode<K, V> {
+    fn new(kind: MainKind<K, V>) -> Arc<Self> {
+        Arc::new(Self {
+            kind,
+            prev: ArcSwapOption::empty(),
+        })
+    }
+}
+
+enum Prev<K, V> {
+    /// The committed value the proposal replaces
+    Main(Arc<MainNode<K, V>>),
+    /// The proposal was aborted and the inode must go back to this value
+    Failed(Arc<MainNode<K, V>>),
+}
+
+struct INode<K, V> {
+    main: ArcSwap<MainNode<K, V>>,
+    gen: Gen,
+}
+
+impl<K, V> INode<K, V> {
+    fn new(main: Arc<MainNode<K, V>>, gen: Gen) -> Self {
+        Self {
+            main: ArcSwap::new(main),
+            gen,
+        }
+    }
+
+    fn copy_to_gen<S>(&self, gen: Gen, ct: &CTrie<K, V, S>) -> Self {
+        Self::new(self.gcas_read(ct), gen)
+    }
+
+    /// Returns the committed main node
+    fn gcas_read<S>(&self, ct: &CTrie<K, V, S>) -> Arc<MainNode<K, V>> {
+        let m = self.main.load_full();
+        if m.prev.load().is_none() {
+            m
+        } else {
+            self.gcas_complete(m, ct)
+        }
+    }
+
+    /// Commits the proposal `m` if the root hasn't changed generation since it
+    /// was made, otherwise rolls it back
+    fn gcas_complete<S>(
+        &self,
+        mut m: Arc<MainNode<K, V>>,
+        ct: &CTrie<K, V, S>,
+    ) -> Arc<MainNode<K, V>> {
+        loop {
+            let prev = match m.prev.load_full() {
+                None => return m,
+                Some(p) => p,
+            };
+
+            let root = ct.read_root(true);
+            match &*prev {
+                Prev::Failed(p) => {
+                    if cas(&self.main, &m, p.clone()) {
+                        return p.clone();
+                    }
+                    m = self.main.load_full();
+                }
+                Prev::Main(p) => {
+                    if root.gen == self.gen && !ct.read_only {
+                        if cas_opt(&m.prev, &Some(prev.clone()), None) {
+                            return m;
+                        }
+                    } else {
+                        let failed = Arc::new(Prev::Failed(p.clone()));
+                        cas_opt(&m.prev, &Some(prev.clone()), Some(failed));
+                        m = self.main.load_full();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Generation compare and swap, only succeeds if the trie wasn't
+    /// snapshotted while the swap was in progress
+    fn gcas<S>(
+        &self,
+        old: &Arc<MainNode<K, V>>,
+        kind: MainKind<K, V>,
+        ct: &CTrie<K, V, S>,
+    ) -> bool {
+        let n = Arc::new(MainNode {
+            kind,
+            prev: ArcSwapOption::from_pointee(Prev::Main(old.clone())),
+        });
+
+        if cas(&self.main, old, n.clone()) {
+            self.gcas_complete(n.clone(), ct);
+            n.prev.load().is_none()
+        } else {
+            false
+        }
+    }
+}
+
+enum Root<K, V> {
+    I(Arc<INode<K, V>>),
+    /// A snapshot in progress, see [`CTrie::rdcss_root`]
+    Desc(RdcssDescriptor<K, V>),
+}
+
+impl<K, V> Root<K, V> {
+    fn inode(&self) -> &Arc<INode<K, V>> {
+        match self {
+            Root::I(i) => i,
+            Root::Desc(_) => panic!("root is a descriptor"),
+        }
+    }
+}
+
+struct RdcssDescriptor<K, V> {
+    old: Arc<Root<K, V>>,
+    expected_main: Arc<MainNode<K, V>>,
+    new: Arc<Root<K, V>>,
+    committed: AtomicBool,
+}
+
+/// Concurrent hash map that is lock-free for all operations, including taking
+/// a consistent snapshot of the whole map in O(1)
+pub struct CTrie<K, V, S = RandomState> {
+    root: ArcSwap<Root<K, V>>,
+    read_only: bool,
+    hash_builder: S,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> CTrie<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Default for CTrie<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Clone> CTrie<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        let gen = new_gen();
+        let empty = MainNode::new(MainKind::C(CNode {
+            bitmap: 0,
+            array: Vec::new(),
+            gen,
+        }));
+        Self::from_root(Arc::new(INode::new(empty, gen)), false, hash_builder)
+    }
+
+    fn from_root(root: Arc<INode<K, V>>, read_only: bool, hash_builder: S) -> Self {
+        Self {
+            root: ArcSwap::from_pointee(Root::I(root)),
+            read_only,
+            hash_builder,
+        }
+    }
+
+    /// Inserts the value, returning the previous value for the key
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        assert!(!self.read_only, "can't insert into a read only snapshot");
+        let hash = self.hash_builder.hash_one(&key);
+        let sn = Arc::new(SNode { key, value, hash });
+        loop {
+            let root = self.read_root(false);
+            if let Ok(prev) = self.rec_insert(&root, &sn, 0, None, root.gen) {
+                return prev;
+            }
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let hash = self.hash_builder.hash_one(key);
+        loop {
+            let root = self.read_root(false);
+            if let Ok(v) = self.rec_lookup(&root, key, hash, 0, None, root.gen) {
+                return v;
+            }
+        }
+    }
+
+    /// Removes the key, returning its value
+    pub fn remove(&self, key: &K) -> Option<V> {
+        assert!(!self.read_only, "can't remove from a read only snapshot");
+        let hash = self.hash_builder.hash_one(key);
+        loop {
+            let root = self.read_root(false);
+            if let Ok(v) = self.rec_remove(&root, key, hash, 0, None, root.gen) {
+                return v;
+            }
+        }
+    }
+
+    /// Returns a writable copy of the map, changes to either aren't visible
+    /// in the other
+    ///
+    /// The nodes are shared and only copied lazily as each map writes to them.
+    pub fn snapshot(&self) -> Self {
+        if self.read_only {
+            let root = self.read_root(false);
+            let copy = root.copy_to_gen(new_gen(), self);
+            return Self::from_root(Arc::new(copy), false, self.hash_builder.clone());
+        }
+
+        loop {
+            let root = self.read_root_full(false);
+            let inode = root.inode();
+            let expected_main = inode.gcas_read(self);
+            let new_root = Arc::new(inode.copy_to_gen(new_gen(), self));
+            if self.rdcss_root(&root, expected_main, new_root) {
+                let copy = inode.copy_to_gen(new_gen(), self);
+                return Self::from_root(Arc::new(copy), false, self.hash_builder.clone());
+            }
+        }
+    }
+
+    /// Returns a read only copy of the map, cheaper than [`CTrie::snapshot`]
+    /// as the snapshot never needs to copy nodes
+    pub fn read_only_snapshot(&self) -> Self {
+        loop {
+            let root = self.read_root_full(false);
+            let inode = root.inode();
+            if self.read_only {
+                return Self::from_root(inode.clone(), true, self.hash_builder.clone());
+            }
+
+            let expected_main = inode.gcas_read(self);
+            let new_root = Arc::new(inode.copy_to_gen(new_gen(), self));
+            if self.rdcss_root(&root, expected_main, new_root) {
+                return Self::from_root(inode.clone(), true, self.hash_builder.clone());
+            }
+        }
+    }
+
+    /// Iterates over a read only snapshot of the map, so concurrent writes
+    /// don't affect the iteration
+    pub fn iter(&self) -> Iter<K, V, S> {
+        let trie = self.read_only_snapshot();
+        let main = trie.read_root(false).gcas_read(&trie);
+        Iter {
+            trie,
+            stack: vec![(main, 0)],
+        }
+    }
+
+    /// Number of keys in the map, this iterates over a snapshot so is O(n)
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Saves a snapshot of the map to `path`, the map can keep being modified
+    /// while the snapshot is written
+    ///
+    /// The snapshot is written to a temporary file and then renamed over
+    /// `path`, so a crash while saving leaves the previous checkpoint intact.
+    pub fn checkpoint(&self, path: &Path) -> anyhow::Result<()>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        let tmp = path.with_extension("tmp");
+        let f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)
+            .context("failed to create checkpoint")?;
+        let mut writer = BufWriter::new(f);
+        rmp_serde::encode::write(&mut writer, &self.read_only_snapshot())?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp, path).context("failed to replace checkpoint")?;
+        Ok(())
+    }
+
+    pub fn load_checkpoint(path: &Path) -> anyhow::Result<Self>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+        S: Default,
+    {
+        let f = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .context("failed to open checkpoint")?;
+        Ok(rmp_serde::from_read(BufReader::new(f))?)
+    }
+
+    fn rec_insert(
+        &self,
+        inode: &Arc<INode<K, V>>,
+        sn: &Arc<SNode<K, V>>,
+        lev: u32,
+        parent: Option<&Arc<INode<K, V>>>,
+        startgen: Gen,
+    ) -> Result<Option<V>, Restart> {
+        let m = inode.gcas_read(self);
+        match &m.kind {
+            MainKind::C(cn) => {
+                let (flag, pos) = flag_pos(sn.hash, lev, cn.bitmap);
+                if cn.bitmap & flag == 0 {
+                    let renewed;
+                    let rn = if cn.gen == inode.gen {
+                        cn
+                    } else {
+                        renewed = cn.renewed(inode.gen, self);
+                        &renewed
+                    };
+                    let ncn = rn.inserted_at(pos, flag, Branch::S(sn.clone()), inode.gen);
+                    return match inode.gcas(&m, MainKind::C(ncn), self) {
+                        true => Ok(None),
+                        false => Err(Restart),
+                    };
+                }
+
+                match &cn.array[pos] {
+                    Branch::I(sub) => {
+                        if sub.gen == startgen {
+                            self.rec_insert(sub, sn, lev + W, Some(inode), startgen)
+                        } else if inode.gcas(&m, MainKind::C(cn.renewed(startgen, self)), self) {
+                            self.rec_insert(inode, sn, lev, parent, startgen)
+                        } else {
+                            Err(Restart)
+                        }
+                    }
+                    Branch::S(old) if old.matches(&sn.key, sn.hash) => {
+                        let ncn = cn.updated_at(pos, Branch::S(sn.clone()), inode.gen);
+                        match inode.gcas(&m, MainKind::C(ncn), self) {
+                            true => Ok(Some(old.value.clone())),
+                            false => Err(Restart),
+                        }
+                    }
+                    Branch::S(old) => {
+                        let renewed;
+                        let rn = if cn.gen == inode.gen {
+                            cn
+                        } else {
+                            renewed = cn.renewed(inode.gen, self);
+                            &renewed
+                        };
+                        let dual = CNode::dual(old.clone(), sn.clone(), lev + W, inode.gen);
+                        let sub = INode::new(MainNode::new(dual), inode.gen);
+                        let ncn = rn.updated_at(pos, Branch::I(Arc::new(sub)), inode.gen);
+                        match inode.gcas(&m, MainKind::C(ncn), self) {
+                            true => Ok(None),
+                            false => Err(Restart),
+                        }
+                    }
+                }
+            }
+            MainKind::T(_) => {
+                self.clean(parent, lev);
+                Err(Restart)
+            }
+            MainKind::L(entries) => {
+                let mut entries = entries.clone();
+                let prev = match entries.iter().position(|e| e.key == sn.key) {
+                    Some(i) => {
+                        let prev = entries[i].value.clone();
+                        entries[i] = sn.clone();
+                        Some(prev)
+                    }
+                    None => {
+                        entries.push(sn.clone());
+                        None
+                    }
+                };
+                match inode.gcas(&m, MainKind::L(entries), self) {
+                    true => Ok(prev),
+                    false => Err(Restart),
+                }
+            }
+        }
+    }
+
+    fn rec_lookup(
+        &self,
+        inode: &Arc<INode<K, V>>,
+        key: &K,
+        hash: u64,
+        lev: u32,
+        parent: Option<&Arc<INode<K, V>>>,
+        startgen: Gen,
+    ) -> Result<Option<V>, Restart> {
+        let m = inode.gcas_read(self);
+        match &m.kind {
+            MainKind::C(cn) => {
+                let (flag, pos) = flag_pos(hash, lev, cn.bitmap);
+                if cn.bitmap & flag == 0 {
+                    return Ok(None);
+                }
+
+                match &cn.array[pos] {
+                    Branch::I(sub) => {
+                        if self.read_only || sub.gen == startgen {
+                            self.rec_lookup(sub, key, hash, lev + W, Some(inode), startgen)
+                        } else if inode.gcas(&m, MainKind::C(cn.renewed(startgen, self)), self) {
+                            self.rec_lookup(inode, key, hash, lev, parent, startgen)
+                        } else {
+                            Err(Restart)
+                        }
+                    }
+                    Branch::S(sn) => Ok(sn.matches(key, hash).then(|| sn.value.clone())),
+                }
+            }
+            MainKind::T(sn) => {
+                if self.read_only {
+                    Ok(sn.matches(key, hash).then(|| sn.value.clone()))
+                } else {
+                    self.clean(parent, lev);
+                    Err(Restart)
+                }
+            }
+            MainKind::L(entries) => Ok(entries
+                .iter()
+                .find(|e| e.key == *key)
+                .map(|e| e.value.clone())),
+        }
+    }
+
+    fn rec_remove(
+        &self,
+        inode: &Arc<INode<K, V>>,
+        key: &K,
+        hash: u64,
+        lev: u32,
+        parent: Option<&Arc<INode<K, V>>>,
+        startgen: Gen,
+    ) -> Result<Option<V>, Restart> {
+        let m = inode.gcas_read(self);
+        match &m.kind {
+            MainKind::C(cn) => {
+                let (flag, pos) = flag_pos(hash, lev, cn.bitmap);
+                if cn.bitmap & flag == 0 {
+                    return Ok(None);
+                }
+
+                let res = match &cn.array[pos] {
+                    Branch::I(sub) => {
+                        if sub.gen == startgen {
+                            self.rec_remove(sub, key, hash, lev + W, Some(inode), startgen)
+                        } else if inode.gcas(&m, MainKind::C(cn.renewed(startgen, self)), self) {
+                            self.rec_remove(inode, key, hash, lev, parent, startgen)
+                        } else {
+                            Err(Restart)
+                        }
+                    }
+                    Branch::S(sn) if sn.matches(key, hash) => {
+                        let ncn = cn.removed_at(pos, flag, inode.gen).into_contracted(lev);
+                        match inode.gcas(&m, ncn, self) {
+                            true => Ok(Some(sn.value.clone())),
+                            false => Err(Restart),
+                        }
+                    }
+                    Branch::S(_) => Ok(None),
+                };
+
+                // the root is never entombed
+                if let (Ok(Some(_)), Some(parent)) = (&res, parent) {
+                    if let MainKind::T(tn) = &inode.gcas_read(self).kind {
+                        self.clean_parent(parent, inode, tn, hash, lev, startgen);
+                    }
+                }
+
+                res
+            }
+            MainKind::T(_) => {
+                self.clean(parent, lev);
+                Err(Restart)
+            }
+            MainKind::L(entries) => {
+                let Some(i) = entries.iter().position(|e| e.key == *key) else {
+                    return Ok(None);
+                };
+
+                let mut entries = entries.clone();
+                let removed = entries.remove(i);
+                let kind = match entries.len() {
+                    1 => MainKind::T(entries.pop().unwrap()),
+                    _ => MainKind::L(entries),
+                };
+                match inode.gcas(&m, kind, self) {
+                    true => Ok(Some(removed.value.clone())),
+                    false => Err(Restart),
+                }
+            }
+        }
+    }
+
+    /// Compresses the parent of an entombed node at `lev`
+    fn clean(&self, parent: Option<&Arc<INode<K, V>>>, lev: u32) {
+        let Some(parent) = parent else {
+            return;
+        };
+
+        let m = parent.gcas_read(self);
+        if let MainKind::C(cn) = &m.kind {
+            let compressed = cn.to_compressed(self, lev - W, parent.gen);
+            parent.gcas(&m, compressed, self);
+        }
+    }
+
+    /// Replaces the entombed `inode` in `parent` with its key
+    fn clean_parent(
+        &self,
+        parent: &Arc<INode<K, V>>,
+        inode: &Arc<INode<K, V>>,
+        tn: &Arc<SNode<K, V>>,
+        hash: u64,
+        lev: u32,
+        startgen: Gen,
+    ) {
+        loop {
+            let pm = parent.gcas_read(self);
+            let MainKind::C(cn) = &pm.kind else {
+                return;
+            };
+
+            let (flag, pos) = flag_pos(hash, lev - W, cn.bitmap);
+            if cn.bitmap & flag == 0 {
+                return;
+            }
+            match &cn.array[pos] {
+                Branch::I(sub) if Arc::ptr_eq(sub, inode) => {}
+                // somebody else already cleaned it up
+                _ => return,
+            }
+
+            let ncn = cn
+                .updated_at(pos, Branch::S(tn.clone()), parent.gen)
+                .into_contracted(lev - W);
+            if parent.gcas(&pm, ncn, self) || self.read_root(false).gen != startgen {
+                return;
+            }
+        }
+    }
+}
+
+impl<K, V, S> CTrie<K, V, S> {
+    fn read_root(&self, abort: bool) -> Arc<INode<K, V>> {
+        self.read_root_full(abort).inode().clone()
+    }
+
+    /// Returns the root, completing a snapshot in progress if there is one
+    fn read_root_full(&self, abort: bool) -> Arc<Root<K, V>> {
+        let root = self.root.load_full();
+        match &*root {
+            Root::I(_) => root,
+            Root::Desc(_) => self.rdcss_complete(abort),
+        }
+    }
+
+    /// Swaps the root from `old` to `new` only if the main node of `old` is
+    /// still `expected_main`, so no update to the root is lost by a snapshot
+    fn rdcss_root(
+        &self,
+        old: &Arc<Root<K, V>>,
+        expected_main: Arc<MainNode<K, V>>,
+        new: Arc<INode<K, V>>,
+    ) -> bool {
+        let desc = Arc::new(Root::Desc(RdcssDescriptor {
+            old: old.clone(),
+            expected_main,
+            new: Arc::new(Root::I(new)),
+            committed: AtomicBool::new(false),
+        }));
+
+        if !cas(&self.root, old, desc.clone()) {
+            return false;
+        }
+
+        self.rdcss_complete(false);
+        match &*desc {
+            Root::Desc(d) => d.committed.load(Ordering::Acquire),
+            Root::I(_) => unreachable!(),
+        }
+    }
+
+    fn rdcss_complete(&self, abort: bool) -> Arc<Root<K, V>> {
+        loop {
+            let root = self.root.load_full();
+            let desc = match &*root {
+                Root::I(_) => return root,
+                Root::Desc(d) => d,
+            };
+
+            if abort {
+                if cas(&self.root, &root, desc.old.clone()) {
+                    return desc.old.clone();
+                }
+                continue;
+            }
+
+            let old_main = desc.old.inode().gcas_read(self);
+            if Arc::ptr_eq(&old_main, &desc.expected_main) {
+                if cas(&self.root, &root, desc.new.clone()) {
+                    desc.committed.store(true, Ordering::Release);
+                    return desc.new.clone();
+                }
+            } else if cas(&self.root, &root, desc.old.clone()) {
+                return desc.old.clone();
+            }
+        }
+    }
+}
+
+pub struct Iter<K, V, S> {
+    /// The read only snapshot being iterated
+    trie: CTrie<K, V, S>,
+    /// Main nodes being visited and the index of the next entry in each
+    stack: Vec<(Arc<MainNode<K, V>>, usize)>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Clone> Iterator for Iter<K, V, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (m, i) = self.stack.last_mut()?;
+            let idx = *i;
+            *i += 1;
+
+            let sn = match &m.kind {
+                MainKind::C(cn) => match cn.array.get(idx) {
+                    Some(Branch::S(sn)) => sn.clone(),
+                    Some(Branch::I(sub)) => {
+                        let main = sub.gcas_read(&self.trie);
+                        self.stack.push((main, 0));
+                        continue;
+                    }
+                    None => {
+                        self.stack.pop();
+                        continue;
+                    }
+                },
+                MainKind::T(sn) if idx == 0 => sn.clone(),
+                MainKind::L(entries) if idx < entries.len() => entries[idx].clone(),
+                MainKind::T(_) | MainKind::L(_) => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            return Some((sn.key.clone(), sn.value.clone()));
+        }
+    }
+}
+
+/// Serializes a read only snapshot of the map, so writes can continue while
+/// it's serialized
+impl<K, V, S> Serialize for CTrie<K, V, S>
+where
+    K: Hash + Eq + Clone + Serialize,
+    V: Clone + Serialize,
+    S: BuildHasher + Clone,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let snapshot = self.read_only_snapshot();
+        let mut map = serializer.serialize_map(Some(snapshot.len()))?;
+        for (k, v) in snapshot.iter() {
+            map.serialize_entry(&k, &v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for CTrie<K, V, S>
+where
+    K: Hash + Eq + Clone + Deserialize<'de>,
+    V: Clone + Deserialize<'de>,
+    S: BuildHasher + Clone + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(CTrieVisitor(PhantomData))
+    }
+}
+
+struct CTrieVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+impl<'de, K, V, S> Visitor<'de> for CTrieVisitor<K, V, S>
+where
+    K: Hash + Eq + Clone + Deserialize<'de>,
+    V: Clone + Deserialize<'de>,
+    S: BuildHasher + Clone + Default,
+{
+    type Value = CTrie<K, V, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let ct = CTrie::with_hasher(S::default());
+        while let Some((k, v)) = access.next_entry()? {
+            ct.insert(k, v);
+        }
+        Ok(ct)
+    }
+}
+
+/// Returns the flag for the hash at `lev` and its position in the cnode array
+fn flag_pos(hash: u64, lev: u32, bitmap: u32) -> (u32, usize) {
+    let idx = (hash >> lev) & 0x1f;
+    let flag = 1 << idx;
+    (flag, (bitmap & (flag - 1)).count_ones() as usize)
+}
+
+fn cas<T>(a: &ArcSwap<T>, current: &Arc<T>, new: Arc<T>) -> bool {
+    let prev = a.compare_and_swap(current, new);
+    Arc::ptr_eq(&prev, current)
+}
+
+fn cas_opt<T>(a: &ArcSwapOption<T>, current: &Option<Arc<T>>, new: Option<Arc<T>>) -> bool {
+    let prev = a.compare_and_swap(current, new);
+    match (&*prev, current) {
+        (None, None) => true,
+        (Some(p), Some(c)) => Arc::ptr_eq(p, c),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        hash::{BuildHasherDefault, Hasher},
+        sync::Arc,
+        thread,
+    };
+
+    use games::{istate::IStateKey, Action};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::CTrie;
+
+    /// Only keeps the lowest 2 bits of the key so most keys collide
+    #[derive(Default)]
+    struct CollidingHasher(u64);
+
+    impl Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            self.0 & 0b11
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for b in bytes {
+                self.0 = self.0.wrapping_mul(31).wrapping_add(*b as u64);
+            }
+        }
+    }
+
+    fn key(i: usize) -> IStateKey {
+        let mut k = IStateKey::default();
+        for b in (i as u32).to_le_bytes() {
+            k.push(Action(b));
+        }
+        k
+    }
+
+    #[test]
+    fn test_ctrie_insert_get_remove() {
+        let ct = CTrie::new();
+        let mut expected = HashMap::new();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+
+        for _ in 0..20_000 {
+            let k = key(rng.gen_range(0..5_000));
+            if rng.gen_bool(0.3) {
+                assert_eq!(ct.remove(&k), expected.remove(&k));
+            } else {
+                let v: u32 = rng.gen();
+                assert_eq!(ct.insert(k, v), expected.insert(k, v));
+            }
+        }
+
+        for i in 0..5_000 {
+            assert_eq!(ct.get(&key(i)), expected.get(&key(i)).copied());
+        }
+        assert_eq!(ct.len(), expected.len());
+    }
+
+    #[test]
+    fn test_ctrie_hash_collisions() {
+        let ct = CTrie::with_hasher(BuildHasherDefault::<CollidingHasher>::default());
+        for i in 0..100 {
+            assert_eq!(ct.insert(i, i), None);
+        }
+        assert_eq!(ct.insert(7, 70), Some(7));
+        assert_eq!(ct.get(&7), Some(70));
+        assert_eq!(ct.len(), 100);
+
+        for i in 0..100 {
+            assert!(ct.remove(&i).is_some());
+        }
+        assert!(ct.is_empty());
+        assert_eq!(ct.get(&7), None);
+    }
+
+    #[test]
+    fn test_ctrie_snapshot() {
+        let ct = CTrie::new();
+        for i in 0..1_000 {
+            ct.insert(i, i);
+        }
+
+        let snapshot = ct.snapshot();
+        let read_only = ct.read_only_snapshot();
+        for i in 0..1_000 {
+            ct.insert(i, i + 1);
+        }
+        ct.remove(&0);
+        snapshot.insert(0, 42);
+
+        assert_eq!(ct.get(&0), None);
+        assert_eq!(ct.get(&1), Some(2));
+        assert_eq!(snapshot.get(&0), Some(42));
+        assert_eq!(snapshot.get(&1), Some(1));
+        assert_eq!(read_only.get(&0), Some(0));
+
+        let mut values = read_only.iter().collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, (0..1_000).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_ctrie_concurrent() {
+        const THREADS: usize = 16;
+        const KEYS: usize = 2_000;
+
+        let ct = Arc::new(CTrie::new());
+        let handles = (0..THREADS)
+            .map(|t| {
+                let ct = ct.clone();
+                thread::spawn(move || {
+                    // every thread updates the same keys to maximize contention
+                    for i in 0..KEYS {
+                        let prev = ct.get(&i).unwrap_or(0);
+                        ct.insert(i, prev.max(t));
+                        if i % 3 == t % 3 {
+                            ct.remove(&(KEYS + i));
+                        } else {
+                            ct.insert(KEYS + i, t);
+                        }
+                    }
+
+                    // snapshots taken during the writes see a consistent map
+                    let snapshot = ct.read_only_snapshot();
+                    assert_eq!(snapshot.iter().count(), snapshot.len());
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        for i in 0..KEYS {
+            assert!(ct.get(&i).is_some());
+        }
+        assert!(ct.len() >= KEYS);
+    }
+
+    #[test]
+    fn test_ctrie_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        let ct = Arc::new(CTrie::new());
+
+        // keys are inserted in order, so a consistent snapshot is always a
+        // prefix of the keys
+        let writer = {
+            let ct = ct.clone();
+            thread::spawn(move || {
+                for i in 0..20_000 {
+                    ct.insert(key(i), i);
+                }
+            })
+        };
+
+        for _ in 0..5 {
+            ct.checkpoint(&path).unwrap();
+            let loaded: CTrie<IStateKey, usize> = CTrie::load_checkpoint(&path).unwrap();
+            let n = loaded.len();
+            for i in 0..n {
+                assert_eq!(loaded.get(&key(i)), Some(i));
+            }
+        }
+
+        writer.join().unwrap();
+        ct.checkpoint(&path).unwrap();
+        let loaded: CTrie<IStateKey, usize> = CTrie::load_checkpoint(&path).unwrap();
+        assert_eq!(loaded.len(), 20_000);
+    }
+}
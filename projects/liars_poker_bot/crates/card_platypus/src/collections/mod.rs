@@ -2,5 +2,8 @@ pub mod actionlist;
 pub mod actiontrie;
 pub mod actionvec;
 pub mod bitarray;
+pub mod ctrie;
 pub mod diskbackedvec;
 pub mod mmapvec;
+pub mod strategywindow;
+pub mod transpositiontable;
@@ -0,0 +1,341 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use anyhow::Context;
+use games::istate::IStateKey;
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::cfres::MAX_ACTIONS_PER_SLOT;
+
+/// Number of slices the window is split into
+const NUM_SLICES: usize = 8;
+
+/// Infostates a window keeps by default, past this the ones updated least
+/// recently are dropped and play falls back to their full average strategy
+pub const DEFAULT_MAX_INFOSTATES: usize = 1 << 20;
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+struct WindowEntry {
+    /// Slice each bucket is accumulating for
+    slices: [usize; NUM_SLICES],
+    sums: [[f32; MAX_ACTIONS_PER_SLOT]; NUM_SLICES],
+}
+
+impl WindowEntry {
+    /// Most recent slice the infostate was updated in
+    fn newest(&self) -> usize {
+        self.slices.iter().copied().max().unwrap_or_default()
+    }
+}
+
+/// Strategy sums over only the most recent `window` iterations
+///
+/// The window is split into slices of iterations and each infostate keeps a
+/// ring buffer with the strategy sums of each slice. Buckets are tagged with
+/// the slice they hold so stale buckets can be reset lazily on the next write
+/// rather than touching every infostate when a slice ends.
+///
+/// Training threads add to their own [`WindowShard`] and merge it in at the
+/// end of each epoch, so they don't contend for the window on every update.
+///
+/// The effective window is between `window - window / NUM_SLICES` and `window`
+/// iterations depending on how far into the current slice training is.
+#[derive(Serialize, Deserialize)]
+pub struct StrategyWindow {
+    window: usize,
+    iteration: usize,
+    /// Not saved, set when the window is created or loaded
+    #[serde(skip)]
+    max_infostates: usize,
+    entries: FxHashMap<IStateKey, WindowEntry>,
+}
+
+impl StrategyWindow {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "window must be at least 1 iteration");
+        Self {
+            window,
+            iteration: 0,
+            max_infostates: DEFAULT_MAX_INFOSTATES,
+            entries: FxHashMap::default(),
+        }
+    }
+
+    /// Keep at most `max_infostates` infostates, see [`DEFAULT_MAX_INFOSTATES`]
+    pub fn with_max_infostates(mut self, max_infostates: usize) -> Self {
+        self.max_infostates = max_infostates;
+        self
+    }
+
+    /// Loads the window from `path` if it exists and covers the same number
+    /// of iterations, otherwise starts a new one
+    pub fn load_or_new(path: &Path, window: usize) -> Self {
+        match Self::load(path) {
+            Ok(w) if w.window == window => w,
+            _ => Self::new(window),
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let f = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .context("failed to open strategy window")?;
+        let window: Self = rmp_serde::from_read(BufReader::new(f))?;
+        Ok(window.with_max_infostates(DEFAULT_MAX_INFOSTATES))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        rmp_serde::encode::write(&mut BufWriter::new(f), self)?;
+        Ok(())
+    }
+
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    /// An empty shard for a training thread to add to, its iterations are
+    /// counted from the window's current one
+    pub fn shard(&self) -> WindowShard {
+        WindowShard {
+            start: self.iteration,
+            slice_len: self.slice_len(),
+            sums: FxHashMap::default(),
+        }
+    }
+
+    /// Adds the sums of a shard, leaving it empty, and moves the window to
+    /// the shard's `iteration` if it's ahead
+    ///
+    /// Sums for slices that have already left the window are dropped.
+    pub fn merge(&mut self, shard: &mut WindowShard, iteration: usize) {
+        self.iteration = self.iteration.max(shard.start + iteration);
+        for ((key, slice), sums) in shard.sums.drain() {
+            let bucket = slice % NUM_SLICES;
+            let entry = self.entries.entry(key).or_default();
+            if entry.slices[bucket] > slice {
+                continue;
+            }
+            if entry.slices[bucket] < slice {
+                entry.slices[bucket] = slice;
+                entry.sums[bucket] = [0.0; MAX_ACTIONS_PER_SLOT];
+            }
+            entry.sums[bucket]
+                .iter_mut()
+                .zip(sums)
+                .for_each(|(a, b)| *a += b);
+        }
+
+        if self.entries.len() > self.max_infostates {
+            self.shrink();
+        }
+    }
+
+    /// Returns the strategy sums accumulated in the window, or None if the
+    /// infostate wasn't updated in the window
+    pub fn strategy_sums(&self, key: &IStateKey) -> Option<[f32; MAX_ACTIONS_PER_SLOT]> {
+        let slice = self.slice();
+        let entry = self.entries.get(key)?;
+
+        let mut sums = [0.0; MAX_ACTIONS_PER_SLOT];
+        for (s, bucket) in entry.slices.iter().zip(entry.sums.iter()) {
+            if slice - s < NUM_SLICES {
+                sums.iter_mut().zip(bucket).for_each(|(a, b)| *a += b);
+            }
+        }
+
+        if sums.iter().sum::<f32>() > 0.0 {
+            Some(sums)
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops the infostates that haven't been updated in the window, then
+    /// the least recently updated ones until there are at most
+    /// `max_infostates`
+    fn shrink(&mut self) {
+        let slice = self.slice();
+        self.entries
+            .retain(|_, e| slice.saturating_sub(e.newest()) < NUM_SLICES);
+
+        let Some(mut excess) = self.entries.len().checked_sub(self.max_infostates) else {
+            return;
+        };
+        if excess == 0 {
+            return;
+        }
+        let mut newest = self.entries.values().map(WindowEntry::newest).collect_vec();
+        let (_, &mut cutoff, _) = newest.select_nth_unstable(excess - 1);
+        self.entries.retain(|_, e| {
+            let keep = e.newest() >= cutoff;
+            excess -= usize::from(!keep);
+            keep
+        });
+        // ties at the cutoff, any of them will do
+        self.entries.retain(|_, e| {
+            let keep = excess == 0 || e.newest() > cutoff;
+            excess -= usize::from(!keep);
+            keep
+        });
+    }
+
+    fn slice_len(&self) -> usize {
+        (self.window / NUM_SLICES).max(1)
+    }
+
+    fn slice(&self) -> usize {
+        self.iteration / self.slice_len()
+    }
+}
+
+/// Strategy sums a training thread added since it last merged into the
+/// [`StrategyWindow`], by infostate and slice
+#[derive(Clone)]
+pub struct WindowShard {
+    /// Window's iteration when the shard was made, a restored window carries
+    /// on from where it was saved
+    start: usize,
+    slice_len: usize,
+    sums: FxHashMap<(IStateKey, usize), [f32; MAX_ACTIONS_PER_SLOT]>,
+}
+
+impl WindowShard {
+    /// Adds `amount` to the strategy sum of the action at `index`, for the
+    /// slice `iteration` is in
+    pub fn add(&mut self, key: IStateKey, iteration: usize, index: usize, amount: f32) {
+        let slice = (self.start + iteration) / self.slice_len;
+        self.sums
+            .entry((key, slice))
+            .or_insert([0.0; MAX_ACTIONS_PER_SLOT])[index] += amount;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use games::{istate::IStateKey, Action};
+
+    use super::{StrategyWindow, NUM_SLICES};
+
+    #[test]
+    fn test_strategy_window_forgets_old_iterations() {
+        let key = IStateKey::default();
+        let mut window = StrategyWindow::new(NUM_SLICES * 10);
+        let mut shard = window.shard();
+
+        shard.add(key, 0, 0, 1.0);
+        window.merge(&mut shard, 0);
+        assert_eq!(
+            window.strategy_sums(&key),
+            Some([1.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+        );
+
+        // still in the window
+        let mut iteration = (NUM_SLICES - 1) * 10;
+        shard.add(key, iteration, 1, 2.0);
+        window.merge(&mut shard, iteration);
+        assert_eq!(
+            window.strategy_sums(&key),
+            Some([1.0, 2.0, 0.0, 0.0, 0.0, 0.0])
+        );
+
+        // first slice has left the window
+        iteration += 10;
+        window.merge(&mut shard, iteration);
+        assert_eq!(
+            window.strategy_sums(&key),
+            Some([0.0, 2.0, 0.0, 0.0, 0.0, 0.0])
+        );
+
+        // writing to a reused bucket clears the old sums
+        shard.add(key, iteration, 1, 1.0);
+        window.merge(&mut shard, iteration);
+        assert_eq!(
+            window.strategy_sums(&key),
+            Some([0.0, 3.0, 0.0, 0.0, 0.0, 0.0])
+        );
+
+        iteration += NUM_SLICES * 10;
+        window.merge(&mut shard, iteration);
+        assert_eq!(window.strategy_sums(&key), None);
+    }
+
+    #[test]
+    fn test_strategy_window_merges_shards() {
+        let key = IStateKey::default();
+        let mut window = StrategyWindow::new(NUM_SLICES * 10);
+        let mut shards = [window.shard(), window.shard()];
+
+        shards[0].add(key, 5, 0, 1.0);
+        shards[1].add(key, 3, 0, 2.0);
+        shards[1].add(key, 25, 1, 1.0);
+        for shard in shards.iter_mut() {
+            window.merge(shard, 25);
+        }
+        assert_eq!(
+            window.strategy_sums(&key),
+            Some([3.0, 1.0, 0.0, 0.0, 0.0, 0.0])
+        );
+
+        // a shard that merges late doesn't bring back a slice that has left
+        shards[0].add(key, 5, 0, 1.0);
+        window.merge(&mut shards[1], 25 + NUM_SLICES * 10);
+        window.merge(&mut shards[0], 30);
+        assert_eq!(window.strategy_sums(&key), None);
+        assert_eq!(window.iteration(), 25 + NUM_SLICES * 10);
+
+        // shards of a restored window carry on from its iteration
+        let mut shard = window.shard();
+        shard.add(key, 0, 2, 1.0);
+        window.merge(&mut shard, 0);
+        assert_eq!(
+            window.strategy_sums(&key),
+            Some([0.0, 0.0, 1.0, 0.0, 0.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn test_strategy_window_max_infostates() {
+        let key = |i: u8| {
+            let mut key = IStateKey::default();
+            key.push(Action(i));
+            key
+        };
+        let mut window = StrategyWindow::new(NUM_SLICES * 10).with_max_infostates(3);
+        let mut shard = window.shard();
+
+        // updated in order, so the first ones are the least recent
+        for i in 0..5 {
+            shard.add(key(i), i as usize * 10, 0, 1.0);
+            window.merge(&mut shard, i as usize * 10);
+        }
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.strategy_sums(&key(1)), None);
+        assert!(window.strategy_sums(&key(2)).is_some());
+        assert!(window.strategy_sums(&key(4)).is_some());
+
+        // infostates that have left the window go first
+        shard.add(key(5), 400, 0, 1.0);
+        window.merge(&mut shard, 400);
+        assert_eq!(window.len(), 1);
+    }
+}
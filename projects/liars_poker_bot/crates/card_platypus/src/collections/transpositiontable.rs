@@ -0,0 +1,211 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use crate::metrics::{tt_evictions, tt_hits, tt_misses};
+
+const DEFAULT_SHARDS: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Entry<K, V> {
+    key: K,
+    value: V,
+    depth: u8,
+}
+
+/// Each bucket holds two entries:
+/// * `preferred`: only replaced by entries at the same or a shallower depth
+/// * `recent`: always replaced, catches everything the preferred slot rejects
+#[derive(Clone, Copy)]
+struct Bucket<K, V> {
+    preferred: Option<Entry<K, V>>,
+    recent: Option<Entry<K, V>>,
+}
+
+impl<K, V> Default for Bucket<K, V> {
+    fn default() -> Self {
+        Self {
+            preferred: None,
+            recent: None,
+        }
+    }
+}
+
+/// Sharded, capacity-bounded transposition table
+///
+/// Depth is the distance from the root of the search. Shallower entries cover
+/// larger subtrees and are more expensive to recompute, so they are preferred
+/// when deciding what to keep.
+///
+/// Memory use is bounded by the capacity set at construction time. Shards are
+/// allocated on first insert, once full new entries evict old ones rather than
+/// growing the table.
+pub struct TranspositionTable<K, V> {
+    shards: Vec<Mutex<Vec<Bucket<K, V>>>>,
+    buckets_per_shard: usize,
+}
+
+impl<K: Hash + Eq + Copy, V: Copy> TranspositionTable<K, V> {
+    /// Create a new table that holds at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self::with_shards(capacity, DEFAULT_SHARDS)
+    }
+
+    pub fn with_shards(capacity: usize, num_shards: usize) -> Self {
+        assert!(num_shards > 0, "must have at least one shard");
+        // each bucket stores 2 entries
+        let buckets_per_shard = (capacity / 2 / num_shards).max(1);
+        let shards = (0..num_shards).map(|_| Mutex::new(Vec::new())).collect();
+
+        Self {
+            shards,
+            buckets_per_shard,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let (shard, bucket) = self.index(key);
+        let shard = self.shards[shard].lock().unwrap();
+
+        let found = shard.get(bucket).and_then(|b| {
+            [b.preferred, b.recent]
+                .into_iter()
+                .flatten()
+                .find(|e| e.key == *key)
+                .map(|e| e.value)
+        });
+
+        match found {
+            Some(_) => tt_hits::increment(),
+            None => tt_misses::increment(),
+        }
+
+        found
+    }
+
+    pub fn insert(&self, key: K, value: V, depth: u8) {
+        let (shard, bucket) = self.index(&key);
+        let mut shard = self.shards[shard].lock().unwrap();
+        if shard.is_empty() {
+            shard.resize(self.buckets_per_shard, Bucket::default());
+        }
+        let bucket = &mut shard[bucket];
+        let new = Entry { key, value, depth };
+
+        // Update in place if we already have the key
+        if let Some(e) = bucket.preferred.as_mut().filter(|e| e.key == key) {
+            *e = new;
+            return;
+        }
+        if let Some(e) = bucket.recent.as_mut().filter(|e| e.key == key) {
+            *e = new;
+            return;
+        }
+
+        match bucket.preferred {
+            Some(p) if p.depth < depth => {
+                // the existing entry is more valuable, fall back to the always-replace slot
+                if bucket.recent.replace(new).is_some() {
+                    tt_evictions::increment();
+                }
+            }
+            Some(p) => {
+                // demote the old preferred entry rather than dropping it
+                bucket.preferred = Some(new);
+                if bucket.recent.replace(p).is_some() {
+                    tt_evictions::increment();
+                }
+            }
+            None => bucket.preferred = Some(new),
+        }
+    }
+
+    pub fn clear(&self) {
+        for s in &self.shards {
+            s.lock().unwrap().clear();
+        }
+    }
+
+    /// Maximum number of entries the table can hold
+    pub fn capacity(&self) -> usize {
+        self.shards.len() * self.buckets_per_shard * 2
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| {
+                s.lock()
+                    .unwrap()
+                    .iter()
+                    .map(|b| b.preferred.is_some() as usize + b.recent.is_some() as usize)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn index(&self, key: &K) -> (usize, usize) {
+        let mut hasher = DefaultHasher::default();
+        key.hash(&mut hasher);
+        let h = hasher.finish() as usize;
+        let shard = h % self.shards.len();
+        let bucket = (h / self.shards.len()) % self.buckets_per_shard;
+        (shard, bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranspositionTable;
+
+    #[test]
+    fn test_transposition_table_bounded() {
+        let tt = TranspositionTable::with_shards(16, 2);
+        assert_eq!(tt.capacity(), 16);
+
+        for i in 0..1000 {
+            tt.insert(i, i, 0);
+        }
+
+        assert!(tt.len() <= tt.capacity());
+        assert_eq!(tt.get(&999), Some(999));
+
+        tt.clear();
+        assert!(tt.is_empty());
+    }
+
+    #[test]
+    fn test_transposition_table_replacement() {
+        // single bucket so every key collides
+        let tt = TranspositionTable::with_shards(2, 1);
+
+        tt.insert(1, 1, 2);
+        tt.insert(2, 2, 5);
+        assert_eq!(tt.get(&1), Some(1));
+        assert_eq!(tt.get(&2), Some(2));
+
+        // deeper entry only replaces the always-replace slot
+        tt.insert(3, 3, 6);
+        assert_eq!(tt.get(&1), Some(1));
+        assert_eq!(tt.get(&2), None);
+        assert_eq!(tt.get(&3), Some(3));
+
+        // shallower entry takes the preferred slot and demotes the old one
+        tt.insert(4, 4, 0);
+        assert_eq!(tt.get(&4), Some(4));
+        assert_eq!(tt.get(&1), Some(1));
+        assert_eq!(tt.get(&3), None);
+
+        // existing keys are updated in place
+        tt.insert(1, 10, 0);
+        assert_eq!(tt.get(&1), Some(10));
+        assert_eq!(tt.get(&4), Some(4));
+    }
+}
@@ -3,12 +3,13 @@ use games::{
     gamestates::{
         bluff::Bluff,
         euchre::{
-            actions::EAction, ismorphic::normalize_euchre_istate,
-            iterator::EuchreIsomorphicIStateIterator,
+            actions::EAction,
+            ismorphic::normalize_euchre_istate,
+            iterator::{istate_cards_played, EuchreIsomorphicIStateIterator},
         },
         kuhn_poker::KuhnPoker,
     },
-    istate::IStateKey,
+    istate::{IStateKey, PackedIStateKey},
     iterator::IStateIterator,
     Action,
 };
@@ -34,9 +35,12 @@ impl Sharder {
     }
 }
 
+/// Keys are packed while the phf is built to shrink the list of istates.
+/// Packed keys hash the same as [`IStateKey`]s, so indexers saved before
+/// packing still load.
 #[derive(Serialize, Deserialize)]
 pub struct Indexer {
-    phf: Mphf<IStateKey>,
+    phf: Mphf<PackedIStateKey>,
     shard_len: usize,
     num_shards: usize,
     /// Returns the normalized istatekey and the associated shard
@@ -49,15 +53,30 @@ impl Indexer {
     pub fn index(&self, key: &IStateKey) -> Option<usize> {
         let (shard, normed) = self.sharder.shard(key)?;
         self.phf
-            .try_hash(&normed)
+            .try_hash(&PackedIStateKey::from(&normed))
             .map(|x| x as usize + (shard * self.shard_len))
     }
 
+    /// Returns the sub shard and normalized key that [`Indexer::index`] hashes,
+    /// useful when debugging index mismatches
+    pub fn locate(&self, key: &IStateKey) -> Option<(usize, IStateKey)> {
+        self.sharder.shard(key)
+    }
+
     /// Returns the total length of the indexer
     pub fn len(&self) -> usize {
         self.shard_len * self.num_shards
     }
 
+    /// Length of each sub shard, sub shard `n` starts at `n * shard_len`
+    pub fn shard_len(&self) -> usize {
+        self.shard_len
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.num_shards
+    }
+
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -71,7 +90,7 @@ impl Indexer {
         // Use an mmap vector as this collection may not fit into memory. This is also
         // more performant than the chunked iterator approach as we do not have an efficient method to
         // find the nth item for the iterator -- a common call in later rounds of the phf.
-        let istates = MMapVec::from_iter(istate_iter);
+        let istates = MMapVec::from_iter(istate_iter.map(PackedIStateKey::from));
         let phf = Mphf::new(GAMMA, &istates);
 
         Self {
@@ -82,9 +101,27 @@ impl Indexer {
         }
     }
 
+    /// Indexer for a single shard of the euchre istates, shard 0 has the
+    /// bidding istates and shard `n` the play istates with `n - 1` cards played
+    pub fn euchre_shard(shard: usize) -> Self {
+        let cards_played = shard.checked_sub(1);
+        let istate_iter = EuchreIsomorphicIStateIterator::with_face_up(shard, &[EAction::NS])
+            .filter(|x| istate_cards_played(x) == cards_played)
+            .map(PackedIStateKey::from);
+        let istates = MMapVec::from_iter(istate_iter);
+        let phf = Mphf::new(GAMMA, &istates);
+
+        Self {
+            phf,
+            shard_len: istates.len(),
+            num_shards: 6,
+            sharder: Sharder::Euchre,
+        }
+    }
+
     pub fn kuhn_poker() -> Self {
         let istate_iter = IStateIterator::new(KuhnPoker::new_state());
-        let istates = istate_iter.collect_vec();
+        let istates = istate_iter.map(PackedIStateKey::from).collect_vec();
         let phf = Mphf::new(GAMMA, &istates);
         Self {
             phf,
@@ -96,7 +133,7 @@ impl Indexer {
 
     pub fn bluff_11() -> Self {
         let istate_iter = IStateIterator::new(Bluff::new_state(1, 1));
-        let istates = istate_iter.collect_vec();
+        let istates = istate_iter.map(PackedIStateKey::from).collect_vec();
         let phf = Mphf::new(GAMMA, &istates);
         Self {
             phf,
@@ -1,46 +1,90 @@
 use std::{
     fs::OpenOptions,
-    io::{Read, Write},
+    io::Read,
     path::{Path, PathBuf},
 };
 
 use anyhow::{bail, Context};
 
-use games::istate::IStateKey;
-use log::{debug, warn};
+use games::{
+    gamestates::euchre::iterator::istate_cards_played,
+    istate::{IStateKey, PackedIStateKey},
+};
+use log::info;
 use memmap2::MmapMut;
+use rustc_hash::FxHashMap;
 
 use crate::algorithms::cfres::InfoState;
 
-use self::indexer::Indexer;
+use self::{
+    indexer::Indexer,
+    shard::Shard,
+    wal::{Wal, WalEntry},
+};
 
 pub mod indexer;
+mod shard;
+pub mod tablebase;
+mod wal;
+
+pub use shard::ShardStats;
+pub use wal::WalConfig;
 
 const BUCKET_SIZE: usize = std::mem::size_of::<InfoState>();
 const REMAP_INCREMENT: usize = 10_000_000;
 
 const INDEXER_NAME: &str = "indexer";
+const ABSTRACT_KEYS_NAME: &str = "abstract_keys";
+/// Shards are saved in subdirectories named with this prefix and their index
+const SHARD_PREFIX: &str = "shard_";
 
 // A performant, optionally diskback node storage system
+//
+// Keys are split into shards that each have their own indexer and mmap, so
+// lookups for similar depths of the game stay close together and shards that
+// aren't needed don't have to be loaded.
 pub struct NodeStore {
-    indexer: Indexer,
-    mmap: MmapMut,
+    /// None for shards that have been dropped
+    shards: Vec<Option<Shard>>,
+    /// Returns the shard a key is stored in
+    shard_of: fn(&IStateKey) -> usize,
     path: Option<PathBuf>,
+    /// Logs writes between commits when enabled, see [`NodeStore::enable_wal`]
+    wal: Option<Wal>,
 }
 
 impl NodeStore {
-    /// len is the number of infostates to provision for
+    /// Shards the istates by the number of cards played, the first shard has
+    /// the bidding istates and shard `n` the play istates with `n - 1` cards
+    /// played
+    ///
+    /// Stores saved before sharding are loaded as a single shard.
     pub fn new_euchre(path: Option<&Path>, max_cards_played: usize) -> anyhow::Result<Self> {
-        let mmap = get_mmap(path, 20_000_000).context("failed to create mmap")?;
-
         let path = path.map(|x| x.to_path_buf());
+
+        if let Some(dir) = path.as_deref().filter(|x| x.join(INDEXER_NAME).exists()) {
+            info!("loading unsharded node store from {}", dir.display());
+            let shard = Shard::open(Some(dir), || Indexer::euchre(max_cards_played))?;
+            return Ok(Self {
+                shards: vec![Some(shard)],
+                shard_of: |_| 0,
+                path,
+                wal: None,
+            });
+        }
+
+        let shards = (0..=max_cards_played)
+            .map(|i| {
+                let shard_path = path.as_ref().map(|x| x.join(format!("{SHARD_PREFIX}{i}")));
+                Shard::open(shard_path.as_deref(), || Indexer::euchre_shard(i)).map(Some)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
         Ok(Self {
-            indexer: load_indexer(path.as_deref()).unwrap_or_else(|x| {
-                warn!("failed to load indexer {}", x);
-                Indexer::euchre(max_cards_played)
-            }),
-            mmap,
+            shards,
+            shard_of: |k| istate_cards_played(k).map_or(0, |n| n + 1),
             path,
+            wal: None,
         })
     }
 
@@ -50,107 +94,172 @@ impl NodeStore {
             panic!("serialization not supported for this game type")
         }
 
-        let mmap = get_mmap(path, 1_000)?;
-
-        let path = path.map(|x| x.to_path_buf());
-        Ok(Self {
-            indexer: Indexer::kuhn_poker(),
-            mmap,
-            path,
-        })
+        Self::single_shard(Indexer::kuhn_poker())
     }
 
     pub fn new_bluff_11(path: Option<&Path>) -> anyhow::Result<Self> {
         if path.is_some() {
             panic!("serialization not supported for this game type")
         }
-        let mmap = get_mmap(path, 10_000)?;
 
-        let path = path.map(|x| x.to_path_buf());
+        Self::single_shard(Indexer::bluff_11())
+    }
+
+    fn single_shard(indexer: Indexer) -> anyhow::Result<Self> {
         Ok(Self {
-            indexer: Indexer::bluff_11(),
-            mmap,
-            path,
+            shards: vec![Some(Shard::open(None, || indexer)?)],
+            shard_of: |_| 0,
+            path: None,
+            wal: None,
         })
     }
 
+    /// Returns None for keys in dropped shards
     pub fn get(&self, key: &IStateKey) -> Option<InfoState> {
-        let index: usize = self
-            .indexer
-            .index(key)
-            .unwrap_or_else(|| panic!("failed to index {:?}", key));
-        self.get_index(index)
+        self.shard(key)?.get(key)
     }
 
-    fn get_index(&self, index: usize) -> Option<InfoState> {
-        let start = index * BUCKET_SIZE;
-
-        if start + BUCKET_SIZE > self.mmap.len() {
-            return None;
-        }
+    /// Gets the value stored for a key from an abstraction
+    ///
+    /// Abstract keys aren't istates so can't be sharded, they're all kept in
+    /// the first shard.
+    pub fn get_abstract(&self, key: &IStateKey) -> Option<InfoState> {
+        self.shards[0].as_ref()?.get_abstract(key)
+    }
 
-        let data = &self.mmap[start..start + BUCKET_SIZE];
+    /// Logs writes to disk between commits so they survive a crash, returns
+    /// the number of writes replayed from an existing log
+    ///
+    /// Writes logged before a crash are replayed into the store. Writes are
+    /// synced in batches, up to a batch of the most recent writes can still
+    /// be lost. Once the log grows past [`WalConfig::compact_bytes`] the store
+    /// is committed and the log emptied. Only stores saved to disk can have a
+    /// log.
+    pub fn enable_wal(&mut self, config: WalConfig) -> anyhow::Result<usize> {
+        let Some(dir) = self.path.clone() else {
+            bail!("a write-ahead log needs a store saved to disk");
+        };
 
-        // Check if the data is uninitialized
-        if data.iter().all(|&x| x == 0) {
-            return None;
+        let (wal, entries) = Wal::open(&dir, config)?;
+        if !entries.is_empty() {
+            info!(
+                "replaying {} writes from the write-ahead log",
+                entries.len()
+            );
+            for entry in &entries {
+                self.apply(entry);
+            }
         }
+        self.wal = Some(wal);
 
-        let info = bytemuck::cast_slice::<u8, InfoState>(data)[0];
-        Some(info)
+        // the replayed writes are safe in the store once it's committed
+        if !entries.is_empty() {
+            self.commit()?;
+        }
+        Ok(entries.len())
     }
 
     pub fn put(&mut self, key: &IStateKey, value: &InfoState) {
-        let index: usize = self
-            .indexer
-            .index(key)
-            .unwrap_or_else(|| panic!("failed to index {:?}", key));
-        let start = index * BUCKET_SIZE;
-
-        while start + BUCKET_SIZE >= self.mmap.len() {
-            let cur_len = self.mmap.len() / BUCKET_SIZE;
-            self.mmap.flush().expect("failed to flush mmap");
-            self.mmap = get_mmap(self.path.as_deref(), cur_len + REMAP_INCREMENT)
-                .expect("failed to resize mmap");
-            debug!("resized mmap");
-        }
+        self.write(WalEntry {
+            is_abstract: false,
+            key: PackedIStateKey::from(key),
+            value: *value,
+        });
+    }
 
-        // let data = rmp_serde::to_vec(value).unwrap();
-        let value = [*value];
-        let data = bytemuck::cast_slice::<InfoState, u8>(&value);
-        assert!(data.len() <= BUCKET_SIZE); // if this is false, we're overflowing into another bucket
-        self.mmap[start..start + data.len()].copy_from_slice(data);
+    /// Stores the value for a key from an abstraction
+    pub fn put_abstract(&mut self, key: &IStateKey, value: &InfoState) {
+        self.write(WalEntry {
+            is_abstract: true,
+            key: PackedIStateKey::from(key),
+            value: *value,
+        });
     }
 
-    pub fn commit(&mut self) -> anyhow::Result<()> {
-        self.mmap.flush().context("failed to flush mmap")?;
+    /// Logs the write if there's a write-ahead log before applying it
+    fn write(&mut self, entry: WalEntry) {
+        self.apply(&entry);
 
-        let Some(dir) = self.path.clone() else {
-            return anyhow::Ok(());
+        let Some(wal) = self.wal.as_mut() else {
+            return;
         };
+        let compact = wal
+            .append(entry)
+            .expect("failed to append to write-ahead log");
+        if compact {
+            self.commit().expect("failed to compact write-ahead log");
+        }
+    }
 
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(dir.join(INDEXER_NAME))?;
+    fn apply(&mut self, entry: &WalEntry) {
+        let key = IStateKey::from(entry.key);
+        if entry.is_abstract {
+            self.shard_mut(0).put_abstract(&key, &entry.value);
+        } else {
+            let shard = (self.shard_of)(&key);
+            self.shard_mut(shard).put(&key, &entry.value);
+        }
+    }
+
+    /// Returns the shard for a key, None if it has been dropped
+    fn shard(&self, key: &IStateKey) -> Option<&Shard> {
+        self.shards.get((self.shard_of)(key))?.as_ref()
+    }
 
-        let buf = serde_json::to_string(&self.indexer)?;
-        file.write_all(buf.as_bytes())?;
+    fn shard_mut(&mut self, shard: usize) -> &mut Shard {
+        let num_shards = self.shards.len();
+        self.shards
+            .get_mut(shard)
+            .unwrap_or_else(|| panic!("no shard {shard}, only have {num_shards}"))
+            .as_mut()
+            .unwrap_or_else(|| panic!("attempted to write to dropped shard {shard}"))
+    }
 
-        anyhow::Ok(())
+    /// Index of the shard a key is stored in
+    pub fn shard_of(&self, key: &IStateKey) -> usize {
+        (self.shard_of)(key)
     }
 
-    /// Returns the number of populated items in the database. Not the total number of items
-    pub fn len(&self) -> usize {
-        let mut items = 0;
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
 
-        for i in 0..self.indexer.len() {
-            if self.get_index(i).is_some() {
-                items += 1;
+    /// Commits and unmaps every shard from `shard` onwards
+    ///
+    /// Reads of keys in dropped shards return None and writes panic. For
+    /// euchre, dropping from shard 1 keeps only the bidding istates.
+    pub fn drop_shards_from(&mut self, shard: usize) -> anyhow::Result<()> {
+        for s in self.shards.iter_mut().skip(shard) {
+            if let Some(mut s) = s.take() {
+                s.commit()?;
             }
         }
+        anyhow::Ok(())
+    }
+
+    /// Returns the stats for each shard, None for dropped shards
+    pub fn shard_stats(&self) -> Vec<Option<ShardStats>> {
+        self.shards
+            .iter()
+            .map(|s| s.as_ref().map(|s| s.stats()))
+            .collect()
+    }
+
+    /// Saves every shard, and empties the write-ahead log now its writes are
+    /// in the store
+    pub fn commit(&mut self) -> anyhow::Result<()> {
+        for shard in self.shards.iter_mut().flatten() {
+            shard.commit()?;
+        }
+        if let Some(wal) = self.wal.as_mut() {
+            wal.truncate()?;
+        }
+        anyhow::Ok(())
+    }
 
-        items
+    /// Returns the number of populated items in the database. Not the total number of items
+    pub fn len(&self) -> usize {
+        self.shards.iter().flatten().map(|s| s.len()).sum()
     }
 
     #[must_use]
@@ -158,9 +267,19 @@ impl NodeStore {
         self.len() == 0
     }
 
+    /// Directory the store is persisted to, None for in-memory stores
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
     /// Returns the full size of the index, regardless of how many entries are populated
     pub fn indexer_len(&self) -> usize {
-        self.indexer.len()
+        self.shards.iter().flatten().map(|s| s.indexer_len()).sum()
+    }
+
+    /// Returns the number of keys from an abstraction that have been stored
+    pub fn abstract_len(&self) -> usize {
+        self.shards.iter().flatten().map(|s| s.abstract_len()).sum()
     }
 }
 
@@ -205,3 +324,98 @@ fn load_indexer(path: Option<&Path>) -> anyhow::Result<Indexer> {
     let indexer: Indexer = serde_json::from_str(&buf)?;
     anyhow::Ok(indexer)
 }
+
+/// Keys saved before they were packed are packed as they're loaded
+fn load_abstract_keys(path: Option<&Path>) -> anyhow::Result<FxHashMap<PackedIStateKey, usize>> {
+    let Some(dir) = path else {
+        bail!("no path");
+    };
+
+    let mut buf = Vec::new();
+    OpenOptions::new()
+        .read(true)
+        .open(dir.join(ABSTRACT_KEYS_NAME))?
+        .read_to_end(&mut buf)?;
+
+    if let Ok(keys) = rmp_serde::from_slice(&buf) {
+        return anyhow::Ok(keys);
+    }
+
+    let keys: FxHashMap<IStateKey, usize> = rmp_serde::from_slice(&buf)?;
+    anyhow::Ok(
+        keys.into_iter()
+            .map(|(k, v)| (PackedIStateKey::from(k), v))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use games::{
+        actions,
+        gamestates::euchre::Euchre,
+        istate::{IStateKey, PackedIStateKey},
+        GameState,
+    };
+    use rustc_hash::FxHashMap;
+
+    use crate::algorithms::cfres::InfoState;
+
+    use super::{load_abstract_keys, NodeStore, WalConfig, ABSTRACT_KEYS_NAME};
+
+    #[test]
+    fn test_load_abstract_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = IStateKey::from(&[3u8, 7, 31, 0][..]);
+
+        // saved before keys were packed
+        let legacy: FxHashMap<IStateKey, usize> = [(key, 5)].into_iter().collect();
+        let file = File::create(dir.path().join(ABSTRACT_KEYS_NAME)).unwrap();
+        rmp_serde::encode::write(&mut &file, &legacy).unwrap();
+        let loaded = load_abstract_keys(Some(dir.path())).unwrap();
+        assert_eq!(loaded.get(&PackedIStateKey::from(key)), Some(&5));
+
+        let file = File::create(dir.path().join(ABSTRACT_KEYS_NAME)).unwrap();
+        rmp_serde::encode::write(&mut &file, &loaded).unwrap();
+        assert_eq!(load_abstract_keys(Some(dir.path())).unwrap(), loaded);
+    }
+
+    #[test]
+    fn test_wal_recovers_uncommitted_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WalConfig {
+            batch_size: 1,
+            ..Default::default()
+        };
+        let mut gs = Euchre::new_state();
+        while gs.is_chance_node() {
+            gs.apply_action(actions!(gs)[0]);
+        }
+        let key = gs.istate_key(gs.cur_player());
+        let abstract_key = IStateKey::from(&[3u8, 7][..]);
+        let mut value = InfoState::new(Vec::new());
+        value.last_iteration = 42;
+
+        let mut store = NodeStore::new_euchre(Some(dir.path()), 0).unwrap();
+        assert_eq!(store.enable_wal(config).unwrap(), 0);
+        store.put(&key, &value);
+        store.put_abstract(&abstract_key, &value);
+        // crash without committing, the mmap isn't flushed
+        drop(store);
+
+        let mut store = NodeStore::new_euchre(Some(dir.path()), 0).unwrap();
+        assert_eq!(store.enable_wal(config).unwrap(), 2);
+        assert_eq!(store.get(&key).unwrap().last_iteration, 42);
+        assert_eq!(
+            store.get_abstract(&abstract_key).unwrap().last_iteration,
+            42
+        );
+
+        // the replayed writes were committed, so the log is empty
+        let mut store = NodeStore::new_euchre(Some(dir.path()), 0).unwrap();
+        assert_eq!(store.enable_wal(config).unwrap(), 0);
+        assert_eq!(store.get(&key).unwrap().last_iteration, 42);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+use games::istate::{IStateKey, PackedIStateKey};
+use log::{debug, warn};
+use memmap2::MmapMut;
+use rustc_hash::FxHashMap;
+
+use crate::algorithms::cfres::InfoState;
+
+use super::{
+    get_mmap, indexer::Indexer, load_abstract_keys, load_indexer, ABSTRACT_KEYS_NAME, BUCKET_SIZE,
+    INDEXER_NAME, REMAP_INCREMENT,
+};
+
+/// Minimum number of infostates to provision for
+const MIN_LEN: usize = 1_000;
+
+/// Accesses to a shard of the node store since it was opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShardStats {
+    pub reads: usize,
+    /// Reads that found a stored value
+    pub hits: usize,
+    pub writes: usize,
+    /// Number of slots in the shard's indexer, regardless of how many are populated
+    pub indexer_len: usize,
+}
+
+/// Infostates for a subset of the keys, with their own indexer and mmap
+pub(super) struct Shard {
+    indexer: Indexer,
+    /// Slots for keys from an abstraction, these aren't known to the indexer
+    /// so are given the slots after the indexer's as they're first stored
+    abstract_keys: FxHashMap<PackedIStateKey, usize>,
+    mmap: MmapMut,
+    path: Option<PathBuf>,
+    reads: AtomicUsize,
+    hits: AtomicUsize,
+    writes: AtomicUsize,
+}
+
+impl Shard {
+    /// Loads the shard from `path` if it was previously saved, otherwise
+    /// creates a new one with the indexer from `new_indexer`
+    pub fn open(
+        path: Option<&Path>,
+        new_indexer: impl FnOnce() -> Indexer,
+    ) -> anyhow::Result<Self> {
+        let indexer = match path {
+            Some(_) => load_indexer(path).unwrap_or_else(|x| {
+                warn!("failed to load indexer {}", x);
+                new_indexer()
+            }),
+            None => new_indexer(),
+        };
+        let abstract_keys = load_abstract_keys(path).unwrap_or_default();
+        let mmap = get_mmap(path, MIN_LEN.max(indexer.len() + abstract_keys.len()))
+            .context("failed to create mmap")?;
+
+        Ok(Self {
+            indexer,
+            abstract_keys,
+            mmap,
+            path: path.map(|x| x.to_path_buf()),
+            reads: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            writes: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn get(&self, key: &IStateKey) -> Option<InfoState> {
+        let index: usize = self
+            .indexer
+            .index(key)
+            .unwrap_or_else(|| panic!("failed to index {:?}", key));
+        self.get_counted(index)
+    }
+
+    pub fn get_abstract(&self, key: &IStateKey) -> Option<InfoState> {
+        match self.abstract_keys.get(&PackedIStateKey::from(key)) {
+            Some(&index) => self.get_counted(index),
+            None => {
+                self.reads.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    fn get_counted(&self, index: usize) -> Option<InfoState> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let info = self.get_index(index);
+        if info.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        info
+    }
+
+    fn get_index(&self, index: usize) -> Option<InfoState> {
+        let start = index * BUCKET_SIZE;
+
+        if start + BUCKET_SIZE > self.mmap.len() {
+            return None;
+        }
+
+        let data = &self.mmap[start..start + BUCKET_SIZE];
+
+        // Check if the data is uninitialized
+        if data.iter().all(|&x| x == 0) {
+            return None;
+        }
+
+        let info = bytemuck::cast_slice::<u8, InfoState>(data)[0];
+        Some(info)
+    }
+
+    pub fn put(&mut self, key: &IStateKey, value: &InfoState) {
+        let index: usize = self
+            .indexer
+            .index(key)
+            .unwrap_or_else(|| panic!("failed to index {:?}", key));
+        self.put_index(index, value);
+    }
+
+    pub fn put_abstract(&mut self, key: &IStateKey, value: &InfoState) {
+        let next = self.indexer.len() + self.abstract_keys.len();
+        let index = *self
+            .abstract_keys
+            .entry(PackedIStateKey::from(key))
+            .or_insert(next);
+        self.put_index(index, value);
+    }
+
+    fn put_index(&mut self, index: usize, value: &InfoState) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        let start = index * BUCKET_SIZE;
+
+        while start + BUCKET_SIZE >= self.mmap.len() {
+            let cur_len = self.mmap.len() / BUCKET_SIZE;
+            self.mmap.flush().expect("failed to flush mmap");
+            self.mmap = get_mmap(self.path.as_deref(), cur_len + REMAP_INCREMENT)
+                .expect("failed to resize mmap");
+            debug!("resized mmap");
+        }
+
+        // the scan is only worth it if the metrics are served
+        #[cfg(feature = "prometheus")]
+        if self.mmap[start..start + BUCKET_SIZE]
+            .iter()
+            .all(|&x| x == 0)
+        {
+            crate::metrics::infostates_stored::increment();
+        }
+
+        // let data = rmp_serde::to_vec(value).unwrap();
+        let value = [*value];
+        let data = bytemuck::cast_slice::<InfoState, u8>(&value);
+        assert!(data.len() <= BUCKET_SIZE); // if this is false, we're overflowing into another bucket
+        self.mmap[start..start + data.len()].copy_from_slice(data);
+    }
+
+    pub fn commit(&mut self) -> anyhow::Result<()> {
+        self.mmap.flush().context("failed to flush mmap")?;
+
+        let Some(dir) = self.path.clone() else {
+            return anyhow::Ok(());
+        };
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(dir.join(INDEXER_NAME))?;
+
+        let buf = serde_json::to_string(&self.indexer)?;
+        file.write_all(buf.as_bytes())?;
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(dir.join(ABSTRACT_KEYS_NAME))?;
+        // json doesn't support non-string map keys
+        rmp_serde::encode::write(&mut BufWriter::new(file), &self.abstract_keys)?;
+
+        anyhow::Ok(())
+    }
+
+    /// Returns the number of populated items in the shard
+    pub fn len(&self) -> usize {
+        (0..self.indexer.len() + self.abstract_keys.len())
+            .filter(|&i| self.get_index(i).is_some())
+            .count()
+    }
+
+    pub fn indexer_len(&self) -> usize {
+        self.indexer.len()
+    }
+
+    pub fn abstract_len(&self) -> usize {
+        self.abstract_keys.len()
+    }
+
+    pub fn stats(&self) -> ShardStats {
+        ShardStats {
+            reads: self.reads.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            indexer_len: self.indexer.len(),
+        }
+    }
+}
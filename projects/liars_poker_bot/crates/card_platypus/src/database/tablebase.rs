@@ -0,0 +1,169 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufReader, BufWriter},
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::Context;
+use games::{
+    gamestates::euchre::{
+        endgame::{endgame_positions, EndgameKey, ENDGAME_TRICKS},
+        EuchreGameState,
+    },
+    Team,
+};
+use log::{debug, info};
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::open_hand_solver::EndgameTable;
+
+pub const DEFAULT_TABLEBASE_PATH: &str = "/var/lib/card_platypus/endgame.tablebase";
+
+/// Precomputed trick outcomes for every canonical position in the final two
+/// tricks of euchre
+#[derive(Serialize, Deserialize, Default)]
+pub struct EuchreTablebase {
+    /// Tricks won by the leader's team for the remaining tricks
+    table: FxHashMap<EndgameKey, u8>,
+}
+
+impl EuchreTablebase {
+    /// Solves every endgame position, this takes a few seconds in release mode
+    pub fn build() -> Self {
+        let table = (1..=ENDGAME_TRICKS)
+            .flat_map(endgame_positions)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|k| (k, k.solve()))
+            .collect();
+
+        Self { table }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let f = OpenOptions::new()
+            .read(true)
+            .open(path)
+            .context("failed to open tablebase")?;
+        let tablebase = rmp_serde::from_read(BufReader::new(f))?;
+        Ok(tablebase)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("failed to create directory")?;
+        }
+
+        let f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        rmp_serde::encode::write(&mut BufWriter::new(f), self)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &EndgameKey) -> Option<u8> {
+        self.table.get(key).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl EndgameTable<EuchreGameState> for EuchreTablebase {
+    fn evaluate(&self, gs: &EuchreGameState, maximizing_team: Team) -> Option<f64> {
+        let key = gs.endgame_key()?;
+        let leader_tricks = self.get(&key)?;
+        Some(gs.evaluate_with_remaining(maximizing_team.into(), leader_tricks))
+    }
+}
+
+/// Returns the tablebase at [`DEFAULT_TABLEBASE_PATH`], it is only loaded once
+/// and then shared between all callers
+pub fn default_euchre_tablebase() -> Option<Arc<EuchreTablebase>> {
+    static TABLEBASE: OnceLock<Option<Arc<EuchreTablebase>>> = OnceLock::new();
+
+    TABLEBASE
+        .get_or_init(
+            || match EuchreTablebase::load(Path::new(DEFAULT_TABLEBASE_PATH)) {
+                Ok(t) => {
+                    info!("loaded endgame tablebase with {} positions", t.len());
+                    Some(Arc::new(t))
+                }
+                Err(e) => {
+                    debug!("no endgame tablebase available: {}", e);
+                    None
+                }
+            },
+        )
+        .clone()
+}
+
+pub fn build_tablebase(path: &str) -> anyhow::Result<()> {
+    info!("building endgame tablebase");
+    let tablebase = EuchreTablebase::build();
+    tablebase.save(Path::new(path))?;
+    println!("wrote {} positions to {}", tablebase.len(), path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use games::{
+        actions,
+        gamestates::euchre::{EPhase, Euchre},
+        GameState,
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::algorithms::{
+        ismcts::Evaluator,
+        open_hand_solver::{OpenHandSolver, Optimizations},
+    };
+
+    use super::EuchreTablebase;
+
+    #[test]
+    fn test_tablebase_matches_open_hand_solver() {
+        let tablebase = std::sync::Arc::new(EuchreTablebase::build());
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        let mut solver = OpenHandSolver::new(Optimizations::new_euchre());
+        let mut with_table =
+            OpenHandSolver::new(Optimizations::new_euchre()).with_endgame_table(tablebase);
+
+        let mut checked = 0;
+        while checked < 50 {
+            // play into the third trick so the search reaches the tablebase
+            let mut gs = Euchre::new_state();
+            while !gs.is_terminal()
+                && (gs.phase() != EPhase::Play || gs.get_hand(gs.cur_player()).len() > 3)
+            {
+                let a = *actions!(gs).choose(&mut rng).unwrap();
+                gs.apply_action(a);
+            }
+            if gs.is_terminal() {
+                continue;
+            }
+
+            for p in 0..2 {
+                assert_eq!(
+                    solver.evaluate_player(&gs, p),
+                    with_table.evaluate_player(&gs, p),
+                    "{}",
+                    gs
+                );
+            }
+            checked += 1;
+        }
+    }
+}
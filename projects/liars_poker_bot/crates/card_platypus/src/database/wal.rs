@@ -0,0 +1,260 @@
+use std::{
+    fs::{File, OpenOptions},
+    hash::Hasher,
+    io::{BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use games::istate::PackedIStateKey;
+use log::warn;
+use rustc_hash::{FxHashMap, FxHasher};
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::cfres::InfoState;
+
+/// Name of the log in the node store's directory
+pub(super) const WAL_NAME: &str = "wal";
+
+/// Bytes before each batch: its length and checksum
+const HEADER_LEN: usize = 12;
+
+/// How often the write-ahead log is synced and compacted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalConfig {
+    /// Changed infostates buffered before they're appended and synced, at
+    /// most this many are lost in a crash
+    pub batch_size: usize,
+    /// Once the log is larger than this the store is committed and the log
+    /// truncated
+    pub compact_bytes: u64,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 10_000,
+            compact_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// A value written to the node store
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub(super) struct WalEntry {
+    /// Keys from an abstraction are stored with `put_abstract`
+    pub is_abstract: bool,
+    pub key: PackedIStateKey,
+    pub value: InfoState,
+}
+
+/// Log of the node store's writes since it was last committed
+///
+/// Entries hold the full value written rather than the change, so replaying
+/// an entry that already reached the mmap is harmless. Each batch is synced
+/// to disk as it's appended, a batch that was only partly written when the
+/// process died is dropped on replay.
+pub(super) struct Wal {
+    file: File,
+    config: WalConfig,
+    /// Entries waiting for the next batch, in the order they were first written
+    pending: Vec<WalEntry>,
+    /// Index in `pending` of each key's entry, later writes replace earlier ones
+    pending_index: FxHashMap<(bool, PackedIStateKey), usize>,
+    /// Bytes in the log file
+    len: u64,
+}
+
+impl Wal {
+    /// Opens the log in `dir`, returns it along with the entries of the
+    /// complete batches already in it
+    pub fn open(dir: &Path, config: WalConfig) -> anyhow::Result<(Self, Vec<WalEntry>)> {
+        assert!(config.batch_size > 0, "batches need at least 1 entry");
+        std::fs::create_dir_all(dir).context("failed to create directory")?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(WAL_NAME))
+            .context("failed to open write-ahead log")?;
+
+        let (entries, len) = read_batches(&mut file)?;
+        // drop any partial batch so new ones are appended after the last good one
+        if len < file.metadata()?.len() {
+            warn!(
+                "dropping {} bytes of incomplete write-ahead log batches",
+                file.metadata()?.len() - len
+            );
+            file.set_len(len)?;
+        }
+        file.seek(SeekFrom::Start(len))?;
+
+        Ok((
+            Self {
+                file,
+                config,
+                pending: Vec::new(),
+                pending_index: FxHashMap::default(),
+                len,
+            },
+            entries,
+        ))
+    }
+
+    /// Buffers a write, returns true once the log should be compacted
+    pub fn append(&mut self, entry: WalEntry) -> anyhow::Result<bool> {
+        let next = self.pending.len();
+        let index = *self
+            .pending_index
+            .entry((entry.is_abstract, entry.key))
+            .or_insert(next);
+        if index == next {
+            self.pending.push(entry);
+        } else {
+            self.pending[index] = entry;
+        }
+
+        if self.pending.len() >= self.config.batch_size {
+            self.flush()?;
+        }
+        Ok(self.len > self.config.compact_bytes)
+    }
+
+    /// Appends the buffered writes as a batch and syncs the log
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let payload = rmp_serde::to_vec(&self.pending)?;
+        let mut batch = Vec::with_capacity(HEADER_LEN + payload.len());
+        batch.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        batch.extend_from_slice(&checksum(&payload).to_le_bytes());
+        batch.extend_from_slice(&payload);
+
+        self.file
+            .write_all(&batch)
+            .context("failed to append to write-ahead log")?;
+        self.file.sync_data()?;
+        self.len += batch.len() as u64;
+        self.pending.clear();
+        self.pending_index.clear();
+        Ok(())
+    }
+
+    /// Empties the log, call once everything in it has been committed to the
+    /// store. Buffered writes are dropped too
+    pub fn truncate(&mut self) -> anyhow::Result<()> {
+        self.pending.clear();
+        self.pending_index.clear();
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.sync_data()?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+fn checksum(payload: &[u8]) -> u64 {
+    let mut hasher = FxHasher::default();
+    hasher.write(payload);
+    hasher.finish()
+}
+
+/// Reads batches until the end of the file or the first incomplete or
+/// corrupt one, returns their entries and the length of the valid batches
+fn read_batches(file: &mut File) -> anyhow::Result<(Vec<WalEntry>, u64)> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    let mut entries = Vec::new();
+    let mut len = 0;
+
+    let mut header = [0; HEADER_LEN];
+    while reader.read_exact(&mut header).is_ok() {
+        let payload_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let expected = u64::from_le_bytes(header[4..].try_into().unwrap());
+
+        let mut payload = vec![0; payload_len];
+        if reader.read_exact(&mut payload).is_err() || checksum(&payload) != expected {
+            break;
+        }
+        let Ok(batch) = rmp_serde::from_slice::<Vec<WalEntry>>(&payload) else {
+            break;
+        };
+
+        entries.extend(batch);
+        len += (HEADER_LEN + payload_len) as u64;
+    }
+
+    Ok((entries, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+
+    use games::istate::{IStateKey, PackedIStateKey};
+
+    use crate::algorithms::cfres::InfoState;
+
+    use super::{Wal, WalConfig, WalEntry, WAL_NAME};
+
+    fn entry(action: u8, iteration: usize) -> WalEntry {
+        let mut value = InfoState::new(Vec::new());
+        value.last_iteration = iteration;
+        WalEntry {
+            is_abstract: false,
+            key: PackedIStateKey::from(IStateKey::from(&[action][..])),
+            value,
+        }
+    }
+
+    fn iterations(entries: &[WalEntry]) -> Vec<usize> {
+        entries.iter().map(|x| x.value.last_iteration).collect()
+    }
+
+    #[test]
+    fn test_wal_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WalConfig {
+            batch_size: 2,
+            compact_bytes: u64::MAX,
+        };
+
+        let (mut wal, entries) = Wal::open(dir.path(), config).unwrap();
+        assert!(entries.is_empty());
+        // later writes to a key replace the buffered one
+        wal.append(entry(1, 1)).unwrap();
+        wal.append(entry(1, 2)).unwrap();
+        wal.append(entry(2, 3)).unwrap();
+        // never flushed, lost in the crash
+        wal.append(entry(3, 4)).unwrap();
+        drop(wal);
+
+        let (mut wal, entries) = Wal::open(dir.path(), config).unwrap();
+        assert_eq!(iterations(&entries), vec![2, 3]);
+
+        // a batch cut off part way through is dropped
+        wal.append(entry(5, 6)).unwrap();
+        wal.append(entry(6, 7)).unwrap();
+        drop(wal);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(dir.path().join(WAL_NAME))
+            .unwrap();
+        file.set_len(file.metadata().unwrap().len() - 3).unwrap();
+
+        let (mut wal, entries) = Wal::open(dir.path(), config).unwrap();
+        assert_eq!(iterations(&entries), vec![2, 3]);
+        wal.append(entry(7, 8)).unwrap();
+        wal.append(entry(8, 9)).unwrap();
+        drop(wal);
+
+        let (mut wal, entries) = Wal::open(dir.path(), config).unwrap();
+        assert_eq!(iterations(&entries), vec![2, 3, 8, 9]);
+        wal.truncate().unwrap();
+        let (_, entries) = Wal::open(dir.path(), config).unwrap();
+        assert!(entries.is_empty());
+    }
+}
@@ -6,6 +6,7 @@ use std::path::Path;
 use card_platypus::algorithms::cfres::{self, InfoState};
 
 use card_platypus::database::indexer::Indexer;
+use card_platypus::database::tablebase::{build_tablebase, DEFAULT_TABLEBASE_PATH};
 use card_platypus::database::NodeStore;
 use clap::{command, Parser, Subcommand, ValueEnum};
 
@@ -13,30 +14,34 @@ use dashmap::iter;
 use games::gamestates::bluff::BluffGameState;
 use games::gamestates::euchre::actions::EAction;
 use games::gamestates::euchre::iterator::EuchreIsomorphicIStateIterator;
-use games::gamestates::euchre::EuchreGameState;
+use games::gamestates::euchre::{Euchre, EuchreGameState};
 use games::gamestates::kuhn_poker::KPGameState;
 use games::istate::IStateKey;
 use games::translate_istate;
 use log::{set_max_level, LevelFilter};
 
-use scripts::agent_exploitability::calcualte_agent_exploitability;
+use scripts::agent_exploitability::{calcualte_agent_exploitability, ExploitabilityArgs};
 use scripts::benchmark::{run_benchmark, BenchmarkArgs};
+use scripts::debug_indexer::{debug_indexer, DebugIndexerArgs};
+use scripts::distill_bid_policy::{distill_bid_policy, DistillBidPolicyArgs};
 use scripts::estimate_euchre_game_tree::estimate_euchre_game_tree;
+use scripts::evaluate_from_logs::{evaluate_from_logs, EvaluateFromLogsArgs};
 use scripts::pass_on_bower::open_hand_score_pass_on_bower;
 use scripts::pass_on_bower_alpha::benchmark_pass_on_bower;
 use scripts::pass_on_bower_cfr::{
     analyze_istate, parse_weights, run_pass_on_bower_cfr, PassOnBowerCFRArgs,
 };
+use scripts::tournament::{run_tournament, TournamentArgs};
 use simplelog::{
     ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode, WriteLogger,
 };
 
-use crate::scripts::config::train_cfr_from_config;
+use crate::scripts::config::{train_cfr_from_config, train_cfr_sweep_from_config};
 
 pub mod scripts;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
-enum GameType {
+pub enum GameType {
     KuhnPoker,
     Euchre,
     Bluff11,
@@ -51,13 +56,19 @@ enum Commands {
     Analyze,
     Play,
     Scratch,
-    Exploitability,
+    Exploitability(ExploitabilityArgs),
     PassOnBowerOpenHand,
     PassOnBowerAlpha { num_games: usize },
     EuchreCFRTrain { profile: String },
+    EuchreCFRSweep { sweep: String, run: Option<usize> },
     PassOnBowerCFRTrain(PassOnBowerCFRArgs),
     PassOnBowerCFRParseWeights { infostate_path: String },
     PassOnBowerCFRAnalyzeIstate { num_games: usize },
+    BuildTablebase { path: Option<String> },
+    DebugIndexer(DebugIndexerArgs),
+    Tournament(TournamentArgs),
+    EvaluateFromLogs(EvaluateFromLogsArgs),
+    DistillBidPolicy(DistillBidPolicyArgs),
 }
 
 /// Simple program to greet a person
@@ -82,6 +93,11 @@ pub struct Args {
 
     #[clap(short = 'v', long, action, default_value_t = 1)]
     verbosity: usize,
+
+    /// Address to serve prometheus metrics on, e.g. 0.0.0.0:9100
+    #[cfg(feature = "prometheus")]
+    #[clap(long)]
+    metrics_addr: Option<String>,
 }
 
 fn main() {
@@ -125,6 +141,14 @@ fn main() {
         ),
     ])
     .unwrap();
+    // only the main thread logs actions
+    Euchre::register_action_labeler();
+
+    #[cfg(feature = "prometheus")]
+    if let Some(addr) = &args.metrics_addr {
+        card_platypus::metrics::prometheus::serve(addr.as_str())
+            .expect("failed to start metrics server");
+    }
 
     match args.command {
         Commands::Run => run(args),
@@ -134,14 +158,28 @@ fn main() {
         Commands::Scratch => run_scratch(args),
         // Mode::PassOnBowerOpenHand => calculate_open_hand_solver_convergence(args),
         Commands::PassOnBowerOpenHand => open_hand_score_pass_on_bower(args),
-        Commands::Exploitability => calcualte_agent_exploitability(args),
+        Commands::Exploitability(exploitability) => {
+            calcualte_agent_exploitability(args.game, exploitability)
+        }
         Commands::PassOnBowerAlpha { num_games } => benchmark_pass_on_bower(num_games),
-        Commands::PassOnBowerCFRTrain(bower_cfr) => run_pass_on_bower_cfr(bower_cfr),
+        Commands::PassOnBowerCFRTrain(bower_cfr) => {
+            run_pass_on_bower_cfr(bower_cfr);
+        }
         Commands::PassOnBowerCFRParseWeights { infostate_path } => {
             parse_weights(infostate_path.as_str())
         }
         Commands::PassOnBowerCFRAnalyzeIstate { num_games } => analyze_istate(num_games),
         Commands::EuchreCFRTrain { profile } => train_cfr_from_config(profile.as_str()).unwrap(),
+        Commands::EuchreCFRSweep { sweep, run } => {
+            train_cfr_sweep_from_config(sweep.as_str(), run).unwrap()
+        }
+        Commands::BuildTablebase { path } => {
+            build_tablebase(path.as_deref().unwrap_or(DEFAULT_TABLEBASE_PATH)).unwrap()
+        }
+        Commands::DebugIndexer(debug) => debug_indexer(debug),
+        Commands::Tournament(tournament) => run_tournament(tournament),
+        Commands::EvaluateFromLogs(evaluate) => evaluate_from_logs(evaluate),
+        Commands::DistillBidPolicy(distill) => distill_bid_policy(distill),
     }
 }
 
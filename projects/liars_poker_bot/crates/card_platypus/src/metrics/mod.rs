@@ -1,3 +1,11 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
 #[macro_export]
 macro_rules! counter {
     // This macro takes an argument of designator `ident` and
@@ -7,17 +15,93 @@ macro_rules! counter {
         pub mod $name {
             use std::sync::atomic::AtomicUsize;
 
-            static mut COUNTER: AtomicUsize = AtomicUsize::new(0);
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
             pub fn increment() {
-                unsafe {
-                    COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                }
+                COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             }
 
             pub fn read() -> usize {
-                unsafe { COUNTER.load(std::sync::atomic::Ordering::Relaxed) }
+                COUNTER.load(std::sync::atomic::Ordering::Relaxed)
             }
         }
     };
 }
+
+// Transposition table statistics for the open hand solver
+counter!(tt_hits);
+counter!(tt_misses);
+counter!(tt_evictions);
+
+// Number of new infostates written to a node store, only counted with the
+// prometheus feature
+counter!(infostates_stored);
+
+// Node store locks taken by CFRES, and how many had to wait for another thread
+counter!(node_store_locks);
+counter!(node_store_lock_contended);
+
+// Play moves served from the istates primed by CFRES, and those computed
+counter!(play_cache_hits);
+counter!(play_cache_misses);
+
+/// Upper bounds, in seconds, of the request latency histogram buckets
+pub const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+/// Latency of requests handled by the euchre server
+pub static REQUEST_LATENCY: Histogram = Histogram::new();
+
+/// Fixed bucket histogram for durations
+pub struct Histogram {
+    buckets: [AtomicUsize; LATENCY_BUCKETS.len()],
+    count: AtomicUsize,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicUsize::new(0) }; LATENCY_BUCKETS.len()],
+            count: AtomicUsize::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, d: Duration) {
+        let secs = d.as_secs_f64();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|b| secs <= *b) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(d.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the cumulative count for each bucket bound
+    pub fn cumulative_buckets(&self) -> Vec<(f64, usize)> {
+        let mut total = 0;
+        LATENCY_BUCKETS
+            .iter()
+            .zip(self.buckets.iter())
+            .map(|(bound, c)| {
+                total += c.load(Ordering::Relaxed);
+                (*bound, total)
+            })
+            .collect()
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Sum of all observations in seconds
+    pub fn sum(&self) -> f64 {
+        self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,177 @@
+//! Exposes the metrics counters in the prometheus text format
+//!
+//! Counters are monotonic, rates like nodes touched per second should be
+//! computed at query time, e.g. `rate(card_platypus_nodes_touched_total[1m])`.
+
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, ToSocketAddrs},
+    thread::{self, JoinHandle},
+};
+
+use log::{info, warn};
+
+use crate::algorithms::cfres::nodes_touched;
+
+use super::{
+    infostates_stored, node_store_lock_contended, node_store_locks, play_cache_hits,
+    play_cache_misses, tt_evictions, tt_hits, tt_misses, Histogram, REQUEST_LATENCY,
+};
+
+/// Renders all metrics in the prometheus text exposition format
+pub fn render() -> String {
+    let mut out = String::new();
+
+    write_counter(
+        &mut out,
+        "card_platypus_nodes_touched_total",
+        "CFR nodes visited during training",
+        nodes_touched::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_infostates_stored_total",
+        "New infostates written to the node store",
+        infostates_stored::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_node_store_locks_total",
+        "Node store locks taken during training",
+        node_store_locks::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_node_store_lock_contended_total",
+        "Node store locks that waited for another thread",
+        node_store_lock_contended::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_play_cache_hits_total",
+        "Play moves served from the primed istates",
+        play_cache_hits::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_play_cache_misses_total",
+        "Play moves computed because the istate wasn't primed",
+        play_cache_misses::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_tt_hits_total",
+        "Open hand solver transposition table hits",
+        tt_hits::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_tt_misses_total",
+        "Open hand solver transposition table misses",
+        tt_misses::read(),
+    );
+    write_counter(
+        &mut out,
+        "card_platypus_tt_evictions_total",
+        "Open hand solver transposition table evictions",
+        tt_evictions::read(),
+    );
+    write_histogram(
+        &mut out,
+        "card_platypus_request_latency_seconds",
+        "Latency of euchre server requests",
+        &REQUEST_LATENCY,
+    );
+
+    out
+}
+
+fn write_counter(out: &mut String, name: &str, help: &str, value: usize) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} counter").unwrap();
+    writeln!(out, "{name} {value}").unwrap();
+}
+
+fn write_histogram(out: &mut String, name: &str, help: &str, h: &Histogram) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} histogram").unwrap();
+    for (bound, count) in h.cumulative_buckets() {
+        writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}").unwrap();
+    }
+    writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", h.count()).unwrap();
+    writeln!(out, "{name}_sum {}", h.sum()).unwrap();
+    writeln!(out, "{name}_count {}", h.count()).unwrap();
+}
+
+/// Serve [`render`] on `addr` from a background thread
+///
+/// Every request gets the metrics regardless of path, this is only meant to
+/// be scraped by prometheus during long training runs.
+pub fn serve(addr: impl ToSocketAddrs) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    info!("serving prometheus metrics on {}", listener.local_addr()?);
+
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("failed to accept metrics connection: {}", e);
+                    continue;
+                }
+            };
+
+            // read the request line, we don't care about the contents
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+
+            let body = render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()) {
+                warn!("failed to write metrics response: {}", e);
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::metrics::{tt_hits, Histogram};
+
+    use super::{render, write_histogram};
+
+    #[test]
+    fn test_render_counters() {
+        tt_hits::increment();
+        let out = render();
+        assert!(out.contains("# TYPE card_platypus_tt_hits_total counter"));
+        assert!(out.contains("card_platypus_nodes_touched_total "));
+        assert!(out.contains("card_platypus_request_latency_seconds_count "));
+    }
+
+    #[test]
+    fn test_render_histogram() {
+        let h = Histogram::new();
+        h.observe(Duration::from_millis(3));
+        h.observe(Duration::from_millis(30));
+        h.observe(Duration::from_secs(10));
+
+        let mut out = String::new();
+        write_histogram(&mut out, "latency", "test", &h);
+        assert!(out.contains("latency_bucket{le=\"0.001\"} 0\n"));
+        assert!(out.contains("latency_bucket{le=\"0.005\"} 1\n"));
+        assert!(out.contains("latency_bucket{le=\"0.05\"} 2\n"));
+        assert!(out.contains("latency_bucket{le=\"5\"} 2\n"));
+        assert!(out.contains("latency_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("latency_count 3\n"));
+    }
+}
@@ -6,6 +6,20 @@ use games::{actions, Action, GameState};
 pub trait Policy<G> {
     /// Returns an ActionVec of legal moves and their associated probability for the current player
     fn action_probabilities(&mut self, gs: &G) -> ActionVec<f64>;
+
+    /// Returns the action probabilities for each state in `states`
+    ///
+    /// Policies that are slow to query one state at a time, like neural networks
+    /// or disk backed stores, should override this to evaluate the states together.
+    /// Used where the states are known up front: exploitability, evaluating logged
+    /// hands and game analysis. Agents playing a game, like in the benchmarks, only
+    /// know the next state once the last action is chosen.
+    fn action_probabilities_batch(&mut self, states: &[G]) -> Vec<ActionVec<f64>> {
+        states
+            .iter()
+            .map(|gs| self.action_probabilities(gs))
+            .collect()
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -1,6 +1,9 @@
+use std::path::Path;
+
 use card_platypus::{
     algorithms::{
-        exploitability::exploitability,
+        cfres::CFRES,
+        exploitability::{exploitability, local_best_response},
         ismcts::{
             ChildSelectionPolicy, ISMCTBotConfig, ISMCTSBot, ISMCTSFinalPolicyType,
             RandomRolloutEvaluator,
@@ -10,12 +13,69 @@ use card_platypus::{
     },
     policy::UniformRandomPolicy,
 };
-use games::gamestates::{bluff::Bluff, kuhn_poker::KuhnPoker};
+use clap::Args;
+use games::gamestates::{bluff::Bluff, euchre::Euchre, kuhn_poker::KuhnPoker};
 use log::info;
 
-use crate::{scripts::benchmark::get_rng, Args};
+use crate::{scripts::benchmark::get_rng, GameType};
+
+/// Only used for euchre, the smaller games are solved exactly
+#[derive(Args, Debug, Clone)]
+pub struct ExploitabilityArgs {
+    /// Weights of the policy to evaluate
+    #[clap(
+        long,
+        default_value = "/var/lib/card_platypus/infostate.three_card_played"
+    )]
+    policy_path: String,
+    #[clap(long, default_value_t = 3)]
+    max_cards_played: usize,
+    /// Deals played for each team's best response
+    #[clap(long, default_value_t = 1000)]
+    num_games: usize,
+    /// Worlds the best response samples to pick each action
+    #[clap(long, default_value_t = 32)]
+    num_worlds: usize,
+}
+
+pub fn calcualte_agent_exploitability(game: GameType, args: ExploitabilityArgs) {
+    match game {
+        GameType::Euchre => euchre_exploitability(args),
+        _ => small_game_exploitability(),
+    }
+}
+
+/// Euchre is too large for an exact best response, estimates a lower bound
+/// with a local best response instead
+fn euchre_exploitability(args: ExploitabilityArgs) {
+    let mut policy = CFRES::new_euchre(
+        get_rng(),
+        args.max_cards_played,
+        Some(Path::new(&args.policy_path)),
+    );
+    info!(
+        "loaded {} istates from {}",
+        policy.num_info_states(),
+        args.policy_path
+    );
+
+    info!(
+        "estimating exploitability for euchre with local best response, {} games, {} worlds...",
+        args.num_games, args.num_worlds
+    );
+    let mut responder = PIMCTSBot::new(args.num_worlds, OpenHandSolver::new_euchre(), get_rng());
+    let e = local_best_response(
+        Euchre::new_state,
+        &mut policy,
+        &mut responder,
+        args.num_games,
+        &mut get_rng(),
+    );
+    info!("team improvements\t{:?}", e.player_improvements);
+    info!("LBR lower bound\t{}", e.nash_conv);
+}
 
-pub fn calcualte_agent_exploitability(_args: Args) {
+fn small_game_exploitability() {
     info!("calculating exploitability for kuhn poker...");
     let g = || (KuhnPoker::game().new)();
 
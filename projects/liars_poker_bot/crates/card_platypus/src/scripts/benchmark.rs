@@ -1,7 +1,7 @@
-use std::{cell::RefCell, collections::HashMap, path::Path, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fs, path::Path, rc::Rc};
 
 use card_platypus::{
-    agents::{Agent, PolicyAgent, RandomAgent},
+    agents::{Agent, PolicyAgent, RandomAgent, Seedable},
     algorithms::cfres::CFRES,
     algorithms::{open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot},
 };
@@ -9,7 +9,7 @@ use clap::{Args, ValueEnum};
 use games::{
     gamestates::{
         bluff::Bluff,
-        euchre::{actions::Card, util::generate_face_up_deals, Euchre, EuchreGameState},
+        euchre::{actions::Card, util::generate_face_up_deals_with_rng, Euchre, EuchreGameState},
         kuhn_poker::KuhnPoker,
     },
     get_games,
@@ -19,10 +19,14 @@ use games::{
 use indicatif::ProgressBar;
 use itertools::Itertools;
 use log::{debug, info, warn};
-use rand::{rngs::StdRng, thread_rng, SeedableRng};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use crate::GameType;
 
+/// May need up to 19 deals for a full game to 10
+const DEALS_PER_GAME: usize = 19;
+
 #[derive(ValueEnum, Clone, Copy, Debug)]
 pub enum BenchmarkMode {
     FullGame,
@@ -30,34 +34,157 @@ pub enum BenchmarkMode {
     Test,
 }
 
-#[derive(Args, Debug, Clone, Copy)]
+#[derive(Args, Debug, Clone)]
 pub struct BenchmarkArgs {
     #[clap(short, long, default_value_t = 20)]
     num_games: usize,
     #[clap(long, value_enum, default_value_t=GameType::Euchre)]
     game: GameType,
     mode: BenchmarkMode,
+    /// Master seed the per game seeds are derived from, random if not set
+    #[clap(long)]
+    seed: Option<u64>,
+    /// File the seed and result of every game is written to
+    #[clap(long, default_value = "benchmark_seeds.json")]
+    seed_file: String,
+    /// Rerun the games from a seed file instead of new games
+    #[clap(long)]
+    replay: Option<String>,
+}
+
+/// Agents are reseeded for every game so that games can be replayed
+pub trait BenchmarkAgent<G: GameState>: Agent<G> + Seedable {}
+impl<G: GameState, T: Agent<G> + Seedable> BenchmarkAgent<G> for T {}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SeedFile {
+    master_seed: u64,
+    games: Vec<GameRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct GameRecord {
+    agent_1: String,
+    agent_2: String,
+    seed: u64,
+    score: [f64; 2],
+}
+
+/// Tracks the seed and result of every game played
+///
+/// When replaying, results are compared against the recorded ones rather than
+/// written out, a mismatch means something in the agents isn't seeded.
+struct SeedRecorder {
+    seeds: SeedFile,
+    path: String,
+    expected: Option<HashMap<(String, String, u64), [f64; 2]>>,
+}
+
+impl SeedRecorder {
+    /// Returns the recorder and the seeds of the games to play
+    fn new(args: &BenchmarkArgs) -> (Self, Vec<u64>) {
+        if let Some(replay) = &args.replay {
+            let data = fs::read_to_string(replay).expect("failed to read seed file");
+            let recorded: SeedFile = serde_json::from_str(&data).expect("invalid seed file");
+            info!(
+                "replaying {} games from {} with master seed {}",
+                recorded.games.len(),
+                replay,
+                recorded.master_seed
+            );
+
+            let seeds = recorded.games.iter().map(|g| g.seed).unique().collect_vec();
+            let expected = recorded
+                .games
+                .iter()
+                .map(|g| ((g.agent_1.clone(), g.agent_2.clone(), g.seed), g.score))
+                .collect();
+            let recorder = SeedRecorder {
+                seeds: SeedFile {
+                    master_seed: recorded.master_seed,
+                    games: Vec::new(),
+                },
+                path: replay.clone(),
+                expected: Some(expected),
+            };
+            return (recorder, seeds);
+        }
+
+        let master_seed = args.seed.unwrap_or_else(|| thread_rng().gen());
+        println!("benchmark master seed: {}", master_seed);
+        let mut rng = StdRng::seed_from_u64(master_seed);
+        let seeds = (0..args.num_games).map(|_| rng.gen()).collect_vec();
+
+        let recorder = SeedRecorder {
+            seeds: SeedFile {
+                master_seed,
+                games: Vec::new(),
+            },
+            path: args.seed_file.clone(),
+            expected: None,
+        };
+        (recorder, seeds)
+    }
+
+    fn is_replay(&self) -> bool {
+        self.expected.is_some()
+    }
+
+    fn record(&mut self, agent_1: &str, agent_2: &str, seed: u64, score: [f64; 2]) {
+        if let Some(expected) = &self.expected {
+            match expected.get(&(agent_1.to_string(), agent_2.to_string(), seed)) {
+                Some(e) if *e != score => warn!(
+                    "replay of {} vs {} with seed {} scored {:?}, recorded {:?}",
+                    agent_1, agent_2, seed, score, e
+                ),
+                _ => {}
+            }
+        }
+
+        self.seeds.games.push(GameRecord {
+            agent_1: agent_1.to_string(),
+            agent_2: agent_2.to_string(),
+            seed,
+            score,
+        });
+    }
+
+    /// Writes the seed file, replays don't overwrite the file they came from
+    fn save(&self) {
+        if self.is_replay() {
+            return;
+        }
+
+        let data = serde_json::to_string_pretty(&self.seeds).unwrap();
+        fs::write(&self.path, data).expect("failed to write seed file");
+    }
 }
 
 pub fn run_benchmark(args: BenchmarkArgs) {
+    let (mut recorder, seeds) = SeedRecorder::new(&args);
+
     match args.mode {
         BenchmarkMode::FullGame => match args.game {
-            GameType::KuhnPoker => run_full_game_benchmark(args, KuhnPoker::game()),
-            GameType::Euchre => run_euchre_benchmark(args),
-            GameType::Bluff11 => run_full_game_benchmark(args, Bluff::game(1, 1)),
-            GameType::Bluff21 => run_full_game_benchmark(args, Bluff::game(2, 1)),
-            GameType::Bluff22 => run_full_game_benchmark(args, Bluff::game(2, 2)),
+            GameType::KuhnPoker => {
+                run_full_game_benchmark(KuhnPoker::game(), &seeds, &mut recorder)
+            }
+            GameType::Euchre => run_euchre_benchmark(&seeds, &mut recorder),
+            GameType::Bluff11 => run_full_game_benchmark(Bluff::game(1, 1), &seeds, &mut recorder),
+            GameType::Bluff21 => run_full_game_benchmark(Bluff::game(2, 1), &seeds, &mut recorder),
+            GameType::Bluff22 => run_full_game_benchmark(Bluff::game(2, 2), &seeds, &mut recorder),
         },
-        BenchmarkMode::JackFaceUp => run_jack_face_up_benchmark(args),
-        BenchmarkMode::Test => run_euchre_test(args),
+        BenchmarkMode::JackFaceUp => run_jack_face_up_benchmark(&args, &seeds, &mut recorder),
+        BenchmarkMode::Test => run_euchre_test(&seeds, &mut recorder),
     }
-}
 
-fn run_euchre_test(args: BenchmarkArgs) {
-    // all agents play the same games
-    let mut game_rng = get_rng();
+    recorder.save();
+    if !recorder.is_replay() {
+        println!("game seeds written to: {}", recorder.path);
+    }
+}
 
-    let mut agents: HashMap<String, &mut dyn Agent<EuchreGameState>> = HashMap::new();
+fn run_euchre_test(seeds: &[u64], recorder: &mut SeedRecorder) {
+    let mut agents: HashMap<String, &mut dyn BenchmarkAgent<EuchreGameState>> = HashMap::new();
 
     let a = &mut PolicyAgent::new(
         PIMCTSBot::new(50, OpenHandSolver::new_euchre(), get_rng()),
@@ -67,15 +194,12 @@ fn run_euchre_test(args: BenchmarkArgs) {
 
     println!("Starting benchmark for agents: {:?}", agents.keys());
 
-    // may need up to 19x the number fo full games to 10
     let eval_chunk = 100; // how many games to evaluate at onces;
-    let mut remaining_games = args.num_games;
+    let mut played_games = 0;
     let mut total_wins = HashMap::new();
 
-    while remaining_games > 0 {
-        let num_games = remaining_games.min(eval_chunk);
-        let games = get_games(Euchre::game(), num_games * 19, &mut game_rng);
-        let iter_wins = score_games(&mut agents, games);
+    for chunk in seeds.chunks(eval_chunk) {
+        let iter_wins = score_games(&mut agents, &Euchre::game(), chunk, recorder);
 
         iter_wins.into_iter().for_each(|(name, new_wins)| {
             total_wins
@@ -83,9 +207,10 @@ fn run_euchre_test(args: BenchmarkArgs) {
                 .and_modify(|w| *w += new_wins)
                 .or_insert(new_wins);
         });
+        // save as we go so long runs can be replayed if interrupted
+        recorder.save();
 
-        remaining_games -= num_games;
-        let played_games = args.num_games - remaining_games;
+        played_games += chunk.len();
         println!("played games: {}", played_games);
         for (name, win_rate) in total_wins
             .iter()
@@ -97,11 +222,8 @@ fn run_euchre_test(args: BenchmarkArgs) {
 }
 
 /// Calculate the win-rate of first to 10 for each agent
-fn run_euchre_benchmark(args: BenchmarkArgs) {
-    // all agents play the same games
-    let mut game_rng = get_rng();
-
-    let mut agents: HashMap<String, &mut dyn Agent<EuchreGameState>> = HashMap::new();
+fn run_euchre_benchmark(seeds: &[u64], recorder: &mut SeedRecorder) {
+    let mut agents: HashMap<String, &mut dyn BenchmarkAgent<EuchreGameState>> = HashMap::new();
 
     let mut a = RandomAgent::default();
     agents.insert("random".to_string(), &mut a);
@@ -164,15 +286,12 @@ fn run_euchre_benchmark(args: BenchmarkArgs) {
 
     println!("Starting benchmark for agents: {:?}", agents.keys());
 
-    // may need up to 19x the number fo full games to 10
     let eval_chunk = 100; // how many games to evaluate at onces;
-    let mut remaining_games = args.num_games;
+    let mut played_games = 0;
     let mut total_wins = HashMap::new();
 
-    while remaining_games > 0 {
-        let num_games = remaining_games.min(eval_chunk);
-        let games = get_games(Euchre::game(), num_games * 19, &mut game_rng);
-        let iter_wins = score_games(&mut agents, games);
+    for chunk in seeds.chunks(eval_chunk) {
+        let iter_wins = score_games(&mut agents, &Euchre::game(), chunk, recorder);
 
         iter_wins.into_iter().for_each(|(name, new_wins)| {
             total_wins
@@ -180,9 +299,10 @@ fn run_euchre_benchmark(args: BenchmarkArgs) {
                 .and_modify(|w| *w += new_wins)
                 .or_insert(new_wins);
         });
+        // save as we go so long runs can be replayed if interrupted
+        recorder.save();
 
-        remaining_games -= num_games;
-        let played_games = args.num_games - remaining_games;
+        played_games += chunk.len();
         println!("played games: {}", played_games);
         for (name, win_rate) in total_wins
             .iter()
@@ -195,15 +315,11 @@ fn run_euchre_benchmark(args: BenchmarkArgs) {
 
 /// Calculate the win-rate of first to 10 for each agent
 fn run_full_game_benchmark<G: GameState + ResampleFromInfoState + Send>(
-    args: BenchmarkArgs,
     game: Game<G>,
+    seeds: &[u64],
+    recorder: &mut SeedRecorder,
 ) {
-    // all agents play the same games
-    let mut game_rng = get_rng();
-    // may need up to 19x the number fo full games to 10
-    let games = get_games(game, args.num_games * 19, &mut game_rng);
-
-    let mut agents: HashMap<String, &mut dyn Agent<G>> = HashMap::new();
+    let mut agents: HashMap<String, &mut dyn BenchmarkAgent<G>> = HashMap::new();
 
     let a = &mut PolicyAgent::new(
         PIMCTSBot::new(50, OpenHandSolver::default(), get_rng()),
@@ -212,18 +328,22 @@ fn run_full_game_benchmark<G: GameState + ResampleFromInfoState + Send>(
 
     agents.insert("pimcts, 50 worlds, open hand".to_string(), a);
 
-    let wins = score_games(&mut agents, games);
+    let wins = score_games(&mut agents, &game, seeds, recorder);
     println!("{:?}", wins);
 }
 
 /// Calculate the win-rate of first to 10 for each agent
+///
+/// Every pairing plays the same games, each game's deals and the agents are
+/// seeded from the game's seed.
 fn score_games<G: GameState + ResampleFromInfoState + Send>(
-    agents: &mut HashMap<String, &mut dyn Agent<G>>,
-    games: Vec<G>,
+    agents: &mut HashMap<String, &mut dyn BenchmarkAgent<G>>,
+    game: &Game<G>,
+    seeds: &[u64],
+    recorder: &mut SeedRecorder,
 ) -> HashMap<(String, String), usize> {
     let mut wins = HashMap::new();
     let agent_names = agents.keys().cloned().collect_vec();
-    let num_games = games.len() / 19;
 
     for a1_name in agent_names.clone() {
         for a2_name in agent_names.clone() {
@@ -237,15 +357,20 @@ fn score_games<G: GameState + ResampleFromInfoState + Send>(
             debug!("starting play for {} vs {}", a1_name, a2_name);
             let mut games_won = [0; 2];
 
-            // Make sure that each "game" to 10 is identical, we may need up to 20 games for this to happen
-            let mut chunked_games = Vec::new();
-            for g in &games.clone().into_iter().chunks(19) {
-                chunked_games.push(g.collect_vec());
-            }
+            let pb = ProgressBar::new(seeds.len() as u64);
 
-            let pb = ProgressBar::new(num_games as u64);
+            for &seed in seeds {
+                // Make sure that each "game" to 10 is identical, we may need up to 20 games for this to happen
+                let games_deals = get_games(
+                    game.clone(),
+                    DEALS_PER_GAME,
+                    &mut StdRng::seed_from_u64(seed),
+                );
+                a1.set_seed(seed);
+                if let Some(a2) = a2.as_mut() {
+                    a2.set_seed(seed.wrapping_add(1));
+                }
 
-            for games_deals in chunked_games {
                 // Play out the game
                 let mut game_score = [0, 0];
                 for (deal_number, deal) in games_deals.into_iter().enumerate() {
@@ -268,6 +393,10 @@ fn score_games<G: GameState + ResampleFromInfoState + Send>(
                         gs.apply_action(a);
                     }
 
+                    if recorder.is_replay() {
+                        info!("seed {}\tdeal {}\t{}", seed, deal_number, gs);
+                    }
+
                     // Need to make sure the teams are consistent throughout
                     let r = [
                         gs.evaluate(agent_1_team),
@@ -280,8 +409,14 @@ fn score_games<G: GameState + ResampleFromInfoState + Send>(
 
                 // record the game results
                 info!(
-                    "\t{}\t{}\t{}\t{}",
-                    a1_name, a2_name, game_score[0], game_score[1]
+                    "\t{}\t{}\t{}\t{}\t{}",
+                    a1_name, a2_name, seed, game_score[0], game_score[1]
+                );
+                recorder.record(
+                    &a1_name,
+                    &a2_name,
+                    seed,
+                    [game_score[0] as f64, game_score[1] as f64],
                 );
 
                 let team_0_win = game_score[0] >= 10;
@@ -306,15 +441,15 @@ fn score_games<G: GameState + ResampleFromInfoState + Send>(
     wins
 }
 
-fn run_jack_face_up_benchmark(args: BenchmarkArgs) {
+fn run_jack_face_up_benchmark(args: &BenchmarkArgs, seeds: &[u64], recorder: &mut SeedRecorder) {
     assert!(matches!(args.game, GameType::Euchre));
 
     // all agents play the same games
     info!("generating games...");
-    let games = get_jack_of_spades_games(args.num_games);
+    let games = get_jack_of_spades_games(seeds);
     info!("finished generated {} games", games.len());
 
-    let mut agents: Vec<(String, Rc<RefCell<dyn Agent<EuchreGameState>>>)> = Vec::new();
+    let mut agents: Vec<(String, Rc<RefCell<dyn BenchmarkAgent<EuchreGameState>>>)> = Vec::new();
 
     let a = PolicyAgent::new(
         PIMCTSBot::new(50, OpenHandSolver::new_euchre(), get_rng()),
@@ -335,7 +470,9 @@ fn run_jack_face_up_benchmark(args: BenchmarkArgs) {
         for (a2_name, a2) in agents.clone() {
             let mut dealer_team_score = 0.0;
             let pb = ProgressBar::new(games.len() as u64);
-            for mut gs in games.clone() {
+            for (&seed, mut gs) in seeds.iter().zip(games.clone()) {
+                a1.borrow_mut().set_seed(seed);
+                a2.borrow_mut().set_seed(seed.wrapping_add(1));
                 while !gs.is_terminal() {
                     assert!(!gs.is_chance_node());
 
@@ -347,6 +484,11 @@ fn run_jack_face_up_benchmark(args: BenchmarkArgs) {
                     gs.apply_action(a);
                 }
 
+                if recorder.is_replay() {
+                    info!("seed {}\t{}", seed, gs);
+                }
+                recorder.record(&a1_name, &a2_name, seed, [gs.evaluate(0), gs.evaluate(1)]);
+
                 dealer_team_score += gs.evaluate(1);
                 pb.inc(1);
             }
@@ -359,9 +501,10 @@ fn run_jack_face_up_benchmark(args: BenchmarkArgs) {
     }
 }
 
-fn get_jack_of_spades_games(n: usize) -> Vec<EuchreGameState> {
-    (0..n)
-        .map(|_| generate_face_up_deals(Card::JS))
+fn get_jack_of_spades_games(seeds: &[u64]) -> Vec<EuchreGameState> {
+    seeds
+        .iter()
+        .map(|&s| generate_face_up_deals_with_rng(Card::JS, &mut StdRng::seed_from_u64(s)))
         .collect_vec()
 }
 
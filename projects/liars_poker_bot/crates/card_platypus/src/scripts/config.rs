@@ -1,16 +1,49 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
 
-use anyhow::{Context, Ok};
-use log::info;
+use anyhow::{bail, Context, Ok};
+use itertools::Itertools;
+use log::{info, warn};
 use serde::Deserialize;
+use toml::Value;
 
-use super::pass_on_bower_cfr::{run_pass_on_bower_cfr, PassOnBowerCFRArgs};
+use super::pass_on_bower_cfr::{run_pass_on_bower_cfr, CFRTrainingResult, PassOnBowerCFRArgs};
 
 const CONFIG_LOCATION: &str = "./Train.toml";
 
 #[derive(Deserialize, Debug)]
 struct Config {
     train: HashMap<String, PassOnBowerCFRArgs>,
+    #[serde(default)]
+    sweep: HashMap<String, SweepConfig>,
+}
+
+/// Runs every combination of the `grid` parameters on top of a base profile
+#[derive(Deserialize, Debug, Clone)]
+struct SweepConfig {
+    /// Training profile the grid values override
+    base: String,
+    /// Each run stores its weights in `{weight_dir}/run_{i}`
+    weight_dir: String,
+    /// CSV file the per-run results are appended to
+    results: String,
+    /// Number of runs to train at once, each in its own process
+    #[serde(default = "workers_default")]
+    workers: usize,
+    /// Parameter name to the values to try, e.g. `max_cards_played = [0, 1]`
+    grid: BTreeMap<String, Vec<Value>>,
+}
+
+fn workers_default() -> usize {
+    1
 }
 
 pub fn train_cfr_from_config(profile: &str) -> anyhow::Result<()> {
@@ -23,3 +56,265 @@ pub fn train_cfr_from_config(profile: &str) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Train every run of a sweep, or only `run` if set
+///
+/// Worker processes call back into this with `run` set, they write their
+/// results next to the run's weights for the parent to collect.
+pub fn train_cfr_sweep_from_config(sweep: &str, run: Option<usize>) -> anyhow::Result<()> {
+    info!("starting sweep: {}", sweep);
+    let toml_str = fs::read_to_string(CONFIG_LOCATION)?;
+    let raw: Value = toml::from_str(&toml_str)?;
+    let toml: Config = toml::from_str(&toml_str)?;
+
+    let config = toml.sweep.get(sweep).context("sweep not found")?;
+    let base = raw
+        .get("train")
+        .and_then(|t| t.get(config.base.as_str()))
+        .and_then(|t| t.as_table())
+        .context("base profile not found")?;
+    let runs = expand_grid(&config.grid);
+    info!("sweep {} has {} runs", sweep, runs.len());
+
+    if let Some(run) = run {
+        let params = runs.get(run).context("run out of range")?;
+        let result = train_run(config, base, run, params)?;
+        fs::write(result_path(config, run), serde_json::to_string(&result)?)?;
+        return Ok(());
+    }
+
+    let mut results = SweepResults::create(config)?;
+    if config.workers <= 1 {
+        for (run, params) in runs.iter().enumerate() {
+            let start = Instant::now();
+            let result = train_run(config, base, run, params)?;
+            results.write(run, params, Some(&result), start.elapsed())?;
+        }
+    } else {
+        run_workers(sweep, config, &runs, &mut results)?;
+    }
+
+    info!("sweep results written to {}", config.results);
+    Ok(())
+}
+
+/// Returns every combination of the grid values
+fn expand_grid(grid: &BTreeMap<String, Vec<Value>>) -> Vec<BTreeMap<String, Value>> {
+    if grid.is_empty() {
+        return vec![BTreeMap::new()];
+    }
+
+    grid.values()
+        .map(|values| values.iter())
+        .multi_cartesian_product()
+        .map(|combination| {
+            grid.keys()
+                .cloned()
+                .zip(combination.into_iter().cloned())
+                .collect()
+        })
+        .collect()
+}
+
+fn train_run(
+    config: &SweepConfig,
+    base: &toml::Table,
+    run: usize,
+    params: &BTreeMap<String, Value>,
+) -> anyhow::Result<CFRTrainingResult> {
+    let mut table = base.clone();
+    for (k, v) in params {
+        table.insert(k.clone(), v.clone());
+    }
+    table.insert(
+        "weight_file".to_string(),
+        Value::String(run_dir(config, run).to_string_lossy().to_string()),
+    );
+
+    let args: PassOnBowerCFRArgs = Value::Table(table)
+        .try_into()
+        .with_context(|| format!("invalid parameters for run {}: {:?}", run, params))?;
+
+    info!("starting sweep run {}: {:?}", run, params);
+    Ok(run_pass_on_bower_cfr(args))
+}
+
+/// Train the runs in child processes, at most `config.workers` at a time
+///
+/// Runs whose worker fails get a `failed` row. If the sweep itself fails, the
+/// workers still running are killed rather than left training on their own.
+fn run_workers(
+    sweep: &str,
+    config: &SweepConfig,
+    runs: &[BTreeMap<String, Value>],
+    results: &mut SweepResults,
+) -> anyhow::Result<()> {
+    let mut running: Vec<(usize, Child, Instant)> = Vec::new();
+    let outcome = schedule_workers(sweep, config, runs, results, &mut running);
+    if outcome.is_err() {
+        for (run, child, _) in running.iter_mut() {
+            warn!("killing worker for sweep run {}", run);
+            // errors if the worker already exited, which is what we want
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+    outcome
+}
+
+fn schedule_workers(
+    sweep: &str,
+    config: &SweepConfig,
+    runs: &[BTreeMap<String, Value>],
+    results: &mut SweepResults,
+    running: &mut Vec<(usize, Child, Instant)>,
+) -> anyhow::Result<()> {
+    let exe = env::current_exe()?;
+    let mut pending = (0..runs.len()).rev().collect_vec();
+
+    while !pending.is_empty() || !running.is_empty() {
+        while running.len() < config.workers {
+            let Some(run) = pending.pop() else {
+                break;
+            };
+            let child = Command::new(&exe)
+                .args(["euchre-cfr-sweep", sweep, &run.to_string()])
+                .spawn()
+                .with_context(|| format!("failed to start worker for run {}", run))?;
+            running.push((run, child, Instant::now()));
+        }
+
+        let mut i = 0;
+        while i < running.len() {
+            let Some(status) = running[i].1.try_wait()? else {
+                i += 1;
+                continue;
+            };
+
+            let (run, _, start) = running.swap_remove(i);
+            let result = if status.success() {
+                read_result(config, run)
+                    .map_err(|e| warn!("failed to read result of sweep run {}: {:#}", run, e))
+                    .ok()
+            } else {
+                warn!("sweep run {} failed: {}", run, status);
+                None
+            };
+            results.write(run, &runs[run], result.as_ref(), start.elapsed())?;
+        }
+
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    Ok(())
+}
+
+fn run_dir(config: &SweepConfig, run: usize) -> PathBuf {
+    Path::new(&config.weight_dir).join(format!("run_{}", run))
+}
+
+fn result_path(config: &SweepConfig, run: usize) -> PathBuf {
+    Path::new(&config.weight_dir).join(format!("run_{}.result.json", run))
+}
+
+fn read_result(config: &SweepConfig, run: usize) -> anyhow::Result<CFRTrainingResult> {
+    let result = fs::read_to_string(result_path(config, run))?;
+    Ok(serde_json::from_str(&result)?)
+}
+
+/// CSV of the sweep results, rows are written as runs finish so partial
+/// sweeps still have results
+struct SweepResults {
+    file: fs::File,
+}
+
+impl SweepResults {
+    fn create(config: &SweepConfig) -> anyhow::Result<Self> {
+        if config.grid.keys().any(|k| k == "weight_file") {
+            bail!("weight_file is set by the sweep and can't be part of the grid");
+        }
+        fs::create_dir_all(&config.weight_dir)?;
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&config.results)
+            .context("failed to create results file")?;
+
+        let header = ["run", "status"]
+            .into_iter()
+            .chain(config.grid.keys().map(|k| k.as_str()))
+            .chain([
+                "iterations",
+                "info_states",
+                "nodes_touched",
+                "score",
+                "baseline",
+                "seconds",
+            ])
+            .map(csv_field)
+            .join(",");
+        writeln!(file, "{}", header)?;
+
+        Ok(Self { file })
+    }
+
+    /// Writes the row of a run, a run without a result is marked as failed and
+    /// its result columns are left empty
+    fn write(
+        &mut self,
+        run: usize,
+        params: &BTreeMap<String, Value>,
+        result: Option<&CFRTrainingResult>,
+        elapsed: Duration,
+    ) -> anyhow::Result<()> {
+        let status = if result.is_some() { "ok" } else { "failed" };
+        let scores = match result {
+            Some(result) => [
+                result.iterations.to_string(),
+                result.info_states.to_string(),
+                result.nodes_touched.to_string(),
+                result.score.to_string(),
+                result.baseline.to_string(),
+            ],
+            None => Default::default(),
+        };
+        let row = [run.to_string(), status.to_string()]
+            .into_iter()
+            .chain(params.values().map(|v| match v {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            }))
+            .chain(scores)
+            .chain([format!("{:.1}", elapsed.as_secs_f64())])
+            .map(|x| csv_field(&x))
+            .join(",");
+        writeln!(self.file, "{}", row)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Quotes a CSV field if it has a separator, quote or newline in it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_field;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("lossy"), "lossy");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("[1, 2]"), "\"[1, 2]\"");
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+    }
+}
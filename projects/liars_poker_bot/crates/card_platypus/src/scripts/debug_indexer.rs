@@ -0,0 +1,121 @@
+use std::{collections::HashMap, panic};
+
+use card_platypus::database::indexer::Indexer;
+use clap::Args;
+use games::{
+    gamestates::euchre::{actions::EAction, iterator::istate_cards_played, EuchreGameState},
+    istate::IStateKey,
+    translate_istate, GameState,
+};
+
+/// Face up card for each sub shard of a euchre indexer, see `euchre_sharder`
+const FACE_UP: [EAction; 6] = [
+    EAction::NS,
+    EAction::TS,
+    EAction::JS,
+    EAction::QS,
+    EAction::KS,
+    EAction::AS,
+];
+
+#[derive(Args, Debug, Clone)]
+pub struct DebugIndexerArgs {
+    /// Builds the indexers for shards up to this many cards played
+    #[clap(long, default_value_t = 0)]
+    max_cards_played: usize,
+    /// Gamestate strings to index, e.g. `9sTsQsKsAs|9cTcKcAcTd|JdQdKdAd9h|JcQcJhAh9d|Js`,
+    /// they're indexed from the view of the player to act
+    hands: Vec<String>,
+}
+
+/// Prints the size and offset of every euchre indexer shard and indexes the
+/// given hands, so index mismatches can be debugged without one off tests
+pub fn debug_indexer(args: DebugIndexerArgs) {
+    let indexers = (0..=args.max_cards_played)
+        .map(Indexer::euchre_shard)
+        .collect::<Vec<_>>();
+
+    println!("| shard | cards played | face up | size | offset | total offset |");
+    println!("|---|---|---|---|---|---|");
+    let mut total_offset = 0;
+    for (shard, indexer) in indexers.iter().enumerate() {
+        let cards_played = match shard {
+            0 => "bidding".to_string(),
+            n => (n - 1).to_string(),
+        };
+        for (sub_shard, face_up) in FACE_UP.iter().enumerate().take(indexer.num_shards()) {
+            let offset = sub_shard * indexer.shard_len();
+            println!(
+                "| {} | {} | {} | {} | {} | {} |",
+                shard,
+                cards_played,
+                face_up,
+                indexer.shard_len(),
+                offset,
+                total_offset + offset
+            );
+        }
+        total_offset += indexer.len();
+    }
+    println!("total size: {}", total_offset);
+
+    // normalized keys seen at each index, to catch hands that collide
+    let mut seen: HashMap<(usize, usize), IStateKey> = HashMap::new();
+    for hand in &args.hands {
+        println!();
+        println!("hand: {}", hand);
+        // the parser panics on illegal hands, keep going with the others
+        let Ok(gs) = panic::catch_unwind(|| EuchreGameState::from(hand.as_str())) else {
+            println!("failed to parse hand");
+            continue;
+        };
+        // hands are sorted when displayed, so compare against a second parse
+        let parsed = gs.to_string();
+        let reparsed = EuchreGameState::from(parsed.as_str()).to_string();
+        println!("parsed: {}", parsed);
+        if reparsed != parsed {
+            println!("round trip mismatch, reparsed as: {}", reparsed);
+        }
+
+        let player = gs.cur_player();
+        let key = gs.istate_key(player);
+        println!("player: {}, istate: {}", player, gs.istate_string(player));
+        println!("key: {:?}", translate_istate!(key, EAction));
+
+        let shard = istate_cards_played(&key).map_or(0, |n| n + 1);
+        let Some(indexer) = indexers.get(shard) else {
+            println!(
+                "shard {} is past max cards played of {}",
+                shard, args.max_cards_played
+            );
+            continue;
+        };
+
+        let Some((sub_shard, normed)) = indexer.locate(&key) else {
+            println!("no face up card, can't be sharded");
+            continue;
+        };
+        println!(
+            "shard: {}, sub shard: {} ({} face up)",
+            shard, sub_shard, FACE_UP[sub_shard]
+        );
+        println!("normalized: {:?}", translate_istate!(normed, EAction));
+
+        let Some(index) = indexer.index(&key) else {
+            println!("index: not found");
+            continue;
+        };
+        println!("index: {}", index);
+
+        match seen.get(&(shard, index)) {
+            Some(other) if *other != normed => println!(
+                "collision with normalized key: {:?}",
+                translate_istate!(other, EAction)
+            ),
+            Some(_) => println!("same normalized key as an earlier hand"),
+            None => {
+                seen.insert((shard, index), normed);
+            }
+        }
+    }
+}
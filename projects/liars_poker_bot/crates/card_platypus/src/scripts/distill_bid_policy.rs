@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use card_platypus::algorithms::{
+    cfres::CFRES,
+    distill::{sample_bids, BidTree},
+};
+use clap::Args;
+use games::gamestates::euchre::EuchreGameState;
+use log::{info, warn};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[derive(Args, Debug, Clone)]
+pub struct DistillBidPolicyArgs {
+    /// Weights of the policy to distill
+    #[clap(
+        long,
+        default_value = "/var/lib/card_platypus/infostate.three_card_played"
+    )]
+    policy_path: String,
+    #[clap(long, default_value_t = 3)]
+    max_cards_played: usize,
+    /// Deals bid by the policy to fit the tree, a quarter as many more are
+    /// held out to measure fidelity
+    #[clap(long, default_value_t = 20000)]
+    num_deals: usize,
+    /// Most rules followed for a single bid
+    #[clap(long, default_value_t = 4)]
+    max_depth: usize,
+    /// Fewest training bids a rule can be fit to
+    #[clap(long, default_value_t = 100)]
+    min_leaf: usize,
+    #[clap(long, default_value_t = 42)]
+    seed: u64,
+    /// Where to save the tree, for use with the `distilled` agent
+    #[clap(long, default_value = "bid_tree.json")]
+    output: String,
+}
+
+/// Fits decision trees of increasing depth to the bids of a trained policy
+///
+/// Prints the fidelity at each depth, to see how much each extra level of
+/// rules buys, and the rules of the deepest tree.
+pub fn distill_bid_policy(args: DistillBidPolicyArgs) {
+    // only bids are sampled, so the play shards aren't needed
+    let mut policy: CFRES<EuchreGameState> = CFRES::new_euchre(
+        StdRng::seed_from_u64(args.seed),
+        args.max_cards_played,
+        Some(Path::new(&args.policy_path)),
+    )
+    .with_bid_policy_only()
+    .expect("failed to drop the play shards");
+    let n = policy.num_info_states();
+    if n == 0 {
+        warn!("no istates loaded from {}", args.policy_path);
+    }
+    info!("loaded {} istates from {}", n, args.policy_path);
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let train = sample_bids(&mut policy, args.num_deals, &mut rng);
+    let test = sample_bids(&mut policy, (args.num_deals / 4).max(1), &mut rng);
+    info!(
+        "sampled {} training and {} held out bids",
+        train.len(),
+        test.len()
+    );
+
+    println!("depth\trules\tagreement\texpected agreement\tsuit agreement");
+    let mut tree = None;
+    for depth in 1..=args.max_depth {
+        let t = BidTree::fit(&train, depth, args.min_leaf);
+        let fidelity = t.fidelity(&test);
+        println!(
+            "{}\t{}\t{:.3}\t{:.3}\t{:.3}",
+            depth,
+            t.num_leaves(),
+            fidelity.agreement,
+            fidelity.expected_agreement,
+            fidelity.suit_agreement
+        );
+        tree = Some(t);
+    }
+
+    let Some(tree) = tree else {
+        return;
+    };
+    println!();
+    print!("{}", tree);
+    tree.save(Path::new(&args.output))
+        .expect("failed to save bid tree");
+    info!("saved bid tree to {}", args.output);
+}
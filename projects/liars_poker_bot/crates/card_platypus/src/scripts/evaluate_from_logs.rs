@@ -0,0 +1,370 @@
+use std::{fs, path::Path};
+
+use card_platypus::{algorithms::cfres::CFRES, policy::Policy};
+use clap::Args;
+use games::{
+    gamestates::euchre::{processors::post_cards_played, Euchre, EuchreGameState},
+    GameState, Player,
+};
+use indicatif::ProgressBar;
+use log::{info, warn};
+use serde::Deserialize;
+
+use super::benchmark::get_rng;
+
+/// Message of the event euchre_server logs when a hand finishes
+const HAND_ENDED: &str = "hand ended";
+/// Marker for the same line in the text logs of older servers
+const LEGACY_HAND_ENDED: &str = "hand ended|id|";
+
+#[derive(Args, Debug, Clone)]
+pub struct EvaluateFromLogsArgs {
+    /// Log file written by euchre_server
+    #[clap(default_value = "euchre_server.log")]
+    log_file: String,
+    /// Weights of the policy to evaluate
+    #[clap(long)]
+    candidate_path: String,
+    #[clap(long, default_value_t = 3)]
+    candidate_max_cards_played: usize,
+    /// Weights of the bot that was deployed when the logs were written
+    #[clap(
+        long,
+        default_value = "/var/lib/card_platypus/infostate.three_card_played"
+    )]
+    logged_path: String,
+    #[clap(long, default_value_t = 3)]
+    logged_max_cards_played: usize,
+}
+
+/// A line of euchre_server's json log, only the fields of `hand ended` events
+/// are read
+#[derive(Deserialize)]
+struct LogEvent {
+    fields: EventFields,
+}
+
+#[derive(Deserialize)]
+struct EventFields {
+    message: Option<String>,
+    /// The finished hand's gamestate
+    gs: Option<String>,
+    /// Player id in each seat, formatted with `Debug`, e.g. `[Some(7), None, None, None]`
+    players: Option<String>,
+}
+
+/// A completed hand from the server logs
+struct LoggedHand {
+    gs: EuchreGameState,
+    /// Seats played by the bot
+    bot_seats: Vec<Player>,
+    /// Seat whose score the server reports as the computer score
+    scoring_seat: Player,
+}
+
+/// Estimates how the candidate policy would have scored on the hands the
+/// deployed bot played on the server
+///
+/// Each hand's score for the bot team is weighted by the product of the
+/// per-decision importance ratios `candidate(a|s) / logged(a|s)` over every
+/// bot decision in the hand. Human decisions and the deal don't depend on the
+/// bot and have a ratio of 1.
+pub fn evaluate_from_logs(args: EvaluateFromLogsArgs) {
+    let logs = fs::read_to_string(&args.log_file).expect("failed to read log file");
+    let hands = logs.lines().filter_map(parse_hand).collect::<Vec<_>>();
+    info!("found {} completed hands in {}", hands.len(), args.log_file);
+    if hands.is_empty() {
+        warn!("no hands to evaluate");
+        return;
+    }
+
+    let mut candidate = load_policy(&args.candidate_path, args.candidate_max_cards_played);
+    let mut logged = load_policy(&args.logged_path, args.logged_max_cards_played);
+    // past both max depths the policies use the same open hand fallback
+    let fallback_depth = args
+        .candidate_max_cards_played
+        .max(args.logged_max_cards_played);
+
+    let mut samples = Vec::with_capacity(hands.len());
+    let mut skipped = 0;
+    let pb = ProgressBar::new(hands.len() as u64);
+    for hand in &hands {
+        match importance_weight(hand, &mut candidate, &mut logged, fallback_depth) {
+            Some(w) => samples.push((w, hand.gs.evaluate(hand.scoring_seat))),
+            None => skipped += 1,
+        }
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+
+    if skipped > 0 {
+        warn!(
+            "skipped {} hands with actions the logged policy would never take, \
+             the logged weights may not match the deployed bot",
+            skipped
+        );
+    }
+    if samples.is_empty() {
+        return;
+    }
+
+    print_estimates(&samples);
+}
+
+fn load_policy(path: &str, max_cards_played: usize) -> CFRES<EuchreGameState> {
+    let policy = CFRES::new_euchre(get_rng(), max_cards_played, Some(Path::new(path)));
+    let n = policy.num_info_states();
+    if n == 0 {
+        warn!("no istates loaded from {}", path);
+    }
+    info!("loaded {} istates from {}", n, path);
+    policy
+}
+
+/// Parses a `hand ended` line, returns None for any other line
+fn parse_hand(line: &str) -> Option<LoggedHand> {
+    match serde_json::from_str::<LogEvent>(line) {
+        Ok(event) => parse_event(event.fields),
+        Err(_) => parse_legacy_hand(line),
+    }
+}
+
+fn parse_event(fields: EventFields) -> Option<LoggedHand> {
+    if fields.message.as_deref() != Some(HAND_ENDED) {
+        return None;
+    }
+
+    let players = fields
+        .players?
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|p| match p.trim() {
+            "None" => Some(None),
+            p => p
+                .strip_prefix("Some(")?
+                .strip_suffix(')')?
+                .parse::<usize>()
+                .ok()
+                .map(Some),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if players.len() != 4 {
+        return None;
+    }
+
+    let bot_seats = (0..4).filter(|&s| players[s].is_none()).collect();
+    // the server scores the team of the lowest player id as the humans
+    let (_, human_seat) = players
+        .iter()
+        .enumerate()
+        .filter_map(|(seat, p)| p.map(|id| (id, seat)))
+        .min()?;
+
+    logged_hand(&fields.gs?, bot_seats, human_seat)
+}
+
+/// Parses a `hand ended` line from the text logs of older servers
+fn parse_legacy_hand(line: &str) -> Option<LoggedHand> {
+    let (_, rest) = line.split_once(LEGACY_HAND_ENDED)?;
+    // the gamestate uses `|` as a separator so can't split on it
+    let (_, rest) = rest.split_once("|game:|")?;
+    let (game, rest) = rest.split_once("|human players:|")?;
+    let (_, players) = rest.split_once("|player ids|")?;
+
+    let bot_seats = players
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .enumerate()
+        .filter(|(_, p)| p.trim() == "None")
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let human_seat = (0..4).find(|s| !bot_seats.contains(s))?;
+
+    logged_hand(game, bot_seats, human_seat)
+}
+
+fn logged_hand(game: &str, bot_seats: Vec<Player>, human_seat: Player) -> Option<LoggedHand> {
+    let gs = replay(game)?;
+    if !gs.is_terminal() {
+        warn!("skipping hand that didn't finish: {}", game);
+        return None;
+    }
+
+    Some(LoggedHand {
+        gs,
+        bot_seats,
+        scoring_seat: (human_seat + 1) % 4,
+    })
+}
+
+/// Rebuilds the gamestate from its logged string, checking that replaying its
+/// key from a new hand reaches the same state as the decisions are evaluated
+/// that way
+fn replay(game: &str) -> Option<EuchreGameState> {
+    let parsed = EuchreGameState::from(game);
+    let mut gs = Euchre::new_state();
+    for a in parsed.key().iter() {
+        gs.apply_action(*a);
+    }
+
+    if gs.to_string() != parsed.to_string() {
+        warn!("failed to replay logged hand: {}", game);
+        return None;
+    }
+    Some(gs)
+}
+
+/// Product of the candidate to logged probability ratios of the bot's actions,
+/// None if the logged policy had no chance of taking one of the actions
+fn importance_weight(
+    hand: &LoggedHand,
+    candidate: &mut CFRES<EuchreGameState>,
+    logged: &mut CFRES<EuchreGameState>,
+    fallback_depth: usize,
+) -> Option<f64> {
+    let mut gs = Euchre::new_state();
+    let mut decisions = Vec::new();
+    let mut played = Vec::new();
+    for &a in hand.gs.key().iter() {
+        let is_bot_decision = !gs.is_chance_node() && hand.bot_seats.contains(&gs.cur_player());
+        if is_bot_decision && !post_cards_played(&gs, fallback_depth) {
+            decisions.push(gs.clone());
+            played.push(a);
+        }
+        gs.apply_action(a);
+    }
+
+    let p_logged = logged.action_probabilities_batch(&decisions);
+    let p_candidate = candidate.action_probabilities_batch(&decisions);
+    let mut weight = 1.0;
+    for ((a, logged), candidate) in played.into_iter().zip(p_logged).zip(p_candidate) {
+        if logged[a] <= 0.0 {
+            return None;
+        }
+        weight *= candidate[a] / logged[a];
+    }
+
+    Some(weight)
+}
+
+/// Prints the ordinary and weighted importance sampling estimates of the
+/// candidate's average score per hand
+fn print_estimates(samples: &[(f64, f64)]) {
+    let n = samples.len() as f64;
+    let logged_mean = samples.iter().map(|(_, r)| r).sum::<f64>() / n;
+
+    let weighted = samples.iter().map(|(w, r)| w * r).collect::<Vec<_>>();
+    let is_mean = weighted.iter().sum::<f64>() / n;
+    let is_var = weighted.iter().map(|x| (x - is_mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+    let is_stderr = (is_var / n).sqrt();
+
+    let weight_sum = samples.iter().map(|(w, _)| w).sum::<f64>();
+    let weight_sq_sum = samples.iter().map(|(w, _)| w * w).sum::<f64>();
+    let wis_mean = if weight_sum > 0.0 {
+        weighted.iter().sum::<f64>() / weight_sum
+    } else {
+        f64::NAN
+    };
+    // effective number of hands after weighting, low values mean the
+    // candidate is too different from the logged bot to trust the estimate
+    let ess = weight_sum * weight_sum / weight_sq_sum;
+
+    println!("hands:\t{}", samples.len());
+    println!("logged bot score per hand:\t{:.3}", logged_mean);
+    println!(
+        "candidate score per hand (is):\t{:.3} +/- {:.3}",
+        is_mean,
+        1.96 * is_stderr
+    );
+    println!("candidate score per hand (wis):\t{:.3}", wis_mean);
+    println!("effective sample size:\t{:.1}", ess);
+}
+
+#[cfg(test)]
+mod tests {
+    use games::{
+        gamestates::euchre::{Euchre, EuchreGameState},
+        GameState,
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use super::parse_hand;
+
+    fn finished_hand() -> EuchreGameState {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        let mut gs = Euchre::new_state();
+        let mut actions = Vec::new();
+        while !gs.is_terminal() {
+            gs.legal_actions(&mut actions);
+            gs.apply_action(*actions.choose(&mut rng).unwrap());
+        }
+        gs
+    }
+
+    #[test]
+    fn test_parse_hand() {
+        let gs = finished_hand();
+
+        let line = format!(
+            r#"{{"timestamp":"2024-05-01T12:00:00.000000Z","level":"INFO","fields":{{"message":"hand ended","bot":"infostate.three_card_played","human_score":4,"computer_score":6,"gs":"{}","human_players":2,"players":"[None, Some(42), None, Some(7)]"}},"target":"euchre_server","span":{{"game_id":"3f1c","name":"game"}},"spans":[{{"game_id":"3f1c","name":"game"}}]}}"#,
+            gs
+        );
+        let hand = parse_hand(&line).unwrap();
+        assert_eq!(hand.gs.to_string(), gs.to_string());
+        assert_eq!(hand.bot_seats, vec![0, 2]);
+        // 7 is the lowest id, so seat 3's team is scored as the humans
+        assert_eq!(hand.scoring_seat, 0);
+
+        let game_over = r#"{"timestamp":"2024-05-01T12:00:01.000000Z","level":"INFO","fields":{"message":"game over","human_score":4,"computer_score":10,"players":"[None, Some(42), None, Some(7)]"},"target":"euchre_server"}"#;
+        assert!(parse_hand(game_over).is_none());
+        assert!(parse_hand("starting load of initial app state...").is_none());
+
+        let legacy = format!(
+            "[2023-11-02T10:00:00Z INFO  euchre_server] hand ended|id|3f1c|game:|{}|human players:|1|player ids|[Some(7), None, None, None]",
+            gs
+        );
+        let hand = parse_hand(&legacy).unwrap();
+        assert_eq!(hand.bot_seats, vec![1, 2, 3]);
+        assert_eq!(hand.scoring_seat, 1);
+    }
+
+    /// A json `hand ended` line for `gs` with the given players
+    fn hand_line(gs: &str, players: &str) -> String {
+        format!(
+            r#"{{"level":"INFO","fields":{{"message":"hand ended","gs":"{}","players":"{}"}},"target":"euchre_server"}}"#,
+            gs, players
+        )
+    }
+
+    #[test]
+    fn test_parse_hand_invalid() {
+        let gs = finished_hand();
+        let players = "[None, Some(42), None, Some(7)]";
+        assert!(parse_hand(&hand_line(&gs.to_string(), players)).is_some());
+
+        // hands that didn't finish
+        let mut unfinished = Euchre::new_state();
+        for a in gs.key().iter().take(10) {
+            unfinished.apply_action(*a);
+        }
+        assert!(parse_hand(&hand_line(&unfinished.to_string(), players)).is_none());
+
+        // the wrong number of seats, no humans and malformed ids
+        for players in [
+            "[None, Some(42), None]",
+            "[None, None, None, None]",
+            "[None, Some(x), None, Some(7)]",
+            "[None, 42, None, Some(7)]",
+        ] {
+            assert!(parse_hand(&hand_line(&gs.to_string(), players)).is_none());
+        }
+
+        // the message is required
+        let line = hand_line(&gs.to_string(), players).replace("hand ended", "game over");
+        assert!(parse_hand(&line).is_none());
+    }
+}
@@ -1,7 +1,11 @@
 pub mod agent_exploitability;
 pub mod benchmark;
 pub mod config;
+pub mod debug_indexer;
+pub mod distill_bid_policy;
 pub mod estimate_euchre_game_tree;
+pub mod evaluate_from_logs;
 pub mod pass_on_bower;
 pub mod pass_on_bower_alpha;
 pub mod pass_on_bower_cfr;
+pub mod tournament;
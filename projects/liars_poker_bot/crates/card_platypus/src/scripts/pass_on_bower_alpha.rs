@@ -5,7 +5,8 @@ use card_platypus::{
     },
 };
 use games::{
-    gamestates::euchre::{actions::EAction, EuchreGameState},
+    gamestates::euchre::{Euchre, EuchreGameState},
+    labels::labeled,
     resample::ResampleFromInfoState,
     GameState,
 };
@@ -78,7 +79,7 @@ pub fn benchmark_pass_on_bower(num_games: usize) {
                     agent.step(gs)
                 };
 
-                debug!("{}: {}: {}", name, gs, EAction::from(a));
+                debug!("{}: {}: {}", name, gs, labeled(Euchre::NAME, a));
                 gs.apply_action(a);
             }
             for (p, r) in returns.iter_mut().enumerate() {
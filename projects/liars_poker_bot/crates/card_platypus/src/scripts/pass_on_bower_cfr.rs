@@ -1,9 +1,18 @@
-use std::{collections::HashMap, fs, ops::Deref, path::Path};
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Deref,
+    path::Path,
+    time::{Duration, Instant},
+};
 
 use card_platypus::{
     agents::{Agent, Seedable},
     algorithms::cfres::{self, CFRES},
-    algorithms::{open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot},
+    algorithms::{
+        abstraction::EuchreBiddingAbstraction, open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot,
+    },
+    database::WalConfig,
 };
 use clap::{Args, ValueEnum};
 use games::{
@@ -32,7 +41,16 @@ enum Normalizer {
     Lossy,
 }
 
+#[derive(ValueEnum, Copy, Clone, Debug, Deserialize, Default)]
+enum AbstractionType {
+    #[default]
+    None,
+    /// Bucket bidding istates by hand strength
+    Bidding,
+}
+
 #[derive(Args, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PassOnBowerCFRArgs {
     training_iterations: usize,
     #[clap(short, long, default_value_t = 200)]
@@ -48,18 +66,53 @@ pub struct PassOnBowerCFRArgs {
     #[clap(long, default_value_t = false)]
     #[serde(default)]
     single_thread: bool,
+    #[clap(long, default_value_t = false)]
+    #[serde(default)]
+    regret_matching_plus: bool,
     #[clap(long, default_value_t = 0)]
     max_cards_played: usize,
     #[clap(long, value_enum, default_value_t=Normalizer::Lossless)]
     #[serde(default)]
     normalizer: Normalizer,
+    /// Play with the average strategy of only the most recent iterations
+    #[clap(long)]
+    #[serde(default)]
+    strategy_window: Option<usize>,
+    #[clap(long, value_enum, default_value_t=AbstractionType::None)]
+    #[serde(default)]
+    abstraction: AbstractionType,
+    /// Log infostate updates between saves so a crash doesn't lose them, at
+    /// some cost to training speed
+    #[clap(long, default_value_t = false)]
+    #[serde(default)]
+    wal: bool,
+    /// Checkpoint the infostates updated since the last save every
+    /// [`CHECKPOINT_INTERVAL`], and resume from the checkpoint of a run that
+    /// stopped before saving
+    #[clap(long, default_value_t = false)]
+    #[serde(default)]
+    checkpoints: bool,
 }
 
+/// Time between checkpoints when they're enabled
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 fn num_scoring_evaluations_default() -> usize {
     10
 }
 
-pub fn run_pass_on_bower_cfr(args: PassOnBowerCFRArgs) {
+/// Outcome of a training run, scored at the final evaluation
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CFRTrainingResult {
+    pub iterations: usize,
+    pub info_states: usize,
+    /// Nodes touched during this run
+    pub nodes_touched: usize,
+    pub score: f64,
+    pub baseline: f64,
+}
+
+pub fn run_pass_on_bower_cfr(args: PassOnBowerCFRArgs) -> CFRTrainingResult {
     if !args.no_linear_cfr {
         cfres::feature::enable(cfres::feature::LinearCFR);
     } else {
@@ -72,11 +125,18 @@ pub fn run_pass_on_bower_cfr(args: PassOnBowerCFRArgs) {
         cfres::feature::disable(cfres::feature::SingleThread);
     }
 
+    if args.regret_matching_plus {
+        cfres::feature::enable(cfres::feature::RegretMatchingPlus);
+    } else {
+        cfres::feature::disable(cfres::feature::RegretMatchingPlus);
+    }
+
     all_deal_cfr(args)
 }
 
-pub fn all_deal_cfr(args: PassOnBowerCFRArgs) {
+pub fn all_deal_cfr(args: PassOnBowerCFRArgs) -> CFRTrainingResult {
     info!("starting new run of cfr. args {:?}", args);
+    let start_nodes_touched = cfres::nodes_touched::read();
 
     info!("starting creation of CFRES, including hash function");
     let infostate_path = args.weight_file.as_str();
@@ -94,6 +154,24 @@ pub fn all_deal_cfr(args: PassOnBowerCFRArgs) {
         ),
     };
 
+    if let Some(window) = args.strategy_window {
+        alg = alg.with_strategy_window(window);
+    }
+
+    if let AbstractionType::Bidding = args.abstraction {
+        alg = alg.with_abstraction(Box::<EuchreBiddingAbstraction>::default());
+    }
+
+    if args.wal {
+        alg = alg
+            .with_wal(WalConfig::default())
+            .expect("failed to open write-ahead log");
+    }
+
+    if args.checkpoints {
+        alg = alg.with_checkpoints().expect("failed to load checkpoint");
+    }
+
     let loaded_states = alg.num_info_states();
     info!(
         "loaded {} info states from {}",
@@ -138,6 +216,7 @@ pub fn all_deal_cfr(args: PassOnBowerCFRArgs) {
     ];
     assert_eq!(generators.len(), NUM_FACE_UP);
 
+    let mut score = f64::NAN;
     for _ in 0..args.num_scoring_evaluations {
         let iterations_per_card =
             args.training_iterations / args.num_scoring_evaluations / generators.len();
@@ -149,10 +228,18 @@ pub fn all_deal_cfr(args: PassOnBowerCFRArgs) {
             pb.inc(1);
         });
 
-        log_score(&mut alg, worlds.clone(), baseline_score);
+        score = log_score(&mut alg, worlds.clone(), baseline_score);
     }
 
     mp.clear().unwrap();
+
+    CFRTrainingResult {
+        iterations: alg.iterations(),
+        info_states: alg.num_info_states(),
+        nodes_touched: cfres::nodes_touched::read() - start_nodes_touched,
+        score,
+        baseline: baseline_score,
+    }
 }
 
 pub fn train_cfr_shot(
@@ -166,15 +253,24 @@ pub fn train_cfr_shot(
     // print_scored_istates(&mut alg);
 
     const TRAINING_PER_ITERATION: usize = 100;
+    let mut last_checkpoint = Instant::now();
     for _ in 0..training_iterations / TRAINING_PER_ITERATION {
         alg.train(TRAINING_PER_ITERATION);
         pb.inc(TRAINING_PER_ITERATION as u64);
+        if last_checkpoint.elapsed() > CHECKPOINT_INTERVAL {
+            alg.checkpoint().unwrap();
+            last_checkpoint = Instant::now();
+        }
     }
     pb.finish_and_clear();
     alg.save().unwrap();
 }
 
-fn log_score(alg: &mut CFRES<EuchreGameState>, worlds: Vec<EuchreGameState>, baseline_score: f64) {
+fn log_score(
+    alg: &mut CFRES<EuchreGameState>,
+    worlds: Vec<EuchreGameState>,
+    baseline_score: f64,
+) -> f64 {
     let score = score_vs_defender(alg, 1, worlds);
     info!(
         "iteration:\t{}\tnodes touched:\t{}\tinfo_states:\t{}\tscore:\t{}\tbaseline:\t{}",
@@ -184,6 +280,7 @@ fn log_score(alg: &mut CFRES<EuchreGameState>, worlds: Vec<EuchreGameState>, bas
         score,
         baseline_score,
     );
+    score
 }
 
 fn score_vs_defender<A: Agent<EuchreGameState> + Seedable>(
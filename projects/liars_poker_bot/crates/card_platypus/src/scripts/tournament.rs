@@ -0,0 +1,388 @@
+use std::{collections::HashMap, fmt::Write as _, fs};
+
+use card_platypus::agents::agent_from_spec;
+use clap::{Args, ValueEnum};
+use games::{gamestates::euchre::Euchre, get_games, GameState};
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use log::{info, warn};
+
+use super::benchmark::get_rng;
+
+/// z score for a 95% confidence interval
+const Z_95: f64 = 1.96;
+const ELO_BASE: f64 = 1500.0;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Csv,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TournamentArgs {
+    /// Agent specs separated by `;`, e.g. `pimcts(n=50,solver=euchre);random`,
+    /// see `agent_from_spec` for the available agents
+    #[clap(
+        long,
+        value_delimiter = ';',
+        default_value = "cfr(path=/var/lib/card_platypus/infostate.baseline/);pimcts;random"
+    )]
+    agents: Vec<String>,
+    /// Deals played by each pairing, every deal is played once from each seat
+    #[clap(long, default_value_t = 1000)]
+    games: usize,
+    #[clap(long, value_enum, default_value_t = ReportFormat::Markdown)]
+    format: ReportFormat,
+    /// Write the report to a file rather than stdout
+    #[clap(long)]
+    output: Option<String>,
+}
+
+/// Results for `agent` playing against `opponent`
+#[derive(Default, Clone, Copy)]
+struct PairingResult {
+    games: usize,
+    wins: usize,
+    points: f64,
+}
+
+/// Plays every pairing of agents on the same deals and reports win rates and ratings
+pub fn run_tournament(args: TournamentArgs) {
+    let names = args
+        .agents
+        .iter()
+        .map(|a| a.trim().to_string())
+        .unique()
+        .collect_vec();
+    if names.len() < 2 {
+        warn!("need at least 2 agents for a tournament");
+        return;
+    }
+
+    let mut agents = HashMap::new();
+    for name in &names {
+        match agent_from_spec(name) {
+            Ok(agent) => agents.insert(name.clone(), agent),
+            Err(e) => {
+                warn!("failed to create agent {}: {:#}", name, e);
+                return;
+            }
+        };
+    }
+
+    // all pairings play the same deals
+    let deals = get_games(Euchre::game(), args.games, &mut get_rng());
+    let mut results: HashMap<(String, String), PairingResult> = HashMap::new();
+
+    for (a1, a2) in names.iter().cloned().tuple_combinations() {
+        info!("starting play for {} vs {}", a1, a2);
+        let mut agent1 = agents.remove(&a1).unwrap();
+        let mut agent2 = agents.remove(&a2).unwrap();
+
+        let pb = ProgressBar::new((deals.len() * 2) as u64);
+        for deal in &deals {
+            // swap seats so neither agent benefits from the deal
+            for agent1_team in 0..2 {
+                let mut gs = deal.clone();
+                while !gs.is_terminal() {
+                    let a = if gs.cur_player() % 2 == agent1_team {
+                        agent1.step(&gs)
+                    } else {
+                        agent2.step(&gs)
+                    };
+                    gs.apply_action(a);
+                }
+
+                let score = gs.evaluate(agent1_team);
+                record(&mut results, &a1, &a2, score);
+                record(&mut results, &a2, &a1, -score);
+                pb.inc(1);
+            }
+        }
+        pb.finish_and_clear();
+
+        agents.insert(a1, agent1);
+        agents.insert(a2, agent2);
+    }
+
+    let ratings = elo_ratings(&names, &results);
+    let report = match args.format {
+        ReportFormat::Markdown => markdown_report(&names, &results, &ratings),
+        ReportFormat::Csv => csv_report(&names, &results, &ratings),
+    };
+
+    match &args.output {
+        Some(path) => {
+            fs::write(path, report).unwrap();
+            println!("report written to: {}", path);
+        }
+        None => println!("{}", report),
+    }
+}
+
+fn record(
+    results: &mut HashMap<(String, String), PairingResult>,
+    agent: &str,
+    opponent: &str,
+    score: f64,
+) {
+    let r = results
+        .entry((agent.to_string(), opponent.to_string()))
+        .or_default();
+    r.games += 1;
+    r.points += score;
+    if score > 0.0 {
+        r.wins += 1;
+    }
+}
+
+/// Wilson score interval for a win rate
+fn confidence_interval(wins: usize, games: usize) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    (center - margin, center + margin)
+}
+
+/// Fits Bradley-Terry strengths to the head to head results and converts them
+/// to the Elo scale, centered on [`ELO_BASE`]
+fn elo_ratings(
+    names: &[String],
+    results: &HashMap<(String, String), PairingResult>,
+) -> HashMap<String, f64> {
+    // half a win and loss in every pairing keeps undefeated agents finite
+    const PRIOR: f64 = 0.5;
+    const ITERATIONS: usize = 1000;
+
+    let wins = |a, b| pairing(results, a, b).map_or(0.0, |r| r.wins as f64) + PRIOR;
+    let games = |a, b| pairing(results, a, b).map_or(0.0, |r| r.games as f64) + 2.0 * PRIOR;
+
+    let mut strength: HashMap<&str, f64> = names.iter().map(|n| (n.as_str(), 1.0)).collect();
+    for _ in 0..ITERATIONS {
+        let mut next = HashMap::new();
+        for a in names.iter().map(String::as_str) {
+            let others = names.iter().map(String::as_str).filter(|b| *b != a);
+            let total_wins: f64 = others.clone().map(|b| wins(a, b)).sum();
+            let denom: f64 = others
+                .map(|b| games(a, b) / (strength[a] + strength[b]))
+                .sum();
+            next.insert(a, total_wins / denom);
+        }
+
+        // normalize to a geometric mean of 1
+        let log_mean = next.values().map(|s| s.ln()).sum::<f64>() / names.len() as f64;
+        strength = next
+            .into_iter()
+            .map(|(a, s)| (a, s / log_mean.exp()))
+            .collect();
+    }
+
+    strength
+        .into_iter()
+        .map(|(a, s)| (a.to_string(), ELO_BASE + 400.0 * s.log10()))
+        .collect()
+}
+
+fn pairing<'a>(
+    results: &'a HashMap<(String, String), PairingResult>,
+    agent: &str,
+    opponent: &str,
+) -> Option<&'a PairingResult> {
+    results.get(&(agent.to_string(), opponent.to_string()))
+}
+
+/// Per agent totals, sorted by rating
+fn standings(
+    names: &[String],
+    results: &HashMap<(String, String), PairingResult>,
+    ratings: &HashMap<String, f64>,
+) -> Vec<(String, PairingResult)> {
+    names
+        .iter()
+        .map(|a| {
+            let total = names.iter().filter_map(|b| pairing(results, a, b)).fold(
+                PairingResult::default(),
+                |acc, r| PairingResult {
+                    games: acc.games + r.games,
+                    wins: acc.wins + r.wins,
+                    points: acc.points + r.points,
+                },
+            );
+            (a.clone(), total)
+        })
+        .sorted_by(|(a, _), (b, _)| ratings[b].total_cmp(&ratings[a]))
+        .collect()
+}
+
+fn markdown_report(
+    names: &[String],
+    results: &HashMap<(String, String), PairingResult>,
+    ratings: &HashMap<String, f64>,
+) -> String {
+    let mut out = String::new();
+    writeln!(out, "## Standings\n").unwrap();
+    writeln!(
+        out,
+        "| agent | elo | games | wins | win rate | 95% ci | avg points |"
+    )
+    .unwrap();
+    writeln!(out, "|---|---|---|---|---|---|---|").unwrap();
+    for (a, r) in standings(names, results, ratings) {
+        let (lo, hi) = confidence_interval(r.wins, r.games);
+        writeln!(
+            out,
+            "| {} | {:.0} | {} | {} | {:.3} | {:.3} - {:.3} | {:.3} |",
+            a,
+            ratings[&a],
+            r.games,
+            r.wins,
+            r.wins as f64 / r.games as f64,
+            lo,
+            hi,
+            r.points / r.games as f64
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\n## Head to head\n").unwrap();
+    writeln!(
+        out,
+        "| agent | opponent | games | wins | win rate | 95% ci | avg points |"
+    )
+    .unwrap();
+    writeln!(out, "|---|---|---|---|---|---|---|").unwrap();
+    for (a, b) in names.iter().tuple_combinations() {
+        let r = pairing(results, a, b).unwrap();
+        let (lo, hi) = confidence_interval(r.wins, r.games);
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {:.3} | {:.3} - {:.3} | {:.3} |",
+            a,
+            b,
+            r.games,
+            r.wins,
+            r.wins as f64 / r.games as f64,
+            lo,
+            hi,
+            r.points / r.games as f64
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+/// One row per ordered pairing, the standings can be derived from these
+fn csv_report(
+    names: &[String],
+    results: &HashMap<(String, String), PairingResult>,
+    ratings: &HashMap<String, f64>,
+) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "agent,opponent,elo,games,wins,win_rate,ci_low,ci_high,avg_points"
+    )
+    .unwrap();
+    for (a, b) in names.iter().cartesian_product(names.iter()) {
+        let Some(r) = pairing(results, a, b) else {
+            continue;
+        };
+        let (lo, hi) = confidence_interval(r.wins, r.games);
+        writeln!(
+            out,
+            // specs can contain commas, so the names are quoted
+            "\"{}\",\"{}\",{:.1},{},{},{},{},{},{}",
+            a,
+            b,
+            ratings[a],
+            r.games,
+            r.wins,
+            r.wins as f64 / r.games as f64,
+            lo,
+            hi,
+            r.points / r.games as f64
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{confidence_interval, elo_ratings, record, PairingResult, ELO_BASE};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_confidence_interval() {
+        assert_eq!(confidence_interval(0, 0), (0.0, 1.0));
+
+        let (low, high) = confidence_interval(50, 100);
+        assert_close(low, 0.4038);
+        assert_close(high, 0.5962);
+
+        // stays in 0..1 at the extremes, unlike the normal approximation
+        let (low, high) = confidence_interval(0, 10);
+        assert_close(low, 0.0);
+        assert!(high > 0.0 && high < 1.0);
+        let (low, high) = confidence_interval(10, 10);
+        assert!(low > 0.0 && low < 1.0);
+        assert_close(high, 1.0);
+
+        // narrows with more games
+        let (low_more, high_more) = confidence_interval(500, 1000);
+        assert!(high_more - low_more < 0.6 * (0.5962 - 0.4038));
+    }
+
+    fn results(
+        pairings: &[(&str, &str, usize, usize)],
+    ) -> HashMap<(String, String), PairingResult> {
+        let mut results = HashMap::new();
+        for &(a, b, wins, losses) in pairings {
+            for _ in 0..wins {
+                record(&mut results, a, b, 1.0);
+                record(&mut results, b, a, -1.0);
+            }
+            for _ in 0..losses {
+                record(&mut results, a, b, -1.0);
+                record(&mut results, b, a, 1.0);
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn test_elo_ratings() {
+        let names = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        // even results rate everyone the same
+        let even = results(&[("a", "b", 50, 50), ("a", "c", 50, 50), ("b", "c", 50, 50)]);
+        for rating in elo_ratings(&names, &even).values() {
+            assert_close(*rating, ELO_BASE);
+        }
+
+        // two agents split the difference around the base, with the prior
+        // adding half a win to each side
+        let names = vec!["a".to_string(), "b".to_string()];
+        let ratings = elo_ratings(&names, &results(&[("a", "b", 75, 25)]));
+        assert_close(ratings["a"], ELO_BASE + 94.281);
+        assert_close(ratings["b"], ELO_BASE - 94.281);
+
+        // undefeated agents still get a finite rating
+        let ratings = elo_ratings(&names, &results(&[("a", "b", 10, 0)]));
+        assert!(ratings["a"].is_finite() && ratings["a"] > ratings["b"]);
+    }
+}
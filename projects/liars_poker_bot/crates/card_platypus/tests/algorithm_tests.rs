@@ -3,11 +3,15 @@ use card_platypus::algorithms::{
     cfres::{self, CFRES},
     exploitability::exploitability,
     ismcts::Evaluator,
-    open_hand_solver::OpenHandSolver,
+    open_hand_solver::{OpenHandSolver, Optimizations},
 };
 use games::{
-    gamestates::{bluff::Bluff, euchre::Euchre, kuhn_poker::KuhnPoker},
-    get_games, GameState,
+    gamestates::{
+        bluff::Bluff,
+        euchre::{Euchre, EuchreGameState},
+        kuhn_poker::KuhnPoker,
+    },
+    get_games, GameState, Team,
 };
 use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, SeedableRng};
 use rayon::prelude::*;
@@ -38,6 +42,61 @@ fn test_alg_open_hand_solver_euchre() {
     });
 }
 
+/// Confirm the pruning and move ordering of the open hand solver don't change the
+/// values compared to a plain minimax search
+#[test]
+fn test_alg_open_hand_solver_matches_minimax() {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(52);
+    let games = get_games(Euchre::game(), 200, &mut rng);
+    let solver = OpenHandSolver::new(Optimizations::new_euchre());
+
+    games.into_par_iter().enumerate().for_each(|(i, mut gs)| {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(i as u64);
+        let mut actions = Vec::new();
+        // minimax is only feasible for the last 3 tricks
+        while !gs.is_terminal() && cards_left(&gs) > 12 {
+            gs.legal_actions(&mut actions);
+            gs.apply_action(*actions.choose(&mut rng).unwrap());
+        }
+
+        while !gs.is_terminal() {
+            let player = gs.cur_player();
+            let v = solver.clone().evaluate_player(&gs, player);
+            let expected = minimax(&mut gs, Team::from(player));
+            assert_eq!(v, expected, "Different evaluations: {}: {}", i, gs);
+            gs.legal_actions(&mut actions);
+            gs.apply_action(*actions.choose(&mut rng).unwrap());
+        }
+    });
+}
+
+fn cards_left(gs: &EuchreGameState) -> usize {
+    (0..4).map(|p| gs.get_hand(p).len()).sum()
+}
+
+/// Unpruned search of every line of play
+fn minimax(gs: &mut EuchreGameState, maximizing_team: Team) -> f64 {
+    if gs.is_terminal() {
+        return gs.evaluate(maximizing_team as usize);
+    }
+
+    let mut actions = Vec::new();
+    gs.legal_actions(&mut actions);
+    let maximizing = Team::from(gs.cur_player()) == maximizing_team;
+    let values = actions.into_iter().map(|a| {
+        gs.apply_action(a);
+        let v = minimax(gs, maximizing_team);
+        gs.undo();
+        v
+    });
+
+    if maximizing {
+        values.fold(f64::NEG_INFINITY, f64::max)
+    } else {
+        values.fold(f64::INFINITY, f64::min)
+    }
+}
+
 #[test]
 fn test_cfr_euchre() {
     cfres::feature::enable(cfres::feature::LinearCFR);
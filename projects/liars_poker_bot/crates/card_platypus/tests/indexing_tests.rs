@@ -1,6 +1,13 @@
-use card_platypus::algorithms::cfres::{DepthChecker, EuchreDepthChecker};
+use card_platypus::{
+    algorithms::cfres::{DepthChecker, EuchreDepthChecker, InfoState},
+    database::NodeStore,
+};
 use games::{
-    gamestates::euchre::{actions::EAction, ismorphic::normalize_euchre_istate, Euchre},
+    gamestates::euchre::{
+        actions::EAction, ismorphic::normalize_euchre_istate, processors::post_cards_played,
+        EPhase, Euchre,
+    },
+    istate::NormalizedAction,
     translate_istate, GameState,
 };
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
@@ -40,3 +47,62 @@ fn test_euchre_indexing() {
         }
     }
 }
+
+#[test]
+fn test_euchre_sharded_node_store() {
+    let max_cards_played = 2;
+    let mut store = NodeStore::new_euchre(None, max_cards_played).unwrap();
+    assert_eq!(store.num_shards(), max_cards_played + 1);
+
+    let depth_checker = EuchreDepthChecker { max_cards_played };
+    let info = InfoState::new(vec![NormalizedAction::new_from_id(0)]);
+    let mut actions = Vec::new();
+    let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+    let mut bid_keys = Vec::new();
+    let mut play_keys = Vec::new();
+
+    for _ in 0..1_000 {
+        let mut gs = Euchre::new_state();
+
+        while !(gs.is_terminal() || depth_checker.is_max_depth(&gs)) {
+            if !gs.is_chance_node() {
+                let key = gs.istate_key(gs.cur_player());
+                let expected_shard = match gs.phase() {
+                    EPhase::Play => {
+                        (1..max_cards_played)
+                            .filter(|&n| post_cards_played(&gs, n))
+                            .count()
+                            + 1
+                    }
+                    _ => 0,
+                };
+                assert_eq!(
+                    store.shard_of(&key),
+                    expected_shard,
+                    "wrong shard for {:?}",
+                    translate_istate!(key, EAction)
+                );
+
+                store.put(&key, &info);
+                match expected_shard {
+                    0 => bid_keys.push(key),
+                    _ => play_keys.push(key),
+                }
+            }
+
+            gs.legal_actions(&mut actions);
+            let a = actions.choose(&mut rng).unwrap();
+            gs.apply_action(*a);
+        }
+    }
+
+    let stats = store.shard_stats();
+    assert!(stats.iter().all(|s| s.unwrap().writes > 0));
+
+    // only the bidding istates are left after dropping the play shards
+    store.drop_shards_from(1).unwrap();
+    assert!(bid_keys.iter().all(|k| store.get(k).is_some()));
+    assert!(play_keys.iter().all(|k| store.get(k).is_none()));
+    assert_eq!(store.shard_stats()[0].unwrap().hits, bid_keys.len());
+    assert_eq!(store.shard_stats()[1], None);
+}
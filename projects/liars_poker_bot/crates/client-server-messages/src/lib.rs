@@ -1,15 +1,42 @@
-use games::{gamestates::euchre::EuchreGameState, Action};
+use std::time::Duration;
+
+use games::{
+    gamestates::euchre::{actions::Suit, Euchre, EuchreGameState},
+    resample::ResampleFromInfoState,
+    Action, GameState,
+};
+use rand::thread_rng;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Version of the messages in this crate, bumped whenever a change means an
+/// older client or server can't read them
+///
+/// Clients send it in the [`PROTOCOL_VERSION_HEADER`] of api requests and
+/// with [`WsClientMessage::Resume`], the server rejects other versions so the
+/// client can ask the user to refresh rather than failing to decode a reply.
+pub const PROTOCOL_VERSION: u32 = 3;
+/// Header api requests carry their client's [`PROTOCOL_VERSION`] in
+pub const PROTOCOL_VERSION_HEADER: &str = "x-protocol-version";
+/// Header requests made as a player carry the [`PlayerAccount::token`] of
+/// their player id in, the server only acts or shows a hand for the account's
+/// owner
+pub const PLAYER_TOKEN_HEADER: &str = "x-player-token";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum GameProcessingState {
     /// When min_players has been specified but there aren't that many players in the game yet
     WaitingPlayerJoin {
         min_players: usize,
     },
     WaitingHumanMove,
-    WaitingMachineMoves,
+    /// A computer player is picking its move, bot moves can be paced so this
+    /// lasts a moment
+    WaitingMachineMoves {
+        /// Seat of the computer player that's thinking
+        acting_seat: usize,
+    },
     WaitingTrickClear {
         ready_players: Vec<usize>,
     },
@@ -17,15 +44,71 @@ pub enum GameProcessingState {
         ready_players: Vec<usize>,
     },
     GameOver,
+    /// Nobody acted for longer than the server's idle timeout, the game can't be resumed
+    Abandoned,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct GameData {
     pub gs: String,
+    /// Player in each seat, None for seats played by the computer. Seats 0
+    /// and 2 are partners, as are 1 and 3
     pub players: Vec<Option<usize>>,
+    /// Score of the team with the lowest human player id, see
+    /// [`GameData::human_team`]
     pub human_score: usize,
+    /// Score of the other team
     pub computer_score: usize,
     pub display_state: GameProcessingState,
+    /// Ids of the people watching the game, they only see the public cards
+    #[serde(default)]
+    pub spectators: Vec<usize>,
+    /// Unix time in seconds of the last player action, set by the server
+    #[serde(default)]
+    pub last_activity: u64,
+    /// Code other players use to join a private game, private games aren't
+    /// listed in the lobby. Only sent to the game's players
+    #[serde(default)]
+    pub join_code: Option<String>,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    /// Name of the bot version the game plays against, set by the server and
+    /// hidden from clients
+    #[serde(default)]
+    pub bot_version: Option<String>,
+    /// Incremented by the server each time the state changes, a reconnecting
+    /// client sends the last one it saw to be sent the states it missed
+    #[serde(default)]
+    pub revision: u64,
+    /// The move that can be taken back with [`GameAction::RequestTakeback`],
+    /// set by the server
+    #[serde(default)]
+    pub takeback: Option<Takeback>,
+    /// Finished hands, oldest first. Left out of client views, they're
+    /// returned by `GET /api/{game_id}/history`
+    #[serde(default)]
+    pub hands: Vec<HandResult>,
+}
+
+/// A human's last move, they can take it back until another human acts or a
+/// bot moves, the bots' bids give away as much about their hands as their cards
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Takeback {
+    pub player_id: usize,
+    /// Actions in the gamestate before the move, it's rewound to this many
+    pub num_actions: usize,
+}
+
+/// See [`GameData::human_team`]
+fn human_team(players: &[Option<usize>]) -> Option<usize> {
+    players
+        .iter()
+        .enumerate()
+        .filter_map(|(seat, p)| p.map(|id| (id, seat)))
+        .min()
+        .map(|(_, seat)| seat % 2)
 }
 
 impl GameData {
@@ -36,15 +119,75 @@ impl GameData {
             human_score: 0,
             computer_score: 0,
             display_state: GameProcessingState::WaitingPlayerJoin { min_players },
+            spectators: Vec::new(),
+            last_activity: 0,
+            join_code: None,
+            difficulty: Difficulty::default(),
+            bot_version: None,
+            revision: 0,
+            takeback: None,
+            hands: Vec::new(),
+        }
+    }
+
+    /// Team `human_score` is kept for, 0 for seats 0 and 2 and 1 for seats 1
+    /// and 3, None if there are no human players
+    ///
+    /// With humans on only one team it's their team. With humans on both it's
+    /// the team of the lowest player id, which doesn't change as the seats
+    /// rotate between hands.
+    pub fn human_team(&self) -> Option<usize> {
+        human_team(&self.players)
+    }
+
+    /// Scores of `player_id`'s team and of their opponents, None if they
+    /// aren't in the game
+    pub fn team_scores(&self, player_id: usize) -> Option<(usize, usize)> {
+        let seat = self.players.iter().position(|p| *p == Some(player_id))?;
+        if Some(seat % 2) == self.human_team() {
+            Some((self.human_score, self.computer_score))
+        } else {
+            Some((self.computer_score, self.human_score))
         }
     }
 
     pub fn to_state(&self) -> EuchreGameState {
         EuchreGameState::from(self.gs.as_str())
     }
+
+    /// The game as `player_id` is allowed to see it, this is all the server
+    /// should send to a client
+    ///
+    /// Players see their own hand and the public cards, anyone else, including
+    /// spectators, only sees the public cards. The other cards are replaced
+    /// with random placeholders consistent with the public actions, so `gs`
+    /// still parses as a full gamestate. Placeholders change between calls.
+    pub fn client_view(&self, player_id: Option<usize>) -> GameData {
+        let gs = self.to_state();
+        let seat = player_id.and_then(|id| self.players.iter().position(|p| *p == Some(id)));
+
+        let view = if gs.is_chance_node() {
+            // games are only stored after the deal, but don't leak a partial one
+            Euchre::new_state()
+        } else {
+            match seat {
+                Some(seat) => gs.resample_from_istate(seat, &mut thread_rng()),
+                None => gs.resample_public(&mut thread_rng()),
+            }
+        };
+
+        GameData {
+            gs: view.to_string(),
+            join_code: seat.and(self.join_code.clone()),
+            bot_version: None,
+            hands: Vec::new(),
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct NewGameResponse {
     pub id: String,
 }
@@ -55,30 +198,59 @@ impl NewGameResponse {
     }
 }
 
+/// How strong the computer players are
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum Difficulty {
+    /// Plays random legal moves
+    Random,
+    /// Takes every trick it can and only calls trump when forced to
+    Greedy,
+    /// Searches a handful of possible deals for each move
+    PimctsSmall,
+    /// The trained CFR policy, with a full search of possible deals late in the hand
+    #[default]
+    Full,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct NewGameRequest {
     pub player_id: usize,
     pub min_players: usize,
+    #[serde(default)]
+    pub difficulty: Difficulty,
 }
 
 impl NewGameRequest {
-    pub fn new(player_id: usize, min_players: usize) -> Self {
+    pub fn new(player_id: usize, min_players: usize, difficulty: Difficulty) -> Self {
         Self {
             player_id,
             min_players,
+            difficulty,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum GameAction {
-    TakeAction(Action),
+    TakeAction(#[cfg_attr(feature = "openapi", schema(value_type = u8))] Action),
     ReadyTrickClear,
     ReadyBidClear,
+    /// Join in the empty seat across from the first human, or any empty
+    /// seat if that one's taken
     RegisterPlayer,
+    /// Join in a specific empty seat, to choose a partner
+    TakeSeat(usize),
+    /// Watch the game without playing, a player in the game can't spectate it
+    Spectate,
+    /// Undo the player's last move, see [`GameData::takeback`]
+    RequestTakeback,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ActionRequest {
     pub player_id: usize,
     pub action: GameAction,
@@ -89,3 +261,613 @@ impl ActionRequest {
         Self { player_id, action }
     }
 }
+
+/// Ends the game early, the other team is awarded the win
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResignRequest {
+    pub player_id: usize,
+}
+
+impl ResignRequest {
+    pub fn new(player_id: usize) -> Self {
+        Self { player_id }
+    }
+}
+
+/// Most characters in a feedback message
+pub const MAX_FEEDBACK_LEN: usize = 1000;
+/// Most actions that can be attached to feedback, clients attach the game's
+/// last ones
+pub const MAX_FEEDBACK_ACTIONS: usize = 24;
+
+/// A report about a game, e.g. a bot play that looked wrong
+///
+/// Feedback is anonymous, the game's recent actions are attached so the
+/// report can be reproduced without knowing who sent it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FeedbackRequest {
+    pub game_id: String,
+    /// At most [`MAX_FEEDBACK_LEN`] characters
+    pub message: String,
+    /// The game's last actions, oldest first, at most [`MAX_FEEDBACK_ACTIONS`]
+    // a Vec<u8> would be documented as binary rather than a list
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<u32>))]
+    pub actions: Vec<Action>,
+}
+
+impl FeedbackRequest {
+    /// Attaches the last [`MAX_FEEDBACK_ACTIONS`] actions of `gd`'s game,
+    /// cards the player couldn't see are the placeholders from
+    /// [`GameData::client_view`]
+    pub fn new(game_id: String, message: String, gd: &GameData) -> Self {
+        let history = gd.to_state().key();
+        let skip = history.len().saturating_sub(MAX_FEEDBACK_ACTIONS);
+        Self {
+            game_id,
+            message,
+            actions: history.into_iter().skip(skip).collect(),
+        }
+    }
+}
+
+/// A public game waiting for players to join, as listed in the lobby
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OpenGame {
+    pub id: String,
+    /// Humans already in the game
+    pub num_players: usize,
+    pub min_players: usize,
+    /// Unix time in seconds the game started waiting for players
+    pub waiting_since: u64,
+}
+
+/// Join the oldest open game in the lobby, or create one if there aren't any
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QuickMatchRequest {
+    pub player_id: usize,
+}
+
+impl QuickMatchRequest {
+    pub fn new(player_id: usize) -> Self {
+        Self { player_id }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PrivateGameResponse {
+    pub id: String,
+    /// Share this with the other player so they can join
+    pub join_code: String,
+}
+
+/// Join a private game with the code its creator shared
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct JoinRequest {
+    pub player_id: usize,
+    pub join_code: String,
+    /// Seat to join in, any empty one if not set
+    #[serde(default)]
+    pub seat: Option<usize>,
+}
+
+impl JoinRequest {
+    pub fn new(player_id: usize, join_code: String) -> Self {
+        Self {
+            player_id,
+            join_code,
+            seat: None,
+        }
+    }
+}
+
+/// Claim a player id, the returned token proves ownership of its stats
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewPlayerRequest {
+    pub player_id: usize,
+}
+
+impl NewPlayerRequest {
+    pub fn new(player_id: usize) -> Self {
+        Self { player_id }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PlayerAccount {
+    pub player_id: usize,
+    /// Secret issued by the server, needed to read the player's stats
+    pub token: String,
+}
+
+/// Results of a player's finished games against the bot
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PlayerStats {
+    pub games_played: usize,
+    pub wins: usize,
+    /// Elo-like rating, updated as if each game was against a bot with a fixed
+    /// rating
+    pub rating: f64,
+}
+
+impl PlayerStats {
+    pub fn new(rating: f64) -> Self {
+        Self {
+            games_played: 0,
+            wins: 0,
+            rating,
+        }
+    }
+
+    /// Share of games won, 0 if no games have been played
+    pub fn win_rate(&self) -> f64 {
+        if self.games_played == 0 {
+            return 0.0;
+        }
+        self.wins as f64 / self.games_played as f64
+    }
+}
+
+/// The bot's opinion of one legal action
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ActionAnalysis {
+    #[cfg_attr(feature = "openapi", schema(value_type = u8))]
+    pub action: Action,
+    /// Probability the bot plays the action
+    pub probability: f64,
+    /// Expected points for the player's team after the action
+    pub ev: f64,
+}
+
+/// A human decision replayed through the bot
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MoveAnalysis {
+    /// Index of the move's hand in the game's [`MatchHistory`], the unfinished
+    /// hand of a resigned game comes after the finished ones
+    pub hand: usize,
+    /// What the player knew before the move, in the same format as the game state
+    pub istate: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = u8))]
+    pub action: Action,
+    /// Every legal action, in the order of the game's legal actions
+    pub actions: Vec<ActionAnalysis>,
+    /// Expected points given up compared to the best action, 0 if the
+    /// player's action was the best
+    pub ev_loss: f64,
+}
+
+/// The player's moves in every hand of a finished game, oldest first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GameAnalysis {
+    pub moves: Vec<MoveAnalysis>,
+}
+
+/// How a finished hand went, the teams are those of [`GameData::human_score`]
+/// and [`GameData::computer_score`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HandResult {
+    /// Player in each seat during the hand, seat 3 dealt. The seats rotate
+    /// between hands
+    pub players: Vec<Option<usize>>,
+    /// Seat that called trump
+    pub caller: usize,
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub trump: Suit,
+    /// Tricks taken, the hand ends as soon as its result is decided so they
+    /// can add up to fewer than 5
+    pub human_tricks: u8,
+    pub computer_tricks: u8,
+    /// Points scored in the hand, only one of the teams scores
+    pub human_points: usize,
+    pub computer_points: usize,
+    /// The team that called trump took fewer than 3 tricks
+    pub euchred: bool,
+    /// The hand's gamestate with every player's cards, kept by the server to
+    /// analyse the game. Left out of [`MatchHistory`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gs: Option<String>,
+}
+
+impl HandResult {
+    /// The result of a finished hand, None if it isn't over
+    pub fn new(
+        gs: &EuchreGameState,
+        players: Vec<Option<usize>>,
+        human_team: usize,
+    ) -> Option<Self> {
+        if !gs.is_terminal() {
+            return None;
+        }
+        let (trump, caller) = gs.trump()?;
+        let computer_team = (human_team + 1) % 2;
+        let tricks = gs.trick_score();
+
+        Some(Self {
+            players,
+            caller,
+            trump,
+            human_tricks: tricks[human_team],
+            computer_tricks: tricks[computer_team],
+            human_points: gs.evaluate(human_team).max(0.0) as usize,
+            computer_points: gs.evaluate(computer_team).max(0.0) as usize,
+            euchred: gs.evaluate(caller) <= 0.0,
+            gs: Some(gs.to_string()),
+        })
+    }
+
+    /// The result without the cards the players didn't show, for clients
+    pub fn public(&self) -> Self {
+        Self {
+            gs: None,
+            ..self.clone()
+        }
+    }
+
+    /// Whether `player_id` was on the team of `human_points`, None if they
+    /// didn't play the hand
+    pub fn on_human_team(&self, player_id: usize) -> Option<bool> {
+        let seat = self.players.iter().position(|p| *p == Some(player_id))?;
+        Some(Some(seat % 2) == human_team(&self.players))
+    }
+}
+
+/// Every finished hand of a game, returned by `GET /api/{game_id}/history`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MatchHistory {
+    pub hands: Vec<HandResult>,
+}
+
+/// The server's protocol version, returned by `GET /api/version`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VersionResponse {
+    /// The [`PROTOCOL_VERSION`] the server was built with, clients built with
+    /// another version need to be reloaded
+    pub protocol_version: u32,
+    /// Version of the server's crate, for display
+    pub server_version: String,
+}
+
+/// Why the server rejected a request, clients decide how to recover from this
+/// rather than the message
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ErrorCode {
+    /// The game doesn't exist, or the server has dropped it after it ended
+    GameNotFound,
+    /// Something other than a game doesn't exist, e.g. a join code or account
+    NotFound,
+    /// The request doesn't make sense, e.g. a malformed game id or a seat that
+    /// doesn't exist
+    InvalidRequest,
+    /// The move isn't legal with the player's hand
+    IllegalMove,
+    /// The request doesn't fit the game's current state, the client's copy of
+    /// the game is out of date
+    OutOfTurn,
+    /// The player isn't seated in the game
+    NotInGame,
+    /// The seat is taken or the game is full
+    SeatTaken,
+    /// The request conflicts with the server's state, e.g. analysing a game
+    /// that isn't over or claiming a claimed player id
+    Conflict,
+    /// The player's token is wrong
+    Unauthorized,
+    /// The client is sending too quickly
+    RateLimited,
+    PayloadTooLarge,
+    /// The body couldn't be read or isn't valid for the endpoint
+    InvalidPayload,
+    /// The client was built for another [`PROTOCOL_VERSION`]
+    VersionMismatch,
+    /// The bot couldn't be reached to make or analyse a move
+    BotUnavailable,
+    Internal,
+}
+
+/// Body of every error response from the api
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiError {
+    pub code: ErrorCode,
+    /// Explanation for the player or developer, not meant to be parsed
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Most characters in a chat message
+pub const MAX_CHAT_LEN: usize = 200;
+
+/// Canned reactions, sent with a click rather than typed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emote {
+    NiceHand,
+    GoodGame,
+    Oops,
+    Thanks,
+    Hurry,
+}
+
+impl Emote {
+    pub const ALL: [Emote; 5] = [
+        Emote::NiceHand,
+        Emote::GoodGame,
+        Emote::Oops,
+        Emote::Thanks,
+        Emote::Hurry,
+    ];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            Emote::NiceHand => "Nice hand!",
+            Emote::GoodGame => "Good game",
+            Emote::Oops => "Oops",
+            Emote::Thanks => "Thanks!",
+            Emote::Hurry => "Hurry up!",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ChatContent {
+    /// Free text, the server filters profanity and rejects messages longer
+    /// than [`MAX_CHAT_LEN`]
+    Text(String),
+    Emote(Emote),
+    /// Sent by the server when the player takes back a move, clients can't
+    /// send it
+    TookBack,
+}
+
+/// A chat message as the server relays it to everyone following the game
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub player_id: usize,
+    /// Seat of the sender when they sent it
+    pub seat: usize,
+    pub content: ChatContent,
+    /// Seconds since the unix epoch
+    pub sent_at: u64,
+}
+
+/// How often each side of the game websocket sends a heartbeat
+pub const WS_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long either side waits without hearing anything before closing the websocket
+pub const WS_CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Messages sent by the client on the `/ws/` websocket, as json text frames
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum WsClientMessage {
+    /// Start receiving the game's state each time it changes, the current
+    /// state is sent immediately
+    ///
+    /// Only the cards `player_id` is entitled to see are sent, without one
+    /// the game is seen as a spectator. A `player_id` needs its account's
+    /// `token`.
+    Subscribe {
+        game_id: String,
+        #[serde(default)]
+        player_id: Option<usize>,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    Unsubscribe {
+        game_id: String,
+    },
+    /// Rejoin a seat after reconnecting, the states after `revision` are sent
+    /// in order and then the game is followed as with `Subscribe`
+    ///
+    /// If the server no longer has all the missed states only the current
+    /// one is sent. A `protocol_version` other than the server's is answered
+    /// with [`WsServerMessage::VersionMismatch`].
+    Resume {
+        game_id: String,
+        player_id: usize,
+        /// [`PlayerAccount::token`] of `player_id`
+        token: String,
+        /// Last [`GameData::revision`] the client saw
+        revision: u64,
+        protocol_version: u32,
+    },
+    /// Send a chat message to everyone following the game, as the player
+    /// the game was subscribed or resumed as. Only players in the game can chat
+    Chat {
+        game_id: String,
+        content: ChatContent,
+    },
+    /// Heartbeat, answered with a [`WsServerMessage::Pong`]
+    Ping,
+}
+
+/// Messages sent by the server on the `/ws/` websocket, as json text frames
+///
+/// The server also sends websocket ping frames every [`WS_HEARTBEAT_INTERVAL`]
+/// and closes the connection if nothing is received for [`WS_CLIENT_TIMEOUT`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum WsServerMessage {
+    GameData {
+        game_id: String,
+        data: GameData,
+    },
+    Pong,
+    Error(String),
+    /// The client was built for another protocol version and needs to be
+    /// reloaded, the server's version is included
+    VersionMismatch {
+        protocol_version: u32,
+    },
+    /// A new chat message in a subscribed game
+    Chat {
+        game_id: String,
+        message: ChatMessage,
+    },
+    /// The game's recent chat messages, oldest first, sent after subscribing
+    ChatHistory {
+        game_id: String,
+        messages: Vec<ChatMessage>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use games::{
+        actions,
+        gamestates::euchre::{Euchre, EuchreGameState},
+        GameState,
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::{ApiError, Difficulty, ErrorCode, GameData, GameProcessingState, HandResult};
+
+    fn game(gs: &EuchreGameState) -> GameData {
+        GameData {
+            gs: gs.to_string(),
+            players: vec![Some(7), None, Some(42), None],
+            human_score: 0,
+            computer_score: 0,
+            display_state: GameProcessingState::WaitingHumanMove,
+            spectators: vec![3],
+            last_activity: 0,
+            join_code: Some("ABC234".to_string()),
+            difficulty: Difficulty::Greedy,
+            bot_version: Some("infostate.three_card_played".to_string()),
+            revision: 3,
+            takeback: None,
+            hands: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_team_scores() {
+        let mut data = game(&Euchre::new_state());
+        data.human_score = 6;
+        data.computer_score = 4;
+        assert_eq!(data.human_team(), Some(0));
+        assert_eq!(data.team_scores(42), Some((6, 4)));
+        assert_eq!(data.team_scores(3), None);
+
+        // the team of the lowest id keeps the human score as seats rotate
+        data.players = vec![Some(7), Some(42), None, Some(5)];
+        assert_eq!(data.human_team(), Some(1));
+        assert_eq!(data.team_scores(5), Some((6, 4)));
+        assert_eq!(data.team_scores(42), Some((6, 4)));
+        assert_eq!(data.team_scores(7), Some((4, 6)));
+        data.players.rotate_left(1);
+        assert_eq!(data.human_team(), Some(0));
+        assert_eq!(data.team_scores(7), Some((4, 6)));
+
+        data.players = vec![None; 4];
+        assert_eq!(data.human_team(), None);
+    }
+
+    #[test]
+    fn test_client_view() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        for _ in 0..20 {
+            let mut gs = Euchre::new_state();
+            while gs.is_chance_node() {
+                let a = *actions!(gs).choose(&mut rng).unwrap();
+                gs.apply_action(a);
+            }
+            check_client_view(gs, &mut rng);
+        }
+    }
+
+    fn check_client_view(mut gs: EuchreGameState, rng: &mut StdRng) {
+        while !gs.is_terminal() {
+            let data = game(&gs);
+
+            // players keep everything they could see
+            for (id, seat) in [(7, 0), (42, 2)] {
+                let view = data.client_view(Some(id));
+                assert_eq!(view.to_state().istate_key(seat), gs.istate_key(seat));
+                assert_eq!(view.join_code, data.join_code);
+                assert_eq!(view.bot_version, None);
+            }
+
+            // spectators and unknown ids only see the public cards
+            for viewer in [Some(3), Some(99), None] {
+                let view = data.client_view(viewer);
+                let seen = view.to_state();
+                assert_eq!(seen.face_up(), gs.face_up());
+                assert_eq!(seen.last_trick(), gs.last_trick());
+                assert_eq!(seen.cur_player(), gs.cur_player());
+                for p in 0..4 {
+                    assert_eq!(seen.played_card(p), gs.played_card(p));
+                    assert_eq!(seen.get_hand(p).len(), gs.get_hand(p).len());
+                }
+                assert_eq!(view.players, data.players);
+                assert_eq!(view.join_code, None);
+            }
+
+            let a = *actions!(gs).choose(rng).unwrap();
+            gs.apply_action(a);
+        }
+    }
+
+    #[test]
+    fn test_hand_result() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        for _ in 0..20 {
+            let mut gs = Euchre::new_state();
+            while !gs.is_terminal() {
+                assert_eq!(HandResult::new(&gs, vec![None; 4], 0), None);
+                let a = *actions!(gs).choose(&mut rng).unwrap();
+                gs.apply_action(a);
+            }
+
+            let (trump, caller) = gs.trump().unwrap();
+            let hand = HandResult::new(&gs, vec![None, Some(7), None, Some(9)], 1).unwrap();
+            assert_eq!(hand.on_human_team(9), Some(true));
+            assert_eq!(hand.on_human_team(8), None);
+            assert_eq!((hand.trump, hand.caller), (trump, caller));
+            assert!(hand.human_tricks + hand.computer_tricks <= 5);
+            assert_eq!(hand.human_tricks, gs.trick_score()[1]);
+            // only one team scores, the defenders when the callers are euchred
+            assert!((hand.human_points == 0) != (hand.computer_points == 0));
+            let caller_points = if caller % 2 == 1 {
+                hand.human_points
+            } else {
+                hand.computer_points
+            };
+            assert_eq!(hand.euchred, caller_points == 0);
+            assert_eq!(hand.euchred, gs.trick_score()[caller % 2] < 3);
+
+            // the cards are kept for analysis but never sent to clients
+            assert_eq!(hand.gs, Some(gs.to_string()));
+            let public = serde_json::to_string(&hand.public()).unwrap();
+            assert!(!public.contains("gs"), "{}", public);
+        }
+    }
+
+    #[test]
+    fn test_api_error() {
+        let err = ApiError::new(ErrorCode::RateLimited, "slow down");
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(json, r#"{"code":"rate_limited","message":"slow down"}"#);
+        assert_eq!(serde_json::from_str::<ApiError>(&json).unwrap(), err);
+    }
+}
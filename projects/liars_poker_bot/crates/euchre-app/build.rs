@@ -0,0 +1,65 @@
+//! Generates the euchre_server api client from `openapi.json`
+//!
+//! The spec is written by `cargo xtask openapi`. The message types are shared
+//! with the server through client-server-messages rather than generated so
+//! helper methods like `GameData::to_state` are still available.
+
+use std::{env, fs, path::Path};
+
+use progenitor::{GenerationSettings, Generator, InterfaceStyle};
+
+const SPEC: &str = "openapi.json";
+
+const SHARED_TYPES: [&str; 25] = [
+    "ActionAnalysis",
+    "ActionRequest",
+    "ApiError",
+    "Difficulty",
+    "ErrorCode",
+    "FeedbackRequest",
+    "GameAction",
+    "GameAnalysis",
+    "GameData",
+    "GameProcessingState",
+    "HandResult",
+    "JoinRequest",
+    "MatchHistory",
+    "MoveAnalysis",
+    "NewGameRequest",
+    "NewGameResponse",
+    "NewPlayerRequest",
+    "OpenGame",
+    "PlayerAccount",
+    "PlayerStats",
+    "PrivateGameResponse",
+    "QuickMatchRequest",
+    "ResignRequest",
+    "Takeback",
+    "VersionResponse",
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SPEC);
+
+    let file = fs::File::open(SPEC).expect("failed to open api spec");
+    let spec = serde_json::from_reader(file).expect("failed to parse api spec");
+
+    let mut settings = GenerationSettings::default();
+    settings.with_interface(InterfaceStyle::Positional);
+    for t in SHARED_TYPES {
+        settings.with_replacement(
+            t,
+            format!("client_server_messages::{}", t),
+            std::iter::empty(),
+        );
+    }
+
+    let tokens = Generator::new(&settings)
+        .generate_tokens(&spec)
+        .expect("failed to generate api client");
+    let ast = syn::parse2(tokens).unwrap();
+    let content = prettyplease::unparse(&ast);
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("api_client.rs");
+    fs::write(out, content).unwrap();
+}
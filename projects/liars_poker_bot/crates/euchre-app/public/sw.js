@@ -0,0 +1,54 @@
+// Caches the app shell so euchre can be opened without a connection.
+//
+// The version is replaced with a hash of the wasm by `cargo xtask serve` and
+// `cargo xtask deploy`, so a new build invalidates the old cache.
+const CACHE = "euchre-app-{cache_version}";
+
+const APP_SHELL = [
+  "/",
+  "/index.html",
+  "/tailwind.css",
+  "/manifest.webmanifest",
+  "/icon.svg",
+  "/assets/dioxus/euchre-app.js",
+  "/assets/dioxus/euchre-app_bg.wasm",
+];
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches.open(CACHE).then((cache) => cache.addAll(APP_SHELL)).then(() => self.skipWaiting())
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) => Promise.all(keys.filter((k) => k !== CACHE).map((k) => caches.delete(k))))
+      .then(() => self.clients.claim())
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const url = new URL(event.request.url);
+
+  // game state always comes from the server
+  if (
+    event.request.method !== "GET" ||
+    url.origin !== self.location.origin ||
+    url.pathname.startsWith("/api") ||
+    url.pathname.startsWith("/ws")
+  ) {
+    return;
+  }
+
+  // every route is rendered by the app, so navigations fall back to the cached index
+  if (event.request.mode === "navigate") {
+    event.respondWith(fetch(event.request).catch(() => caches.match("/index.html")));
+    return;
+  }
+
+  event.respondWith(
+    caches.match(event.request).then((cached) => cached || fetch(event.request))
+  );
+});
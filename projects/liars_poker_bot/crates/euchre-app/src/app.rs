@@ -1,13 +1,22 @@
-use client_server_messages::{NewGameRequest, NewGameResponse};
+use client_server_messages::{Difficulty, NewGameRequest};
 // import the prelude to get access to the `rsx!` macro and the `Scope` and `Element` types
 use dioxus::prelude::*;
 use dioxus_router::prelude::*;
 
 use super::{
-    base_url, hide_element,
+    hide_element,
     in_game::InGame,
-    settings::{get_player_id, min_players, register_settings, set_min_players},
-    show_element, ACTION_BUTTON_CLASS, SERVER,
+    offline::OfflineGame,
+    requests::api_client,
+    review::Review,
+    settings::{
+        animate_cards, claim_player_id, difficulty, get_player_id, high_contrast, min_players,
+        register_settings, set_animate_cards, set_difficulty, set_high_contrast, set_min_players,
+    },
+    show_element,
+    stats::Stats,
+    watch::Watch,
+    ACTION_BUTTON_CLASS,
 };
 use log::info;
 
@@ -18,12 +27,24 @@ pub enum Route {
     #[route("/")]
     Index {},
 
+    #[route("/game/offline")]
+    OfflineGame,
+
     #[route("/game/:game_id")]
     InGame { game_id: String },
 
+    #[route("/game/:game_id/review")]
+    Review { game_id: String },
+
     #[route("/game")]
     NewGame,
 
+    #[route("/stats")]
+    Stats {},
+
+    #[route("/watch/:game_id")]
+    Watch { game_id: String },
+
     #[route("/:..segments")]
     NotFound { segments: Vec<String> },
 }
@@ -37,7 +58,16 @@ pub fn App(cx: Scope) -> Element {
     // let send_task = use_coroutine_handle::<WsSendMessage>(cx).expect("error getting ws task");
     // send_msg(send_task, "test message".to_string());
 
-    render! { Router::<Route> {} }
+    // cards pick up the high contrast theme from the `group` they're in
+    let theme = if high_contrast(cx) {
+        "group high-contrast"
+    } else {
+        "group"
+    };
+
+    render! {
+        div { class: "{theme}", Router::<Route> {} }
+    }
 }
 
 #[component]
@@ -48,13 +78,62 @@ fn NotFound(cx: Scope, segments: Vec<String>) -> Element {
     }
 }
 
+/// Options for the computer players, in order of strength
+const DIFFICULTIES: [(Difficulty, &str); 4] = [
+    (Difficulty::Random, "Beginner"),
+    (Difficulty::Greedy, "Easy"),
+    (Difficulty::PimctsSmall, "Medium"),
+    (Difficulty::Full, "Hard"),
+];
+
 #[component]
 fn Index(cx: Scope) -> Element {
     show_element("intro");
+    let cur_difficulty = difficulty(cx);
+    let animate = animate_cards(cx);
+    let contrast = high_contrast(cx);
 
     render!(
         div { class: "max-w-xlg grid space-y-4 mx-4 my-4",
 
+            div { class: "grid justify-items-center", role: "group", aria_label: "Settings",
+                label {
+                    "Computer difficulty: "
+                    select {
+                        class: "border rounded px-2",
+                        onchange: move |evt| {
+                            if let Some((d, _)) = DIFFICULTIES
+                                .iter()
+                                .find(|(_, name)| *name == evt.value.as_str())
+                            {
+                                set_difficulty(cx, *d);
+                            }
+                        },
+                        for (d, name) in DIFFICULTIES {
+                            option { value: name, selected: d == cur_difficulty, name }
+                        }
+                    }
+                }
+                label {
+                    input {
+                        class: "mr-2",
+                        r#type: "checkbox",
+                        checked: animate,
+                        onclick: move |_| set_animate_cards(cx, !animate)
+                    }
+                    "Animate cards"
+                }
+                label {
+                    input {
+                        class: "mr-2",
+                        r#type: "checkbox",
+                        checked: contrast,
+                        onclick: move |_| set_high_contrast(cx, !contrast)
+                    }
+                    "High contrast cards"
+                }
+            }
+
             div { class: "grid justify-items-center",
                 div {
                     button {
@@ -76,6 +155,34 @@ fn Index(cx: Scope) -> Element {
                         },
                         "Play with human partner"
                     }
+
+                    button {
+                        class: "{ACTION_BUTTON_CLASS} font-medium px-2 mx-2",
+                        onclick: move |_| {
+                            set_min_players(cx, 4);
+                            let nav = use_navigator(cx);
+                            nav.push(Route::NewGame {});
+                        },
+                        "Play with 4 humans"
+                    }
+
+                    button {
+                        class: "{ACTION_BUTTON_CLASS} font-medium px-2 mx-2",
+                        onclick: move |_| {
+                            let nav = use_navigator(cx);
+                            nav.push(Route::OfflineGame {});
+                        },
+                        "Play offline"
+                    }
+
+                    button {
+                        class: "{ACTION_BUTTON_CLASS} font-medium px-2 mx-2",
+                        onclick: move |_| {
+                            let nav = use_navigator(cx);
+                            nav.push(Route::Stats {});
+                        },
+                        "Your stats"
+                    }
                 }
             }
         }
@@ -88,19 +195,16 @@ fn NewGame(cx: Scope) -> Element {
 
     let player_id = get_player_id(cx).unwrap();
     let min_players = min_players(cx);
-    let new_game_req = NewGameRequest::new(player_id, min_players);
+    let new_game_req = NewGameRequest::new(player_id, min_players, difficulty(cx));
     info!("requesting a new game: {:?}", new_game_req);
 
-    let client = reqwest::Client::new();
     let new_game_response = use_future(cx, (), |_| async move {
-        client
-            .post(base_url() + "/" + SERVER)
-            .json(&new_game_req)
-            .send()
-            .await
-            .expect("error unwraping response")
-            .json::<NewGameResponse>()
+        // the game is played as the player id, which needs its token
+        claim_player_id(player_id).await;
+        api_client()
+            .new_game(&new_game_req)
             .await
+            .map(|r| r.into_inner())
     });
 
     let nav = use_navigator(cx);
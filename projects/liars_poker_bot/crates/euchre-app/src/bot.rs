@@ -0,0 +1,115 @@
+//! Computer players for offline games, run in the browser
+//!
+//! This is a trimmed down version of card_platypus' `PIMCTSBot` searching with
+//! an `OpenHandSolver`, which don't build for wasm since they use threads and
+//! memory mapped tables. The search has no transposition table, so only a few
+//! deals are searched for each move.
+
+use games::{
+    actions,
+    gamestates::euchre::{
+        processors::{euchre_early_terminate, process_euchre_actions},
+        EuchreGameState,
+    },
+    resample::ResampleFromInfoState,
+    Action, GameState, Team,
+};
+use rand::{rngs::StdRng, thread_rng, SeedableRng};
+
+/// Deals searched for each move, the same as the server's small pimcts bot
+pub const OFFLINE_BOT_WORLDS: usize = 8;
+
+/// Picks the move with the best average value over deals consistent with what
+/// the bot has seen, each deal is solved as if all the cards were face up
+pub struct OfflineBot {
+    worlds: usize,
+    rng: StdRng,
+}
+
+impl Default for OfflineBot {
+    fn default() -> Self {
+        Self::new(OFFLINE_BOT_WORLDS, StdRng::from_rng(thread_rng()).unwrap())
+    }
+}
+
+impl OfflineBot {
+    pub fn new(worlds: usize, rng: StdRng) -> Self {
+        Self { worlds, rng }
+    }
+
+    pub fn step(&mut self, gs: &EuchreGameState) -> Action {
+        let actions = actions!(gs);
+        if actions.len() == 1 {
+            return actions[0];
+        }
+
+        let player = gs.cur_player();
+        let mut worlds: Vec<EuchreGameState> = (0..self.worlds)
+            .map(|_| gs.resample_from_istate(player, &mut self.rng))
+            .collect();
+
+        let mut best = (actions[0], f64::NEG_INFINITY);
+        for a in actions {
+            let mut value = 0.0;
+            for w in worlds.iter_mut() {
+                w.apply_action(a);
+                value += alpha_beta(w, Team::from(player), f64::NEG_INFINITY, f64::INFINITY);
+                w.undo();
+            }
+            // ties go to the first action, as for the server's bots
+            if value > best.1 {
+                best = (a, value);
+            }
+        }
+        best.0
+    }
+}
+
+/// Value of `gs` for `maximizing_team` when everyone can see all the cards
+fn alpha_beta(
+    gs: &mut EuchreGameState,
+    maximizing_team: Team,
+    mut alpha: f64,
+    mut beta: f64,
+) -> f64 {
+    if euchre_early_terminate(gs) {
+        // the winner is decided, play out the hand to get the score
+        let mut applied = 0;
+        while !gs.is_terminal() {
+            gs.apply_action(actions!(gs)[0]);
+            applied += 1;
+        }
+        let v = gs.evaluate(maximizing_team.into());
+        for _ in 0..applied {
+            gs.undo();
+        }
+        return v;
+    }
+
+    let mut actions = actions!(gs);
+    process_euchre_actions(gs, &mut actions);
+    let maximizing = Team::from(gs.cur_player()) == maximizing_team;
+
+    let mut value = if maximizing {
+        f64::NEG_INFINITY
+    } else {
+        f64::INFINITY
+    };
+    for a in actions {
+        gs.apply_action(a);
+        let child = alpha_beta(gs, maximizing_team, alpha, beta);
+        gs.undo();
+
+        if maximizing {
+            value = value.max(child);
+            alpha = alpha.max(value);
+        } else {
+            value = value.min(child);
+            beta = beta.min(value);
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    value
+}
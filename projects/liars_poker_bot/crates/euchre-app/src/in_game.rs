@@ -3,7 +3,10 @@
 use std::{fmt::Display, time::Duration};
 
 use async_std::task;
-use client_server_messages::{ActionRequest, GameAction, GameData, GameProcessingState};
+use client_server_messages::{
+    ActionRequest, ChatContent, ChatMessage, Emote, FeedbackRequest, GameAction, GameData,
+    GameProcessingState, ResignRequest, MAX_CHAT_LEN, MAX_FEEDBACK_LEN,
+};
 use dioxus::prelude::*;
 use dioxus_router::prelude::*;
 use futures_util::StreamExt;
@@ -11,17 +14,28 @@ use games::{
     actions,
     gamestates::euchre::{
         actions::{Card, EAction, Suit},
+        legality::IllegalReason,
         EPhase, EuchreGameState,
     },
     GameState, Player,
 };
-use log::info;
+use log::{error, info, warn};
 
 use crate::{
-    app::Route, base_url, hide_element, requests::make_game_request, settings::get_player_id,
-    ACTION_BUTTON_CLASS, SERVER,
+    app::Route,
+    base_url, hide_element,
+    requests::{
+        api_client, api_error, game_request, make_game_request, ChatSender, GameEvent,
+        GameSubscription, Recovery,
+    },
+    settings::{animate_cards, get_player_id},
+    ACTION_BUTTON_CLASS,
 };
 
+/// How long a finished trick takes to sweep towards the winner, matches the
+/// `duration-500` of [`TableLocation::sweep_class`]
+const TRICK_SWEEP_TIME: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Clone)]
 pub enum TableLocation {
     North,
@@ -49,6 +63,35 @@ impl TableLocation {
             .position(|x| x.is_some() && x.unwrap() == player_id)
             .unwrap()
     }
+
+    /// Animation for a card played from this seat, flying in from the edge of
+    /// the table, the keyframes are in tailwind.config.js
+    fn fly_in_class(&self) -> &'static str {
+        match self {
+            TableLocation::North => "inline-block animate-card-from-north",
+            TableLocation::South => "inline-block animate-card-from-south",
+            TableLocation::East => "inline-block animate-card-from-east",
+            TableLocation::West => "inline-block animate-card-from-west",
+        }
+    }
+
+    /// Transition for a finished trick sweeping towards this seat
+    fn sweep_class(&self) -> &'static str {
+        match self {
+            TableLocation::North => {
+                "inline-block transition duration-500 ease-in -translate-y-32 opacity-0"
+            }
+            TableLocation::South => {
+                "inline-block transition duration-500 ease-in translate-y-32 opacity-0"
+            }
+            TableLocation::East => {
+                "inline-block transition duration-500 ease-in translate-x-32 opacity-0"
+            }
+            TableLocation::West => {
+                "inline-block transition duration-500 ease-in -translate-x-32 opacity-0"
+            }
+        }
+    }
 }
 
 impl Display for TableLocation {
@@ -67,6 +110,8 @@ pub enum InGameState {
     Loading,
     NotFound,
     GameFull,
+    /// The server speaks another protocol version, the page needs a reload
+    Outdated,
     UnknownError(String),
     Ok(GameData),
 }
@@ -76,16 +121,28 @@ pub fn InGame(cx: Scope, game_id: String) -> Element {
     hide_element("intro");
 
     let player_id = get_player_id(cx).unwrap();
-    let client = reqwest::Client::new();
-    let game_url = format!("{}/{}/{}", base_url(), SERVER, game_id);
+    let client = api_client();
+    let polling_game_id = game_id.clone();
 
     let state = use_state(cx, || InGameState::Loading);
+    let chat = use_state(cx, Vec::<ChatMessage>::new);
+    let chat_sender = use_state(cx, || None::<ChatSender>);
+    let chat_draft = use_state(cx, String::new);
+    // why the server rejected the last action, if the game carried on
+    let toast = use_state(cx, || None::<String>);
+    let feedback = use_state(cx, || FeedbackForm::Closed);
     let _gs_polling_task = use_coroutine(cx, |_rx: UnboundedReceiver<()>| {
         let game_data = state.to_owned();
+        let chat = chat.to_owned();
+        let chat_sender = chat_sender.to_owned();
         async move {
+            // revision of the last state seen, so a reconnect is sent what was missed
+            let mut last_seen = None;
             loop {
-                // get the latest state
-                let mut new_state = make_game_request(client.get(game_url.clone())).await;
+                // get the latest state, fails with Outdated if the server was upgraded
+                let mut new_state =
+                    make_game_request(client.get_game(&polling_game_id, Some(player_id as u64)))
+                        .await;
 
                 // make sure we're an active player, and try to register as one if we can
                 new_state = match new_state {
@@ -94,42 +151,104 @@ pub fn InGame(cx: Scope, game_id: String) -> Element {
                     }
                     InGameState::Ok(gd) if gd.players.len() < 2 => InGameState::Ok(gd),
                     InGameState::Ok(_) => {
-                        make_game_request(
-                            client
-                                .post(game_url.clone())
-                                .json(&ActionRequest::new(player_id, GameAction::RegisterPlayer)),
-                        )
+                        make_game_request(client.post_game(
+                            &polling_game_id,
+                            &ActionRequest::new(player_id, GameAction::RegisterPlayer),
+                        ))
                         .await
                     }
 
                     _ => new_state,
                 };
 
-                game_data.set(new_state);
+                // the server pushes the state as it changes, if the connection is lost
+                // resume from the last state seen so the missed transitions are shown
+                let revision = match &new_state {
+                    InGameState::Ok(gd) if gd.players.contains(&Some(player_id)) => {
+                        Some(last_seen.unwrap_or(gd.revision))
+                    }
+                    _ => None,
+                };
+                if last_seen.is_none() || revision.is_none() {
+                    game_data.set(new_state);
+                }
+
+                if let Some(revision) = revision {
+                    match GameSubscription::resume(&polling_game_id, player_id, revision) {
+                        Ok(mut subscription) => {
+                            chat_sender.set(Some(subscription.chat_sender()));
+                            while let Some(event) = subscription.next_event().await {
+                                match event {
+                                    GameEvent::State(gd) => {
+                                        last_seen = Some(gd.revision);
+                                        game_data.set(InGameState::Ok(gd));
+                                    }
+                                    GameEvent::Chat(msg) => chat.with_mut(|x| x.push(msg)),
+                                    GameEvent::ChatHistory(messages) => chat.set(messages),
+                                }
+                            }
+                            chat_sender.set(None);
+                            if subscription.outdated() {
+                                game_data.set(InGameState::Outdated);
+                                return;
+                            }
+                            warn!("lost connection to game updates, reconnecting");
+                        }
+                        Err(e) => error!("failed to subscribe to game updates: {:?}", e),
+                    }
+                }
                 task::sleep(Duration::from_secs(5)).await;
             }
         }
     });
 
     let player_id = get_player_id(cx).unwrap();
-    let target = format!("{}/{}/{}", base_url(), SERVER, game_id);
+    let action_game_id = game_id.clone();
     let _action_task = use_coroutine(cx, |mut rx: UnboundedReceiver<GameAction>| {
         let game_data = state.to_owned();
+        let toast = toast.to_owned();
 
         async move {
-            let client = reqwest::Client::new();
+            let client = api_client();
 
             while let Some(action) = rx.next().await {
                 info!("sending actiond: {:?}", action);
                 let req = ActionRequest::new(player_id, action);
 
-                let new_state = make_game_request(client.post(target.clone()).json(&req)).await;
+                let e = match game_request(client.post_game(&action_game_id, &req)).await {
+                    Ok(gd) => {
+                        toast.set(None);
+                        // the bots move after the reply is sent, their moves
+                        // can arrive on the subscription first
+                        let seen = matches!(
+                            &*game_data.current(),
+                            InGameState::Ok(x) if x.revision >= gd.revision
+                        );
+                        if !seen {
+                            game_data.set(InGameState::Ok(gd));
+                        }
+                        continue;
+                    }
+                    Err(e) => e,
+                };
 
-                // only set the state to this if it's a valid response. We could get 400 errors
-                // for trying to play a move multiple times
-                if let InGameState::Ok(_) = new_state {
-                    game_data.set(new_state);
-                }
+                warn!("action rejected: {:?}", e);
+                let new_state = match Recovery::from(e.code) {
+                    Recovery::Toast => {
+                        toast.set(Some(e.message));
+                        continue;
+                    }
+                    Recovery::Refresh => {
+                        make_game_request(client.get_game(&action_game_id, Some(player_id as u64)))
+                            .await
+                    }
+                    Recovery::Rejoin => {
+                        let rejoin = ActionRequest::new(player_id, GameAction::RegisterPlayer);
+                        make_game_request(client.post_game(&action_game_id, &rejoin)).await
+                    }
+                    Recovery::Fatal => InGameState::from(e),
+                };
+                game_data.set(new_state);
             }
         }
     });
@@ -147,7 +266,12 @@ pub fn InGame(cx: Scope, game_id: String) -> Element {
         InGameState::Ok(gd) if matches!(gd.display_state, GameProcessingState::GameOver) => {
             GameOver(cx, game_id.clone())
         }
+        InGameState::Ok(gd) if matches!(gd.display_state, GameProcessingState::Abandoned) => {
+            GameAbandoned(cx)
+        }
         InGameState::Ok(gd) => {
+            // only render what this player is allowed to see
+            let gd = gd.client_view(Some(player_id));
             let south_player = gd
                 .players
                 .iter()
@@ -157,10 +281,32 @@ pub fn InGame(cx: Scope, game_id: String) -> Element {
             render!(
                 div { class: "h-screen grid sm:flex sm:flex-row m-1",
                     div { class: "sm:basis-3/4", PlayArea(cx, gd.clone(), south_player) }
+                    Toast(cx, toast.to_owned()),
                     div { class: "sm:basis-1/4",
                         GameData(cx, gd.gs.clone(), south_player),
-                        RunningStats(cx, gd.computer_score, gd.human_score),
-                        PlayerStats(cx, gd.players.clone())
+                        RunningStats(cx, gd.team_scores(player_id).unwrap_or_default()),
+                        Scoresheet {
+                            game_id: game_id.clone(),
+                            total_score: gd.human_score + gd.computer_score
+                        }
+                        PlayerStats(cx, gd.players.clone(), south_player),
+                        Chat(
+                            cx,
+                            chat.get().clone(),
+                            chat_sender.get().clone(),
+                            chat_draft.to_owned(),
+                            south_player
+                        ),
+                        TakebackButton(cx, gd.clone()),
+                        ResignButton(cx, game_id.clone(), state.to_owned()),
+                        FeedbackButton(
+                            cx,
+                            game_id.clone(),
+                            gd.clone(),
+                            feedback.to_owned(),
+                            toast.to_owned()
+                        ),
+                        WatchLink(cx, game_id.clone())
                     }
                 }
             )
@@ -169,10 +315,12 @@ pub fn InGame(cx: Scope, game_id: String) -> Element {
         InGameState::Loading => Loading(cx),
         InGameState::UnknownError(msg) => UnknownError(cx, msg),
         InGameState::GameFull => GameFull(cx),
+        InGameState::Outdated => Outdated(cx),
     }
 }
 
 fn GameOver<T>(cx: Scope<T>, game_id: String) -> Element {
+    let review_game_id = game_id.clone();
     render!(
         div { class: "px-8 pt-8",
             div { class: "font-bold text-xl font-large text-black", "Thanks for playing!" }
@@ -186,6 +334,33 @@ fn GameOver<T>(cx: Scope<T>, game_id: String) -> Element {
                     "game registration"
                 }
             }
+            button {
+                class: "{ACTION_BUTTON_CLASS} font-medium px-2 mx-2 mt-8",
+                onclick: move |_| {
+                    let nav = use_navigator(cx);
+                    nav.push(Route::Review {
+                        game_id: review_game_id.clone(),
+                    });
+                },
+                "Review your game"
+            }
+            button {
+                class: "{ACTION_BUTTON_CLASS} font-medium px-2 mx-2 mt-8",
+                onclick: move |_| {
+                    let nav = use_navigator(cx);
+                    nav.push(Route::Index {});
+                },
+                "Return home to start a new game"
+            }
+        }
+    )
+}
+
+fn GameAbandoned<T>(cx: Scope<T>) -> Element {
+    render!(
+        div { class: "px-8 pt-8",
+            div { class: "font-bold text-xl font-large text-black", "Game abandoned" }
+            div { "Nobody played a move in this game for a while, so it was ended." }
             button {
                 class: "{ACTION_BUTTON_CLASS} font-medium px-2 mx-2 mt-8",
                 onclick: move |_| {
@@ -198,10 +373,248 @@ fn GameOver<T>(cx: Scope<T>, game_id: String) -> Element {
     )
 }
 
+/// Undoes the player's last move, shown until another human acts or a bot
+/// plays a card
+fn TakebackButton<T>(cx: Scope<T>, gd: GameData) -> Element {
+    let action_task = use_coroutine_handle::<GameAction>(cx).expect("error getting action task");
+    let player_id = get_player_id(cx).unwrap();
+    if gd.takeback.map(|x| x.player_id) != Some(player_id) {
+        return render!({});
+    }
+
+    render!(
+        div { class: "pt-8",
+            button {
+                class: "{ACTION_BUTTON_CLASS} font-medium px-2",
+                onclick: move |_| { action_task.send(GameAction::RequestTakeback) },
+                "Take back last move"
+            }
+        }
+    )
+}
+
+/// Forfeits the game, the server pushes the game over state to all players
+fn ResignButton<T>(cx: Scope<T>, game_id: String, state: UseState<InGameState>) -> Element {
+    let player_id = get_player_id(cx).unwrap();
+
+    render!(
+        div { class: "pt-8",
+            button {
+                class: "{ACTION_BUTTON_CLASS} font-medium px-2",
+                onclick: move |_| {
+                    let game_id = game_id.clone();
+                    let state = state.clone();
+                    cx.spawn(async move {
+                        let client = api_client();
+                        let req = ResignRequest::new(player_id);
+                        let new_state = make_game_request(client.resign_game(&game_id, &req)).await;
+                        if let InGameState::Ok(_) = new_state {
+                            state.set(new_state);
+                        }
+                    });
+                },
+                "Resign game"
+            }
+        }
+    )
+}
+
+/// Where the player is in reporting a problem, see [`FeedbackButton`]
+#[derive(Debug, Clone, PartialEq)]
+enum FeedbackForm {
+    Closed,
+    /// Being written, with the draft so far
+    Open(String),
+    Sent,
+}
+
+/// Reports a problem with the game, e.g. a strange bot play, with the game's
+/// last actions attached so it can be reproduced
+fn FeedbackButton<T>(
+    cx: Scope<T>,
+    game_id: String,
+    gd: GameData,
+    form: UseState<FeedbackForm>,
+    toast: UseState<Option<String>>,
+) -> Element {
+    let draft = match form.get().clone() {
+        FeedbackForm::Closed => {
+            return render!(
+                div { class: "pt-8",
+                    button {
+                        class: "{ACTION_BUTTON_CLASS} font-medium px-2",
+                        onclick: move |_| form.set(FeedbackForm::Open(String::new())),
+                        "Report a problem"
+                    }
+                }
+            )
+        }
+        FeedbackForm::Sent => {
+            return render!(div { class: "pt-8", "Thanks, your report was sent." })
+        }
+        FeedbackForm::Open(draft) => draft,
+    };
+    let submit_form = form.clone();
+    let cancel_form = form.clone();
+
+    render!(
+        form {
+            class: "flex flex-col gap-y-2 pt-8",
+            prevent_default: "onsubmit",
+            onsubmit: move |_| {
+                let FeedbackForm::Open(message) = submit_form.get() else {
+                    return;
+                };
+                let message = message.trim().to_string();
+                if message.is_empty() {
+                    return;
+                }
+                let req = FeedbackRequest::new(game_id.clone(), message, &gd);
+                let form = submit_form.clone();
+                let toast = toast.clone();
+                cx.spawn(async move {
+                    match api_client().post_feedback(&req).await {
+                        Ok(_) => form.set(FeedbackForm::Sent),
+                        Err(e) => toast.set(Some(api_error(e).await.message)),
+                    }
+                });
+            },
+            div { class: "font-bold", "What went wrong?" }
+            textarea {
+                class: "border rounded px-2 h-24",
+                maxlength: "{MAX_FEEDBACK_LEN}",
+                placeholder: "e.g. why did my partner trump my ace?",
+                value: "{draft}",
+                oninput: move |e| form.set(FeedbackForm::Open(e.value.clone()))
+            }
+            div { class: "text-sm text-gray-600",
+                "Reports are anonymous, the game's recent moves are sent with them."
+            }
+            div { class: "flex gap-x-2",
+                button { class: "{ACTION_BUTTON_CLASS} px-2", r#type: "submit", "Send" }
+                button {
+                    class: "{ACTION_BUTTON_CLASS} px-2",
+                    r#type: "button",
+                    onclick: move |_| cancel_form.set(FeedbackForm::Closed),
+                    "Cancel"
+                }
+            }
+        }
+    )
+}
+
+/// Recent chat messages, with a box to send a message or an emote
+///
+/// Messages can only be sent while subscribed to the game's updates.
+fn Chat<T>(
+    cx: Scope<T>,
+    messages: Vec<ChatMessage>,
+    sender: Option<ChatSender>,
+    draft: UseState<String>,
+    south_player: Player,
+) -> Element {
+    let send = move |content: ChatContent| match &sender {
+        Some(sender) => {
+            if let Err(e) = sender.send(content) {
+                error!("{:?}", e);
+            }
+        }
+        None => warn!("not connected, chat message dropped"),
+    };
+    let send_emote = send.clone();
+    let submit_draft = draft.clone();
+    let value = draft.get().clone();
+
+    let lines = messages.into_iter().map(|msg| {
+        let who = match (msg.seat + 4 - south_player) % 4 {
+            0 => "You",
+            1 => "West",
+            2 => "North",
+            _ => "East",
+        };
+        let text = match msg.content {
+            ChatContent::Text(text) => text,
+            ChatContent::Emote(emote) => emote.text().to_string(),
+            ChatContent::TookBack => "took back their last move".to_string(),
+        };
+        (who, text)
+    });
+
+    render!(
+        div { class: "pt-8",
+            div { class: "font-bold text-xl font-large text-black", "Chat" }
+            div { class: "max-h-40 overflow-y-auto",
+                for (who , text) in lines {
+                    div {
+                        span { class: "font-bold", "{who}: " }
+                        "{text}"
+                    }
+                }
+            }
+            form {
+                class: "flex gap-x-2 pt-2",
+                prevent_default: "onsubmit",
+                onsubmit: move |_| {
+                    let text = submit_draft.get().trim().to_string();
+                    if !text.is_empty() {
+                        send(ChatContent::Text(text));
+                        submit_draft.set(String::new());
+                    }
+                },
+                input {
+                    class: "grow border rounded px-2",
+                    maxlength: "{MAX_CHAT_LEN}",
+                    placeholder: "Say something",
+                    value: "{value}",
+                    oninput: move |e| draft.set(e.value.clone())
+                }
+                button { class: "{ACTION_BUTTON_CLASS} px-2", r#type: "submit", "Send" }
+            }
+            div { class: "flex flex-wrap gap-2 pt-2",
+                for emote in Emote::ALL {
+                    button {
+                        class: "{ACTION_BUTTON_CLASS} text-sm px-2",
+                        onclick: {
+                            let send = send_emote.clone();
+                            move |_| send(ChatContent::Emote(emote))
+                        },
+                        emote.text()
+                    }
+                }
+            }
+        }
+    )
+}
+
+/// Link for friends to watch the game without playing in it
+fn WatchLink<T>(cx: Scope<T>, game_id: String) -> Element {
+    let url = format!("{}/watch/{}", base_url(), game_id);
+
+    render!(
+        div { class: "pt-8",
+            div { class: "font-bold", "Friends can watch at:" }
+            a { class: "text-blue-600 visited:text-purple-600 break-all", href: "{url}", "{url}" }
+        }
+    )
+}
+
 fn Loading<T>(cx: Scope<T>) -> Element {
     render!("loading...")
 }
 
+/// Why the last action was rejected, e.g. an illegal move, dismissed with a click
+fn Toast<T>(cx: Scope<T>, toast: UseState<Option<String>>) -> Element {
+    let msg = toast.get().clone()?;
+    render!(
+        div {
+            class: "fixed bottom-4 inset-x-0 mx-auto w-fit max-w-sm rounded-md bg-red-600 px-4 py-2 text-white shadow-lg cursor-pointer",
+            role: "alert",
+            onclick: move |_| toast.set(None),
+            "{msg}"
+        }
+    )
+}
+
 fn GameNotFound<T>(cx: Scope<T>) -> Element {
     render!("error, the request game wasn't found. Try going back and starting a new one...")
 }
@@ -210,6 +623,23 @@ fn GameFull<T>(cx: Scope<T>) -> Element {
     render!("game is full. Try creating a new game instead")
 }
 
+pub(crate) fn Outdated<T>(cx: Scope<T>) -> Element {
+    render!(
+        div { class: "max-w-xlg grid space-y-4 mx-4 my-4",
+            p { "The server was upgraded, please refresh the page to keep playing." }
+            button {
+                class: "{ACTION_BUTTON_CLASS} font-medium px-2",
+                onclick: move |_| {
+                    if let Some(window) = web_sys::window() {
+                        let _ = window.location().reload();
+                    }
+                },
+                "Refresh"
+            }
+        }
+    )
+}
+
 fn UnknownError<'a, T>(cx: Scope<'a, T>, msg: &'a String) -> Element<'a> {
     render!(
         div { class: "max-w-xlg grid space-y-4 mx-4 my-4",
@@ -228,7 +658,7 @@ fn WaitingForOtherPlayers<T>(cx: Scope<T>) -> Element {
     )
 }
 
-fn GameData<T>(cx: Scope<T>, gs: String, south_player: usize) -> Element {
+pub(crate) fn GameData<T>(cx: Scope<T>, gs: String, south_player: usize) -> Element {
     let gs = EuchreGameState::from(gs.as_str());
     let trump_details = gs.trump();
 
@@ -253,6 +683,11 @@ fn GameData<T>(cx: Scope<T>, gs: String, south_player: usize) -> Element {
     } else {
         "Trump has not been called".to_string()
     };
+    // screen readers don't all read the suit and card symbols
+    let trump_label = match trump_details {
+        Some((suit, _)) => trump_string.replace(suit.icon(), suit.name()),
+        None => trump_string.clone(),
+    };
 
     let face_up = gs.face_up();
     let face_up_str = if let Some(card) = face_up {
@@ -260,6 +695,10 @@ fn GameData<T>(cx: Scope<T>, gs: String, south_player: usize) -> Element {
     } else {
         "Face up card not yet dealt".to_string()
     };
+    let face_up_label = match face_up {
+        Some(card) => format!("Face up card is: {}", card.name()),
+        None => face_up_str.clone(),
+    };
 
     let south_trick_wins = gs.trick_score()[south_player % 2];
     let east_trick_wins = gs.trick_score()[(south_player + 1) % 2];
@@ -268,8 +707,14 @@ fn GameData<T>(cx: Scope<T>, gs: String, south_player: usize) -> Element {
         div {
             div { class: "pt-8 font-bold text-xl font-large text-black", "Game information" }
             div { "Dealer is {dealer_seat}" }
-            div { face_up_str }
-            div { trump_string }
+            div {
+                span { aria_hidden: "true", face_up_str }
+                span { class: "sr-only", face_up_label }
+            }
+            div {
+                span { aria_hidden: "true", trump_string }
+                span { class: "sr-only", trump_label }
+            }
             div { class: "font-bold", "Tricks taken:" }
             div { class: "grid grid-cols-2",
                 div { "North/South" }
@@ -281,7 +726,7 @@ fn GameData<T>(cx: Scope<T>, gs: String, south_player: usize) -> Element {
     )
 }
 
-fn LastTrick<T>(cx: Scope<T>, game_data: GameData, player: Player) -> Element {
+pub(crate) fn LastTrick<T>(cx: Scope<T>, game_data: GameData, player: Player) -> Element {
     let gs = EuchreGameState::from(game_data.gs.as_str());
     if !matches!(
         game_data.display_state,
@@ -290,53 +735,176 @@ fn LastTrick<T>(cx: Scope<T>, game_data: GameData, player: Player) -> Element {
         return None;
     }
 
-    let last_trick = gs.last_trick();
-    if let Some((starter, mut trick)) = last_trick {
-        trick.rotate_left(4 - starter);
+    let (trick, _) = last_trick(&gs)?;
+    render!(CardIcon(cx, trick[player]))
+}
 
-        render!(CardIcon(cx, trick[player]))
-    } else {
-        None
-    }
+/// The cards of the last finished trick indexed by the player who played them,
+/// and who won it
+pub(crate) fn last_trick(gs: &EuchreGameState) -> Option<([Card; 4], Player)> {
+    gs.tricks().last().map(|(leader, mut trick, winner)| {
+        trick.rotate_right(leader);
+        (trick, winner)
+    })
 }
 
-fn RunningStats<T>(cx: Scope<T>, machine_score: usize, human_score: usize) -> Element {
+/// Games won by the north/south team, from `south_player`'s seat, and by the
+/// east/west team
+pub(crate) fn RunningStats<T>(cx: Scope<T>, scores: (usize, usize)) -> Element {
+    let (south_score, east_score) = scores;
     render!(
         div {
             div { class: "pt-8 font-bold text-xl font-large text-black", "Running stats" }
             div { class: "grid grid-cols-2",
-                div { "Humans" }
-                div { "Machines" }
-                div { "{human_score}" }
-                div { "{machine_score}" }
+                div { "North/South" }
+                div { "East/West" }
+                div { "{south_score}" }
+                div { "{east_score}" }
             }
         }
     )
 }
 
-fn PlayerStats<T>(cx: Scope<T>, players: Vec<Option<usize>>) -> Element {
-    let num_humans = players.iter().filter(|x| x.is_some()).count();
-    if num_humans > 1 {
-        render!(
-            div { class: "pt-8 font-bold text-xl font-large text-black", "Player details" }
-            div { "North: Human" }
-            div { "South: Human" }
-            div { "East: Computer" }
-            div { "West: Computer" }
-        )
-    } else {
-        render!(
-            div { class: "pt-8 font-bold text-xl font-large text-black", "Player details" }
-            div { "North: Computer" }
-            div { "South: Human" }
-            div { "East: Computer" }
-            div { "West: Computer" }
-        )
+/// Each finished hand of the match, reloaded when the score changes
+///
+/// Scores are for the player's team first, as in [`RunningStats`].
+#[component]
+fn Scoresheet(cx: Scope, game_id: String, total_score: usize) -> Element {
+    let player_id = get_player_id(cx).unwrap();
+    let history = use_future(cx, (*total_score,), |_| {
+        let game_id = game_id.clone();
+        async move {
+            api_client()
+                .get_game_history(&game_id)
+                .await
+                .map(|r| r.into_inner())
+        }
+    });
+
+    let hands = match history.value() {
+        Some(Ok(history)) => history.hands.clone(),
+        Some(Err(e)) => {
+            error!("failed to load the match history: {:?}", e);
+            return render!({});
+        }
+        None => return render!({}),
+    };
+
+    let mut totals = (0, 0);
+    let rows = hands
+        .iter()
+        .enumerate()
+        .filter_map(|(i, hand)| {
+            let seat = hand.players.iter().position(|x| *x == Some(player_id))?;
+            let human = (hand.human_tricks, hand.human_points);
+            let computer = (hand.computer_tricks, hand.computer_points);
+            let ((tricks, points), (their_tricks, their_points)) =
+                match hand.on_human_team(player_id)? {
+                    true => (human, computer),
+                    false => (computer, human),
+                };
+            totals = (totals.0 + points, totals.1 + their_points);
+
+            // everyone moves a seat each hand, so the relative seats don't change
+            let caller = match (hand.caller + 4 - seat) % 4 {
+                0 => "You",
+                1 => "West",
+                2 => "North",
+                _ => "East",
+            };
+            let euchred = if hand.euchred { ", euchred" } else { "" };
+            Some((
+                i + 1,
+                format!("{} by {}{}", hand.trump.icon(), caller, euchred),
+                format!("{}-{}", tricks, their_tricks),
+                format!("{}-{}", totals.0, totals.1),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    if rows.is_empty() {
+        return render!({});
     }
+
+    render!(
+        div {
+            div { class: "pt-8 font-bold text-xl font-large text-black", "Scoresheet" }
+            div { class: "grid grid-cols-4",
+                div { "Hand" }
+                div { "Trump" }
+                div { "Tricks" }
+                div { "Score" }
+                for (hand , trump , tricks , score) in rows {
+                    div { "{hand}" }
+                    div { "{trump}" }
+                    div { "{tricks}" }
+                    div { "{score}" }
+                }
+            }
+        }
+    )
+}
+
+fn PlayerStats<T>(cx: Scope<T>, players: Vec<Option<usize>>, south_player: Player) -> Element {
+    let who = |offset: usize| match players[(south_player + offset) % 4] {
+        Some(_) => "Human",
+        None => "Computer",
+    };
+    let (north, east, west) = (who(2), who(3), who(1));
+
+    render!(
+        div { class: "pt-8 font-bold text-xl font-large text-black", "Player details" }
+        div { "North: {north}" }
+        div { "South: Human" }
+        div { "East: {east}" }
+        div { "West: {west}" }
+    )
 }
 
-fn PlayArea<T>(cx: Scope<T>, game_data: GameData, south_player: usize) -> Element {
+pub(crate) fn PlayArea<T>(cx: Scope<T>, game_data: GameData, south_player: usize) -> Element {
     let gs = EuchreGameState::from(game_data.gs.as_str());
+    let animate = animate_cards(cx);
+    // the state of the game a finished trick is being swept away in, the trick
+    // is cleared once the sweep is over
+    let sweeping = use_state(cx, || None::<String>);
+    let sweep_to = match sweeping.get() {
+        Some(swept) if *swept == game_data.gs => {
+            last_trick(&gs).map(|(_, winner)| seat(winner, south_player))
+        }
+        _ => None,
+    };
+
+    // the option picked with the number keys, for the state it was picked in
+    let action_task = use_coroutine_handle::<GameAction>(cx).expect("error getting action task");
+    let picked = use_state(cx, || None::<(String, usize)>);
+    let options = keyboard_options(&gs, south_player, &game_data.display_state);
+    let selected = match picked.get() {
+        Some((picked_gs, i)) if *picked_gs == game_data.gs => options.get(*i).copied(),
+        _ => None,
+    };
+    let selection_status = match selected {
+        Some(a) => match gs.explain_illegal(a.into()) {
+            Some(reason) => format!("{}, can't be played: {}", action_name(a), reason),
+            None => format!("{} selected, press Enter to confirm", action_name(a)),
+        },
+        None => String::new(),
+    };
+    let key_gs = game_data.gs.clone();
+    let key_state = gs.clone();
+    let on_key = move |evt: KeyboardEvent| {
+        let key = evt.key().to_string();
+        if key == "Enter" {
+            if let Some(a) = selected {
+                if key_state.explain_illegal(a.into()).is_none() {
+                    action_task.send(GameAction::TakeAction(a.into()));
+                }
+            }
+        } else if let Ok(n) = key.parse::<usize>() {
+            if (1..=options.len()).contains(&n) {
+                picked.set(Some((key_gs.clone(), n - 1)));
+            }
+        }
+    };
 
     let west_player = (south_player + 1) % 4;
     let north_player = (south_player + 2) % 4;
@@ -375,30 +943,38 @@ fn PlayArea<T>(cx: Scope<T>, game_data: GameData, south_player: usize) -> Elemen
 
     cx.render(rsx! {
 
-        div { class: "grid grid-cols-5 content-between gap-2",
+        div {
+            class: "grid grid-cols-5 content-between gap-2",
+            tabindex: "0",
+            autofocus: true,
+            aria_label: "Euchre table, press a number to pick a card or bid and Enter to confirm it",
+            onkeydown: on_key,
+            div { class: "sr-only", aria_live: "polite", "{selection_status}" }
             // North area
             div { class: "col-start-2 col-span-3 grid",
-                div { class: "justify-self-center", north_label }
+                div { class: "justify-self-center",
+                    SeatLabel(cx, north_label, north_player, game_data.display_state.clone(), animate)
+                }
                 OpponentHand(cx, gs.get_hand(north_player).len())
             }
 
             // Middle area
             div { class: "row-start-2",
-                div { class: "text-center", west_label }
+                div { class: "text-center",
+                    SeatLabel(cx, west_label, west_player, game_data.display_state.clone(), animate)
+                }
                 OpponentHand(cx, gs.get_hand(west_player).len())
             }
 
             div { class: "col-span-3 grid grid-cols-3 items-center justify-items-center space-y-4",
                 div { class: "col-start-2",
-                    PlayedCard(cx, gs.played_card(north_player)),
-                    LastTrick(cx, game_data.clone(), north_player),
+                    TrickCard(cx, game_data.clone(), north_player, south_player, sweep_to.clone(), animate),
                     if show_bids  {
                         Bids(cx, gs.clone(), north_player)
                     }
                 }
                 div { class: "row-start-2",
-                    PlayedCard(cx, gs.played_card(west_player)),
-                    LastTrick(cx, game_data.clone(), west_player),
+                    TrickCard(cx, game_data.clone(), west_player, south_player, sweep_to.clone(), animate),
                     if show_bids {
                         Bids(cx, gs.clone(), west_player)
                     }
@@ -411,44 +987,201 @@ fn PlayArea<T>(cx: Scope<T>, game_data: GameData, south_player: usize) -> Elemen
                     if !gs.is_terminal() && !gs.is_trick_over() {
                         TurnTracker(cx, gs.clone(), south_player)
                     }
-                    ClearButton(cx, game_data.clone().display_state, game_data.clone())
+                    ClearButton(
+                        cx,
+                        game_data.clone().display_state,
+                        game_data.clone(),
+                        sweeping.to_owned(),
+                        animate
+                    )
                 }
 
                 div { class: "row-start-2 col-start-3",
-                    PlayedCard(cx, gs.played_card(east_player)),
-                    LastTrick(cx, game_data.clone(), east_player),
+                    TrickCard(cx, game_data.clone(), east_player, south_player, sweep_to.clone(), animate),
                     if show_bids {
                         Bids(cx, gs.clone(), east_player)
                     }
                 }
 
                 div { class: "row-start-3 col-start-2",
-                    PlayedCard(cx, gs.played_card(south_player)),
-                    LastTrick(cx, game_data.clone(), south_player),
+                    TrickCard(cx, game_data.clone(), south_player, south_player, sweep_to.clone(), animate),
                     if show_bids {
                         Bids(cx, gs.clone(), south_player)
                     }
                 }
             }
             div { class: "",
-                div { class: "text-center", east_label }
+                div { class: "text-center",
+                    SeatLabel(cx, east_label, east_player, game_data.display_state.clone(), animate)
+                }
                 OpponentHand(cx, gs.get_hand(east_player).len())
             }
 
             // bottom area
             div { class: "row-start-3 col-span-5 grid justify-items-center",
                 div { class: "self-end", south_label }
-                PlayerActions(cx, gs.clone(), south_player, game_data.display_state.clone())
+                PlayerActions(
+                    cx,
+                    gs.clone(),
+                    south_player,
+                    game_data.display_state.clone(),
+                    selected
+                )
             }
         }
     })
 }
 
-fn ClearButton<T>(cx: Scope<T>, display_state: GameProcessingState, gd: GameData) -> Element {
+/// What the player can pick from with the number keys, in the order they're
+/// shown
+///
+/// Cards that can't be played are included, so the numbers match the cards
+/// in the player's hand.
+fn keyboard_options(
+    gs: &EuchreGameState,
+    south_player: Player,
+    display_state: &GameProcessingState,
+) -> Vec<EAction> {
+    use EAction::*;
+    if gs.is_chance_node()
+        || gs.is_terminal()
+        || gs.cur_player() != south_player
+        || matches!(
+            display_state,
+            GameProcessingState::WaitingBidClear { ready_players: _ }
+                | GameProcessingState::WaitingTrickClear { ready_players: _ }
+        )
+    {
+        return Vec::new();
+    }
+
+    let actions: Vec<EAction> = actions!(gs).into_iter().map(EAction::from).collect();
+    if actions.contains(&Pickup) {
+        vec![Pickup, Pass]
+    } else if actions.contains(&Clubs) || actions.contains(&Spades) {
+        vec![Spades, Clubs, Hearts, Diamonds, Pass]
+    } else {
+        gs.get_hand(south_player)
+            .into_iter()
+            .map(EAction::from)
+            .collect()
+    }
+}
+
+/// Spoken name of an action, for screen readers
+fn action_name(a: EAction) -> String {
+    use EAction::*;
+    match a {
+        Pickup => "Pick up".to_string(),
+        Pass => "Pass".to_string(),
+        Clubs => "Call clubs".to_string(),
+        Spades => "Call spades".to_string(),
+        Hearts => "Call hearts".to_string(),
+        Diamonds => "Call diamonds".to_string(),
+        DiscardMarker => "Discard".to_string(),
+        card => card.card().name(),
+    }
+}
+
+/// Outline around the option picked with the number keys
+fn selected_class(selected: Option<EAction>, a: EAction) -> &'static str {
+    if selected == Some(a) {
+        "ring-4 ring-blue-600"
+    } else {
+        ""
+    }
+}
+
+/// Text color of a card's suit, with a four color deck on a white background
+/// for the high contrast theme
+fn card_class(c: Card) -> &'static str {
+    match c.suit() {
+        Suit::Spades => "text-black group-[.high-contrast]:bg-white",
+        Suit::Clubs => {
+            "text-black group-[.high-contrast]:text-green-800 group-[.high-contrast]:bg-white"
+        }
+        Suit::Hearts => {
+            "text-red-500 group-[.high-contrast]:text-red-700 group-[.high-contrast]:bg-white"
+        }
+        Suit::Diamonds => {
+            "text-red-500 group-[.high-contrast]:text-blue-700 group-[.high-contrast]:bg-white"
+        }
+    }
+}
+
+/// The seat `player` sits in at the table as seen by `south_player`
+fn seat(player: Player, south_player: Player) -> TableLocation {
+    match (player + 4 - south_player) % 4 {
+        0 => TableLocation::South,
+        1 => TableLocation::West,
+        2 => TableLocation::North,
+        _ => TableLocation::East,
+    }
+}
+
+/// The card `player` has in the current trick, or in the finished trick while
+/// it's waiting to be cleared
+///
+/// When animated, cards fly in from the seat that played them and the finished
+/// trick is swept towards `sweep_to`, the winner's seat.
+fn TrickCard<T>(
+    cx: Scope<T>,
+    game_data: GameData,
+    player: Player,
+    south_player: Player,
+    sweep_to: Option<TableLocation>,
+    animate: bool,
+) -> Element {
+    let gs = game_data.to_state();
+    let card = match game_data.display_state {
+        GameProcessingState::WaitingTrickClear { ready_players: _ } => {
+            last_trick(&gs).map(|(trick, _)| trick[player])
+        }
+        _ => gs.played_card(player),
+    };
+
+    // the class is only changed for the sweep, so cards already on the table
+    // don't fly in again when a card is played
+    let class = match (animate, sweep_to) {
+        (false, _) => "",
+        (true, Some(winner)) => winner.sweep_class(),
+        (true, None) => seat(player, south_player).fly_in_class(),
+    };
+
+    match card {
+        Some(c) => render!( span { class: "{class}", CardIcon(cx, c) } ),
+        None => PlayedCard(cx, None),
+    }
+}
+
+fn ClearButton<T>(
+    cx: Scope<T>,
+    display_state: GameProcessingState,
+    gd: GameData,
+    sweeping: UseState<Option<String>>,
+    animate: bool,
+) -> Element {
     let action_task = use_coroutine_handle::<GameAction>(cx).expect("error getting action task");
     let player_id = get_player_id(cx).unwrap();
     let gs = gd.to_state();
 
+    // sweeps the trick away before clearing it if cards are animated
+    let is_sweeping = sweeping.get().as_ref() == Some(&gd.gs);
+    let swept = gd.gs.clone();
+    let clear_trick = move || {
+        if !animate {
+            action_task.send(GameAction::ReadyTrickClear);
+            return;
+        }
+
+        sweeping.set(Some(swept.clone()));
+        let action_task = action_task.clone();
+        cx.spawn(async move {
+            task::sleep(TRICK_SWEEP_TIME).await;
+            action_task.send(GameAction::ReadyTrickClear);
+        });
+    };
+
     match display_state {
         GameProcessingState::WaitingTrickClear { ready_players }
         | GameProcessingState::WaitingBidClear { ready_players }
@@ -456,6 +1189,9 @@ fn ClearButton<T>(cx: Scope<T>, display_state: GameProcessingState, gd: GameData
         {
             render!( div { class: "text-center", "waiting on other players..." } )
         }
+        GameProcessingState::WaitingTrickClear { ready_players: _ } if is_sweeping => {
+            render!({})
+        }
         GameProcessingState::WaitingTrickClear { ready_players: _ } if gs.is_terminal() => {
             let south_player = TableLocation::south_player(player_id, &gd);
             let south_wins = gs.trick_score()[south_player % 2];
@@ -467,18 +1203,19 @@ fn ClearButton<T>(cx: Scope<T>, display_state: GameProcessingState, gd: GameData
                 div { "East/West tricks: {east_wins}" }
                 button {
                     class: "bg-white outline outline-black hover:bg-slate-100 focus:outline-none focus:ring focus:bg-slate-100 active:bg-slate-200 px-5 py-2 text-sm leading-5 rounded-full font-semibold text-black",
-                    onclick: move |_| { action_task.send(GameAction::ReadyTrickClear) },
+                    onclick: move |_| clear_trick(),
                     "Next hand"
                 }
             )
         }
         GameProcessingState::WaitingTrickClear { ready_players: _ } => {
-            let winner = TableLocation::to_location(player_id, &gd, gs.cur_player());
+            let winner = last_trick(&gs).map_or(gs.cur_player(), |(_, winner)| winner);
+            let winner = TableLocation::to_location(player_id, &gd, winner);
             render!(
                 div { "{winner} wins" }
                 button {
                     class: "bg-white outline outline-black hover:bg-slate-100 focus:outline-none focus:ring focus:bg-slate-100 active:bg-slate-200 px-5 py-2 text-sm leading-5 rounded-full font-semibold text-black",
-                    onclick: move |_| { action_task.send(GameAction::ReadyTrickClear) },
+                    onclick: move |_| clear_trick(),
                     "Clear trick"
                 }
             )
@@ -496,7 +1233,7 @@ fn ClearButton<T>(cx: Scope<T>, display_state: GameProcessingState, gd: GameData
     }
 }
 
-fn Bids<T>(cx: Scope<T>, gs: EuchreGameState, player: Player) -> Element {
+pub(crate) fn Bids<T>(cx: Scope<T>, gs: EuchreGameState, player: Player) -> Element {
     use EAction::*;
     let bids: Vec<Option<&str>> = gs
         .bids()
@@ -526,18 +1263,25 @@ fn Bids<T>(cx: Scope<T>, gs: EuchreGameState, player: Player) -> Element {
     }
 }
 
-fn OpponentHand<T>(cx: Scope<T>, num_cards: usize) -> Element {
+pub(crate) fn OpponentHand<T>(cx: Scope<T>, num_cards: usize) -> Element {
     let mut s = String::new();
     for _ in 0..num_cards {
         s.push('🂠')
     }
 
+    let label = format!("{} cards", num_cards);
     cx.render(rsx! {
-        div { class: "text-3xl lg:text-6xl", style: "text-align:center", s.as_str() }
+        div {
+            class: "text-3xl lg:text-6xl",
+            style: "text-align:center",
+            role: "img",
+            aria_label: "{label}",
+            s.as_str()
+        }
     })
 }
 
-fn PlayedCard<T>(cx: Scope<T>, c: Option<Card>) -> Element {
+pub(crate) fn PlayedCard<T>(cx: Scope<T>, c: Option<Card>) -> Element {
     if let Some(c) = c {
         cx.render(rsx! {CardIcon(cx, c)})
     } else {
@@ -545,17 +1289,52 @@ fn PlayedCard<T>(cx: Scope<T>, c: Option<Card>) -> Element {
     }
 }
 
-fn TurnTracker<T>(cx: Scope<T>, gs: EuchreGameState, south_player: usize) -> Element {
-    let arrow = match gs.cur_player() {
-        x if x == (south_player + 1) % 4 => "←",
-        x if x == (south_player + 2) % 4 => "↑",
-        x if x == (south_player + 3) % 4 => "→",
-        _ => "↓",
+/// A seat's name, followed by a thinking indicator while the computer in the
+/// seat picks its move
+pub(crate) fn SeatLabel<T>(
+    cx: Scope<T>,
+    label: impl Display,
+    player: Player,
+    display_state: GameProcessingState,
+    animate: bool,
+) -> Element {
+    let thinking = matches!(
+        display_state,
+        GameProcessingState::WaitingMachineMoves { acting_seat } if acting_seat == player
+    );
+    let class = if animate { "animate-pulse" } else { "" };
+    render!(
+        span { "{label}" }
+        if thinking {
+            span {
+                class: "ml-2 italic text-slate-500 {class}",
+                role: "status",
+                aria_label: "{label} is thinking",
+                "thinking…"
+            }
+        }
+    )
+}
+
+pub(crate) fn TurnTracker<T>(cx: Scope<T>, gs: EuchreGameState, south_player: usize) -> Element {
+    let (arrow, label) = match gs.cur_player() {
+        x if x == (south_player + 1) % 4 => ("←", "West's turn"),
+        x if x == (south_player + 2) % 4 => ("↑", "North's turn"),
+        x if x == (south_player + 3) % 4 => ("→", "East's turn"),
+        _ => ("↓", "Your turn"),
     };
-    cx.render(rsx! { div { class: "text-4xl lg:text-6xl", "{arrow}" } })
+    cx.render(rsx! {
+        div {
+            class: "text-4xl lg:text-6xl",
+            role: "status",
+            aria_live: "polite",
+            aria_label: "{label}",
+            "{arrow}"
+        }
+    })
 }
 
-fn FaceUpCard<T>(cx: Scope<T>, c: Option<Card>) -> Element {
+pub(crate) fn FaceUpCard<T>(cx: Scope<T>, c: Option<Card>) -> Element {
     if let Some(c) = c {
         cx.render(rsx! {CardIcon(cx, c)})
     } else {
@@ -564,14 +1343,11 @@ fn FaceUpCard<T>(cx: Scope<T>, c: Option<Card>) -> Element {
 }
 
 fn CardIcon<T>(cx: Scope<T>, c: Card) -> Element {
-    use games::gamestates::euchre::actions::Suit::*;
-    let color = match c.suit() {
-        Clubs | Spades => "black",
-        Hearts | Diamonds => "red",
-    };
+    let class = card_class(c);
+    let name = c.name();
 
     cx.render(rsx! {
-        span { class: "text-7xl", color: color, c.icon() }
+        span { class: "text-7xl {class}", role: "img", aria_label: "{name}", c.icon() }
     })
 }
 
@@ -580,6 +1356,7 @@ fn PlayerActions<T>(
     gs: EuchreGameState,
     south_player: usize,
     display_state: GameProcessingState,
+    selected: Option<EAction>,
 ) -> Element {
     if gs.is_chance_node() {
         return render!({});
@@ -614,6 +1391,8 @@ fn PlayerActions<T>(
         };
         // special case for play pickup and pass
         let hand = gs.get_hand(south_player);
+        let pickup_selected = selected_class(selected, EAction::Pickup);
+        let pass_selected = selected_class(selected, EAction::Pass);
         render!(
             div { class: "grid gap-y-4 justify-items-center",
                 div { class: "flex gap-x-4",
@@ -623,13 +1402,13 @@ fn PlayerActions<T>(
                 }
                 div { class: "flex gap-x-4",
                     button {
-                        class: "basis-1/2 text-xl {ACTION_BUTTON_CLASS}",
+                        class: "basis-1/2 text-xl {ACTION_BUTTON_CLASS} {pickup_selected}",
                         onclick: move |_| { action_task.send(GameAction::TakeAction(EAction::Pickup.into())) },
                         "{pickup_text}"
                     }
 
                     button {
-                        class: "basis-1/2 text-xl {ACTION_BUTTON_CLASS}",
+                        class: "basis-1/2 text-xl {ACTION_BUTTON_CLASS} {pass_selected}",
                         onclick: move |_| { action_task.send(GameAction::TakeAction(EAction::Pass.into())) },
                         "Pass"
                     }
@@ -640,6 +1419,11 @@ fn PlayerActions<T>(
         // special case for choosing suit, we test for two suits in case one of them was the face up card and is not
         // a valid suit selection action
         let hand = gs.get_hand(south_player);
+        let spades_selected = selected_class(selected, EAction::Spades);
+        let clubs_selected = selected_class(selected, EAction::Clubs);
+        let hearts_selected = selected_class(selected, EAction::Hearts);
+        let diamonds_selected = selected_class(selected, EAction::Diamonds);
+        let pass_selected = selected_class(selected, EAction::Pass);
         render!(
             div { class: "grid gap-y-4",
                 div { class: "flex gap-x-4",
@@ -649,31 +1433,35 @@ fn PlayerActions<T>(
                 }
                 div { class: "flex gap-x-4",
                     button {
-                        class: "text-xl text-black {ACTION_BUTTON_CLASS}",
+                        class: "text-xl text-black {ACTION_BUTTON_CLASS} {spades_selected}",
+                        aria_label: "Call spades",
                         onclick: move |_| { action_task.send(GameAction::TakeAction(EAction::Spades.into())) },
                         Suit::Spades.icon()
                     }
 
                     button {
-                        class: "text-xl text-black {ACTION_BUTTON_CLASS}",
+                        class: "text-xl text-black {ACTION_BUTTON_CLASS} {clubs_selected}",
+                        aria_label: "Call clubs",
                         onclick: move |_| { action_task.send(GameAction::TakeAction(EAction::Clubs.into())) },
                         Suit::Clubs.icon()
                     }
 
                     button {
-                        class: "text-xl text-red-500 {ACTION_BUTTON_CLASS}",
+                        class: "text-xl text-red-500 {ACTION_BUTTON_CLASS} {hearts_selected}",
+                        aria_label: "Call hearts",
                         onclick: move |_| { action_task.send(GameAction::TakeAction(EAction::Hearts.into())) },
                         Suit::Hearts.icon()
                     }
 
                     button {
-                        class: "text-xl text-red-500 {ACTION_BUTTON_CLASS}",
+                        class: "text-xl text-red-500 {ACTION_BUTTON_CLASS} {diamonds_selected}",
+                        aria_label: "Call diamonds",
                         onclick: move |_| { action_task.send(GameAction::TakeAction(EAction::Diamonds.into())) },
                         Suit::Diamonds.icon()
                     }
 
                     button {
-                        class: "text-xl {ACTION_BUTTON_CLASS}",
+                        class: "text-xl {ACTION_BUTTON_CLASS} {pass_selected}",
                         onclick: move |_| { action_task.send(GameAction::TakeAction(EAction::Pass.into())) },
                         "Pass"
                     }
@@ -681,41 +1469,55 @@ fn PlayerActions<T>(
             }
         )
     } else {
-        let hand: Vec<(Card, Option<EAction>)> = gs
+        let hand: Vec<(Card, Result<EAction, IllegalReason>)> = gs
             .get_hand(south_player)
             .into_iter()
-            .map(|c| (c, actions.iter().find(|a| a.card() == c).cloned()))
+            .map(|c| {
+                let a = EAction::from(c);
+                (c, gs.explain_illegal(a.into()).map_or(Ok(a), Err))
+            })
             .collect();
 
         render!(
             div { class: "flex flex-wrap space-x-4",
                 for (c , a) in hand.into_iter() {
-                    ActionButton(cx, c, a)
+                    ActionButton(cx, c, a, selected)
                 }
             }
         )
     }
 }
 
-fn ActionButton<T>(cx: Scope<T>, card: Card, action: Option<EAction>) -> Element {
-    use games::gamestates::euchre::actions::Suit::*;
-    let color = match card.suit() {
-        Clubs | Spades => "text-black",
-        Hearts | Diamonds => "text-red-500",
-    };
+/// A card in the player's hand, cards that can't be played are disabled and
+/// say why when hovered
+fn ActionButton<T>(
+    cx: Scope<T>,
+    card: Card,
+    action: Result<EAction, IllegalReason>,
+    selected: Option<EAction>,
+) -> Element {
+    let color = card_class(card);
+    let ring = selected_class(selected, EAction::from(card));
+    let name = card.name();
     let action_task = use_coroutine_handle::<GameAction>(cx).expect("error getting action task");
 
-    if let Some(a) = action {
-        render!(
+    match action {
+        Ok(a) => render!(
             button {
-                class: "text-7xl py-2 {ACTION_BUTTON_CLASS} {color}",
+                class: "text-7xl py-2 {ACTION_BUTTON_CLASS} {color} {ring}",
+                aria_label: "Play {name}",
                 onclick: move |_| { action_task.send(GameAction::TakeAction(a.into())) },
                 card.icon()
             }
-        )
-    } else {
-        render!(
-            button { disabled: "true", class: "text-7xl py-2 {ACTION_BUTTON_CLASS} {color}", card.icon() }
-        )
+        ),
+        Err(reason) => render!(
+            button {
+                disabled: "true",
+                title: "{reason}",
+                aria_label: "{name}, can't be played: {reason}",
+                class: "text-7xl py-2 {ACTION_BUTTON_CLASS} {color} {ring}",
+                card.icon()
+            }
+        ),
     }
 }
@@ -1,12 +1,17 @@
+use log::{info, warn};
 use wasm_bindgen::JsCast;
 use web_sys::HtmlElement;
 
 pub mod app;
+pub mod bot;
 pub mod in_game;
+pub mod offline;
 pub mod requests;
+pub mod review;
 pub mod settings;
+pub mod stats;
+pub mod watch;
 
-pub const SERVER: &str = "api";
 pub const ACTION_BUTTON_CLASS: &str = "bg-white outline outline-black hover:bg-slate-100 focus:outline-none focus:ring focus:bg-slate-100 active:bg-slate-200 rounded-lg disabled:outline-white";
 
 pub fn base_url() -> String {
@@ -40,3 +45,19 @@ pub fn show_element(element_id: &str) {
         .set_property("display", "block")
         .unwrap();
 }
+
+/// Registers the service worker that caches the app for offline use and lets
+/// it be installed as a pwa
+pub fn register_service_worker() {
+    let window = web_sys::window().expect("should have a window in this context");
+    let navigator = window.navigator();
+
+    // not available on insecure origins or older browsers
+    if !js_sys::Reflect::has(&navigator, &"serviceWorker".into()).unwrap_or(false) {
+        warn!("service workers not supported, app won't work offline");
+        return;
+    }
+
+    let _ = navigator.service_worker().register("/sw.js");
+    info!("registered service worker");
+}
@@ -3,5 +3,6 @@
 fn main() {
     // launch the web app
     wasm_logger::init(wasm_logger::Config::default());
+    euchre_app::register_service_worker();
     dioxus_web::launch(euchre_app::app::App);
 }
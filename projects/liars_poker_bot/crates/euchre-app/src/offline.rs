@@ -0,0 +1,218 @@
+#![allow(non_snake_case)]
+
+use std::time::Duration;
+
+use async_std::task;
+use client_server_messages::{GameAction, GameData, GameProcessingState};
+use dioxus::prelude::*;
+use dioxus_router::prelude::*;
+use futures_util::StreamExt;
+use games::{
+    gamestates::euchre::{Euchre, EuchreGameState},
+    GameState,
+};
+use log::warn;
+use rand::{seq::SliceRandom, thread_rng};
+
+use crate::{
+    app::Route,
+    bot::OfflineBot,
+    hide_element,
+    in_game::{GameData, PlayArea, RunningStats},
+    settings::get_player_id,
+    ACTION_BUTTON_CLASS,
+};
+
+/// Points needed to win, as for games on the server
+const WINNING_SCORE: usize = 10;
+/// Pause before each computer move so the moves can be followed
+const BOT_MOVE_DELAY: Duration = Duration::from_millis(500);
+
+/// A game against the computer that's played entirely in the browser, so it
+/// works without a connection to the server
+///
+/// Offline games aren't saved and don't count towards the player's stats.
+#[component]
+pub fn OfflineGame(cx: Scope) -> Element {
+    hide_element("intro");
+
+    let player_id = get_player_id(cx).unwrap();
+    let state = use_state(cx, || GameData::new(deal(), player_id, 1));
+
+    let _action_task = use_coroutine(cx, |mut rx: UnboundedReceiver<GameAction>| {
+        let game_data = state.to_owned();
+        async move {
+            let mut bot = OfflineBot::default();
+            let mut gd = game_data.get().clone();
+
+            // the computer may be first to act
+            play_computer_moves(&mut gd, &game_data, &mut bot).await;
+            while let Some(action) = rx.next().await {
+                if let Err(e) = handle_action(&mut gd, action, player_id) {
+                    warn!("ignoring action: {}", e);
+                    continue;
+                }
+                play_computer_moves(&mut gd, &game_data, &mut bot).await;
+            }
+        }
+    });
+
+    let gd = state.get();
+    if matches!(gd.display_state, GameProcessingState::GameOver) {
+        return OfflineGameOver(cx, gd.team_scores(player_id).unwrap_or_default());
+    }
+
+    // the human moves to the next seat each hand, as on the server
+    let south_player = gd
+        .players
+        .iter()
+        .position(|x| *x == Some(player_id))
+        .unwrap();
+
+    render!(
+        div { class: "h-screen grid sm:flex sm:flex-row m-1",
+            div { class: "sm:basis-3/4", PlayArea(cx, gd.clone(), south_player) }
+            div { class: "sm:basis-1/4",
+                GameData(cx, gd.gs.clone(), south_player),
+                RunningStats(cx, gd.team_scores(player_id).unwrap_or_default()),
+                div { class: "pt-8", "Playing offline, this game isn't saved" }
+            }
+        }
+    )
+}
+
+fn OfflineGameOver<T>(cx: Scope<T>, scores: (usize, usize)) -> Element {
+    let (south_score, east_score) = scores;
+    let result = if south_score > east_score {
+        "You won"
+    } else {
+        "You lost"
+    };
+
+    render!(
+        div { class: "px-8 pt-8",
+            div { class: "font-bold text-xl font-large text-black", "{result} {south_score} to {east_score}" }
+            button {
+                class: "{ACTION_BUTTON_CLASS} font-medium px-2 mx-2 mt-8",
+                onclick: move |_| {
+                    let nav = use_navigator(cx);
+                    nav.push(Route::Index {});
+                },
+                "Return home to start a new game"
+            }
+        }
+    )
+}
+
+/// Advances the game, showing the computer's moves one at a time until the
+/// player needs to act
+async fn play_computer_moves(gd: &mut GameData, state: &UseState<GameData>, bot: &mut OfflineBot) {
+    while advance_display_state(gd) {
+        state.set(gd.clone());
+        task::sleep(BOT_MOVE_DELAY).await;
+        let mut gs = gd.to_state();
+        gs.apply_action(bot.step(&gs));
+        gd.gs = gs.to_string();
+    }
+    state.set(gd.clone());
+}
+
+/// Deals a new hand
+fn deal() -> EuchreGameState {
+    let mut gs = Euchre::new_state();
+
+    let mut actions = Vec::new();
+    while gs.is_chance_node() {
+        gs.legal_actions(&mut actions);
+        let a = actions.choose(&mut thread_rng()).unwrap();
+        gs.apply_action(*a);
+    }
+
+    gs
+}
+
+/// Applies the player's action, takebacks aren't supported offline
+fn handle_action(gd: &mut GameData, action: GameAction, player_id: usize) -> Result<(), String> {
+    use GameProcessingState::*;
+    match action {
+        GameAction::TakeAction(a) if matches!(gd.display_state, WaitingHumanMove) => {
+            let mut gs = gd.to_state();
+            if gd.players[gs.cur_player()] != Some(player_id) {
+                return Err("not the player's turn".to_string());
+            }
+            if let Some(reason) = gs.explain_illegal(a) {
+                return Err(format!("illegal action: {}", reason));
+            }
+            gs.apply_action(a);
+            gd.gs = gs.to_string();
+            Ok(())
+        }
+        GameAction::ReadyTrickClear | GameAction::ReadyBidClear => match &mut gd.display_state {
+            WaitingTrickClear { ready_players } | WaitingBidClear { ready_players } => {
+                ready_players.push(player_id);
+                Ok(())
+            }
+            state => Err(format!("can't clear in state {:?}", state)),
+        },
+        action => Err(format!(
+            "can't {:?} in state {:?}",
+            action, gd.display_state
+        )),
+    }
+}
+
+/// Moves the game to its next display state, returns true if the computer
+/// needs to move before the player can
+///
+/// The same transitions as the server's, for a game with one player.
+fn advance_display_state(gd: &mut GameData) -> bool {
+    let mut gs = gd.to_state();
+
+    use GameProcessingState::*;
+    let new_state = match &gd.display_state {
+        WaitingPlayerJoin { .. } | WaitingHumanMove | WaitingMachineMoves { .. } => {
+            if gs.is_trick_over() {
+                WaitingTrickClear {
+                    ready_players: vec![],
+                }
+            } else if gs.bidding_ended() {
+                WaitingBidClear {
+                    ready_players: vec![],
+                }
+            } else if gd.players[gs.cur_player()].is_none() {
+                WaitingMachineMoves {
+                    acting_seat: gs.cur_player(),
+                }
+            } else {
+                WaitingHumanMove
+            }
+        }
+        WaitingTrickClear { ready_players } | WaitingBidClear { ready_players }
+            if !ready_players.is_empty() =>
+        {
+            if gs.is_terminal() {
+                let human_team = gd.human_team().expect("couldn't find human player");
+                gd.human_score += gs.evaluate(human_team).max(0.0) as usize;
+                gd.computer_score += gs.evaluate((human_team + 1) % 4).max(0.0) as usize;
+
+                gs = deal();
+                gd.players.rotate_left(1);
+            }
+
+            if gd.human_score >= WINNING_SCORE || gd.computer_score >= WINNING_SCORE {
+                GameOver
+            } else if gd.players[gs.cur_player()].is_none() {
+                WaitingMachineMoves {
+                    acting_seat: gs.cur_player(),
+                }
+            } else {
+                WaitingHumanMove
+            }
+        }
+        state => state.clone(),
+    };
+    gd.display_state = new_state;
+    gd.gs = gs.to_string();
+
+    matches!(gd.display_state, WaitingMachineMoves { .. })
+}
@@ -1,38 +1,352 @@
-use std::time::Duration;
+use std::{future::Future, time::Duration};
 
+use anyhow::anyhow;
 use async_std::stream::StreamExt;
-use async_std::task;
-use client_server_messages::{GameAction, GameData};
+use async_std::{future, task};
+use client_server_messages::{
+    ApiError, ChatContent, ChatMessage, ErrorCode, GameAction, GameData, WsClientMessage,
+    WsServerMessage, PLAYER_TOKEN_HEADER, PROTOCOL_VERSION, PROTOCOL_VERSION_HEADER,
+    WS_CLIENT_TIMEOUT, WS_HEARTBEAT_INTERVAL,
+};
 use dioxus::prelude::*;
 
-use log::{debug, error, info};
-use reqwest::{RequestBuilder, StatusCode};
+use log::{debug, error, info, warn};
+use progenitor_client::{Error, ResponseValue};
+use reqwest::header::{HeaderMap, HeaderValue};
 use wasm_bindgen::prelude::*;
 
 use web_sys::{ErrorEvent, MessageEvent, WebSocket};
 
 use crate::base_url;
 use crate::in_game::InGameState;
+use crate::settings::player_token;
 
 // macro_rules! console_log {
 //     ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
 // }
 
-pub async fn make_game_request(req: RequestBuilder) -> InGameState {
-    match req.send().await {
-        Ok(res) => match res.status() {
-            StatusCode::OK => match res.json::<GameData>().await {
-                Ok(gd) => InGameState::Ok(gd),
-                Err(x) => 
-                     InGameState::UnknownError(format!("error parsing json response: {:?}", x)),
-                
+/// Client for the euchre_server REST api, generated from `openapi.json`
+pub mod api {
+    include!(concat!(env!("OUT_DIR"), "/api_client.rs"));
+}
+
+/// Requests carry the client's protocol version, the server rejects them once
+/// it's been upgraded to another one. Once the player id is claimed they carry
+/// its token too, the server needs it for anything done as the player
+pub fn api_client() -> api::Client {
+    let mut headers = HeaderMap::new();
+    headers.insert(PROTOCOL_VERSION_HEADER, HeaderValue::from(PROTOCOL_VERSION));
+    if let Some(token) = player_token().and_then(|x| HeaderValue::from_str(&x).ok()) {
+        headers.insert(PLAYER_TOKEN_HEADER, token);
+    }
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .expect("failed to build http client");
+    api::Client::new_with_client(&base_url(), client)
+}
+
+/// The server's explanation of a failed request
+///
+/// Errors from the middleware, like rate limits, aren't in the api spec and
+/// arrive as unexpected responses, their body is still an [`ApiError`].
+pub async fn api_error(e: Error<ApiError>) -> ApiError {
+    match e {
+        Error::ErrorResponse(e) => e.into_inner(),
+        Error::UnexpectedResponse(resp) => {
+            let status = resp.status();
+            resp.json().await.unwrap_or_else(|_| {
+                ApiError::new(
+                    ErrorCode::Internal,
+                    format!("unexpected response from the server: {}", status),
+                )
+            })
+        }
+        e => ApiError::new(
+            ErrorCode::Internal,
+            format!(
+                "couldn't reach the server, try refreshing the page or checking your internet: {}",
+                e
+            ),
+        ),
+    }
+}
+
+pub async fn game_request(
+    req: impl Future<Output = Result<ResponseValue<GameData>, Error<ApiError>>>,
+) -> Result<GameData, ApiError> {
+    match req.await {
+        Ok(gd) => Ok(gd.into_inner()),
+        Err(e) => Err(api_error(e).await),
+    }
+}
+
+pub async fn make_game_request(
+    req: impl Future<Output = Result<ResponseValue<GameData>, Error<ApiError>>>,
+) -> InGameState {
+    match game_request(req).await {
+        Ok(gd) => InGameState::Ok(gd),
+        Err(e) => InGameState::from(e),
+    }
+}
+
+impl From<ApiError> for InGameState {
+    fn from(e: ApiError) -> Self {
+        match e.code {
+            ErrorCode::GameNotFound => InGameState::NotFound,
+            ErrorCode::VersionMismatch => InGameState::Outdated,
+            ErrorCode::SeatTaken => InGameState::GameFull,
+            ErrorCode::InvalidRequest => InGameState::UnknownError(format!(
+                "error joining game. the url may be incorrect. try going back and starting a new game: {}",
+                e.message
+            )),
+            _ => InGameState::UnknownError(format!(
+                "error occured while updating game state: {}",
+                e.message
+            )),
+        }
+    }
+}
+
+/// What the app does when the server rejects a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Take the player's seat again
+    Rejoin,
+    /// Reload the game, the app's copy is out of date
+    Refresh,
+    /// Tell the player what went wrong and carry on
+    Toast,
+    /// The game can't go on, show the error instead
+    Fatal,
+}
+
+impl From<ErrorCode> for Recovery {
+    fn from(code: ErrorCode) -> Self {
+        use ErrorCode::*;
+        match code {
+            NotInGame => Recovery::Rejoin,
+            // e.g. a move sent twice
+            OutOfTurn => Recovery::Refresh,
+            IllegalMove | InvalidRequest | RateLimited | PayloadTooLarge | InvalidPayload
+            | BotUnavailable | Internal => Recovery::Toast,
+            GameNotFound | NotFound | SeatTaken | Conflict | Unauthorized | VersionMismatch => {
+                Recovery::Fatal
+            }
+        }
+    }
+}
+
+/// Url of the server's websocket, on the same host as the app
+pub fn ws_url() -> String {
+    let base_url = base_url();
+    let host = base_url
+        .split_once("://")
+        .map_or(base_url.as_str(), |(_, host)| host);
+    let scheme = if base_url.starts_with("https") {
+        "wss"
+    } else {
+        "ws"
+    };
+    format!("{}://{}/ws/", scheme, host)
+}
+
+/// Pushed by the server on a [`GameSubscription`]
+pub enum GameEvent {
+    State(GameData),
+    Chat(ChatMessage),
+    /// The recent messages, sent after subscribing
+    ChatHistory(Vec<ChatMessage>),
+}
+
+/// Sends chat messages on a subscription's websocket, sending fails once the
+/// subscription is dropped
+#[derive(Clone)]
+pub struct ChatSender {
+    ws: WebSocket,
+    game_id: String,
+}
+
+impl ChatSender {
+    /// Sent as the player the game was subscribed as
+    pub fn send(&self, content: ChatContent) -> anyhow::Result<()> {
+        let msg = serde_json::to_string(&WsClientMessage::Chat {
+            game_id: self.game_id.clone(),
+            content,
+        })?;
+        self.ws
+            .send_with_str(&msg)
+            .map_err(|e| anyhow!("failed to send chat message: {:?}", e))
+    }
+}
+
+/// Receives the state of a game from the server each time it changes, and its
+/// chat messages
+///
+/// The websocket is closed when this is dropped.
+pub struct GameSubscription {
+    ws: WebSocket,
+    recv: UnboundedReceiver<String>,
+    game_id: String,
+    /// Set once the server says it speaks another protocol version
+    outdated: bool,
+}
+
+impl GameSubscription {
+    /// Only the cards `player_id` can see are sent, without one only the
+    /// public cards are
+    pub fn new(game_id: &str, player_id: Option<usize>) -> anyhow::Result<Self> {
+        Self::open(
+            game_id,
+            WsClientMessage::Subscribe {
+                game_id: game_id.to_string(),
+                player_id,
+                token: player_id.and_then(|_| player_token()),
             },
-            StatusCode::NOT_FOUND => InGameState::NotFound,
-            StatusCode::FORBIDDEN => InGameState::UnknownError("failed to join game, there are already two other human players in the game. try starting a new game".to_string()),
-            StatusCode::BAD_REQUEST => InGameState::UnknownError(format!("error joining game. the url may be incorrect. try going back and starting a new game: {:?}", res)),
-            _ => InGameState::UnknownError(format!("error occured while updating game state: {:?}", res)),
-        },
-        Err(e) => InGameState::UnknownError(format!("encountered an unexpected error fetching the game state. try refreshing the page or checking tour internet, {:?}", e)),
+        )
+    }
+
+    /// Rejoins `player_id`'s seat, the states after `revision` are sent
+    /// before any new ones
+    pub fn resume(game_id: &str, player_id: usize, revision: u64) -> anyhow::Result<Self> {
+        Self::open(
+            game_id,
+            WsClientMessage::Resume {
+                game_id: game_id.to_string(),
+                player_id,
+                token: player_token().unwrap_or_default(),
+                revision,
+                protocol_version: PROTOCOL_VERSION,
+            },
+        )
+    }
+
+    pub fn chat_sender(&self) -> ChatSender {
+        ChatSender {
+            ws: self.ws.clone(),
+            game_id: self.game_id.clone(),
+        }
+    }
+
+    /// Whether the subscription ended because the server was upgraded
+    pub fn outdated(&self) -> bool {
+        self.outdated
+    }
+
+    /// Connects to the server, sending `first` once the connection is open
+    fn open(game_id: &str, first: WsClientMessage) -> anyhow::Result<Self> {
+        let ws =
+            WebSocket::new(&ws_url()).map_err(|e| anyhow!("failed to open websocket: {:?}", e))?;
+        let (send, recv) = futures::channel::mpsc::unbounded();
+
+        let subscribe = serde_json::to_string(&first)?;
+        let open_ws = ws.clone();
+        let onopen_callback = Closure::<dyn FnMut()>::new(move || {
+            info!("websocket connected to server");
+            if let Err(e) = open_ws.send_with_str(&subscribe) {
+                error!("error subscribing to game: {:?}", e);
+            }
+        });
+        ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
+        onopen_callback.forget();
+
+        let msg_send = send.clone();
+        let on_msg_callback =
+            Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| match e.data().as_string() {
+                Some(msg) => {
+                    if let Err(e) = msg_send.unbounded_send(msg) {
+                        error!("error sending message to subscription: {:?}", e);
+                    }
+                }
+                None => error!("error turning websocket msg to string: {:?}", e.data()),
+            });
+        ws.set_onmessage(Some(on_msg_callback.as_ref().unchecked_ref()));
+        on_msg_callback.forget();
+
+        // ends the subscription once the connection is lost
+        let onclose_callback = Closure::<dyn FnMut()>::new(move || send.close_channel());
+        ws.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
+        onclose_callback.forget();
+
+        Ok(Self {
+            ws,
+            recv,
+            game_id: game_id.to_string(),
+            outdated: false,
+        })
+    }
+
+    /// Waits for the next state of the game, chat messages are skipped.
+    /// Returns None once the connection is lost or the server was upgraded
+    pub async fn next(&mut self) -> Option<GameData> {
+        loop {
+            if let GameEvent::State(gd) = self.next_event().await? {
+                return Some(gd);
+            }
+        }
+    }
+
+    /// Waits for the next state or chat message, returns None once the
+    /// connection is lost or the server was upgraded
+    ///
+    /// Pings the server while waiting, and gives up on the connection if the
+    /// server stops responding.
+    pub async fn next_event(&mut self) -> Option<GameEvent> {
+        let mut last_heard = js_sys::Date::now();
+        loop {
+            match future::timeout(WS_HEARTBEAT_INTERVAL, self.recv.next()).await {
+                Ok(Some(msg)) => {
+                    last_heard = js_sys::Date::now();
+                    match serde_json::from_str::<WsServerMessage>(&msg) {
+                        Ok(WsServerMessage::GameData { game_id, data })
+                            if game_id == self.game_id =>
+                        {
+                            return Some(GameEvent::State(data))
+                        }
+                        Ok(WsServerMessage::Chat { game_id, message })
+                            if game_id == self.game_id =>
+                        {
+                            return Some(GameEvent::Chat(message))
+                        }
+                        Ok(WsServerMessage::ChatHistory { game_id, messages })
+                            if game_id == self.game_id =>
+                        {
+                            return Some(GameEvent::ChatHistory(messages))
+                        }
+                        Ok(WsServerMessage::Error(e)) => error!("websocket error: {}", e),
+                        Ok(WsServerMessage::VersionMismatch { protocol_version }) => {
+                            warn!("server was upgraded to protocol {}", protocol_version);
+                            self.outdated = true;
+                            let _ = self.ws.close();
+                            return None;
+                        }
+                        Ok(_) => {}
+                        Err(e) => error!("failed to parse websocket message: {:?}", e),
+                    }
+                }
+                Ok(None) => return None,
+                Err(_) => {
+                    if js_sys::Date::now() - last_heard > WS_CLIENT_TIMEOUT.as_millis() as f64 {
+                        warn!("server stopped responding on websocket");
+                        let _ = self.ws.close();
+                        return None;
+                    }
+
+                    if self.ws.ready_state() == WebSocket::OPEN {
+                        let ping = serde_json::to_string(&WsClientMessage::Ping).unwrap();
+                        if let Err(e) = self.ws.send_with_str(&ping) {
+                            error!("error sending ping on websocket: {:?}", e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for GameSubscription {
+    fn drop(&mut self) {
+        let _ = self.ws.close();
     }
 }
 
@@ -79,8 +393,7 @@ pub fn send_msg(send_task: &Coroutine<WsSendMessage>, msg: String) {
 ///
 /// Responses are saved to the shared state `WsResponseMsg`
 pub fn set_up_ws<T>(cx: &Scope<T>) {
-    let base_url = base_url();
-    let url = format!("ws://{}/ws/", &base_url["https://".len() - 1..]);
+    let url = ws_url();
     info!("starting web socket connection to {} ...", url);
 
     use_shared_state_provider(cx, || WsResponseMessage {
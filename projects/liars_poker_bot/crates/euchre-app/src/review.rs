@@ -0,0 +1,93 @@
+#![allow(non_snake_case)]
+
+use client_server_messages::MoveAnalysis;
+use dioxus::prelude::*;
+use dioxus_router::prelude::*;
+use games::gamestates::euchre::actions::EAction;
+use log::error;
+
+use crate::{
+    app::Route, hide_element, requests::api_client, settings::get_player_id, ACTION_BUTTON_CLASS,
+};
+
+/// The bot's opinion of the player's moves in every hand of a finished game
+#[component]
+pub fn Review(cx: Scope, game_id: String) -> Element {
+    hide_element("intro");
+
+    let player_id = get_player_id(cx).unwrap();
+    let analysis = use_future(cx, (), |_| {
+        let game_id = game_id.clone();
+        async move {
+            api_client()
+                .get_game_analysis(&game_id, player_id as u64)
+                .await
+                .map(|r| r.into_inner())
+                .map_err(|e| {
+                    error!("failed to load analysis: {:?}", e);
+                    "error loading the review, try refreshing the page"
+                })
+        }
+    });
+
+    let body = match analysis.value() {
+        Some(Ok(analysis)) if analysis.moves.is_empty() => {
+            render!( div { "You didn't make any moves in this game" } )
+        }
+        Some(Ok(analysis)) => render!(
+            div { class: "grid space-y-4",
+                for m in analysis.moves.iter() {
+                    Move(cx, m.clone())
+                }
+            }
+        ),
+        Some(Err(e)) => render!( div { "{e}" } ),
+        None => render!( div { "Analysing your game, this can take a minute..." } ),
+    };
+
+    render!(
+        div { class: "max-w-xlg grid space-y-4 mx-4 my-4",
+            div { class: "text-xl font-medium", "Review of your game" }
+            body
+            div {
+                button {
+                    class: "{ACTION_BUTTON_CLASS} font-medium px-2",
+                    onclick: move |_| {
+                        let nav = use_navigator(cx);
+                        nav.push(Route::Index {});
+                    },
+                    "Back"
+                }
+            }
+        }
+    )
+}
+
+fn Move<T>(cx: Scope<T>, m: MoveAnalysis) -> Element {
+    let hand = m.hand + 1;
+    let istate = m.istate;
+    let played = EAction::from(m.action);
+    let verdict = if m.ev_loss < 0.05 {
+        "the bot agrees".to_string()
+    } else {
+        format!("gives up {:.2} points", m.ev_loss)
+    };
+
+    render!(
+        div { class: "grid space-y-1",
+            div { class: "font-medium", "Hand {hand}" }
+            div { class: "font-mono", "{istate}" }
+            div { "You played {played}, {verdict}" }
+            div { class: "grid grid-cols-3 gap-x-4 max-w-xs",
+                div { class: "font-medium", "Action" }
+                div { class: "font-medium", "Bot plays" }
+                div { class: "font-medium", "Points" }
+                for a in m.actions.iter() {
+                    div { format!("{}", EAction::from(a.action)) }
+                    div { format!("{:.0}%", a.probability * 100.0) }
+                    div { format!("{:.2}", a.ev) }
+                }
+            }
+        }
+    )
+}
@@ -1,18 +1,32 @@
+use client_server_messages::{Difficulty, NewPlayerRequest};
 use dioxus::prelude::*;
-use log::debug;
+use log::{debug, info, warn};
 use rand::{thread_rng, Rng};
 
+use crate::requests::api_client;
+
 const PLAYER_ID_KEY: &str = "PLAYER_ID";
+const PLAYER_TOKEN_KEY: &str = "PLAYER_TOKEN";
+const ANIMATE_CARDS_KEY: &str = "ANIMATE_CARDS";
+const HIGH_CONTRAST_KEY: &str = "HIGH_CONTRAST";
 
 /// Register all settings data to default
 pub fn register_settings<T>(cx: Scope<T>) {
     debug!("registering settings shared state...");
     use_shared_state_provider(cx, || MinPlayers(1));
+    use_shared_state_provider(cx, || BotDifficulty(Difficulty::default()));
     use_shared_state_provider(cx, || EventId { id: "".to_string() });
 
     let local_storage = web_sys::window().unwrap().local_storage().unwrap().unwrap();
     let stored_id = local_storage.get_item(PLAYER_ID_KEY);
 
+    // animations are on unless they've been turned off
+    let animate = !matches!(local_storage.get_item(ANIMATE_CARDS_KEY), Ok(Some(x)) if x == "false");
+    use_shared_state_provider(cx, || AnimateCards(animate));
+    let high_contrast =
+        matches!(local_storage.get_item(HIGH_CONTRAST_KEY), Ok(Some(x)) if x == "true");
+    use_shared_state_provider(cx, || HighContrast(high_contrast));
+
     let player_id: usize = match stored_id.map(|x| x.map(|y| y.parse())) {
         Ok(Some(Ok(x))) => x,
         _ => {
@@ -27,6 +41,46 @@ pub fn register_settings<T>(cx: Scope<T>) {
     };
 
     use_shared_state_provider(cx, || PlayerId { id: player_id });
+
+    // claim the id straight away so it's usually done before the first game
+    use_future(cx, (), move |_| claim_player_id(player_id));
+}
+
+/// Claims the player id if it hasn't been, the server only lets the holder of
+/// its token play as it and tracks their stats
+pub async fn claim_player_id(player_id: usize) {
+    if player_token().is_some() {
+        return;
+    }
+
+    match api_client()
+        .new_player(&NewPlayerRequest::new(player_id))
+        .await
+    {
+        Ok(account) => {
+            info!("created account for player {}", player_id);
+            web_sys::window()
+                .unwrap()
+                .local_storage()
+                .unwrap()
+                .unwrap()
+                .set_item(PLAYER_TOKEN_KEY, &account.into_inner().token)
+                .expect("error storing player token");
+        }
+        Err(e) => warn!(
+            "failed to create account, online games can't be played: {:?}",
+            e
+        ),
+    }
+}
+
+/// Token proving ownership of the player id, None until the server has issued one
+pub fn player_token() -> Option<String> {
+    web_sys::window()?
+        .local_storage()
+        .ok()??
+        .get_item(PLAYER_TOKEN_KEY)
+        .ok()?
 }
 
 struct PlayerId {
@@ -74,3 +128,61 @@ pub fn set_min_players<T>(cx: Scope<T>, min_players: usize) {
     // We do a silent write to avoid re-rendering things
     s.write_silent().0 = min_players;
 }
+
+struct BotDifficulty(Difficulty);
+
+pub fn difficulty<T>(cx: Scope<T>) -> Difficulty {
+    use_shared_state::<BotDifficulty>(cx)
+        .map(|x| x.read().0)
+        .expect("settings not found. did you register settings?")
+}
+
+pub fn set_difficulty<T>(cx: Scope<T>, difficulty: Difficulty) {
+    debug!("setting difficulty: {:?}", difficulty);
+    let s = use_shared_state::<BotDifficulty>(cx).unwrap();
+
+    s.write_silent().0 = difficulty;
+}
+
+struct AnimateCards(bool);
+
+/// Whether played cards and cleared tricks are animated, saved in the browser
+/// so it's kept between games
+pub fn animate_cards<T>(cx: Scope<T>) -> bool {
+    use_shared_state::<AnimateCards>(cx)
+        .map(|x| x.read().0)
+        .expect("settings not found. did you register settings?")
+}
+
+pub fn set_animate_cards<T>(cx: Scope<T>, animate: bool) {
+    debug!("setting animate cards: {}", animate);
+    let s = use_shared_state::<AnimateCards>(cx).unwrap();
+    s.write().0 = animate;
+    save_setting(ANIMATE_CARDS_KEY, animate);
+}
+
+struct HighContrast(bool);
+
+/// Whether cards are drawn with a four color deck on a white background, so
+/// the suits are easier to tell apart
+pub fn high_contrast<T>(cx: Scope<T>) -> bool {
+    use_shared_state::<HighContrast>(cx)
+        .map(|x| x.read().0)
+        .expect("settings not found. did you register settings?")
+}
+
+pub fn set_high_contrast<T>(cx: Scope<T>, high_contrast: bool) {
+    debug!("setting high contrast: {}", high_contrast);
+    let s = use_shared_state::<HighContrast>(cx).unwrap();
+    s.write().0 = high_contrast;
+    save_setting(HIGH_CONTRAST_KEY, high_contrast);
+}
+
+/// Saves a setting in the browser so it's kept between games
+fn save_setting(key: &str, value: bool) {
+    if let Some(Ok(Some(local_storage))) = web_sys::window().map(|w| w.local_storage()) {
+        if local_storage.set_item(key, &value.to_string()).is_err() {
+            warn!("failed to save setting {}", key);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+#![allow(non_snake_case)]
+
+use dioxus::prelude::*;
+use dioxus_router::prelude::*;
+use log::error;
+
+use crate::{
+    app::Route,
+    hide_element,
+    requests::api_client,
+    settings::{get_player_id, player_token},
+    ACTION_BUTTON_CLASS,
+};
+
+/// The player's results against the bot
+#[component]
+pub fn Stats(cx: Scope) -> Element {
+    hide_element("intro");
+
+    let player_id = get_player_id(cx).unwrap();
+    let stats = use_future(cx, (), |_| async move {
+        let token = player_token()
+            .ok_or("stats aren't tracked until your account is created, try again later")?;
+        api_client()
+            .get_player_stats(player_id as u64, &token)
+            .await
+            .map(|r| r.into_inner())
+            .map_err(|e| {
+                error!("failed to load stats: {:?}", e);
+                "error loading stats, try refreshing the page"
+            })
+    });
+
+    let body = match stats.value() {
+        Some(Ok(stats)) => render!(
+            div { class: "grid grid-cols-2 gap-2 max-w-xs",
+                div { "Games played" }
+                div { format!("{}", stats.games_played) }
+                div { "Wins" }
+                div { format!("{}", stats.wins) }
+                div { "Win rate" }
+                div { format!("{:.0}%", stats.win_rate() * 100.0) }
+                div { "Rating" }
+                div { format!("{:.0}", stats.rating) }
+            }
+        ),
+        Some(Err(e)) => render!( div { "{e}" } ),
+        None => render!( div { "Loading stats..." } ),
+    };
+
+    render!(
+        div { class: "max-w-xlg grid space-y-4 mx-4 my-4",
+            div { class: "text-xl font-medium", "Your stats against the computer" }
+            body
+            div {
+                button {
+                    class: "{ACTION_BUTTON_CLASS} font-medium px-2",
+                    onclick: move |_| {
+                        let nav = use_navigator(cx);
+                        nav.push(Route::Index {});
+                    },
+                    "Back"
+                }
+            }
+        }
+    )
+}
@@ -0,0 +1,219 @@
+#![allow(non_snake_case)]
+
+use std::time::Duration;
+
+use async_std::task;
+use client_server_messages::{ActionRequest, GameAction, GameData, GameProcessingState};
+use dioxus::prelude::*;
+use dioxus_router::prelude::*;
+use games::{
+    gamestates::euchre::{EPhase, EuchreGameState},
+    GameState, Player,
+};
+use log::{error, info, warn};
+
+use crate::{
+    app::Route,
+    hide_element,
+    in_game::{self, InGameState},
+    requests::{api_client, make_game_request, GameSubscription},
+    settings::{animate_cards, get_player_id},
+    ACTION_BUTTON_CLASS,
+};
+
+/// Follows a game without playing in it, only the public cards are shown
+#[component]
+pub fn Watch(cx: Scope, game_id: String) -> Element {
+    hide_element("intro");
+
+    let player_id = get_player_id(cx).unwrap();
+    let state = use_state(cx, || InGameState::Loading);
+    let _gs_polling_task = use_coroutine(cx, |_rx: UnboundedReceiver<()>| {
+        let game_data = state.to_owned();
+        let game_id = game_id.clone();
+        async move {
+            let client = api_client();
+            let spectate = ActionRequest::new(player_id, GameAction::Spectate);
+            // players can't spectate their own game, they can still watch it
+            if let Err(e) = client.post_game(&game_id, &spectate).await {
+                info!("not added as a spectator: {:?}", e);
+            }
+
+            loop {
+                let new_state = make_game_request(client.get_game(&game_id, None)).await;
+                let subscribe = matches!(new_state, InGameState::Ok(_));
+                game_data.set(new_state);
+
+                // the server pushes the state as it changes, if the connection is lost
+                // refresh the state and reconnect
+                if subscribe {
+                    match GameSubscription::new(&game_id, None) {
+                        Ok(mut subscription) => {
+                            while let Some(gd) = subscription.next().await {
+                                game_data.set(InGameState::Ok(gd));
+                            }
+                            warn!("lost connection to game updates, reconnecting");
+                        }
+                        Err(e) => error!("failed to subscribe to game updates: {:?}", e),
+                    }
+                }
+                task::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    });
+
+    match state.get() {
+        InGameState::Ok(gd) => {
+            // sit with the humans so their cards are at the bottom
+            let south_player = gd.players.iter().position(|x| x.is_some()).unwrap_or(0);
+            let scores = gd.players[south_player]
+                .and_then(|id| gd.team_scores(id))
+                .unwrap_or((gd.human_score, gd.computer_score));
+            let status = match gd.display_state {
+                GameProcessingState::WaitingPlayerJoin { .. } => "Waiting for players to join...",
+                GameProcessingState::GameOver => "Game over",
+                GameProcessingState::Abandoned => "Game abandoned",
+                _ => "",
+            };
+
+            render!(
+                div { class: "h-screen grid sm:flex sm:flex-row m-1",
+                    div { class: "sm:basis-3/4", Table(cx, gd.clone(), south_player) }
+                    div { class: "sm:basis-1/4",
+                        div { class: "pt-8 font-bold text-xl font-large text-black", status }
+                        in_game::GameData(cx, gd.gs.clone(), south_player),
+                        in_game::RunningStats(cx, scores),
+                        div { class: "pt-8",
+                            button {
+                                class: "{ACTION_BUTTON_CLASS} font-medium px-2",
+                                onclick: move |_| {
+                                    let nav = use_navigator(cx);
+                                    nav.push(Route::Index {});
+                                },
+                                "Stop watching"
+                            }
+                        }
+                    }
+                }
+            )
+        }
+        InGameState::NotFound => {
+            render!("error, the game wasn't found. Check the link you were sent is complete")
+        }
+        InGameState::Loading | InGameState::GameFull => render!("loading..."),
+        InGameState::Outdated => in_game::Outdated(cx),
+        InGameState::UnknownError(msg) => render!(
+            div { class: "max-w-xlg grid space-y-4 mx-4 my-4",
+                p { "Encountered an unexpected error. Try refreshing the page." }
+                p { "Error: {msg}" }
+            }
+        ),
+    }
+}
+
+/// Who is in a seat, and whether they're dealing
+fn seat_label(name: &str, seat: Player, players: &[Option<usize>]) -> String {
+    let who = match players[seat] {
+        Some(_) => "Human",
+        None => "Computer",
+    };
+    let dealer = if seat == 3 { ", Dealer" } else { "" };
+    format!("{name} ({who}{dealer})")
+}
+
+/// The table seen from `south_player`'s seat, hands are only shown face down
+fn Table<T>(cx: Scope<T>, game_data: GameData, south_player: Player) -> Element {
+    let gs = EuchreGameState::from(game_data.gs.as_str());
+
+    let west_player = (south_player + 1) % 4;
+    let north_player = (south_player + 2) % 4;
+    let east_player = (south_player + 3) % 4;
+
+    let north_label = seat_label("North", north_player, &game_data.players);
+    let south_label = seat_label("South", south_player, &game_data.players);
+    let east_label = seat_label("East", east_player, &game_data.players);
+    let west_label = seat_label("West", west_player, &game_data.players);
+    let animate = animate_cards(cx);
+
+    use GameProcessingState::*;
+    let show_bids = matches!(
+        game_data.display_state,
+        WaitingBidClear { ready_players: _ }
+    ) || gs.phase() == EPhase::Pickup
+        || gs.phase() == EPhase::ChooseTrump;
+
+    render!(
+        div { class: "grid grid-cols-5 content-between gap-2",
+            // North area
+            div { class: "col-start-2 col-span-3 grid",
+                div { class: "justify-self-center",
+                    in_game::SeatLabel(cx, &north_label, north_player, game_data.display_state.clone(), animate)
+                }
+                in_game::OpponentHand(cx, gs.get_hand(north_player).len())
+            }
+
+            // Middle area
+            div { class: "row-start-2",
+                div { class: "text-center",
+                    in_game::SeatLabel(cx, &west_label, west_player, game_data.display_state.clone(), animate)
+                }
+                in_game::OpponentHand(cx, gs.get_hand(west_player).len())
+            }
+
+            div { class: "col-span-3 grid grid-cols-3 items-center justify-items-center space-y-4",
+                div { class: "col-start-2",
+                    Played(cx, game_data.clone(), gs.clone(), north_player, show_bids)
+                }
+                div { class: "row-start-2",
+                    Played(cx, game_data.clone(), gs.clone(), west_player, show_bids)
+                }
+                div { class: "row-start-2 col-start-2 grid justify-items-center",
+                    in_game::FaceUpCard(cx, gs.displayed_face_up_card()),
+                    if matches!(game_data.display_state, WaitingBidClear { ready_players: _ }) {
+                        in_game::FaceUpCard(cx, gs.face_up())
+                    }
+                    if !gs.is_terminal() && !gs.is_trick_over() {
+                        in_game::TurnTracker(cx, gs.clone(), south_player)
+                    }
+                }
+                div { class: "row-start-2 col-start-3",
+                    Played(cx, game_data.clone(), gs.clone(), east_player, show_bids)
+                }
+                div { class: "row-start-3 col-start-2",
+                    Played(cx, game_data.clone(), gs.clone(), south_player, show_bids)
+                }
+            }
+            div { class: "",
+                div { class: "text-center",
+                    in_game::SeatLabel(cx, &east_label, east_player, game_data.display_state.clone(), animate)
+                }
+                in_game::OpponentHand(cx, gs.get_hand(east_player).len())
+            }
+
+            // bottom area
+            div { class: "row-start-3 col-span-5 grid justify-items-center",
+                div { class: "self-end",
+                    in_game::SeatLabel(cx, &south_label, south_player, game_data.display_state.clone(), animate)
+                }
+                in_game::OpponentHand(cx, gs.get_hand(south_player).len())
+            }
+        }
+    )
+}
+
+/// The card `player` played in the current or last trick, and their bids
+fn Played<T>(
+    cx: Scope<T>,
+    game_data: GameData,
+    gs: EuchreGameState,
+    player: Player,
+    show_bids: bool,
+) -> Element {
+    render!(
+        in_game::PlayedCard(cx, gs.played_card(player)),
+        in_game::LastTrick(cx, game_data.clone(), player),
+        if show_bids {
+            in_game::Bids(cx, gs.clone(), player)
+        }
+    )
+}
@@ -10,7 +10,30 @@ module.exports = {
     "./index.html",
   ],
   theme: {
-    extend: {},
+    extend: {
+      // played cards fly in from the player's seat, see TableLocation in
+      // src/in_game.rs
+      keyframes: {
+        "card-from-north": {
+          from: { transform: "translateY(-8rem)", opacity: "0" },
+        },
+        "card-from-south": {
+          from: { transform: "translateY(8rem)", opacity: "0" },
+        },
+        "card-from-east": {
+          from: { transform: "translateX(8rem)", opacity: "0" },
+        },
+        "card-from-west": {
+          from: { transform: "translateX(-8rem)", opacity: "0" },
+        },
+      },
+      animation: {
+        "card-from-north": "card-from-north 300ms ease-out",
+        "card-from-south": "card-from-south 300ms ease-out",
+        "card-from-east": "card-from-east 300ms ease-out",
+        "card-from-west": "card-from-west 300ms ease-out",
+      },
+    },
   },
   plugins: [],
 }
@@ -0,0 +1,728 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, Context, ContextFutureSpawner,
+    Handler, MailboxError, Message, MessageResult, Recipient, SyncContext, WrapFuture,
+};
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use card_platypus::agents::Agent;
+use client_server_messages::{
+    ActionRequest, ApiError, ChatContent, ChatMessage, ErrorCode, GameData, GameProcessingState,
+    ResignRequest,
+};
+use games::{
+    gamestates::euchre::{Euchre, EuchreGameState},
+    labels::labeled,
+    Action, GameState,
+};
+use rand::thread_rng;
+use tracing::{debug, error, info, info_span, Span};
+use uuid::Uuid;
+
+use crate::{
+    advance_display_state,
+    chat::{ChatLog, CHAT_HISTORY},
+    handle_bot_move, handle_ready_clear, handle_register_player, handle_resign, handle_spectate,
+    handle_take_action, handle_takeback,
+    lobby::Lobby,
+    pacing::BotPacing,
+    players::record_game,
+    storage::{PlayerRepository, SaveQueue},
+};
+
+/// How often each game checks whether it has been idle for too long
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// States kept for clients resuming after a dropped connection, a game takes
+/// a few dozen to play a hand
+const RESUME_HISTORY: usize = 64;
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug)]
+pub enum GameError {
+    BadRequest(String),
+    /// The move isn't legal with the player's hand
+    IllegalMove(String),
+    /// The request doesn't fit the game's current state
+    OutOfTurn(String),
+    /// The player isn't seated in the game
+    NotInGame(String),
+    /// The seat is taken or the game is full
+    Forbidden(String),
+    /// The bot workers couldn't be reached
+    BotUnavailable(MailboxError),
+    /// The player is sending too quickly, they can retry after the duration
+    RateLimited(Duration),
+}
+
+impl GameError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            GameError::BadRequest(_) => ErrorCode::InvalidRequest,
+            GameError::IllegalMove(_) => ErrorCode::IllegalMove,
+            GameError::OutOfTurn(_) => ErrorCode::OutOfTurn,
+            GameError::NotInGame(_) => ErrorCode::NotInGame,
+            GameError::Forbidden(_) => ErrorCode::SeatTaken,
+            GameError::BotUnavailable(_) => ErrorCode::BotUnavailable,
+            GameError::RateLimited(_) => ErrorCode::RateLimited,
+        }
+    }
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameError::BadRequest(x)
+            | GameError::IllegalMove(x)
+            | GameError::OutOfTurn(x)
+            | GameError::NotInGame(x)
+            | GameError::Forbidden(x) => f.write_str(x),
+            GameError::BotUnavailable(e) => write!(f, "bot unavailable: {}", e),
+            GameError::RateLimited(retry_after) => write!(
+                f,
+                "sending too quickly, retry in {:.1}s",
+                retry_after.as_secs_f64()
+            ),
+        }
+    }
+}
+
+impl ResponseError for GameError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            GameError::BadRequest(_) | GameError::IllegalMove(_) | GameError::OutOfTurn(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            GameError::NotInGame(_) | GameError::Forbidden(_) => StatusCode::FORBIDDEN,
+            GameError::BotUnavailable(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            GameError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ApiError::new(self.code(), self.to_string()))
+    }
+}
+
+/// Owns the state of a single game, so requests for different games don't
+/// block each other
+///
+/// Messages are handled one at a time. Bot moves are scheduled on the actor,
+/// so a request arriving while a bot thinks is handled straight away and a
+/// move it makes stale is dropped.
+///
+/// The actor stops once no player has acted for `idle_timeout`, unfinished
+/// games are marked [`GameProcessingState::Abandoned`] first.
+pub struct GameActor {
+    id: Uuid,
+    data: GameData,
+    bots: Addr<BotWorker>,
+    /// Websocket sessions to push the game state to when it changes
+    subscribers: Vec<Recipient<GameUpdate>>,
+    /// Recent states, oldest first, sent to clients that missed them
+    history: StateHistory,
+    /// Websocket sessions to push chat messages to
+    chat_subscribers: Vec<Recipient<ChatUpdate>>,
+    chat: ChatLog,
+    saves: SaveQueue,
+    /// Stats of the game's players are updated when it ends
+    players: Arc<dyn PlayerRepository>,
+    lobby: Arc<Lobby>,
+    idle_timeout: Duration,
+    pacing: BotPacing,
+    /// How fast the bot in each seat thinks, see [`BotPacing::seat_speeds`]
+    seat_speeds: [f64; 4],
+    /// Entered while handling the game's messages, so its logs carry the game id
+    span: Span,
+}
+
+impl GameActor {
+    pub fn new(
+        id: Uuid,
+        mut data: GameData,
+        bots: Addr<BotWorker>,
+        saves: SaveQueue,
+        players: Arc<dyn PlayerRepository>,
+        lobby: Arc<Lobby>,
+        idle_timeout: Duration,
+    ) -> Self {
+        // new games and games saved before activity was tracked
+        if data.last_activity == 0 {
+            data.last_activity = unix_now();
+        }
+
+        Self {
+            id,
+            data,
+            bots,
+            subscribers: Vec::new(),
+            history: StateHistory::new(RESUME_HISTORY),
+            chat_subscribers: Vec::new(),
+            chat: ChatLog::new(CHAT_HISTORY),
+            saves,
+            players,
+            lobby,
+            idle_timeout,
+            pacing: BotPacing::instant(),
+            seat_speeds: [1.0; 4],
+            span: info_span!("game", game_id = %id),
+        }
+    }
+
+    /// Bots wait out a think time before each move, they move as soon as
+    /// they've picked one otherwise
+    pub fn with_pacing(mut self, pacing: BotPacing) -> Self {
+        self.seat_speeds = pacing.seat_speeds(&mut thread_rng());
+        self.pacing = pacing;
+        self
+    }
+
+    /// Moves the game on after its state changed, the bots keep playing until
+    /// a human needs to act
+    ///
+    /// The state is saved and sent to subscribers after each move. Each bot
+    /// move is played once the bot's think time is up, see [`BotPacing`], the
+    /// game's other messages are handled in the meantime.
+    fn progress(&mut self, ctx: &mut Context<Self>) {
+        let _span = self.span.clone().entered();
+        let was_over = matches!(self.data.display_state, GameProcessingState::GameOver);
+        let needs_bot = advance_display_state(&mut self.data);
+        if !was_over && matches!(self.data.display_state, GameProcessingState::GameOver) {
+            record_game(self.players.as_ref(), &self.data);
+        }
+        self.state_changed();
+
+        if !needs_bot {
+            return;
+        }
+
+        let gs = EuchreGameState::from(self.data.gs.as_str());
+        let revision = self.data.revision;
+        let asked = Instant::now();
+        self.bots
+            .send(BotMove(gs.clone()))
+            .into_actor(self)
+            .map(move |res, act, ctx| match res {
+                Ok((a, difficulty)) => {
+                    let speed = act.seat_speeds[gs.cur_player()];
+                    let think_time = act.pacing.think_time(speed, difficulty, &mut thread_rng());
+                    // picking the move and waiting for a free worker count
+                    // towards the think time
+                    let wait = think_time.saturating_sub(asked.elapsed());
+                    ctx.run_later(wait, move |act, ctx| {
+                        act.play_bot_move(gs, a, revision, ctx)
+                    });
+                }
+                Err(e) => {
+                    let _span = act.span.clone().entered();
+                    error!("failed to get bot move: {}", GameError::BotUnavailable(e));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    fn play_bot_move(
+        &mut self,
+        gs: EuchreGameState,
+        a: Action,
+        revision: u64,
+        ctx: &mut Context<Self>,
+    ) {
+        // a human changed the game while the bot was thinking, e.g. took back
+        // a move or resigned, so the move no longer fits
+        if self.data.revision != revision {
+            return;
+        }
+        debug!(
+            seat = gs.cur_player(),
+            action = %labeled(Euchre::NAME, a),
+            "bot moved"
+        );
+        handle_bot_move(&mut self.data, gs, a);
+        self.progress(ctx);
+    }
+}
+
+impl GameActor {
+    fn state_changed(&mut self) {
+        let _span = self.span.clone().entered();
+        self.data.revision += 1;
+        self.history.push(self.data.clone());
+        self.saves.save(&self.id, &self.data);
+        self.lobby.update(&self.id, &self.data);
+
+        self.subscribers.retain(|s| s.connected());
+        for s in &self.subscribers {
+            s.do_send(GameUpdate {
+                game_id: self.id,
+                data: self.data.clone(),
+            });
+        }
+    }
+
+    /// Sends a new chat message to the chat subscribers
+    fn chat_changed(&mut self, message: ChatMessage) {
+        self.chat_subscribers.retain(|s| s.connected());
+        for s in &self.chat_subscribers {
+            s.do_send(ChatUpdate {
+                game_id: self.id,
+                message: message.clone(),
+            });
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        unix_now().saturating_sub(self.data.last_activity) > self.idle_timeout.as_secs()
+    }
+
+    /// Marks the game abandoned if nobody has acted for too long and stops the
+    /// actor, so it can be removed from the app state
+    ///
+    /// The abandoned game counts as a loss for its human players.
+    fn check_idle(&mut self, ctx: &mut Context<Self>) {
+        if !self.is_idle() {
+            return;
+        }
+        let _span = self.span.clone().entered();
+
+        if !matches!(
+            self.data.display_state,
+            GameProcessingState::GameOver | GameProcessingState::Abandoned
+        ) {
+            info!(
+                bot = crate::bot_version(&self.data),
+                human_score = self.data.human_score,
+                computer_score = self.data.computer_score,
+                players = ?self.data.players,
+                "game abandoned"
+            );
+            self.data.display_state = GameProcessingState::Abandoned;
+            record_game(self.players.as_ref(), &self.data);
+            self.state_changed();
+        }
+        ctx.stop();
+    }
+}
+
+impl Actor for GameActor {
+    type Context = Context<Self>;
+
+    /// Lets the bots move if they start the game
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(IDLE_CHECK_INTERVAL, |act, ctx| act.check_idle(ctx));
+        self.progress(ctx);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "GameData")]
+pub struct GetGame;
+
+impl Handler<GetGame> for GameActor {
+    type Result = MessageResult<GetGame>;
+
+    fn handle(&mut self, _: GetGame, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.data.clone())
+    }
+}
+
+/// Pushed to subscribers whenever the game's state changes
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct GameUpdate {
+    pub game_id: Uuid,
+    pub data: GameData,
+}
+
+/// Registers for [`GameUpdate`]s, returns the current state of the game
+#[derive(Message)]
+#[rtype(result = "GameData")]
+pub struct Subscribe(pub Recipient<GameUpdate>);
+
+impl Handler<Subscribe> for GameActor {
+    type Result = MessageResult<Subscribe>;
+
+    fn handle(&mut self, msg: Subscribe, _: &mut Self::Context) -> Self::Result {
+        if !self.subscribers.contains(&msg.0) {
+            self.subscribers.push(msg.0);
+        }
+        MessageResult(self.data.clone())
+    }
+}
+
+/// Registers a player's connection for [`GameUpdate`]s after a reconnect,
+/// returns the states since `revision` in order
+///
+/// Fails if the player isn't in the game.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<GameData>, GameError>")]
+pub struct Resume {
+    pub recipient: Recipient<GameUpdate>,
+    pub player_id: usize,
+    pub revision: u64,
+}
+
+impl Handler<Resume> for GameActor {
+    type Result = Result<Vec<GameData>, GameError>;
+
+    fn handle(&mut self, msg: Resume, _: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
+        if !self.data.players.contains(&Some(msg.player_id)) {
+            return Err(GameError::NotInGame("player isn't in the game".to_string()));
+        }
+
+        if !self.subscribers.contains(&msg.recipient) {
+            self.subscribers.push(msg.recipient);
+        }
+        let missed = self.history.since(msg.revision, &self.data);
+        info!(
+            player_id = msg.player_id,
+            revision = msg.revision,
+            missed = missed.len(),
+            "player resumed"
+        );
+        Ok(missed)
+    }
+}
+
+/// The last few states of a game
+struct StateHistory {
+    states: VecDeque<GameData>,
+    capacity: usize,
+}
+
+impl StateHistory {
+    fn new(capacity: usize) -> Self {
+        Self {
+            states: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, data: GameData) {
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(data);
+    }
+
+    /// The states after `revision`, oldest first
+    ///
+    /// Only `current` is returned if the client is up to date, or if some of
+    /// the states it missed are no longer kept.
+    fn since(&self, revision: u64, current: &GameData) -> Vec<GameData> {
+        let complete = self
+            .states
+            .front()
+            .is_some_and(|x| x.revision <= revision.saturating_add(1));
+        if !complete || revision >= current.revision {
+            return vec![current.clone()];
+        }
+
+        self.states
+            .iter()
+            .filter(|x| x.revision > revision)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Unsubscribe(pub Recipient<GameUpdate>);
+
+impl Handler<Unsubscribe> for GameActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _: &mut Self::Context) {
+        self.subscribers.retain(|s| s != &msg.0);
+    }
+}
+
+/// Pushed to chat subscribers for each new message
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChatUpdate {
+    pub game_id: Uuid,
+    pub message: ChatMessage,
+}
+
+/// Registers for [`ChatUpdate`]s, returns the recent messages
+#[derive(Message)]
+#[rtype(result = "Vec<ChatMessage>")]
+pub struct SubscribeChat(pub Recipient<ChatUpdate>);
+
+impl Handler<SubscribeChat> for GameActor {
+    type Result = MessageResult<SubscribeChat>;
+
+    fn handle(&mut self, msg: SubscribeChat, _: &mut Self::Context) -> Self::Result {
+        if !self.chat_subscribers.contains(&msg.0) {
+            self.chat_subscribers.push(msg.0);
+        }
+        MessageResult(self.chat.messages())
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnsubscribeChat(pub Recipient<ChatUpdate>);
+
+impl Handler<UnsubscribeChat> for GameActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnsubscribeChat, _: &mut Self::Context) {
+        self.chat_subscribers.retain(|s| s != &msg.0);
+    }
+}
+
+/// Sends a chat message from a player in the game to the chat subscribers
+///
+/// Chatting doesn't keep a game from being abandoned.
+#[derive(Message)]
+#[rtype(result = "Result<(), GameError>")]
+pub struct PostChat {
+    pub player_id: usize,
+    pub content: ChatContent,
+}
+
+impl Handler<PostChat> for GameActor {
+    type Result = Result<(), GameError>;
+
+    fn handle(&mut self, msg: PostChat, _: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
+        let Some(seat) = self
+            .data
+            .players
+            .iter()
+            .position(|x| *x == Some(msg.player_id))
+        else {
+            return Err(GameError::NotInGame(
+                "only players in the game can chat".to_string(),
+            ));
+        };
+
+        let message =
+            self.chat
+                .post(msg.player_id, seat, msg.content, unix_now(), Instant::now())?;
+        debug!(player_id = msg.player_id, "chat message sent");
+        self.chat_changed(message);
+        Ok(())
+    }
+}
+
+/// Applies a player's action, then the bots play until a human needs to act
+///
+/// Returns the state after the player's action, the bot moves are pushed to
+/// subscribers as they're played. A takeback is announced in the chat, and no bots move after it since it's
+/// the player's turn again.
+#[derive(Message)]
+#[rtype(result = "Result<GameData, GameError>")]
+pub struct PostAction(pub ActionRequest);
+
+impl Handler<PostAction> for GameActor {
+    type Result = Result<GameData, GameError>;
+
+    fn handle(&mut self, msg: PostAction, ctx: &mut Self::Context) -> Self::Result {
+        use client_server_messages::GameAction::*;
+        let _span = self.span.clone().entered();
+        let req = msg.0;
+        // watching doesn't keep a game from being abandoned
+        let is_activity = !matches!(req.action, Spectate);
+        let is_takeback = matches!(req.action, RequestTakeback);
+        let result = match req.action {
+            TakeAction(a) => handle_take_action(&mut self.data, a, req.player_id),
+            ReadyTrickClear | ReadyBidClear => handle_ready_clear(&mut self.data, req.player_id),
+            RegisterPlayer => handle_register_player(&mut self.data, req.player_id, None),
+            TakeSeat(seat) => handle_register_player(&mut self.data, req.player_id, Some(seat)),
+            Spectate => handle_spectate(&mut self.data, req.player_id),
+            RequestTakeback => handle_takeback(&mut self.data, req.player_id),
+        };
+
+        result?;
+        if is_activity {
+            self.data.last_activity = unix_now();
+        }
+
+        if is_takeback {
+            if let Some(seat) = self
+                .data
+                .players
+                .iter()
+                .position(|x| *x == Some(req.player_id))
+            {
+                let message =
+                    self.chat
+                        .notice(req.player_id, seat, ChatContent::TookBack, unix_now());
+                self.chat_changed(message);
+            }
+            self.state_changed();
+            return Ok(self.data.clone());
+        }
+
+        self.progress(ctx);
+        Ok(self.data.clone())
+    }
+}
+
+/// Ends the game with a win for the resigning player's opponents
+#[derive(Message)]
+#[rtype(result = "Result<GameData, GameError>")]
+pub struct Resign(pub ResignRequest);
+
+impl Handler<Resign> for GameActor {
+    type Result = Result<GameData, GameError>;
+
+    fn handle(&mut self, msg: Resign, _: &mut Self::Context) -> Self::Result {
+        let _span = self.span.clone().entered();
+        handle_resign(&mut self.data, msg.0.player_id)?;
+        record_game(self.players.as_ref(), &self.data);
+        self.data.last_activity = unix_now();
+        self.state_changed();
+        Ok(self.data.clone())
+    }
+}
+
+/// Picks bot moves on its own thread, a pool of these is shared by all games
+pub struct BotWorker {
+    bot: Box<dyn Agent<EuchreGameState> + Send>,
+}
+
+impl BotWorker {
+    pub fn new(bot: impl Agent<EuchreGameState> + Send + 'static) -> Self {
+        Self { bot: Box::new(bot) }
+    }
+}
+
+impl Actor for BotWorker {
+    type Context = SyncContext<Self>;
+}
+
+/// Returns the bot's action and how hard it was to pick, see
+/// [`Agent::step_with_difficulty`]
+#[derive(Message)]
+#[rtype(result = "(Action, Option<f64>)")]
+pub struct BotMove(pub EuchreGameState);
+
+impl Handler<BotMove> for BotWorker {
+    type Result = MessageResult<BotMove>;
+
+    fn handle(&mut self, msg: BotMove, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.bot.step_with_difficulty(&msg.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use actix::{Actor, SyncArbiter};
+    use card_platypus::agents::agent_from_spec;
+    use client_server_messages::{
+        ActionRequest, GameAction, GameData, GameProcessingState, ResignRequest,
+    };
+    use games::gamestates::euchre::{actions::EAction, Euchre};
+    use uuid::Uuid;
+
+    use super::{BotWorker, GameActor, GetGame, PostAction, Resign, StateHistory};
+    use crate::{
+        lobby::Lobby,
+        new_game,
+        pacing::BotPacing,
+        storage::{MemoryGameRepository, MemoryPlayerRepository, SaveQueue},
+    };
+
+    /// Game with a human in seat 0, who bids first, and slow bots
+    fn start_game() -> actix::Addr<GameActor> {
+        let bots = SyncArbiter::start(1, || {
+            BotWorker::new(agent_from_spec("random").expect("invalid bot spec"))
+        });
+        let pacing = BotPacing {
+            median: Duration::from_millis(300),
+            sigma: 0.0,
+            seat_spread: 0.0,
+            max: Duration::from_millis(300),
+        };
+        GameActor::new(
+            Uuid::new_v4(),
+            GameData::new(new_game(), 7, 1),
+            bots,
+            SaveQueue::start(Arc::new(MemoryGameRepository::default())),
+            Arc::new(MemoryPlayerRepository::default()),
+            Arc::new(Lobby::default()),
+            Duration::from_secs(60),
+        )
+        .with_pacing(pacing)
+        .start()
+    }
+
+    #[actix_web::test]
+    async fn test_bots_move_in_background() {
+        let game = start_game();
+        let pass = ActionRequest::new(7, GameAction::TakeAction(EAction::Pass.into()));
+        let data = game.send(PostAction(pass)).await.unwrap().unwrap();
+        assert!(matches!(
+            data.display_state,
+            GameProcessingState::WaitingMachineMoves { acting_seat: 1 }
+        ));
+
+        // the game answers while the bot is thinking
+        let waiting = game.send(GetGame).await.unwrap();
+        assert_eq!(waiting.revision, data.revision);
+
+        actix::clock::sleep(Duration::from_millis(500)).await;
+        let moved = game.send(GetGame).await.unwrap();
+        assert!(moved.revision > data.revision);
+        assert_ne!(moved.gs, data.gs);
+    }
+
+    #[actix_web::test]
+    async fn test_stale_bot_move_dropped() {
+        let game = start_game();
+        let pass = ActionRequest::new(7, GameAction::TakeAction(EAction::Pass.into()));
+        game.send(PostAction(pass)).await.unwrap().unwrap();
+        let resigned = game
+            .send(Resign(ResignRequest::new(7)))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // the bot was already asked for its move, but the game is over
+        actix::clock::sleep(Duration::from_millis(500)).await;
+        let data = game.send(GetGame).await.unwrap();
+        assert!(matches!(data.display_state, GameProcessingState::GameOver));
+        assert_eq!(data.gs, resigned.gs);
+        assert_eq!(data.revision, resigned.revision);
+    }
+
+    fn revisions(states: Vec<GameData>) -> Vec<u64> {
+        states.iter().map(|x| x.revision).collect()
+    }
+
+    #[test]
+    fn test_state_history() {
+        let mut history = StateHistory::new(3);
+        let mut data = GameData::new(Euchre::new_state(), 7, 1);
+        assert_eq!(revisions(history.since(0, &data)), vec![0]);
+
+        for revision in 1..=5 {
+            data.revision = revision;
+            history.push(data.clone());
+        }
+
+        assert_eq!(revisions(history.since(2, &data)), vec![3, 4, 5]);
+        assert_eq!(revisions(history.since(4, &data)), vec![5]);
+        // up to date, or too far behind to replay
+        assert_eq!(revisions(history.since(5, &data)), vec![5]);
+        assert_eq!(revisions(history.since(1, &data)), vec![5]);
+        assert_eq!(revisions(history.since(u64::MAX, &data)), vec![5]);
+    }
+}
@@ -0,0 +1,152 @@
+use actix::{Actor, Handler, Message, MessageResult, SyncContext};
+use card_platypus::{
+    algorithms::{cfres::CFRES, open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot},
+    policy::Policy,
+};
+use client_server_messages::{ActionAnalysis, GameAnalysis, MoveAnalysis};
+use games::{
+    gamestates::euchre::{Euchre, EuchreGameState},
+    GameState, Player,
+};
+use rand::{rngs::StdRng, thread_rng, SeedableRng};
+
+/// Deals sampled to estimate the value of each action
+const ANALYSIS_WORLDS: usize = 32;
+
+/// Replays `player`'s decisions in `gs`, the `hand`th of its game, through
+/// `policy`. The value of each legal action is estimated by `evaluator`
+pub fn analyze(
+    hand: usize,
+    gs: &EuchreGameState,
+    player: Player,
+    policy: &mut impl Policy<EuchreGameState>,
+    evaluator: &mut PIMCTSBot<EuchreGameState, OpenHandSolver<EuchreGameState>>,
+) -> Vec<MoveAnalysis> {
+    let mut replay = Euchre::new_state();
+    let mut decisions = Vec::new();
+    let mut played = Vec::new();
+    for a in gs.key() {
+        if !replay.is_chance_node() && replay.cur_player() == player {
+            decisions.push(replay.clone());
+            played.push(a);
+        }
+        replay.apply_action(a);
+    }
+
+    let probs = policy.action_probabilities_batch(&decisions);
+    let mut moves = Vec::new();
+    for ((state, a), probs) in decisions.iter().zip(played).zip(probs) {
+        let actions = evaluator
+            .action_values(state)
+            .into_iter()
+            .map(|(action, ev)| ActionAnalysis {
+                action,
+                probability: probs[action],
+                ev,
+            })
+            .collect::<Vec<_>>();
+
+        let best = actions.iter().map(|x| x.ev).fold(f64::MIN, f64::max);
+        let played = actions
+            .iter()
+            .find(|x| x.action == a)
+            .expect("played action must be legal");
+        moves.push(MoveAnalysis {
+            hand,
+            istate: state.istate_string(player),
+            action: a,
+            ev_loss: best - played.ev,
+            actions,
+        });
+    }
+
+    moves
+}
+
+/// Analyses finished games on its own threads, so the analysis doesn't hold
+/// up the workers picking moves for live games
+pub struct AnalysisWorker {
+    policy: CFRES<EuchreGameState>,
+    evaluator: PIMCTSBot<EuchreGameState, OpenHandSolver<EuchreGameState>>,
+}
+
+impl AnalysisWorker {
+    pub fn new(policy: CFRES<EuchreGameState>) -> Self {
+        Self {
+            policy,
+            evaluator: PIMCTSBot::new(
+                ANALYSIS_WORLDS,
+                OpenHandSolver::new_euchre(),
+                StdRng::from_rng(thread_rng()).unwrap(),
+            ),
+        }
+    }
+}
+
+impl Actor for AnalysisWorker {
+    type Context = SyncContext<Self>;
+}
+
+/// Analyses all of a player's decisions in a game in one message
+#[derive(Message)]
+#[rtype(result = "GameAnalysis")]
+pub struct Analyze {
+    /// The hands the player was in, oldest first, with their index in the
+    /// game and the player's seat in them
+    pub hands: Vec<(usize, EuchreGameState, Player)>,
+}
+
+impl Handler<Analyze> for AnalysisWorker {
+    type Result = MessageResult<Analyze>;
+
+    fn handle(&mut self, msg: Analyze, _: &mut Self::Context) -> Self::Result {
+        let moves = msg
+            .hands
+            .iter()
+            .flat_map(|(hand, gs, player)| {
+                analyze(*hand, gs, *player, &mut self.policy, &mut self.evaluator)
+            })
+            .collect();
+        MessageResult(GameAnalysis { moves })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use card_platypus::algorithms::{open_hand_solver::OpenHandSolver, pimcts::PIMCTSBot};
+    use games::{gamestates::euchre::Euchre, GameState};
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use super::analyze;
+
+    #[test]
+    fn test_analyze() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        let mut gs = Euchre::new_state();
+        let mut actions = Vec::new();
+        let mut player_moves = Vec::new();
+        while !gs.is_terminal() {
+            gs.legal_actions(&mut actions);
+            let a = *actions.choose(&mut rng).unwrap();
+            if !gs.is_chance_node() && gs.cur_player() == 1 {
+                player_moves.push(a);
+            }
+            gs.apply_action(a);
+        }
+
+        let mut policy = PIMCTSBot::new(4, OpenHandSolver::new_euchre(), rng.clone());
+        let mut evaluator = PIMCTSBot::new(4, OpenHandSolver::new_euchre(), rng);
+        let moves = analyze(3, &gs, 1, &mut policy, &mut evaluator);
+
+        assert_eq!(
+            moves.iter().map(|m| m.action).collect::<Vec<_>>(),
+            player_moves
+        );
+        for m in moves {
+            assert_eq!(m.hand, 3);
+            assert!(m.ev_loss >= 0.0);
+            let total: f64 = m.actions.iter().map(|x| x.probability).sum();
+            assert!((total - 1.0).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::{collections::HashMap, path::PathBuf, sync::Mutex, time::Instant};
+
+use actix::{Addr, SyncArbiter};
+use card_platypus::{
+    agents::{agent_from_spec, Seedable},
+    algorithms::cfres::CFRES,
+};
+use client_server_messages::{Difficulty, GameData};
+use games::gamestates::euchre::EuchreGameState;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use tracing::{info, warn};
+
+use crate::{actors::BotWorker, analysis::AnalysisWorker};
+
+/// Number of threads picking bot moves for each version, shared by all games
+const BOT_WORKERS: usize = 4;
+/// Number of threads for each of the weaker difficulties
+const WEAK_BOT_WORKERS: usize = 2;
+/// Number of threads analysing finished games, kept apart from the bot workers
+/// so analysis doesn't slow down live games
+const ANALYSIS_WORKERS: usize = 1;
+/// Agent specs for the weaker difficulties, searching 8 deals for each move
+/// at [`Difficulty::PimctsSmall`]
+const RANDOM_SPEC: &str = "random";
+const GREEDY_SPEC: &str = "greedy";
+const PIMCTS_SMALL_SPEC: &str = "pimcts(n=8,solver=euchre)";
+/// Deals whose opening istates are cached at startup, see [`CFRES::prime`]
+const PRIME_DEALS: usize = 100;
+const DEFAULT_BOT_PATH: &str = "/var/lib/card_platypus/infostate.three_card_played";
+const DEFAULT_MAX_CARDS_PLAYED: usize = 3;
+
+/// Weights for a bot policy, games are tagged with the name of the weights
+/// directory
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    pub path: PathBuf,
+    pub max_cards_played: usize,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_BOT_PATH),
+            max_cards_played: DEFAULT_MAX_CARDS_PLAYED,
+        }
+    }
+}
+
+impl BotConfig {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map_or_else(|| self.path.to_string_lossy(), |x| x.to_string_lossy())
+            .to_string()
+    }
+}
+
+struct BotVersion {
+    name: String,
+    workers: Addr<BotWorker>,
+}
+
+impl BotVersion {
+    /// Also returns the loaded bot, its clones share the workers' node store
+    ///
+    /// Must be called from within a running actix system
+    fn load(config: &BotConfig) -> (Self, CFRES<EuchreGameState>) {
+        let name = config.name();
+        let mut bot = CFRES::new_euchre(
+            StdRng::from_rng(thread_rng()).unwrap(),
+            config.max_cards_played,
+            Some(&config.path),
+        );
+
+        let n = bot.num_info_states();
+        info!(
+            "loaded bot {name} with {n} infostates and {} max cards played",
+            config.max_cards_played
+        );
+
+        // the first moves of a fresh server would otherwise wait on disk reads
+        // and rollouts, the workers' clones share the cached istates
+        let start = Instant::now();
+        let primed = bot.prime(PRIME_DEALS);
+        info!(
+            "primed {primed} istates for {name} from {PRIME_DEALS} deals in {:?}",
+            start.elapsed()
+        );
+
+        (Self::from_policy(name, &bot), bot)
+    }
+
+    /// Starts the workers for a loaded policy
+    ///
+    /// Must be called from within a running actix system
+    fn from_policy(name: String, bot: &CFRES<EuchreGameState>) -> Self {
+        // the workers share the bot's node store, the mutex is only needed so
+        // the factory is Sync
+        let bot = Mutex::new(bot.clone());
+        let workers = SyncArbiter::start(BOT_WORKERS, move || {
+            // each worker gets its own play seed so they don't make the same
+            // choices
+            let mut bot = bot.lock().unwrap().clone();
+            bot.set_seed(thread_rng().gen());
+            BotWorker::new(bot)
+        });
+
+        Self { name, workers }
+    }
+
+    /// Starts the workers for a difficulty that doesn't use trained weights,
+    /// returns None for [`Difficulty::Full`]
+    ///
+    /// Must be called from within a running actix system
+    fn start(difficulty: Difficulty) -> Option<Self> {
+        let (name, spec) = match difficulty {
+            Difficulty::Random => ("random", RANDOM_SPEC),
+            Difficulty::Greedy => ("greedy", GREEDY_SPEC),
+            Difficulty::PimctsSmall => ("pimcts_small", PIMCTS_SMALL_SPEC),
+            Difficulty::Full => return None,
+        };
+
+        let workers = SyncArbiter::start(WEAK_BOT_WORKERS, move || {
+            BotWorker::new(agent_from_spec(spec).expect("invalid bot spec"))
+        });
+        Some(Self {
+            name: name.to_string(),
+            workers,
+        })
+    }
+}
+
+/// The bot versions games are played against
+///
+/// New games at [`Difficulty::Full`] play the baseline, or the canary for
+/// `canary_ratio` of them, so a policy upgrade can be compared against the
+/// baseline on real players before it's rolled out. Each game keeps the
+/// version it started with, the version is included in the game's log lines.
+/// Games at the other difficulties play a bot named after the difficulty.
+pub struct BotVersions {
+    baseline: BotVersion,
+    canary: Option<BotVersion>,
+    canary_ratio: f64,
+    weaker: HashMap<Difficulty, BotVersion>,
+    /// Replays finished games through the baseline
+    analysis: Addr<AnalysisWorker>,
+}
+
+impl BotVersions {
+    /// Must be called from within a running actix system
+    pub fn load(baseline: &BotConfig, canary: Option<&BotConfig>, canary_ratio: f64) -> Self {
+        let (baseline, bot) = BotVersion::load(baseline);
+        let canary = canary.map(|c| BotVersion::load(c).0);
+        Self::start(baseline, bot, canary, canary_ratio)
+    }
+
+    /// A baseline that hasn't been trained and no canary, so tests don't need
+    /// the weights
+    ///
+    /// Must be called from within a running actix system
+    #[cfg(test)]
+    pub fn untrained() -> Self {
+        let bot = CFRES::new_euchre(StdRng::seed_from_u64(42), 0, None);
+        let baseline = BotVersion::from_policy("untrained".to_string(), &bot);
+        Self::start(baseline, bot, None, 0.0)
+    }
+
+    /// Starts the weaker difficulties and the analysis workers around the
+    /// loaded versions, analysis uses `bot`, the baseline's policy
+    fn start(
+        baseline: BotVersion,
+        bot: CFRES<EuchreGameState>,
+        canary: Option<BotVersion>,
+        canary_ratio: f64,
+    ) -> Self {
+        if let Some(canary) = &canary {
+            if canary.name == baseline.name {
+                warn!(
+                    "canary and baseline are both named {}, games can't be told apart",
+                    canary.name
+                );
+            }
+            info!("{} of new games play the canary", canary_ratio);
+        }
+
+        let weaker = [
+            Difficulty::Random,
+            Difficulty::Greedy,
+            Difficulty::PimctsSmall,
+        ]
+        .into_iter()
+        .filter_map(|d| BotVersion::start(d).map(|v| (d, v)))
+        .collect();
+
+        let bot = Mutex::new(bot);
+        let analysis = SyncArbiter::start(ANALYSIS_WORKERS, move || {
+            AnalysisWorker::new(bot.lock().unwrap().clone())
+        });
+
+        Self {
+            baseline,
+            canary,
+            canary_ratio: canary_ratio.clamp(0.0, 1.0),
+            weaker,
+            analysis,
+        }
+    }
+
+    /// Picks the version a new game plays against
+    pub fn assign(&self, difficulty: Difficulty) -> String {
+        if let Some(v) = self.weaker.get(&difficulty) {
+            return v.name.clone();
+        }
+
+        match &self.canary {
+            Some(canary) if thread_rng().gen_bool(self.canary_ratio) => canary.name.clone(),
+            _ => self.baseline.name.clone(),
+        }
+    }
+
+    /// Returns the workers that analyse finished games
+    pub fn analysis(&self) -> Addr<AnalysisWorker> {
+        self.analysis.clone()
+    }
+
+    /// Returns the workers for the game's difficulty and version
+    ///
+    /// Full difficulty games without a version, or with one that's no longer
+    /// loaded, are switched to the baseline.
+    pub fn workers(&self, data: &mut GameData) -> Addr<BotWorker> {
+        if let Some(v) = self.weaker.get(&data.difficulty) {
+            data.bot_version = Some(v.name.clone());
+            return v.workers.clone();
+        }
+
+        let version = [Some(&self.baseline), self.canary.as_ref()]
+            .into_iter()
+            .flatten()
+            .find(|v| Some(&v.name) == data.bot_version.as_ref());
+
+        match version {
+            Some(v) => v.workers.clone(),
+            None => {
+                if let Some(old) = &data.bot_version {
+                    warn!(
+                        "bot version {} isn't loaded, switching game to {}",
+                        old, self.baseline.name
+                    );
+                }
+                data.bot_version = Some(self.baseline.name.clone());
+                self.baseline.workers.clone()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use card_platypus::agents::agent_from_spec;
+
+    use super::{BotConfig, GREEDY_SPEC, PIMCTS_SMALL_SPEC, RANDOM_SPEC};
+
+    #[test]
+    fn test_bot_config_name() {
+        assert_eq!(BotConfig::default().name(), "infostate.three_card_played");
+
+        let config = BotConfig {
+            path: PathBuf::from("/var/lib/card_platypus/infostate.four_card_played/"),
+            max_cards_played: 4,
+        };
+        assert_eq!(config.name(), "infostate.four_card_played");
+    }
+
+    #[test]
+    fn test_weaker_bot_specs() {
+        for spec in [RANDOM_SPEC, GREEDY_SPEC, PIMCTS_SMALL_SPEC] {
+            assert!(agent_from_spec(spec).is_ok(), "{}", spec);
+        }
+    }
+}
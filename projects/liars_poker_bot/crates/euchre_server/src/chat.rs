@@ -0,0 +1,193 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+use client_server_messages::{ChatContent, ChatMessage, MAX_CHAT_LEN};
+
+use crate::{
+    actors::GameError,
+    limits::{BucketConfig, TokenBucket},
+};
+
+/// Messages kept for each game, sent to clients when they subscribe
+pub const CHAT_HISTORY: usize = 50;
+
+/// A player can send a few messages at once, and then one every few seconds
+const CHAT_LIMIT: BucketConfig = BucketConfig {
+    burst: 5.0,
+    per_second: 0.3,
+};
+
+/// Replaced with asterisks, words containing these are matched too
+const BLOCKED_WORDS: [&str; 9] = [
+    "fuck", "shit", "bullshit", "bitch", "cunt", "dick", "bastard", "asshole", "whore",
+];
+
+/// Recent chat messages of a game, and how quickly each player is sending
+pub struct ChatLog {
+    messages: VecDeque<ChatMessage>,
+    capacity: usize,
+    limits: HashMap<usize, TokenBucket>,
+}
+
+impl ChatLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+            limits: HashMap::new(),
+        }
+    }
+
+    /// The kept messages, oldest first
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        self.messages.iter().cloned().collect()
+    }
+
+    /// Checks and filters a message, adding it to the log. Returns the
+    /// message as it should be sent to everyone
+    pub fn post(
+        &mut self,
+        player_id: usize,
+        seat: usize,
+        content: ChatContent,
+        sent_at: u64,
+        now: Instant,
+    ) -> Result<ChatMessage, GameError> {
+        let content = match content {
+            ChatContent::Text(text) => {
+                let text = text.trim();
+                if text.is_empty() {
+                    return Err(GameError::BadRequest("chat message is empty".to_string()));
+                }
+                if text.chars().count() > MAX_CHAT_LEN {
+                    return Err(GameError::BadRequest(format!(
+                        "chat messages can be at most {} characters",
+                        MAX_CHAT_LEN
+                    )));
+                }
+                ChatContent::Text(filter_profanity(text))
+            }
+            ChatContent::TookBack => {
+                return Err(GameError::BadRequest(
+                    "takebacks are announced by the server".to_string(),
+                ))
+            }
+            emote => emote,
+        };
+
+        self.limits
+            .entry(player_id)
+            .or_insert_with(|| TokenBucket::new(&CHAT_LIMIT, now))
+            .take(&CHAT_LIMIT, now)
+            .map_err(GameError::RateLimited)?;
+
+        Ok(self.notice(player_id, seat, content, sent_at))
+    }
+
+    /// Adds a message on behalf of a player without checking it, for the
+    /// server's announcements
+    pub fn notice(
+        &mut self,
+        player_id: usize,
+        seat: usize,
+        content: ChatContent,
+        sent_at: u64,
+    ) -> ChatMessage {
+        let message = ChatMessage {
+            player_id,
+            seat,
+            content,
+            sent_at,
+        };
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message.clone());
+        message
+    }
+}
+
+/// Stars out the words that are a blocked word, ignoring case and
+/// punctuation. Only whole words match so names like "Dickens" get through.
+fn filter_profanity(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            let lower = word.to_lowercase();
+            let blocked = lower
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|part| BLOCKED_WORDS.contains(&part));
+            if blocked {
+                "*".repeat(word.chars().count())
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use client_server_messages::{ChatContent, Emote, MAX_CHAT_LEN};
+
+    use super::{filter_profanity, ChatLog};
+    use crate::actors::GameError;
+
+    #[test]
+    fn test_filter_profanity() {
+        assert_eq!(filter_profanity("nice  hand"), "nice  hand");
+        assert_eq!(filter_profanity("oh SHIT, euchred"), "oh ***** euchred");
+        assert_eq!(filter_profanity("bullshit"), "********");
+        assert_eq!(filter_profanity("dick's"), "******");
+        assert_eq!(
+            filter_profanity("reading Dickens, classic"),
+            "reading Dickens, classic"
+        );
+    }
+
+    #[test]
+    fn test_chat_log() {
+        let mut log = ChatLog::new(3);
+        let now = Instant::now();
+        let text = |x: &str| ChatContent::Text(x.to_string());
+
+        let msg = log.post(7, 0, text("  hi partner "), 1, now).unwrap();
+        assert_eq!(msg.content, text("hi partner"));
+        assert!(matches!(
+            log.post(7, 0, text(" "), 1, now),
+            Err(GameError::BadRequest(_))
+        ));
+        let long = "a".repeat(MAX_CHAT_LEN + 1);
+        assert!(matches!(
+            log.post(7, 0, text(&long), 1, now),
+            Err(GameError::BadRequest(_))
+        ));
+        assert!(matches!(
+            log.post(7, 0, ChatContent::TookBack, 1, now),
+            Err(GameError::BadRequest(_))
+        ));
+
+        // each player has their own limit
+        for _ in 0..4 {
+            log.post(7, 0, ChatContent::Emote(Emote::NiceHand), 1, now)
+                .unwrap();
+        }
+        assert!(matches!(
+            log.post(7, 0, text("hi"), 1, now),
+            Err(GameError::RateLimited(_))
+        ));
+        log.post(42, 2, text("hi"), 1, now).unwrap();
+        log.post(7, 0, text("hi"), 1, now + Duration::from_secs(10))
+            .unwrap();
+
+        // only the latest messages are kept
+        let messages = log.messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].player_id, 42);
+        assert_eq!(messages[2].content, text("hi"));
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    http::{header, StatusCode},
+    post,
+    web::{self, Json},
+    HttpRequest, HttpResponse, Responder, ResponseError,
+};
+use client_server_messages::{
+    ApiError, ErrorCode, FeedbackRequest, MAX_FEEDBACK_ACTIONS, MAX_FEEDBACK_LEN,
+};
+use dashmap::DashMap;
+use games::Action;
+use serde::Serialize;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::limits::{client_ip, BucketConfig, TokenBucket};
+
+/// An address can send a few reports at once, and then one a minute
+const FEEDBACK_LIMIT: BucketConfig = BucketConfig {
+    burst: 3.0,
+    per_second: 1.0 / 60.0,
+};
+
+/// Shared by every address, so spreading reports over many addresses can't
+/// fill the disk
+const TOTAL_FEEDBACK_LIMIT: BucketConfig = BucketConfig {
+    burst: 30.0,
+    per_second: 1.0 / 10.0,
+};
+
+/// A line of the feedback file
+#[derive(Serialize)]
+struct FeedbackRecord<'a> {
+    /// Seconds since the unix epoch
+    received_at: u64,
+    game_id: String,
+    message: &'a str,
+    actions: &'a [Action],
+}
+
+/// Appends feedback to a file as json lines, limiting how quickly it can be
+/// sent
+pub struct FeedbackLog {
+    file: Mutex<File>,
+    ips: DashMap<String, TokenBucket>,
+    total: Mutex<TokenBucket>,
+}
+
+impl FeedbackLog {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+            ips: DashMap::new(),
+            total: Mutex::new(TokenBucket::new(&TOTAL_FEEDBACK_LIMIT, Instant::now())),
+        })
+    }
+
+    /// Checks the feedback and the limits, then saves it
+    pub fn submit(
+        &self,
+        ip: &str,
+        req: &FeedbackRequest,
+        now: Instant,
+    ) -> Result<(), FeedbackError> {
+        let game_id = Uuid::parse_str(&req.game_id)
+            .map_err(|_| FeedbackError::Invalid("couldn't parse game id".to_string()))?;
+        let message = check_message(&req.message)?;
+        if req.actions.len() > MAX_FEEDBACK_ACTIONS {
+            return Err(FeedbackError::Invalid(format!(
+                "at most {} actions can be attached",
+                MAX_FEEDBACK_ACTIONS
+            )));
+        }
+
+        self.ips
+            .entry(ip.to_string())
+            .or_insert_with(|| TokenBucket::new(&FEEDBACK_LIMIT, now))
+            .take(&FEEDBACK_LIMIT, now)
+            .map_err(FeedbackError::RateLimited)?;
+        self.total
+            .lock()
+            .unwrap()
+            .take(&TOTAL_FEEDBACK_LIMIT, now)
+            .map_err(FeedbackError::RateLimited)?;
+
+        let record = FeedbackRecord {
+            received_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            game_id: game_id.to_string(),
+            message: &message,
+            actions: &req.actions,
+        };
+        let mut line = serde_json::to_vec(&record).map_err(|e| FeedbackError::Storage(e.into()))?;
+        line.push(b'\n');
+        // one write per record so lines from concurrent requests don't interleave
+        self.file
+            .lock()
+            .unwrap()
+            .write_all(&line)
+            .map_err(FeedbackError::Storage)?;
+        Ok(())
+    }
+
+    /// Drops the buckets of addresses that have refilled, see
+    /// [`TokenBucket::is_full`]. Returns the number removed
+    pub fn remove_idle(&self, now: Instant) -> usize {
+        let before = self.ips.len();
+        self.ips.retain(|_, b| !b.is_full(&FEEDBACK_LIMIT, now));
+        before - self.ips.len()
+    }
+}
+
+/// Trims the message, rejecting empty and long messages and control
+/// characters other than line breaks
+fn check_message(message: &str) -> Result<String, FeedbackError> {
+    let message = message.trim();
+    if message.is_empty() {
+        return Err(FeedbackError::Invalid("feedback is empty".to_string()));
+    }
+    if message.chars().count() > MAX_FEEDBACK_LEN {
+        return Err(FeedbackError::Invalid(format!(
+            "feedback can be at most {} characters",
+            MAX_FEEDBACK_LEN
+        )));
+    }
+    if message.chars().any(|c| c.is_control() && c != '\n') {
+        return Err(FeedbackError::Invalid(
+            "feedback can't contain control characters".to_string(),
+        ));
+    }
+    Ok(message.to_string())
+}
+
+#[derive(Debug)]
+pub enum FeedbackError {
+    Invalid(String),
+    /// How long until another report can be sent
+    RateLimited(Duration),
+    Storage(std::io::Error),
+}
+
+impl Display for FeedbackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeedbackError::Invalid(x) => f.write_str(x),
+            FeedbackError::RateLimited(retry_after) => write!(
+                f,
+                "too much feedback, retry in {:.0}s",
+                retry_after.as_secs_f64().ceil()
+            ),
+            FeedbackError::Storage(_) => f.write_str("couldn't save feedback"),
+        }
+    }
+}
+
+impl ResponseError for FeedbackError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            FeedbackError::Invalid(_) => StatusCode::BAD_REQUEST,
+            FeedbackError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            FeedbackError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let code = match self {
+            FeedbackError::Invalid(_) => ErrorCode::InvalidRequest,
+            FeedbackError::RateLimited(_) => ErrorCode::RateLimited,
+            FeedbackError::Storage(_) => ErrorCode::Internal,
+        };
+        let mut res = HttpResponse::build(self.status_code());
+        if let FeedbackError::RateLimited(retry_after) = self {
+            let secs = retry_after.as_secs_f64().ceil() as u64;
+            res.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        res.json(ApiError::new(code, self.to_string()))
+    }
+}
+
+/// Report a problem with a game, e.g. a strange bot play
+#[utoipa::path(
+    request_body = FeedbackRequest,
+    responses(
+        (status = 204, description = "feedback saved"),
+        (status = 400, description = "invalid game id, message or actions", body = ApiError),
+        (status = 429, description = "too much feedback has been sent recently", body = ApiError)
+    )
+)]
+#[post("/api/feedback")]
+pub async fn post_feedback(
+    req: HttpRequest,
+    body: Json<FeedbackRequest>,
+    feedback: web::Data<FeedbackLog>,
+) -> impl Responder {
+    match feedback.submit(&client_ip(&req), &body, Instant::now()) {
+        Ok(()) => {
+            info!(game_id = body.game_id, "feedback received");
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            if let FeedbackError::Storage(x) = &e {
+                error!("failed to save feedback: {:?}", x);
+            }
+            e.error_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        web, App,
+    };
+    use client_server_messages::{
+        ApiError, ErrorCode, FeedbackRequest, MAX_FEEDBACK_ACTIONS, MAX_FEEDBACK_LEN,
+    };
+    use games::Action;
+    use uuid::Uuid;
+
+    use super::{post_feedback, FeedbackError, FeedbackLog, FEEDBACK_LIMIT};
+
+    fn temp_log(name: &str) -> (std::path::PathBuf, FeedbackLog) {
+        let path = std::env::temp_dir().join(format!("{}-{}.jsonl", name, Uuid::new_v4()));
+        let log = FeedbackLog::open(&path).unwrap();
+        (path, log)
+    }
+
+    fn request(message: &str) -> FeedbackRequest {
+        FeedbackRequest {
+            game_id: Uuid::new_v4().to_string(),
+            message: message.to_string(),
+            actions: vec![Action(3), Action(14)],
+        }
+    }
+
+    #[test]
+    fn test_submit_feedback() {
+        let (path, log) = temp_log("test_submit_feedback");
+        let now = Instant::now();
+
+        let req = request("  why did my partner trump my ace?\n ");
+        log.submit("1.2.3.4", &req, now).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let record: serde_json::Value = serde_json::from_str(saved.trim_end()).unwrap();
+        assert_eq!(record["game_id"], req.game_id.as_str());
+        assert_eq!(record["message"], "why did my partner trump my ace?");
+        assert_eq!(record["actions"], serde_json::json!([3, 14]));
+
+        let invalid = [
+            FeedbackRequest {
+                game_id: "not a game".to_string(),
+                ..request("hi")
+            },
+            request(" "),
+            request(&"a".repeat(MAX_FEEDBACK_LEN + 1)),
+            request("hi\u{7}"),
+            FeedbackRequest {
+                actions: vec![Action(0); MAX_FEEDBACK_ACTIONS + 1],
+                ..request("hi")
+            },
+        ];
+        for req in invalid {
+            assert!(matches!(
+                log.submit("1.2.3.4", &req, now),
+                Err(FeedbackError::Invalid(_))
+            ));
+        }
+
+        // invalid reports don't use up the limit
+        for _ in 1..FEEDBACK_LIMIT.burst as usize {
+            log.submit("1.2.3.4", &request("hi"), now).unwrap();
+        }
+        assert!(matches!(
+            log.submit("1.2.3.4", &request("hi"), now),
+            Err(FeedbackError::RateLimited(_))
+        ));
+        log.submit("5.6.7.8", &request("hi"), now).unwrap();
+
+        assert_eq!(log.remove_idle(now), 0);
+        assert_eq!(log.remove_idle(now + Duration::from_secs(3600)), 2);
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(saved.lines().count(), FEEDBACK_LIMIT.burst as usize + 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_post_feedback() {
+        let (path, log) = temp_log("test_post_feedback");
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(log))
+                .service(post_feedback),
+        )
+        .await;
+
+        let post = |req: &FeedbackRequest| {
+            TestRequest::post()
+                .uri("/api/feedback")
+                .set_json(req)
+                .to_request()
+        };
+        let resp = call_service(&app, post(&request("the bot passed a sure thing"))).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let resp = call_service(&app, post(&request(""))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: ApiError = read_body_json(resp).await;
+        assert_eq!(body.code, ErrorCode::InvalidRequest);
+
+        for _ in 1..FEEDBACK_LIMIT.burst as usize {
+            call_service(&app, post(&request("hi"))).await;
+        }
+        let resp = call_service(&app, post(&request("hi"))).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body: ApiError = read_body_json(resp).await;
+        assert_eq!(body.code, ErrorCode::RateLimited);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+use std::{collections::HashMap, time::Instant};
+
+use actix::{
+    fut, Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, ContextFutureSpawner, Handler,
+    StreamHandler, WrapFuture,
+};
+use actix_web::web;
+use actix_web_actors::ws;
+use client_server_messages::{
+    ChatContent, WsClientMessage, WsServerMessage, PROTOCOL_VERSION, WS_CLIENT_TIMEOUT,
+    WS_HEARTBEAT_INTERVAL,
+};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    actors::{
+        ChatUpdate, GameActor, GameUpdate, PostChat, Resume, Subscribe, SubscribeChat, Unsubscribe,
+        UnsubscribeChat,
+    },
+    players::authenticate,
+    AppState,
+};
+
+/// A client's websocket connection, pushes the state and chat messages of the
+/// games it has subscribed to
+pub struct EuchreGameWs {
+    state: web::Data<AppState>,
+    /// Last time anything was heard from the client
+    hb: Instant,
+    /// Games subscribed to and the player they're viewed as
+    subscriptions: HashMap<Uuid, (Addr<GameActor>, Option<usize>)>,
+}
+
+impl EuchreGameWs {
+    pub fn new(state: web::Data<AppState>) -> Self {
+        Self {
+            state,
+            hb: Instant::now(),
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    fn send(ctx: &mut ws::WebsocketContext<Self>, msg: &WsServerMessage) {
+        match serde_json::to_string(msg) {
+            Ok(text) => ctx.text(text),
+            Err(e) => warn!("failed to serialize websocket message: {}", e),
+        }
+    }
+
+    /// Pings the client and closes the connection if it's stopped responding
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(WS_HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > WS_CLIENT_TIMEOUT {
+                debug!("websocket client timed out, disconnecting");
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping(b"");
+        });
+    }
+
+    /// Looks up a game the client asked for, sending an error if it can't be found
+    fn find_game(
+        &self,
+        game_id: &str,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Option<(Uuid, Addr<GameActor>)> {
+        let Ok(id) = Uuid::parse_str(game_id) else {
+            Self::send(
+                ctx,
+                &WsServerMessage::Error("couldn't parse game id".to_string()),
+            );
+            return None;
+        };
+
+        let Some(game) = self.state.game(&id) else {
+            Self::send(
+                ctx,
+                &WsServerMessage::Error(format!("game not found: {}", id)),
+            );
+            return None;
+        };
+
+        Some((id, game))
+    }
+
+    /// Checks `token` belongs to the player, sending an error if it doesn't
+    fn authenticate(
+        &self,
+        player_id: usize,
+        token: Option<&str>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> bool {
+        match authenticate(self.state.players.as_ref(), player_id, token) {
+            Ok(()) => true,
+            Err(e) => {
+                Self::send(ctx, &WsServerMessage::Error(e.to_string()));
+                false
+            }
+        }
+    }
+
+    fn subscribe(
+        &mut self,
+        game_id: String,
+        viewer: Option<usize>,
+        token: Option<String>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        if let Some(player_id) = viewer {
+            if !self.authenticate(player_id, token.as_deref(), ctx) {
+                return;
+            }
+        }
+        let Some((id, game)) = self.find_game(&game_id, ctx) else {
+            return;
+        };
+
+        game.send(Subscribe(ctx.address().recipient()))
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(data) => {
+                        act.subscribe_chat(id, &game, ctx);
+                        act.subscriptions.insert(id, (game, viewer));
+                        let data = data.client_view(viewer);
+                        Self::send(ctx, &WsServerMessage::GameData { game_id, data });
+                    }
+                    Err(_) => Self::send(
+                        ctx,
+                        &WsServerMessage::Error(format!("game not found: {}", id)),
+                    ),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    /// Puts a reconnecting player back in their seat, sending the states they
+    /// missed
+    fn resume(
+        &mut self,
+        game_id: String,
+        player_id: usize,
+        token: String,
+        revision: u64,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        if !self.authenticate(player_id, Some(&token), ctx) {
+            return;
+        }
+        let Some((id, game)) = self.find_game(&game_id, ctx) else {
+            return;
+        };
+
+        let resume = Resume {
+            recipient: ctx.address().recipient(),
+            player_id,
+            revision,
+        };
+        game.send(resume)
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Ok(missed)) => {
+                        act.subscribe_chat(id, &game, ctx);
+                        act.subscriptions.insert(id, (game, Some(player_id)));
+                        for data in missed {
+                            let data = data.client_view(Some(player_id));
+                            let game_id = game_id.clone();
+                            Self::send(ctx, &WsServerMessage::GameData { game_id, data });
+                        }
+                    }
+                    Ok(Err(e)) => Self::send(ctx, &WsServerMessage::Error(e.to_string())),
+                    Err(_) => Self::send(
+                        ctx,
+                        &WsServerMessage::Error(format!("game not found: {}", id)),
+                    ),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    /// Follows the game's chat, sending its recent messages
+    fn subscribe_chat(
+        &mut self,
+        id: Uuid,
+        game: &Addr<GameActor>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        game.send(SubscribeChat(ctx.address().recipient()))
+            .into_actor(self)
+            .then(move |res, _, ctx| {
+                if let Ok(messages) = res {
+                    let game_id = id.to_string();
+                    Self::send(ctx, &WsServerMessage::ChatHistory { game_id, messages });
+                }
+                fut::ready(())
+            })
+            .spawn(ctx);
+    }
+
+    fn unsubscribe(&mut self, game_id: &str, ctx: &mut ws::WebsocketContext<Self>) {
+        let Some((game, _)) = Uuid::parse_str(game_id)
+            .ok()
+            .and_then(|id| self.subscriptions.remove(&id))
+        else {
+            return;
+        };
+        game.do_send(Unsubscribe(ctx.address().recipient()));
+        game.do_send(UnsubscribeChat(ctx.address().recipient()));
+    }
+
+    /// Posts as the player the game was subscribed as, their token was
+    /// checked then
+    fn chat(
+        &mut self,
+        game_id: String,
+        content: ChatContent,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let Some((game, player_id)) = Uuid::parse_str(&game_id)
+            .ok()
+            .and_then(|id| self.subscriptions.get(&id))
+            .and_then(|(game, viewer)| Some((game.clone(), (*viewer)?)))
+        else {
+            Self::send(
+                ctx,
+                &WsServerMessage::Error("subscribe to the game as a player to chat".to_string()),
+            );
+            return;
+        };
+
+        game.send(PostChat { player_id, content })
+            .into_actor(self)
+            .then(move |res, _, ctx| {
+                match res {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => Self::send(ctx, &WsServerMessage::Error(e.to_string())),
+                    Err(_) => Self::send(
+                        ctx,
+                        &WsServerMessage::Error(format!("game not found: {}", game_id)),
+                    ),
+                }
+                fut::ready(())
+            })
+            .spawn(ctx);
+    }
+}
+
+impl Actor for EuchreGameWs {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.heartbeat(ctx);
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        for (game, _) in self.subscriptions.values() {
+            game.do_send(Unsubscribe(ctx.address().recipient()));
+            game.do_send(UnsubscribeChat(ctx.address().recipient()));
+        }
+    }
+}
+
+impl Handler<GameUpdate> for EuchreGameWs {
+    type Result = ();
+
+    fn handle(&mut self, msg: GameUpdate, ctx: &mut Self::Context) {
+        let viewer = self
+            .subscriptions
+            .get(&msg.game_id)
+            .and_then(|(_, viewer)| *viewer);
+        Self::send(
+            ctx,
+            &WsServerMessage::GameData {
+                game_id: msg.game_id.to_string(),
+                data: msg.data.client_view(viewer),
+            },
+        );
+    }
+}
+
+impl Handler<ChatUpdate> for EuchreGameWs {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChatUpdate, ctx: &mut Self::Context) {
+        Self::send(
+            ctx,
+            &WsServerMessage::Chat {
+                game_id: msg.game_id.to_string(),
+                message: msg.message,
+            },
+        );
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for EuchreGameWs {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("websocket protocol error: {}", e);
+                ctx.stop();
+                return;
+            }
+        };
+
+        self.hb = Instant::now();
+        match msg {
+            ws::Message::Ping(msg) => ctx.pong(&msg),
+            ws::Message::Pong(_) => {}
+            ws::Message::Text(text) => match serde_json::from_str::<WsClientMessage>(&text) {
+                Ok(WsClientMessage::Subscribe {
+                    game_id,
+                    player_id,
+                    token,
+                }) => self.subscribe(game_id, player_id, token, ctx),
+                Ok(WsClientMessage::Resume {
+                    protocol_version, ..
+                }) if protocol_version != PROTOCOL_VERSION => Self::send(
+                    ctx,
+                    &WsServerMessage::VersionMismatch {
+                        protocol_version: PROTOCOL_VERSION,
+                    },
+                ),
+                Ok(WsClientMessage::Resume {
+                    game_id,
+                    player_id,
+                    token,
+                    revision,
+                    ..
+                }) => self.resume(game_id, player_id, token, revision, ctx),
+                Ok(WsClientMessage::Unsubscribe { game_id }) => self.unsubscribe(&game_id, ctx),
+                Ok(WsClientMessage::Chat { game_id, content }) => self.chat(game_id, content, ctx),
+                Ok(WsClientMessage::Ping) => Self::send(ctx, &WsServerMessage::Pong),
+                Err(e) => Self::send(
+                    ctx,
+                    &WsServerMessage::Error(format!("couldn't parse message: {}", e)),
+                ),
+            },
+            ws::Message::Close(reason) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
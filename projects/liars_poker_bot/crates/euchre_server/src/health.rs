@@ -0,0 +1,217 @@
+use std::{
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    middleware::Next,
+    web, HttpResponse, Responder,
+};
+use client_server_messages::{ApiError, ErrorCode};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::AppState;
+
+/// How long clients are told to wait before retrying while the server starts
+const RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// Where the server is in starting up, games are only served once it's
+/// [`StartupState::Ready`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum StartupState {
+    /// Accepting connections, the bots haven't started loading
+    Starting,
+    /// Loading and priming the bot weights, this takes minutes for the larger
+    /// policies
+    LoadingBots,
+    /// Restarting the games that were active when the server stopped
+    ResumingGames,
+    Ready,
+    /// The bots couldn't be loaded, the server needs to be restarted
+    Failed {
+        reason: String,
+    },
+}
+
+/// The [`StartupState`] and how long the server has been starting for
+pub struct Startup {
+    state: RwLock<StartupState>,
+    started: Instant,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self {
+            state: RwLock::new(StartupState::Starting),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Startup {
+    pub fn get(&self) -> StartupState {
+        self.state.read().unwrap().clone()
+    }
+
+    pub fn set(&self, state: StartupState) {
+        match &state {
+            StartupState::Failed { reason } => {
+                error!(
+                    "startup failed after {:?}: {}",
+                    self.started.elapsed(),
+                    reason
+                )
+            }
+            state => info!("startup {:?} after {:?}", state, self.started.elapsed()),
+        }
+        *self.state.write().unwrap() = state;
+    }
+
+    pub fn is_ready(&self) -> bool {
+        *self.state.read().unwrap() == StartupState::Ready
+    }
+}
+
+/// Body of `/readyz`
+#[derive(Debug, Serialize)]
+struct Readiness {
+    #[serde(flatten)]
+    startup: StartupState,
+    /// Whether the game and player storage answered
+    storage: bool,
+}
+
+/// Liveness check, answers as soon as the server is accepting connections
+#[get("/healthz")]
+pub async fn healthz() -> impl Responder {
+    HttpResponse::Ok().body("ok")
+}
+
+/// Readiness check, 200 once the bots are loaded, the saved games resumed and
+/// the storage can be reached, otherwise 503
+#[get("/readyz")]
+pub async fn readyz(data: web::Data<AppState>) -> impl Responder {
+    let storage = match data.check_storage() {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("storage isn't reachable: {:?}", e);
+            false
+        }
+    };
+    let readiness = Readiness {
+        startup: data.startup.get(),
+        storage,
+    };
+
+    if readiness.startup == StartupState::Ready && readiness.storage {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+/// Whether the request needs the bots, clients can still load the page and
+/// check the server's version while it starts
+fn needs_ready(path: &str) -> bool {
+    (path.starts_with("/api") && path != "/api/version" && path != "/api/spec")
+        || path.starts_with("/ws/")
+}
+
+/// Turns away api and websocket requests with a 503 until the server is ready
+pub async fn require_ready<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let ready = req
+        .app_data::<web::Data<Startup>>()
+        .is_none_or(|s| s.is_ready());
+    if !ready && needs_ready(req.path()) {
+        let resp = HttpResponse::ServiceUnavailable()
+            .insert_header(("Retry-After", RETRY_AFTER.as_secs().to_string()))
+            .json(ApiError::new(
+                ErrorCode::BotUnavailable,
+                "the server is starting up, try again shortly",
+            ));
+        return Ok(req.into_response(resp).map_into_right_body());
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        middleware::from_fn,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        web, App, HttpResponse,
+    };
+    use client_server_messages::{ApiError, ErrorCode};
+
+    use super::{require_ready, Startup, StartupState};
+
+    #[actix_web::test]
+    async fn test_require_ready() {
+        let startup = web::Data::new(Startup::default());
+        let app = init_service(
+            App::new()
+                .app_data(startup.clone())
+                .wrap(from_fn(require_ready))
+                .route("/api/lobby", web::get().to(HttpResponse::Ok))
+                .route("/api/version", web::get().to(HttpResponse::Ok))
+                .route("/ws/", web::get().to(HttpResponse::Ok))
+                .route("/index.html", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let get = |uri| TestRequest::get().uri(uri).to_request();
+        for state in [
+            StartupState::Starting,
+            StartupState::LoadingBots,
+            StartupState::Failed {
+                reason: "missing weights".to_string(),
+            },
+        ] {
+            startup.set(state);
+            for uri in ["/api/lobby", "/ws/"] {
+                let resp = call_service(&app, get(uri)).await;
+                assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE, "{}", uri);
+                let body: ApiError = read_body_json(resp).await;
+                assert_eq!(body.code, ErrorCode::BotUnavailable);
+            }
+            for uri in ["/api/version", "/index.html"] {
+                let resp = call_service(&app, get(uri)).await;
+                assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+            }
+        }
+
+        startup.set(StartupState::Ready);
+        assert!(startup.is_ready());
+        for uri in ["/api/lobby", "/ws/", "/api/version", "/index.html"] {
+            let resp = call_service(&app, get(uri)).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn test_startup_state_json() {
+        let failed = StartupState::Failed {
+            reason: "missing weights".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(failed).unwrap(),
+            serde_json::json!({"state": "failed", "reason": "missing weights"})
+        );
+        assert_eq!(
+            serde_json::to_value(StartupState::LoadingBots).unwrap(),
+            serde_json::json!({"state": "loading_bots"})
+        );
+    }
+}
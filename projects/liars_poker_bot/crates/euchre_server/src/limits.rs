@@ -0,0 +1,491 @@
+use std::{
+    fmt::Display,
+    hash::Hash,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::{JsonPayloadError, PayloadError},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use client_server_messages::{ApiError, ErrorCode, PLAYER_TOKEN_HEADER};
+use dashmap::DashMap;
+use serde::Deserialize;
+
+use crate::{players::authenticate, storage::PlayerRepository};
+
+/// Requests to paths starting with these count against the rate limits,
+/// static files are always served
+const LIMITED_PATHS: [&str; 2] = ["/api", "/ws"];
+
+/// A client can make `burst` requests at once, and then `per_second` requests
+/// a second after that
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketConfig {
+    pub burst: f64,
+    pub per_second: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitConfig {
+    /// Shared by every request from an address
+    pub per_ip: BucketConfig,
+    /// Shared by every request made with a player's token, wherever it comes
+    /// from
+    pub per_player: BucketConfig,
+    /// Largest request body accepted
+    pub max_payload_bytes: usize,
+    /// Reverse proxies in front of the server, the forwarded headers are only
+    /// believed for requests they make
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for LimitConfig {
+    fn default() -> Self {
+        Self {
+            per_ip: BucketConfig {
+                burst: 60.0,
+                per_second: 20.0,
+            },
+            per_player: BucketConfig {
+                burst: 30.0,
+                per_second: 10.0,
+            },
+            max_payload_bytes: 16 * 1024,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+pub struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(config: &BucketConfig, now: Instant) -> Self {
+        Self {
+            tokens: config.burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.per_second).min(config.burst);
+        self.updated = now;
+    }
+
+    /// Takes a token, returns how long until the next one if the bucket is empty
+    pub fn take(&mut self, config: &BucketConfig, now: Instant) -> Result<(), Duration> {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - self.tokens) / config.per_second;
+            Err(Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX))
+        }
+    }
+
+    /// Whether the bucket has refilled, idle buckets can be dropped since
+    /// they'd be recreated full anyway
+    pub fn is_full(&mut self, config: &BucketConfig, now: Instant) -> bool {
+        self.refill(config, now);
+        self.tokens >= config.burst
+    }
+}
+
+/// Token buckets for every address and player that has made a request recently
+pub struct RateLimiter {
+    config: LimitConfig,
+    ips: DashMap<String, TokenBucket>,
+    players: DashMap<usize, TokenBucket>,
+    /// Checks the tokens of requests made as a player
+    accounts: Arc<dyn PlayerRepository>,
+}
+
+impl RateLimiter {
+    pub fn new(config: LimitConfig, accounts: Arc<dyn PlayerRepository>) -> Self {
+        Self {
+            config,
+            ips: DashMap::new(),
+            players: DashMap::new(),
+            accounts,
+        }
+    }
+
+    pub fn config(&self) -> &LimitConfig {
+        &self.config
+    }
+
+    fn check_ip(&self, ip: &str, now: Instant) -> Result<(), LimitError> {
+        take(&self.ips, ip.to_string(), &self.config.per_ip, now)
+    }
+
+    fn check_player(&self, player_id: usize, now: Instant) -> Result<(), LimitError> {
+        take(&self.players, player_id, &self.config.per_player, now)
+    }
+
+    /// Drops the buckets that have refilled, see [`TokenBucket::is_full`].
+    /// Returns the number removed
+    pub fn remove_idle(&self, now: Instant) -> usize {
+        let before = self.ips.len() + self.players.len();
+        self.ips.retain(|_, b| !b.is_full(&self.config.per_ip, now));
+        self.players
+            .retain(|_, b| !b.is_full(&self.config.per_player, now));
+        before - self.ips.len() - self.players.len()
+    }
+}
+
+fn take<K: Hash + Eq>(
+    buckets: &DashMap<K, TokenBucket>,
+    key: K,
+    config: &BucketConfig,
+    now: Instant,
+) -> Result<(), LimitError> {
+    buckets
+        .entry(key)
+        .or_insert_with(|| TokenBucket::new(config, now))
+        .take(config, now)
+        .map_err(|retry_after| LimitError::RateLimited { retry_after })
+}
+
+/// A request rejected before it reached a handler
+#[derive(Debug)]
+pub enum LimitError {
+    RateLimited {
+        retry_after: Duration,
+    },
+    /// The body was larger than the limit, in bytes
+    PayloadTooLarge(usize),
+    /// The body couldn't be read or isn't valid for the endpoint
+    InvalidPayload(String),
+}
+
+impl LimitError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            LimitError::RateLimited { .. } => ErrorCode::RateLimited,
+            LimitError::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+            LimitError::InvalidPayload(_) => ErrorCode::InvalidPayload,
+        }
+    }
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitError::RateLimited { retry_after } => write!(
+                f,
+                "too many requests, retry in {:.1}s",
+                retry_after.as_secs_f64()
+            ),
+            LimitError::PayloadTooLarge(limit) => {
+                write!(f, "request body is larger than {} bytes", limit)
+            }
+            LimitError::InvalidPayload(x) => f.write_str(x),
+        }
+    }
+}
+
+impl ResponseError for LimitError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            LimitError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            LimitError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            LimitError::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        if let LimitError::RateLimited { retry_after } = self {
+            let secs = retry_after.as_secs_f64().ceil() as u64;
+            res.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        res.json(ApiError::new(self.code(), self.to_string()))
+    }
+}
+
+/// Error handler for the json extractor, so malformed bodies get the same
+/// error responses as the middleware
+pub fn json_error(err: JsonPayloadError, _: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::OverflowKnownLength { limit, .. }
+        | JsonPayloadError::Overflow { limit } => LimitError::PayloadTooLarge(limit).into(),
+        e => LimitError::InvalidPayload(e.to_string()).into(),
+    }
+}
+
+#[derive(Deserialize)]
+struct PlayerField {
+    player_id: Option<usize>,
+}
+
+/// Player named by the query string or json body, the body is put back for
+/// the handler
+async fn player_id(req: &mut ServiceRequest, limit: usize) -> Result<Option<usize>, LimitError> {
+    if let Ok(query) = web::Query::<PlayerField>::from_query(req.query_string()) {
+        if query.player_id.is_some() {
+            return Ok(query.player_id);
+        }
+    }
+
+    if req.method() != Method::POST {
+        return Ok(None);
+    }
+
+    let body =
+        req.extract::<web::Bytes>()
+            .await
+            .map_err(|e| match e.as_error::<PayloadError>() {
+                Some(PayloadError::Overflow) => LimitError::PayloadTooLarge(limit),
+                _ => LimitError::InvalidPayload(e.to_string()),
+            })?;
+    // invalid bodies are rejected by the handler's extractor
+    let player_id = serde_json::from_slice::<PlayerField>(&body)
+        .ok()
+        .and_then(|x| x.player_id);
+    req.set_payload(body.into());
+    Ok(player_id)
+}
+
+/// Address a request came from
+///
+/// Anyone can set the forwarded headers, so they're only used when the
+/// connection is from one of the [`LimitConfig::trusted_proxies`] of the
+/// app's [`RateLimiter`].
+pub fn client_ip(req: &HttpRequest) -> String {
+    let Some(peer) = req.peer_addr().map(|x| x.ip()) else {
+        return "unknown".to_string();
+    };
+    let trusted = req
+        .app_data::<web::Data<RateLimiter>>()
+        .is_some_and(|x| x.config().trusted_proxies.contains(&peer));
+    if trusted {
+        if let Some(ip) = req.connection_info().realip_remote_addr() {
+            return ip.to_string();
+        }
+    }
+    peer.to_string()
+}
+
+/// Takes the request's tokens, reading the body if it's needed to find the
+/// player
+async fn check_limits(req: &mut ServiceRequest, limiter: &RateLimiter) -> Result<(), LimitError> {
+    let now = Instant::now();
+    limiter.check_ip(&client_ip(req.request()), now)?;
+
+    if let Some(player_id) = player_id(req, limiter.config().max_payload_bytes).await? {
+        // anyone can name a player, only requests with their token use up
+        // the player's tokens. The rest are turned away by the handlers and
+        // only count against the address
+        let token = req
+            .headers()
+            .get(PLAYER_TOKEN_HEADER)
+            .and_then(|x| x.to_str().ok());
+        if authenticate(limiter.accounts.as_ref(), player_id, token).is_ok() {
+            limiter.check_player(player_id, now)?;
+        }
+    }
+    Ok(())
+}
+
+/// Rejects api requests once their address or player is out of tokens, and
+/// bodies over the payload limit
+///
+/// Does nothing if there isn't a [`RateLimiter`] in the app data.
+pub async fn limit_requests<B: MessageBody>(
+    mut req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let limited = LIMITED_PATHS.iter().any(|x| req.path().starts_with(x));
+    let limiter = req.app_data::<web::Data<RateLimiter>>().cloned();
+    if let Some(limiter) = limiter.filter(|_| limited) {
+        if let Err(e) = check_limits(&mut req, &limiter).await {
+            return Ok(req.error_response(e).map_into_right_body());
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use actix_web::{
+        http::{header, StatusCode},
+        middleware::from_fn,
+        test::{call_and_read_body_json, call_service, init_service, read_body_json, TestRequest},
+        web, App, HttpResponse,
+    };
+    use client_server_messages::{ApiError, ErrorCode, PLAYER_TOKEN_HEADER};
+
+    use super::{
+        client_ip, json_error, limit_requests, BucketConfig, LimitConfig, RateLimiter, TokenBucket,
+    };
+    use crate::{players::create_account, storage::MemoryPlayerRepository};
+
+    fn limiter(config: LimitConfig) -> RateLimiter {
+        RateLimiter::new(config, Arc::new(MemoryPlayerRepository::default()))
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let config = BucketConfig {
+            burst: 2.0,
+            per_second: 4.0,
+        };
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(&config, now);
+
+        assert!(bucket.take(&config, now).is_ok());
+        assert!(bucket.take(&config, now).is_ok());
+        assert_eq!(bucket.take(&config, now), Err(Duration::from_millis(250)));
+
+        // refills at per_second, up to the burst
+        let later = now + Duration::from_millis(250);
+        assert!(bucket.take(&config, later).is_ok());
+        assert!(bucket.take(&config, later).is_err());
+        let idle = later + Duration::from_secs(60);
+        bucket.refill(&config, idle);
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    #[test]
+    fn test_remove_idle() {
+        let limiter = limiter(LimitConfig::default());
+        let now = Instant::now();
+        limiter.check_ip("127.0.0.1", now).unwrap();
+        limiter.check_player(7, now).unwrap();
+
+        assert_eq!(limiter.remove_idle(now), 0);
+        assert_eq!(limiter.remove_idle(now + Duration::from_secs(60)), 2);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let proxy = "10.0.0.1:4000".parse().unwrap();
+        let request = |config: LimitConfig| {
+            TestRequest::default()
+                .peer_addr(proxy)
+                .insert_header((header::X_FORWARDED_FOR, "203.0.113.7"))
+                .app_data(web::Data::new(limiter(config)))
+                .to_http_request()
+        };
+
+        // anyone can claim to be forwarding for someone else
+        assert_eq!(client_ip(&request(LimitConfig::default())), "10.0.0.1");
+
+        let config = LimitConfig {
+            trusted_proxies: vec![proxy.ip()],
+            ..LimitConfig::default()
+        };
+        assert_eq!(client_ip(&request(config)), "203.0.113.7");
+        assert_eq!(
+            client_ip(&TestRequest::default().to_http_request()),
+            "unknown"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_limit_requests() {
+        let config = LimitConfig {
+            per_ip: BucketConfig {
+                burst: 5.0,
+                per_second: 0.1,
+            },
+            per_player: BucketConfig {
+                burst: 1.0,
+                per_second: 0.1,
+            },
+            max_payload_bytes: 64,
+            trusted_proxies: Vec::new(),
+        };
+        let limit = config.max_payload_bytes;
+        let accounts = Arc::new(MemoryPlayerRepository::default());
+        let token = create_account(accounts.as_ref(), 7).unwrap().unwrap().token;
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(RateLimiter::new(config, accounts)))
+                .app_data(web::PayloadConfig::new(limit))
+                .app_data(
+                    web::JsonConfig::default()
+                        .limit(limit)
+                        .error_handler(json_error),
+                )
+                .wrap(from_fn(limit_requests))
+                .route(
+                    "/api",
+                    web::post().to(|body: web::Json<serde_json::Value>| async move {
+                        HttpResponse::Ok().json(body.0)
+                    }),
+                ),
+        )
+        .await;
+
+        // the handler still sees the body
+        let req = TestRequest::post()
+            .uri("/api")
+            .insert_header((PLAYER_TOKEN_HEADER, token.as_str()))
+            .set_json(serde_json::json!({"player_id": 7}))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["player_id"], 7);
+
+        // naming the player without their token doesn't use up their tokens
+        let req = TestRequest::post()
+            .uri("/api?player_id=7")
+            .set_json(serde_json::json!({}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // out of tokens for the player
+        let req = TestRequest::post()
+            .uri("/api?player_id=7")
+            .insert_header((PLAYER_TOKEN_HEADER, token.as_str()))
+            .set_json(serde_json::json!({}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "10");
+        let body: ApiError = read_body_json(resp).await;
+        assert_eq!(body.code, ErrorCode::RateLimited);
+
+        let req = TestRequest::post()
+            .uri("/api")
+            .set_payload(vec![b' '; 65])
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // the last token for the address
+        let req = TestRequest::post()
+            .uri("/api")
+            .set_payload("not json")
+            .insert_header(header::ContentType::json())
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: ApiError = read_body_json(resp).await;
+        assert_eq!(body.code, ErrorCode::InvalidPayload);
+
+        let req = TestRequest::post().uri("/api").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,160 @@
+use client_server_messages::{GameData, GameProcessingState, OpenGame};
+use dashmap::DashMap;
+use rand::{thread_rng, Rng};
+use uuid::Uuid;
+
+/// Characters used in join codes, without the ones that are easy to confuse
+const JOIN_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const JOIN_CODE_LEN: usize = 6;
+
+/// Index of the games waiting for players
+///
+/// Games update it each time their state changes, so listing the open games
+/// doesn't need to message every game. Join codes are only valid while their
+/// game is waiting for players.
+#[derive(Default)]
+pub struct Lobby {
+    /// Public games waiting for players
+    open: DashMap<Uuid, OpenGame>,
+    /// Codes of the private games waiting for players
+    join_codes: DashMap<String, Uuid>,
+}
+
+impl Lobby {
+    pub fn update(&self, id: &Uuid, data: &GameData) {
+        let min_players = match data.display_state {
+            GameProcessingState::WaitingPlayerJoin { min_players } => Some(min_players),
+            _ => None,
+        };
+
+        match (min_players, &data.join_code) {
+            (Some(min_players), None) => {
+                self.open.insert(
+                    *id,
+                    OpenGame {
+                        id: id.to_string(),
+                        num_players: data.players.iter().flatten().count(),
+                        min_players,
+                        waiting_since: data.last_activity,
+                    },
+                );
+            }
+            (Some(_), Some(code)) => {
+                self.join_codes.insert(code.clone(), *id);
+            }
+            (None, code) => {
+                self.open.remove(id);
+                if let Some(code) = code {
+                    self.join_codes.remove(code);
+                }
+            }
+        }
+    }
+
+    /// Public games waiting for players, oldest first
+    pub fn open_games(&self) -> Vec<OpenGame> {
+        let mut games = self
+            .open
+            .iter()
+            .map(|x| x.value().clone())
+            .collect::<Vec<_>>();
+        games.sort_by(|a, b| (a.waiting_since, &a.id).cmp(&(b.waiting_since, &b.id)));
+        games
+    }
+
+    /// Codes are case insensitive
+    pub fn find_code(&self, code: &str) -> Option<Uuid> {
+        self.join_codes
+            .get(&code.trim().to_uppercase())
+            .map(|x| *x.value())
+    }
+
+    /// Returns a code that no waiting private game is using
+    pub fn new_join_code(&self) -> String {
+        let mut rng = thread_rng();
+        loop {
+            let code = (0..JOIN_CODE_LEN)
+                .map(|_| JOIN_CODE_CHARS[rng.gen_range(0..JOIN_CODE_CHARS.len())] as char)
+                .collect::<String>();
+            if !self.join_codes.contains_key(&code) {
+                return code;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use client_server_messages::{Difficulty, GameData, GameProcessingState};
+    use uuid::Uuid;
+
+    use super::Lobby;
+
+    fn game(display_state: GameProcessingState, waiting_since: u64) -> GameData {
+        GameData {
+            gs: "AsJhJdQdAd|QcTs9h9dTd|TcKcJsQsKd|9sKsThQhAh|9c|PPPP".to_string(),
+            players: vec![Some(0), None, None, None],
+            human_score: 0,
+            computer_score: 0,
+            display_state,
+            spectators: Vec::new(),
+            last_activity: waiting_since,
+            join_code: None,
+            difficulty: Difficulty::default(),
+            bot_version: None,
+            revision: 0,
+            takeback: None,
+            hands: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_lobby_open_games() {
+        let lobby = Lobby::default();
+        let waiting = GameProcessingState::WaitingPlayerJoin { min_players: 2 };
+
+        let newer = Uuid::new_v4();
+        let older = Uuid::new_v4();
+        let started = Uuid::new_v4();
+        lobby.update(&newer, &game(waiting.clone(), 20));
+        lobby.update(&older, &game(waiting.clone(), 10));
+        lobby.update(&started, &game(GameProcessingState::WaitingHumanMove, 0));
+
+        let open = lobby.open_games();
+        assert_eq!(
+            open.iter().map(|x| x.id.clone()).collect::<Vec<_>>(),
+            vec![older.to_string(), newer.to_string()]
+        );
+        assert_eq!(open[0].num_players, 1);
+        assert_eq!(open[0].min_players, 2);
+
+        // games leave the lobby once they start
+        lobby.update(&older, &game(GameProcessingState::WaitingHumanMove, 30));
+        assert_eq!(lobby.open_games().len(), 1);
+        lobby.update(&newer, &game(GameProcessingState::Abandoned, 40));
+        assert!(lobby.open_games().is_empty());
+    }
+
+    #[test]
+    fn test_lobby_join_codes() {
+        let lobby = Lobby::default();
+        let id = Uuid::new_v4();
+        let code = lobby.new_join_code();
+        assert_eq!(code.len(), 6);
+
+        let mut private = game(GameProcessingState::WaitingPlayerJoin { min_players: 2 }, 0);
+        private.join_code = Some(code.clone());
+        lobby.update(&id, &private);
+
+        // private games aren't listed
+        assert!(lobby.open_games().is_empty());
+        assert_eq!(lobby.find_code(&code), Some(id));
+        assert_eq!(lobby.find_code(&code.to_lowercase()), Some(id));
+        assert_eq!(lobby.find_code("AAAAAA"), None);
+        assert_ne!(lobby.new_join_code(), code);
+
+        private.display_state = GameProcessingState::WaitingHumanMove;
+        lobby.update(&id, &private);
+        assert_eq!(lobby.find_code(&code), None);
+    }
+}
@@ -1,163 +1,894 @@
 use std::{
-    collections::HashMap,
+    fmt::Display,
     fs::OpenOptions,
-    path::{Path, PathBuf},
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
     str::FromStr,
-    sync::Mutex,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
 };
 
-use actix::StreamHandler;
+use actix::{Actor, Addr, Arbiter};
 use actix_files::NamedFile;
 use actix_web::{
+    dev::Service,
     get,
-    middleware::Logger,
+    http::StatusCode,
+    middleware::{from_fn, Logger},
     post,
     web::{self, Json},
     App, HttpRequest, HttpResponse, HttpServer, Responder,
 };
 use actix_web_actors::ws;
-use card_platypus::{agents::Agent, algorithms::cfres::CFRES};
+use actors::{GameActor, GameError, GetGame, PostAction, Resign};
+use analysis::Analyze;
+use bots::{BotConfig, BotVersions};
+use card_platypus::metrics::REQUEST_LATENCY;
 use client_server_messages::{
-    ActionRequest, GameData, GameProcessingState, NewGameRequest, NewGameResponse,
+    ActionAnalysis, ActionRequest, ApiError, Difficulty, ErrorCode, FeedbackRequest, GameAction,
+    GameAnalysis, GameData, GameProcessingState, HandResult, JoinRequest, MatchHistory,
+    MoveAnalysis, NewGameRequest, NewGameResponse, NewPlayerRequest, OpenGame, PlayerAccount,
+    PlayerStats, PrivateGameResponse, QuickMatchRequest, ResignRequest, Takeback, VersionResponse,
+    PLAYER_TOKEN_HEADER, PROTOCOL_VERSION,
 };
+use dashmap::DashMap;
+use feedback::{post_feedback, FeedbackLog};
+use game_ws::EuchreGameWs;
 use games::{
-    actions,
     gamestates::euchre::{Euchre, EuchreGameState},
+    labels::labeled,
     Action, GameState,
 };
-use log::{info, set_max_level, LevelFilter};
-use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
-use simplelog::{
-    ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode, WriteLogger,
+use health::{healthz, readyz, require_ready, Startup, StartupState};
+use limits::{json_error, limit_requests, BucketConfig, LimitConfig, RateLimiter};
+use lobby::Lobby;
+use pacing::BotPacing;
+use players::{authenticate, create_account, AuthError};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use serde::Deserialize;
+use storage::{
+    GameRepository, MemoryGameRepository, MemoryPlayerRepository, PlayerRepository, SaveQueue,
 };
+use tracing::{error, field, info, info_span, warn, Instrument, Span};
+use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*};
+use utoipa::{IntoParams, OpenApi};
 use uuid::Uuid;
-
+use version::require_protocol_version;
+
+mod actors;
+mod analysis;
+mod bots;
+mod chat;
+mod feedback;
+mod game_ws;
+mod health;
+mod limits;
+mod lobby;
+mod pacing;
+mod players;
+mod storage;
+mod version;
+
+/// Active games and player accounts are saved here, games are resumed on restart
+#[cfg(feature = "sqlite")]
+const GAMES_DB: &str = "euchre_server.db";
+/// Games with no player actions for this long are abandoned, can be overridden
+/// with the `GAME_IDLE_TIMEOUT_MINS` environment variable
+const DEFAULT_GAME_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// How often stopped games are removed from the app state
+const GAME_CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Share of new games that play the canary bot when `CANARY_BOT_PATH` is set,
+/// can be overridden with `CANARY_RATIO`
+const DEFAULT_CANARY_RATIO: f64 = 0.1;
+/// Feedback from players is appended here, one json object per line
+const FEEDBACK_FILE: &str = "feedback.jsonl";
+
+/// OpenAPI spec for the REST api, served at `/api/spec`
+///
+/// euchre-app generates its client from this, run `cargo xtask openapi` after
+/// changing the api to update it.
+///
+/// Requests made as a player need the token of its account in the
+/// `x-player-token` header, see [`AppState::authenticate`].
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        api_index,
+        get_game,
+        post_game,
+        resign_game,
+        list_lobby,
+        quick_match,
+        new_private_game,
+        join_private_game,
+        new_player,
+        get_player_stats,
+        get_game_analysis,
+        get_game_history,
+        get_version,
+        feedback::post_feedback
+    ),
+    components(schemas(
+        NewGameRequest,
+        Difficulty,
+        NewGameResponse,
+        GameData,
+        GameProcessingState,
+        ActionRequest,
+        ResignRequest,
+        GameAction,
+        Takeback,
+        OpenGame,
+        QuickMatchRequest,
+        PrivateGameResponse,
+        JoinRequest,
+        NewPlayerRequest,
+        PlayerAccount,
+        PlayerStats,
+        GameAnalysis,
+        MoveAnalysis,
+        ActionAnalysis,
+        HandResult,
+        MatchHistory,
+        VersionResponse,
+        FeedbackRequest,
+        ApiError,
+        ErrorCode
+    ))
+)]
+struct ApiDoc;
+
+/// Each game is owned by its own actor, so a slow bot move only blocks
+/// requests for that game
 struct AppState {
-    games: Mutex<HashMap<Uuid, GameData>>,
-    bot: Mutex<CFRES<EuchreGameState>>,
+    games: DashMap<Uuid, Addr<GameActor>>,
+    /// Set once loaded, see [`AppState::load`]
+    bots: OnceLock<BotVersions>,
+    startup: web::Data<Startup>,
+    repo: Arc<dyn GameRepository>,
+    /// Saves the games' states from a writer thread
+    saves: SaveQueue,
+    players: Arc<dyn PlayerRepository>,
+    lobby: Arc<Lobby>,
+    idle_timeout: Duration,
+    pacing: BotPacing,
 }
 
 impl Default for AppState {
+    /// Keeps games in memory, must be called from within a running actix system
     fn default() -> Self {
-        let bot = CFRES::new_euchre(
-            StdRng::from_rng(thread_rng()).unwrap(),
-            3,
-            Some(Path::new(
-                "/var/lib/card_platypus/infostate.three_card_played",
-            )),
+        let state = Self::new(
+            Arc::new(MemoryGameRepository::default()),
+            Arc::new(MemoryPlayerRepository::default()),
+            DEFAULT_GAME_IDLE_TIMEOUT,
+            BotPacing::default(),
         );
+        state.load(|| BotVersions::load(&BotConfig::default(), None, 0.0));
+        state
+    }
+}
+
+impl AppState {
+    /// Api requests are turned away until [`AppState::load`] is called. Must
+    /// be called from within a running actix system
+    fn new(
+        repo: Arc<dyn GameRepository>,
+        players: Arc<dyn PlayerRepository>,
+        idle_timeout: Duration,
+        pacing: BotPacing,
+    ) -> Self {
+        Self {
+            games: DashMap::new(),
+            bots: OnceLock::new(),
+            startup: web::Data::new(Startup::default()),
+            saves: SaveQueue::start(repo.clone()),
+            repo,
+            players,
+            lobby: Arc::new(Lobby::default()),
+            idle_timeout,
+            pacing,
+        }
+    }
+
+    /// Loads the bots and resumes the active games saved in the repo, then
+    /// marks the server as ready
+    ///
+    /// Must be called from within a running actix system
+    fn load(&self, load_bots: impl FnOnce() -> BotVersions) {
+        self.startup.set(StartupState::LoadingBots);
+        // missing or corrupt weights panic deep in the loader, report them on
+        // /readyz rather than taking down the server
+        let bots = match panic::catch_unwind(AssertUnwindSafe(load_bots)) {
+            Ok(bots) => self.bots.get_or_init(|| bots),
+            Err(e) => {
+                let reason = e
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| e.downcast_ref::<&str>().map(|x| x.to_string()))
+                    .unwrap_or_else(|| "bot loader panicked".to_string());
+                self.startup.set(StartupState::Failed { reason });
+                return;
+            }
+        };
 
-        let n = bot.num_info_states();
-        info!("loaded bot with {n} infostates and 3 max cards played");
+        self.startup.set(StartupState::ResumingGames);
+        let repo = &self.repo;
+        let saves = &self.saves;
+        let players = &self.players;
+        let lobby = &self.lobby;
+        let idle_timeout = self.idle_timeout;
+        let pacing = self.pacing;
+        let games = &self.games;
 
-        let games: Mutex<HashMap<Uuid, GameData>> = Default::default();
-        let pick_suit_game = GameData {
+        let mut pick_suit_game = GameData {
             gs: "AsJhJdQdAd|QcTs9h9dTd|TcKcJsQsKd|9sKsThQhAh|9c|PPPP".to_string(),
             players: vec![Some(0), None, Some(42), None],
             human_score: 2,
             computer_score: 0,
             display_state: GameProcessingState::WaitingHumanMove,
+            spectators: Vec::new(),
+            last_activity: 0,
+            join_code: None,
+            difficulty: Difficulty::default(),
+            bot_version: None,
+            revision: 0,
+            takeback: None,
+            hands: Vec::new(),
         };
-        games.lock().unwrap().insert(
-            Uuid::from_str("e8aa648a-9483-4bcf-8f81-292222a30557").unwrap(),
-            pick_suit_game,
-        );
 
-        info!("loaded debuging gamestates: {:?}", games.lock().unwrap());
+        let saved = repo.load_active().unwrap_or_else(|e| {
+            error!("failed to load saved games: {:?}", e);
+            Vec::new()
+        });
+        info!("resuming {} saved games", saved.len());
+        for (id, mut data) in saved {
+            let workers = bots.workers(&mut data);
+            let game = GameActor::new(
+                id,
+                data,
+                workers,
+                saves.clone(),
+                players.clone(),
+                lobby.clone(),
+                idle_timeout,
+            )
+            .with_pacing(pacing)
+            .start();
+            games.insert(id, game);
+        }
 
-        Self {
-            games,
-            bot: Mutex::new(bot),
+        let pick_suit_id = Uuid::from_str("e8aa648a-9483-4bcf-8f81-292222a30557").unwrap();
+        games.entry(pick_suit_id).or_insert_with(|| {
+            info!(game_id = %pick_suit_id, "loaded debuging gamestates: {:?}", pick_suit_game);
+            let workers = bots.workers(&mut pick_suit_game);
+            GameActor::new(
+                pick_suit_id,
+                pick_suit_game,
+                workers,
+                saves.clone(),
+                players.clone(),
+                lobby.clone(),
+                idle_timeout,
+            )
+            .with_pacing(pacing)
+            .start()
+        });
+
+        self.startup.set(StartupState::Ready);
+    }
+
+    /// The bots are loaded before api requests are let through, see
+    /// [`health::require_ready`]
+    fn bots(&self) -> &BotVersions {
+        self.bots.get().expect("bots aren't loaded yet")
+    }
+
+    /// Checks the game and player storage can be reached
+    fn check_storage(&self) -> anyhow::Result<()> {
+        self.repo.ping()?;
+        self.players.ping()
+    }
+
+    /// Starts a new game with `player_id` in a random seat, returns its id
+    fn new_game(
+        &self,
+        player_id: usize,
+        min_players: usize,
+        join_code: Option<String>,
+        difficulty: Difficulty,
+    ) -> Uuid {
+        let game_id = Uuid::new_v4();
+
+        // there's no one to wait for once every seat is filled
+        let min_players = min_players.min(4);
+        let mut game_data = GameData::new(new_game(), player_id, min_players);
+        game_data.join_code = join_code;
+        game_data.difficulty = difficulty;
+        game_data.bot_version = Some(self.bots().assign(difficulty));
+        let workers = self.bots().workers(&mut game_data);
+        // randomize who starts with deal
+        game_data.players.rotate_right(thread_rng().gen_range(0..4));
+        // the actor starts the bots if they have the first moves
+        let game = GameActor::new(
+            game_id,
+            game_data,
+            workers,
+            self.saves.clone(),
+            self.players.clone(),
+            self.lobby.clone(),
+            self.idle_timeout,
+        )
+        .with_pacing(self.pacing)
+        .start();
+        self.games.insert(game_id, game);
+
+        game_id
+    }
+
+    /// Adds the player to the game, in `seat` if it's set. Succeeds if
+    /// they're already in it
+    async fn join(
+        &self,
+        game_id: &Uuid,
+        player_id: usize,
+        seat: Option<usize>,
+    ) -> Result<(), HttpResponse> {
+        let game = match self.game(game_id) {
+            Some(x) => x,
+            None => return Err(game_not_found()),
+        };
+
+        let action = match seat {
+            Some(seat) => GameAction::TakeSeat(seat),
+            None => GameAction::RegisterPlayer,
+        };
+        let req = ActionRequest::new(player_id, action);
+        match game.send(PostAction(req)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(actix_web::ResponseError::error_response(&e)),
+            Err(_) => Err(game_not_found()),
         }
     }
+
+    /// Checks the request carries the token of `player_id`'s account in its
+    /// [`PLAYER_TOKEN_HEADER`], it's needed for anything done as a player
+    fn authenticate(&self, req: &HttpRequest, player_id: usize) -> Result<(), AuthError> {
+        let token = req
+            .headers()
+            .get(PLAYER_TOKEN_HEADER)
+            .and_then(|x| x.to_str().ok());
+        authenticate(self.players.as_ref(), player_id, token)
+    }
+
+    fn game(&self, game_id: &Uuid) -> Option<Addr<GameActor>> {
+        self.games.get(game_id).map(|x| x.clone())
+    }
+
+    /// Drops the games whose actors have stopped after being idle, returns the
+    /// number removed
+    fn remove_stopped_games(&self) -> usize {
+        let before = self.games.len();
+        self.games.retain(|_, game| game.connected());
+        before - self.games.len()
+    }
 }
 
+/// Create a new game
+#[utoipa::path(
+    operation_id = "new_game",
+    request_body = NewGameRequest,
+    responses(
+        (status = 200, description = "game created", body = NewGameResponse),
+        (status = 401, description = "invalid player token", body = ApiError)
+    )
+)]
 #[post("/api")]
-async fn api_index(json: Json<NewGameRequest>, data: web::Data<AppState>) -> impl Responder {
-    let game_id = Uuid::new_v4();
-    let gs = new_game();
+async fn api_index(
+    json: Json<NewGameRequest>,
+    http: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = data.authenticate(&http, json.0.player_id) {
+        return actix_web::ResponseError::error_response(&e);
+    }
 
-    let mut game_data = GameData::new(gs, json.0.player_id, json.0.min_players);
-    // randomize who starts with deal
-    game_data.players.rotate_right(thread_rng().gen_range(0..4));
-    progress_game(&mut game_data, &data.bot, &game_id);
-    data.games.lock().unwrap().insert(game_id, game_data);
+    let game_id = data.new_game(
+        json.0.player_id,
+        json.0.min_players,
+        None,
+        json.0.difficulty,
+    );
 
-    info!("new game created");
+    info!(%game_id, "new game created");
 
     let response = NewGameResponse::new(game_id);
 
     HttpResponse::Ok().json(response)
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ViewerQuery {
+    /// Player requesting the game, only the cards they can see are returned.
+    /// Without one only the public cards are returned. Needs the player's token
+    player_id: Option<usize>,
+}
+
+/// Get the current state of a game
+#[utoipa::path(
+    params(("game_id" = String, Path, description = "id of the game"), ViewerQuery),
+    responses(
+        (status = 200, description = "current game state", body = GameData),
+        (status = 400, description = "invalid game id", body = ApiError),
+        (status = 401, description = "invalid player token", body = ApiError),
+        (status = 404, description = "game not found", body = ApiError)
+    )
+)]
 #[get("/api/{game_id}")]
-async fn get_game(path: web::Path<String>, data: web::Data<AppState>) -> impl Responder {
-    let game_id_parse = Uuid::parse_str(path.into_inner().as_str());
+async fn get_game(
+    path: web::Path<String>,
+    query: web::Query<ViewerQuery>,
+    http: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if let Some(player_id) = query.player_id {
+        if let Err(e) = data.authenticate(&http, player_id) {
+            return actix_web::ResponseError::error_response(&e);
+        }
+    }
+
+    let game_id = match parse_game_id(path.into_inner().as_str()) {
+        Ok(x) => x,
+        Err(x) => return x,
+    };
 
-    if game_id_parse.is_err() {
-        return HttpResponse::BadRequest().finish();
+    let game = match data.game(&game_id) {
+        Some(x) => x,
+        None => return game_not_found(),
+    };
+
+    match game.send(GetGame).await {
+        Ok(game_data) => HttpResponse::Ok().json(game_data.client_view(query.player_id)),
+        Err(_) => game_not_found(),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AnalysisQuery {
+    /// Player whose moves are analysed, needs the player's token
+    player_id: usize,
+}
+
+/// Replay a player's moves in every hand of a finished game through the bot
+///
+/// Returns the bot's policy and the expected points of each legal action for
+/// every decision the player made, oldest first.
+#[utoipa::path(
+    params(("game_id" = String, Path, description = "id of the game"), AnalysisQuery),
+    responses(
+        (status = 200, description = "analysis of the player's moves", body = GameAnalysis),
+        (status = 400, description = "invalid game id", body = ApiError),
+        (status = 401, description = "invalid player token", body = ApiError),
+        (status = 403, description = "player isn't in the game", body = ApiError),
+        (status = 404, description = "game not found", body = ApiError),
+        (status = 409, description = "game isn't over", body = ApiError),
+        (status = 500, description = "bot unavailable", body = ApiError)
+    )
+)]
+#[get("/api/{game_id}/analysis")]
+async fn get_game_analysis(
+    path: web::Path<String>,
+    query: web::Query<AnalysisQuery>,
+    http: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = data.authenticate(&http, query.player_id) {
+        return actix_web::ResponseError::error_response(&e);
     }
 
-    let game_id = game_id_parse.unwrap();
+    let game_id = match parse_game_id(path.into_inner().as_str()) {
+        Ok(x) => x,
+        Err(x) => return x,
+    };
+
+    let Some(game) = data.game(&game_id) else {
+        return game_not_found();
+    };
+
+    let Ok(game_data) = game.send(GetGame).await else {
+        return game_not_found();
+    };
 
-    let games = data.games.lock().unwrap();
-    if !games.contains_key(&game_id) {
-        return HttpResponse::NotFound().finish();
+    if !matches!(game_data.display_state, GameProcessingState::GameOver) {
+        return api_error(StatusCode::CONFLICT, ErrorCode::Conflict, "game isn't over");
     }
 
-    let game_data = games.get(&game_id).unwrap();
+    let seat = |players: &[Option<usize>]| players.iter().position(|x| *x == Some(query.player_id));
+    let Some(player) = seat(&game_data.players) else {
+        return api_error(
+            StatusCode::FORBIDDEN,
+            ErrorCode::NotInGame,
+            "player isn't in the game",
+        );
+    };
 
-    HttpResponse::Ok().json(game_data)
+    // the seats rotate between hands, and the game's state has moved on to the
+    // next deal once the last hand is scored. A resigned game's unfinished
+    // hand is the current state
+    let mut hands = game_data
+        .hands
+        .iter()
+        .enumerate()
+        .filter_map(|(i, hand)| {
+            let gs = EuchreGameState::from(hand.gs.as_deref()?);
+            Some((i, gs, seat(&hand.players)?))
+        })
+        .collect::<Vec<_>>();
+    hands.push((game_data.hands.len(), game_data.to_state(), player));
+
+    let analyze = Analyze { hands };
+    match data.bots().analysis().send(analyze).await {
+        Ok(analysis) => HttpResponse::Ok().json(analysis),
+        Err(e) => actix_web::ResponseError::error_response(&GameError::BotUnavailable(e)),
+    }
 }
 
+/// Get the results of a game's finished hands, for a running scoresheet
+#[utoipa::path(
+    params(("game_id" = String, Path, description = "id of the game")),
+    responses(
+        (status = 200, description = "finished hands, oldest first", body = MatchHistory),
+        (status = 400, description = "invalid game id", body = ApiError),
+        (status = 404, description = "game not found", body = ApiError)
+    )
+)]
+#[get("/api/{game_id}/history")]
+async fn get_game_history(path: web::Path<String>, data: web::Data<AppState>) -> impl Responder {
+    let game_id = match parse_game_id(path.into_inner().as_str()) {
+        Ok(x) => x,
+        Err(x) => return x,
+    };
+
+    let Some(game) = data.game(&game_id) else {
+        return game_not_found();
+    };
+
+    match game.send(GetGame).await {
+        Ok(game_data) => HttpResponse::Ok().json(MatchHistory {
+            hands: game_data.hands.iter().map(HandResult::public).collect(),
+        }),
+        Err(_) => game_not_found(),
+    }
+}
+
+/// Take an action in a game, bots play until a human needs to act
+#[utoipa::path(
+    request_body = ActionRequest,
+    params(("game_id" = String, Path, description = "id of the game")),
+    responses(
+        (status = 200, description = "game state after the action", body = GameData),
+        (status = 400, description = "invalid game id or action", body = ApiError),
+        (status = 401, description = "invalid player token", body = ApiError),
+        (status = 403, description = "player isn't in the game, the game is full or the seat is taken", body = ApiError),
+        (status = 404, description = "game not found", body = ApiError)
+    )
+)]
 #[post("/api/{game_id}")]
 async fn post_game(
     req: web::Json<ActionRequest>,
     path: web::Path<String>,
+    http: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    match req.action {
+        GameAction::TakeAction(a) => info!(
+            player_id = req.player_id,
+            action = %labeled(Euchre::NAME, a),
+            "received request"
+        ),
+        _ => info!(player_id = req.player_id, action = ?req.action, "received request"),
+    }
+
+    if let Err(e) = data.authenticate(&http, req.player_id) {
+        return actix_web::ResponseError::error_response(&e);
+    }
+
+    let game_id = match parse_game_id(path.into_inner().as_str()) {
+        Ok(x) => x,
+        Err(x) => return x,
+    };
+
+    let game = match data.game(&game_id) {
+        Some(x) => x,
+        None => return game_not_found(),
+    };
+
+    let player_id = req.player_id;
+    match game.send(PostAction(req.into_inner())).await {
+        Ok(Ok(game_data)) => HttpResponse::Ok().json(game_data.client_view(Some(player_id))),
+        Ok(Err(e)) => actix_web::ResponseError::error_response(&e),
+        Err(_) => game_not_found(),
+    }
+}
+
+/// Forfeit a game, the other team is awarded the win
+#[utoipa::path(
+    request_body = ResignRequest,
+    params(("game_id" = String, Path, description = "id of the game")),
+    responses(
+        (status = 200, description = "game state after resigning", body = GameData),
+        (status = 400, description = "invalid game id or the game is already over", body = ApiError),
+        (status = 401, description = "invalid player token", body = ApiError),
+        (status = 403, description = "player isn't in the game", body = ApiError),
+        (status = 404, description = "game not found", body = ApiError)
+    )
+)]
+#[post("/api/{game_id}/resign")]
+async fn resign_game(
+    req: web::Json<ResignRequest>,
+    path: web::Path<String>,
+    http: HttpRequest,
     data: web::Data<AppState>,
 ) -> impl Responder {
-    info!("received request: {:?}", req);
+    if let Err(e) = data.authenticate(&http, req.player_id) {
+        return actix_web::ResponseError::error_response(&e);
+    }
 
     let game_id = match parse_game_id(path.into_inner().as_str()) {
         Ok(x) => x,
         Err(x) => return x,
     };
 
-    let mut games = data.games.lock().unwrap();
-    let game_data = match games.get_mut(&game_id) {
+    let game = match data.game(&game_id) {
+        Some(x) => x,
+        None => return game_not_found(),
+    };
+
+    let player_id = req.player_id;
+    match game.send(Resign(req.into_inner())).await {
+        Ok(Ok(game_data)) => HttpResponse::Ok().json(game_data.client_view(Some(player_id))),
+        Ok(Err(e)) => actix_web::ResponseError::error_response(&e),
+        Err(_) => game_not_found(),
+    }
+}
+
+/// List the public games waiting for players, oldest first
+#[utoipa::path(responses((status = 200, description = "open games", body = Vec<OpenGame>)))]
+#[get("/api/lobby")]
+async fn list_lobby(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(data.lobby.open_games())
+}
+
+/// Join the oldest open game, or create one if there aren't any
+#[utoipa::path(
+    request_body = QuickMatchRequest,
+    responses(
+        (status = 200, description = "game joined or created", body = NewGameResponse),
+        (status = 401, description = "invalid player token", body = ApiError)
+    )
+)]
+#[post("/api/lobby/quick-match")]
+async fn quick_match(
+    req: Json<QuickMatchRequest>,
+    http: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let player_id = req.player_id;
+    if let Err(e) = data.authenticate(&http, player_id) {
+        return actix_web::ResponseError::error_response(&e);
+    }
+
+    for game in data.lobby.open_games() {
+        let Ok(game_id) = Uuid::parse_str(&game.id) else {
+            continue;
+        };
+        // another player may have filled the game since it was listed
+        if data.join(&game_id, player_id, None).await.is_ok() {
+            info!(%game_id, "quick match joined game");
+            return HttpResponse::Ok().json(NewGameResponse::new(game_id));
+        }
+    }
+
+    let game_id = data.new_game(player_id, 2, None, Difficulty::default());
+    info!(%game_id, "quick match created game");
+    HttpResponse::Ok().json(NewGameResponse::new(game_id))
+}
+
+/// Create a game that isn't listed in the lobby, other players join with its code
+#[utoipa::path(
+    request_body = NewGameRequest,
+    responses(
+        (status = 200, description = "game created", body = PrivateGameResponse),
+        (status = 401, description = "invalid player token", body = ApiError)
+    )
+)]
+#[post("/api/lobby/private")]
+async fn new_private_game(
+    json: Json<NewGameRequest>,
+    http: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = data.authenticate(&http, json.0.player_id) {
+        return actix_web::ResponseError::error_response(&e);
+    }
+
+    let join_code = data.lobby.new_join_code();
+    // nobody could join a game that starts straight away
+    let min_players = json.0.min_players.clamp(2, 4);
+    let game_id = data.new_game(
+        json.0.player_id,
+        min_players,
+        Some(join_code.clone()),
+        json.0.difficulty,
+    );
+
+    info!(%game_id, "new private game created");
+
+    HttpResponse::Ok().json(PrivateGameResponse {
+        id: game_id.to_string(),
+        join_code,
+    })
+}
+
+/// Join a private game with its code
+#[utoipa::path(
+    request_body = JoinRequest,
+    responses(
+        (status = 200, description = "game joined", body = NewGameResponse),
+        (status = 401, description = "invalid player token", body = ApiError),
+        (status = 403, description = "game is full or the seat is taken", body = ApiError),
+        (status = 404, description = "no game waiting for players with this code", body = ApiError)
+    )
+)]
+#[post("/api/lobby/join")]
+async fn join_private_game(
+    req: Json<JoinRequest>,
+    http: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = data.authenticate(&http, req.player_id) {
+        return actix_web::ResponseError::error_response(&e);
+    }
+
+    let game_id = match data.lobby.find_code(&req.join_code) {
         Some(x) => x,
-        None => return HttpResponse::NotFound().finish(),
+        None => {
+            return api_error(
+                StatusCode::NOT_FOUND,
+                ErrorCode::NotFound,
+                "no game found for join code",
+            )
+        }
     };
 
-    // redo this to move the progress game call to the end of this call? Have functions all return results with the error
-    use client_server_messages::GameAction::*;
-    let result = match req.action {
-        TakeAction(a) => handle_take_action(game_data, a, req.player_id),
-        ReadyTrickClear | ReadyBidClear => handle_ready_clear(game_data, req.player_id),
+    match data.join(&game_id, req.player_id, req.seat).await {
+        Ok(()) => HttpResponse::Ok().json(NewGameResponse::new(game_id)),
+        Err(e) => e,
+    }
+}
+
+/// Claim a player id, the stats of the games it finishes are tracked
+#[utoipa::path(
+    request_body = NewPlayerRequest,
+    responses(
+        (status = 200, description = "account created", body = PlayerAccount),
+        (status = 409, description = "player id already claimed", body = ApiError),
+        (status = 500, description = "accounts couldn't be loaded", body = ApiError)
+    )
+)]
+#[post("/api/players")]
+async fn new_player(req: Json<NewPlayerRequest>, data: web::Data<AppState>) -> impl Responder {
+    match create_account(data.players.as_ref(), req.player_id) {
+        Ok(Some(account)) => {
+            info!("created account for player {}", account.player_id);
+            HttpResponse::Ok().json(account)
+        }
+        Ok(None) => api_error(
+            StatusCode::CONFLICT,
+            ErrorCode::Conflict,
+            "player id already claimed",
+        ),
+        Err(e) => {
+            error!("failed to create account: {:?}", e);
+            internal_error()
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TokenQuery {
+    /// Token issued when the player id was claimed
+    token: String,
+}
 
-        RegisterPlayer => handle_register_player(game_data, req.player_id),
+/// Get a player's stats, only the player holding the account's token can see them
+#[utoipa::path(
+    params(("player_id" = usize, Path, description = "id of the player"), TokenQuery),
+    responses(
+        (status = 200, description = "stats of the player's finished games", body = PlayerStats),
+        (status = 401, description = "invalid token", body = ApiError),
+        (status = 404, description = "player id hasn't been claimed", body = ApiError),
+        (status = 500, description = "accounts couldn't be loaded", body = ApiError)
+    )
+)]
+#[get("/api/players/{player_id}/stats")]
+async fn get_player_stats(
+    path: web::Path<usize>,
+    query: web::Query<TokenQuery>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let player_id = path.into_inner();
+    let token = match data.players.token(player_id) {
+        Ok(Some(x)) => x,
+        Ok(None) => {
+            return api_error(
+                StatusCode::NOT_FOUND,
+                ErrorCode::NotFound,
+                "player id hasn't been claimed",
+            )
+        }
+        Err(e) => {
+            error!("failed to load account for player {}: {:?}", player_id, e);
+            return internal_error();
+        }
     };
+    if token != query.token {
+        return api_error(
+            StatusCode::UNAUTHORIZED,
+            ErrorCode::Unauthorized,
+            "invalid token",
+        );
+    }
 
-    if let Err(x) = result {
-        return x;
+    match data.players.stats(player_id) {
+        Ok(Some(stats)) => HttpResponse::Ok().json(stats),
+        Ok(None) => api_error(
+            StatusCode::NOT_FOUND,
+            ErrorCode::NotFound,
+            "player id hasn't been claimed",
+        ),
+        Err(e) => {
+            error!("failed to load stats for player {}: {:?}", player_id, e);
+            internal_error()
+        }
     }
+}
 
-    progress_game(game_data, &data.bot, &game_id);
+/// Get the server's protocol version
+///
+/// Clients built for another version need to be reloaded. Api requests
+/// with a different version in the `x-protocol-version` header are rejected
+/// with a 426.
+#[utoipa::path(responses((status = 200, description = "server version", body = VersionResponse)))]
+#[get("/api/version")]
+async fn get_version() -> impl Responder {
+    HttpResponse::Ok().json(VersionResponse {
+        protocol_version: PROTOCOL_VERSION,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
 
-    HttpResponse::Ok().json(&game_data)
+#[get("/api/spec")]
+async fn api_spec() -> impl Responder {
+    HttpResponse::Ok().json(ApiDoc::openapi())
 }
 
-fn handle_ready_clear(game_data: &mut GameData, player_id: usize) -> Result<(), HttpResponse> {
+fn handle_ready_clear(game_data: &mut GameData, player_id: usize) -> Result<(), GameError> {
     match &mut game_data.display_state {
         GameProcessingState::WaitingTrickClear { ready_players }
         | GameProcessingState::WaitingBidClear { ready_players } => {
             if !ready_players.contains(&player_id) {
                 ready_players.push(player_id);
             }
+            // clearing shows the next bids or trick, so the move stands
+            game_data.takeback = None;
 
             Ok(())
         }
-        _ => Err(HttpResponse::BadRequest().body(format!(
+        _ => Err(GameError::OutOfTurn(format!(
             "can't ready to clear in current state: {:?}",
             game_data.display_state
         ))),
@@ -168,12 +899,12 @@ fn handle_take_action(
     game_data: &mut GameData,
     a: Action,
     player_id: usize,
-) -> Result<(), HttpResponse> {
+) -> Result<(), GameError> {
     if !matches!(
         game_data.display_state,
         GameProcessingState::WaitingHumanMove
     ) {
-        return Err(HttpResponse::BadRequest().body(format!(
+        return Err(GameError::OutOfTurn(format!(
             "cannot take action in current state: {:?}",
             game_data.display_state
         )));
@@ -181,11 +912,6 @@ fn handle_take_action(
 
     let mut gs = EuchreGameState::from(game_data.gs.as_str());
 
-    let legal_actions = actions!(gs);
-    if !legal_actions.contains(&a) {
-        return Err(HttpResponse::BadRequest().body("illegal action attempted"));
-    }
-
     let player = match game_data
         .players
         .iter()
@@ -193,145 +919,378 @@ fn handle_take_action(
     {
         Some(x) => x,
         None => {
-            return Err(HttpResponse::BadRequest()
-                .body("attempted to make a move for a player not registered to this game"))
+            return Err(GameError::NotInGame(
+                "attempted to make a move for a player not registered to this game".to_string(),
+            ))
         }
     };
 
     if gs.cur_player() != player {
-        return Err(HttpResponse::BadRequest().body(format!(
-            "attempted action on wrong players turn. Current player is: {}.\n request: {:?}\ngs: {}",
+        // the gamestate has every hand in it, so it's only logged
+        warn!(player, action = ?a, %gs, "action on wrong player's turn");
+        return Err(GameError::OutOfTurn(format!(
+            "attempted action on wrong players turn. Current player is: {}.\n request: {:?}",
             gs.cur_player(),
-            a, gs
+            a
+        )));
+    }
+
+    // checked after the turn so the reason is about the player's own hand
+    if let Some(reason) = gs.explain_illegal(a) {
+        return Err(GameError::IllegalMove(format!(
+            "illegal action: {}",
+            reason
         )));
     }
 
+    let num_actions = gs.key().len();
     gs.apply_action(a);
     game_data.gs = gs.to_string();
+    game_data.takeback = Some(Takeback {
+        player_id,
+        num_actions,
+    });
 
     Ok(())
 }
 
-fn handle_register_player(game_data: &mut GameData, player_id: usize) -> Result<(), HttpResponse> {
-    let num_humans = game_data.players.iter().flatten().count();
-    if num_humans >= 2 {
-        return Err(HttpResponse::Forbidden().body("game already has 2 human players"));
+/// Rewinds the game to before the player's last move
+///
+/// The move can only be taken back until another human acts or a bot moves,
+/// see [`GameData::takeback`]. A bot move that's still being picked is dropped
+/// since the game changed under it.
+fn handle_takeback(game_data: &mut GameData, player_id: usize) -> Result<(), GameError> {
+    if !matches!(
+        game_data.display_state,
+        GameProcessingState::WaitingHumanMove
+            | GameProcessingState::WaitingTrickClear { .. }
+            | GameProcessingState::WaitingBidClear { .. }
+    ) {
+        return Err(GameError::OutOfTurn(format!(
+            "cannot take back a move in current state: {:?}",
+            game_data.display_state
+        )));
     }
 
-    let cur_player_index = game_data
-        .players
-        .iter()
-        .position(|x| x.is_some())
-        .expect("error finding current player");
-    game_data.players[(cur_player_index + 2) % 4] = Some(player_id);
+    let mut gs = EuchreGameState::from(game_data.gs.as_str());
+    let takeback = game_data
+        .takeback
+        .filter(|x| x.player_id == player_id && x.num_actions < gs.key().len())
+        .ok_or_else(|| GameError::BadRequest("no move to take back".to_string()))?;
+
+    while gs.key().len() > takeback.num_actions {
+        gs.undo();
+    }
+    game_data.gs = gs.to_string();
+    // moves are only taken while waiting on a human
+    game_data.display_state = GameProcessingState::WaitingHumanMove;
+    game_data.takeback = None;
+    info!(player_id, bot = bot_version(game_data), "move taken back");
 
     Ok(())
 }
 
-fn progress_game(game_data: &mut GameData, bot: &Mutex<CFRES<EuchreGameState>>, game_id: &Uuid) {
+/// Plays a bot's move, the human move before it can't be taken back anymore
+fn handle_bot_move(game_data: &mut GameData, mut gs: EuchreGameState, a: Action) {
+    gs.apply_action(a);
+    game_data.gs = gs.to_string();
+    game_data.takeback = None;
+}
+
+/// Seats the player, the computer keeps playing the other empty seats
+///
+/// Without a `seat` the player partners the first human if they can, and
+/// otherwise takes the first empty seat. Succeeds if they're already in the
+/// game.
+fn handle_register_player(
+    game_data: &mut GameData,
+    player_id: usize,
+    seat: Option<usize>,
+) -> Result<(), GameError> {
+    if game_data.players.contains(&Some(player_id)) {
+        return Ok(());
+    }
+
+    let num_seats = game_data.players.len();
+    let seat = match seat {
+        Some(seat) if seat >= num_seats => {
+            return Err(GameError::BadRequest(format!(
+                "invalid seat {}, there are {} seats",
+                seat, num_seats
+            )))
+        }
+        Some(seat) if game_data.players[seat].is_some() => {
+            return Err(GameError::Forbidden(format!("seat {} is taken", seat)))
+        }
+        Some(seat) => seat,
+        None => {
+            let partner_seat = game_data
+                .players
+                .iter()
+                .position(|x| x.is_some())
+                .map(|x| (x + 2) % num_seats)
+                .filter(|x| game_data.players[*x].is_none());
+            partner_seat
+                .or_else(|| game_data.players.iter().position(|x| x.is_none()))
+                .ok_or_else(|| GameError::Forbidden("game is full".to_string()))?
+        }
+    };
+
+    game_data.players[seat] = Some(player_id);
+    game_data.spectators.retain(|x| *x != player_id);
+
+    Ok(())
+}
+
+fn handle_spectate(game_data: &mut GameData, player_id: usize) -> Result<(), GameError> {
+    if game_data.players.contains(&Some(player_id)) {
+        return Err(GameError::BadRequest(
+            "players can't spectate their own game".to_string(),
+        ));
+    }
+
+    if !game_data.spectators.contains(&player_id) {
+        game_data.spectators.push(player_id);
+    }
+
+    Ok(())
+}
+
+fn handle_resign(game_data: &mut GameData, player_id: usize) -> Result<(), GameError> {
+    if !game_data.players.contains(&Some(player_id)) {
+        return Err(GameError::NotInGame(
+            "attempted to resign for a player not registered to this game".to_string(),
+        ));
+    }
+
+    if matches!(
+        game_data.display_state,
+        GameProcessingState::GameOver | GameProcessingState::Abandoned
+    ) {
+        return Err(GameError::OutOfTurn(format!(
+            "cannot resign in current state: {:?}",
+            game_data.display_state
+        )));
+    }
+
+    // the other team is awarded the win
+    let seat = game_data.players.iter().position(|x| *x == Some(player_id));
+    if seat.map(|x| x % 2) == game_data.human_team() {
+        game_data.computer_score = game_data.computer_score.max(10);
+    } else {
+        game_data.human_score = game_data.human_score.max(10);
+    }
+    game_data.display_state = GameProcessingState::GameOver;
+    info!(
+        player_id,
+        bot = bot_version(game_data),
+        human_score = game_data.human_score,
+        computer_score = game_data.computer_score,
+        players = ?game_data.players,
+        "game resigned"
+    );
+
+    Ok(())
+}
+
+/// Moves the game to its next display state, returns true if a bot needs to
+/// act before a human can
+///
+/// The caller applies the bot's action and calls this again until it returns
+/// false.
+fn advance_display_state(game_data: &mut GameData) -> bool {
     let mut gs = EuchreGameState::from(game_data.gs.as_str());
 
     use GameProcessingState::*;
     // set the current state
     let num_humans = game_data.players.iter().flatten().count();
 
-    loop {
-        let new_state = match &game_data.display_state {
-            WaitingPlayerJoin { min_players } => {
-                if game_data.players.iter().filter(|x| x.is_some()).count() < *min_players {
-                    WaitingPlayerJoin {
-                        min_players: *min_players,
-                    }
-                } else {
-                    match game_data.players[gs.cur_player()] {
-                        Some(_) => WaitingHumanMove,
-                        None => WaitingMachineMoves,
-                    }
+    let new_state = match &game_data.display_state {
+        WaitingPlayerJoin { min_players } => {
+            if game_data.players.iter().filter(|x| x.is_some()).count() < *min_players {
+                WaitingPlayerJoin {
+                    min_players: *min_players,
+                }
+            } else {
+                match game_data.players[gs.cur_player()] {
+                    Some(_) => WaitingHumanMove,
+                    None => WaitingMachineMoves {
+                        acting_seat: gs.cur_player(),
+                    },
                 }
             }
-            WaitingHumanMove | WaitingMachineMoves => {
-                if gs.is_trick_over() {
-                    WaitingTrickClear {
-                        ready_players: vec![],
-                    }
-                } else if gs.bidding_ended() {
-                    WaitingBidClear {
-                        ready_players: vec![],
-                    }
-                } else if game_data.players[gs.cur_player()].is_none() {
-                    WaitingMachineMoves
-                } else {
-                    WaitingHumanMove
+        }
+        WaitingHumanMove | WaitingMachineMoves { .. } => {
+            if gs.is_trick_over() {
+                WaitingTrickClear {
+                    ready_players: vec![],
+                }
+            } else if gs.bidding_ended() {
+                WaitingBidClear {
+                    ready_players: vec![],
                 }
+            } else if game_data.players[gs.cur_player()].is_none() {
+                WaitingMachineMoves {
+                    acting_seat: gs.cur_player(),
+                }
+            } else {
+                WaitingHumanMove
             }
-            WaitingTrickClear { ready_players } | WaitingBidClear { ready_players } => {
-                if ready_players.len() == num_humans {
-                    if gs.is_terminal() {
-                        let human_team = game_data
-                            .players
-                            .iter()
-                            .position(|x| x.is_some())
-                            .expect("couldn't find human player");
-                        game_data.human_score += gs.evaluate(human_team).max(0.0) as usize;
-                        game_data.computer_score +=
-                            gs.evaluate((human_team + 1) % 4).max(0.0) as usize;
-                        info!(
-                            "hand ended|id|{}|human:|{}|game:|{}|human players:|{}|player ids|{:?}",
-                            game_id,
-                            game_data.human_score,
-                            gs,
-                            game_data.players.iter().flatten().count(),
-                            game_data.players,
-                        );
-
-                        gs = new_game();
-                        game_data.players.rotate_left(1);
-                    }
+        }
+        WaitingTrickClear { ready_players } | WaitingBidClear { ready_players } => {
+            if ready_players.len() == num_humans {
+                if gs.is_terminal() {
+                    let human_team = game_data.human_team().expect("couldn't find human player");
+                    game_data.human_score += gs.evaluate(human_team).max(0.0) as usize;
+                    game_data.computer_score += gs.evaluate((human_team + 1) % 4).max(0.0) as usize;
+                    game_data.hands.extend(HandResult::new(
+                        &gs,
+                        game_data.players.clone(),
+                        human_team,
+                    ));
+                    info!(
+                        bot = bot_version(game_data),
+                        human_score = game_data.human_score,
+                        computer_score = game_data.computer_score,
+                        %gs,
+                        human_players = game_data.players.iter().flatten().count(),
+                        players = ?game_data.players,
+                        "hand ended"
+                    );
+
+                    gs = new_game();
+                    game_data.players.rotate_left(1);
+                }
 
-                    if game_data.human_score >= 10 || game_data.computer_score >= 10 {
-                        info!(
-                            "game over|id|{}|human:|{}|computer|{}|player ids|{:?}",
-                            game_id,
-                            game_data.human_score,
-                            game_data.computer_score,
-                            game_data.players
-                        );
-                        GameOver
-                    } else if game_data.players[gs.cur_player()].is_none() {
-                        WaitingMachineMoves
-                    } else {
-                        WaitingHumanMove
+                if game_data.human_score >= 10 || game_data.computer_score >= 10 {
+                    info!(
+                        bot = bot_version(game_data),
+                        human_score = game_data.human_score,
+                        computer_score = game_data.computer_score,
+                        players = ?game_data.players,
+                        "game over"
+                    );
+                    GameOver
+                } else if game_data.players[gs.cur_player()].is_none() {
+                    WaitingMachineMoves {
+                        acting_seat: gs.cur_player(),
                     }
                 } else {
-                    game_data.display_state.clone()
+                    WaitingHumanMove
                 }
+            } else {
+                game_data.display_state.clone()
             }
-            // this is a terminal state
-            GameOver => GameOver,
-        };
-        game_data.display_state = new_state;
-
-        if !matches!(game_data.display_state, WaitingMachineMoves) {
-            break;
         }
+        // these are terminal states
+        GameOver => GameOver,
+        Abandoned => Abandoned,
+    };
+    game_data.display_state = new_state;
+    game_data.gs = gs.to_string();
 
-        // Apply bot actions for all non players
-        let mut agent = bot.lock().unwrap();
-
-        let a = agent.step(&gs);
-        gs.apply_action(a);
-    }
+    matches!(game_data.display_state, WaitingMachineMoves { .. })
+}
 
-    game_data.gs = gs.to_string();
+/// Bot version tag for a game's log lines
+fn bot_version(game_data: &GameData) -> &str {
+    game_data.bot_version.as_deref().unwrap_or("unknown")
 }
 
+/// Parses a game id from a request path, the id is added to the request's span
 fn parse_game_id(game_id: &str) -> Result<Uuid, HttpResponse> {
     let game_id_parse = Uuid::parse_str(game_id);
 
     if let Ok(uuid) = game_id_parse {
+        Span::current().record("game_id", field::display(uuid));
         Ok(uuid)
     } else {
-        Err(HttpResponse::BadRequest().body("couldn't parse game id"))
+        Err(api_error(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidRequest,
+            "couldn't parse game id",
+        ))
+    }
+}
+
+/// Error response with an [`ApiError`] body, every non-200 api response has one
+fn api_error(status: StatusCode, code: ErrorCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(ApiError::new(code, message))
+}
+
+fn game_not_found() -> HttpResponse {
+    api_error(
+        StatusCode::NOT_FOUND,
+        ErrorCode::GameNotFound,
+        "game not found",
+    )
+}
+
+/// The details are logged rather than sent to the client
+fn internal_error() -> HttpResponse {
+    api_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorCode::Internal,
+        "internal server error",
+    )
+}
+
+/// Parses an environment variable, returns None if it isn't set or is invalid
+fn env_var<T: FromStr>(name: &str) -> Option<T>
+where
+    T::Err: Display,
+{
+    let x = std::env::var(name).ok()?;
+    match x.parse() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!("invalid {} {:?}: {}, using default", name, x, e);
+            None
+        }
+    }
+}
+
+/// Overrides a token bucket from the `{prefix}_BURST` and `{prefix}_PER_SEC`
+/// environment variables
+fn bucket_config(prefix: &str, default: BucketConfig) -> BucketConfig {
+    BucketConfig {
+        burst: env_var(&format!("{}_BURST", prefix)).unwrap_or(default.burst),
+        per_second: env_var(&format!("{}_PER_SEC", prefix))
+            .filter(|x: &f64| *x > 0.0)
+            .unwrap_or(default.per_second),
+    }
+}
+
+/// Reverse proxies from the comma separated `TRUSTED_PROXIES` environment
+/// variable, invalid addresses are skipped
+fn trusted_proxies() -> Vec<IpAddr> {
+    let Ok(x) = std::env::var("TRUSTED_PROXIES") else {
+        return Vec::new();
+    };
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .filter_map(|x| match x.parse() {
+            Ok(ip) => Some(ip),
+            Err(e) => {
+                warn!("invalid trusted proxy {:?}: {}, skipping", x, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Overrides the bots' think time from the `BOT_THINK_MS`, `BOT_THINK_MAX_MS`
+/// and `BOT_THINK_SIGMA` environment variables, `BOT_THINK_MS=0` turns it off
+fn bot_pacing() -> BotPacing {
+    let default = BotPacing::default();
+    BotPacing {
+        median: env_var("BOT_THINK_MS").map_or(default.median, Duration::from_millis),
+        max: env_var("BOT_THINK_MAX_MS").map_or(default.max, Duration::from_millis),
+        sigma: env_var("BOT_THINK_SIGMA")
+            .filter(|x: &f64| *x >= 0.0)
+            .unwrap_or(default.sigma),
+        ..default
     }
 }
 
@@ -345,38 +1304,164 @@ async fn not_found() -> actix_web::Result<NamedFile> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    set_max_level(LevelFilter::Trace);
-    let config = ConfigBuilder::new().set_time_format_rfc3339().build();
-
-    CombinedLogger::init(vec![
-        TermLogger::new(
-            LevelFilter::Debug,
-            config.clone(),
-            TerminalMode::Mixed,
-            ColorChoice::Auto,
-        ),
-        WriteLogger::new(
-            LevelFilter::Info,
-            config,
-            OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open("euchre_server.log")
-                .unwrap(),
-        ),
-    ])
-    .unwrap();
+    // print the spec for generating clients without starting the server
+    if std::env::args().any(|a| a == "--openapi") {
+        println!("{}", ApiDoc::openapi().to_pretty_json().unwrap());
+        return Ok(());
+    }
+
+    // one json object per line in the log file, with the game and request
+    // spans each event happened in
+    let log_file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open("euchre_server.log")?;
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_filter(LevelFilter::DEBUG))
+        .with(
+            fmt::layer()
+                .json()
+                .with_writer(Mutex::new(log_file))
+                .with_filter(LevelFilter::INFO),
+        )
+        .init();
+    // labelers are per thread, the http workers register their own below
+    Euchre::register_action_labeler();
 
     info!("starting load of initial app state...");
-    let app_state = web::Data::new(AppState::default());
+    #[cfg(feature = "sqlite")]
+    let repo: Arc<dyn GameRepository> = Arc::new(
+        storage::SqliteGameRepository::open(std::path::Path::new(GAMES_DB))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+    );
+    #[cfg(feature = "sqlite")]
+    let players: Arc<dyn PlayerRepository> = Arc::new(
+        storage::SqlitePlayerRepository::open(std::path::Path::new(GAMES_DB))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+    );
+    #[cfg(not(feature = "sqlite"))]
+    let repo: Arc<dyn GameRepository> = Arc::new(MemoryGameRepository::default());
+    #[cfg(not(feature = "sqlite"))]
+    let players: Arc<dyn PlayerRepository> = Arc::new(MemoryPlayerRepository::default());
+    let idle_timeout = env_var::<u64>("GAME_IDLE_TIMEOUT_MINS")
+        .map_or(DEFAULT_GAME_IDLE_TIMEOUT, |mins| {
+            Duration::from_secs(mins * 60)
+        });
+    info!(
+        "games are abandoned after {:?} without activity",
+        idle_timeout
+    );
+
+    let mut baseline = BotConfig::default();
+    if let Some(path) = env_var("BOT_PATH") {
+        baseline.path = path;
+    }
+    if let Some(max_cards_played) = env_var("BOT_MAX_CARDS_PLAYED") {
+        baseline.max_cards_played = max_cards_played;
+    }
+    let canary = env_var::<PathBuf>("CANARY_BOT_PATH").map(|path| BotConfig {
+        path,
+        max_cards_played: env_var("CANARY_BOT_MAX_CARDS_PLAYED")
+            .unwrap_or(baseline.max_cards_played),
+    });
+    let canary_ratio = env_var("CANARY_RATIO").unwrap_or(DEFAULT_CANARY_RATIO);
+
+    let pacing = bot_pacing();
+    info!("bot pacing: {:?}", pacing);
+
+    let app_state = web::Data::new(AppState::new(repo, players.clone(), idle_timeout, pacing));
+    // loading the bots takes minutes, do it on its own arbiter so /healthz and
+    // /readyz are answered in the meantime
+    let loader = app_state.clone();
+    Arbiter::new().spawn_fn(move || {
+        loader.load(|| BotVersions::load(&baseline, canary.as_ref(), canary_ratio))
+    });
+
+    let mut limits = LimitConfig::default();
+    limits.per_ip = bucket_config("RATE_LIMIT_IP", limits.per_ip);
+    limits.per_player = bucket_config("RATE_LIMIT_PLAYER", limits.per_player);
+    if let Some(max_payload_bytes) = env_var("MAX_PAYLOAD_BYTES") {
+        limits.max_payload_bytes = max_payload_bytes;
+    }
+    limits.trusted_proxies = trusted_proxies();
+    info!("request limits: {:?}", limits);
+    let limiter = web::Data::new(RateLimiter::new(limits, players));
+    let feedback = web::Data::new(FeedbackLog::open(std::path::Path::new(FEEDBACK_FILE))?);
+
+    let cleanup_state = app_state.clone();
+    let cleanup_limiter = limiter.clone();
+    let cleanup_feedback = feedback.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(GAME_CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let removed = cleanup_state.remove_stopped_games();
+            if removed > 0 {
+                info!(
+                    "removed {} stopped games, {} remaining",
+                    removed,
+                    cleanup_state.games.len()
+                );
+            }
+            cleanup_limiter.remove_idle(Instant::now());
+            cleanup_feedback.remove_idle(Instant::now());
+        }
+    });
 
     HttpServer::new(move || {
+        // each worker runs its requests and game actors on its own thread
+        Euchre::register_action_labeler();
+        let max_payload_bytes = limiter.config().max_payload_bytes;
         App::new()
             .app_data(app_state.clone())
+            .app_data(app_state.startup.clone())
+            .app_data(limiter.clone())
+            .app_data(feedback.clone())
+            .app_data(web::PayloadConfig::new(max_payload_bytes))
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(max_payload_bytes)
+                    .error_handler(json_error),
+            )
+            .wrap(from_fn(require_ready))
+            .wrap(from_fn(require_protocol_version))
+            .wrap(from_fn(limit_requests))
             .wrap(Logger::default())
+            .wrap_fn(|req, srv| {
+                let start = Instant::now();
+                let span = info_span!(
+                    "request",
+                    method = %req.method(),
+                    path = req.path(),
+                    game_id = field::Empty
+                );
+                let fut = span.in_scope(|| srv.call(req));
+                async move {
+                    let res = fut.await;
+                    REQUEST_LATENCY.observe(start.elapsed());
+                    res
+                }
+                .instrument(span)
+            })
+            .configure(configure_metrics)
+            .service(healthz)
+            .service(readyz)
             .service(api_index)
+            // needs to be before get_game so they aren't treated as game ids
+            .service(api_spec)
+            .service(get_version)
+            .service(post_feedback)
+            .service(list_lobby)
+            .service(quick_match)
+            .service(new_private_game)
+            .service(join_private_game)
+            .service(new_player)
+            .service(get_player_stats)
             .service(get_game)
+            .service(get_game_analysis)
+            .service(get_game_history)
             .service(post_game)
+            .service(resign_game)
             .route("/ws/", web::get().to(handle_euchre_ws))
             // Need to register this last so other services are accessible
             .service(actix_files::Files::new("/", "./static").index_file("index.html"))
@@ -387,6 +1472,21 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
+#[cfg(feature = "prometheus")]
+fn configure_metrics(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "/metrics",
+        web::get().to(|| async {
+            HttpResponse::Ok()
+                .content_type("text/plain; version=0.0.4")
+                .body(card_platypus::metrics::prometheus::render())
+        }),
+    );
+}
+
+#[cfg(not(feature = "prometheus"))]
+fn configure_metrics(_cfg: &mut web::ServiceConfig) {}
+
 fn new_game() -> EuchreGameState {
     let mut gs = Euchre::new_state();
 
@@ -400,46 +1500,32 @@ fn new_game() -> EuchreGameState {
     gs
 }
 
-struct EuchreGameWs {}
-impl actix::Actor for EuchreGameWs {
-    type Context = actix_web_actors::ws::WebsocketContext<Self>;
-}
-
-/// Handler for ws::Message message
-impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for EuchreGameWs {
-    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
-        match msg {
-            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
-            Ok(ws::Message::Text(text)) => ctx.text(text),
-            Ok(ws::Message::Binary(bin)) => ctx.binary(bin),
-            _ => (),
-        }
-    }
-}
-
+/// Websocket clients subscribe to games to be pushed their state, see
+/// [`client_server_messages::WsClientMessage`]
 async fn handle_euchre_ws(
     req: HttpRequest,
     stream: web::Payload,
+    data: web::Data<AppState>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    // req: web::Json<ActionRequest>,
-    // path: web::Path<String>,
-    // data: web::Data<AppState>,
-
-    info!("websocket message received: {:?}", req);
-    let resp = ws::start(EuchreGameWs {}, &req, stream);
-    resp
+    info!("websocket connection: {:?}", req);
+    ws::start(EuchreGameWs::new(data), &req, stream)
 }
 
 #[cfg(test)]
 mod tests {
-    use actix_web::{dev::ServiceResponse, test};
+    use actix_web::{
+        dev::ServiceResponse,
+        test::{self, read_body},
+    };
 
+    use games::gamestates::euchre::actions::EAction;
+    use rand::{rngs::StdRng, SeedableRng};
     use serde::de::DeserializeOwned;
 
     use super::*;
 
     async fn deserialize_body<T: DeserializeOwned>(resp: ServiceResponse) -> T {
-        let body = test::read_body(resp).await;
+        let body = read_body(resp).await;
         serde_json::from_str(std::str::from_utf8(body.as_ref()).unwrap()).unwrap()
     }
 
@@ -501,4 +1587,245 @@ mod tests {
 
     //     let _game_data: GameData = deserialize_body(resp).await;
     // }
+
+    #[actix_web::test]
+    async fn test_requests_need_player_token() {
+        let state = web::Data::new(AppState::new(
+            Arc::new(MemoryGameRepository::default()),
+            Arc::new(MemoryPlayerRepository::default()),
+            DEFAULT_GAME_IDLE_TIMEOUT,
+            BotPacing::instant(),
+        ));
+        let token = create_account(state.players.as_ref(), 7)
+            .unwrap()
+            .unwrap()
+            .token;
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .service(get_game)
+                .service(post_game),
+        )
+        .await;
+
+        let game_uri = format!("/api/{}", Uuid::new_v4());
+        let view = |token: Option<&str>| {
+            let req = test::TestRequest::get().uri(&format!("{}?player_id=7", game_uri));
+            match token {
+                Some(token) => req.insert_header((PLAYER_TOKEN_HEADER, token)),
+                None => req,
+            }
+            .to_request()
+        };
+        let resp = test::call_service(&app, view(None)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = test::call_service(&app, view(Some("guess"))).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        // past the check, there's no game to show
+        let resp = test::call_service(&app, view(Some(&token))).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // spectators don't need a token
+        let req = test::TestRequest::get().uri(&game_uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // nobody can act for an id that hasn't been claimed
+        let req = test::TestRequest::post()
+            .uri(&game_uri)
+            .insert_header((PLAYER_TOKEN_HEADER, token.as_str()))
+            .set_json(ActionRequest::new(42, GameAction::RegisterPlayer))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let error: ApiError = deserialize_body(resp).await;
+        assert_eq!(error.code, ErrorCode::Unauthorized);
+    }
+
+    #[actix_web::test]
+    async fn test_game_analysis() {
+        let state = web::Data::new(AppState::new(
+            Arc::new(MemoryGameRepository::default()),
+            Arc::new(MemoryPlayerRepository::default()),
+            DEFAULT_GAME_IDLE_TIMEOUT,
+            BotPacing::instant(),
+        ));
+        state.load(BotVersions::untrained);
+        let token = create_account(state.players.as_ref(), 7)
+            .unwrap()
+            .unwrap()
+            .token;
+
+        // two hands played to the end, the player changes seats between them
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        let mut data = GameData::new(new_game(), 7, 1);
+        let mut expected = Vec::new();
+        let mut actions = Vec::new();
+        for hand in 0..2 {
+            let seat = data.players.iter().position(|x| *x == Some(7)).unwrap();
+            let mut gs = new_game();
+            while !gs.is_terminal() {
+                gs.legal_actions(&mut actions);
+                let a = *actions.choose(&mut rng).unwrap();
+                if gs.cur_player() == seat {
+                    expected.push((hand, a));
+                }
+                gs.apply_action(a);
+            }
+            data.hands
+                .extend(HandResult::new(&gs, data.players.clone(), 0));
+            data.players.rotate_left(1);
+        }
+        // scoring the last hand deals the next one
+        data.display_state = GameProcessingState::GameOver;
+
+        let game_id = Uuid::new_v4();
+        let workers = state.bots().workers(&mut data);
+        let game = GameActor::new(
+            game_id,
+            data,
+            workers,
+            state.saves.clone(),
+            state.players.clone(),
+            state.lobby.clone(),
+            state.idle_timeout,
+        )
+        .start();
+        state.games.insert(game_id, game);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .service(get_game_analysis)
+                .service(get_game_history),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/{}/analysis?player_id=7", game_id))
+            .insert_header((PLAYER_TOKEN_HEADER, token.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let analysis: GameAnalysis = deserialize_body(resp).await;
+        assert_eq!(
+            analysis
+                .moves
+                .iter()
+                .map(|m| (m.hand, m.action))
+                .collect::<Vec<_>>(),
+            expected
+        );
+
+        // the deals stay on the server
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/{}/history", game_id))
+            .to_request();
+        let history: MatchHistory = deserialize_body(test::call_service(&app, req).await).await;
+        assert_eq!(history.hands.len(), 2);
+        assert!(history.hands.iter().all(|x| x.gs.is_none()));
+    }
+
+    fn new_game_data() -> GameData {
+        GameData {
+            gs: "AsJhJdQdAd|QcTs9h9dTd|TcKcJsQsKd|9sKsThQhAh|9c|".to_string(),
+            players: vec![None, Some(0), None, None],
+            human_score: 0,
+            computer_score: 0,
+            display_state: GameProcessingState::WaitingPlayerJoin { min_players: 4 },
+            spectators: Vec::new(),
+            last_activity: 0,
+            join_code: None,
+            difficulty: Difficulty::default(),
+            bot_version: None,
+            revision: 0,
+            takeback: None,
+            hands: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_register_player() {
+        let mut gd = new_game_data();
+
+        // without a seat players sit across from the first human
+        handle_register_player(&mut gd, 1, None).unwrap();
+        assert_eq!(gd.players, vec![None, Some(0), None, Some(1)]);
+        handle_register_player(&mut gd, 1, Some(0)).unwrap();
+        assert_eq!(gd.players, vec![None, Some(0), None, Some(1)]);
+
+        assert!(matches!(
+            handle_register_player(&mut gd, 2, Some(1)),
+            Err(GameError::Forbidden(_))
+        ));
+        assert!(matches!(
+            handle_register_player(&mut gd, 2, Some(4)),
+            Err(GameError::BadRequest(_))
+        ));
+        handle_register_player(&mut gd, 2, Some(2)).unwrap();
+        handle_register_player(&mut gd, 3, None).unwrap();
+        assert_eq!(gd.players, vec![Some(3), Some(0), Some(2), Some(1)]);
+        assert!(matches!(
+            handle_register_player(&mut gd, 4, None),
+            Err(GameError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn test_out_of_turn_hides_hands() {
+        let mut gd = new_game_data();
+        gd.players = vec![Some(0), Some(1), None, None];
+        gd.display_state = GameProcessingState::WaitingHumanMove;
+
+        let err = handle_take_action(&mut gd, EAction::Pass.into(), 1).unwrap_err();
+        let GameError::OutOfTurn(msg) = err else {
+            panic!("expected OutOfTurn, got {:?}", err);
+        };
+        assert!(!msg.contains("AsJhJdQdAd"), "{}", msg);
+    }
+
+    #[test]
+    fn test_takeback() {
+        let mut gd = new_game_data();
+        gd.players = vec![Some(0), None, None, None];
+        gd.display_state = GameProcessingState::WaitingHumanMove;
+        let start = gd.to_state().to_string();
+        assert!(matches!(
+            handle_takeback(&mut gd, 0),
+            Err(GameError::BadRequest(_))
+        ));
+
+        handle_take_action(&mut gd, EAction::Pass.into(), 0).unwrap();
+        assert!(matches!(
+            handle_takeback(&mut gd, 1),
+            Err(GameError::BadRequest(_))
+        ));
+        handle_takeback(&mut gd, 0).unwrap();
+        assert_eq!(gd.gs, start);
+        assert!(gd.takeback.is_none());
+
+        // a bot's bid gives away something about its hand
+        handle_take_action(&mut gd, EAction::Pass.into(), 0).unwrap();
+        let gs = gd.to_state();
+        handle_bot_move(&mut gd, gs, EAction::Pass.into());
+        assert!(matches!(
+            handle_takeback(&mut gd, 0),
+            Err(GameError::BadRequest(_))
+        ));
+
+        // a card finishing the trick
+        let start = "TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|QdKdTd";
+        gd.gs = start.to_string();
+        gd.players = vec![None, None, None, Some(0)];
+        handle_take_action(&mut gd, EAction::QH.into(), 0).unwrap();
+        gd.display_state = GameProcessingState::WaitingTrickClear {
+            ready_players: vec![],
+        };
+        handle_takeback(&mut gd, 0).unwrap();
+        assert_eq!(gd.gs, EuchreGameState::from(start).to_string());
+        assert!(matches!(
+            gd.display_state,
+            GameProcessingState::WaitingHumanMove
+        ));
+    }
 }
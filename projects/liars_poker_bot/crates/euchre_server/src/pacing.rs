@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Artificial think time for the bots, so they don't answer instantly and
+/// can't be picked out by how fast they move
+///
+/// Think times are log normal around `median`, longer for close decisions and
+/// shorter for obvious ones. Each bot seat in a game also gets its own speed,
+/// so the seats don't all play at the same pace.
+#[derive(Debug, Clone, Copy)]
+pub struct BotPacing {
+    /// Think time for a decision of middling difficulty, zero turns pacing off
+    pub median: Duration,
+    /// Standard deviation of the log of the think time
+    pub sigma: f64,
+    /// Seats are up to this much faster or slower than the median, as a
+    /// fraction of it
+    pub seat_spread: f64,
+    /// Longest a bot will think, including the time to pick its move
+    pub max: Duration,
+}
+
+impl Default for BotPacing {
+    fn default() -> Self {
+        Self {
+            median: Duration::from_millis(700),
+            sigma: 0.3,
+            seat_spread: 0.3,
+            max: Duration::from_secs(3),
+        }
+    }
+}
+
+impl BotPacing {
+    /// Bots move as soon as they've picked a move
+    pub fn instant() -> Self {
+        Self {
+            median: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    /// Speed multipliers for each seat of a new game
+    pub fn seat_speeds(&self, rng: &mut impl Rng) -> [f64; 4] {
+        let spread = self.seat_spread.clamp(0.0, 1.0);
+        [(); 4].map(|_| 1.0 + rng.gen_range(-spread..=spread))
+    }
+
+    /// How long a bot with `seat_speed` thinks about a move
+    ///
+    /// `difficulty` is from 0 for an obvious move to 1 for a toss up, see
+    /// [`card_platypus::agents::decision_difficulty`], moves of unknown
+    /// difficulty are treated as middling. A toss up takes 4 times as long as
+    /// an obvious move.
+    pub fn think_time(
+        &self,
+        seat_speed: f64,
+        difficulty: Option<f64>,
+        rng: &mut impl Rng,
+    ) -> Duration {
+        if self.median.is_zero() {
+            return Duration::ZERO;
+        }
+
+        let difficulty = difficulty.unwrap_or(0.5).clamp(0.0, 1.0);
+        let noise = (self.sigma * standard_normal(rng)).exp();
+        let secs =
+            self.median.as_secs_f64() * 2f64.powf(2.0 * difficulty - 1.0) * seat_speed * noise;
+        Duration::try_from_secs_f64(secs)
+            .unwrap_or(Duration::ZERO)
+            .min(self.max)
+    }
+}
+
+/// Box-Muller transform of two uniform samples
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::BotPacing;
+
+    fn mean_think_time(pacing: &BotPacing, difficulty: Option<f64>) -> Duration {
+        let mut rng = StdRng::seed_from_u64(42);
+        (0..1000)
+            .map(|_| pacing.think_time(1.0, difficulty, &mut rng))
+            .sum::<Duration>()
+            / 1000
+    }
+
+    #[test]
+    fn test_think_time() {
+        let pacing = BotPacing::default();
+        let obvious = mean_think_time(&pacing, Some(0.0));
+        let middling = mean_think_time(&pacing, None);
+        let toss_up = mean_think_time(&pacing, Some(1.0));
+        assert!(obvious < middling && middling < toss_up);
+        assert!(toss_up <= pacing.max);
+
+        let pacing = BotPacing {
+            max: Duration::from_millis(100),
+            ..Default::default()
+        };
+        assert_eq!(mean_think_time(&pacing, Some(1.0)), pacing.max);
+
+        let instant = BotPacing::instant();
+        assert_eq!(mean_think_time(&instant, Some(1.0)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_seat_speeds() {
+        let pacing = BotPacing::default();
+        let mut rng = StdRng::seed_from_u64(42);
+        for speed in pacing.seat_speeds(&mut rng) {
+            assert!((0.7..=1.3).contains(&speed));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Display;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use client_server_messages::{
+    ApiError, ErrorCode, GameData, GameProcessingState, PlayerAccount, PlayerStats,
+};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use tracing::{error, info};
+
+use crate::storage::PlayerRepository;
+
+/// Rating of new players
+pub const INITIAL_RATING: f64 = 1500.0;
+/// Fixed rating of the bot, players are only rated against it
+const BOT_RATING: f64 = 1500.0;
+/// Largest change to a rating from a single game
+const K_FACTOR: f64 = 32.0;
+const TOKEN_LEN: usize = 32;
+
+/// Claims `player_id` for a new account, returns None if it's already taken
+pub fn create_account(
+    players: &dyn PlayerRepository,
+    player_id: usize,
+) -> anyhow::Result<Option<PlayerAccount>> {
+    let account = PlayerAccount {
+        player_id,
+        token: thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LEN)
+            .map(char::from)
+            .collect(),
+    };
+
+    match players.create(&account, &PlayerStats::new(INITIAL_RATING))? {
+        true => Ok(Some(account)),
+        false => Ok(None),
+    }
+}
+
+/// Why a request couldn't be made as a player
+#[derive(Debug)]
+pub enum AuthError {
+    /// The token is missing or wasn't issued for the player id, ids that
+    /// haven't been claimed have no valid token
+    InvalidToken,
+    /// The accounts couldn't be loaded, the details are logged
+    Storage,
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::InvalidToken => f.write_str("invalid token for player"),
+            AuthError::Storage => f.write_str("internal server error"),
+        }
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            AuthError::Storage => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let code = match self {
+            AuthError::InvalidToken => ErrorCode::Unauthorized,
+            AuthError::Storage => ErrorCode::Internal,
+        };
+        HttpResponse::build(self.status_code()).json(ApiError::new(code, self.to_string()))
+    }
+}
+
+/// Checks `token` was issued when `player_id` was claimed, so nobody else can
+/// see their hand or act for them
+pub fn authenticate(
+    players: &dyn PlayerRepository,
+    player_id: usize,
+    token: Option<&str>,
+) -> Result<(), AuthError> {
+    match players.token(player_id) {
+        Ok(Some(expected)) if Some(expected.as_str()) == token => Ok(()),
+        Ok(_) => Err(AuthError::InvalidToken),
+        Err(e) => {
+            error!("failed to load account for player {}: {:?}", player_id, e);
+            Err(AuthError::Storage)
+        }
+    }
+}
+
+/// Elo update for a game against the bot
+fn update_rating(rating: f64, won: bool) -> f64 {
+    let expected = 1.0 / (1.0 + 10f64.powf((BOT_RATING - rating) / 400.0));
+    let score = if won { 1.0 } else { 0.0 };
+    rating + K_FACTOR * (score - expected)
+}
+
+/// Adds a finished game to the stats of its human players, players without
+/// an account aren't tracked
+///
+/// An abandoned game is a loss whatever the score, otherwise leaving while
+/// ahead would keep the win.
+pub fn record_game(players: &dyn PlayerRepository, data: &GameData) {
+    let abandoned = matches!(data.display_state, GameProcessingState::Abandoned);
+    for player_id in data.players.iter().flatten() {
+        let won = !abandoned
+            && data
+                .team_scores(*player_id)
+                .is_some_and(|(own, other)| own > other);
+        let updated = players.update_stats(*player_id, &mut |stats| {
+            stats.games_played += 1;
+            if won {
+                stats.wins += 1;
+            }
+            stats.rating = update_rating(stats.rating, won);
+        });
+
+        match updated {
+            Ok(true) => info!(player_id, won, "recorded game"),
+            Ok(false) => {}
+            Err(e) => error!(player_id, "failed to record game: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use client_server_messages::{Difficulty, GameData, GameProcessingState};
+
+    use crate::storage::{MemoryPlayerRepository, PlayerRepository};
+
+    use super::{
+        authenticate, create_account, record_game, update_rating, AuthError, INITIAL_RATING,
+    };
+
+    #[test]
+    fn test_authenticate() {
+        let players = MemoryPlayerRepository::default();
+        let account = create_account(&players, 7).unwrap().unwrap();
+
+        assert!(authenticate(&players, 7, Some(&account.token)).is_ok());
+        assert!(matches!(
+            authenticate(&players, 7, Some("guess")),
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            authenticate(&players, 7, None),
+            Err(AuthError::InvalidToken)
+        ));
+        // unclaimed ids can't be used by anyone
+        assert!(matches!(
+            authenticate(&players, 42, Some(&account.token)),
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn test_update_rating() {
+        // even players move by half the k factor
+        assert_eq!(update_rating(INITIAL_RATING, true), INITIAL_RATING + 16.0);
+        assert_eq!(update_rating(INITIAL_RATING, false), INITIAL_RATING - 16.0);
+
+        // beating the bot is worth less the further ahead of it a player is
+        let strong = INITIAL_RATING + 400.0;
+        assert!(update_rating(strong, true) - strong < 16.0);
+        assert!(strong - update_rating(strong, false) > 16.0);
+    }
+
+    #[test]
+    fn test_record_game() {
+        let players = MemoryPlayerRepository::default();
+        let account = create_account(&players, 7).unwrap().unwrap();
+        assert_eq!(account.token.len(), 32);
+        assert!(create_account(&players, 7).unwrap().is_none());
+
+        let mut data = GameData {
+            gs: "AsJhJdQdAd|QcTs9h9dTd|TcKcJsQsKd|9sKsThQhAh|9c|PPPP".to_string(),
+            // 42 doesn't have an account
+            players: vec![Some(7), None, Some(42), None],
+            human_score: 10,
+            computer_score: 4,
+            display_state: GameProcessingState::GameOver,
+            spectators: Vec::new(),
+            last_activity: 0,
+            join_code: None,
+            difficulty: Difficulty::default(),
+            bot_version: None,
+            revision: 0,
+            takeback: None,
+            hands: Vec::new(),
+        };
+        record_game(&players, &data);
+        data.human_score = 6;
+        data.computer_score = 10;
+        record_game(&players, &data);
+
+        let stats = players.stats(7).unwrap().unwrap();
+        assert_eq!(stats.games_played, 2);
+        assert_eq!(stats.wins, 1);
+        assert_eq!(stats.win_rate(), 0.5);
+        // a win then a loss at the same rating loses a little
+        assert!(stats.rating < INITIAL_RATING);
+        assert!(players.stats(42).unwrap().is_none());
+
+        // leaving while ahead is still a loss
+        data.human_score = 8;
+        data.computer_score = 2;
+        data.display_state = GameProcessingState::Abandoned;
+        record_game(&players, &data);
+        let stats = players.stats(7).unwrap().unwrap();
+        assert_eq!(stats.games_played, 3);
+        assert_eq!(stats.wins, 1);
+    }
+}
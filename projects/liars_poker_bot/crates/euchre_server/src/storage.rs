@@ -0,0 +1,664 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use actix::{Actor, Addr, Handler, Message, SyncArbiter, SyncContext};
+use client_server_messages::{GameData, GameProcessingState, PlayerAccount, PlayerStats};
+use tracing::error;
+use uuid::Uuid;
+
+/// Persists games so they survive a server restart
+///
+/// Games are saved on every state change through a [`SaveQueue`], which
+/// writes from its own thread so implementations can block.
+pub trait GameRepository: Send + Sync {
+    fn save(&self, id: &Uuid, data: &GameData) -> anyhow::Result<()>;
+
+    /// Saves several games at once, implementations can write them together
+    fn save_all(&self, games: &[(Uuid, GameData)]) -> anyhow::Result<()> {
+        games.iter().try_for_each(|(id, data)| self.save(id, data))
+    }
+
+    /// Returns every saved game that isn't over
+    fn load_active(&self) -> anyhow::Result<Vec<(Uuid, GameData)>>;
+
+    /// Fails if the storage can't be reached, for the readiness check
+    fn ping(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Persists player accounts and the stats of their finished games
+pub trait PlayerRepository: Send + Sync {
+    /// Returns false if the player id has already been claimed
+    fn create(&self, account: &PlayerAccount, stats: &PlayerStats) -> anyhow::Result<bool>;
+
+    /// Returns None if the player id hasn't been claimed
+    fn token(&self, player_id: usize) -> anyhow::Result<Option<String>>;
+
+    /// Returns None if the player id hasn't been claimed
+    fn stats(&self, player_id: usize) -> anyhow::Result<Option<PlayerStats>>;
+
+    /// Applies `update` to the player's stats, returns false if the player id
+    /// hasn't been claimed
+    ///
+    /// Games finishing at the same time don't overwrite each other's updates.
+    fn update_stats(
+        &self,
+        player_id: usize,
+        update: &mut dyn FnMut(&mut PlayerStats),
+    ) -> anyhow::Result<bool>;
+
+    /// Fails if the storage can't be reached, for the readiness check
+    fn ping(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Saves games on a writer thread, so a slow write doesn't hold up the game
+/// actors
+///
+/// Only the latest state of each game is queued, a game that changes again
+/// before the writer gets to it replaces its queued state. The writer saves
+/// everything queued in one [`GameRepository::save_all`]. States still queued
+/// when the server stops are lost.
+#[derive(Clone)]
+pub struct SaveQueue {
+    pending: Arc<Mutex<HashMap<Uuid, GameData>>>,
+    writer: Addr<GameWriter>,
+}
+
+impl SaveQueue {
+    /// Must be called from within a running actix system
+    pub fn start(repo: Arc<dyn GameRepository>) -> Self {
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let queued = pending.clone();
+        // a single writer, concurrent writes would only wait on each other
+        let writer = SyncArbiter::start(1, move || GameWriter {
+            repo: repo.clone(),
+            pending: queued.clone(),
+        });
+        Self { pending, writer }
+    }
+
+    /// Queues the game's state to be saved, doesn't wait for the write
+    pub fn save(&self, id: &Uuid, data: &GameData) {
+        let mut pending = self.pending.lock().unwrap();
+        // the writer is already due to take a queue that isn't empty
+        let was_empty = pending.is_empty();
+        pending.insert(*id, data.clone());
+        drop(pending);
+        if was_empty {
+            self.writer.do_send(Flush);
+        }
+    }
+}
+
+struct GameWriter {
+    repo: Arc<dyn GameRepository>,
+    pending: Arc<Mutex<HashMap<Uuid, GameData>>>,
+}
+
+impl Actor for GameWriter {
+    type Context = SyncContext<Self>;
+}
+
+/// Saves every queued game
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Flush;
+
+impl Handler<Flush> for GameWriter {
+    type Result = ();
+
+    fn handle(&mut self, _: Flush, _: &mut Self::Context) {
+        let games = std::mem::take(&mut *self.pending.lock().unwrap())
+            .into_iter()
+            .collect::<Vec<_>>();
+        if games.is_empty() {
+            return;
+        }
+        if let Err(e) = self.repo.save_all(&games) {
+            error!("failed to save {} games: {:?}", games.len(), e);
+        }
+    }
+}
+
+/// Parses a saved game, upgrading games saved before
+/// [`GameProcessingState::WaitingMachineMoves`] had an acting seat
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+fn parse_saved_game(json: &str) -> serde_json::Result<GameData> {
+    let mut value: serde_json::Value = serde_json::from_str(json)?;
+    if value["display_state"] == "WaitingMachineMoves" {
+        // the seat is worked out again when the game is resumed
+        value["display_state"] = serde_json::json!({ "WaitingMachineMoves": { "acting_seat": 0 } });
+    }
+    serde_json::from_value(value)
+}
+
+fn is_over(data: &GameData) -> bool {
+    matches!(
+        data.display_state,
+        GameProcessingState::GameOver | GameProcessingState::Abandoned
+    )
+}
+
+/// Keeps games in memory, they're lost on restart. Used for tests and debugging
+#[derive(Default)]
+pub struct MemoryGameRepository {
+    games: Mutex<HashMap<Uuid, GameData>>,
+}
+
+impl GameRepository for MemoryGameRepository {
+    fn save(&self, id: &Uuid, data: &GameData) -> anyhow::Result<()> {
+        self.games.lock().unwrap().insert(*id, data.clone());
+        Ok(())
+    }
+
+    fn load_active(&self) -> anyhow::Result<Vec<(Uuid, GameData)>> {
+        Ok(self
+            .games
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, data)| !is_over(data))
+            .map(|(id, data)| (*id, data.clone()))
+            .collect())
+    }
+}
+
+/// Keeps accounts in memory, they're lost on restart. Used for tests and debugging
+#[derive(Default)]
+pub struct MemoryPlayerRepository {
+    players: Mutex<HashMap<usize, (String, PlayerStats)>>,
+}
+
+impl PlayerRepository for MemoryPlayerRepository {
+    fn create(&self, account: &PlayerAccount, stats: &PlayerStats) -> anyhow::Result<bool> {
+        let mut players = self.players.lock().unwrap();
+        if players.contains_key(&account.player_id) {
+            return Ok(false);
+        }
+        players.insert(account.player_id, (account.token.clone(), stats.clone()));
+        Ok(true)
+    }
+
+    fn token(&self, player_id: usize) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .players
+            .lock()
+            .unwrap()
+            .get(&player_id)
+            .map(|(token, _)| token.clone()))
+    }
+
+    fn stats(&self, player_id: usize) -> anyhow::Result<Option<PlayerStats>> {
+        Ok(self
+            .players
+            .lock()
+            .unwrap()
+            .get(&player_id)
+            .map(|(_, stats)| stats.clone()))
+    }
+
+    fn update_stats(
+        &self,
+        player_id: usize,
+        update: &mut dyn FnMut(&mut PlayerStats),
+    ) -> anyhow::Result<bool> {
+        match self.players.lock().unwrap().get_mut(&player_id) {
+            Some((_, stats)) => {
+                update(stats);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteGameRepository, SqlitePlayerRepository};
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::{path::Path, sync::Mutex};
+
+    use anyhow::Context;
+    use client_server_messages::{GameData, PlayerAccount, PlayerStats};
+    use rusqlite::{params, Connection, OptionalExtension};
+    use uuid::Uuid;
+
+    use super::{is_over, parse_saved_game, GameRepository, PlayerRepository};
+
+    fn open_connection(path: &Path) -> anyhow::Result<Connection> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open game database {:?}", path))?;
+        // writes happen on every move, don't wait on fsyncs of the main db
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        Ok(conn)
+    }
+
+    /// Stores each game as a json row in a sqlite database
+    pub struct SqliteGameRepository {
+        conn: Mutex<Connection>,
+    }
+
+    impl SqliteGameRepository {
+        pub fn open(path: &Path) -> anyhow::Result<Self> {
+            Self::with_connection(open_connection(path)?)
+        }
+
+        #[cfg(test)]
+        pub fn open_in_memory() -> anyhow::Result<Self> {
+            Self::with_connection(Connection::open_in_memory()?)
+        }
+
+        fn with_connection(conn: Connection) -> anyhow::Result<Self> {
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS games (
+                    id TEXT PRIMARY KEY,
+                    data TEXT NOT NULL,
+                    game_over INTEGER NOT NULL,
+                    updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+                )",
+                [],
+            )
+            .context("failed to create games table")?;
+
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+    }
+
+    fn upsert_game(conn: &Connection, id: &Uuid, data: &GameData) -> anyhow::Result<()> {
+        let json = serde_json::to_string(data)?;
+        conn.execute(
+            "INSERT INTO games (id, data, game_over) VALUES (?1, ?2, ?3)
+            ON CONFLICT(id) DO UPDATE SET
+                data = excluded.data,
+                game_over = excluded.game_over,
+                updated_at = unixepoch()",
+            params![id.to_string(), json, is_over(data)],
+        )?;
+        Ok(())
+    }
+
+    impl GameRepository for SqliteGameRepository {
+        fn save(&self, id: &Uuid, data: &GameData) -> anyhow::Result<()> {
+            upsert_game(&self.conn.lock().unwrap(), id, data)
+        }
+
+        /// Writes the games in one transaction, so there's one sync per batch
+        fn save_all(&self, games: &[(Uuid, GameData)]) -> anyhow::Result<()> {
+            let mut conn = self.conn.lock().unwrap();
+            let tx = conn.transaction()?;
+            for (id, data) in games {
+                upsert_game(&tx, id, data)?;
+            }
+            tx.commit()?;
+            Ok(())
+        }
+
+        fn load_active(&self) -> anyhow::Result<Vec<(Uuid, GameData)>> {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT id, data FROM games WHERE game_over = 0")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+
+            let mut games = Vec::new();
+            for row in rows {
+                let (id, json) = row?;
+                let id = Uuid::parse_str(&id).with_context(|| format!("invalid game id {}", id))?;
+                let data = parse_saved_game(&json)
+                    .with_context(|| format!("failed to parse saved game {}", id))?;
+                games.push((id, data));
+            }
+            Ok(games)
+        }
+
+        fn ping(&self) -> anyhow::Result<()> {
+            self.conn
+                .lock()
+                .unwrap()
+                .query_row("SELECT 1", [], |_| Ok(()))
+                .context("failed to query the game database")
+        }
+    }
+
+    /// Stores each player's stats as a json row, can share a database with
+    /// [`SqliteGameRepository`]
+    pub struct SqlitePlayerRepository {
+        conn: Mutex<Connection>,
+    }
+
+    impl SqlitePlayerRepository {
+        pub fn open(path: &Path) -> anyhow::Result<Self> {
+            Self::with_connection(open_connection(path)?)
+        }
+
+        #[cfg(test)]
+        pub fn open_in_memory() -> anyhow::Result<Self> {
+            Self::with_connection(Connection::open_in_memory()?)
+        }
+
+        fn with_connection(conn: Connection) -> anyhow::Result<Self> {
+            // ids are stored as text since they don't fit in sqlite's signed integers
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS players (
+                    id TEXT PRIMARY KEY,
+                    token TEXT NOT NULL,
+                    stats TEXT NOT NULL,
+                    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+                )",
+                [],
+            )
+            .context("failed to create players table")?;
+
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        fn stats_locked(
+            conn: &Connection,
+            player_id: usize,
+        ) -> anyhow::Result<Option<PlayerStats>> {
+            let json = conn
+                .query_row(
+                    "SELECT stats FROM players WHERE id = ?1",
+                    params![player_id.to_string()],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?;
+            json.map(|x| {
+                serde_json::from_str(&x)
+                    .with_context(|| format!("failed to parse stats of player {}", player_id))
+            })
+            .transpose()
+        }
+    }
+
+    impl PlayerRepository for SqlitePlayerRepository {
+        fn create(&self, account: &PlayerAccount, stats: &PlayerStats) -> anyhow::Result<bool> {
+            let inserted = self.conn.lock().unwrap().execute(
+                "INSERT OR IGNORE INTO players (id, token, stats) VALUES (?1, ?2, ?3)",
+                params![
+                    account.player_id.to_string(),
+                    account.token,
+                    serde_json::to_string(stats)?
+                ],
+            )?;
+            Ok(inserted == 1)
+        }
+
+        fn token(&self, player_id: usize) -> anyhow::Result<Option<String>> {
+            Ok(self
+                .conn
+                .lock()
+                .unwrap()
+                .query_row(
+                    "SELECT token FROM players WHERE id = ?1",
+                    params![player_id.to_string()],
+                    |row| row.get(0),
+                )
+                .optional()?)
+        }
+
+        fn stats(&self, player_id: usize) -> anyhow::Result<Option<PlayerStats>> {
+            Self::stats_locked(&self.conn.lock().unwrap(), player_id)
+        }
+
+        fn update_stats(
+            &self,
+            player_id: usize,
+            update: &mut dyn FnMut(&mut PlayerStats),
+        ) -> anyhow::Result<bool> {
+            // holding the lock for the read and write keeps updates atomic
+            let conn = self.conn.lock().unwrap();
+            let Some(mut stats) = Self::stats_locked(&conn, player_id)? else {
+                return Ok(false);
+            };
+            update(&mut stats);
+            conn.execute(
+                "UPDATE players SET stats = ?2 WHERE id = ?1",
+                params![player_id.to_string(), serde_json::to_string(&stats)?],
+            )?;
+            Ok(true)
+        }
+
+        fn ping(&self) -> anyhow::Result<()> {
+            self.conn
+                .lock()
+                .unwrap()
+                .query_row("SELECT 1", [], |_| Ok(()))
+                .context("failed to query the player database")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            mpsc::{channel, Receiver, Sender},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use client_server_messages::{
+        Difficulty, GameData, GameProcessingState, PlayerAccount, PlayerStats,
+    };
+    use uuid::Uuid;
+
+    use super::{
+        parse_saved_game, GameRepository, MemoryGameRepository, MemoryPlayerRepository,
+        PlayerRepository, SaveQueue,
+    };
+
+    fn game(display_state: GameProcessingState) -> GameData {
+        GameData {
+            gs: "AsJhJdQdAd|QcTs9h9dTd|TcKcJsQsKd|9sKsThQhAh|9c|PPPP".to_string(),
+            players: vec![Some(0), None, Some(42), None],
+            human_score: 2,
+            computer_score: 0,
+            display_state,
+            spectators: Vec::new(),
+            last_activity: 0,
+            join_code: None,
+            difficulty: Difficulty::default(),
+            bot_version: None,
+            revision: 0,
+            takeback: None,
+            hands: Vec::new(),
+        }
+    }
+
+    fn check_repository(repo: &dyn GameRepository) {
+        repo.ping().unwrap();
+        assert!(repo.load_active().unwrap().is_empty());
+
+        let active = Uuid::new_v4();
+        let finished = Uuid::new_v4();
+        let abandoned = Uuid::new_v4();
+        repo.save(
+            &active,
+            &game(GameProcessingState::WaitingPlayerJoin { min_players: 2 }),
+        )
+        .unwrap();
+        repo.save(&finished, &game(GameProcessingState::WaitingHumanMove))
+            .unwrap();
+
+        // later saves replace the earlier state
+        let mut moved = game(GameProcessingState::WaitingHumanMove);
+        moved.human_score = 4;
+        repo.save(&active, &moved).unwrap();
+        repo.save_all(&[
+            (finished, game(GameProcessingState::GameOver)),
+            (abandoned, game(GameProcessingState::Abandoned)),
+        ])
+        .unwrap();
+
+        let loaded = repo.load_active().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].0, active);
+        assert_eq!(loaded[0].1.human_score, 4);
+        assert!(matches!(
+            loaded[0].1.display_state,
+            GameProcessingState::WaitingHumanMove
+        ));
+    }
+
+    fn check_player_repository(repo: &dyn PlayerRepository) {
+        repo.ping().unwrap();
+        // larger than sqlite's integers
+        let id = usize::MAX - 1;
+        assert_eq!(repo.token(id).unwrap(), None);
+        assert_eq!(repo.stats(id).unwrap(), None);
+        assert!(!repo.update_stats(id, &mut |s| s.wins += 1).unwrap());
+
+        let account = PlayerAccount {
+            player_id: id,
+            token: "secret".to_string(),
+        };
+        assert!(repo.create(&account, &PlayerStats::new(1500.0)).unwrap());
+        // ids can only be claimed once
+        let stolen = PlayerAccount {
+            player_id: id,
+            token: "other".to_string(),
+        };
+        assert!(!repo.create(&stolen, &PlayerStats::new(0.0)).unwrap());
+        assert_eq!(repo.token(id).unwrap(), Some("secret".to_string()));
+
+        assert!(repo
+            .update_stats(id, &mut |s| {
+                s.games_played += 1;
+                s.rating += 16.0;
+            })
+            .unwrap());
+        let stats = repo.stats(id).unwrap().unwrap();
+        assert_eq!(stats.games_played, 1);
+        assert_eq!(stats.wins, 0);
+        assert_eq!(stats.rating, 1516.0);
+    }
+
+    #[test]
+    fn test_memory_repository() {
+        check_repository(&MemoryGameRepository::default());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_repository() {
+        check_repository(&super::SqliteGameRepository::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_memory_player_repository() {
+        check_player_repository(&MemoryPlayerRepository::default());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_player_repository() {
+        check_player_repository(&super::SqlitePlayerRepository::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_parse_saved_game() {
+        let data = game(GameProcessingState::WaitingMachineMoves { acting_seat: 3 });
+        let json = serde_json::to_string(&data).unwrap();
+        let parsed = parse_saved_game(&json).unwrap();
+        assert!(matches!(
+            parsed.display_state,
+            GameProcessingState::WaitingMachineMoves { acting_seat: 3 }
+        ));
+
+        // saved before the acting seat was added
+        let mut value = serde_json::to_value(&data).unwrap();
+        value["display_state"] = "WaitingMachineMoves".into();
+        let parsed = parse_saved_game(&value.to_string()).unwrap();
+        assert!(matches!(
+            parsed.display_state,
+            GameProcessingState::WaitingMachineMoves { .. }
+        ));
+    }
+
+    /// Sends each batch it's asked to save, once it's let through by `gate`
+    struct GatedRepository {
+        gate: Mutex<Receiver<()>>,
+        started: Mutex<Sender<()>>,
+        saved: Mutex<Sender<Vec<(Uuid, u64)>>>,
+    }
+
+    impl GameRepository for GatedRepository {
+        fn save(&self, id: &Uuid, data: &GameData) -> anyhow::Result<()> {
+            self.save_all(&[(*id, data.clone())])
+        }
+
+        fn save_all(&self, games: &[(Uuid, GameData)]) -> anyhow::Result<()> {
+            self.started.lock().unwrap().send(()).unwrap();
+            self.gate.lock().unwrap().recv().unwrap();
+            let mut batch = games
+                .iter()
+                .map(|(id, data)| (*id, data.revision))
+                .collect::<Vec<_>>();
+            batch.sort();
+            self.saved.lock().unwrap().send(batch).unwrap();
+            Ok(())
+        }
+
+        fn load_active(&self) -> anyhow::Result<Vec<(Uuid, GameData)>> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Waits without blocking the runtime, the sync arbiter passes messages
+    /// on to the writer from it
+    async fn recv<T>(rx: &Receiver<T>) -> T {
+        loop {
+            if let Ok(x) = rx.try_recv() {
+                return x;
+            }
+            actix::clock::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_save_queue_coalesces_writes() {
+        let (gate, gate_rx) = channel();
+        let (started_tx, started) = channel();
+        let (saved_tx, saved) = channel();
+        let queue = SaveQueue::start(Arc::new(GatedRepository {
+            gate: Mutex::new(gate_rx),
+            started: Mutex::new(started_tx),
+            saved: Mutex::new(saved_tx),
+        }));
+        let state = |revision| {
+            let mut data = game(GameProcessingState::WaitingHumanMove);
+            data.revision = revision;
+            data
+        };
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // hold up the writer on the first save
+        queue.save(&a, &state(1));
+        recv(&started).await;
+
+        // queued while the writer is busy, only the latest state of each game
+        // is written
+        queue.save(&a, &state(2));
+        queue.save(&b, &state(1));
+        queue.save(&a, &state(3));
+        gate.send(()).unwrap();
+        gate.send(()).unwrap();
+
+        assert_eq!(recv(&saved).await, vec![(a, 1)]);
+        let mut expected = vec![(a, 3), (b, 1)];
+        expected.sort();
+        assert_eq!(recv(&saved).await, expected);
+    }
+}
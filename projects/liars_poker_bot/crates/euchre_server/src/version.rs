@@ -0,0 +1,124 @@
+use std::fmt::Display;
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::StatusCode,
+    middleware::Next,
+    HttpResponse, ResponseError,
+};
+use client_server_messages::{ApiError, ErrorCode, PROTOCOL_VERSION, PROTOCOL_VERSION_HEADER};
+use tracing::debug;
+
+/// An api request from a client built for another protocol version
+#[derive(Debug, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// The version the client sent, None if it couldn't be parsed
+    pub client: Option<u32>,
+}
+
+impl Display for VersionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the server was upgraded to protocol version {}, please refresh the page",
+            PROTOCOL_VERSION
+        )
+    }
+}
+
+impl ResponseError for VersionMismatch {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::UPGRADE_REQUIRED
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .json(ApiError::new(ErrorCode::VersionMismatch, self.to_string()))
+    }
+}
+
+/// Checks the request's [`PROTOCOL_VERSION_HEADER`], requests without one are
+/// let through so the api can still be used by hand
+fn check_version(req: &ServiceRequest) -> Result<(), VersionMismatch> {
+    let Some(value) = req.headers().get(PROTOCOL_VERSION_HEADER) else {
+        return Ok(());
+    };
+
+    let client = value.to_str().ok().and_then(|x| x.trim().parse().ok());
+    match client {
+        Some(PROTOCOL_VERSION) => Ok(()),
+        client => Err(VersionMismatch { client }),
+    }
+}
+
+/// Rejects api requests from clients built for another protocol version,
+/// before the handler fails to decode their body or they fail to decode the
+/// response
+pub async fn require_protocol_version<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    // clients can always ask which version the server is on
+    if req.path().starts_with("/api") && req.path() != "/api/version" {
+        if let Err(e) = check_version(&req) {
+            debug!(client = ?e.client, "rejected request from another protocol version");
+            return Ok(req.error_response(e).map_into_right_body());
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        middleware::from_fn,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        web, App, HttpResponse,
+    };
+    use client_server_messages::{ApiError, ErrorCode, PROTOCOL_VERSION, PROTOCOL_VERSION_HEADER};
+
+    use super::require_protocol_version;
+
+    #[actix_web::test]
+    async fn test_require_protocol_version() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(require_protocol_version))
+                .route("/api", web::get().to(HttpResponse::Ok))
+                .route("/api/version", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let current = PROTOCOL_VERSION.to_string();
+        let old = (PROTOCOL_VERSION - 1).to_string();
+        for (version, expected) in [
+            (Some(current.as_str()), StatusCode::OK),
+            (None, StatusCode::OK),
+            (Some(old.as_str()), StatusCode::UPGRADE_REQUIRED),
+            (Some("not a version"), StatusCode::UPGRADE_REQUIRED),
+        ] {
+            let mut req = TestRequest::get().uri("/api");
+            if let Some(version) = version {
+                req = req.insert_header((PROTOCOL_VERSION_HEADER, version));
+            }
+            let resp = call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), expected, "version {:?}", version);
+
+            if expected != StatusCode::OK {
+                let body: ApiError = read_body_json(resp).await;
+                assert_eq!(body.code, ErrorCode::VersionMismatch);
+            }
+        }
+
+        let req = TestRequest::get()
+            .uri("/api/version")
+            .insert_header((PROTOCOL_VERSION_HEADER, old.as_str()))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+    }
+}
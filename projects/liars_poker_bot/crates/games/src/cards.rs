@@ -1,3 +1,5 @@
+use std::ops::{BitAnd, BitOr, Sub};
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Card(u64);
 
@@ -39,6 +41,30 @@ impl Hand {
     }
 }
 
+impl From<[u16; 4]> for Hand {
+    /// Each element is the [`RankSet`] mask of the suit with that index
+    fn from(value: [u16; 4]) -> Self {
+        unsafe { std::mem::transmute(value) }
+    }
+}
+
+impl From<Hand> for [u16; 4] {
+    fn from(value: Hand) -> Self {
+        unsafe { std::mem::transmute(value) }
+    }
+}
+
+impl Hand {
+    /// The ranks held in `suit`
+    pub fn ranks(&self, suit: Suit) -> RankSet {
+        RankSet(<[u16; 4]>::from(*self)[suit as usize])
+    }
+
+    pub fn from_ranks(suits: [RankSet; 4]) -> Self {
+        suits.map(u16::from).into()
+    }
+}
+
 impl Iterator for Hand {
     type Item = Card;
 
@@ -54,6 +80,7 @@ impl Iterator for Hand {
 }
 
 #[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Rank {
     Two = 1 << 2,
     Three = 1 << 3,
@@ -76,6 +103,228 @@ impl From<Rank> for u16 {
     }
 }
 
+impl Rank {
+    /// Ranks from lowest to highest
+    pub const ALL: [Rank; 13] = {
+        use Rank::*;
+        [
+            Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Jack, Queen, King, Ace,
+        ]
+    };
+
+    /// Returns the rank for a mask with a single bit set
+    pub fn from_bit(bit: u16) -> Option<Rank> {
+        Self::ALL.into_iter().find(|r| *r as u16 == bit)
+    }
+}
+
+/// Set of ranks within a single suit, the building block for indexing hands
+///
+/// Stored as a `u16` mask where each rank is the bit given by its [`Rank`]
+/// discriminant, so `Two` is bit 2 and `Ace` is bit 14. Bits 0, 1 and 15 are
+/// never set. This is the same layout each suit has in a [`Card`] or [`Hand`],
+/// so converting between them is a shift rather than a per card loop.
+///
+/// Iterating yields the ranks from lowest to highest.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RankSet(u16);
+
+impl RankSet {
+    const ALL_MASK: u16 = 0b0111_1111_1111_1100;
+
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Every rank in a suit
+    pub fn all() -> Self {
+        Self(Self::ALL_MASK)
+    }
+
+    /// Returns `None` if `mask` sets a bit that isn't a rank
+    pub fn from_mask(mask: u16) -> Option<Self> {
+        (mask & !Self::ALL_MASK == 0).then_some(Self(mask))
+    }
+
+    pub fn mask(&self) -> u16 {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(&self, rank: Rank) -> bool {
+        self.0 & rank as u16 != 0
+    }
+
+    pub fn insert(&mut self, rank: Rank) {
+        self.0 |= rank as u16;
+    }
+
+    pub fn remove(&mut self, rank: Rank) {
+        self.0 &= !(rank as u16);
+    }
+
+    pub fn union(self, other: RankSet) -> RankSet {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: RankSet) -> RankSet {
+        Self(self.0 & other.0)
+    }
+
+    pub fn difference(self, other: RankSet) -> RankSet {
+        Self(self.0 & !other.0)
+    }
+
+    /// The ranks not in this set
+    pub fn complement(self) -> RankSet {
+        Self(!self.0 & Self::ALL_MASK)
+    }
+
+    pub fn lowest(&self) -> Option<Rank> {
+        Rank::from_bit(self.0 & self.0.wrapping_neg())
+    }
+
+    pub fn highest(&self) -> Option<Rank> {
+        if self.is_empty() {
+            return None;
+        }
+        Rank::from_bit(1 << (15 - self.0.leading_zeros()))
+    }
+
+    /// Removes the `used` ranks from the suit and shifts the ranks above each
+    /// of them down by one
+    ///
+    /// The result is this set's position among the ranks that are still
+    /// available, relabelled as if the suit only had those ranks. For example,
+    /// with `Two` used, `{Three, Five}` becomes `{Two, Four}`. Ranks in both
+    /// sets are dropped. This is how a later round's cards are indexed
+    /// independently of what was dealt in earlier rounds.
+    pub fn compress(self, used: RankSet) -> RankSet {
+        let mut out = 0;
+        for bit in BitIter(self.difference(used).0) {
+            let below = used.0 & (bit - 1);
+            out |= bit >> below.count_ones();
+        }
+        Self(out)
+    }
+
+    /// Inverse of [`RankSet::compress`], maps ranks relative to the available
+    /// ones back to the actual ranks
+    ///
+    /// Ranks above the number of available ones are dropped.
+    pub fn expand(self, used: RankSet) -> RankSet {
+        let mut out = 0;
+        let mut free = BitIter(used.complement().0);
+        let mut prev = Rank::Two as u16;
+        for bit in BitIter(self.0) {
+            // skip the free ranks for the unset bits between this and the last one
+            let Some(target) = free.nth((bit / prev).trailing_zeros() as usize) else {
+                break;
+            };
+            out |= target;
+            prev = bit << 1;
+        }
+        Self(out)
+    }
+
+    pub fn iter(&self) -> RankIter {
+        RankIter(BitIter(self.0))
+    }
+}
+
+impl std::fmt::Debug for RankSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl From<Rank> for RankSet {
+    fn from(value: Rank) -> Self {
+        Self(value as u16)
+    }
+}
+
+impl From<RankSet> for u16 {
+    fn from(value: RankSet) -> Self {
+        value.0
+    }
+}
+
+impl FromIterator<Rank> for RankSet {
+    fn from_iter<T: IntoIterator<Item = Rank>>(iter: T) -> Self {
+        let mut set = RankSet::empty();
+        iter.into_iter().for_each(|r| set.insert(r));
+        set
+    }
+}
+
+impl BitAnd for RankSet {
+    type Output = RankSet;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+impl BitOr for RankSet {
+    type Output = RankSet;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+impl Sub for RankSet {
+    type Output = RankSet;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.difference(rhs)
+    }
+}
+
+impl IntoIterator for RankSet {
+    type Item = Rank;
+    type IntoIter = RankIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Yields each set bit of a mask as its own mask, lowest first
+struct BitIter(u16);
+
+impl Iterator for BitIter {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+        let bit = self.0 & self.0.wrapping_neg();
+        self.0 &= !bit;
+        Some(bit)
+    }
+}
+
+/// Iterator over the ranks of a [`RankSet`], lowest first
+pub struct RankIter(BitIter);
+
+impl Iterator for RankIter {
+    type Item = Rank;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().and_then(Rank::from_bit)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Suit {
     Spade = 0,
@@ -112,4 +361,73 @@ mod tests {
 
         assert_eq!(deck_set, should_set);
     }
+
+    #[test]
+    fn test_rank_set() {
+        use Rank::*;
+        let set: RankSet = [Ace, Three, Ten].into_iter().collect();
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![Three, Ten, Ace]);
+        assert_eq!(set.lowest(), Some(Three));
+        assert_eq!(set.highest(), Some(Ace));
+        assert_eq!(RankSet::empty().highest(), None);
+
+        let other = RankSet::from(Ten) | RankSet::from(King);
+        assert_eq!((set & other).iter().collect::<Vec<_>>(), vec![Ten]);
+        assert_eq!((set - other).iter().collect::<Vec<_>>(), vec![Three, Ace]);
+        assert_eq!(set.complement().len(), 10);
+        assert_eq!(set | set.complement(), RankSet::all());
+
+        assert_eq!(RankSet::from_mask(set.mask()), Some(set));
+        assert_eq!(RankSet::from_mask(1), None);
+        assert_eq!(
+            RankSet::all().iter().collect::<Vec<_>>(),
+            Rank::ALL.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_rank_set_compress() {
+        use Rank::*;
+        let used: RankSet = [Two, Four].into_iter().collect();
+        let set: RankSet = [Three, Five, Ace].into_iter().collect();
+
+        let compressed = set.compress(used);
+        assert_eq!(
+            compressed.iter().collect::<Vec<_>>(),
+            vec![Two, Three, Queen]
+        );
+        assert_eq!(compressed.expand(used), set);
+
+        // used ranks are dropped
+        assert_eq!(used.compress(used), RankSet::empty());
+        assert_eq!(RankSet::all().compress(used).len(), 11);
+        assert_eq!(RankSet::all().expand(used), used.complement());
+
+        // every set roundtrips for any used mask
+        for used in [0, 0b0101_0000_0001_0100, 0b0111_1111_0000_0000] {
+            let used = RankSet::from_mask(used).unwrap();
+            for mask in 0..=RankSet::all().mask() {
+                let Some(set) = RankSet::from_mask(mask) else {
+                    continue;
+                };
+                let set = set - used;
+                assert_eq!(set.compress(used).expand(used), set);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hand_ranks() {
+        let card = Card::new(Rank::Queen, Suit::Diamonds);
+        let hand = Hand(card.0);
+        assert_eq!(hand.ranks(Suit::Diamonds), RankSet::from(Rank::Queen));
+        assert!(hand.ranks(Suit::Spade).is_empty());
+
+        let deck = Hand::standard();
+        for suit in [Suit::Spade, Suit::Clubs, Suit::Diamonds, Suit::Hearts] {
+            assert_eq!(deck.ranks(suit), RankSet::all());
+        }
+        assert_eq!(Hand::from_ranks([RankSet::all(); 4]).0, deck.0);
+    }
 }
@@ -0,0 +1,187 @@
+//! Randomized checks that any [`GameState`] implementation should pass
+//!
+//! Games are played with random actions while randomly undoing and redoing
+//! them. After every step the observable state is compared against what was
+//! seen the last time the game was at that point, so an `undo` that doesn't
+//! restore everything, or an `apply_action` that depends on hidden state, is
+//! caught close to where it happens.
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{istate::IStateKey, Action, Game, GameState, Player};
+
+/// Chance of undoing some actions rather than playing the next one
+const UNDO_PROBABILITY: f64 = 0.2;
+
+/// Everything about a gamestate that should be restored by undo
+#[derive(Debug, PartialEq, Clone)]
+struct Snapshot {
+    key: IStateKey,
+    istate_keys: Vec<IStateKey>,
+    istate_strings: Vec<String>,
+    is_terminal: bool,
+    is_chance_node: bool,
+    cur_player: Option<Player>,
+    actions: Vec<Action>,
+    hash: Option<u64>,
+    display: String,
+}
+
+impl Snapshot {
+    fn new<G: GameState>(gs: &G) -> Self {
+        let num_players = gs.num_players();
+        let is_terminal = gs.is_terminal();
+        let is_chance_node = gs.is_chance_node();
+
+        let mut actions = Vec::new();
+        if !is_terminal {
+            gs.legal_actions(&mut actions);
+        }
+
+        Self {
+            key: gs.key(),
+            // not all players have an istate while cards are still being dealt
+            istate_keys: match is_chance_node {
+                true => Vec::new(),
+                false => (0..num_players).map(|p| gs.istate_key(p)).collect(),
+            },
+            istate_strings: match is_chance_node {
+                true => Vec::new(),
+                false => (0..num_players).map(|p| gs.istate_string(p)).collect(),
+            },
+            is_terminal,
+            is_chance_node,
+            cur_player: (!is_terminal).then(|| gs.cur_player()),
+            actions,
+            hash: gs.transposition_table_hash(),
+            display: gs.to_string(),
+        }
+    }
+}
+
+/// Plays `num_games` random games of `game`, panicking if an invariant is broken
+///
+/// At every step this checks that:
+/// * non-terminal states have legal actions, sorted and without duplicates
+/// * undo restores the exact state from before the action
+/// * redoing the same action reaches the exact same state again
+/// * terminal states have a finite score for every player
+///
+/// Failures include the seed of the game so they can be reproduced with
+/// [`fuzz_game_with_seed`].
+pub fn fuzz_game<G: GameState>(game: &Game<G>, num_games: usize, rng: &mut impl Rng) {
+    for _ in 0..num_games {
+        fuzz_game_with_seed(game, rng.gen());
+    }
+}
+
+/// Plays a single random game of `game`, see [`fuzz_game`]
+pub fn fuzz_game_with_seed<G: GameState>(game: &Game<G>, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut gs = (game.new)();
+
+    // history[i] is the snapshot after the first i actions
+    let mut history = vec![Snapshot::new(&gs)];
+    let mut played: Vec<Action> = Vec::new();
+
+    loop {
+        let cur = history.last().unwrap();
+        check_snapshot(cur, seed, &played);
+
+        if cur.is_terminal {
+            for p in 0..gs.num_players() {
+                let v = gs.evaluate(p);
+                assert!(
+                    v.is_finite(),
+                    "seed {}: non-finite score {} for player {} after {:?}",
+                    seed,
+                    v,
+                    p,
+                    played
+                );
+            }
+            break;
+        }
+
+        if !played.is_empty() && rng.gen_bool(UNDO_PROBABILITY) {
+            let n = rng.gen_range(1..=played.len());
+            undo_and_redo(&mut gs, &history, &played, n, seed);
+        }
+
+        let a = *history.last().unwrap().actions.choose(&mut rng).unwrap();
+        gs.apply_action(a);
+        played.push(a);
+        history.push(Snapshot::new(&gs));
+
+        // the same action from the same state must always give the same state
+        gs.undo();
+        assert_snapshot(&gs, &history[history.len() - 2], seed, &played, "undo");
+        gs.apply_action(a);
+        assert_snapshot(&gs, &history[history.len() - 1], seed, &played, "redo");
+    }
+}
+
+/// Undo the last `n` actions, checking each intermediate state, then replay them
+fn undo_and_redo<G: GameState>(
+    gs: &mut G,
+    history: &[Snapshot],
+    played: &[Action],
+    n: usize,
+    seed: u64,
+) {
+    for i in 1..=n {
+        gs.undo();
+        let expected = &history[history.len() - 1 - i];
+        assert_snapshot(gs, expected, seed, &played[..played.len() - i], "undo");
+    }
+
+    for i in (0..n).rev() {
+        let a = played[played.len() - 1 - i];
+        gs.apply_action(a);
+        let expected = &history[history.len() - 1 - i];
+        assert_snapshot(gs, expected, seed, &played[..played.len() - i], "redo");
+    }
+}
+
+fn assert_snapshot<G: GameState>(
+    gs: &G,
+    expected: &Snapshot,
+    seed: u64,
+    played: &[Action],
+    step: &str,
+) {
+    let actual = Snapshot::new(gs);
+    assert_eq!(
+        &actual, expected,
+        "seed {}: state differs after {} at {:?}",
+        seed, step, played
+    );
+}
+
+/// Invariants that only depend on a single state
+fn check_snapshot(s: &Snapshot, seed: u64, played: &[Action]) {
+    if s.is_terminal {
+        return;
+    }
+
+    assert!(
+        !s.actions.is_empty(),
+        "seed {}: no legal actions for non-terminal state after {:?}",
+        seed,
+        played
+    );
+    assert!(
+        s.actions.windows(2).all(|w| w[0] < w[1]),
+        "seed {}: legal actions not sorted or contain duplicates after {:?}: {:?}",
+        seed,
+        played,
+        s.actions
+    );
+    assert!(
+        s.is_chance_node || s.cur_player.is_some_and(|p| p < s.istate_keys.len()),
+        "seed {}: invalid current player {:?} after {:?}",
+        seed,
+        s.cur_player,
+        played
+    );
+}
@@ -305,6 +305,24 @@ impl BluffGameState {
         }
     }
 
+    /// The dice rolled so far by `player` in the order they were rolled
+    fn dice_string(&self, player: Player) -> String {
+        self.dice[player].iter().map(|d| d.to_string()).collect()
+    }
+
+    /// Bids as `{count}x{face}`, separated by commas
+    fn bid_history(&self) -> String {
+        let public_start = (self.num_dice[0] + self.num_dice[1]).min(self.key.len());
+        self.key[public_start..]
+            .iter()
+            .map(|&a| match BluffActions::from(a) {
+                BluffActions::Bid(n, d) => format!("{}x{}", n, d),
+                a => a.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
     fn last_bid(&self) -> BluffActions {
         // at least one action other than the dice rolling
         if self.key.len() <= self.num_dice[0] + self.num_dice[1] {
@@ -362,28 +380,9 @@ impl GameState for BluffGameState {
         key
     }
 
+    /// Returns the player's dice and then the bid history, e.g. `12|1x1,2x3,C`
     fn istate_string(&self, player: Player) -> String {
-        let mut istate = String::new();
-
-        let k = self.istate_key(player);
-
-        // push the dice
-        for i in 0..self.num_dice[player] {
-            let s = format!("{}", BluffActions::from(k[i]));
-            istate.push_str(&s);
-        }
-        istate.push('|');
-
-        for i in self.num_dice[player]..k.len() {
-            let s = format!("{}", BluffActions::from(k[i]));
-            istate.push_str(&s);
-
-            if i != k.len() - 1 {
-                istate.push('|');
-            }
-        }
-
-        istate
+        format!("{}|{}", self.dice_string(player), self.bid_history())
     }
 
     fn is_terminal(&self) -> bool {
@@ -502,17 +501,16 @@ fn calculate_payoff(
     }
 }
 
+/// Both players' dice and then the bid history, e.g. `12|34|1x1,2x3,C`
 impl Display for BluffGameState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let num_dice = self.num_dice.iter().sum();
-        for (i, a) in self.key().iter().enumerate() {
-            write!(f, "{}", BluffActions::from(*a)).unwrap();
-            if i == self.num_dice[0] - 1 || i == num_dice - 1 || (i >= num_dice) {
-                write!(f, "|").unwrap();
-            }
-        }
-
-        write!(f, "")
+        write!(
+            f,
+            "{}|{}|{}",
+            self.dice_string(0),
+            self.dice_string(1),
+            self.bid_history()
+        )
     }
 }
 
@@ -524,6 +522,7 @@ mod tests {
 
     use crate::{
         actions,
+        fuzz::fuzz_game,
         gamestates::bluff::{Bluff, BluffGameState, Phase, FACES},
         Action, GameState,
     };
@@ -637,21 +636,22 @@ mod tests {
         ]);
 
         let istate = gs.istate_string(0);
-        assert_eq!(istate, "12|11");
+        assert_eq!(istate, "12|1x1");
         let istate = gs.istate_string(1);
-        assert_eq!(istate, "34|11");
+        assert_eq!(istate, "34|1x1");
 
         gs.apply_action(BluffActions::Bid(2, Dice::Three).into());
         let istate = gs.istate_string(0);
-        assert_eq!(istate, "12|11|23");
+        assert_eq!(istate, "12|1x1,2x3");
         let istate = gs.istate_string(1);
-        assert_eq!(istate, "34|11|23");
+        assert_eq!(istate, "34|1x1,2x3");
 
         gs.apply_action(BluffActions::Call.into());
         let istate = gs.istate_string(0);
-        assert_eq!(istate, "12|11|23|C");
+        assert_eq!(istate, "12|1x1,2x3,C");
         let istate = gs.istate_string(1);
-        assert_eq!(istate, "34|11|23|C");
+        assert_eq!(istate, "34|1x1,2x3,C");
+        assert_eq!(gs.to_string(), "12|34|1x1,2x3,C");
     }
 
     #[test]
@@ -731,9 +731,9 @@ mod tests {
         assert_eq!(gs.evaluate(1), 1.0);
 
         let istate = gs.istate_string(0);
-        assert_eq!(istate, "1|11|21|C");
+        assert_eq!(istate, "1|1x1,2x1,C");
         let istate = gs.istate_string(1);
-        assert_eq!(istate, "*|11|21|C");
+        assert_eq!(istate, "*|1x1,2x1,C");
     }
 
     #[test]
@@ -810,9 +810,9 @@ mod tests {
         assert_eq!(gs.evaluate(1), 1.0);
 
         let istate = gs.istate_string(0);
-        assert_eq!(istate, "11|11|31|C");
+        assert_eq!(istate, "11|1x1,3x1,C");
         let istate = gs.istate_string(1);
-        assert_eq!(istate, "*|11|31|C");
+        assert_eq!(istate, "*|1x1,3x1,C");
     }
 
     #[test]
@@ -850,4 +850,11 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_bluff_fuzz() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        fuzz_game(&Bluff::game(1, 1), 500, &mut rng);
+        fuzz_game(&Bluff::game(2, 2), 100, &mut rng);
+    }
 }
@@ -246,6 +246,20 @@ impl Card {
         }
     }
 
+    /// Spoken name of the card, e.g. "Jack of Spades"
+    pub fn name(&self) -> String {
+        // the rank is the first character of the short form, e.g. "Js"
+        let rank = match self.to_string().chars().next() {
+            Some('9') => "Nine",
+            Some('T') => "Ten",
+            Some('J') => "Jack",
+            Some('Q') => "Queen",
+            Some('K') => "King",
+            _ => "Ace",
+        };
+        format!("{} of {}", rank, self.suit().name())
+    }
+
     pub fn icon(&self) -> &str {
         match self {
             Card::NC => "🃙",
@@ -486,6 +500,15 @@ pub enum Suit {
 }
 
 impl Suit {
+    pub fn name(&self) -> &str {
+        match self {
+            Suit::Clubs => "Clubs",
+            Suit::Spades => "Spades",
+            Suit::Hearts => "Hearts",
+            Suit::Diamonds => "Diamonds",
+        }
+    }
+
     pub fn icon(&self) -> &str {
         match self {
             Suit::Clubs => "♣",
@@ -535,4 +558,12 @@ mod tests {
             assert_eq!(card, *c);
         }
     }
+
+    #[test]
+    fn test_card_name() {
+        assert_eq!(Card::JS.name(), "Jack of Spades");
+        assert_eq!(Card::ND.name(), "Nine of Diamonds");
+        assert_eq!(Card::TH.name(), "Ten of Hearts");
+        assert_eq!(Card::AC.name(), "Ace of Clubs");
+    }
 }
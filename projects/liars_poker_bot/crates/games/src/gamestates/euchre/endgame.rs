@@ -0,0 +1,320 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::Player;
+
+use super::{
+    actions::{Card, Suit},
+    EPhase, EuchreGameState,
+};
+
+/// Number of tricks covered by endgame positions
+pub const ENDGAME_TRICKS: usize = 2;
+/// Cards that must be played before a position can be an endgame
+pub const ENDGAME_CARDS_PLAYED: usize = 20 - ENDGAME_TRICKS * 4;
+
+const NUM_SUITS: usize = 4;
+const TRUMP: usize = 0;
+
+/// Canonical representation of a euchre position at the start of one of the
+/// final two tricks.
+///
+/// Only the relative order of the remaining cards within each effective suit
+/// matters for who wins the remaining tricks, so each suit is stored as the
+/// seats (relative to the trick leader) holding its cards from highest to lowest.
+/// Trump is always the first suit, the off suits are sorted so that isomorphic
+/// positions share a key.
+///
+/// Layout: the low 16 bits store the number of cards in each suit, 4 bits per
+/// suit. The high 16 bits store the owning seat of each card, 2 bits per card.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug, PartialOrd, Ord)]
+pub struct EndgameKey(u32);
+
+impl EndgameKey {
+    fn new(mut suits: [Vec<u8>; NUM_SUITS]) -> Self {
+        // off suits are interchangeable
+        suits[TRUMP + 1..].sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+        let mut lens = 0;
+        let mut owners = 0;
+        let mut i = 0;
+        for (s, seats) in suits.iter().enumerate() {
+            lens |= (seats.len() as u32) << (s * 4);
+            for seat in seats {
+                owners |= (*seat as u32) << (i * 2);
+                i += 1;
+            }
+        }
+        assert!(i <= 8, "too many cards for an endgame position");
+
+        EndgameKey(lens | owners << 16)
+    }
+
+    fn suits(&self) -> [Vec<u8>; NUM_SUITS] {
+        let mut suits: [Vec<u8>; NUM_SUITS] = Default::default();
+        let mut i = 0;
+        for (s, seats) in suits.iter_mut().enumerate() {
+            let len = (self.0 >> (s * 4)) & 0b1111;
+            for _ in 0..len {
+                seats.push(((self.0 >> (16 + i * 2)) & 0b11) as u8);
+                i += 1;
+            }
+        }
+        suits
+    }
+
+    /// Returns the number of tricks won by the leader's team for the remaining
+    /// tricks, assuming both teams play perfectly
+    pub fn solve(&self) -> u8 {
+        let mut hands: [Vec<(usize, usize)>; 4] = Default::default();
+        for (s, seats) in self.suits().iter().enumerate() {
+            for (rank, seat) in seats.iter().enumerate() {
+                hands[*seat as usize].push((s, rank));
+            }
+        }
+        solve_trick(&mut hands, 0, &mut Vec::with_capacity(4))
+    }
+}
+
+/// Returns all canonical endgame positions with `tricks` remaining
+pub fn endgame_positions(tricks: usize) -> Vec<EndgameKey> {
+    assert!(tricks <= ENDGAME_TRICKS);
+    let num_cards = tricks * 4;
+
+    // every ordering of which seat owns each card
+    let seats = (0..4u8).flat_map(|s| vec![s; tricks]).collect_vec();
+    let orderings = seats
+        .iter()
+        .copied()
+        .permutations(num_cards)
+        .unique()
+        .collect_vec();
+
+    let mut keys = Vec::new();
+    // there are 7 trump cards, 6 cards in two of the off suits and 5 in the same color suit
+    for trump in 0..=num_cards.min(7) {
+        for a in 0..=(num_cards - trump).min(6) {
+            for b in 0..=(num_cards - trump - a).min(a) {
+                let c = num_cards - trump - a - b;
+                if c > b {
+                    continue;
+                }
+                let lens = [trump, a, b, c];
+                for seats in &orderings {
+                    let mut suits: [Vec<u8>; NUM_SUITS] = Default::default();
+                    let mut start = 0;
+                    for (s, len) in lens.iter().enumerate() {
+                        suits[s] = seats[start..start + len].to_vec();
+                        start += len;
+                    }
+                    keys.push(EndgameKey::new(suits));
+                }
+            }
+        }
+    }
+
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Minimax over the trick count for the leader's team, seat 0 and 2 maximize
+fn solve_trick(
+    hands: &mut [Vec<(usize, usize)>; 4],
+    leader: usize,
+    trick: &mut Vec<(usize, usize)>,
+) -> u8 {
+    if trick.len() == 4 {
+        let led = trick[0].0;
+        let (winner, _) = trick
+            .iter()
+            .enumerate()
+            .filter(|(_, (s, _))| *s == TRUMP || *s == led)
+            // lower rank is a higher card, trump beats the led suit
+            .min_by_key(|(_, (s, r))| (*s != TRUMP, *r))
+            .unwrap();
+        let winner = (leader + winner) % 4;
+        let won = (winner % 2 == 0) as u8;
+
+        if hands[winner].is_empty() {
+            return won;
+        }
+
+        let played = std::mem::take(trick);
+        let v = won + solve_trick(hands, winner, trick);
+        *trick = played;
+        return v;
+    }
+
+    let seat = (leader + trick.len()) % 4;
+    let must_follow = trick
+        .first()
+        .map(|(led, _)| *led)
+        .filter(|led| hands[seat].iter().any(|(s, _)| s == led));
+
+    let maximizing = seat % 2 == 0;
+    let mut best = if maximizing { u8::MIN } else { u8::MAX };
+    for i in 0..hands[seat].len() {
+        let card = hands[seat][i];
+        if must_follow.is_some_and(|led| card.0 != led) {
+            continue;
+        }
+
+        hands[seat].remove(i);
+        trick.push(card);
+        let v = solve_trick(hands, leader, trick);
+        trick.pop();
+        hands[seat].insert(i, card);
+
+        best = if maximizing { best.max(v) } else { best.min(v) };
+    }
+    best
+}
+
+impl EuchreGameState {
+    /// Returns the canonical endgame key if the game is at the start of one
+    /// of the final two tricks
+    pub fn endgame_key(&self) -> Option<EndgameKey> {
+        if self.phase() != EPhase::Play
+            || self.cards_played < ENDGAME_CARDS_PLAYED
+            || !self.is_start_of_trick()
+            || self.cards_played == 20
+        {
+            return None;
+        }
+
+        let (trump, _) = self.trump()?;
+        let leader = self.cur_player;
+
+        let mut cards: [Vec<(u8, u8)>; NUM_SUITS] = Default::default();
+        for p in 0..self.num_players {
+            let seat = ((p + 4 - leader) % 4) as u8;
+            for c in self.get_hand(p) {
+                let suit = self.get_suit(c);
+                cards[suit as usize].push((strength(c, suit, trump), seat));
+            }
+        }
+
+        let mut suits: [Vec<u8>; NUM_SUITS] = Default::default();
+        let off_suits = [Suit::Spades, Suit::Clubs, Suit::Hearts, Suit::Diamonds]
+            .into_iter()
+            .filter(|s| *s != trump);
+        for (i, s) in [trump].into_iter().chain(off_suits).enumerate() {
+            suits[i] = cards[s as usize]
+                .iter()
+                .sorted_by(|a, b| b.0.cmp(&a.0))
+                .map(|(_, seat)| *seat)
+                .collect();
+        }
+
+        Some(EndgameKey::new(suits))
+    }
+
+    /// Returns the final score for player `p` if the leader of the current
+    /// trick's team wins `leader_tricks` of the remaining tricks
+    pub fn evaluate_with_remaining(&self, p: Player, leader_tricks: u8) -> f64 {
+        let remaining = ((20 - self.cards_played) / 4) as u8;
+        assert!(leader_tricks <= remaining);
+
+        let mut tricks = self.tricks_won;
+        let leader_team = self.cur_player % 2;
+        tricks[leader_team] += leader_tricks;
+        tricks[(leader_team + 1) % 2] += remaining - leader_tricks;
+
+        let v = self.score(tricks[0], tricks[1]);
+        if p % 2 == 0 {
+            v
+        } else {
+            -v
+        }
+    }
+}
+
+/// Relative strength of a card within its effective suit, higher is better
+fn strength(c: Card, effective_suit: Suit, trump: Suit) -> u8 {
+    let is_jack = matches!(c, Card::JC | Card::JS | Card::JH | Card::JD);
+    match (is_jack, effective_suit == trump, c.suit() == trump) {
+        (true, true, true) => 7,  // right bower
+        (true, true, false) => 6, // left bower
+        // position of the card within its suit block: 9, T, J, Q, K, A
+        _ => (c.mask().trailing_zeros() % 8) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::{
+        actions,
+        gamestates::euchre::{Euchre, EuchreGameState},
+        GameState,
+    };
+
+    use super::{endgame_positions, EndgameKey, ENDGAME_CARDS_PLAYED};
+
+    #[test]
+    fn test_endgame_key_round_trip() {
+        for key in endgame_positions(2) {
+            let suits = key.suits();
+            assert_eq!(EndgameKey::new(suits), key);
+        }
+    }
+
+    #[test]
+    fn test_endgame_positions_cover_games() {
+        let positions = endgame_positions(2);
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+
+        for _ in 0..200 {
+            let gs = play_to_endgame(&mut rng);
+            let key = gs.endgame_key().unwrap();
+            assert!(positions.binary_search(&key).is_ok(), "{}", gs);
+        }
+    }
+
+    #[test]
+    fn test_endgame_solve_matches_search() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(7);
+
+        for _ in 0..200 {
+            let mut gs = play_to_endgame(&mut rng);
+            let key = gs.endgame_key().unwrap();
+            let leader = gs.cur_player();
+            let start = gs.trick_score()[leader % 2];
+            let expected = max_leader_tricks(&mut gs, leader) - start;
+            assert_eq!(key.solve(), expected, "{}", gs);
+        }
+    }
+
+    fn play_to_endgame(rng: &mut StdRng) -> EuchreGameState {
+        loop {
+            let mut gs = Euchre::new_state();
+            while !gs.is_terminal() && gs.cards_played < ENDGAME_CARDS_PLAYED {
+                let a = *actions!(gs).choose(rng).unwrap();
+                gs.apply_action(a);
+            }
+
+            if !gs.is_terminal() {
+                return gs;
+            }
+        }
+    }
+
+    /// Brute force the tricks won by the leader's team, playing out every trick
+    fn max_leader_tricks(gs: &mut EuchreGameState, leader: usize) -> u8 {
+        if gs.cards_played == 20 {
+            return gs.trick_score()[leader % 2];
+        }
+
+        let maximizing = gs.cur_player() % 2 == leader % 2;
+        let mut best = if maximizing { u8::MIN } else { u8::MAX };
+        for a in actions!(gs) {
+            gs.apply_action(a);
+            let v = max_leader_tricks(gs, leader);
+            gs.undo();
+            best = if maximizing { best.max(v) } else { best.min(v) };
+        }
+        best
+    }
+}
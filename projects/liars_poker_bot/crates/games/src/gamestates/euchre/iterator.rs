@@ -363,6 +363,24 @@ impl EuchreIState {
     }
 }
 
+/// Returns the number of cards played before the istate, or None if the istate
+/// is from the bidding
+///
+/// Uses the same phase logic as the iterator, so only supports istates in
+/// the same form as the ones it returns.
+pub fn istate_cards_played(istate: &IStateKey) -> Option<usize> {
+    let last = EAction::from(*istate.last()?);
+    if istate.len() <= 6 || matches!(last, Pass | DiscardMarker) {
+        return None;
+    }
+
+    // discarded cards were already seen when dealt, so each card after the
+    // hand and face up card is a played card
+    let mut seen = ActionSet::default();
+    istate.iter().for_each(|a| seen.add(EAction::from(*a)));
+    Some((seen.0 & !NON_CARD_ACTIONS).count_ones() as usize - 6)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -397,10 +415,21 @@ mod tests {
         // Validate overall counts
         let iterator = EuchreIsomorphicIStateIterator::with_face_up(0, &[EAction::NS]);
         assert_eq!(iterator.count(), 229_229);
+        let mut iterator = EuchreIsomorphicIStateIterator::with_face_up(0, &[EAction::NS]);
+        assert!(iterator.all(|x| istate_cards_played(&x).is_none()));
         let iterator = EuchreIsomorphicIStateIterator::new(0);
         assert_eq!(iterator.count(), 229_229 * 6);
 
         let iterator = EuchreIsomorphicIStateIterator::with_face_up(1, &[EAction::NS]);
         assert_eq!(iterator.count(), 556_171);
+
+        // play istates only go up to the max cards played
+        let cards_played = EuchreIsomorphicIStateIterator::with_face_up(2, &[EAction::NS])
+            .map(|x| istate_cards_played(&x))
+            .counts();
+        assert_eq!(
+            cards_played.keys().sorted().collect_vec(),
+            vec![&None, &Some(0), &Some(1)]
+        );
     }
 }
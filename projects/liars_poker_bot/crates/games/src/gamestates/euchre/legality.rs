@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::{actions, Action, GameState};
+
+use super::{
+    actions::{Card, EAction, Suit},
+    deck::CardLocation,
+    EPhase, EuchreGameState,
+};
+
+/// Why an action can't be taken by the current player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IllegalReason {
+    /// The hand is over
+    GameOver,
+    /// The action isn't a euchre action
+    UnknownAction,
+    /// The action belongs to another phase, e.g. playing a card while bidding
+    WrongPhase(EPhase),
+    /// The card has already been dealt
+    AlreadyDealt(Card),
+    /// The card isn't in the current player's hand
+    NotInHand(Card),
+    /// The player has a card of the led suit, the left bower counts as trump
+    MustFollowSuit(Suit),
+    /// The suit of the turned down card can't be called as trump
+    TurnedDownSuit(Suit),
+    /// Everyone else passed so the dealer has to call trump
+    DealerMustCall,
+}
+
+impl Display for IllegalReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use IllegalReason::*;
+        match self {
+            GameOver => write!(f, "the hand is over"),
+            UnknownAction => write!(f, "not a euchre action"),
+            WrongPhase(phase) => {
+                let hint = match phase {
+                    EPhase::DealHands | EPhase::DealFaceUp => "the cards are still being dealt",
+                    EPhase::Pickup => "bidding on the face up card, pick it up or pass",
+                    EPhase::Discard => "the dealer must discard a card",
+                    EPhase::ChooseTrump => "bidding on trump, call a suit or pass",
+                    EPhase::Play => "bidding is over, play a card",
+                };
+                f.write_str(hint)
+            }
+            AlreadyDealt(card) => write!(f, "{} has already been dealt", card),
+            NotInHand(card) => write!(f, "{} isn't in your hand", card),
+            MustFollowSuit(suit) => write!(f, "you must follow suit, play a {}", suit.icon()),
+            TurnedDownSuit(suit) => {
+                write!(f, "{} was turned down, call another suit", suit.icon())
+            }
+            DealerMustCall => write!(f, "the dealer can't pass, call a suit"),
+        }
+    }
+}
+
+impl EuchreGameState {
+    /// Returns why the current player can't take `a`, None if it's legal
+    pub fn explain_illegal(&self, a: Action) -> Option<IllegalReason> {
+        use IllegalReason::*;
+
+        // actions are the index of the set bit in an `EAction`
+        let Some(ea) = 1u32.checked_shl(a.0 as u32).and_then(EAction::from_u32) else {
+            return Some(UnknownAction);
+        };
+        if ea == EAction::DiscardMarker {
+            return Some(UnknownAction);
+        }
+        if self.is_terminal() {
+            return Some(GameOver);
+        }
+        if actions!(self).contains(&a) {
+            return None;
+        }
+
+        let phase = self.phase();
+        let card = Card::from_u32(ea as u32);
+        let suit = match ea {
+            EAction::Spades => Some(Suit::Spades),
+            EAction::Clubs => Some(Suit::Clubs),
+            EAction::Hearts => Some(Suit::Hearts),
+            EAction::Diamonds => Some(Suit::Diamonds),
+            _ => None,
+        };
+
+        let reason = match (phase, card, suit) {
+            (EPhase::DealHands | EPhase::DealFaceUp, Some(card), _) => AlreadyDealt(card),
+            (EPhase::Discard, Some(card), _) => NotInHand(card),
+            (EPhase::Play, Some(card), _)
+                if self.deck.get(card) != CardLocation::from(self.cur_player) =>
+            {
+                NotInHand(card)
+            }
+            // the only other reason a card in hand can't be played
+            (EPhase::Play, Some(_), _) => MustFollowSuit(self.get_suit(self.get_leading_card())),
+            (EPhase::ChooseTrump, _, Some(suit)) => TurnedDownSuit(suit),
+            (EPhase::ChooseTrump, _, None) if ea == EAction::Pass => DealerMustCall,
+            (phase, _, _) => WrongPhase(phase),
+        };
+        Some(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gamestates::euchre::{
+            actions::{Card, EAction, Suit},
+            EPhase, EuchreGameState,
+        },
+        Action,
+    };
+
+    use super::IllegalReason::*;
+
+    #[test]
+    fn test_explain_illegal() {
+        // clubs are trump, player 2 is following a diamond lead
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|QdKd");
+        assert_eq!(gs.explain_illegal(EAction::TD.into()), None);
+        assert_eq!(
+            gs.explain_illegal(EAction::AC.into()),
+            Some(MustFollowSuit(Suit::Diamonds))
+        );
+        assert_eq!(
+            gs.explain_illegal(EAction::QS.into()),
+            Some(NotInHand(Card::QS))
+        );
+        assert_eq!(
+            gs.explain_illegal(EAction::Pass.into()),
+            Some(WrongPhase(EPhase::Play))
+        );
+        assert_eq!(gs.explain_illegal(Action(15)), Some(UnknownAction));
+        assert_eq!(gs.explain_illegal(Action(200)), Some(UnknownAction));
+
+        let gs = EuchreGameState::from("AsJhJdQdAd|QcTs9h9dTd|TcKcJsQsKd|9sKsThQhAh|9c|PPPP");
+        assert_eq!(
+            gs.explain_illegal(EAction::Clubs.into()),
+            Some(TurnedDownSuit(Suit::Clubs))
+        );
+        assert_eq!(
+            gs.explain_illegal(EAction::AS.into()),
+            Some(WrongPhase(EPhase::ChooseTrump))
+        );
+
+        let gs = EuchreGameState::from("AsJhJdQdAd|QcTs9h9dTd|TcKcJsQsKd|9sKsThQhAh|9c|PPPPPPP");
+        assert_eq!(
+            gs.explain_illegal(EAction::Pass.into()),
+            Some(DealerMustCall)
+        );
+    }
+}
@@ -23,8 +23,10 @@ pub(super) const CARDS_PER_HAND: usize = 5;
 
 pub mod actions;
 mod deck;
+pub mod endgame;
 pub mod ismorphic;
 pub mod iterator;
+pub mod legality;
 mod parser;
 pub mod processors;
 pub mod resample;
@@ -32,6 +34,26 @@ pub mod util;
 
 pub struct Euchre {}
 impl Euchre {
+    /// Name the euchre action labeler is registered under, see [`crate::labels`]
+    pub const NAME: &'static str = "euchre";
+
+    /// Labels euchre actions in logs written from this thread, e.g. `Js (card)`
+    ///
+    /// Dealing, discarding and playing all use the card actions, so cards are
+    /// labeled the same in every phase.
+    pub fn register_action_labeler() {
+        crate::labels::register_action_labeler(Self::NAME, |a| {
+            let ea = EAction::from(a);
+            let kind = match ea {
+                EAction::Spades | EAction::Clubs | EAction::Hearts | EAction::Diamonds => "call",
+                EAction::Pickup => "pickup",
+                EAction::Pass => "pass",
+                EAction::DiscardMarker => "discard",
+                _ => "card",
+            };
+            format!("{} ({})", ea, kind)
+        });
+    }
     pub fn new_state() -> EuchreGameState {
         EuchreGameState {
             num_players: 4,
@@ -87,6 +109,17 @@ pub enum EPhase {
     Play,
 }
 
+/// Which players are playing without their partner for the hand
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
+pub enum LoneHand {
+    #[default]
+    None,
+    /// The maker is going alone against both defenders
+    Maker,
+    /// The maker is going alone and is being defended by a single defender
+    MakerAndDefender,
+}
+
 impl EuchreGameState {
     /// Returns players bid actions if they have taken them so far
     pub fn bids(&self) -> [Option<EAction>; 8] {
@@ -130,22 +163,48 @@ impl EuchreGameState {
         self.cards_played % 4 == 0 && self.cards_played > 0
     }
 
+    /// The last completed trick, see [`EuchreGameState::tricks`]
     pub fn last_trick(&self) -> Option<(Player, [Card; 4])> {
-        if self.cards_played < 4 {
-            return None;
-        }
+        self.tricks()
+            .last()
+            .map(|(leader, trick, _)| (leader, trick))
+    }
 
-        let cards_played_in_cur_trick = self.cards_played % 4;
+    /// Completed tricks in the order they were played, as the player who led,
+    /// the cards in the order they were played and the player who won
+    pub fn tricks(&self) -> impl Iterator<Item = (Player, [Card; 4], Player)> + '_ {
+        // the played cards are the end of the key
+        let first_play = self.key.len() - self.cards_played;
+        (0..self.cards_played / 4).map(move |t| {
+            let sidx = first_play + t * 4;
+            let mut trick = [Card::NS; 4];
+            for (i, c) in trick.iter_mut().enumerate() {
+                *c = EAction::from(self.key[sidx + i]).card();
+            }
+            (self.play_order[sidx], trick, self.trick_winners[t])
+        })
+    }
+
+    /// Returns who would be winning the current trick if the current player
+    /// played `card`
+    pub fn trick_winner_with(&self, card: Card) -> Player {
+        assert_eq!(
+            self.phase(),
+            EPhase::Play,
+            "can only evaluate tricks in the play phase"
+        );
 
-        let sidx = self.key.len() - cards_played_in_cur_trick - 4;
+        let cards_played_in_trick = self.cards_played % 4;
+        let sidx = self.key.len() - cards_played_in_trick;
         let mut trick = [Card::NS; 4];
-        for (i, t) in trick.iter_mut().enumerate() {
+        for (i, t) in trick.iter_mut().enumerate().take(cards_played_in_trick) {
             *t = EAction::from(self.key[sidx + i]).card();
         }
+        trick[cards_played_in_trick] = card;
 
-        let trick_starter = self.play_order[sidx];
-
-        Some((trick_starter, trick))
+        let starter =
+            (self.cur_player + self.num_players - cards_played_in_trick) % self.num_players;
+        self.evaluate_trick(&trick[..=cards_played_in_trick], starter)
     }
 
     /// Return all cards currently in a players hand
@@ -577,37 +636,62 @@ impl EuchreGameState {
         (self.tricks_won[0], self.tricks_won[1])
     }
 
+    /// Returns who is going alone for the current hand.
+    ///
+    /// Going alone is not yet part of the action set, so every hand is
+    /// currently played with full teams.
+    pub fn lone_hand(&self) -> LoneHand {
+        LoneHand::None
+    }
+
     /// Returns the score for team 0 based on tricks won for each team
     fn score(&self, tricks0: u8, tricks1: u8) -> f64 {
-        // needs to be a winner
-        assert!(tricks0 >= 3 || tricks1 >= 3);
         assert_eq!(self.phase(), EPhase::Play);
-
-        let team_0_call = self.trump_caller % 2 == 0;
-        match (tricks0, tricks1, team_0_call) {
-            (5, 0, _) => 2.0,
-            (0, 5, _) => -2.0,
-            (3 | 4, _, true) => 1.0,
-            (3 | 4, _, false) => 2.0,
-            (_, 3 | 4, true) => -2.0,
-            (_, 3 | 4, false) => -1.0,
-            _ => panic!(
-                "invalid trick state to call score: {}, {}",
-                tricks0, tricks1
-            ),
-        }
+        score_hand(tricks0, tricks1, self.trump_caller % 2, self.lone_hand())
     }
 }
 
+/// Returns the points won by team 0 for a hand
+///
+/// Official scoring:
+/// * makers win 3 or 4 tricks: 1 point
+/// * makers win all 5 tricks: 2 points, 4 if the maker went alone
+/// * makers are euchred: 2 points to the defenders, 4 if a lone maker was
+///   euchred by a lone defender
+fn score_hand(tricks0: u8, tricks1: u8, maker_team: usize, lone: LoneHand) -> f64 {
+    // needs to be a winner
+    assert!(tricks0 >= 3 || tricks1 >= 3);
+
+    let (maker_tricks, sign) = match maker_team {
+        0 => (tricks0, 1.0),
+        1 => (tricks1, -1.0),
+        _ => panic!("invalid maker team: {}", maker_team),
+    };
+
+    let maker_points = match (maker_tricks, lone) {
+        (5, LoneHand::None) => 2.0,
+        (5, LoneHand::Maker | LoneHand::MakerAndDefender) => 4.0,
+        (3 | 4, _) => 1.0,
+        (0..=2, LoneHand::None | LoneHand::Maker) => -2.0,
+        (0..=2, LoneHand::MakerAndDefender) => -4.0,
+        _ => panic!(
+            "invalid trick state to call score: {}, {}",
+            tricks0, tricks1
+        ),
+    };
+
+    sign * maker_points
+}
+
 impl Display for EuchreGameState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let key = &self.key();
-        let mut first_play = None;
+        let first_play = key.len() - self.cards_played;
         let mut is_last_take = false;
 
-        for i in 0..key.len() {
+        for i in 0..first_play {
             let a = EAction::from(key[i]);
-            write!(f, "{}", a).unwrap();
+            write!(f, "{}", a)?;
 
             let append_pipe = match a {
                 // dealing cards
@@ -615,35 +699,31 @@ impl Display for EuchreGameState {
                 // faceup
                 _ if i == 20 => true,
                 EAction::Pickup => true,
-                EAction::Clubs | EAction::Diamonds | EAction::Hearts | EAction::Spades => {
-                    first_play = Some(i + 1);
-                    true
-                }
+                EAction::Clubs | EAction::Diamonds | EAction::Hearts | EAction::Spades => true,
                 // discard action
-                _ if i > 20 && is_last_take => {
-                    first_play = Some(i + 1);
-                    true
-                }
-                EAction::Pass => false,
-                EAction::DiscardMarker => false,
-                // everything else is Play
-                _ => ((i - first_play.unwrap() + 1) % 4 == 0) && (i != first_play.unwrap()),
-                // _ => false,
+                _ if i > 20 && is_last_take => true,
+                _ => false,
             };
             if append_pipe {
-                write!(f, "|").unwrap();
+                write!(f, "|")?;
             }
 
-            if is_last_take {
-                is_last_take = false;
-            }
+            is_last_take = a == EAction::Pickup;
+        }
 
-            if a == EAction::Pickup {
-                is_last_take = true;
+        for (_, trick, _) in self.tricks() {
+            for c in trick {
+                write!(f, "{}", c)?;
             }
+            write!(f, "|")?;
         }
 
-        write!(f, "")
+        // the unfinished trick
+        for a in key.iter().skip(first_play + self.cards_played / 4 * 4) {
+            write!(f, "{}", EAction::from(*a))?;
+        }
+
+        Ok(())
     }
 }
 
@@ -1038,6 +1118,7 @@ mod tests {
 
     use crate::{
         actions,
+        fuzz::fuzz_game,
         gamestates::euchre::{actions::Card, deck::CARDS, EAction, EPhase, Euchre, Suit},
         resample::ResampleFromInfoState,
     };
@@ -1176,6 +1257,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn euchre_test_trick_winner_with() {
+        use Card::*;
+        // clubs are trump, Qd and Kd have been played
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|QdKd");
+        assert_eq!(gs.cur_player(), 2);
+        assert_eq!(gs.trick_winner_with(TD), 1);
+        assert_eq!(gs.trick_winner_with(AD), 2);
+
+        // leading always wins the trick so far
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|");
+        assert_eq!(gs.cur_player(), 0);
+        assert_eq!(gs.trick_winner_with(QD), 0);
+
+        // player 1 has no spades and can trump the lead
+        let gs = EuchreGameState::from("TcQs9hJdQd|QcThJhKhKd|AcTsAhTdAd|9cKc9sKsQh|Jc|T|Kc|Qs");
+        assert_eq!(gs.trick_winner_with(QC), 1);
+        assert_eq!(gs.trick_winner_with(KD), 0);
+    }
+
     #[test]
     fn euchre_test_suit() {
         let mut s = Euchre::new_state();
@@ -1324,6 +1425,112 @@ mod tests {
         }
     }
 
+    #[test]
+    fn euchre_test_resample_public() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        let mut actions = Vec::new();
+
+        for _ in 0..100 {
+            let mut s = Euchre::new_state();
+            while s.is_chance_node() {
+                s.legal_actions(&mut actions);
+                let a = actions.choose(&mut rng).unwrap();
+                s.apply_action(*a);
+            }
+
+            while !s.is_terminal() {
+                let sampled = s.resample_public(&mut rng);
+                let public = |gs: &EuchreGameState| {
+                    let key = gs.key();
+                    (
+                        gs.face_up(),
+                        gs.trump(),
+                        gs.phase(),
+                        gs.cur_player(),
+                        key[key.len() - gs.cards_played..].to_vec(),
+                    )
+                };
+                assert_eq!(public(&sampled), public(&s));
+                // the resampled hands must be able to make the same plays
+                let parsed = EuchreGameState::from(sampled.to_string().as_str());
+                assert_eq!(parsed.to_string(), sampled.to_string());
+                for p in 0..4 {
+                    assert_eq!(sampled.get_hand(p).len(), s.get_hand(p).len());
+                }
+
+                s.legal_actions(&mut actions);
+                let a = actions.choose(&mut rng).unwrap();
+                s.apply_action(*a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_euchre_score_hand() {
+        use super::{score_hand, LoneHand::*};
+
+        // team 0 makes trump
+        assert_eq!(score_hand(3, 2, 0, None), 1.0);
+        assert_eq!(score_hand(4, 1, 0, None), 1.0);
+        assert_eq!(score_hand(5, 0, 0, None), 2.0);
+        assert_eq!(score_hand(2, 3, 0, None), -2.0);
+        assert_eq!(score_hand(0, 5, 0, None), -2.0);
+
+        // team 1 makes trump
+        assert_eq!(score_hand(2, 3, 1, None), -1.0);
+        assert_eq!(score_hand(0, 5, 1, None), -2.0);
+        assert_eq!(score_hand(3, 2, 1, None), 2.0);
+        assert_eq!(score_hand(5, 0, 1, None), 2.0);
+
+        // lone hands
+        assert_eq!(score_hand(3, 2, 0, Maker), 1.0);
+        assert_eq!(score_hand(4, 1, 0, Maker), 1.0);
+        assert_eq!(score_hand(5, 0, 0, Maker), 4.0);
+        assert_eq!(score_hand(0, 5, 1, Maker), -4.0);
+        assert_eq!(score_hand(2, 3, 0, Maker), -2.0);
+        assert_eq!(score_hand(3, 2, 1, Maker), 2.0);
+
+        // defended lone hands
+        assert_eq!(score_hand(5, 0, 0, MakerAndDefender), 4.0);
+        assert_eq!(score_hand(3, 2, 0, MakerAndDefender), 1.0);
+        assert_eq!(score_hand(2, 3, 0, MakerAndDefender), -4.0);
+        assert_eq!(score_hand(3, 2, 1, MakerAndDefender), 4.0);
+
+        // early termination once the hand is decided
+        assert_eq!(score_hand(3, 1, 0, None), 1.0);
+        assert_eq!(score_hand(1, 3, 0, None), -2.0);
+    }
+
+    #[test]
+    fn test_tricks() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let mut gs = Euchre::new_state();
+            while !gs.is_terminal() {
+                let a = *actions!(gs).choose(&mut rng).unwrap();
+                gs.apply_action(a);
+
+                let tricks = gs.tricks().collect_vec();
+                assert_eq!(tricks.len(), gs.cards_played / 4);
+                assert_eq!(
+                    gs.last_trick(),
+                    tricks.last().map(|(leader, trick, _)| (*leader, *trick))
+                );
+
+                let mut won = [0u8; 2];
+                let mut leader = tricks.first().map(|(leader, _, _)| *leader);
+                for (l, trick, winner) in &tricks {
+                    // the winner of each trick leads the next
+                    assert_eq!(Some(*l), leader);
+                    assert_eq!(gs.evaluate_trick(trick, *l), *winner);
+                    won[winner % 2] += 1;
+                    leader = Some(*winner);
+                }
+                assert_eq!(won, gs.trick_score());
+            }
+        }
+    }
+
     #[test]
     fn test_undo_euchre() {
         let mut rng: StdRng = SeedableRng::seed_from_u64(0);
@@ -1356,4 +1563,10 @@ mod tests {
             )
         }
     }
+
+    #[test]
+    fn euchre_test_fuzz() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        fuzz_game(&Euchre::game(), 100, &mut rng);
+    }
 }
@@ -61,6 +61,11 @@ pub fn euchre_early_terminate(gs: &EuchreGameState) -> bool {
 }
 
 fn process_play_actions(gs: &EuchreGameState, actions: &mut Vec<Action>) {
+    // the order doesn't change the value, but good moves first lead to more
+    // alpha-beta cutoffs
+    evaluate_trump_first(gs, actions);
+    evaluate_winning_cards_first(gs, actions);
+
     // if have the highest trump, and it's a new trick, likely want to play that, evaluate it first
     evaluate_highest_trump_first(gs, actions);
 
@@ -130,6 +135,55 @@ fn evaluate_highest_trump_first(gs: &EuchreGameState, actions: &mut Vec<Action>)
     }
 }
 
+/// When leading, evaluate trump first from highest to lowest. The winner of the
+/// last trick leads, and drawing out trump tends to keep the lead.
+fn evaluate_trump_first(gs: &EuchreGameState, actions: &mut [Action]) {
+    if !gs.is_start_of_trick() {
+        return;
+    }
+
+    let trump = match gs.trump {
+        Some(t) => t,
+        None => return,
+    };
+    let trump_cards = get_cards(trump, gs.trump);
+    // sort is stable, so the off suit cards keep their order
+    actions.sort_by_key(|a| {
+        let c = EAction::from(*a).card();
+        trump_cards
+            .iter()
+            .position(|&x| x == c)
+            .map_or(1, |rank| -(rank as i32) - 1)
+    });
+}
+
+/// When following and the opponents are winning the trick, evaluate the cards
+/// that take the trick first
+fn evaluate_winning_cards_first(gs: &EuchreGameState, actions: &mut [Action]) {
+    let num_played = gs.cards_played % 4;
+    if num_played == 0 {
+        return;
+    }
+
+    let player = gs.cur_player();
+    let starter = (player + 4 - num_played) % 4;
+    let mut trick = gs.key[gs.key.len() - num_played..]
+        .iter()
+        .map(|&a| EAction::from(a).card())
+        .collect_vec();
+
+    if gs.evaluate_trick(&trick, starter) % 2 == player % 2 {
+        // partner is already winning
+        return;
+    }
+
+    trick.push(Card::NS);
+    actions.sort_by_key(|a| {
+        trick[num_played] = EAction::from(*a).card();
+        gs.evaluate_trick(&trick, starter) != player
+    });
+}
+
 fn evaluate_picked_up_card_last(gs: &EuchreGameState, actions: &mut Vec<Action>) {
     let face_up = gs
         .face_up()
@@ -190,7 +244,11 @@ mod tests {
 
     use crate::{
         gamestates::euchre::{
-            actions::EAction, processors::evaluate_highest_trump_first, EuchreGameState,
+            actions::EAction,
+            processors::{
+                evaluate_highest_trump_first, evaluate_trump_first, evaluate_winning_cards_first,
+            },
+            EuchreGameState,
         },
         GameState,
     };
@@ -228,6 +286,46 @@ mod tests {
         assert_eq!(actions, old_actions);
     }
 
+    #[test]
+    fn test_trump_first() {
+        // clubs are trump, so the left bower is the highest trump
+        let gs = EuchreGameState::from("KcTsJsQsAd|9cTcAcKsAs|ThKh9dJdKd|JcJhQhAhQd|Qc|PT|Ah|");
+        let mut actions = Vec::new();
+        gs.legal_actions(&mut actions);
+        evaluate_trump_first(&gs, &mut actions);
+        assert_eq!(
+            actions.into_iter().map(EAction::from).collect_vec(),
+            vec![
+                EAction::JS,
+                EAction::KC,
+                EAction::TS,
+                EAction::QS,
+                EAction::AD
+            ]
+        );
+    }
+
+    #[test]
+    fn test_winning_cards_first() {
+        let gs = EuchreGameState::from("KcTsJsQsAd|9cTcAcKsAs|ThKh9dJdKd|JcJhQhAhQd|Qc|PT|Ah|Kc");
+        let mut actions = Vec::new();
+        gs.legal_actions(&mut actions);
+        evaluate_winning_cards_first(&gs, &mut actions);
+        assert_eq!(
+            actions.into_iter().map(EAction::from).collect_vec(),
+            vec![EAction::AC, EAction::NC, EAction::TC]
+        );
+
+        // partner is winning with the ace, so nothing is re-ordered
+        let gs =
+            EuchreGameState::from("KcTsJsQsAd|9cTcAcKsAs|ThKh9dJdKd|JcJhQhAhQd|Qc|PT|Ah|TsAs9d");
+        let mut actions = Vec::new();
+        gs.legal_actions(&mut actions);
+        let old_actions = actions.clone();
+        evaluate_winning_cards_first(&gs, &mut actions);
+        assert_eq!(actions, old_actions);
+    }
+
     #[test]
     fn test_remove_equivalent_cards() {
         // shouldn't remove any cards
@@ -8,7 +8,11 @@ use crate::{
     Action, GameState, Player,
 };
 
-use super::{actions::Card, deck::Hand, EuchreGameState};
+use super::{
+    actions::Card,
+    deck::{CardLocation, Hand},
+    EuchreGameState,
+};
 
 /// Resample from info state method for euchre
 ///
@@ -16,6 +20,21 @@ use super::{actions::Card, deck::Hand, EuchreGameState};
 /// It's not yet clear what impact this has on the results of downstream algorithms
 impl ResampleFromInfoState for EuchreGameState {
     fn resample_from_istate<T: rand::Rng>(&self, player: Player, rng: &mut T) -> Self {
+        self.resample(Some(player), rng)
+    }
+}
+
+impl EuchreGameState {
+    /// Resample every card that isn't public, as seen by someone not playing in the game
+    ///
+    /// Only the face up card and the played cards are kept, all hands and the
+    /// dealer's discard are resampled.
+    pub fn resample_public<T: rand::Rng>(&self, rng: &mut T) -> Self {
+        self.resample(None, rng)
+    }
+
+    /// Resample the cards hidden from `player`, or from everyone if `None`
+    fn resample<T: rand::Rng>(&self, player: Option<Player>, rng: &mut T) -> Self {
         if self.phase() == EPhase::DealHands || self.phase() == EPhase::DealFaceUp {
             panic!("don't yet support resampling of deal phase gamestates")
         }
@@ -46,10 +65,14 @@ impl ResampleFromInfoState for EuchreGameState {
             .zip(self.play_order.iter())
             .take(20)
             .map(|(a, p)| (EAction::from(*a).card(), p))
-            .filter(|(_, p)| **p == player)
-            .for_each(|(c, _)| known_cards[player].add(c));
+            .filter(|(_, p)| Some(**p) == player)
+            .for_each(|(c, p)| known_cards[*p].add(c));
 
         // Remove a suit from allowed cards if player didn't previously follow suit
+        //
+        // The dealer's voids are kept separately, the face up card they picked
+        // up may be in one and need to be the card they discarded
+        let mut dealer_voids = Hand::default();
         let offset = key.len() - self.cards_played;
         for t in 0..5 {
             let trick_start = offset + t * 4;
@@ -61,14 +84,18 @@ impl ResampleFromInfoState for EuchreGameState {
             let lead_player = self.play_order[trick_start];
             let lead_suit = self.get_suit(lead.unwrap());
 
-            for i in 1..3 {
+            for i in 1..4 {
                 if let Some(played_card) =
                     key.get(trick_start + i).map(|x| EAction::from(*x).card())
                 {
                     let played_suit = self.get_suit(played_card);
                     if played_suit != lead_suit {
                         let suit_cards = suit_mask(lead_suit, self.trump);
-                        allowed_cards[(lead_player + i) % 4].remove_all(suit_cards);
+                        let p = (lead_player + i) % 4;
+                        allowed_cards[p].remove_all(suit_cards);
+                        if p == 3 {
+                            dealer_voids.add_all(suit_cards);
+                        }
                     }
                 }
             }
@@ -95,11 +122,27 @@ impl ResampleFromInfoState for EuchreGameState {
             allowed_cards
         );
 
+        // if the dealer didn't follow the face up card's suit and hasn't played
+        // it, they must have discarded it, so can't have been dealt another card
+        // to discard
+        let face_up_played = key[offset..]
+            .iter()
+            .any(|a| EAction::from(*a).card() == face_up);
+        let dealer_discards = self.key[21..]
+            .iter()
+            .any(|a| EAction::from(*a) == EAction::Pickup)
+            && (face_up_played || !dealer_voids.contains(face_up));
+        let constraints = DealConstraints {
+            known: known_cards,
+            allowed: allowed_cards,
+            face_up,
+            dealer_discards,
+        };
         let mut ngs = Euchre::new_state();
         let mut pool = Pool::new(Vec::new);
         assert!(
-            search_for_deal(&mut ngs, known_cards, allowed_cards, face_up, 0, rng, &mut pool),
-            "Failed to find a valid deal for resample of {} for {}\nknown cards: {:?}\nallowed cards: {:?}",
+            search_for_deal(&mut ngs, &constraints, 0, rng, &mut pool),
+            "Failed to find a valid deal for resample of {} for {:?}\nknown cards: {:?}\nallowed cards: {:?}",
             self,
             player, known_cards, allowed_cards
         );
@@ -117,7 +160,7 @@ impl ResampleFromInfoState for EuchreGameState {
             //
             // If it's not a discard, we apply the actions in the order we saw them.
             // discard is the only private action after deal phase
-            if is_last_pickup && player != 3 {
+            if is_last_pickup && player != Some(3) {
                 assert_eq!(ngs.cur_player(), 3);
 
                 let played_cards = self
@@ -129,6 +172,10 @@ impl ResampleFromInfoState for EuchreGameState {
 
                 ngs.legal_actions(&mut actions);
                 actions.shuffle(rng);
+                // the dealer may hold a card from a suit they later didn't follow,
+                // either the face up card or one dealt to make the deal work,
+                // discard it first
+                actions.sort_by_key(|x| !dealer_voids.contains(EAction::from(*x).card()));
                 for da in actions.iter().map(|x| EAction::from(*x)) {
                     let card = da.card();
                     if !played_cards.contains(&card) {
@@ -150,17 +197,28 @@ impl ResampleFromInfoState for EuchreGameState {
     }
 }
 
-/// Searches the game tree for a deal that meets all constraints
-fn search_for_deal<T: rand::Rng>(
-    gs: &mut EuchreGameState,
+/// What's known about the deal from the point of view being resampled
+struct DealConstraints {
+    /// Cards each player must have been dealt
     known: [Hand; 4],
+    /// Other cards each player could have been dealt
     allowed: [Hand; 4],
     face_up: Card,
+    /// The dealer picked up the face up card, so they can be dealt one card
+    /// that breaks the constraints, which they then discarded
+    dealer_discards: bool,
+}
+
+/// Searches the game tree for a deal that meets all constraints
+fn search_for_deal<T: rand::Rng>(
+    gs: &mut EuchreGameState,
+    constraints: &DealConstraints,
     depth: usize,
     rng: &mut T,
     pool: &mut Pool<Vec<Action>>,
 ) -> bool {
-    if !meets_constraints(gs, known, allowed) {
+    let DealConstraints { known, allowed, .. } = *constraints;
+    if !meets_constraints(gs, known, allowed) || !can_complete_deal(gs, constraints) {
         return false;
     }
 
@@ -191,11 +249,11 @@ fn search_for_deal<T: rand::Rng>(
     // they will ultimately discard
     //
     // We can give them any remaining card and skip constraint checking
-    if actions.is_empty() && depth == 19 {
+    if actions.is_empty() && depth == 19 && constraints.dealer_discards {
         assert_eq!(gs.cur_player, 3);
         gs.legal_actions(&mut actions);
         // don't deal the faceup card
-        actions.retain(|x| EAction::from(*x).card() != face_up);
+        actions.retain(|x| EAction::from(*x).card() != constraints.face_up);
         let a = actions.choose(rng).unwrap();
         gs.apply_action(*a);
         pool.attach(actions);
@@ -204,7 +262,7 @@ fn search_for_deal<T: rand::Rng>(
 
     for a in actions.iter() {
         gs.apply_action(*a);
-        if !search_for_deal(gs, known, allowed, face_up, depth + 1, rng, pool) {
+        if !search_for_deal(gs, constraints, depth + 1, rng, pool) {
             gs.undo()
         } else {
             pool.attach(actions);
@@ -235,3 +293,79 @@ fn meets_constraints(gs: &EuchreGameState, known: [Hand; 4], allowed: [Hand; 4])
 
     true
 }
+
+/// Checks the undealt cards can still be split between the players and the
+/// kitty without breaking a constraint
+///
+/// Without this the search can spend a very long time on deals that were
+/// ruled out by one of the first cards dealt.
+fn can_complete_deal(gs: &EuchreGameState, constraints: &DealConstraints) -> bool {
+    let mut undealt = gs.deck.get_all(CardLocation::None);
+    undealt.remove(constraints.face_up);
+    let cards = undealt.cards();
+
+    let mut unknown = Hand::all_cards();
+    constraints
+        .known
+        .iter()
+        .for_each(|k| unknown.remove_all(*k));
+
+    let mut slots = Vec::with_capacity(cards.len());
+    for p in 0..4 {
+        let mut accepts = constraints.known[p];
+        accepts.add_all(constraints.allowed[p]);
+        let remaining = 5 - gs.deck.get_all(p.into()).len();
+        slots.resize(slots.len() + remaining, accepts);
+    }
+    // the dealer's slots are the last player slots
+    let dealer_has_slot = gs.deck.get_all(3.into()).len() < 5;
+    let dealer_slots_end = slots.len();
+    // the rest go to the kitty
+    slots.resize(cards.len(), unknown);
+
+    if can_assign(&cards, &slots) {
+        return true;
+    }
+
+    // the dealer's discard can be any card, like one in the kitty
+    if constraints.dealer_discards && dealer_has_slot {
+        slots[dealer_slots_end - 1] = unknown;
+        return can_assign(&cards, &slots);
+    }
+    false
+}
+
+/// Whether every card can be put in its own slot, where each slot only
+/// accepts some cards
+fn can_assign(cards: &[Card], slots: &[Hand]) -> bool {
+    fn assign(
+        card: usize,
+        cards: &[Card],
+        slots: &[Hand],
+        assigned: &mut [Option<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for (s, accepts) in slots.iter().enumerate() {
+            if visited[s] || !accepts.contains(cards[card]) {
+                continue;
+            }
+            visited[s] = true;
+
+            let free = match assigned[s] {
+                Some(other) => assign(other, cards, slots, assigned, visited),
+                None => true,
+            };
+            if free {
+                assigned[s] = Some(card);
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut assigned = vec![None; slots.len()];
+    (0..cards.len()).all(|c| {
+        let mut visited = vec![false; slots.len()];
+        assign(c, cards, slots, &mut assigned, &mut visited)
+    })
+}
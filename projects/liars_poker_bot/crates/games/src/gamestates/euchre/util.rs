@@ -1,4 +1,4 @@
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
 
 use crate::{
     gamestates::euchre::{actions::EAction, EPhase, Euchre},
@@ -9,14 +9,17 @@ use super::{actions::Card, EuchreGameState};
 
 /// Generator for games with arbitrary face up cards
 pub fn generate_face_up_deals(face_up: Card) -> EuchreGameState {
+    generate_face_up_deals_with_rng(face_up, &mut thread_rng())
+}
+
+/// Same as [`generate_face_up_deals`] with the deal drawn from `rng`
+pub fn generate_face_up_deals_with_rng(face_up: Card, rng: &mut impl Rng) -> EuchreGameState {
     let mut gs = Euchre::new_state();
     let mut actions = Vec::new();
     for _ in 0..20 {
         gs.legal_actions(&mut actions);
         actions.retain(|&a| EAction::from(a).card() != face_up);
-        let a = actions
-            .choose(&mut thread_rng())
-            .expect("error dealing cards");
+        let a = actions.choose(rng).expect("error dealing cards");
         gs.apply_action(*a);
         actions.clear();
     }
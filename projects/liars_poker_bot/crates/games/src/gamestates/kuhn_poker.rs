@@ -317,6 +317,7 @@ mod tests {
 
     use crate::{
         actions,
+        fuzz::fuzz_game,
         gamestates::kuhn_poker::{KPAction, KuhnPoker},
         GameState,
     };
@@ -375,4 +376,10 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn kuhn_poker_fuzz() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        fuzz_game(&KuhnPoker::game(), 1000, &mut rng);
+    }
 }
@@ -1,4 +1,5 @@
 pub mod bluff;
 pub mod euchre;
 pub mod kuhn_poker;
+pub mod tictactoe;
 pub mod updownriver;
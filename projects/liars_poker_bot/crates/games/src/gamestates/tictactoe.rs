@@ -0,0 +1,301 @@
+//! TicTacToe, the smallest complete [`GameState`] in the crate
+//!
+//! This is meant as the reference for adding a new game. It implements every
+//! method of the trait, including the optional transposition hash, and its
+//! tests run the checks every game should pass:
+//! * [`fuzz_game`](crate::fuzz::fuzz_game) for undo and action ordering
+//! * [`IStateIterator`](crate::iterator::IStateIterator) over the full tree
+//! * exploitability in `card_platypus`, which walks every state of the game
+//!
+//! The game has no chance nodes and no hidden information, so a player's
+//! istate is just the board. Actions are the index of the cell to mark:
+//! ```text
+//! 0|1|2
+//! 3|4|5
+//! 6|7|8
+//! ```
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::{Display, Write},
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    istate::{IStateKey, IsomorphicHash},
+    Action, Game, GameState, Player,
+};
+
+const NUM_CELLS: usize = 9;
+
+const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+    [0, 4, 8],
+    [2, 4, 6],
+];
+
+/// Contents of a cell, the discriminant is what's stored in the istate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Cell {
+    Empty,
+    X,
+    O,
+}
+
+impl Cell {
+    /// Player 0 plays X
+    fn for_player(p: Player) -> Self {
+        match p {
+            0 => Cell::X,
+            1 => Cell::O,
+            _ => panic!("invalid player: {}", p),
+        }
+    }
+}
+
+impl From<Cell> for Action {
+    fn from(value: Cell) -> Self {
+        Action(value as u8)
+    }
+}
+
+pub struct TicTacToe {}
+impl TicTacToe {
+    pub fn new_state() -> TTTGameState {
+        TTTGameState {
+            key: IStateKey::default(),
+            board: [Cell::Empty; NUM_CELLS],
+        }
+    }
+
+    pub fn game() -> Game<TTTGameState> {
+        Game {
+            new: Box::new(|| -> TTTGameState { Self::new_state() }),
+            max_players: 2,
+            max_actions: NUM_CELLS,
+        }
+    }
+
+    pub fn from_actions(actions: &[usize]) -> TTTGameState {
+        let mut gs = Self::new_state();
+        for &a in actions {
+            gs.apply_action(Action(a as u8));
+        }
+        gs
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug, Hash)]
+pub struct TTTGameState {
+    /// Cells marked so far, in order
+    key: IStateKey,
+    /// Derived from `key`, kept so checking for a winner doesn't replay the game
+    board: [Cell; NUM_CELLS],
+}
+
+impl TTTGameState {
+    pub fn cell(&self, i: usize) -> Cell {
+        self.board[i]
+    }
+
+    /// The player with three in a row, if any
+    pub fn winner(&self) -> Option<Player> {
+        LINES.iter().find_map(|l| match l.map(|i| self.board[i]) {
+            [Cell::X, Cell::X, Cell::X] => Some(0),
+            [Cell::O, Cell::O, Cell::O] => Some(1),
+            _ => None,
+        })
+    }
+}
+
+impl Display for TTTGameState {
+    /// Rows separated by `|`, e.g. `XO.|.X.|..O`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, c) in self.board.iter().enumerate() {
+            if i > 0 && i % 3 == 0 {
+                f.write_char('|')?;
+            }
+            f.write_char(match c {
+                Cell::Empty => '.',
+                Cell::X => 'X',
+                Cell::O => 'O',
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl GameState for TTTGameState {
+    fn apply_action(&mut self, a: Action) {
+        let i = a.0 as usize;
+        assert!(
+            i < NUM_CELLS && self.board[i] == Cell::Empty,
+            "invalid action {} for {}",
+            a,
+            self
+        );
+        self.board[i] = Cell::for_player(self.cur_player());
+        self.key.push(a);
+    }
+
+    /// Empty cells in ascending order
+    fn legal_actions(&self, actions: &mut Vec<Action>) {
+        actions.clear();
+
+        if self.is_terminal() {
+            return;
+        }
+
+        for (i, c) in self.board.iter().enumerate() {
+            if *c == Cell::Empty {
+                actions.push(Action(i as u8));
+            }
+        }
+    }
+
+    /// 1 for a win, -1 for a loss and 0 for a draw
+    fn evaluate(&self, p: Player) -> f64 {
+        if !self.is_terminal() {
+            panic!("evaluate called on non-terminal gamestate");
+        }
+
+        match self.winner() {
+            Some(w) if w == p => 1.0,
+            Some(_) => -1.0,
+            None => 0.0,
+        }
+    }
+
+    /// The contents of each cell, both players see the same istate
+    ///
+    /// Different move orders reaching the same board share an istate, so
+    /// algorithms only learn each position once.
+    fn istate_key(&self, _: Player) -> IStateKey {
+        let mut istate = IStateKey::default();
+        for c in self.board {
+            istate.push(c.into());
+        }
+        istate
+    }
+
+    fn istate_string(&self, _: Player) -> String {
+        self.to_string()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.key.len() == NUM_CELLS || self.winner().is_some()
+    }
+
+    fn is_chance_node(&self) -> bool {
+        false
+    }
+
+    fn num_players(&self) -> usize {
+        2
+    }
+
+    fn cur_player(&self) -> Player {
+        self.key.len() % 2
+    }
+
+    /// The cells in the order they were marked
+    fn key(&self) -> IStateKey {
+        self.key
+    }
+
+    /// Only the board matters for the rest of the game, not the move order
+    fn transposition_table_hash(&self) -> Option<IsomorphicHash> {
+        let mut hasher = DefaultHasher::default();
+        self.board.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    fn undo(&mut self) {
+        let a = self.key.pop();
+        self.board[a.0 as usize] = Cell::Empty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use itertools::Itertools;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{actions, fuzz::fuzz_game, iterator::IStateIterator, Action, GameState};
+
+    use super::TicTacToe;
+
+    #[test]
+    fn tictactoe_test_win() {
+        // X takes the top row while O plays the middle
+        let gs = TicTacToe::from_actions(&[0, 4, 1, 5, 2]);
+        assert_eq!(gs.to_string(), "XXX|.OO|...");
+        assert!(gs.is_terminal());
+        assert_eq!(gs.winner(), Some(0));
+        assert_eq!(gs.evaluate(0), 1.0);
+        assert_eq!(gs.evaluate(1), -1.0);
+        assert_eq!(actions!(gs), vec![]);
+    }
+
+    #[test]
+    fn tictactoe_test_draw() {
+        let gs = TicTacToe::from_actions(&[0, 4, 8, 1, 7, 6, 2, 5, 3]);
+        assert_eq!(gs.to_string(), "XOX|XOO|OXX");
+        assert!(gs.is_terminal());
+        assert_eq!(gs.winner(), None);
+        assert_eq!(gs.evaluate(0), 0.0);
+        assert_eq!(gs.evaluate(1), 0.0);
+    }
+
+    #[test]
+    fn tictactoe_test_transpositions() {
+        let a = TicTacToe::from_actions(&[0, 4, 8]);
+        let b = TicTacToe::from_actions(&[8, 4, 0]);
+
+        assert_ne!(a.key(), b.key());
+        assert_eq!(a.istate_key(0), b.istate_key(0));
+        assert_eq!(a.istate_key(1), b.istate_key(1));
+        assert_eq!(a.transposition_table_hash(), b.transposition_table_hash());
+        assert_eq!(a.cur_player(), 1);
+        assert_eq!(
+            actions!(a),
+            [1, 2, 3, 5, 6, 7].map(Action).to_vec(),
+            "legal actions should be the empty cells"
+        );
+    }
+
+    #[test]
+    fn tictactoe_test_undo() {
+        let mut gs = TicTacToe::from_actions(&[0, 4, 1]);
+        let before = gs.clone();
+        gs.apply_action(Action(2));
+        gs.undo();
+        assert_eq!(gs, before);
+        assert_eq!(gs.to_string(), "XX.|.O.|...");
+    }
+
+    #[test]
+    fn tictactoe_fuzz() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        fuzz_game(&TicTacToe::game(), 1000, &mut rng);
+    }
+
+    #[test]
+    fn tictactoe_test_iterator() {
+        let istates = IStateIterator::new(TicTacToe::new_state()).collect_vec();
+
+        // 5478 legal boards, less the 958 that end the game and the empty
+        // board, which the iterator doesn't visit
+        assert_eq!(istates.len(), 4519);
+        assert_eq!(istates.iter().collect::<HashSet<_>>().len(), 4519);
+    }
+}
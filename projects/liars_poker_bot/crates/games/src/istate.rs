@@ -196,6 +196,124 @@ impl Iterator for IStateKeyIterator {
     }
 }
 
+/// Bits stored for each action of a [`PackedIStateKey`], enough for the 32
+/// distinct euchre actions
+const PACKED_ACTION_BITS: usize = 5;
+const PACKED_ACTION_MASK: u64 = (1 << PACKED_ACTION_BITS) - 1;
+/// The length is kept in the bits after the last action
+const PACKED_LEN_SHIFT: usize = PACKED_KEY_CAPACITY * PACKED_ACTION_BITS % 64;
+/// Most actions a [`PackedIStateKey`] can hold, longer than any euchre istate
+pub const PACKED_KEY_CAPACITY: usize = 48;
+
+/// An [`IStateKey`] with 5 bits per action instead of a full byte, for
+/// storing large numbers of keys
+///
+/// The key is 32 bytes rather than the 72 of an [`IStateKey`]. Hashing
+/// matches the unpacked key, so a phf built from either type indexes the same
+/// keys and saved indexers stay valid.
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PackedIStateKey {
+    words: [u64; 4],
+}
+
+unsafe impl bytemuck::Pod for PackedIStateKey {}
+unsafe impl bytemuck::Zeroable for PackedIStateKey {}
+
+impl PackedIStateKey {
+    pub fn len(&self) -> usize {
+        (self.words[3] >> PACKED_LEN_SHIFT) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the action at `index`
+    pub fn get(&self, index: usize) -> Action {
+        assert!(index < self.len());
+        let bit = index * PACKED_ACTION_BITS;
+        let (word, shift) = (bit / 64, bit % 64);
+        let mut v = self.words[word] >> shift;
+        // the action is split across two words
+        if shift + PACKED_ACTION_BITS > 64 {
+            v |= self.words[word + 1] << (64 - shift);
+        }
+        Action((v & PACKED_ACTION_MASK) as u8)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Action> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    fn push(&mut self, a: Action) {
+        let len = self.len();
+        assert!(
+            len < PACKED_KEY_CAPACITY,
+            "packed keys hold at most {PACKED_KEY_CAPACITY} actions"
+        );
+        assert!(
+            (a.0 as u64) <= PACKED_ACTION_MASK,
+            "action {a:?} doesn't fit in {PACKED_ACTION_BITS} bits"
+        );
+
+        let bit = len * PACKED_ACTION_BITS;
+        let (word, shift) = (bit / 64, bit % 64);
+        self.words[word] |= (a.0 as u64) << shift;
+        if shift + PACKED_ACTION_BITS > 64 {
+            self.words[word + 1] |= (a.0 as u64) >> (64 - shift);
+        }
+
+        self.words[3] &= !(u64::MAX << PACKED_LEN_SHIFT);
+        self.words[3] |= ((len + 1) as u64) << PACKED_LEN_SHIFT;
+    }
+}
+
+/// Panics if the key is longer than [`PACKED_KEY_CAPACITY`] or has an action
+/// above 31
+impl From<&IStateKey> for PackedIStateKey {
+    fn from(value: &IStateKey) -> Self {
+        let mut key = PackedIStateKey::default();
+        for a in value.iter() {
+            key.push(*a);
+        }
+        key
+    }
+}
+
+impl From<IStateKey> for PackedIStateKey {
+    fn from(value: IStateKey) -> Self {
+        PackedIStateKey::from(&value)
+    }
+}
+
+impl From<PackedIStateKey> for IStateKey {
+    fn from(value: PackedIStateKey) -> Self {
+        let mut key = IStateKey::default();
+        for a in value.iter() {
+            key.push(a);
+        }
+        key
+    }
+}
+
+impl Hash for PackedIStateKey {
+    /// Feeds the hasher the same values as [`IStateKey`]: the length, then
+    /// the length prefix and actions of the slice
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        self.len().hash(state);
+        for a in self.iter() {
+            a.hash(state);
+        }
+    }
+}
+
+impl Debug for PackedIStateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
 /// A key representing the state of the game (with perfect information). Used for transposition table lookups
 pub type IsomorphicHash = u64;
 
@@ -266,4 +384,47 @@ macro_rules! translate_istate {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::Action;
+
+    use super::{IStateKey, PackedIStateKey, PACKED_KEY_CAPACITY};
+
+    fn hash(x: impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_packed_istate_key() {
+        assert_eq!(std::mem::size_of::<PackedIStateKey>(), 32);
+
+        let mut rng: StdRng = SeedableRng::seed_from_u64(42);
+        for len in 0..=PACKED_KEY_CAPACITY {
+            let mut key = IStateKey::default();
+            (0..len).for_each(|_| key.push(Action(rng.gen_range(0..32))));
+
+            let packed = PackedIStateKey::from(&key);
+            assert_eq!(packed.len(), len);
+            assert_eq!(IStateKey::from(packed), key);
+            assert_eq!(hash(packed), hash(key));
+        }
+
+        // the largest action in every position, including those split across words
+        let key = IStateKey::from(&[31u8; PACKED_KEY_CAPACITY][..]);
+        assert_eq!(IStateKey::from(PackedIStateKey::from(&key)), key);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_packed_istate_key_action_too_large() {
+        let _ = PackedIStateKey::from(&IStateKey::from(&[32u8][..]));
+    }
+}
@@ -0,0 +1,124 @@
+//! Human readable labels for actions in logs
+//!
+//! Outside of the game that created them, [`Action`]s are opaque numbers. A
+//! game can register a labeler for the current thread so logs show e.g.
+//! `Js (card)` rather than `14`, without each call site knowing the game's
+//! action type. Without a registered labeler, actions are shown as numbers.
+
+use std::{cell::RefCell, collections::HashMap, fmt::Display};
+
+use crate::Action;
+
+pub type ActionLabeler = fn(Action) -> String;
+
+thread_local! {
+    static LABELERS: RefCell<HashMap<&'static str, ActionLabeler>> = RefCell::new(HashMap::new());
+}
+
+/// Labels `game`'s actions with `labeler` in logs written from this thread,
+/// replacing any labeler already registered for the game
+pub fn register_action_labeler(game: &'static str, labeler: ActionLabeler) {
+    LABELERS.with(|l| l.borrow_mut().insert(game, labeler));
+}
+
+/// Goes back to showing `game`'s actions as numbers on this thread
+pub fn unregister_action_labeler(game: &'static str) {
+    LABELERS.with(|l| l.borrow_mut().remove(game));
+}
+
+/// Returns the label for `a`, or its number if no labeler is registered for
+/// `game` on this thread
+pub fn action_label(game: &str, a: Action) -> String {
+    match LABELERS.with(|l| l.borrow().get(game).copied()) {
+        Some(labeler) => labeler(a),
+        None => a.to_string(),
+    }
+}
+
+/// Displays an action with its game's labeler, the label is only built if the
+/// action is formatted so it's cheap to pass to disabled log levels
+#[derive(Clone, Copy)]
+pub struct LabeledAction<'a> {
+    pub game: &'a str,
+    pub action: Action,
+}
+
+impl Display for LabeledAction<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&action_label(self.game, self.action))
+    }
+}
+
+/// Displays a list of actions with their game's labeler, e.g. `[Js (card), P (pass)]`
+#[derive(Clone, Copy)]
+pub struct LabeledActions<'a> {
+    pub game: &'a str,
+    pub actions: &'a [Action],
+}
+
+impl Display for LabeledActions<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[")?;
+        for (i, a) in self.actions.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(&action_label(self.game, *a))?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// Wraps `a` to be displayed with `game`'s labeler, for log messages
+///
+/// ```
+/// use games::{labels::labeled, Action};
+///
+/// log::debug!("bot played {}", labeled("euchre", Action(14)));
+/// ```
+pub fn labeled(game: &str, a: Action) -> LabeledAction<'_> {
+    LabeledAction { game, action: a }
+}
+
+/// Wraps `actions` to be displayed with `game`'s labeler, see [`labeled`]
+pub fn labeled_all<'a>(game: &'a str, actions: &'a [Action]) -> LabeledActions<'a> {
+    LabeledActions { game, actions }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gamestates::euchre::{actions::EAction, Euchre},
+        Action,
+    };
+
+    use super::{
+        action_label, labeled, labeled_all, register_action_labeler, unregister_action_labeler,
+    };
+
+    #[test]
+    fn test_action_labels() {
+        let a: Action = EAction::JS.into();
+        assert_eq!(labeled(Euchre::NAME, a).to_string(), a.0.to_string());
+
+        Euchre::register_action_labeler();
+        assert_eq!(labeled(Euchre::NAME, a).to_string(), "Js (card)");
+        assert_eq!(
+            labeled_all(Euchre::NAME, &[a, EAction::Pass.into()]).to_string(),
+            "[Js (card), P (pass)]"
+        );
+        // other games are unaffected
+        assert_eq!(action_label("kuhn_poker", a), a.0.to_string());
+
+        // labelers are per thread
+        let other_thread = std::thread::spawn(move || action_label(Euchre::NAME, a))
+            .join()
+            .unwrap();
+        assert_eq!(other_thread, a.0.to_string());
+
+        register_action_labeler(Euchre::NAME, |a| format!("action {}", a.0));
+        assert_eq!(action_label(Euchre::NAME, a), format!("action {}", a.0));
+        unregister_action_labeler(Euchre::NAME);
+        assert_eq!(action_label(Euchre::NAME, a), a.0.to_string());
+    }
+}
@@ -5,9 +5,11 @@ use std::{
 };
 
 pub mod cards;
+pub mod fuzz;
 pub mod gamestates;
 pub mod istate;
 pub mod iterator;
+pub mod labels;
 pub mod pool;
 pub mod resample;
 
@@ -132,6 +134,7 @@ mod tests {
             bluff::Bluff,
             euchre::{actions::EAction, Euchre},
             kuhn_poker::KuhnPoker,
+            tictactoe::TicTacToe,
         },
         Game, GameState,
     };
@@ -141,6 +144,7 @@ mod tests {
         _test_actions_sorted(Euchre::game());
         _test_actions_sorted(Bluff::game(2, 2));
         _test_actions_sorted(KuhnPoker::game());
+        _test_actions_sorted(TicTacToe::game());
     }
 
     /// Helper function to ensure games always return actions in a sorted order.
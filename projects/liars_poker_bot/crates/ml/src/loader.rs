@@ -0,0 +1,320 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{sync_channel, Receiver},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{bail, Context};
+use sha2::{Digest, Sha256};
+
+/// Bytes read at a time while a file is hashed
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Lists the shards in a directory with their checksums, in the format written
+/// by `sha256sum shard-* > SHA256SUMS`
+pub const MANIFEST: &str = "SHA256SUMS";
+
+/// Where the self-play trajectory shards are generated
+///
+/// Shards are read in the order of the directory's [`MANIFEST`], which is read
+/// once when a [`ShardLoader`] starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Remote {
+    /// Read with `ssh {host} cat`, keys come from the user's ssh config
+    Ssh { host: String, dir: String },
+    /// Read with a GET of `{base_url}/{name}`, e.g. a directory served by nginx
+    Http { base_url: String },
+    /// A directory on this machine, e.g. a mounted share
+    Local { dir: PathBuf },
+}
+
+impl Remote {
+    /// Parses `http(s)://host/dir`, `host:dir` as for scp, or a local path
+    pub fn parse(s: &str) -> Remote {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Remote::Http {
+                base_url: s.trim_end_matches('/').to_string(),
+            };
+        }
+
+        match s.split_once(':') {
+            Some((host, dir)) if !host.is_empty() && !host.contains('/') => Remote::Ssh {
+                host: host.to_string(),
+                dir: dir.trim_end_matches('/').to_string(),
+            },
+            _ => Remote::Local { dir: s.into() },
+        }
+    }
+
+    /// Reads a whole file from the shard directory as it arrives, returning
+    /// its contents and lowercase hex sha256
+    fn fetch(&self, name: &str) -> anyhow::Result<(Vec<u8>, String)> {
+        match self {
+            Remote::Ssh { host, dir } => {
+                let path = if dir.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{}", dir, name)
+                };
+                // the path is run by the remote shell, names are checked when
+                // the manifest is parsed
+                let mut child = Command::new("ssh")
+                    .args(["-o", "BatchMode=yes", host, "cat", "--", &path])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("failed to run ssh to {}", host))?;
+                let read = read_hashed(child.stdout.take().unwrap());
+                let status = child.wait()?;
+                if !status.success() {
+                    let mut stderr = String::new();
+                    child.stderr.take().unwrap().read_to_string(&mut stderr)?;
+                    bail!("ssh {} cat {} failed: {}", host, path, stderr.trim());
+                }
+                Ok(read?)
+            }
+            Remote::Http { base_url } => {
+                let url = format!("{}/{}", base_url, name);
+                let response = ureq::get(&url)
+                    .call()
+                    .with_context(|| format!("failed to get {}", url))?;
+                Ok(read_hashed(response.into_reader())?)
+            }
+            Remote::Local { dir } => {
+                let path = dir.join(name);
+                let f = File::open(&path).with_context(|| format!("failed to read {:?}", path))?;
+                Ok(read_hashed(f)?)
+            }
+        }
+    }
+}
+
+/// Reads to the end in chunks, hashing each as it's read rather than going
+/// over the whole file again afterwards
+fn read_hashed(mut reader: impl Read) -> std::io::Result<(Vec<u8>, String)> {
+    let mut data = Vec::new();
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&chunk[..n]);
+        data.extend_from_slice(&chunk[..n]);
+    }
+    Ok((data, format!("{:x}", hasher.finalize())))
+}
+
+/// A shard listed in the [`MANIFEST`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardEntry {
+    pub name: String,
+    /// Lowercase hex sha256 of the shard's contents
+    pub sha256: String,
+}
+
+/// Parses the lines of a [`MANIFEST`], skipping blank ones
+///
+/// Names must be plain file names in the shard directory, since they end up in
+/// a remote shell command for [`Remote::Ssh`].
+pub fn parse_manifest(text: &str) -> anyhow::Result<Vec<ShardEntry>> {
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some((sha256, name)) = line.split_once(' ') else {
+            bail!("manifest line {} has no file name: {}", i + 1, line);
+        };
+        // sha256sum marks files read in binary mode with a '*'
+        let name = name.trim_start_matches(' ').trim_start_matches('*');
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("manifest line {} has an invalid sha256: {}", i + 1, line);
+        }
+        let plain = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if name.is_empty() || name.starts_with(['.', '-']) || !plain {
+            bail!("manifest line {} has an invalid file name: {}", i + 1, line);
+        }
+
+        entries.push(ShardEntry {
+            name: name.to_string(),
+            sha256: sha256.to_ascii_lowercase(),
+        });
+    }
+    Ok(entries)
+}
+
+/// The contents of a shard whose checksum matched the manifest
+#[derive(Debug, Clone)]
+pub struct Shard {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Fetches a shard, retrying if the download fails or the checksum doesn't
+/// match, e.g. because the shard was still being written
+fn fetch_shard(remote: &Remote, entry: &ShardEntry, retries: usize) -> anyhow::Result<Shard> {
+    let mut last_error = None;
+    for _ in 0..=retries {
+        match remote.fetch(&entry.name) {
+            Ok((data, sha256)) => {
+                if sha256 == entry.sha256 {
+                    return Ok(Shard {
+                        name: entry.name.clone(),
+                        data,
+                    });
+                }
+                last_error = Some(anyhow::anyhow!(
+                    "checksum mismatch for {}: expected {}, got {}",
+                    entry.name,
+                    entry.sha256,
+                    sha256
+                ));
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap())
+}
+
+/// Streams shards from a [`Remote`] in manifest order, fetching the next few
+/// on a background thread while the current one is trained on
+///
+/// A shard that still fails after the retries is returned as an error and ends
+/// the stream.
+pub struct ShardLoader {
+    shards: Receiver<anyhow::Result<Shard>>,
+    len: usize,
+    _fetcher: JoinHandle<()>,
+}
+
+impl ShardLoader {
+    /// Reads the manifest and starts fetching, at most `prefetch` shards are
+    /// held before they're consumed
+    pub fn new(remote: Remote, prefetch: usize, retries: usize) -> anyhow::Result<Self> {
+        let (manifest, _) = remote
+            .fetch(MANIFEST)
+            .context("failed to read the manifest")?;
+        let entries = parse_manifest(&String::from_utf8(manifest)?)?;
+        let len = entries.len();
+
+        let (tx, rx) = sync_channel(prefetch);
+        let fetcher = thread::spawn(move || {
+            for entry in entries {
+                let shard = fetch_shard(&remote, &entry, retries);
+                let failed = shard.is_err();
+                // stop once the loader is dropped
+                if tx.send(shard).is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self {
+            shards: rx,
+            len,
+            _fetcher: fetcher,
+        })
+    }
+
+    /// Number of shards in the manifest
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Iterator for ShardLoader {
+    type Item = anyhow::Result<Shard>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.shards.recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use sha2::{Digest, Sha256};
+
+    use super::{parse_manifest, read_hashed, Remote, ShardLoader, CHUNK_SIZE, MANIFEST};
+
+    #[test]
+    fn test_parse_remote() {
+        assert_eq!(
+            Remote::parse("https://example.com/shards/"),
+            Remote::Http {
+                base_url: "https://example.com/shards".to_string()
+            }
+        );
+        assert_eq!(
+            Remote::parse("root@trainer:/var/lib/card_platypus/shards"),
+            Remote::Ssh {
+                host: "root@trainer".to_string(),
+                dir: "/var/lib/card_platypus/shards".to_string()
+            }
+        );
+        assert_eq!(
+            Remote::parse("/mnt/shards"),
+            Remote::Local {
+                dir: "/mnt/shards".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let sha = "a".repeat(64);
+        let entries =
+            parse_manifest(&format!("{sha}  shard-0.bin\n\n{sha} *shard-1.bin\n")).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "shard-1.bin");
+
+        assert!(parse_manifest(&format!("{sha}  ../shard-0.bin")).is_err());
+        assert!(parse_manifest(&format!("{sha}  shard;rm -rf")).is_err());
+        assert!(parse_manifest("abc  shard-0.bin").is_err());
+    }
+
+    #[test]
+    fn test_read_hashed() {
+        let data = (0..CHUNK_SIZE * 2 + 7).map(|i| i as u8).collect::<Vec<_>>();
+        let (read, sha256) = read_hashed(data.as_slice()).unwrap();
+        assert_eq!(read, data);
+        assert_eq!(sha256, format!("{:x}", Sha256::digest(&data)));
+    }
+
+    #[test]
+    fn test_shard_loader() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let shards = [("shard-0.bin", vec![1u8, 2, 3]), ("shard-1.bin", vec![4u8])];
+        let mut manifest = String::new();
+        for (name, data) in &shards {
+            fs::write(dir.join(name), data).unwrap();
+            manifest += &format!("{:x}  {}\n", Sha256::digest(data), name);
+        }
+        // listed with the checksum of other contents
+        manifest += &format!("{:x}  shard-2.bin\n", Sha256::digest([5u8]));
+        fs::write(dir.join("shard-2.bin"), [6u8]).unwrap();
+        fs::write(dir.join(MANIFEST), manifest).unwrap();
+
+        let loader = ShardLoader::new(Remote::Local { dir }, 1, 1).unwrap();
+        assert_eq!(loader.len(), 3);
+        let loaded: Vec<_> = loader.collect();
+
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded[0].as_ref().unwrap().data, vec![1, 2, 3]);
+        assert_eq!(loaded[1].as_ref().unwrap().name, "shard-1.bin");
+        assert!(loaded[2].is_err());
+    }
+}
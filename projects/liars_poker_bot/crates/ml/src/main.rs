@@ -1,8 +1,15 @@
 use burn::tensor::{backend::Backend, Tensor};
+use loader::{Remote, Shard, ShardLoader};
 
+mod loader;
 mod ppo;
 mod stats;
 
+/// Shards fetched ahead of the one being trained on
+const PREFETCH: usize = 4;
+/// Times a shard is fetched again if the download or checksum fails
+const RETRIES: usize = 3;
+
 fn computation<B: Backend>() {
     // Create the device where to do the computation
     let device = Default::default();
@@ -14,6 +21,44 @@ fn computation<B: Backend>() {
     println!("{:}", tensor1 + tensor2);
 }
 
-fn main() {
-    computation::<burn::backend::Wgpu>();
+/// Trains on each shard as it arrives from the self-play machine
+///
+/// The network training isn't written yet, so for now each shard is only
+/// copied to the device, which exercises the loading end to end.
+fn train<B: Backend>(shards: ShardLoader) -> anyhow::Result<()> {
+    let device = Default::default();
+    let total = shards.len();
+    for (i, shard) in shards.enumerate() {
+        let Shard { name, data } = shard?;
+        let values = data.iter().map(|&x| x as f32).collect::<Vec<_>>();
+        let tensor: Tensor<B, 1> = Tensor::from_floats(values.as_slice(), &device);
+        println!(
+            "shard {}/{} {}: {} bytes, mean {}",
+            i + 1,
+            total,
+            name,
+            data.len(),
+            tensor.mean().into_scalar()
+        );
+    }
+    Ok(())
+}
+
+/// Without arguments runs a test computation, `--shards <remote>` trains on
+/// the shards at `remote`, see [`Remote::parse`]
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.iter().position(|x| x == "--shards") {
+        Some(i) => {
+            let Some(remote) = args.get(i + 1) else {
+                anyhow::bail!("--shards needs a remote, e.g. host:/path/to/shards");
+            };
+            let shards = ShardLoader::new(Remote::parse(remote), PREFETCH, RETRIES)?;
+            train::<burn::backend::Wgpu>(shards)
+        }
+        None => {
+            computation::<burn::backend::Wgpu>();
+            Ok(())
+        }
+    }
 }
@@ -1,9 +1,15 @@
-use std::path::Path;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::Path,
+};
 
 use anyhow::Ok;
 use clap::{command, Parser, Subcommand};
 use itertools::Itertools;
 use notify::{RecursiveMode, Watcher};
+use serde::Deserialize;
+use serde_json::Value;
 
 use xshell::{cmd, Shell};
 
@@ -16,6 +22,8 @@ enum Commands {
     Serve,
     Deploy,
     UpdateNginx,
+    /// Regenerate the euchre-app api spec from euchre_server
+    Openapi,
     Profile {
         #[clap(short, long, default_value_t = 0)]
         pid: usize,
@@ -38,6 +46,7 @@ fn main() -> anyhow::Result<()> {
         Commands::Serve => serve(),
         Commands::Deploy => deploy(),
         Commands::UpdateNginx => update_nginx(),
+        Commands::Openapi => openapi(),
         Commands::Profile { pid } => profile(pid),
     }
 }
@@ -86,19 +95,25 @@ fn get_server_logs() -> anyhow::Result<()> {
     .run()?;
 
     let logs = cmd!(sh, "cat {local_log_file}").read()?;
-
-    let num_player_ids = logs
-        .split('\n')
-        .filter(|x| x.contains("player_id"))
-        .filter_map(|x| x.split(' ').nth(7))
+    // lines from before the server logged json are skipped
+    let lines = logs
+        .lines()
+        .filter_map(|x| serde_json::from_str::<LogLine>(x).ok())
+        .collect_vec();
+
+    let num_player_ids = lines
+        .iter()
+        .filter_map(|x| x.get("player_id")?.as_u64())
         .unique()
         .count();
 
     println!("unique player ids: {}", num_player_ids);
 
-    let num_games = logs
-        .split('\n')
-        .filter(|x| x.contains("game ended"))
+    let num_games = lines
+        .iter()
+        .filter(|x| x.fields.get("message").and_then(Value::as_str) == Some("game over"))
+        .filter_map(|x| x.get("game_id")?.as_str())
+        .unique()
         .count();
 
     println!("num games: {}", num_games);
@@ -108,16 +123,33 @@ fn get_server_logs() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// An event in euchre_server's json log
+#[derive(Deserialize)]
+struct LogLine {
+    fields: HashMap<String, Value>,
+    /// Spans the event happened in, outermost first
+    #[serde(default)]
+    spans: Vec<HashMap<String, Value>>,
+}
+
+impl LogLine {
+    /// Value of `key` on the event, or on the innermost span that has it
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.fields
+            .get(key)
+            .or_else(|| self.spans.iter().rev().find_map(|x| x.get(key)))
+    }
+}
+
 fn serve() -> anyhow::Result<()> {
     // Automatically select the best implementation for your platform.
     let mut watcher =
         notify::recommended_watcher(|res: Result<notify::Event, notify::Error>| match res {
             std::result::Result::Ok(event) => {
-                if event
-                    .paths
-                    .iter()
-                    .any(|x| x.extension().map_or(false, |x| x == "html" || x == "rs"))
-                {
+                if event.paths.iter().any(|x| {
+                    x.extension()
+                        .map_or(false, |x| x == "html" || x == "rs" || x == "js")
+                }) {
                     println!("{:?}", event);
                     build_and_deploy_app()
                 }
@@ -138,6 +170,11 @@ fn serve() -> anyhow::Result<()> {
         RecursiveMode::Recursive,
     )?;
 
+    watcher.watch(
+        Path::new("./crates/euchre-app/public"),
+        RecursiveMode::Recursive,
+    )?;
+
     let sh = Shell::new()?;
     sh.change_dir("crates/euchre_server");
     cmd!(sh, "cargo watch --ignore euchre_server.log -x run").run()?;
@@ -158,12 +195,33 @@ fn build_and_deploy_app() {
         .run()
         .unwrap();
 
+    if let Err(e) = stamp_service_worker(&sh) {
+        println!("Error: {:?}", e);
+    }
+
     sh.change_dir("..");
     cmd!(sh, "rsync -r ./euchre-app/dist/. ./euchre_server/static")
         .run()
         .unwrap();
 }
 
+/// Sets the service worker's cache version to a hash of the built app, so
+/// installed apps drop their cached assets when a new version is deployed
+///
+/// Must be run from the euchre-app directory after `dx build`
+fn stamp_service_worker(sh: &Shell) -> anyhow::Result<()> {
+    let mut hasher = DefaultHasher::new();
+    sh.read_binary_file("./dist/assets/dioxus/euchre-app_bg.wasm")?
+        .hash(&mut hasher);
+    sh.read_file("./dist/tailwind.css")?.hash(&mut hasher);
+    let version = format!("{:x}", hasher.finish());
+
+    let sw = sh.read_file("./dist/sw.js")?;
+    sh.write_file("./dist/sw.js", sw.replace("{cache_version}", &version))?;
+
+    Ok(())
+}
+
 fn deploy() -> anyhow::Result<()> {
     let sh = Shell::new()?;
     sh.change_dir("crates/euchre-app");
@@ -171,6 +229,7 @@ fn deploy() -> anyhow::Result<()> {
     cmd!(sh, "dx build --profile wasm").run()?;
 
     cmd!(sh, "npx tailwindcss -i ./input.css -o ./dist/tailwind.css").run()?;
+    stamp_service_worker(&sh)?;
 
     cmd!(sh, "rsync -r ./dist/. root@{REMOTE_ADDR}:~/deploy/static").run()?;
 
@@ -198,6 +257,11 @@ fn deploy() -> anyhow::Result<()> {
     )
     .run()?;
 
+    // the bots take a few minutes to load, games aren't served until then. ssh
+    // passes the command through the remote shell, so it keeps its quotes
+    let wait_ready = "'until curl -sf http://localhost:4000/readyz; do sleep 5; done'";
+    cmd!(sh, "ssh root@{REMOTE_ADDR} timeout 600 sh -c {wait_ready}").run()?;
+
     Ok(())
 }
 
@@ -213,3 +277,12 @@ fn update_nginx() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn openapi() -> anyhow::Result<()> {
+    let sh = Shell::new()?;
+    sh.change_dir("crates/euchre_server");
+    let spec = cmd!(sh, "cargo run -q -- --openapi").read()?;
+    sh.write_file("../euchre-app/openapi.json", spec + "\n")?;
+
+    Ok(())
+}
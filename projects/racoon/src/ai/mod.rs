@@ -1,7 +1,11 @@
 use bevy::prelude::*;
 use rand::{thread_rng, Rng};
 
-use crate::{physics::Position, units::GoalPos};
+use crate::{
+    game::GameState,
+    physics::Position,
+    units::{Base, GoalPos},
+};
 
 use self::graphics::render_ai_goals;
 
@@ -15,24 +19,33 @@ pub struct AIPlugin {}
 impl Plugin for AIPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup)
-            .add_systems(Update, (set_goal_system, remove_goal_system))
+            .add_systems(
+                Update,
+                (set_goal_system, remove_goal_system).run_if(in_state(GameState::Playing)),
+            )
             .add_systems(Update, render_ai_goals);
     }
 }
 
 fn setup() {}
 
-/// Add a goal position to every ai controlled entity
+/// Add a goal position to every ai controlled entity, they head for the base
+/// if there is one and wander otherwise
 fn set_goal_system(
     mut commands: Commands,
     query: Query<Entity, (With<AIControlled>, Without<GoalPos>)>,
+    base: Query<&Position, With<Base>>,
 ) {
     let rng = &mut thread_rng();
     for entity in &query {
-        commands.entity(entity).insert(GoalPos(Vec2 {
-            x: rng.gen_range(-500.0..500.),
-            y: rng.gen_range(-500.0..500.),
-        }));
+        let goal = match base.get_single() {
+            Ok(pos) => **pos,
+            Err(_) => Vec2 {
+                x: rng.gen_range(-500.0..500.),
+                y: rng.gen_range(-500.0..500.),
+            },
+        };
+        commands.entity(entity).insert(GoalPos(goal));
     }
 }
 
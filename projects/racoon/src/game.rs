@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+
+use crate::{
+    ui::NORMAL_BUTTON,
+    units::{Base, Health},
+};
+
+const OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.7);
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+
+pub struct GamePlugin {}
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<GameState>()
+            .init_resource::<Score>()
+            .add_systems(
+                Update,
+                (score_system, base_destroyed_system).run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::GameOver), game_over_setup)
+            .add_systems(
+                Update,
+                restart_button_system.run_if(in_state(GameState::GameOver)),
+            )
+            .add_systems(OnExit(GameState::GameOver), reset_game);
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    Playing,
+    /// The base has fallen, waiting for the player to restart
+    GameOver,
+}
+
+/// Marks entities that only live for a single game, they're despawned on
+/// restart and spawned again when the next game starts
+#[derive(Component)]
+pub struct InGame;
+
+/// Seconds the base has survived
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct Score(pub f32);
+
+#[derive(Component)]
+struct GameOverScreen;
+
+#[derive(Component)]
+struct RestartButton;
+
+fn score_system(time: Res<Time>, mut score: ResMut<Score>) {
+    **score += time.delta_seconds();
+}
+
+fn base_destroyed_system(
+    query: Query<&Health, With<Base>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if query.iter().any(|health| **health <= 0.) {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn game_over_setup(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Score>) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(20.0),
+                    ..default()
+                },
+                background_color: OVERLAY_COLOR.into(),
+                ..default()
+            },
+            GameOverScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Game over",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 60.0,
+                    color: TEXT_COLOR,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                format!("The base survived {:.0} seconds", **score),
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 30.0,
+                    color: TEXT_COLOR,
+                },
+            ));
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(150.0),
+                            height: Val::Px(65.0),
+                            border: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    },
+                    RestartButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Restart",
+                        TextStyle {
+                            font,
+                            font_size: 20.0,
+                            color: TEXT_COLOR,
+                        },
+                    ));
+                });
+        });
+}
+
+fn restart_button_system(
+    query: Query<&Interaction, (Changed<Interaction>, With<RestartButton>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if query.iter().any(|i| *i == Interaction::Pressed) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+/// Clears out the finished game, the plugins spawn the next one when
+/// [`GameState::Playing`] is entered
+fn reset_game(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<InGame>, With<GameOverScreen>)>>,
+    mut score: ResMut<Score>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+    **score = 0.;
+}
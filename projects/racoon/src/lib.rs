@@ -1,4 +1,5 @@
 pub mod ai;
+pub mod game;
 pub mod graphics;
 pub mod input;
 pub mod physics;
@@ -1,12 +1,13 @@
 use bevy::prelude::*;
 use racoon::{
     ai::{AIControlled, AIPlugin},
+    game::{GamePlugin, GameState, InGame},
     graphics::{AnimatedSpriteBundle, GraphicsPlugin},
     input::{CursorPlugin, MouseCoords},
     physics::{PhyscisPlugin, Position},
     simulation::SimulationPlugin,
     ui::UIPlugin,
-    units::{EnemyBundle, EnemySpawnBundle, SpawnerTimer, UnitsPlugin},
+    units::{BaseBundle, EnemyBundle, EnemySpawnBundle, SpawnerTimer, UnitsPlugin},
 };
 
 fn main() {
@@ -20,9 +21,11 @@ fn main() {
             UnitsPlugin {},
             SimulationPlugin {},
             UIPlugin {},
+            GamePlugin {},
         ))
-        .add_systems(Startup, setup)
-        .add_systems(Update, (mouse_click_system, spawner_system))
+        .add_systems(OnEnter(GameState::Playing), setup)
+        .add_systems(Update, mouse_click_system)
+        .add_systems(Update, spawner_system.run_if(in_state(GameState::Playing)))
         // .add_systems(Update, grid_system)
         .run();
 }
@@ -37,9 +40,12 @@ fn mouse_click_system(mouse_cords: Res<MouseCoords>, mouse_button_input: Res<Inp
     }
 }
 
+/// Spawns the entities for a new game, they're despawned when the game is
+/// restarted
 fn setup(mut commands: Commands) {
-    commands.spawn(EnemySpawnBundle::new(Vec2 { x: -153., y: 76. }));
-    commands.spawn(EnemySpawnBundle::new(Vec2 { x: 183., y: 76. }));
+    commands.spawn((BaseBundle::new(Vec2 { x: 15., y: -150. }), InGame));
+    commands.spawn((EnemySpawnBundle::new(Vec2 { x: -153., y: 76. }), InGame));
+    commands.spawn((EnemySpawnBundle::new(Vec2 { x: 183., y: 76. }), InGame));
 }
 
 fn spawner_system(
@@ -57,7 +63,7 @@ fn spawner_system(
                 TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 32.0), 2, 2, None, None);
             let texture_atlas_handle = texture_atlases.add(texture_atlas);
             let sprite = AnimatedSpriteBundle::new(texture_atlas_handle, spawn_pos);
-            commands.spawn((EnemyBundle::new(spawn_pos, sprite), AIControlled {}));
+            commands.spawn((EnemyBundle::new(spawn_pos, sprite), AIControlled {}, InGame));
         }
     }
 }
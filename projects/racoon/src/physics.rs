@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
-use crate::simulation::{Coordinates, SIMULATION_HEIGHT, SIMULATION_WIDTH};
+use crate::{
+    game::GameState,
+    simulation::{Coordinates, SIMULATION_HEIGHT, SIMULATION_WIDTH},
+};
 
 const GRID_SIZE: f32 = 50.;
 
@@ -11,7 +14,10 @@ pub struct PhyscisPlugin {}
 
 impl Plugin for PhyscisPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(FixedUpdate, velocity_system);
+        app.add_systems(
+            FixedUpdate,
+            velocity_system.run_if(in_state(GameState::Playing)),
+        );
     }
 }
 
@@ -3,6 +3,7 @@ use std::time::Duration;
 use bevy::prelude::*;
 
 use crate::{
+    game::GameState,
     graphics::AnimatedSpriteBundle,
     physics::{Position, Velocity},
 };
@@ -15,16 +16,31 @@ const SPAWN_RANGE: f32 = 20.;
 
 const UNIT_VELOCITY: f32 = 30.;
 
+const BASE_COLOR: Color = Color::rgb(0.3, 0.7, 0.3);
+const BASE_SIZE: Vec3 = Vec3::new(40.0, 40.0, 0.0);
+const BASE_HEALTH: f32 = 100.;
+const HEALTH_BAR_OFFSET: f32 = 30.;
+
+/// How close an enemy needs to be to the base to attack it
+const ATTACK_RANGE: f32 = 30.;
+/// Damage each attacking enemy does to the base per second
+const ENEMY_DPS: f32 = 5.;
+
 pub struct UnitsPlugin {}
 
 impl Plugin for UnitsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, unit_movement_system);
+        app.add_systems(
+            Update,
+            (unit_movement_system, enemy_attack_system).run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(Update, render_health_system);
     }
 }
 
 #[derive(Bundle)]
 pub struct EnemyBundle {
+    enemy: Enemy,
     position: Position,
     velocity: Velocity,
     shape: Shape,
@@ -34,6 +50,7 @@ pub struct EnemyBundle {
 impl EnemyBundle {
     pub fn new(pos: Vec2, sprite: AnimatedSpriteBundle) -> Self {
         Self {
+            enemy: Enemy,
             position: Position(pos),
             shape: Shape::Circle,
             velocity: Velocity(Vec2 { x: 0., y: 0. }),
@@ -42,6 +59,37 @@ impl EnemyBundle {
     }
 }
 
+/// The base the player defends, the game is lost when its health runs out
+#[derive(Bundle)]
+pub struct BaseBundle {
+    base: Base,
+    health: Health,
+    position: Position,
+    sprite: SpriteBundle,
+}
+
+impl BaseBundle {
+    pub fn new(pos: Vec2) -> Self {
+        Self {
+            base: Base,
+            health: Health(BASE_HEALTH),
+            position: Position(pos),
+            sprite: SpriteBundle {
+                transform: Transform {
+                    translation: Vec3::new(pos.x, pos.y, 0.0),
+                    scale: BASE_SIZE,
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: BASE_COLOR,
+                    ..default()
+                },
+                ..default()
+            },
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct EnemySpawnBundle {
     position: Position,
@@ -88,6 +136,15 @@ pub enum SpawnerType {
     Enemy,
 }
 
+#[derive(Component)]
+pub struct Base;
+
+#[derive(Component)]
+pub struct Enemy;
+
+#[derive(Component, Deref, DerefMut)]
+pub struct Health(pub f32);
+
 #[derive(Component)]
 enum Shape {
     Circle,
@@ -101,3 +158,33 @@ fn unit_movement_system(mut query: Query<(&mut Velocity, &Position, &GoalPos)>)
         *vel = Velocity((**gpos - **pos).normalize() * UNIT_VELOCITY);
     }
 }
+
+/// Enemies in range of the base damage it
+fn enemy_attack_system(
+    time: Res<Time>,
+    mut bases: Query<(&Position, &mut Health), With<Base>>,
+    enemies: Query<&Position, With<Enemy>>,
+) {
+    let delta = time.delta_seconds();
+    for (base_pos, mut health) in &mut bases {
+        let attackers = enemies
+            .iter()
+            .filter(|pos| (***pos - **base_pos).length() < ATTACK_RANGE)
+            .count();
+        **health -= attackers as f32 * ENEMY_DPS * delta;
+    }
+}
+
+/// Draws the remaining health above the base
+fn render_health_system(mut gizmos: Gizmos, query: Query<(&Position, &Health), With<Base>>) {
+    for (pos, health) in &query {
+        let start = **pos + Vec2::new(-BASE_SIZE.x / 2., HEALTH_BAR_OFFSET);
+        let remaining = (**health / BASE_HEALTH).clamp(0., 1.);
+        gizmos.line_2d(start, start + Vec2::new(BASE_SIZE.x, 0.), Color::RED);
+        gizmos.line_2d(
+            start,
+            start + Vec2::new(BASE_SIZE.x * remaining, 0.),
+            Color::GREEN,
+        );
+    }
+}
@@ -1,14 +1,61 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion};
 
 use renderer::{PixelBuffer, Universe};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     let mut universe = Universe::new();
     let mut pixel_buffer = PixelBuffer::new(&universe);
+    universe.orbit_camera(0.1f64.to_degrees(), 0.0);
 
     c.bench_function("render", |b| {
-        b.iter(|| universe.render(black_box(0.1), &mut pixel_buffer))
+        b.iter(|| universe.render(&mut pixel_buffer, 1))
     });
+
+    c.bench_function("render_4spp", |b| {
+        b.iter(|| universe.render(&mut pixel_buffer, 4))
+    });
+
+    #[cfg(feature = "obj")]
+    {
+        universe.add_obj(&uv_sphere(64, 32), 200, 200, 200).unwrap();
+        c.bench_function("render_mesh", |b| {
+            b.iter(|| universe.render(&mut pixel_buffer, 1))
+        });
+    }
+}
+
+/// OBJ source for a sphere in the middle of the scene made of
+/// `2 * segments * (rings - 1)` triangles
+#[cfg(feature = "obj")]
+fn uv_sphere(segments: usize, rings: usize) -> String {
+    let mut source = String::new();
+    for ring in 0..=rings {
+        let theta = std::f64::consts::PI * ring as f64 / rings as f64;
+        for segment in 0..segments {
+            let phi = 2.0 * std::f64::consts::PI * segment as f64 / segments as f64;
+            source += &format!(
+                "v {} {} {}\n",
+                0.7 * theta.sin() * phi.cos(),
+                1.0 + 0.7 * theta.cos(),
+                -3.0 + 0.7 * theta.sin() * phi.sin()
+            );
+        }
+    }
+    // OBJ vertices are numbered from 1
+    let vertex = |ring: usize, segment: usize| ring * segments + segment % segments + 1;
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let (a, b) = (vertex(ring, segment), vertex(ring, segment + 1));
+            let (c, d) = (vertex(ring + 1, segment), vertex(ring + 1, segment + 1));
+            if ring > 0 {
+                source += &format!("f {} {} {}\n", a, b, c);
+            }
+            if ring < rings - 1 {
+                source += &format!("f {} {} {}\n", b, d, c);
+            }
+        }
+    }
+    return source;
 }
 
 criterion_group!(benches, criterion_benchmark);
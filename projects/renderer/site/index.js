@@ -43,19 +43,213 @@ import("./node_modules/renderer/renderer.js").then((js) => {
     }
   };
 
+  // Renders frames in tiles spread over web workers, each with its own
+  // universe. Scene changes go through `call` so every copy stays in sync.
+  const TileScheduler = class {
+    constructor(universe, workerCount) {
+      this.universe = universe;
+      this.workers = [];
+      this.rendering = false;
+      this.pending = [];
+      this.ready = Promise.all([...Array(workerCount)].map(() => new Promise((resolve, reject) => {
+        const worker = new Worker("./worker.js");
+        worker.onmessage = () => resolve(worker);
+        worker.onerror = reject;
+        this.workers.push(worker);
+      })));
+    }
+
+    // Calls a Universe method here and in every worker, returning the result from here
+    //
+    // Input events can fire while a frame renders, so calls made then wait
+    // for the next frame, otherwise the tiles still to come would see a
+    // different camera than the ones already done. Nothing is returned for
+    // those, the ones that need a result are made between frames.
+    call(method, ...args) {
+      if (this.rendering) {
+        this.pending.push([method, args]);
+        return undefined;
+      }
+      for (const worker of this.workers) {
+        worker.postMessage({ type: "call", method: method, args: args });
+      }
+      return this.universe[method](...args);
+    }
+
+    // Resolves once every tile has been written to the pixel buffer
+    render(pixelBuffer, samples) {
+      for (const [method, args] of this.pending.splice(0)) {
+        this.call(method, ...args);
+      }
+      this.rendering = true;
+      const tileCount = this.universe.tile_count();
+      let next = 0;
+      let done = 0;
+      return new Promise((resolve) => {
+        const renderNext = (worker) => {
+          if (next < tileCount) {
+            worker.postMessage({ type: "render", tile: next, samples: samples });
+            next += 1;
+          }
+        };
+        for (const worker of this.workers) {
+          worker.onmessage = (event) => {
+            pixelBuffer.write_tile(this.universe, event.data.tile, event.data.pixels);
+            done += 1;
+            if (done === tileCount) {
+              this.rendering = false;
+              resolve();
+            } else {
+              renderNext(worker);
+            }
+          };
+          renderNext(worker);
+        }
+      });
+    }
+  };
+
   var pos = 0.0;
   const Universe = js.Universe.new();
   const Painter = js.Painter.new();
   const PixelBuffer = js.PixelBuffer.new(Universe);
 
-  const renderLoop = () => {
+  // Renders on the main thread, used when workers aren't available
+  const serial = {
+    call: (method, ...args) => Universe[method](...args),
+    render: (pixelBuffer, samples) => Universe.render(pixelBuffer, samples),
+  };
+  var scheduler = serial;
+
+  // Light circling over the spheres, moved before each frame
+  var lamp;
+  const addLamp = () => {
+    lamp = scheduler.call("add_point_light", 0.0, 1.5, -3.0, 20.0, 0.5);
+  };
+  const moveLamp = () => {
+    scheduler.call("move_light", lamp, 2.0 * Math.cos(pos * 4), 1.5, -3.0 + 2.0 * Math.sin(pos * 4));
+  };
+
+  // Drag to orbit, shift or right drag to pan, scroll to dolly. The camera
+  // circles on its own until it's first moved.
+  var autoOrbit = true;
+  var cameraMoved = false;
+  const ORBIT_SPEED = 0.005 * 180 / Math.PI; // degrees per frame
+  const STILL_SAMPLES = 4; // rays per pixel
+  const cameraControls = (call) => {
+    const canvas = document.getElementById("canvas");
+    var drag = null;
+    canvas.addEventListener("contextmenu", (event) => event.preventDefault());
+    canvas.addEventListener("pointerdown", (event) => {
+      canvas.setPointerCapture(event.pointerId);
+      drag = { x: event.clientX, y: event.clientY, pan: event.shiftKey || event.button === 2 };
+      autoOrbit = false;
+    });
+    canvas.addEventListener("pointermove", (event) => {
+      if (!drag) {
+        return;
+      }
+      const dx = event.clientX - drag.x;
+      const dy = event.clientY - drag.y;
+      drag.x = event.clientX;
+      drag.y = event.clientY;
+      cameraMoved = true;
+      if (drag.pan) {
+        call("pan_camera", -dx * 0.01, dy * 0.01);
+      } else {
+        call("orbit_camera", dx * 0.5, dy * 0.5);
+      }
+    });
+    canvas.addEventListener("pointerup", () => {
+      drag = null;
+    });
+    canvas.addEventListener("wheel", (event) => {
+      event.preventDefault();
+      autoOrbit = false;
+      cameraMoved = true;
+      call("dolly_camera", -event.deltaY * 0.005);
+    }, { passive: false });
+  };
+
+  // Exposure and tone mapping, in stops and a ToneMapping name
+  const settingsControls = (call) => {
+    const exposure = document.getElementById("exposure");
+    exposure.addEventListener("input", () => call("set_exposure", parseFloat(exposure.value)));
+    const toneMapping = document.getElementById("tone-mapping");
+    toneMapping.addEventListener("change", () => call("set_tone_mapping", js.ToneMapping[toneMapping.value]));
+  };
+  const controls = (call) => {
+    cameraControls(call);
+    settingsControls(call);
+  };
+
+  const renderLoop = async () => {
     fps.render();
-    Universe.render(pos, PixelBuffer);
+    moveLamp();
+    if (autoOrbit) {
+      scheduler.call("orbit_camera", ORBIT_SPEED, 0.0);
+    }
+    // Frames are replaced straight away while the camera moves, so only
+    // smooth edges once it stops
+    const samples = autoOrbit || cameraMoved ? 1 : STILL_SAMPLES;
+    cameraMoved = false;
+    await scheduler.render(PixelBuffer, samples);
     Painter.paint(Universe, PixelBuffer);
     pos += 0.005;
 
     requestAnimationFrame(renderLoop);
   };
 
-  renderLoop();
+  // Scene changes made before the workers start are replayed in them, so
+  // make them once the workers are ready
+  const startCpu = () => {
+    const WORKERS = Math.min(navigator.hardwareConcurrency || 4, 8);
+    if (typeof Worker === "undefined") {
+      addLamp();
+      controls((...args) => scheduler.call(...args));
+      renderLoop();
+      return;
+    }
+    const tiled = new TileScheduler(Universe, WORKERS);
+    tiled.ready.then(() => {
+      scheduler = tiled;
+    }).catch(() => {
+      for (const worker of tiled.workers) {
+        worker.terminate();
+      }
+    }).finally(() => {
+      addLamp();
+      controls((...args) => scheduler.call(...args));
+      renderLoop();
+    });
+  };
+
+  const gpuRenderLoop = (gpu) => async () => {
+    fps.render();
+    if (autoOrbit) {
+      Universe.orbit_camera(ORBIT_SPEED, 0.0);
+    }
+    await gpu.render(Universe);
+    gpu.copy_to(PixelBuffer);
+    Painter.paint(Universe, PixelBuffer);
+    pos += 0.005;
+
+    requestAnimationFrame(gpuRenderLoop(gpu));
+  };
+
+  // Only built with the webgpu feature, use the cpu tracer if WebGPU isn't
+  // available or can't trace the scene
+  const SAMPLES = 16;
+  if (js.GpuRenderer) {
+    js.GpuRenderer.create(Universe, SAMPLES).then((gpu) => {
+      if (gpu) {
+        controls((method, ...args) => Universe[method](...args));
+        gpuRenderLoop(gpu)();
+      } else {
+        startCpu();
+      }
+    });
+  } else {
+    startCpu();
+  }
 });
\ No newline at end of file
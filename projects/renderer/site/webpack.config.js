@@ -1,9 +1,19 @@
 const path = require('path');
-module.exports = {
-  entry: "./index.js",
-  output: {
-    path: path.resolve(__dirname, "dist"),
-    filename: "index.js",
+const output = {
+  path: path.resolve(__dirname, "dist"),
+  filename: "[name].js",
+};
+module.exports = [
+  {
+    entry: { index: "./index.js" },
+    output: output,
+    mode: "development"
   },
-  mode: "development"
-};
\ No newline at end of file
+  // Tile rendering workers need their own build to load wasm outside the page
+  {
+    entry: { worker: "./worker.js" },
+    output: output,
+    target: "webworker",
+    mode: "development"
+  }
+];
@@ -0,0 +1,17 @@
+// Renders tiles for the main thread, see TileScheduler in index.js
+import("./node_modules/renderer/renderer.js").then((js) => {
+  const universe = js.Universe.new();
+
+  onmessage = (event) => {
+    const msg = event.data;
+    if (msg.type === "call") {
+      // Keep this universe's scene in sync with the main thread's
+      universe[msg.method](...msg.args);
+    } else if (msg.type === "render") {
+      const pixels = universe.render_tile(msg.tile, msg.samples);
+      postMessage({ tile: msg.tile, pixels: pixels }, [pixels.buffer]);
+    }
+  };
+
+  postMessage({ ready: true });
+});
@@ -0,0 +1,428 @@
+use na::{Point3, Vector3};
+
+use crate::rendering::{Element, Intersectable, Ray};
+use crate::scene::{Plane, Sphere, Triangle};
+
+/// Most elements in a leaf before it's worth splitting
+const MAX_LEAF_SIZE: usize = 2;
+/// Buckets centroids are sorted into when looking for a split
+const BINS: usize = 12;
+/// Cost of testing a ray against a node's bounds, relative to testing it
+/// against an element
+const TRAVERSAL_COST: f64 = 1.0;
+
+/// Axis aligned bounding box
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Point3<f64>,
+    pub max: Point3<f64>,
+}
+
+impl Aabb {
+    /// Box that contains nothing, the starting point for `union`
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn grow(&self, point: &Point3<f64>) -> Aabb {
+        Aabb {
+            min: self.min.inf(point),
+            max: self.max.sup(point),
+        }
+    }
+
+    pub fn centroid(&self) -> Point3<f64> {
+        na::center(&self.min, &self.max)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        if d.x < 0.0 {
+            return 0.0; // empty
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Returns the distance to where the ray enters the box, 0 if it starts
+    /// inside. `inv_direction` is 1 over each component of the ray's
+    /// direction.
+    pub fn intersect(&self, ray: &Ray, inv_direction: &Vector3<f64>) -> Option<f64> {
+        // Slab test, adapted from: https://tavianator.com/2011/ray_box.html
+        let mut t_min: f64 = 0.0;
+        let mut t_max = f64::INFINITY;
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t2 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+        if t_min <= t_max {
+            Some(t_min)
+        } else {
+            None
+        }
+    }
+}
+
+pub trait Bounded {
+    /// Box around the whole element, None if it's infinite
+    fn bounds(&self) -> Option<Aabb>;
+}
+
+impl Bounded for Element {
+    fn bounds(&self) -> Option<Aabb> {
+        match *self {
+            Element::Sphere(ref s) => s.bounds(),
+            Element::Plane(ref p) => p.bounds(),
+            Element::Triangle(ref t) => t.bounds(),
+        }
+    }
+}
+
+impl Bounded for Sphere {
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vector3::new(self.radius, self.radius, self.radius);
+        Some(Aabb {
+            min: self.center - r,
+            max: self.center + r,
+        })
+    }
+}
+
+impl Bounded for Plane {
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+impl Bounded for Triangle {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::empty().grow(&self.a).grow(&self.b).grow(&self.c))
+    }
+}
+
+#[derive(Clone)]
+enum NodeKind {
+    /// `count` elements starting at `first` in `Bvh::indices`
+    Leaf { first: usize, count: usize },
+    /// `axis` is the one the children were split along
+    Interior {
+        left: usize,
+        right: usize,
+        axis: usize,
+    },
+}
+
+#[derive(Clone)]
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+/// Bounding volume hierarchy over the elements of a scene, so rays only test
+/// elements whose boxes they pass through
+///
+/// Built with surface area heuristic splits. Elements that can't be bounded,
+/// like planes, and elements added since the last `build` are tested
+/// against every ray. Call `refit` after moving elements and `build` after
+/// adding or removing them, refitting is faster but the tree gets worse the
+/// further elements move.
+#[derive(Clone)]
+pub struct Bvh {
+    /// Root first, children always come after their parent
+    nodes: Vec<Node>,
+    /// Indexes of bounded elements, each leaf covers a range of these
+    indices: Vec<usize>,
+    /// Indexes of elements that can't be bounded
+    unbounded: Vec<usize>,
+    /// Number of elements when the tree was built
+    len: usize,
+}
+
+impl Bvh {
+    pub fn build(elements: &[Element]) -> Bvh {
+        let mut bounds = Vec::with_capacity(elements.len());
+        let mut indices = Vec::new();
+        let mut unbounded = Vec::new();
+        for (i, element) in elements.iter().enumerate() {
+            match element.bounds() {
+                Some(b) => {
+                    bounds.push(b);
+                    indices.push(i);
+                }
+                None => {
+                    bounds.push(Aabb::empty());
+                    unbounded.push(i);
+                }
+            }
+        }
+
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            indices: indices,
+            unbounded: unbounded,
+            len: elements.len(),
+        };
+        if !bvh.indices.is_empty() {
+            bvh.build_node(&bounds, 0, bvh.indices.len());
+        }
+        return bvh;
+    }
+
+    /// Adds the node for `count` indices starting at `first` and its children,
+    /// returning its index
+    fn build_node(&mut self, bounds: &[Aabb], first: usize, count: usize) -> usize {
+        let range = first..first + count;
+        let node_bounds = self.indices[range.clone()]
+            .iter()
+            .fold(Aabb::empty(), |b, i| b.union(&bounds[*i]));
+        let node_index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: node_bounds,
+            kind: NodeKind::Leaf {
+                first: first,
+                count: count,
+            },
+        });
+        if count <= MAX_LEAF_SIZE {
+            return node_index;
+        }
+
+        // Split along the longest axis of the centroids
+        let centroid_bounds = self.indices[range.clone()]
+            .iter()
+            .fold(Aabb::empty(), |b, i| b.grow(&bounds[*i].centroid()));
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis = extent.imax();
+        if extent[axis] <= 0.0 {
+            return node_index; // every centroid is in the same place
+        }
+        let bin_of = |i: usize| {
+            let offset = (bounds[i].centroid()[axis] - centroid_bounds.min[axis]) / extent[axis];
+            ((offset * BINS as f64) as usize).min(BINS - 1)
+        };
+
+        let mut bins = [(Aabb::empty(), 0); BINS];
+        for i in &self.indices[range.clone()] {
+            let bin = &mut bins[bin_of(*i)];
+            bin.0 = bin.0.union(&bounds[*i]);
+            bin.1 += 1;
+        }
+
+        // Cost of splitting after each bin, relative to testing every element
+        let mut best = (f64::INFINITY, 0);
+        for split in 1..BINS {
+            let (left, n_left) = bins[..split]
+                .iter()
+                .fold((Aabb::empty(), 0), |(b, n), (bin, c)| (b.union(bin), n + c));
+            let (right, n_right) = bins[split..]
+                .iter()
+                .fold((Aabb::empty(), 0), |(b, n), (bin, c)| (b.union(bin), n + c));
+            if n_left == 0 || n_right == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (left.surface_area() * n_left as f64 + right.surface_area() * n_right as f64)
+                    / node_bounds.surface_area();
+            if cost < best.0 {
+                best = (cost, split);
+            }
+        }
+        if best.0 >= count as f64 {
+            return node_index; // testing every element is cheaper
+        }
+
+        let (mut left, mut right): (Vec<usize>, Vec<usize>) = self.indices[range.clone()]
+            .iter()
+            .partition(|i| bin_of(**i) < best.1);
+        let n_left = left.len();
+        left.append(&mut right);
+        self.indices[range].copy_from_slice(&left);
+
+        let left = self.build_node(bounds, first, n_left);
+        let right = self.build_node(bounds, first + n_left, count - n_left);
+        self.nodes[node_index].kind = NodeKind::Interior {
+            left: left,
+            right: right,
+            axis: axis,
+        };
+        return node_index;
+    }
+
+    /// Updates the bounds of every node after elements have moved, keeping
+    /// the tree the same
+    pub fn refit(&mut self, elements: &[Element]) {
+        // Children come after their parents, so they're updated first
+        for n in (0..self.nodes.len()).rev() {
+            let bounds = match self.nodes[n].kind {
+                NodeKind::Leaf { first, count } => self.indices[first..first + count]
+                    .iter()
+                    .filter_map(|i| elements[*i].bounds())
+                    .fold(Aabb::empty(), |b, e| b.union(&e)),
+                NodeKind::Interior { left, right, .. } => {
+                    self.nodes[left].bounds.union(&self.nodes[right].bounds)
+                }
+            };
+            self.nodes[n].bounds = bounds;
+        }
+    }
+
+    /// Returns the distance to the closest element the ray hits
+    pub fn intersect<'a>(&self, elements: &'a [Element], ray: &Ray) -> Option<(f64, &'a Element)> {
+        let mut closest: Option<(f64, &Element)> = None;
+        let test = |i: usize, closest: &mut Option<(f64, &'a Element)>| {
+            if let Some(distance) = elements[i].intersect(ray) {
+                if closest.map_or(true, |(d, _)| distance < d) {
+                    *closest = Some((distance, &elements[i]));
+                }
+            }
+        };
+
+        for i in &self.unbounded {
+            test(*i, &mut closest);
+        }
+        for i in self.len..elements.len() {
+            test(i, &mut closest);
+        }
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let inv_direction = ray.direction.map(|d| 1.0 / d);
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            match node.bounds.intersect(ray, &inv_direction) {
+                Some(entry) if closest.map_or(true, |(d, _)| entry < d) => {}
+                _ => continue,
+            }
+
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for i in &self.indices[first..first + count] {
+                        test(*i, &mut closest);
+                    }
+                }
+                NodeKind::Interior { left, right, axis } => {
+                    // Visit the child nearer the ray first, it's more likely
+                    // to hit and rule out the other one
+                    if ray.direction[axis] < 0.0 {
+                        stack.push(left);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+        return closest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::{Point3, Vector3};
+
+    use super::Bvh;
+    use crate::rendering::{Element, Intersectable, Ray};
+    use crate::scene::{Color, Material, Plane, Sphere, Triangle};
+
+    /// Linear congruential generator, so the scenes are the same every run
+    struct Lcg(u64);
+
+    impl Lcg {
+        /// Uniform in `-1.0..1.0`
+        fn next(&mut self) -> f64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            return (self.0 >> 11) as f64 / (1u64 << 52) as f64 - 1.0;
+        }
+
+        fn point(&mut self, scale: f64) -> Point3<f64> {
+            return Point3::new(self.next(), self.next(), self.next()) * scale;
+        }
+
+        fn vector(&mut self) -> Vector3<f64> {
+            return Vector3::new(self.next(), self.next(), self.next());
+        }
+    }
+
+    fn random_elements(rng: &mut Lcg, count: usize) -> Vec<Element> {
+        let material = Material::diffuse(Color::from_srgb(200, 200, 200));
+        let mut elements = Vec::new();
+        for i in 0..count {
+            let center = rng.point(10.0);
+            if i % 2 == 0 {
+                elements.push(Element::Sphere(Sphere {
+                    center: center,
+                    radius: 0.2 + rng.next().abs(),
+                    material: material,
+                }));
+            } else {
+                elements.push(Element::Triangle(Triangle {
+                    a: center + rng.vector(),
+                    b: center + rng.vector(),
+                    c: center + rng.vector(),
+                    material: material,
+                }));
+            }
+        }
+        return elements;
+    }
+
+    /// Checks the tree finds the same closest hit as testing every element
+    fn assert_matches_brute_force(bvh: &Bvh, elements: &[Element], rng: &mut Lcg) {
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray {
+                origin: rng.point(15.0),
+                direction: rng.vector().normalize(),
+            };
+            let expected = elements
+                .iter()
+                .filter_map(|e| e.intersect(&ray))
+                .fold(None, |closest: Option<f64>, d| {
+                    Some(closest.map_or(d, |c| c.min(d)))
+                });
+            let actual = bvh.intersect(elements, &ray).map(|(d, _)| d);
+            assert_eq!(actual, expected);
+            hits += usize::from(expected.is_some());
+        }
+        // otherwise the rays are all missing and the test shows nothing
+        assert!(hits > 100, "only {} rays hit", hits);
+    }
+
+    #[test]
+    fn test_intersect_matches_brute_force() {
+        let mut rng = Lcg(42);
+        let mut elements = random_elements(&mut rng, 200);
+        elements.push(Element::Plane(Plane {
+            origin: Point3::new(0.0, -12.0, 0.0),
+            normal: Vector3::new(0.0, 1.0, 0.0),
+            material: Material::diffuse(Color::from_srgb(200, 200, 200)),
+        }));
+
+        let mut bvh = Bvh::build(&elements);
+        assert_matches_brute_force(&bvh, &elements, &mut rng);
+
+        for element in elements.iter_mut() {
+            let offset = rng.vector() * 3.0;
+            element.translate(offset);
+        }
+        bvh.refit(&elements);
+        assert_matches_brute_force(&bvh, &elements, &mut rng);
+    }
+}
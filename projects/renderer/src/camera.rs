@@ -0,0 +1,95 @@
+use na::{Point3, Vector3};
+
+use crate::rendering::Camera;
+
+/// Closest the camera can get to what it's looking at
+const MIN_DISTANCE: f64 = 0.1;
+/// Orbiting stops this far from looking straight up or down, where the
+/// camera's up direction is undefined
+const MAX_PITCH: f64 = 89.0 * std::f64::consts::PI / 180.0;
+
+/// Camera that looks from `position` at `target`, moved by the user
+///
+/// Methods take angles in degrees like the JS API. `camera` turns it into
+/// the `Camera` rays are cast from.
+#[derive(Clone)]
+pub struct ViewCamera {
+    pub position: Point3<f64>,
+    pub target: Point3<f64>,
+    /// Field of view across the image in radians
+    pub fov: f64,
+}
+
+impl ViewCamera {
+    pub fn new(position: Point3<f64>, target: Point3<f64>, fov_degrees: f64) -> ViewCamera {
+        ViewCamera {
+            position: position,
+            target: target,
+            fov: fov_degrees.to_radians(),
+        }
+    }
+
+    pub fn set_fov(&mut self, fov_degrees: f64) {
+        self.fov = fov_degrees.clamp(1.0, 179.0).to_radians();
+    }
+
+    /// Moves towards the target by `distance`, or away from it if negative,
+    /// stopping short of the target
+    pub fn dolly(&mut self, distance: f64) {
+        let offset = self.position - self.target;
+        let length = (offset.norm() - distance).max(MIN_DISTANCE);
+        self.position = self.target + offset.normalize() * length;
+    }
+
+    /// Slides the camera and its target sideways by `right` and `up`, as
+    /// seen on screen
+    pub fn pan(&mut self, right: f64, up: f64) {
+        let (_, screen_right, screen_up) = self.axes();
+        let offset = screen_right * right + screen_up * up;
+        self.position += offset;
+        self.target += offset;
+    }
+
+    /// Circles the camera around its target, `yaw` around the vertical axis
+    /// and `pitch` up or down over it
+    pub fn orbit(&mut self, yaw_degrees: f64, pitch_degrees: f64) {
+        let offset = self.position - self.target;
+        let distance = offset.norm();
+        let yaw = offset.z.atan2(offset.x) + yaw_degrees.to_radians();
+        let pitch = ((offset.y / distance).asin() + pitch_degrees.to_radians())
+            .clamp(-MAX_PITCH, MAX_PITCH);
+        self.position = self.target
+            + distance
+                * Vector3::new(
+                    pitch.cos() * yaw.cos(),
+                    pitch.sin(),
+                    pitch.cos() * yaw.sin(),
+                );
+    }
+
+    /// Unit vectors forwards, and right and up as seen on screen
+    fn axes(&self) -> (Vector3<f64>, Vector3<f64>, Vector3<f64>) {
+        let direction = (self.target - self.position).normalize();
+        // Looking straight up or down, any horizontal right will do
+        let right = direction
+            .cross(&Vector3::y())
+            .try_normalize(1e-9)
+            .unwrap_or_else(Vector3::x);
+        let up = right.cross(&direction);
+        return (direction, right, up);
+    }
+
+    pub fn camera(&self) -> Camera {
+        // From: https://stackoverflow.com/questions/13078243/how-to-move-a-camera-using-in-a-ray-tracer
+        // `Ray::create_prime` expects `right` and `up` to point left and down
+        // on screen
+        let (direction, right, up) = self.axes();
+        let scale = 2.0 * (self.fov / 2.0).tan();
+        Camera {
+            direction: direction,
+            location: self.position,
+            right: -right * scale,
+            up: -up * scale,
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::rendering::{Camera, Element};
+use crate::scene::{DirectionalLight, LightSource, Scene};
+use crate::tonemap::RenderSettings;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct GpuSphere {
+    center_radius: [f32; 4],
+    color: [f32; 4],
+}
+
+/// Matches `Uniforms` in tracer.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Uniforms {
+    location: [f32; 4],
+    direction: [f32; 4],
+    right: [f32; 4],
+    up: [f32; 4],
+    light: [f32; 4],
+    size: [u32; 4],
+    tone: [f32; 4],
+}
+
+/// Runs the ray tracer as a WGSL compute shader
+///
+/// The scene is uploaded once, each frame only updates the camera. Works with
+/// WebGPU in the browser and with the native wgpu backends.
+pub struct GpuTracer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    uniforms: wgpu::Buffer,
+    output: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    base: Uniforms,
+}
+
+impl GpuTracer {
+    /// The shader only traces spheres, lit by at most one directional light
+    pub fn supports(scene: &Scene) -> bool {
+        let spheres_only = scene
+            .elements
+            .iter()
+            .all(|e| matches!(e, Element::Sphere(_)));
+        let directional_only = scene
+            .lights
+            .iter()
+            .all(|l| matches!(l, LightSource::Directional(_)));
+        return spheres_only && directional_only && scene.lights.len() <= 1;
+    }
+
+    /// Returns None if no adapter is available or the scene has something
+    /// the shader can't trace, callers should fall back to the cpu tracer
+    pub async fn new(scene: &Scene, samples: u32) -> Option<GpuTracer> {
+        if !GpuTracer::supports(scene) {
+            return None;
+        }
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await
+            .ok()?;
+
+        let spheres: Vec<GpuSphere> = scene
+            .elements
+            .iter()
+            .filter_map(|e| match e {
+                Element::Sphere(s) => Some(GpuSphere {
+                    center_radius: [
+                        s.center.x as f32,
+                        s.center.y as f32,
+                        s.center.z as f32,
+                        s.radius as f32,
+                    ],
+                    color: [
+                        s.material.color.r,
+                        s.material.color.g,
+                        s.material.color.b,
+                        0.0,
+                    ],
+                }),
+                _ => None,
+            })
+            .collect();
+        let num_spheres = spheres.len() as u32;
+        // storage buffers can't be empty
+        let spheres = if spheres.is_empty() {
+            vec![GpuSphere::zeroed()]
+        } else {
+            spheres
+        };
+
+        // Black if the scene has no lights
+        let light = scene
+            .lights
+            .iter()
+            .find_map(|l| match l {
+                LightSource::Directional(d) => Some(d.clone()),
+                _ => None,
+            })
+            .unwrap_or(DirectionalLight {
+                direction: na::Vector3::new(0.0, -1.0, 0.0),
+                intensity: 0.0,
+            });
+
+        let base = Uniforms {
+            location: [0.0; 4],
+            direction: [0.0; 4],
+            right: [0.0; 4],
+            up: [0.0; 4],
+            light: [
+                light.direction.x as f32,
+                light.direction.y as f32,
+                light.direction.z as f32,
+                light.intensity,
+            ],
+            size: [scene.width, scene.height, samples.max(1), num_spheres],
+            tone: [1.0, 0.0, 0.0, 0.0],
+        };
+
+        let uniforms = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("uniforms"),
+            contents: bytemuck::bytes_of(&base),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sphere_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("spheres"),
+            contents: bytemuck::cast_slice(&spheres),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let output_size = (scene.width * scene.height * 4) as u64;
+        let output = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pixels"),
+            size: output_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: output_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("tracer"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("tracer.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("tracer"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tracer"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: sphere_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: output.as_entire_binding(),
+                },
+            ],
+        });
+
+        return Some(GpuTracer {
+            device,
+            queue,
+            pipeline,
+            bind_group,
+            uniforms,
+            output,
+            readback,
+            base,
+        });
+    }
+
+    /// Renders a frame into `pixels` as rgba bytes
+    ///
+    /// Only one frame can be in flight at a time, wait for the previous call
+    /// to finish before starting the next. Returns false if the frame couldn't
+    /// be read back, `pixels` is left unchanged in that case.
+    pub async fn render(
+        &self,
+        camera: &Camera,
+        settings: &RenderSettings,
+        pixels: &mut [u8],
+    ) -> bool {
+        let uniforms = Uniforms {
+            location: [
+                camera.location.x as f32,
+                camera.location.y as f32,
+                camera.location.z as f32,
+                0.0,
+            ],
+            direction: to_vec4(&camera.direction),
+            right: to_vec4(&camera.right),
+            up: to_vec4(&camera.up),
+            tone: [
+                settings.exposure.exp2(),
+                settings.tone_mapping as u32 as f32,
+                0.0,
+                0.0,
+            ],
+            ..self.base
+        };
+        self.queue
+            .write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&uniforms));
+
+        let [width, height, _, _] = self.base.size;
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("tracer"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(
+                (width + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+                (height + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&self.output, 0, &self.readback, 0, self.output.size());
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = self.readback.slice(..);
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        // Native backends need to be polled to make progress, this is a no-op
+        // in the browser
+        self.device.poll(wgpu::Maintain::Wait);
+
+        if !matches!(receiver.await, Ok(Ok(()))) {
+            return false;
+        }
+
+        pixels.copy_from_slice(&slice.get_mapped_range());
+        self.readback.unmap();
+        return true;
+    }
+}
+
+fn to_vec4(v: &na::Vector3<f64>) -> [f32; 4] {
+    [v.x as f32, v.y as f32, v.z as f32, 0.0]
+}
@@ -6,11 +6,25 @@ extern crate nalgebra as na;
 use na::{Point3, Vector3};
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
 
+mod bvh;
+mod camera;
+#[cfg(feature = "webgpu")]
+mod gpu;
+#[cfg(feature = "obj")]
+mod obj;
 mod rendering;
 mod scene;
+mod tonemap;
 
+use bvh::Bvh;
+use camera::ViewCamera;
 use rendering::{Camera, Element, Ray};
-use scene::{Color, Light, Scene, Sphere};
+use scene::{
+    Color, DirectionalLight, LightSource, Material, Mesh, Plane, PointLight, Scene, Sphere,
+    SpotLight, Triangle,
+};
+use tonemap::RenderSettings;
+pub use tonemap::ToneMapping;
 
 #[wasm_bindgen]
 extern "C" {
@@ -31,48 +45,443 @@ impl PixelBuffer {
         let pixels = vec![0u8; (universe.scene.width * universe.scene.height * 4) as usize];
         return PixelBuffer { pixels: pixels };
     }
+
+    /// Copies pixels from `Universe::render_tile` into the frame
+    pub fn write_tile(&mut self, universe: &Universe, index: usize, pixels: &[u8]) {
+        let width = universe.scene.width;
+        let tile = tiles(&universe.scene)[index];
+        let row_length = (tile.width * 4) as usize;
+        for (row, tile_row) in pixels.chunks(row_length).enumerate() {
+            let start = (((tile.y + row as u32) * width + tile.x) * 4) as usize;
+            self.pixels[start..start + row_length].copy_from_slice(tile_row);
+        }
+    }
+}
+
+/// Side of the square tiles a frame is split into for rendering, tiles on the
+/// right and bottom edges are cut to fit
+const TILE_SIZE: u32 = 64;
+
+/// Part of a frame that can be rendered on its own, so tiles can be
+/// rendered in parallel by web workers
+#[derive(Clone, Copy)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Tiles covering the frame, left to right then top to bottom
+fn tiles(scene: &Scene) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..scene.height).step_by(TILE_SIZE as usize) {
+        for x in (0..scene.width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile {
+                x: x,
+                y: y,
+                width: TILE_SIZE.min(scene.width - x),
+                height: TILE_SIZE.min(scene.height - y),
+            });
+        }
+    }
+    return tiles;
 }
 
 #[wasm_bindgen]
 pub struct Universe {
     scene: Scene,
+    camera: ViewCamera,
+    settings: RenderSettings,
 }
 
 #[wasm_bindgen]
 impl Universe {
     pub fn new() -> Universe {
         let scene = create_scene();
-        return Universe { scene: scene };
-    }
-
-    /// Renders frame with camera at specied point arount a circle
-    pub fn render(&mut self, angle: f64, pixel_buffer: &mut PixelBuffer) {
-        let camera = create_camera(angle);
-        for y in 0..self.scene.height {
-            let start_index = (y * self.scene.width * 4) as usize;
-            let end_index = ((y + 1) * self.scene.width * 4) as usize;
-            let pixel_column = &mut pixel_buffer.pixels[start_index..end_index];
-            for (x, chunk) in pixel_column.chunks_mut(4).enumerate() {
-                render_pixel(x as u32, y, chunk, &self.scene, &camera);
+        return Universe {
+            scene: scene,
+            camera: create_camera(),
+            settings: RenderSettings {
+                exposure: 0.0,
+                tone_mapping: ToneMapping::Aces,
+            },
+        };
+    }
+
+    /// Renders a frame from the camera with `samples` rays per pixel
+    ///
+    /// Renders one tile at a time on the calling thread, the site uses
+    /// `render_tile` from web workers instead when it can.
+    pub fn render(&mut self, pixel_buffer: &mut PixelBuffer, samples: u32) {
+        let camera = self.camera.camera();
+        let width = self.scene.width;
+        for tile in tiles(&self.scene) {
+            let start_index = ((tile.y * width + tile.x) * 4) as usize;
+            let pixels = &mut pixel_buffer.pixels[start_index..];
+            render_tile(
+                &tile,
+                pixels,
+                width,
+                &self.scene,
+                &camera,
+                samples,
+                &self.settings,
+            );
+        }
+    }
+
+    /// Number of tiles in a frame, see `render_tile`
+    pub fn tile_count(&self) -> usize {
+        tiles(&self.scene).len()
+    }
+
+    /// Renders one tile of the frame from the camera with `samples` rays per
+    /// pixel, returns the tile's rgba pixels for `PixelBuffer::write_tile`
+    pub fn render_tile(&self, index: usize, samples: u32) -> Vec<u8> {
+        let camera = self.camera.camera();
+        let tile = tiles(&self.scene)[index];
+        let mut pixels = vec![0u8; (tile.width * tile.height * 4) as usize];
+        render_tile(
+            &tile,
+            &mut pixels,
+            tile.width,
+            &self.scene,
+            &camera,
+            samples,
+            &self.settings,
+        );
+        return pixels;
+    }
+
+    /// Brightens the frame by `stops` before tone mapping, each stop doubles
+    /// it and negative stops darken
+    pub fn set_exposure(&mut self, stops: f32) {
+        self.settings.exposure = stops;
+    }
+
+    /// Sets how colors too bright for the display are brought into range
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.settings.tone_mapping = tone_mapping;
+    }
+
+    pub fn set_camera_position(&mut self, x: f64, y: f64, z: f64) {
+        self.camera.position = Point3::new(x, y, z);
+    }
+
+    /// Turns the camera to face a point, orbiting and dollying move around it
+    pub fn look_at(&mut self, x: f64, y: f64, z: f64) {
+        self.camera.target = Point3::new(x, y, z);
+    }
+
+    /// Sets the field of view across the image in degrees
+    pub fn set_camera_fov(&mut self, degrees: f64) {
+        self.camera.set_fov(degrees);
+    }
+
+    /// Moves the camera `distance` towards the point it looks at, negative
+    /// moves away
+    pub fn dolly_camera(&mut self, distance: f64) {
+        self.camera.dolly(distance);
+    }
+
+    /// Slides the camera and the point it looks at across the screen
+    pub fn pan_camera(&mut self, right: f64, up: f64) {
+        self.camera.pan(right, up);
+    }
+
+    /// Circles the camera around the point it looks at, `yaw` degrees around
+    /// the vertical and `pitch` degrees over the top, stopping short of
+    /// straight up or down
+    pub fn orbit_camera(&mut self, yaw: f64, pitch: f64) {
+        self.camera.orbit(yaw, pitch);
+    }
+
+    /// Adds a light shining along a direction, like the sun, and returns its
+    /// index
+    pub fn add_directional_light(&mut self, dx: f64, dy: f64, dz: f64, intensity: f32) -> usize {
+        self.scene
+            .lights
+            .push(LightSource::Directional(DirectionalLight {
+                direction: Vector3::new(dx, dy, dz),
+                intensity: intensity,
+            }));
+        return self.scene.lights.len() - 1;
+    }
+
+    /// Adds a light shining in every direction from a point and returns its
+    /// index. Intensity fades to `intensity / (1 + falloff * distance^2)`.
+    pub fn add_point_light(
+        &mut self,
+        x: f64,
+        y: f64,
+        z: f64,
+        intensity: f32,
+        falloff: f32,
+    ) -> usize {
+        self.scene.lights.push(LightSource::Point(PointLight {
+            position: Point3::new(x, y, z),
+            intensity: intensity,
+            falloff: falloff,
+        }));
+        return self.scene.lights.len() - 1;
+    }
+
+    /// Adds a point light that only shines within `cone_angle` degrees of a
+    /// direction and returns its index
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_spot_light(
+        &mut self,
+        x: f64,
+        y: f64,
+        z: f64,
+        dx: f64,
+        dy: f64,
+        dz: f64,
+        cone_angle: f64,
+        intensity: f32,
+        falloff: f32,
+    ) -> usize {
+        self.scene.lights.push(LightSource::Spot(SpotLight {
+            position: Point3::new(x, y, z),
+            direction: Vector3::new(dx, dy, dz),
+            cone_angle: cone_angle.to_radians(),
+            intensity: intensity,
+            falloff: falloff,
+        }));
+        return self.scene.lights.len() - 1;
+    }
+
+    /// Moves a point or spot light, directional lights have no position
+    pub fn move_light(&mut self, index: usize, x: f64, y: f64, z: f64) -> Result<(), JsValue> {
+        match self.scene.lights.get_mut(index) {
+            Some(LightSource::Point(l)) => l.position = Point3::new(x, y, z),
+            Some(LightSource::Spot(l)) => l.position = Point3::new(x, y, z),
+            Some(LightSource::Directional(_)) => {
+                return Err(JsValue::from_str("directional lights can't be moved"))
+            }
+            None => return Err(no_light(index)),
+        }
+        Ok(())
+    }
+
+    /// Points a directional or spot light in a new direction
+    pub fn aim_light(&mut self, index: usize, dx: f64, dy: f64, dz: f64) -> Result<(), JsValue> {
+        match self.scene.lights.get_mut(index) {
+            Some(LightSource::Directional(l)) => l.direction = Vector3::new(dx, dy, dz),
+            Some(LightSource::Spot(l)) => l.direction = Vector3::new(dx, dy, dz),
+            Some(LightSource::Point(_)) => {
+                return Err(JsValue::from_str("point lights shine in every direction"))
             }
+            None => return Err(no_light(index)),
+        }
+        Ok(())
+    }
 
-            pixel_column
-                .chunks_mut(4)
-                .enumerate()
-                .for_each(|(x, chunk)| render_pixel(x as u32, y, chunk, &self.scene, &camera));
+    /// Removes a light, lights after it move down one index
+    pub fn remove_light(&mut self, index: usize) -> Result<(), JsValue> {
+        if index >= self.scene.lights.len() {
+            return Err(no_light(index));
+        }
+        self.scene.lights.remove(index);
+        Ok(())
+    }
+
+    pub fn light_count(&self) -> usize {
+        self.scene.lights.len()
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.scene.elements.len()
+    }
+
+    /// Moves `count` elements starting at `first`, like the triangles of a
+    /// mesh from `add_obj`, and refits the bvh around them
+    pub fn translate_elements(
+        &mut self,
+        first: usize,
+        count: usize,
+        dx: f64,
+        dy: f64,
+        dz: f64,
+    ) -> Result<(), JsValue> {
+        let elements = self
+            .scene
+            .elements
+            .get_mut(first..first + count)
+            .ok_or_else(|| {
+                JsValue::from_str(&format!("no elements at {}..{}", first, first + count))
+            })?;
+        for element in elements {
+            element.translate(Vector3::new(dx, dy, dz));
+        }
+        self.scene.refit_bvh();
+        Ok(())
+    }
+
+    /// Rebuilds the bvh from scratch, refitting after many moves leaves it
+    /// slower to trace than a fresh one
+    pub fn rebuild_bvh(&mut self) {
+        self.scene.rebuild_bvh();
+    }
+
+    /// Updates the bvh's bounds without changing its structure
+    pub fn refit_bvh(&mut self) {
+        self.scene.refit_bvh();
+    }
+}
+
+fn no_light(index: usize) -> JsValue {
+    JsValue::from_str(&format!("no light at index {}", index))
+}
+
+#[cfg(feature = "obj")]
+#[wasm_bindgen]
+impl Universe {
+    /// Adds the triangles of a Wavefront OBJ file to the scene and returns the
+    /// index of the first one, the rest follow it
+    pub fn add_obj(&mut self, source: &str, r: u8, g: u8, b: u8) -> Result<usize, JsValue> {
+        let mesh = obj::parse_obj(source, Material::diffuse(Color::from_srgb(r, g, b)))
+            .map_err(|e| JsValue::from_str(&e))?;
+        let first = self.scene.elements.len();
+        self.scene.elements.extend(mesh.elements());
+        self.scene.rebuild_bvh();
+        Ok(first)
+    }
+}
+
+/// Renders frames with the WebGPU compute backend
+#[cfg(feature = "webgpu")]
+#[wasm_bindgen]
+pub struct GpuRenderer {
+    tracer: std::rc::Rc<gpu::GpuTracer>,
+    frame: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+}
+
+#[cfg(feature = "webgpu")]
+#[wasm_bindgen]
+impl GpuRenderer {
+    /// Resolves to a renderer for the universe's scene, or undefined if WebGPU
+    /// isn't available or can't trace the scene, see `GpuTracer::supports`,
+    /// and the cpu tracer should be used instead
+    pub fn create(universe: &Universe, samples: u32) -> js_sys::Promise {
+        let scene = universe.scene.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let renderer = gpu::GpuTracer::new(&scene, samples)
+                .await
+                .map(|tracer| GpuRenderer {
+                    tracer: std::rc::Rc::new(tracer),
+                    frame: std::rc::Rc::new(std::cell::RefCell::new(vec![
+                        0u8;
+                        (scene.width * scene.height * 4)
+                            as usize
+                    ])),
+                });
+            Ok(renderer.map_or(JsValue::UNDEFINED, JsValue::from))
+        })
+    }
+
+    /// Renders a frame from the universe's camera and render settings,
+    /// resolves once the frame can be copied with `copy_to`
+    pub fn render(&self, universe: &Universe) -> js_sys::Promise {
+        let tracer = self.tracer.clone();
+        let frame = self.frame.clone();
+        let camera = universe.camera.camera();
+        let settings = universe.settings;
+        wasm_bindgen_futures::future_to_promise(async move {
+            let mut pixels = vec![0u8; frame.borrow().len()];
+            if tracer.render(&camera, &settings, &mut pixels).await {
+                *frame.borrow_mut() = pixels;
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    pub fn copy_to(&self, pixel_buffer: &mut PixelBuffer) {
+        pixel_buffer.pixels.copy_from_slice(&self.frame.borrow());
+    }
+}
+
+/// Renders `tile` into `pixels`, starting at the tile's top left corner with
+/// rows `stride` pixels apart
+fn render_tile(
+    tile: &Tile,
+    pixels: &mut [u8],
+    stride: u32,
+    scene: &Scene,
+    camera: &Camera,
+    samples: u32,
+    settings: &RenderSettings,
+) {
+    for row in 0..tile.height {
+        let start_index = (row * stride * 4) as usize;
+        let end_index = start_index + (tile.width * 4) as usize;
+        let pixel_row = &mut pixels[start_index..end_index];
+        for (x, chunk) in pixel_row.chunks_mut(4).enumerate() {
+            render_pixel(
+                tile.x + x as u32,
+                tile.y + row,
+                chunk,
+                scene,
+                camera,
+                samples,
+                settings,
+            );
         }
     }
 }
 
-fn render_pixel(x: u32, y: u32, pixel_chunk: &mut [u8], scene: &Scene, camera: &Camera) {
-    let ray = Ray::create_prime(x, y, scene, camera);
-    let color = rendering::cast_ray(scene, &ray);
-    pixel_chunk[0] = color.r;
-    pixel_chunk[1] = color.g;
-    pixel_chunk[2] = color.b;
+/// Averages `samples` rays through the pixel, jittered across it so edges
+/// blend, 1 sample goes through the middle
+///
+/// Samples are averaged as linear colors, then tone mapped and gamma encoded
+/// for the pixel.
+fn render_pixel(
+    x: u32,
+    y: u32,
+    pixel_chunk: &mut [u8],
+    scene: &Scene,
+    camera: &Camera,
+    samples: u32,
+    settings: &RenderSettings,
+) {
+    let samples = samples.max(1);
+    let mut sum = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+    };
+    for sample in 0..samples {
+        let offset = if samples == 1 {
+            (0.5, 0.5)
+        } else {
+            jitter(x, y, sample)
+        };
+        let ray = Ray::create_prime(x, y, offset, scene, camera);
+        sum = sum + rendering::cast_ray(scene, &ray, 0);
+    }
+    let [r, g, b] = settings.to_srgb8(sum * (1.0 / samples as f32));
+    pixel_chunk[0] = r;
+    pixel_chunk[1] = g;
+    pixel_chunk[2] = b;
     pixel_chunk[3] = 255u8; // no transparency
 }
 
+/// Offset within a pixel for one of its samples, each between 0 and 1
+///
+/// Hashes the pixel and sample instead of drawing random numbers, so a tile
+/// renders the same in any worker.
+fn jitter(x: u32, y: u32, sample: u32) -> (f64, f64) {
+    // splitmix64 finalizer, from: https://prng.di.unimi.it/splitmix64.c
+    let mut z =
+        ((x as u64) << 40 ^ (y as u64) << 20 ^ sample as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    let unit = |bits: u64| (bits & 0xffff_ffff) as f64 / (1u64 << 32) as f64;
+    (unit(z), unit(z >> 32))
+}
+
 /// Paint pixels to a canvas
 #[wasm_bindgen]
 pub struct Painter {
@@ -116,63 +525,99 @@ fn get_canvas() -> CanvasRenderingContext2d {
 
 /// Create a basic scene with a few objects and some lighting.
 fn create_scene() -> Scene {
-    let red = Color { r: 200, g: 0, b: 0 };
+    let red = Color::from_srgb(200, 0, 0);
 
-    let green = Color { r: 0, g: 200, b: 0 };
+    let green = Color::from_srgb(0, 200, 0);
 
-    let blue = Color { r: 0, g: 0, b: 200 };
+    let blue = Color::from_srgb(0, 0, 200);
 
     let sphere1 = Element::Sphere(Sphere {
         center: Point3::new(0.0, 0.0, -2.0),
         radius: 0.5,
-        color: red,
+        material: Material::diffuse(red),
     });
 
     let sphere2 = Element::Sphere(Sphere {
         center: Point3::new(-1.0, 0.0, -3.0),
         radius: 0.5,
-        color: green,
+        material: Material {
+            reflectivity: 0.6,
+            ..Material::diffuse(green)
+        },
     });
 
     let sphere3 = Element::Sphere(Sphere {
         center: Point3::new(1.5, 0.0, -4.0),
         radius: 0.5,
-        color: blue,
+        material: Material {
+            transparency: 0.8,
+            ior: 1.5,
+            ..Material::diffuse(blue)
+        },
+    });
+
+    let floor = Element::Plane(Plane {
+        origin: Point3::new(0.0, -0.5, 0.0),
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        material: Material {
+            reflectivity: 0.2,
+            ..Material::diffuse(Color::from_srgb(150, 150, 150))
+        },
     });
 
+    let mut pyramid = create_pyramid(0.8, Material::diffuse(Color::from_srgb(200, 200, 0)));
+    pyramid.translate(Vector3::new(0.0, -0.5, -4.0));
+
+    let mut elements = vec![sphere1, sphere2, sphere3, floor];
+    elements.extend(pyramid.elements());
+
     let scene = Scene {
         width: 500,
         height: 500,
-        light: Light {
+        lights: vec![LightSource::Directional(DirectionalLight {
             direction: Vector3::new(1.0, -1.0, 0.0),
             intensity: 30.0,
-        },
-        elements: vec![sphere1, sphere2, sphere3],
+        })],
+        bvh: Bvh::build(&elements),
+        elements: elements,
+        max_depth: 5,
     };
     return scene;
 }
 
-// Create a camera that rotates around the scene
-fn create_camera(angle: f64) -> Camera {
-    // Calculate camera postion as point on a circle
-    // Adapted from: https://stackoverflow.com/questions/839899/how-do-i-calculate-a-point-on-a-circle-s-circumference
-    let radius = 5.0; // Radius of orbit
-    let orbit_center = Point3::new(0.0, 0.0, -3.0);
-    let x = orbit_center.x + radius * angle.cos();
-    let z = orbit_center.z + radius * angle.sin();
-    let camera_location = Point3::new(x, 0.0, z);
-
-    // From: https://stackoverflow.com/questions/13078243/how-to-move-a-camera-using-in-a-ray-tracer
-    let camera_direction: Vector3<f64> = (orbit_center - camera_location).normalize();
-    let initial_camera_up = Vector3::new(0.0, 1.0, 0.0);
-    let camera_right = initial_camera_up.cross(&camera_direction);
-    let camera_up = camera_right.cross(&camera_direction);
-
-    let camera = Camera {
-        direction: camera_direction,
-        location: camera_location,
-        right: camera_right,
-        up: camera_up,
+/// Square based pyramid standing on the origin, `size` wide and tall
+fn create_pyramid(size: f64, material: Material) -> Mesh {
+    let half = size / 2.0;
+    let top = Point3::new(0.0, size, 0.0);
+    let corners = [
+        Point3::new(-half, 0.0, half),
+        Point3::new(half, 0.0, half),
+        Point3::new(half, 0.0, -half),
+        Point3::new(-half, 0.0, -half),
+    ];
+
+    // Sides, counter-clockwise from outside
+    let mut triangles = Vec::new();
+    for i in 0..corners.len() {
+        triangles.push(Triangle {
+            a: corners[i],
+            b: corners[(i + 1) % corners.len()],
+            c: top,
+            material: material,
+        });
+    }
+    return Mesh {
+        triangles: triangles,
     };
-    return camera;
+}
+
+/// Camera looking at the middle of the scene from the side
+fn create_camera() -> ViewCamera {
+    // About 53 degrees, the image is as wide as it is far from the camera
+    let fov = 2.0 * 0.5f64.atan().to_degrees();
+    ViewCamera::new(
+        Point3::new(5.0, 0.0, -3.0),
+        Point3::new(0.0, 0.0, -3.0),
+        fov,
+    )
 }
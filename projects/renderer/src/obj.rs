@@ -0,0 +1,165 @@
+use na::Point3;
+
+use crate::scene::{Material, Mesh, Triangle};
+
+/// Parses the vertices and faces of a Wavefront OBJ file
+///
+/// wasm can't read files, so the site fetches the file and passes its
+/// contents. Faces with more than 3 vertices are split into a fan of
+/// triangles. Texture coordinates, normals, groups and materials are
+/// ignored, every triangle gets `material`.
+pub fn parse_obj(source: &str, material: Material) -> Result<Mesh, String> {
+    let mut vertices: Vec<Point3<f64>> = Vec::new();
+    let mut triangles = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                let coords = parts
+                    .take(3)
+                    .map(|p| p.parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|e| format!("line {}: {}", i + 1, e))?;
+                if coords.len() != 3 {
+                    return Err(format!("line {}: vertex needs 3 coordinates", i + 1));
+                }
+                vertices.push(Point3::new(coords[0], coords[1], coords[2]));
+            }
+            Some("f") => {
+                let face = parts
+                    .map(|p| parse_index(p, vertices.len()))
+                    .collect::<Option<Vec<usize>>>()
+                    .ok_or_else(|| format!("line {}: invalid vertex index", i + 1))?;
+                if face.len() < 3 {
+                    return Err(format!("line {}: face needs at least 3 vertices", i + 1));
+                }
+                for j in 1..face.len() - 1 {
+                    triangles.push(Triangle {
+                        a: vertices[face[0]],
+                        b: vertices[face[j]],
+                        c: vertices[face[j + 1]],
+                        material: material,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    return Ok(Mesh {
+        triangles: triangles,
+    });
+}
+
+/// Returns the 0 based index of a face vertex like `3`, `3/1` or `3/1/2`,
+/// negative indexes count back from the last vertex read so far
+fn parse_index(s: &str, num_vertices: usize) -> Option<usize> {
+    let index = s.split('/').next()?.parse::<i64>().ok()?;
+    let index = if index < 0 {
+        num_vertices as i64 + index
+    } else {
+        index - 1
+    };
+
+    if index < 0 || index >= num_vertices as i64 {
+        return None;
+    }
+    return Some(index as usize);
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::parse_obj;
+    use crate::scene::{Color, Material};
+
+    fn material() -> Material {
+        Material::diffuse(Color::from_srgb(200, 200, 200))
+    }
+
+    #[test]
+    fn test_parse_obj_fan() {
+        let source = "# square
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3 4
+";
+        let mesh = parse_obj(source, material()).unwrap();
+        assert_eq!(mesh.triangles.len(), 2);
+        // every triangle shares the first vertex
+        let corners = |t: usize| {
+            let t = &mesh.triangles[t];
+            [t.a, t.b, t.c]
+        };
+        assert_eq!(
+            corners(0),
+            [
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0)
+            ]
+        );
+        assert_eq!(
+            corners(1),
+            [
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_obj_indices() {
+        // negative indexes count back from the vertices read so far, texture
+        // and normal indexes are ignored
+        let source = "v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+f -3 -2 -1
+f 1/1/1 2/1/1 3/1/1
+f 1//1 2//1 3//1
+f 1/1 2/1 3/1
+v 5 5 5
+f -1 1 2
+";
+        let mesh = parse_obj(source, material()).unwrap();
+        assert_eq!(mesh.triangles.len(), 5);
+        for t in &mesh.triangles[..4] {
+            assert_eq!(t.a, Point3::new(0.0, 0.0, 0.0));
+            assert_eq!(t.b, Point3::new(1.0, 0.0, 0.0));
+            assert_eq!(t.c, Point3::new(0.0, 1.0, 0.0));
+        }
+        assert_eq!(mesh.triangles[4].a, Point3::new(5.0, 5.0, 5.0));
+    }
+
+    #[test]
+    fn test_parse_obj_errors() {
+        let err = |source: &str| parse_obj(source, material()).err().unwrap();
+        assert_eq!(err("v 0 0\n"), "line 1: vertex needs 3 coordinates");
+        assert!(err("v 0 x 0\n").starts_with("line 1: "));
+        assert_eq!(
+            err("v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            "line 3: face needs at least 3 vertices"
+        );
+        // 0 and indexes past the vertices read so far
+        assert_eq!(
+            err("v 0 0 0\nv 1 0 0\nf 0 1 2\n"),
+            "line 3: invalid vertex index"
+        );
+        assert_eq!(
+            err("v 0 0 0\nv 1 0 0\nf 1 2 3\nv 0 1 0\n"),
+            "line 3: invalid vertex index"
+        );
+        assert_eq!(
+            err("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 1 2\n"),
+            "line 4: invalid vertex index"
+        );
+    }
+}
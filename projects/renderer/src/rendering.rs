@@ -1,4 +1,4 @@
-use crate::scene::{Color, Scene, Sphere};
+use crate::scene::{Color, Material, Plane, Scene, Sphere, Triangle};
 use na::{Point3, Vector3};
 
 pub struct Ray {
@@ -14,12 +14,18 @@ pub struct Camera {
     pub location: Point3<f64>,
 }
 
+/// Secondary rays start this far off the surface so they don't hit it again
+const SHADOW_BIAS: f64 = 1e-6;
+
 impl Ray {
     /// Create primes
     /// And https://stackoverflow.com/questions/13078243/how-to-move-a-camera-using-in-a-ray-tracer
-    pub fn create_prime(x: u32, y: u32, scene: &Scene, camera: &Camera) -> Ray {
-        let normalized_x = 1.0 - (x as f64 / scene.width as f64) - 0.5;
-        let normalized_y = (y as f64 / scene.height as f64) - 0.5;
+    ///
+    /// `offset` is where in the pixel the ray goes through, each between 0
+    /// and 1 from the top left corner.
+    pub fn create_prime(x: u32, y: u32, offset: (f64, f64), scene: &Scene, camera: &Camera) -> Ray {
+        let normalized_x = 1.0 - ((x as f64 + offset.0) / scene.width as f64) - 0.5;
+        let normalized_y = ((y as f64 + offset.1) / scene.height as f64) - 0.5;
 
         let direction: Vector3<f64> =
             normalized_x * camera.right + normalized_y * camera.up + camera.direction;
@@ -28,16 +34,96 @@ impl Ray {
             direction: direction.normalize(),
         }
     }
+
+    /// Mirror reflection of `incident` at a hit point with `normal`
+    ///
+    /// Adapted from: https://bheisler.github.io/post/writing-raytracer-in-rust-part-3/
+    pub fn create_reflection(
+        normal: Vector3<f64>,
+        incident: Vector3<f64>,
+        hit_point: Point3<f64>,
+    ) -> Ray {
+        let normal = facing(normal, incident);
+        Ray {
+            origin: hit_point + normal * SHADOW_BIAS,
+            direction: incident - (2.0 * incident.dot(&normal) * normal),
+        }
+    }
+
+    /// Ray that continues through a transparent surface, bent by Snell's law
+    ///
+    /// Rays leaving the surface go from `ior` back to air. Returns None for
+    /// total internal reflection, where nothing gets through.
+    pub fn create_transmission(
+        normal: Vector3<f64>,
+        incident: Vector3<f64>,
+        hit_point: Point3<f64>,
+        ior: f64,
+    ) -> Option<Ray> {
+        let mut ref_n = normal;
+        let mut eta_t = ior;
+        let mut eta_i = 1.0;
+        let mut i_dot_n = incident.dot(&normal);
+        if i_dot_n < 0.0 {
+            // Entering the surface
+            i_dot_n = -i_dot_n;
+        } else {
+            // Leaving the surface, the normal points the wrong way
+            ref_n = -normal;
+            eta_i = ior;
+            eta_t = 1.0;
+        }
+
+        let eta = eta_i / eta_t;
+        let k = 1.0 - (eta * eta) * (1.0 - i_dot_n * i_dot_n);
+        if k < 0.0 {
+            return None;
+        }
+        Some(Ray {
+            origin: hit_point - ref_n * SHADOW_BIAS,
+            direction: ((incident + i_dot_n * ref_n) * eta - ref_n * k.sqrt()).normalize(),
+        })
+    }
 }
 
+/// Flips `normal` to the side `incident` came from
+fn facing(normal: Vector3<f64>, incident: Vector3<f64>) -> Vector3<f64> {
+    if normal.dot(&incident) > 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/// Distances and determinants smaller than this are treated as misses
+const EPSILON: f64 = 1e-9;
+
+#[derive(Clone)]
 pub enum Element {
     Sphere(Sphere),
+    Plane(Plane),
+    Triangle(Triangle),
 }
 
 impl Element {
-    pub fn color(&self) -> &Color {
+    pub fn material(&self) -> &Material {
         match *self {
-            Element::Sphere(ref s) => &s.color,
+            Element::Sphere(ref s) => &s.material,
+            Element::Plane(ref p) => &p.material,
+            Element::Triangle(ref t) => &t.material,
+        }
+    }
+
+    /// Moves the element by `offset`
+    pub fn translate(&mut self, offset: Vector3<f64>) {
+        match *self {
+            Element::Sphere(ref mut s) => s.center += offset,
+            Element::Plane(ref mut p) => p.origin += offset,
+            Element::Triangle(ref mut t) => {
+                t.a += offset;
+                t.b += offset;
+                t.c += offset;
+            }
         }
     }
 }
@@ -52,12 +138,16 @@ impl Intersectable for Element {
     fn intersect(&self, ray: &Ray) -> Option<f64> {
         match *self {
             Element::Sphere(ref s) => s.intersect(ray),
+            Element::Plane(ref p) => p.intersect(ray),
+            Element::Triangle(ref t) => t.intersect(ray),
         }
     }
 
     fn surface_normal(&self, hit_point: &Point3<f64>) -> Vector3<f64> {
         match *self {
             Element::Sphere(ref s) => s.surface_normal(hit_point),
+            Element::Plane(ref p) => p.surface_normal(hit_point),
+            Element::Triangle(ref t) => t.surface_normal(hit_point),
         }
     }
 }
@@ -86,7 +176,9 @@ impl Intersectable for Sphere {
             return None;
         }
 
-        let distance = if t0 < t1 { t0 } else { t1 };
+        // Rays that start inside the sphere, like refracted rays, hit the
+        // far side
+        let distance = if t0 < 0.0 { t1 } else { t0 };
         Some(distance)
     }
 
@@ -95,46 +187,245 @@ impl Intersectable for Sphere {
     }
 }
 
-fn get_color(scene: &Scene, ray: &Ray, distance: f64, element: &Element) -> Color {
+impl Intersectable for Plane {
+    fn intersect(&self, ray: &Ray) -> Option<f64> {
+        let normal = self.normal.normalize();
+        let denom = normal.dot(&ray.direction);
+        // Parallel to the plane
+        if denom.abs() < EPSILON {
+            return None;
+        }
+
+        let distance = (self.origin - ray.origin).dot(&normal) / denom;
+        if distance < EPSILON {
+            return None;
+        }
+        Some(distance)
+    }
+
+    fn surface_normal(&self, _: &Point3<f64>) -> Vector3<f64> {
+        self.normal.normalize()
+    }
+}
+
+impl Intersectable for Triangle {
+    fn intersect(&self, ray: &Ray) -> Option<f64> {
+        // Möller–Trumbore, adapted from: https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+        let edge1 = self.b - self.a;
+        let edge2 = self.c - self.a;
+        let h = ray.direction.cross(&edge2);
+        let det = edge1.dot(&h);
+        // Parallel to the triangle
+        if det.abs() < EPSILON {
+            return None;
+        }
+
+        // Barycentric coordinates of the hit point, both need to be in the
+        // triangle
+        let inv_det = 1.0 / det;
+        let s = ray.origin - self.a;
+        let u = inv_det * s.dot(&h);
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(&edge1);
+        let v = inv_det * ray.direction.dot(&q);
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = inv_det * edge2.dot(&q);
+        if distance < EPSILON {
+            return None;
+        }
+        Some(distance)
+    }
+
+    fn surface_normal(&self, _: &Point3<f64>) -> Vector3<f64> {
+        (self.b - self.a).cross(&(self.c - self.a)).normalize()
+    }
+}
+
+/// Diffuse color at a hit point, summed over the lights that aren't blocked
+/// by something between them and the hit point
+fn shade_diffuse(
+    scene: &Scene,
+    material: &Material,
+    hit_point: Point3<f64>,
+    surface_normal: Vector3<f64>,
+) -> Color {
+    let light_reflected = material.albedo / std::f32::consts::PI;
+    let mut color = BLACK;
+    for light in &scene.lights {
+        let direction_to_light = light.direction_from(&hit_point);
+        let facing_light = surface_normal.dot(&direction_to_light) as f32;
+        if facing_light <= 0.0 {
+            continue;
+        }
+        let intensity = light.intensity_at(&hit_point);
+        if intensity <= 0.0 {
+            continue;
+        }
+
+        let shadow_ray = Ray {
+            origin: hit_point + surface_normal * SHADOW_BIAS,
+            direction: direction_to_light,
+        };
+        let light_distance = light.distance(&hit_point);
+        let in_shadow = trace(scene, &shadow_ray).is_some_and(|(d, _)| d < light_distance);
+        if in_shadow {
+            continue;
+        }
+
+        let light_power = facing_light * intensity;
+        color = color + material.color * (light_power * light_reflected);
+    }
+    color
+}
+
+/// Fraction of light reflected rather than refracted at a transparent
+/// surface
+///
+/// Adapted from: https://bheisler.github.io/post/writing-raytracer-in-rust-part-3/
+fn fresnel(incident: Vector3<f64>, normal: Vector3<f64>, ior: f64) -> f64 {
+    let i_dot_n = incident.dot(&normal);
+    let mut eta_i = 1.0;
+    let mut eta_t = ior;
+    if i_dot_n > 0.0 {
+        eta_i = eta_t;
+        eta_t = 1.0;
+    }
+
+    let sin_t = eta_i / eta_t * (1.0 - i_dot_n * i_dot_n).max(0.0).sqrt();
+    if sin_t > 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t * sin_t).max(0.0).sqrt();
+    let cos_i = i_dot_n.abs();
+    let r_s = ((eta_t * cos_i) - (eta_i * cos_t)) / ((eta_t * cos_i) + (eta_i * cos_t));
+    let r_p = ((eta_i * cos_i) - (eta_t * cos_t)) / ((eta_i * cos_i) + (eta_t * cos_t));
+    (r_s * r_s + r_p * r_p) / 2.0
+}
+
+fn get_color(scene: &Scene, ray: &Ray, distance: f64, element: &Element, depth: u32) -> Color {
     let hit_point = ray.origin + (ray.direction * distance);
     let surface_normal = element.surface_normal(&hit_point);
-    let direction_to_light = -scene.light.direction.normalize();
-    let light_power =
-        (surface_normal.dot(&direction_to_light) as f32).max(0.0) * scene.light.intensity;
-    const ALBEDO: f32 = 0.18; // placeholder
-    let light_reflected = ALBEDO / std::f32::consts::PI;
-
-    let color = Color {
-        r: (element.color().r as f32 * light_power * light_reflected) as u8,
-        g: (element.color().g as f32 * light_power * light_reflected) as u8,
-        b: (element.color().b as f32 * light_power * light_reflected) as u8,
-    };
-    color.clamp()
-}
+    let material = element.material();
 
-const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+    let diffuse = 1.0 - material.reflectivity - material.transparency;
+    let mut color = shade_diffuse(scene, material, hit_point, surface_normal) * diffuse.max(0.0);
 
-pub fn cast_ray(scene: &Scene, ray: &Ray) -> Color {
-    let mut closest_element: Option<&Element> = None;
-    let mut closest_distance: Option<f64> = None;
-    for element in &scene.elements {
-        let distance = element.intersect(&ray);
-        if !distance.is_none() && (closest_distance.is_none() || distance < closest_distance) {
-            closest_distance = distance;
-            closest_element = Some(&element);
+    if material.reflectivity > 0.0 {
+        let reflection_ray = Ray::create_reflection(surface_normal, ray.direction, hit_point);
+        color = color + cast_ray(scene, &reflection_ray, depth + 1) * material.reflectivity;
+    }
+
+    if material.transparency > 0.0 {
+        let kr = fresnel(ray.direction, surface_normal, material.ior) as f32;
+        let reflection_ray = Ray::create_reflection(surface_normal, ray.direction, hit_point);
+        let mut transmitted = cast_ray(scene, &reflection_ray, depth + 1) * kr;
+        if kr < 1.0 {
+            if let Some(transmission_ray) =
+                Ray::create_transmission(surface_normal, ray.direction, hit_point, material.ior)
+            {
+                transmitted =
+                    transmitted + cast_ray(scene, &transmission_ray, depth + 1) * (1.0 - kr);
+            }
         }
+        color = color + transmitted * material.transparency;
     }
 
-    let color = if !closest_element.is_none() {
-        get_color(
-            &scene,
-            &ray,
-            closest_distance.unwrap(),
-            closest_element.unwrap(),
-        )
-    } else {
-        BLACK
+    color
+}
+
+const BLACK: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+};
+
+/// Returns the distance to the closest element the ray hits
+fn trace<'a>(scene: &'a Scene, ray: &Ray) -> Option<(f64, &'a Element)> {
+    scene.bvh.intersect(&scene.elements, ray)
+}
+
+/// Returns the color seen along a ray, `depth` is the number of bounces so
+/// far and rays past `scene.max_depth` are black
+pub fn cast_ray(scene: &Scene, ray: &Ray, depth: u32) -> Color {
+    if depth > scene.max_depth {
+        return BLACK;
+    }
+
+    let color = match trace(scene, ray) {
+        Some((distance, element)) => get_color(scene, ray, distance, element, depth),
+        None => BLACK,
     };
 
     return color;
 }
+
+#[cfg(test)]
+mod tests {
+    use na::{Point3, Vector3};
+
+    use super::{Intersectable, Ray};
+    use crate::scene::{Color, Material, Triangle};
+
+    /// Triangle in the z = -5 plane around the z axis
+    fn triangle() -> Triangle {
+        Triangle {
+            a: Point3::new(-1.0, -1.0, -5.0),
+            b: Point3::new(1.0, -1.0, -5.0),
+            c: Point3::new(0.0, 1.0, -5.0),
+            material: Material::diffuse(Color::from_srgb(200, 200, 200)),
+        }
+    }
+
+    fn ray(origin: Point3<f64>, direction: Vector3<f64>) -> Ray {
+        Ray {
+            origin: origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    #[test]
+    fn test_triangle_hit() {
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+        let hit = triangle().intersect(&ray(Point3::origin(), forward));
+        assert!((hit.unwrap() - 5.0).abs() < 1e-9);
+
+        // from behind, triangles are hit from both sides
+        let hit = triangle().intersect(&ray(Point3::new(0.0, 0.0, -10.0), -forward));
+        assert!((hit.unwrap() - 5.0).abs() < 1e-9);
+
+        // at an angle, through the middle of the bottom edge
+        let hit = triangle().intersect(&ray(
+            Point3::new(0.0, 4.0, 0.0),
+            Vector3::new(0.0, -5.0, -5.0),
+        ));
+        assert!((hit.unwrap() - 50f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_triangle_miss() {
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+        // outside the triangle, past its edges and corners
+        for origin in [
+            Point3::new(0.0, -1.5, 0.0),
+            Point3::new(0.9, 0.9, 0.0),
+            Point3::new(-0.9, 0.9, 0.0),
+            Point3::new(0.0, 1.5, 0.0),
+        ] {
+            assert_eq!(triangle().intersect(&ray(origin, forward)), None);
+        }
+        // pointing away
+        assert_eq!(triangle().intersect(&ray(Point3::origin(), -forward)), None);
+        // parallel to the triangle
+        let sideways = Vector3::new(1.0, 0.0, 0.0);
+        assert_eq!(
+            triangle().intersect(&ray(Point3::new(-5.0, 0.0, -5.0), sideways)),
+            None
+        );
+    }
+}
@@ -1,20 +1,78 @@
+use std::ops::{Add, Mul};
+
 use na::{Point3, Vector3};
 
+use crate::bvh::Bvh;
 use crate::rendering::Element;
+use crate::tonemap::decode_srgb;
 
+/// Linear color, 1 is the brightest a display shows but lit surfaces can be
+/// brighter, `RenderSettings` tone maps them down
 #[derive(Copy, Clone)]
 pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
 }
 
 impl Color {
-    pub fn clamp(&self) -> Color {
+    /// Color from 0-255 sRGB channels, like a color picker gives
+    pub fn from_srgb(r: u8, g: u8, b: u8) -> Color {
+        Color {
+            r: decode_srgb(r),
+            g: decode_srgb(g),
+            b: decode_srgb(b),
+        }
+    }
+}
+
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, other: Color) -> Color {
+        Color {
+            r: self.r + other.r,
+            g: self.g + other.g,
+            b: self.b + other.b,
+        }
+    }
+}
+
+impl Mul<f32> for Color {
+    type Output = Color;
+
+    fn mul(self, factor: f32) -> Color {
         Color {
-            r: self.r.min(255).max(0),
-            b: self.b.min(255).max(0),
-            g: self.g.min(255).max(0),
+            r: self.r * factor,
+            g: self.g * factor,
+            b: self.b * factor,
+        }
+    }
+}
+
+/// How a surface reflects and lets through light
+///
+/// `reflectivity` and `transparency` are between 0 and 1, the rest of the
+/// light is diffuse. Transparent surfaces bend rays by their index of
+/// refraction, `ior`, 1.5 is about glass.
+#[derive(Copy, Clone)]
+pub struct Material {
+    pub color: Color,
+    pub albedo: f32,
+    pub reflectivity: f32,
+    pub transparency: f32,
+    pub ior: f64,
+}
+
+impl Material {
+    /// Matte surface that doesn't reflect or let light through
+    pub fn diffuse(color: Color) -> Material {
+        Material {
+            color: color,
+            albedo: 0.18,
+            reflectivity: 0.0,
+            transparency: 0.0,
+            ior: 1.0,
         }
     }
 }
@@ -23,17 +81,146 @@ impl Color {
 pub struct Sphere {
     pub center: Point3<f64>,
     pub radius: f64,
-    pub color: Color,
+    pub material: Material,
+}
+
+/// Infinite plane through `origin`, lit on the side `normal` points to
+#[derive(Copy, Clone)]
+pub struct Plane {
+    pub origin: Point3<f64>,
+    pub normal: Vector3<f64>,
+    pub material: Material,
+}
+
+/// Triangle with corners in counter-clockwise order when seen from the front
+#[derive(Copy, Clone)]
+pub struct Triangle {
+    pub a: Point3<f64>,
+    pub b: Point3<f64>,
+    pub c: Point3<f64>,
+    pub material: Material,
+}
+
+/// Triangles that make up one object
+///
+/// Added to the scene as one element per triangle, see `elements`.
+#[derive(Clone)]
+pub struct Mesh {
+    pub triangles: Vec<Triangle>,
+}
+
+impl Mesh {
+    pub fn elements(&self) -> impl Iterator<Item = Element> + '_ {
+        self.triangles.iter().map(|t| Element::Triangle(*t))
+    }
+
+    /// Moves every triangle by `offset`
+    pub fn translate(&mut self, offset: Vector3<f64>) {
+        for t in &mut self.triangles {
+            t.a += offset;
+            t.b += offset;
+            t.c += offset;
+        }
+    }
+}
+
+/// Light from far away, like the sun, that lights everything equally
+#[derive(Clone)]
+pub struct DirectionalLight {
+    pub direction: Vector3<f64>,
+    pub intensity: f32,
+}
+
+/// Light shining in every direction from `position`
+///
+/// Intensity at distance `d` is `intensity / (1 + falloff * d^2)`, so 0
+/// falloff doesn't fade and 1 fades with the inverse square of the distance.
+#[derive(Clone)]
+pub struct PointLight {
+    pub position: Point3<f64>,
+    pub intensity: f32,
+    pub falloff: f32,
 }
 
-pub struct Light {
+/// Point light that only shines within `cone_angle` radians of `direction`
+#[derive(Clone)]
+pub struct SpotLight {
+    pub position: Point3<f64>,
     pub direction: Vector3<f64>,
+    pub cone_angle: f64,
     pub intensity: f32,
+    pub falloff: f32,
 }
 
+#[derive(Clone)]
+pub enum LightSource {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+impl LightSource {
+    /// Unit vector from `hit_point` towards the light
+    pub fn direction_from(&self, hit_point: &Point3<f64>) -> Vector3<f64> {
+        match *self {
+            LightSource::Directional(ref l) => -l.direction.normalize(),
+            LightSource::Point(ref l) => (l.position - hit_point).normalize(),
+            LightSource::Spot(ref l) => (l.position - hit_point).normalize(),
+        }
+    }
+
+    /// Distance from `hit_point` to the light, infinite for directional
+    /// lights
+    pub fn distance(&self, hit_point: &Point3<f64>) -> f64 {
+        match *self {
+            LightSource::Directional(_) => f64::INFINITY,
+            LightSource::Point(ref l) => (l.position - hit_point).norm(),
+            LightSource::Spot(ref l) => (l.position - hit_point).norm(),
+        }
+    }
+
+    /// Intensity that reaches `hit_point`, ignoring anything in the way
+    pub fn intensity_at(&self, hit_point: &Point3<f64>) -> f32 {
+        match *self {
+            LightSource::Directional(ref l) => l.intensity,
+            LightSource::Point(ref l) => {
+                attenuate(l.intensity, l.falloff, self.distance(hit_point))
+            }
+            LightSource::Spot(ref l) => {
+                let to_hit = (hit_point - l.position).normalize();
+                if to_hit.angle(&l.direction) > l.cone_angle {
+                    return 0.0;
+                }
+                attenuate(l.intensity, l.falloff, self.distance(hit_point))
+            }
+        }
+    }
+}
+
+fn attenuate(intensity: f32, falloff: f32, distance: f64) -> f32 {
+    intensity / (1.0 + falloff * (distance * distance) as f32)
+}
+
+#[derive(Clone)]
 pub struct Scene {
     pub width: u32,
     pub height: u32,
-    pub light: Light,
+    pub lights: Vec<LightSource>,
     pub elements: Vec<Element>,
+    /// Reflected and refracted rays bounce at most this many times
+    pub max_depth: u32,
+    /// Built from `elements`, see `rebuild_bvh` and `refit_bvh`
+    pub bvh: Bvh,
+}
+
+impl Scene {
+    /// Rebuilds the bvh after elements are added or removed
+    pub fn rebuild_bvh(&mut self) {
+        self.bvh = Bvh::build(&self.elements);
+    }
+
+    /// Updates the bvh after elements move
+    pub fn refit_bvh(&mut self) {
+        self.bvh.refit(&self.elements);
+    }
 }
@@ -0,0 +1,72 @@
+use wasm_bindgen::prelude::*;
+
+use crate::scene::Color;
+
+/// Curve that fits linear colors brighter than 1 into what a display can show
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// Clips anything brighter than 1 to white
+    Clamp,
+    /// `x / (1 + x)`, gets close to white but never reaches it
+    Reinhard,
+    /// Filmic curve from the Academy Color Encoding System
+    Aces,
+}
+
+impl ToneMapping {
+    /// Maps a linear channel to between 0 and 1
+    pub fn apply(&self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        match *self {
+            ToneMapping::Clamp => x.min(1.0),
+            ToneMapping::Reinhard => x / (1.0 + x),
+            ToneMapping::Aces => {
+                // Curve fit by Krzysztof Narkowicz, from:
+                // https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+                let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+                mapped.clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// How linear colors from the tracer become the pixels on screen
+#[derive(Copy, Clone)]
+pub struct RenderSettings {
+    /// In stops, each one doubles the brightness before tone mapping
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+}
+
+impl RenderSettings {
+    /// 0-255 sRGB channels for a linear color
+    pub fn to_srgb8(self, color: Color) -> [u8; 3] {
+        let scale = self.exposure.exp2();
+        let channel = |c: f32| encode_srgb(self.tone_mapping.apply(c * scale));
+        [channel(color.r), channel(color.g), channel(color.b)]
+    }
+}
+
+/// Gamma encodes a linear channel between 0 and 1 for display
+///
+/// From: https://en.wikipedia.org/wiki/SRGB#Transfer_function_(%22gamma%22)
+pub fn encode_srgb(linear: f32) -> u8 {
+    let linear = linear.clamp(0.0, 1.0);
+    let encoded = if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// Linear channel for a 0-255 sRGB channel, the inverse of `encode_srgb`
+pub fn decode_srgb(encoded: u8) -> f32 {
+    let encoded = encoded as f32 / 255.0;
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,135 @@
+// Compute shader version of the cpu tracer in rendering.rs, one invocation per pixel
+
+struct Sphere {
+    // xyz center, w radius
+    center_radius: vec4<f32>,
+    // linear rgb
+    color: vec4<f32>,
+};
+
+struct Uniforms {
+    location: vec4<f32>,
+    direction: vec4<f32>,
+    right: vec4<f32>,
+    up: vec4<f32>,
+    // xyz direction, w intensity
+    light: vec4<f32>,
+    // width, height, samples per pixel, number of spheres
+    size: vec4<u32>,
+    // x exposure multiplier, y tone mapping as numbered in tonemap.rs
+    tone: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> u: Uniforms;
+@group(0) @binding(1) var<storage, read> spheres: array<Sphere>;
+@group(0) @binding(2) var<storage, read_write> pixels: array<u32>;
+
+const ALBEDO: f32 = 0.18;
+const PI: f32 = 3.14159265;
+
+fn hash(n: u32) -> u32 {
+    var x = n;
+    x = x ^ (x >> 16u);
+    x = x * 0x7feb352du;
+    x = x ^ (x >> 15u);
+    x = x * 0x846ca68bu;
+    x = x ^ (x >> 16u);
+    return x;
+}
+
+fn rand(seed: u32) -> f32 {
+    return f32(hash(seed)) / 4294967295.0;
+}
+
+// Returns the distance to the sphere, negative if the ray misses
+fn intersect(s: Sphere, origin: vec3<f32>, direction: vec3<f32>) -> f32 {
+    let l = s.center_radius.xyz - origin;
+    let adj = dot(l, direction);
+    let d2 = dot(l, l) - adj * adj;
+    let radius2 = s.center_radius.w * s.center_radius.w;
+    if (d2 > radius2) {
+        return -1.0;
+    }
+    let thc = sqrt(radius2 - d2);
+    let t0 = adj - thc;
+    let t1 = adj + thc;
+    if (t0 < 0.0 && t1 < 0.0) {
+        return -1.0;
+    }
+    return min(t0, t1);
+}
+
+fn cast_ray(origin: vec3<f32>, direction: vec3<f32>) -> vec3<f32> {
+    var closest = -1;
+    var closest_distance = 0.0;
+    for (var i = 0u; i < u.size.w; i = i + 1u) {
+        let d = intersect(spheres[i], origin, direction);
+        if (d >= 0.0 && (closest < 0 || d < closest_distance)) {
+            closest = i32(i);
+            closest_distance = d;
+        }
+    }
+
+    if (closest < 0) {
+        return vec3<f32>(0.0);
+    }
+
+    let s = spheres[closest];
+    let hit_point = origin + direction * closest_distance;
+    let surface_normal = normalize(hit_point - s.center_radius.xyz);
+    let direction_to_light = -normalize(u.light.xyz);
+    let light_power = max(dot(surface_normal, direction_to_light), 0.0) * u.light.w;
+    let light_reflected = ALBEDO / PI;
+    return s.color.rgb * light_power * light_reflected;
+}
+
+// Matches ToneMapping::apply in tonemap.rs
+fn tone_map(x: vec3<f32>) -> vec3<f32> {
+    let c = max(x, vec3<f32>(0.0));
+    let mode = u32(u.tone.y);
+    if (mode == 1u) {
+        return c / (1.0 + c);
+    }
+    if (mode == 2u) {
+        return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+    }
+    return min(c, vec3<f32>(1.0));
+}
+
+// Matches encode_srgb in tonemap.rs, without the conversion to bytes
+fn encode_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let c = clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0));
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, 12.92 * c, c <= vec3<f32>(0.0031308));
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let width = u.size.x;
+    let height = u.size.y;
+    if (id.x >= width || id.y >= height) {
+        return;
+    }
+
+    let samples = max(u.size.z, 1u);
+    var color = vec3<f32>(0.0);
+    for (var s = 0u; s < samples; s = s + 1u) {
+        // one sample goes through the middle of the pixel to match the cpu tracer
+        var jitter = vec2<f32>(0.5);
+        if (samples > 1u) {
+            let seed = ((id.y * width + id.x) * samples + s) * 2u;
+            jitter = vec2<f32>(rand(seed), rand(seed + 1u));
+        }
+
+        let normalized_x = 1.0 - (f32(id.x) + jitter.x) / f32(width) - 0.5;
+        let normalized_y = (f32(id.y) + jitter.y) / f32(height) - 0.5;
+        let direction = normalize(
+            normalized_x * u.right.xyz + normalized_y * u.up.xyz + u.direction.xyz
+        );
+        color = color + cast_ray(u.location.xyz, direction);
+    }
+
+    let mapped = encode_srgb(tone_map(color / f32(samples) * u.tone.x));
+    let c = vec3<u32>(round(mapped * 255.0));
+    // rgba bytes in memory order, no transparency
+    pixels[id.y * width + id.x] = c.x | (c.y << 8u) | (c.z << 16u) | (255u << 24u);
+}
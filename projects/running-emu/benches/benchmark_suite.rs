@@ -0,0 +1,50 @@
+//! Runs every map in the suite with every feature combination and prints a tab separated table.
+//!
+//! The greedy and random baseline attackers are run on every map too, with the default features.
+//!
+//! `cargo bench --bench benchmark_suite -- lpa` only runs feature combinations containing `lpa`.
+use running_emu::{
+    benchmark::{
+        baseline_policies, benchmark_maps, feature_combinations, run_benchmark,
+        run_policy_benchmark, timed_systems, BenchmarkResult,
+    },
+    FeatureFlags,
+};
+
+fn main() {
+    // cargo passes `--bench` along with any filter
+    let filter = std::env::args().skip(1).find(|a| !a.starts_with("--"));
+
+    let maps = benchmark_maps(&[10, 20, 30], &[0, 10, 25]);
+    let matches = |name: &str| match &filter {
+        Some(f) => name.contains(f.as_str()),
+        None => true,
+    };
+    let combinations = feature_combinations()
+        .into_iter()
+        .filter(|(name, _)| matches(name))
+        .collect::<Vec<_>>();
+
+    let mut results = Vec::new();
+    for map in maps.iter() {
+        for (name, features) in combinations.iter() {
+            eprintln!("running {} with {}", map.name, name);
+            results.push(run_benchmark(map, name, *features));
+        }
+
+        let mut features = FeatureFlags::new();
+        features.time_systems = true;
+        for (name, mut policy) in baseline_policies() {
+            if matches(&name) {
+                eprintln!("running {} with {}", map.name, name);
+                results.push(run_policy_benchmark(map, &name, features, policy.as_mut()));
+            }
+        }
+    }
+
+    let systems = timed_systems(&results);
+    println!("{}", BenchmarkResult::header(&systems));
+    for r in results.iter() {
+        println!("{}", r.to_row(&systems));
+    }
+}
@@ -1,5 +1,11 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use running_emu::{create_map, FeatureFlags, PathingAlgorithm};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use hecs::World;
+use running_emu::{
+    ai_pathing::{get_goal_lpapather, get_start_lpapather},
+    create_map, create_random_map, parse_map,
+    spatial::{system_update_spatial_cache, SpatialCache},
+    step_game_world, system_vision, FeatureFlags, PathingAlgorithm,
+};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let map = "@..............
@@ -31,6 +37,72 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("find path 100x100", |b| {
         b.iter(|| running_emu::run_sim_from_map(black_box(&large_map), features))
     });
+
+    // Spiral with pits rather than walls, so the agent has to explore around them
+    let spiral = "@..............
+    .OOOOOOOOOOOOO.
+    .O...........O.
+    .O.OOOOOOOOO.O.
+    .O.O.......O.O.
+    .O.OOOOOOO.O.O.
+    .O......GO.O.O.
+    .OOOOOOOOO.O.O.
+    ...........O...";
+    let maps = [
+        ("spiral", spiral.to_string()),
+        ("random 20x20", create_random_map(20, 25, 3)),
+        ("random 40x40", create_random_map(40, 25, 11)),
+    ];
+    let algorithms = [
+        ("LPA*", PathingAlgorithm::LpaStar),
+        ("D* Lite", PathingAlgorithm::DStarLite),
+    ];
+
+    let mut group = c.benchmark_group("lpa vs d* lite");
+    for (map_name, map) in maps.iter() {
+        for (name, algorithm) in algorithms.iter() {
+            features.pathing_algorithm = *algorithm;
+            // Criterion only reports time, so print the work done alongside it
+            let stats = running_emu::run_sim_with_stats(map, features);
+            println!(
+                "{} on {}: {} steps, {} expansions",
+                name, map_name, stats.steps, stats.expansions
+            );
+
+            group.bench_with_input(BenchmarkId::new(*name, map_name), map, |b, map| {
+                b.iter(|| running_emu::run_sim_from_map(black_box(map), features))
+            });
+        }
+    }
+    group.finish();
+
+    // Per step cost of keeping the spatial cache current on a large map, it used to be rebuilt
+    // every step rather than updated from the previous step's moves
+    features.pathing_algorithm = PathingAlgorithm::LpaStar;
+    let mut world = World::new();
+    parse_map(&mut world, &create_map(100));
+    let mut start_pather = get_start_lpapather(&world);
+    let mut goal_pather = get_goal_lpapather(&world);
+    system_vision(&mut world);
+    step_game_world(&mut world, features, &mut start_pather, &mut goal_pather);
+
+    let mut group = c.benchmark_group("spatial cache 100x100");
+    group.bench_function("rebuild", |b| {
+        b.iter(|| SpatialCache::new(black_box(&world)))
+    });
+    // Reapplying the same step's moves leaves the cache unchanged, so every iteration does the
+    // same work
+    group.bench_function("incremental", |b| {
+        b.iter(|| system_update_spatial_cache(black_box(&mut world)))
+    });
+    group.bench_function("vision", |b| b.iter(|| system_vision(&mut world)));
+
+    let mut uncached = World::new();
+    parse_map(&mut uncached, &create_map(100));
+    group.bench_function("vision without cache", |b| {
+        b.iter(|| system_vision(&mut uncached))
+    });
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
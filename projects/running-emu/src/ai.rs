@@ -1,8 +1,13 @@
 use hecs::World;
 
-use crate::{Attack, AttackerAgent, Damage, Health, Position};
+use crate::{
+    events::{publish, AttackEvent},
+    Attack, AttackerAgent, Health, Position,
+};
 
 /// Have towers attack units in range
+///
+/// Publishes an `AttackEvent` for each attack, damage is applied by the health system.
 pub fn system_defense_ai(world: &mut World) {
     let mut targets = Vec::new();
 
@@ -17,19 +22,61 @@ pub fn system_defense_ai(world: &mut World) {
     {
         for (target, target_pos) in &targets {
             if pos.0.dist(&target_pos) <= attack.range as i32 {
-                attacks.push((
-                    target,
-                    Damage {
-                        amount: attack.damage,
-                        from: e,
-                    },
-                ));
+                attacks.push(AttackEvent {
+                    target: *target,
+                    from: e,
+                    amount: attack.damage,
+                });
                 continue; // Only one attack per tick
             }
         }
     }
 
-    for (target, damage) in attacks {
-        world.insert_one(*target, damage).unwrap();
+    for attack in attacks {
+        publish(world, attack);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hecs::World;
+
+    use super::system_defense_ai;
+    use crate::{
+        events::{read_events, AttackEvent},
+        spatial::Point,
+        Attack, AttackerAgent, Health, Position,
+    };
+
+    #[test]
+    fn test_defense_ai_attacks_in_range() {
+        let mut world = World::new();
+        let agent = world.spawn((Position(Point { x: 0, y: 0 }), AttackerAgent, Health(10)));
+        let tower = world.spawn((
+            Position(Point { x: 2, y: 0 }),
+            Attack {
+                damage: 5,
+                range: 2,
+            },
+        ));
+        world.spawn((
+            Position(Point { x: 5, y: 0 }),
+            Attack {
+                damage: 5,
+                range: 2,
+            },
+        ));
+
+        system_defense_ai(&mut world);
+        assert_eq!(
+            read_events::<AttackEvent>(&world),
+            vec![AttackEvent {
+                target: agent,
+                from: tower,
+                amount: 5
+            }]
+        );
+        // Damage is left for the health system
+        assert_eq!(world.get::<Health>(agent).unwrap().0, 10);
     }
 }
@@ -1,4 +1,4 @@
-use std::cmp::{min, Reverse};
+use std::cmp::{max, min, Reverse};
 
 use crossterm::style::Color;
 use hecs::World;
@@ -6,11 +6,13 @@ use itertools::Itertools;
 use priority_queue::PriorityQueue;
 
 use crate::{
+    events::{publish, AttackEvent, MoveEvent},
     get_goal, get_start,
-    graph::{get_neighbors, CostMap, CostMapView, EdgeType},
+    graph::{CostMap, CostMapView, EdgeType},
+    policy::{AttackerPolicy, Observation},
     spatial::{get_entities, Point},
-    Attack, AttackerAgent, BackgroundHighlight, Damage, FeatureFlags, Health, PathingAlgorithm,
-    Position, TargetLocation,
+    Attack, AttackerAgent, BackgroundHighlight, Health, Key, Position, TargetLocation, Visibility,
+    MIN_MOVE_COST,
 };
 
 /// Move agents that have a target location and attack if needed.
@@ -28,6 +30,7 @@ pub fn system_ai_action(world: &mut World) {
 
     // Agents that can attack, attack if a health entity in front, otherwise they move
     let mut attacks_to_apply = Vec::new();
+    let mut moves = Vec::new();
     for (e, (pos, target, attack)) in
         world.query_mut::<(&mut Position, &mut TargetLocation, &Attack)>()
     {
@@ -38,15 +41,18 @@ pub fn system_ai_action(world: &mut World) {
         let path = get_path(pos.0, target.0.unwrap(), &cost_view).unwrap();
         let target_move = path[1];
         if let Some((target, _)) = health_entities.iter().find(|(_, p)| *p == target_move) {
-            attacks_to_apply.push((
-                target,
-                Damage {
-                    amount: attack.damage,
-                    from: e,
-                },
-            ));
+            attacks_to_apply.push(AttackEvent {
+                target: *target,
+                from: e,
+                amount: attack.damage,
+            });
         } else {
             // Nothing in the way, can move
+            moves.push(MoveEvent {
+                entity: e,
+                from: pos.0,
+                to: target_move,
+            });
             pos.0 = target_move;
         }
 
@@ -55,23 +61,19 @@ pub fn system_ai_action(world: &mut World) {
         }
     }
 
-    for (target, dmg) in attacks_to_apply {
-        world.insert_one(*target, dmg).unwrap();
+    for attack in attacks_to_apply {
+        publish(world, attack);
+    }
+    for m in moves {
+        publish(world, m);
     }
 }
 
 /// Identify where agents should move next to explore.
 ///
-/// This system sets target locations and handles route highlighting.
-///
-/// The lowest cost space is always explored next rather than traditional breadth first search.
-/// This ensures that tiles costs always represent the 'cheapest' way to get to the tile.
-pub fn system_exploration(
-    world: &mut World,
-    features: FeatureFlags,
-    pather_start: &mut LpaStarPather,
-    pather_goal: &mut LpaStarPather,
-) -> bool {
+/// This system sets target locations, `policy` picks the next target whenever the agent has
+/// reached its current one or doesn't have one. Returns true once the goal is reached.
+pub fn system_exploration(world: &mut World, policy: &mut dyn AttackerPolicy) -> bool {
     let agent_ids = world
         .query_mut::<&AttackerAgent>()
         .into_iter()
@@ -86,86 +88,66 @@ pub fn system_exploration(
     }
 
     let target_loc = world.get::<TargetLocation>(agent_id).unwrap().0;
-
-    // Generate the next target if we're there or don't have a goal.
-    //
-    // The next target is chosen by constructing a matrix of scores for all possible explored locations and choosing the minimum
-    // An explored location will not be chosen. The scores for squares have the following form:
-    //  Score(point) = cost to get there from start + distance from the agent + cost to get to goal assuming un-explored squares have only travel cost
-    if target_loc.is_none() || cur_loc == target_loc.unwrap() {
-        let costs = CostMap::from_world(&world);
-        let start_view = CostMapView::new(&costs, vec![EdgeType::Visible]);
-        let goal_view = CostMapView::new(&costs, vec![EdgeType::Visible, EdgeType::Fog]);
-
-        let candidate_points = get_edge_points(&start_view, goal);
-
-        let v;
-        let start_travel_costs = match features.pathing_algorithm {
-            PathingAlgorithm::Astar => {
-                v = get_travel_costs(start, &start_view);
-                &v
-            }
-            PathingAlgorithm::LpaStar => {
-                pather_start.update_tile_costs(&start_view);
-                pather_start.get_travel_costs()
-            }
-        };
-
-        let v;
-        let goal_travel_costs = match features.pathing_algorithm {
-            PathingAlgorithm::Astar => {
-                v = get_travel_costs(goal, &goal_view);
-                &v
-            }
-            PathingAlgorithm::LpaStar => {
-                pather_goal.update_tile_costs(&goal_view);
-                pather_goal.get_travel_costs()
+    let costs = CostMap::from_world(&world);
+    // Tiles on the way can be forgotten with `FeatureFlags::memory_decay`
+    let lost = target_loc.map_or(false, |t| {
+        get_path(
+            cur_loc,
+            t,
+            &CostMapView::new(&costs, vec![EdgeType::Visible]),
+        )
+        .is_none()
+    });
+
+    // Generate the next target if we're there, don't have a goal or can no longer get there.
+    if target_loc.is_none() || cur_loc == target_loc.unwrap() || lost {
+        let mut candidates = get_edge_points(&costs, goal);
+        // Already here, can be an edge when diagonals weren't in sight
+        candidates.retain(|p| *p != cur_loc);
+        // Keys are worth fetching even once everything around them has been seen
+        for (_, (p, v, _)) in world.query::<(&Position, &Visibility, &Key)>().iter() {
+            if v.0 && !candidates.contains(&p.0) {
+                candidates.push(p.0);
             }
-        };
-
-        let mut candidate_scores = Vec::with_capacity(candidate_points.len());
-        for p in candidate_points.iter() {
-            let score = CandidateScore {
-                dist_to_start: start_travel_costs[p.y][p.x],
-                dist_to_goal: goal_travel_costs[p.y][p.x],
-                dist_to_agent: p.dist(&cur_loc),
-            };
-            candidate_scores.push(score);
         }
 
-        let min_val = *candidate_scores.iter().min().unwrap();
-        let min_index = candidate_scores.iter().position(|x| *x == min_val).unwrap();
-        let min_p = candidate_points[min_index];
+        let observation = Observation {
+            agent: cur_loc,
+            start: start,
+            goal: goal,
+            costs: costs,
+            candidates: candidates,
+        };
+        let target = policy
+            .choose_target(&observation)
+            .expect("no reachable locations left to explore");
         world
-            .insert_one(agent_id, TargetLocation(Some(min_p)))
+            .insert_one(agent_id, TargetLocation(Some(target)))
             .unwrap();
     }
 
     return false;
 }
 
-/// Returns visible points with at least one invisible neighbor
+/// Returns visible points that can be reached from an invisible neighbor
 ///
 /// The Goal is treated as a special case since it's always visible. Goal is only returned
 /// if there is at least one visible square near it.
-fn get_edge_points(tile_costs: &CostMapView, goal: Point) -> Vec<Point> {
-    let width = tile_costs.width;
-    let height = tile_costs.height;
+fn get_edge_points(costs: &CostMap, goal: Point) -> Vec<Point> {
+    let visible_view = CostMapView::new(costs, vec![EdgeType::Visible]);
+    let fog_view = CostMapView::new(costs, vec![EdgeType::Fog]);
+    let width = costs.width;
+    let height = costs.height;
 
     let mut points = Vec::new();
 
     for y in 0..height {
         for x in 0..width {
             let p = Point { x: x, y: y };
-            let n = tile_costs.get_predecessors(p).len();
-            let is_edge = (n > 0) // needs at least one connection
-                && match p {
-                    p if p == goal => n > 0, // Goal is valid if at least one connected point
-                    p if (p.x == width - 1 || p.x == 0) && (p.y == 0 || p.y == height - 1) => n < 2, // corner point, 2 edges
-                    p if p.x == 0 || p.y == 0 => n < 3, // side point, 3 edges
-                    p if p.x == width - 1 || p.y == height - 1 => n < 3, // side point, 3 edges
-                    _ => n < 4,
-                };
+            // Doors and one way tiles remove edges, so count the fog edges rather than
+            // comparing the visible edges against the number of neighbors
+            let is_edge = !visible_view.get_predecessors(p).is_empty() // needs at least one connection
+                && (p == goal || !fog_view.get_predecessors(p).is_empty());
 
             if is_edge {
                 points.push(p)
@@ -176,41 +158,6 @@ fn get_edge_points(tile_costs: &CostMapView, goal: Point) -> Vec<Point> {
     return points;
 }
 
-/// Helper for sorting candidate locations.
-#[derive(PartialEq, Eq, Clone, Copy)]
-struct CandidateScore {
-    dist_to_start: i32,
-    dist_to_goal: i32,
-    dist_to_agent: i32,
-}
-
-impl Ord for CandidateScore {
-    /// Compare on dist to goal + dist to start, if a tiebreaker, use goal dist as secondary sort, if still tied, used agent dist
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let self_total = self
-            .dist_to_start
-            .checked_add(self.dist_to_goal)
-            .unwrap_or(i32::MAX);
-        let other_total = other
-            .dist_to_start
-            .checked_add(other.dist_to_goal)
-            .unwrap_or(i32::MAX);
-        if self_total != other_total {
-            return self_total.cmp(&other_total);
-        } else if self.dist_to_goal != other.dist_to_goal {
-            return self.dist_to_goal.cmp(&other.dist_to_goal);
-        } else {
-            return self.dist_to_agent.cmp(&other.dist_to_agent);
-        }
-    }
-}
-
-impl PartialOrd for CandidateScore {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 /// Highlight target locations and expected path, useful for debugging
 ///
 /// Only highlights tiles with a sprite
@@ -277,35 +224,125 @@ fn get_path(start: Point, end: Point, costs: &CostMapView) -> Option<Vec<Point>>
                 let new_cost = distance + cost;
                 distance_matrix[n.y][n.x] = Some(new_cost);
                 // Distance heuristic for A*
-                let goal_dist =
-                    (end.x as i32 - n.x as i32).abs() + (end.y as i32 - n.y as i32).abs();
+                let goal_dist = heuristic(&end, &n, costs.rules().diagonal);
                 queue.push(n, Reverse(goal_dist + new_cost));
             }
         }
     }
 
-    return Some(get_path_from_distances(start, end, &distance_matrix));
+    let path = get_path_from_distances(start, end, &distance_matrix, costs);
+    if costs.rules().smooth_paths {
+        return Some(smooth_path(&path, costs));
+    }
+    return Some(path);
+}
+
+/// Lower bound on the cost of travelling between points
+fn heuristic(a: &Point, b: &Point, diagonal: bool) -> i32 {
+    let dist = match diagonal {
+        true => a.diagonal_dist(b),
+        false => a.dist(b),
+    };
+    return dist * MIN_MOVE_COST;
+}
+
+/// Straighten a path by cutting out detours where a straight line is no more expensive.
+///
+/// Paths found on a grid wander between routes of equal cost, so from each point on the path
+/// this looks for the furthest later point a straight line reaches for no more than following
+/// the path would cost. The line has to follow edges in `costs`, so it never goes anywhere the
+/// path couldn't.
+pub fn smooth_path(path: &Vec<Point>, costs: &CostMapView) -> Vec<Point> {
+    // Cost of following the path to each point
+    let mut path_costs = vec![0];
+    for (from, to) in path.iter().tuple_windows() {
+        let cost = match costs.get_cost(*from, *to) {
+            Some(c) => c,
+            None => return path.clone(), // Not a path through `costs`
+        };
+        path_costs.push(path_costs.last().unwrap() + cost);
+    }
+
+    let mut smoothed = vec![path[0]];
+    let mut i = 0;
+    while i < path.len() - 1 {
+        let mut best = (i + 1, vec![path[i + 1]]);
+        for j in i + 2..path.len() {
+            let line = get_line(path[i], path[j], costs.rules().diagonal);
+            match get_line_cost(path[i], &line, costs) {
+                Some(c) if c <= path_costs[j] - path_costs[i] => best = (j, line),
+                _ => break,
+            }
+        }
+        smoothed.extend(best.1);
+        i = best.0;
+    }
+    return smoothed;
+}
+
+/// Points on a straight line from `a` to `b`, not including `a`. Each point is a single step
+/// from the last, steps are only diagonal if `diagonal` is set.
+fn get_line(a: Point, b: Point, diagonal: bool) -> Vec<Point> {
+    let dx = b.x as i32 - a.x as i32;
+    let dy = b.y as i32 - a.y as i32;
+    let at = |x: i32, y: i32| Point {
+        x: (a.x as i32 + x) as usize,
+        y: (a.y as i32 + y) as usize,
+    };
+
+    let mut line = Vec::new();
+    if diagonal {
+        // Round to the nearest tile at each step along the longer axis
+        let n = max(dx.abs(), dy.abs());
+        let lerp = |d: i32, i: i32| (2 * d * i + n * d.signum()) / (2 * n);
+        for i in 1..=n {
+            line.push(at(lerp(dx, i), lerp(dy, i)));
+        }
+    } else {
+        // Step along whichever axis keeps closest to the line
+        let (mut x, mut y) = (0, 0);
+        while (x, y) != (dx, dy) {
+            if (2 * x.abs() + 1) * dy.abs() < (2 * y.abs() + 1) * dx.abs() {
+                x += dx.signum();
+            } else {
+                y += dy.signum();
+            }
+            line.push(at(x, y));
+        }
+    }
+    return line;
+}
+
+/// Cost of following `line` from `start`, or None if a step isn't an edge in `costs`
+fn get_line_cost(start: Point, line: &Vec<Point>, costs: &CostMapView) -> Option<i32> {
+    let mut cost = 0;
+    let mut from = start;
+    for to in line {
+        cost += costs.get_cost(from, *to)?;
+        from = *to;
+    }
+    return Some(cost);
 }
 
 /// Return optimal path connecting start to end given a set of travel costs.
 ///
 /// Given the cost matrix, we can start at the goal and greedily follow the lowest cost
-///  path back to the starting point to get an optimal path.
+///  path back to the starting point to get an optimal path. Only steps along edges in `costs`
+///  since one way tiles and locked doors can't be crossed in both directions.
 pub fn get_path_from_distances(
     start: Point,
     end: Point,
     travel_costs: &Vec<Vec<Option<i32>>>,
+    costs: &CostMapView,
 ) -> Vec<Point> {
     let mut path = vec![end];
-    let width = travel_costs[0].len();
-    let height = travel_costs.len();
 
     while path.last().unwrap().clone() != start {
         let p = path.last().unwrap();
-        let neighbors = get_neighbors(*p, width, height);
+        let neighbors = costs.neighbors(*p);
         let (_, min) = neighbors
             .iter()
-            .filter(|p| !travel_costs[p.y][p.x].is_none())
+            .filter(|n| !travel_costs[n.y][n.x].is_none() && costs.get_cost(**n, *p).is_some())
             .enumerate()
             .min_by(|a, b| travel_costs[a.1.y][a.1.x].cmp(&travel_costs[b.1.y][b.1.x]))
             .unwrap();
@@ -319,8 +356,9 @@ pub fn get_path_from_distances(
 
 /// Return the lowest travel cost matrix for all visible tiles if possible
 ///
-/// None means no path is possible or there isn't tile information
-fn get_travel_costs(start: Point, tile_costs: &CostMapView) -> Vec<Vec<i32>> {
+/// None means no path is possible or there isn't tile information. With `reverse` the costs are
+/// for travelling to `start` rather than from it.
+pub fn get_travel_costs(start: Point, tile_costs: &CostMapView, reverse: bool) -> Vec<Vec<i32>> {
     let width = tile_costs.width;
     let height = tile_costs.height;
     let mut travel_costs = vec![vec![i32::MAX; width]; height];
@@ -333,7 +371,11 @@ fn get_travel_costs(start: Point, tile_costs: &CostMapView) -> Vec<Vec<i32>> {
         let (node, _) = queue.pop().unwrap();
         let distance = travel_costs[node.y][node.x];
 
-        for (n, cost) in tile_costs.get_successors(node) {
+        let edges = match reverse {
+            true => tile_costs.get_predecessors(node),
+            false => tile_costs.get_successors(node),
+        };
+        for (n, cost) in edges {
             let d = travel_costs[n.y][n.x];
 
             let new_cost = distance.checked_add(cost).unwrap_or(i32::MAX);
@@ -380,6 +422,9 @@ pub fn system_print_tile_costs(world: &World) {
 
 /// Implementation for LPA*, based on:
 /// https://en.wikipedia.org/wiki/Lifelong_Planning_A*
+///
+/// Also runs D* Lite when `goal` follows the agent with [`LpaStarPather::move_goal`], see:
+/// http://idm-lab.org/bib/abstracts/papers/aaai02b.pdf
 pub struct LpaStarPather {
     queue: PriorityQueue<Point, Reverse<LpaKey>>,
     g: Vec<Vec<i32>>,
@@ -388,6 +433,15 @@ pub struct LpaStarPather {
     height: usize,
     start: Point,
     goal: Point,
+    /// Find the cost of reaching `start` rather than leaving it, for when `start` is the goal
+    reverse: bool,
+    /// D* Lite key modifier, the heuristic distance `goal` has moved since the search began so
+    /// keys already in the queue stay lower bounds
+    km: i32,
+    /// Number of nodes popped from the queue over the life of the pather
+    expansions: usize,
+    /// Whether the costs allow diagonal steps, which changes the search heuristic
+    diagonal: bool,
 }
 
 #[derive(PartialEq, Eq)]
@@ -419,7 +473,7 @@ pub fn get_start_lpapather(world: &World) -> LpaStarPather {
     let tile_costs = CostMap::from_world(world);
     let cost_view = CostMapView::new(&tile_costs, vec![EdgeType::Visible]);
 
-    return LpaStarPather::new(start, goal, &cost_view);
+    return LpaStarPather::new(start, goal, &cost_view, false);
 }
 
 pub fn get_goal_lpapather(world: &World) -> LpaStarPather {
@@ -428,11 +482,12 @@ pub fn get_goal_lpapather(world: &World) -> LpaStarPather {
     let tile_costs = CostMap::from_world(world);
     let cost_view = CostMapView::new(&tile_costs, vec![EdgeType::Visible, EdgeType::Fog]);
 
-    return LpaStarPather::new(goal, start, &cost_view);
+    // Costs are towards the goal so they add up with the start pather's, see `ExplorationPolicy`
+    return LpaStarPather::new(goal, start, &cost_view, true);
 }
 
 impl LpaStarPather {
-    fn new(start: Point, goal: Point, tile_costs: &CostMapView) -> Self {
+    fn new(start: Point, goal: Point, tile_costs: &CostMapView, reverse: bool) -> Self {
         let width = tile_costs.width;
         let height = tile_costs.height;
 
@@ -450,19 +505,24 @@ impl LpaStarPather {
             height,
             start,
             goal,
+            reverse,
+            km: 0,
+            expansions: 0,
+            diagonal: tile_costs.rules().diagonal,
         };
 
         pather.rhs[start.y][start.x] = 0;
         pather.queue.push(start, pather.calculate_key(&start));
 
-        pather.compute_shortest_path(tile_costs);
+        pather.compute_shortest_path(tile_costs, &[]);
 
         return pather;
     }
 
     fn calculate_key(&self, p: &Point) -> Reverse<LpaKey> {
-        let k1 = min(self.g[p.y][p.x], self.rhs[p.y][p.x]) + self.goal.dist(p);
         let k2 = min(self.g[p.y][p.x], self.rhs[p.y][p.x]);
+        // Unreachable points can be keyed when checking if a search is done
+        let k1 = k2.saturating_add(heuristic(&self.goal, p, self.diagonal) + self.km);
         return Reverse(LpaKey { k1: k1, k2: k2 });
     }
 
@@ -476,24 +536,50 @@ impl LpaStarPather {
         return self.g[p.y][p.x];
     }
 
-    fn compute_shortest_path(&mut self, tile_costs: &CostMapView) {
-        // This needs to be greater than since we're actually comparing the reverse of the keys
-        while !self.queue.is_empty()
-        // Remove the normal termination logic as we want to populate the entire matrix. May need to revisit for
-        // performance reasons.
-        // && *self.queue.peek().unwrap().1 > self.calculate_key(&self.goal))
-        // || (self.get_rhs(self.goal) != self.get_g(self.goal))
-        {
-            let node = self.queue.pop().unwrap().0;
+    /// Returns true if `p` has its final cost for this search, nodes still in the queue can't
+    /// lower it
+    fn is_settled(&self, p: &Point) -> bool {
+        // This needs to be less than since we're actually comparing the reverse of the keys
+        return self.get_g(*p) == self.get_rhs(*p)
+            && self
+                .queue
+                .peek()
+                .map_or(true, |(_, top)| *top <= self.calculate_key(p));
+    }
+
+    /// Expands nodes until every point in `stop_at` is settled, or until the whole matrix is
+    /// populated if `stop_at` is empty.
+    fn compute_shortest_path(&mut self, tile_costs: &CostMapView, stop_at: &[Point]) {
+        // Settled points stay settled for the rest of the search, so each only needs to be
+        // checked until it is
+        let mut settled = 0;
+        while !self.queue.is_empty() {
+            if !stop_at.is_empty() {
+                while settled < stop_at.len() && self.is_settled(&stop_at[settled]) {
+                    settled += 1;
+                }
+                if settled == stop_at.len() {
+                    break;
+                }
+            }
+
+            let (node, key) = self.queue.pop().unwrap();
+            // Keyed before `goal` moved, put it back in order
+            let new_key = self.calculate_key(&node);
+            if key > new_key {
+                self.queue.push(node, new_key);
+                continue;
+            }
+            self.expansions += 1;
             if self.get_g(node) > self.get_rhs(node) {
                 self.g[node.y][node.x] = self.get_rhs(node);
-                for s in get_neighbors(node, self.width, self.height) {
+                for s in tile_costs.neighbors(node) {
                     self.update_node(&s, tile_costs);
                 }
             } else {
                 self.g[node.y][node.x] = i32::MAX;
                 self.update_node(&node, tile_costs);
-                for s in get_neighbors(node, self.width, self.height) {
+                for s in tile_costs.neighbors(node) {
                     self.update_node(&s, tile_costs);
                 }
             }
@@ -505,10 +591,14 @@ impl LpaStarPather {
     fn update_node(&mut self, p: &Point, tile_costs: &CostMapView) {
         if *p != self.start {
             self.rhs[p.y][p.x] = i32::MAX;
-            for (n, cost) in tile_costs.get_predecessors(*p) {
+            // Edges can be one way, so a reverse search follows the edges leaving `p`
+            let edges = match self.reverse {
+                true => tile_costs.get_successors(*p),
+                false => tile_costs.get_predecessors(*p),
+            };
+            for (n, cost) in edges {
                 self.rhs[p.y][p.x] = min(
                     self.rhs[p.y][p.x],
-                    // Ok to unwrap here, edges are guaranteed to be bi-directional, if not, there is a bug
                     self.get_g(n).checked_add(cost).unwrap_or(i32::MAX),
                 )
             }
@@ -519,7 +609,23 @@ impl LpaStarPather {
         }
     }
 
-    fn update_tile_costs(&mut self, cost_view: &CostMapView) {
+    /// Switch heuristics if diagonal movement was turned on or off since the last update.
+    ///
+    /// Changing the heuristic changes every key, so the whole queue is re-keyed.
+    fn set_diagonal(&mut self, diagonal: bool) {
+        if self.diagonal == diagonal {
+            return;
+        }
+        self.diagonal = diagonal;
+        let nodes = self.queue.iter().map(|(p, _)| *p).collect_vec();
+        for p in nodes {
+            let key = self.calculate_key(&p);
+            self.queue.change_priority(&p, key);
+        }
+    }
+
+    pub fn update_tile_costs(&mut self, cost_view: &CostMapView) {
+        self.set_diagonal(cost_view.rules().diagonal);
         // May need to switch back to tracking changed for performance, but for now, update all nodes
         for y in 0..cost_view.height {
             for x in 0..cost_view.width {
@@ -528,12 +634,142 @@ impl LpaStarPather {
             }
         }
 
-        self.compute_shortest_path(cost_view);
+        self.compute_shortest_path(cost_view, &[]);
+    }
+
+    /// D* Lite version of `update_tile_costs`, only searches until the costs for `targets` are
+    /// known. Costs for other points may be out of date.
+    pub fn update_tile_costs_for(&mut self, cost_view: &CostMapView, targets: &[Point]) {
+        self.set_diagonal(cost_view.rules().diagonal);
+        for y in 0..cost_view.height {
+            for x in 0..cost_view.width {
+                let p = Point { x: x, y: y };
+                self.update_node(&p, cost_view);
+            }
+        }
+
+        self.compute_shortest_path(cost_view, targets);
+    }
+
+    /// Point the search heuristic at `goal`, e.g. the agent's new location.
+    ///
+    /// Rather than re-keying the queue, the distance moved is added to future keys and stale keys
+    /// are fixed as they're popped.
+    pub fn move_goal(&mut self, goal: Point) {
+        self.km += heuristic(&self.goal, &goal, self.diagonal);
+        self.goal = goal;
     }
 
     pub fn get_travel_costs(&self) -> &Vec<Vec<i32>> {
         return &self.g;
     }
+
+    /// Number of nodes expanded since the pather was created
+    pub fn expansions(&self) -> usize {
+        return self.expansions;
+    }
+
+    /// Text form of the search state, read back with `LpaStarPather::load`.
+    ///
+    /// Costs are written a row per line with `inf` for unreachable points.
+    pub fn save(&self) -> String {
+        let p = |p: &Point| format!("{},{}", p.x, p.y);
+        let costs = |row: &Vec<i32>| {
+            row.iter()
+                .map(|c| match *c {
+                    i32::MAX => "inf".to_string(),
+                    c => c.to_string(),
+                })
+                .join(" ")
+        };
+
+        let mut lines = vec![
+            format!("size {} {}", self.width, self.height),
+            format!("start {}", p(&self.start)),
+            format!("goal {}", p(&self.goal)),
+            format!("reverse {}", self.reverse),
+            format!("km {}", self.km),
+            format!("expansions {}", self.expansions),
+            format!("diagonal {}", self.diagonal),
+        ];
+        lines.extend(self.g.iter().map(|row| format!("g {}", costs(row))));
+        lines.extend(self.rhs.iter().map(|row| format!("rhs {}", costs(row))));
+        for (node, Reverse(key)) in self.queue.iter() {
+            lines.push(format!("queue {} {} {}", p(node), key.k1, key.k2));
+        }
+
+        let mut s = lines.join("\n");
+        s.push('\n');
+        return s;
+    }
+
+    pub fn load(s: &str) -> Result<Self, String> {
+        let mut pather = Self {
+            queue: PriorityQueue::new(),
+            g: Vec::new(),
+            rhs: Vec::new(),
+            width: 0,
+            height: 0,
+            start: Point { x: 0, y: 0 },
+            goal: Point { x: 0, y: 0 },
+            reverse: false,
+            km: 0,
+            expansions: 0,
+            diagonal: false,
+        };
+
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            let err = || format!("invalid pather line: {}", line);
+            let (name, value) = line.split_once(' ').ok_or_else(err)?;
+            let costs = || -> Result<Vec<i32>, String> {
+                value
+                    .split_whitespace()
+                    .map(|c| match c {
+                        "inf" => Ok(i32::MAX),
+                        c => c.parse().map_err(|_| err()),
+                    })
+                    .collect()
+            };
+            match name {
+                "size" => {
+                    let (w, h) = value.split_once(' ').ok_or_else(err)?;
+                    pather.width = w.parse().map_err(|_| err())?;
+                    pather.height = h.parse().map_err(|_| err())?;
+                }
+                "start" => pather.start = value.parse()?,
+                "goal" => pather.goal = value.parse()?,
+                "reverse" => pather.reverse = value.parse().map_err(|_| err())?,
+                "km" => pather.km = value.parse().map_err(|_| err())?,
+                "expansions" => pather.expansions = value.parse().map_err(|_| err())?,
+                "diagonal" => pather.diagonal = value.parse().map_err(|_| err())?,
+                "g" => pather.g.push(costs()?),
+                "rhs" => pather.rhs.push(costs()?),
+                "queue" => {
+                    let parts: Vec<&str> = value.split_whitespace().collect();
+                    if parts.len() != 3 {
+                        return Err(err());
+                    }
+                    let key = LpaKey {
+                        k1: parts[1].parse().map_err(|_| err())?,
+                        k2: parts[2].parse().map_err(|_| err())?,
+                    };
+                    pather.queue.push(parts[0].parse()?, Reverse(key));
+                }
+                _ => return Err(err()),
+            }
+        }
+
+        let sized = |costs: &Vec<Vec<i32>>| {
+            costs.len() == pather.height && costs.iter().all(|row| row.len() == pather.width)
+        };
+        if !sized(&pather.g) || !sized(&pather.rhs) {
+            return Err(format!(
+                "pather costs don't match its size of {}x{}",
+                pather.width, pather.height
+            ));
+        }
+        return Ok(pather);
+    }
 }
 
 #[cfg(test)]
@@ -541,7 +777,10 @@ mod tests {
     use std::vec;
 
     use super::*;
-    use crate::Point;
+    use crate::{
+        graph::{set_movement_rules, MovementRules},
+        parse_map, system_vision, Point,
+    };
 
     #[test]
     fn test_lpa_no_update() {
@@ -550,7 +789,7 @@ mod tests {
         let start = Point { x: 0, y: 0 };
         let goal = Point { x: 2, y: 2 };
 
-        let pather = LpaStarPather::new(start, goal, &cost_view);
+        let pather = LpaStarPather::new(start, goal, &cost_view, false);
 
         assert_eq!(pather.g, vec![vec![0, 1, 2], vec![1, 2, 3], vec![2, 3, 4]]);
     }
@@ -562,7 +801,7 @@ mod tests {
         let start = Point { x: 0, y: 0 };
         let goal = Point { x: 2, y: 2 };
 
-        let pather = LpaStarPather::new(start, goal, &cost_view);
+        let pather = LpaStarPather::new(start, goal, &cost_view, false);
 
         assert_eq!(
             pather.g,
@@ -577,7 +816,7 @@ mod tests {
         let start = Point { x: 0, y: 0 };
         let goal = Point { x: 2, y: 2 };
 
-        let mut pather = LpaStarPather::new(start, goal, &cost_view);
+        let mut pather = LpaStarPather::new(start, goal, &cost_view, false);
 
         assert_eq!(pather.g, vec![vec![0, 1, 2], vec![1, 2, 3], vec![2, 3, 4]]);
 
@@ -593,6 +832,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_d_star_lite_update() {
+        let tile_costs = CostMap::_from_vec(&vec![vec![0; 5]; 5]);
+        let cost_view = CostMapView::new(&tile_costs, vec![EdgeType::Visible]);
+        let start = Point { x: 0, y: 0 };
+        let goal = Point { x: 4, y: 4 };
+
+        let mut lpa = LpaStarPather::new(start, goal, &cost_view, false);
+        let mut d_star = LpaStarPather::new(start, goal, &cost_view, false);
+
+        // discover a wall while the agent is part way there
+        let updated = CostMap::_from_vec(&vec![
+            vec![0, 0, 10, 0, 0],
+            vec![0, 0, 10, 0, 0],
+            vec![0, 0, 10, 0, 0],
+            vec![0, 0, 10, 0, 0],
+            vec![0, 0, 0, 0, 0],
+        ]);
+        let cost_view = CostMapView::new(&updated, vec![EdgeType::Visible]);
+        let targets = vec![Point { x: 3, y: 0 }, Point { x: 1, y: 3 }];
+
+        lpa.update_tile_costs(&cost_view);
+        let expansions = d_star.expansions();
+        d_star.move_goal(Point { x: 1, y: 1 });
+        d_star.update_tile_costs_for(&cost_view, &targets);
+
+        for p in targets {
+            assert_eq!(d_star.g[p.y][p.x], lpa.g[p.y][p.x]);
+        }
+        assert_eq!(d_star.g[0][3], 11);
+        assert!(d_star.expansions() - expansions < lpa.expansions() - expansions);
+    }
+
     #[test]
     fn find_path_no_cost() {
         let tile_costs = CostMap::_from_vec(&vec![vec![0; 3]; 3]);
@@ -635,7 +907,7 @@ mod tests {
         let cost_view = CostMapView::new(&tile_costs, vec![EdgeType::Visible]);
         let start = Point { x: 0, y: 0 };
 
-        let travel_costs = get_travel_costs(start, &cost_view);
+        let travel_costs = get_travel_costs(start, &cost_view, false);
         assert_eq!(
             travel_costs,
             vec![vec![0, 1, 2], vec![1, 2, 3], vec![2, 3, 4]]
@@ -648,10 +920,92 @@ mod tests {
         let cost_view = CostMapView::new(&tile_costs, vec![EdgeType::Visible]);
         let start = Point { x: 0, y: 0 };
 
-        let travel_costs = get_travel_costs(start, &cost_view);
+        let travel_costs = get_travel_costs(start, &cost_view, false);
         assert_eq!(
             travel_costs,
             vec![vec![0, 11, 6], vec![1, 12, 5], vec![2, 3, 4]]
         )
     }
+
+    #[test]
+    fn test_travel_cost_reverse() {
+        let tile_costs = CostMap::_from_vec(&vec![vec![0, 10, 0], vec![0, 10, 0], vec![0, 0, 0]]);
+        let cost_view = CostMapView::new(&tile_costs, vec![EdgeType::Visible]);
+        let start = Point { x: 0, y: 0 };
+        let goal = Point { x: 2, y: 2 };
+
+        // Only entering the expensive tiles is charged, leaving them is free
+        let travel_costs = get_travel_costs(start, &cost_view, true);
+        assert_eq!(
+            travel_costs,
+            vec![vec![0, 1, 6], vec![1, 2, 5], vec![2, 3, 4]]
+        );
+
+        let pather = LpaStarPather::new(start, goal, &cost_view, true);
+        assert_eq!(pather.g, travel_costs);
+
+        // The costs to and from the middle tile add up to the cheapest route through it, costs
+        // from the goal would charge for entering it twice
+        let from_start = get_travel_costs(start, &cost_view, false);
+        let to_goal = get_travel_costs(goal, &cost_view, true);
+        let from_goal = get_travel_costs(goal, &cost_view, false);
+        assert_eq!(from_start[1][1] + to_goal[1][1], 14);
+        assert_eq!(from_start[1][1] + from_goal[1][1], 24);
+    }
+
+    #[test]
+    fn test_smooth_path() {
+        let tile_costs = CostMap::_from_vec(&vec![vec![0, 0, 0], vec![0, 0, 0], vec![0, 9, 0]]);
+        let cost_view = CostMapView::new(&tile_costs, vec![EdgeType::Visible]);
+        let p = |x, y| Point { x: x, y: y };
+
+        // The detour is cut out
+        let path = vec![p(0, 0), p(0, 1), p(1, 1), p(1, 0), p(2, 0)];
+        assert_eq!(
+            smooth_path(&path, &cost_view),
+            vec![p(0, 0), p(1, 0), p(2, 0)]
+        );
+
+        // Going through (1, 2) would be a straighter line, but costs more
+        let path = vec![p(0, 1), p(1, 1), p(2, 1), p(2, 2)];
+        assert_eq!(smooth_path(&path, &cost_view), path);
+
+        // Staircases become as close to a straight line as the grid allows
+        let path = vec![p(0, 0), p(1, 0), p(2, 0), p(2, 1), p(2, 2)];
+        assert_eq!(
+            smooth_path(&path, &cost_view),
+            vec![p(0, 0), p(1, 0), p(1, 1), p(2, 1), p(2, 2)]
+        );
+    }
+
+    #[test]
+    fn test_diagonal_edges() {
+        let edges = |map: &str, diagonal: bool| {
+            let mut world = World::new();
+            parse_map(&mut world, map);
+            set_movement_rules(
+                &mut world,
+                MovementRules {
+                    diagonal: diagonal,
+                    smooth_paths: false,
+                },
+            );
+            system_vision(&mut world);
+            let costs = CostMap::from_world(&world);
+            let view = CostMapView::new(&costs, vec![EdgeType::Visible, EdgeType::Fog]);
+            return (
+                view.get_cost(Point { x: 0, y: 0 }, Point { x: 1, y: 1 }),
+                view.get_cost(Point { x: 1, y: 1 }, Point { x: 0, y: 0 }),
+            );
+        };
+
+        assert_eq!(edges("@.\n.G", false), (None, None));
+        assert_eq!(edges("@.\n.G", true), (Some(3), Some(3)));
+        // Can't cut past walls or one way tiles
+        assert_eq!(edges("@W\n.G", true), (None, None));
+        assert_eq!(edges("@>\n.G", true), (None, None));
+        assert_eq!(edges("@.\n=G", true), (Some(3), Some(3)));
+        assert_eq!(edges("@.\n~G", true), (Some(3), Some(3)));
+        assert_eq!(edges("@.\n.~", true), (Some(9), Some(3)));
+    }
 }
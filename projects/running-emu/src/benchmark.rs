@@ -0,0 +1,309 @@
+use std::time::{Duration, Instant};
+
+use hecs::World;
+
+use crate::{
+    create_map, create_random_map,
+    graph::set_movement_rules,
+    parse_map,
+    policy::{AttackerPolicy, GreedyPolicy, RandomPolicy},
+    run_sim, step_game_world_with_policy, system_vision, FeatureFlags, PathingAlgorithm,
+};
+
+/// Seed for `RandomPolicy` so every suite run makes the same choices
+pub const BENCHMARK_RANDOM_SEED: u64 = 42;
+
+/// Ticks units and tiles must be out of sight before they're forgotten when memory decay is benchmarked
+pub const BENCHMARK_MEMORY_DECAY: usize = 20;
+
+/// Time spent in each system over a run, in the order the systems first ran.
+///
+/// Stored on its own entity, like the event queues, and only recorded when
+/// `FeatureFlags::time_systems` is set.
+pub struct SystemTimings(pub Vec<(&'static str, Duration)>);
+
+/// Run a system, adding its run time to `SystemTimings` if timing is enabled
+pub fn timed<T>(
+    world: &mut World,
+    features: FeatureFlags,
+    name: &'static str,
+    system: impl FnOnce(&mut World) -> T,
+) -> T {
+    if !features.time_systems {
+        return system(world);
+    }
+
+    let start = Instant::now();
+    let result = system(world);
+    let elapsed = start.elapsed();
+
+    if let Some((_, timings)) = world.query_mut::<&mut SystemTimings>().into_iter().next() {
+        match timings.0.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += elapsed,
+            None => timings.0.push((name, elapsed)),
+        }
+        return result;
+    }
+
+    world.spawn((SystemTimings(vec![(name, elapsed)]),));
+    return result;
+}
+
+/// Returns the time spent in each system so far
+pub fn read_timings(world: &World) -> Vec<(&'static str, Duration)> {
+    return world
+        .query::<&SystemTimings>()
+        .iter()
+        .next()
+        .map_or_else(Vec::new, |(_, timings)| timings.0.clone());
+}
+
+pub struct BenchmarkMap {
+    pub name: String,
+    pub map: String,
+}
+
+/// Maps for the benchmark suite, generated from fixed seeds so every run sees the same maps
+pub fn benchmark_maps(sizes: &[usize], wall_percents: &[u64]) -> Vec<BenchmarkMap> {
+    let mut maps = Vec::new();
+    for &size in sizes {
+        for &wall_percent in wall_percents {
+            let map = match wall_percent {
+                0 => create_map(size),
+                _ => create_random_map(size, wall_percent, size as u64 * 100 + wall_percent),
+            };
+            maps.push(BenchmarkMap {
+                name: format!("{}x{} {}% walls", size, size, wall_percent),
+                map: map,
+            });
+        }
+    }
+    return maps;
+}
+
+/// Every combination of the feature flags that change how the simulation runs, with a short
+/// name for each. Rendering and debug output are always off.
+pub fn feature_combinations() -> Vec<(String, FeatureFlags)> {
+    let mut combinations = Vec::new();
+    for algorithm in [
+        PathingAlgorithm::Astar,
+        PathingAlgorithm::LpaStar,
+        PathingAlgorithm::DStarLite,
+    ] {
+        for spatial_cache in [false, true] {
+            for travel_matrix in [false, true] {
+                for memory_decay in [None, Some(BENCHMARK_MEMORY_DECAY)] {
+                    let mut features = FeatureFlags::new();
+                    features.render = false;
+                    features.pathing_algorithm = algorithm;
+                    features.entity_spatial_cache = spatial_cache;
+                    features.travel_matrix_for_goal_distance = travel_matrix;
+                    features.memory_decay = memory_decay;
+                    features.time_systems = true;
+
+                    let algorithm_name = match algorithm {
+                        PathingAlgorithm::Astar => "astar",
+                        PathingAlgorithm::LpaStar => "lpa",
+                        PathingAlgorithm::DStarLite => "dstar",
+                    };
+                    let mut name = algorithm_name.to_string();
+                    if spatial_cache {
+                        name.push_str("+cache");
+                    }
+                    if travel_matrix {
+                        name.push_str("+matrix");
+                    }
+                    if memory_decay.is_some() {
+                        name.push_str("+decay");
+                    }
+                    combinations.push((name, features));
+                }
+            }
+        }
+    }
+    return combinations;
+}
+
+pub struct BenchmarkResult {
+    pub map: String,
+    pub features: String,
+    pub steps: i32,
+    pub expansions: usize,
+    pub total: Duration,
+    pub systems: Vec<(&'static str, Duration)>,
+}
+
+impl BenchmarkResult {
+    /// Tab separated column names for `to_row`, one column per system in `systems`
+    pub fn header(systems: &[&'static str]) -> String {
+        let mut columns = vec!["map", "features", "steps", "expansions", "total ms"];
+        columns.extend(systems.iter());
+        return columns.join("\t");
+    }
+
+    /// Tab separated results with times in milliseconds, systems that didn't run are left blank
+    pub fn to_row(&self, systems: &[&'static str]) -> String {
+        let mut columns = vec![
+            self.map.clone(),
+            self.features.clone(),
+            self.steps.to_string(),
+            self.expansions.to_string(),
+            format_ms(self.total),
+        ];
+        for name in systems {
+            let time = self.systems.iter().find(|(n, _)| n == name);
+            columns.push(time.map(|(_, t)| format_ms(*t)).unwrap_or_default());
+        }
+        return columns.join("\t");
+    }
+}
+
+fn format_ms(d: Duration) -> String {
+    return format!("{:.3}", d.as_secs_f64() * 1000.0);
+}
+
+/// Runs a map to the end headlessly
+pub fn run_benchmark(map: &BenchmarkMap, name: &str, features: FeatureFlags) -> BenchmarkResult {
+    let mut features = features;
+    features.render = false;
+
+    let mut world = World::new();
+    parse_map(&mut world, &map.map);
+    let start = Instant::now();
+    let stats = run_sim(&mut world, features);
+    let total = start.elapsed();
+
+    return BenchmarkResult {
+        map: map.name.clone(),
+        features: name.to_string(),
+        steps: stats.steps,
+        expansions: stats.expansions,
+        total: total,
+        systems: read_timings(&world),
+    };
+}
+
+/// Baseline attackers to compare against the default exploration, with a short name for each.
+/// Created fresh for each map.
+pub fn baseline_policies() -> Vec<(String, Box<dyn AttackerPolicy>)> {
+    return vec![
+        ("greedy".to_string(), Box::new(GreedyPolicy)),
+        (
+            "random".to_string(),
+            Box::new(RandomPolicy::new(BENCHMARK_RANDOM_SEED)),
+        ),
+    ];
+}
+
+/// Runs a map to the end headlessly with a different attacker. Expansions are always 0 since
+/// the baselines don't keep pathers.
+pub fn run_policy_benchmark(
+    map: &BenchmarkMap,
+    name: &str,
+    features: FeatureFlags,
+    policy: &mut dyn AttackerPolicy,
+) -> BenchmarkResult {
+    let mut features = features;
+    features.render = false;
+
+    let mut world = World::new();
+    parse_map(&mut world, &map.map);
+    set_movement_rules(&mut world, features.movement_rules());
+    let start = Instant::now();
+    system_vision(&mut world);
+    let mut steps = 0;
+    loop {
+        steps += 1;
+        if step_game_world_with_policy(&mut world, features, policy) {
+            break;
+        }
+    }
+    let total = start.elapsed();
+
+    return BenchmarkResult {
+        map: map.name.clone(),
+        features: name.to_string(),
+        steps: steps,
+        expansions: 0,
+        total: total,
+        systems: read_timings(&world),
+    };
+}
+
+/// Runs every map with every set of features
+pub fn run_suite(
+    maps: &[BenchmarkMap],
+    combinations: &[(String, FeatureFlags)],
+) -> Vec<BenchmarkResult> {
+    let mut results = Vec::new();
+    for map in maps {
+        for (name, features) in combinations {
+            results.push(run_benchmark(map, name, *features));
+        }
+    }
+    return results;
+}
+
+/// Names of every system timed in `results`, in the order they first ran
+pub fn timed_systems(results: &[BenchmarkResult]) -> Vec<&'static str> {
+    let mut systems = Vec::new();
+    for r in results {
+        for (name, _) in r.systems.iter() {
+            if !systems.contains(name) {
+                systems.push(*name);
+            }
+        }
+    }
+    return systems;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_suite() {
+        let maps = benchmark_maps(&[6], &[0, 20]);
+        assert_eq!(maps.len(), 2);
+        // same seed, same map
+        assert_eq!(maps[1].map, benchmark_maps(&[6], &[20])[0].map);
+
+        let combinations = feature_combinations();
+        assert_eq!(combinations.len(), 24);
+        let results = run_suite(&maps, &combinations);
+        assert_eq!(results.len(), 48);
+
+        for r in results.iter() {
+            let same = results
+                .iter()
+                .find(|o| o.map == r.map && o.features == "lpa")
+                .unwrap();
+            // Caching doesn't change the route
+            if r.features.starts_with("lpa") && !r.features.contains("decay") {
+                assert_eq!(r.steps, same.steps, "{}", r.features);
+            }
+        }
+
+        for (name, mut policy) in baseline_policies() {
+            let features = FeatureFlags::new();
+            let r = run_policy_benchmark(&maps[1], &name, features, policy.as_mut());
+            assert!(r.steps > 0);
+            assert_eq!(r.features, name);
+        }
+
+        let systems = timed_systems(&results);
+        assert!(systems.contains(&"exploration"));
+        assert!(systems.contains(&"spatial cache"));
+        let lpa = results.iter().find(|r| r.features == "lpa").unwrap();
+        assert!(lpa.expansions > 0);
+        let row = lpa.to_row(&systems);
+        assert!(row.starts_with("6x6 0% walls\tlpa\t"));
+        // no time for the spatial cache when it's disabled
+        assert_eq!(row.split('\t').count(), systems.len() + 5);
+        assert!(row.contains("\t\t"));
+        assert_eq!(
+            BenchmarkResult::header(&systems).split('\t').count(),
+            systems.len() + 5
+        );
+    }
+}
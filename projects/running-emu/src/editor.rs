@@ -0,0 +1,141 @@
+use crate::spatial::Point;
+
+/// Tiles that can be painted, see `parse_map` for what each one is
+pub const BRUSHES: &str = "@G.WDTO~=^v<>KL";
+
+/// A map being edited in the text format read by `parse_map`
+pub struct LevelEditor {
+    tiles: Vec<Vec<char>>,
+    brush: char,
+}
+
+impl LevelEditor {
+    /// Start editing a map, indentation and blank lines are ignored like in `parse_map`
+    pub fn from_map(map: &str) -> Result<Self, String> {
+        let tiles: Vec<Vec<char>> = map
+            .lines()
+            .map(|l| l.chars().filter(|c| *c != ' ').collect::<Vec<char>>())
+            .filter(|row| !row.is_empty())
+            .collect();
+
+        if tiles.is_empty() {
+            return Err("map is empty".to_string());
+        }
+        if tiles.iter().any(|row| row.len() != tiles[0].len()) {
+            return Err("rows vary in width".to_string());
+        }
+        if let Some(c) = tiles.iter().flatten().find(|c| !BRUSHES.contains(**c)) {
+            return Err(format!("unknown tile: {}", c));
+        }
+
+        return Ok(Self {
+            tiles: tiles,
+            brush: 'W',
+        });
+    }
+
+    pub fn tiles(&self) -> &Vec<Vec<char>> {
+        return &self.tiles;
+    }
+
+    pub fn brush(&self) -> char {
+        return self.brush;
+    }
+
+    /// Returns false and keeps the current brush if `c` isn't a tile
+    pub fn set_brush(&mut self, c: char) -> bool {
+        if !BRUSHES.contains(c) {
+            return false;
+        }
+        self.brush = c;
+        return true;
+    }
+
+    /// Paint the current brush at `p`, points off the map are ignored.
+    ///
+    /// There's only one start and goal, so painting either moves it.
+    pub fn paint(&mut self, p: Point) {
+        if p.y >= self.tiles.len() || p.x >= self.tiles[0].len() {
+            return;
+        }
+
+        if self.brush == '@' || self.brush == 'G' {
+            for c in self.tiles.iter_mut().flatten() {
+                if *c == self.brush {
+                    *c = '.';
+                }
+            }
+        }
+        self.tiles[p.y][p.x] = self.brush;
+    }
+
+    /// Checks the map can be simulated, it needs exactly one start and goal
+    pub fn validate(&self) -> Result<(), String> {
+        for (tile, name) in [('@', "start"), ('G', "goal")] {
+            let count = self.tiles.iter().flatten().filter(|c| **c == tile).count();
+            if count != 1 {
+                return Err(format!("map needs one {}, found {}", name, count));
+            }
+        }
+        return Ok(());
+    }
+
+    /// The map in the format read by `parse_map`
+    pub fn to_map(&self) -> String {
+        return self
+            .tiles
+            .iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_sim_from_map, FeatureFlags};
+
+    #[test]
+    fn test_level_editor() {
+        let mut editor = LevelEditor::from_map(
+            "@...
+            ....
+            ...G",
+        )
+        .unwrap();
+        assert_eq!(editor.to_map(), "@...\n....\n...G");
+
+        editor.paint(Point { x: 1, y: 0 });
+        editor.paint(Point { x: 1, y: 1 });
+        assert!(editor.set_brush('T'));
+        editor.paint(Point { x: 3, y: 0 });
+        assert!(!editor.set_brush('x'));
+        assert_eq!(editor.brush(), 'T');
+        // Off the map
+        editor.paint(Point { x: 4, y: 0 });
+        assert_eq!(editor.to_map(), "@W.T\n.W..\n...G");
+
+        // Moving the goal
+        editor.set_brush('G');
+        editor.paint(Point { x: 0, y: 2 });
+        assert_eq!(editor.tiles()[2], vec!['G', '.', '.', '.']);
+        assert!(editor.validate().is_ok());
+
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        assert!(run_sim_from_map(&editor.to_map(), features) > 0);
+
+        editor.set_brush('.');
+        editor.paint(Point { x: 0, y: 0 });
+        assert!(editor.validate().is_err());
+    }
+
+    #[test]
+    fn test_level_editor_invalid_maps() {
+        assert!(LevelEditor::from_map("").is_err());
+        assert!(LevelEditor::from_map("@..\n.G").is_err());
+        assert!(LevelEditor::from_map("@.x\n..G").is_err());
+        assert!(LevelEditor::from_map("@..\n..G\n").is_ok());
+    }
+}
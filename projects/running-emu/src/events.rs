@@ -0,0 +1,119 @@
+use hecs::{Component, Entity, World};
+
+use crate::spatial::Point;
+
+/// An entity attacked another this tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttackEvent {
+    pub target: Entity,
+    pub from: Entity,
+    pub amount: i32,
+}
+
+/// An entity became visible this tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiscoverEvent {
+    pub entity: Entity,
+}
+
+/// An entity ran out of health this tick and was despawned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeathEvent {
+    pub entity: Entity,
+}
+
+/// An entity changed position this tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveEvent {
+    pub entity: Entity,
+    pub from: Point,
+    pub to: Point,
+}
+
+/// An entity with a position was despawned this tick, for any reason
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DespawnEvent {
+    pub entity: Entity,
+    pub at: Point,
+}
+
+/// Events of a single type published during the current tick.
+///
+/// Stored on its own entity, like the spatial cache, and created on the first publish.
+pub struct EventQueue<E>(Vec<E>);
+
+/// Add an event for systems later in the tick to consume
+pub fn publish<E: Component>(world: &mut World, event: E) {
+    if let Some((_, queue)) = world.query_mut::<&mut EventQueue<E>>().into_iter().next() {
+        queue.0.push(event);
+        return;
+    }
+
+    world.spawn((EventQueue(vec![event]),));
+}
+
+/// Returns the events published so far this tick, in the order they were published
+pub fn read_events<E: Component + Clone>(world: &World) -> Vec<E> {
+    return world
+        .query::<&EventQueue<E>>()
+        .iter()
+        .next()
+        .map_or_else(Vec::new, |(_, queue)| queue.0.clone());
+}
+
+fn clear_queue<E: Component>(world: &mut World) {
+    for (_, queue) in world.query_mut::<&mut EventQueue<E>>() {
+        queue.0.clear();
+    }
+}
+
+/// Drop the events from the previous tick, should be run before any system that publishes
+pub fn system_clear_events(world: &mut World) {
+    clear_queue::<AttackEvent>(world);
+    clear_queue::<DiscoverEvent>(world);
+    clear_queue::<DeathEvent>(world);
+    clear_queue::<MoveEvent>(world);
+    clear_queue::<DespawnEvent>(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use hecs::World;
+
+    use super::{publish, read_events, system_clear_events, AttackEvent, DeathEvent};
+
+    #[test]
+    fn test_event_queue() {
+        let mut world = World::new();
+        let a = world.spawn(());
+        let b = world.spawn(());
+
+        assert!(read_events::<AttackEvent>(&world).is_empty());
+
+        let first = AttackEvent {
+            target: a,
+            from: b,
+            amount: 1,
+        };
+        let second = AttackEvent {
+            target: b,
+            from: a,
+            amount: 2,
+        };
+        publish(&mut world, first);
+        publish(&mut world, second);
+        publish(&mut world, DeathEvent { entity: a });
+
+        // Reading doesn't consume, every system sees the whole tick
+        assert_eq!(read_events::<AttackEvent>(&world), vec![first, second]);
+        assert_eq!(read_events::<AttackEvent>(&world), vec![first, second]);
+        assert_eq!(
+            read_events::<DeathEvent>(&world),
+            vec![DeathEvent { entity: a }]
+        );
+
+        system_clear_events(&mut world);
+        assert!(read_events::<AttackEvent>(&world).is_empty());
+        assert!(read_events::<DeathEvent>(&world).is_empty());
+    }
+}
@@ -7,15 +7,61 @@ use std::{
 use hecs::World;
 use itertools::Itertools;
 
-use crate::{get_max_point, spatial::Point, Attack, AttackerAgent, Health, Position, Visibility};
+use crate::{
+    get_max_point,
+    spatial::{Direction, Point},
+    Attack, AttackerAgent, Health, Keys, LockedDoor, OneWay, Position, TerrainCost, Visibility,
+    MOVE_COST,
+};
+
+/// Neighbors of a point, the 4 adjacent ones followed by the 4 diagonal ones
+type Edges = [Option<(Point, i32, EdgeType)>; 8];
+
+/// How agents are allowed to move, set from `FeatureFlags` at the start of each step.
+///
+/// Stored on its own entity like the event queues. Worlds without one use 4-connected movement
+/// and unsmoothed paths.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MovementRules {
+    /// Allow diagonal steps, as long as neither tile being cut past is blocked
+    pub diagonal: bool,
+    /// Straighten paths with `ai_pathing::smooth_path` after they're found
+    pub smooth_paths: bool,
+}
+
+/// Replaces the movement rules in `world`, only adding them if they aren't the default
+pub fn set_movement_rules(world: &mut World, rules: MovementRules) {
+    if let Some((_, r)) = world.query_mut::<&mut MovementRules>().into_iter().next() {
+        *r = rules;
+        return;
+    }
+    if rules != MovementRules::default() {
+        world.spawn((rules,));
+    }
+}
+
+pub fn get_movement_rules(world: &World) -> MovementRules {
+    return world
+        .query::<&MovementRules>()
+        .iter()
+        .next()
+        .map_or_else(MovementRules::default, |(_, rules)| *rules);
+}
+
+/// Diagonal steps cost about sqrt(2) times as much as moving onto the tile normally, rounded up
+/// so costs stay whole
+pub fn diagonal_move_cost(cost: i32) -> i32 {
+    return (cost * 3 + 1) / 2;
+}
 
 /// Underlying datastructure used for path finding
 #[derive(Debug)]
 pub struct CostMap {
     /// Vector of the list of neighbors, indexed as `x + y * height`
-    successors: Vec<[Option<(Point, i32, EdgeType)>; 4]>,
+    successors: Vec<Edges>,
     pub width: usize,
     pub height: usize,
+    pub rules: MovementRules,
 }
 
 impl CostMap {
@@ -64,10 +110,44 @@ impl CostMap {
             health_mask[pos.0.y][pos.0.x] = health.0;
         }
 
+        // Cost of moving onto a tile, unseen tiles are assumed to be plain
+        let mut move_mask = vec![vec![MOVE_COST; width]; height];
+        for (_, (pos, _, terrain)) in world
+            .query::<(&Position, &Visibility, &TerrainCost)>()
+            .into_iter()
+            .filter(|(_, (_, vis, _))| vis.0)
+        {
+            move_mask[pos.0.y][pos.0.x] = terrain.0;
+        }
+
+        let mut one_way_mask: Vec<Vec<Option<Direction>>> = vec![vec![None; width]; height];
+        for (_, (pos, _, one_way)) in world
+            .query::<(&Position, &Visibility, &OneWay)>()
+            .into_iter()
+            .filter(|(_, (_, vis, _))| vis.0)
+        {
+            one_way_mask[pos.0.y][pos.0.x] = Some(one_way.0);
+        }
+
+        // Locked doors can be moved through while the agent carries a key
+        let has_key = world
+            .query::<(&Keys, &AttackerAgent)>()
+            .into_iter()
+            .any(|(_, (keys, _))| keys.0 > 0);
+        let mut locked_mask = vec![vec![false; width]; height];
+        for (_, (pos, _, _)) in world
+            .query::<(&Position, &Visibility, &LockedDoor)>()
+            .into_iter()
+            .filter(|(_, (_, vis, _))| vis.0 && !has_key)
+        {
+            locked_mask[pos.0.y][pos.0.x] = true;
+        }
+
         let mut g = CostMap {
-            successors: vec![[None; 4]; width * height],
+            successors: vec![[None; 8]; width * height],
             width: max_p.x,
             height: max_p.y,
+            rules: get_movement_rules(world),
         };
 
         // Apply the costs
@@ -75,11 +155,21 @@ impl CostMap {
             for x in 0..width {
                 let to = Point { x: x, y: y };
                 for from in get_neighbors(to, width, height) {
+                    // One way tiles can only be entered and left moving in their direction
+                    let dir = from.direction_to(&to);
+                    let wrong_way = [from, to]
+                        .iter()
+                        .any(|p| one_way_mask[p.y][p.x].is_some_and(|d| Some(d) != dir));
+                    if wrong_way || locked_mask[to.y][to.x] {
+                        continue; // No edge
+                    }
+
                     // The cost to travel to a node is:
-                    // the damage you receive upon arriving + the damage you'll take while killing whatever is on the tile + 1 (for travel)
-                    let cost = dmg_mask[to.y][to.x]
-                        + health_mask[to.y][to.x] * dmg_mask[from.y][from.x]
-                        + 1;
+                    // the damage you receive upon arriving + the damage you'll take while killing whatever is on the tile + the terrain cost (for travel)
+                    let cost = (dmg_mask[to.y][to.x]
+                        + health_mask[to.y][to.x] * dmg_mask[from.y][from.x])
+                        * MOVE_COST
+                        + move_mask[to.y][to.x];
                     let edge_type = match vis_mask[from.y][from.x] && vis_mask[to.y][to.x] {
                         true => EdgeType::Visible,
                         _ => EdgeType::Fog,
@@ -89,6 +179,45 @@ impl CostMap {
             }
         }
 
+        if !g.rules.diagonal {
+            return g;
+        }
+
+        // Diagonal steps need both tiles being cut past to be open, so are added once the
+        // adjacent edges are known
+        for y in 0..height {
+            for x in 0..width {
+                let to = Point { x: x, y: y };
+                for from in get_diagonal_neighbors(to, width, height) {
+                    let corners = [Point { x: to.x, y: from.y }, Point { x: from.x, y: to.y }];
+                    let open = corners.iter().all(|c| {
+                        health_mask[c.y][c.x] == 0
+                            && g.get_edge(from, *c).is_some()
+                            && g.get_edge(*c, to).is_some()
+                    });
+                    let wrong_way = [from, to].iter().any(|p| one_way_mask[p.y][p.x].is_some());
+                    if !open || wrong_way || locked_mask[to.y][to.x] {
+                        continue; // No edge
+                    }
+
+                    let cost = (dmg_mask[to.y][to.x]
+                        + health_mask[to.y][to.x] * dmg_mask[from.y][from.x])
+                        * MOVE_COST
+                        + diagonal_move_cost(move_mask[to.y][to.x]);
+                    // Can't know a corner is open without seeing it
+                    let visible = [from, to]
+                        .iter()
+                        .chain(corners.iter())
+                        .all(|p| vis_mask[p.y][p.x]);
+                    let edge_type = match visible {
+                        true => EdgeType::Visible,
+                        _ => EdgeType::Fog,
+                    };
+                    g.set_cost(from, to, cost, edge_type);
+                }
+            }
+        }
+
         return g;
     }
 
@@ -98,9 +227,10 @@ impl CostMap {
         let height = vec.len();
 
         let mut g = CostMap {
-            successors: vec![[None; 4]; width * height],
+            successors: vec![[None; 8]; width * height],
             width: width,
             height: height,
+            rules: MovementRules::default(),
         };
 
         for y in 0..height {
@@ -125,26 +255,34 @@ impl CostMap {
     }
 
     #[inline]
-    fn get_successors_mut(&mut self, p: Point) -> &mut [Option<(Point, i32, EdgeType)>; 4] {
+    fn get_successors_mut(&mut self, p: Point) -> &mut Edges {
         return &mut self.successors[p.x + p.y * self.width];
     }
 
     /// Returns a list of successors and the cost to move to each
-    fn get_successors(&self, p: Point) -> [Option<(Point, i32, EdgeType)>; 4] {
+    fn get_successors(&self, p: Point) -> Edges {
         return self.successors[p.x + p.y * self.width];
     }
 
-    /// Returns a list of predecessors and the cost to move to each
-    fn get_predecessors(&self, p: Point) -> [Option<(Point, i32, EdgeType)>; 4] {
-        // Graph is always bi directional, so can use successort to look up predecessors
-        let successors = self.get_successors(p);
-        let mut predecessors = [None; 4];
-
-        for (s, _, _) in successors.iter().filter_map(|x| x.as_ref()) {
-            // Should be fine since bi-directional
-            let (cost, t) = self.get_edge(*s, p).unwrap();
-            let index = CostMap::get_index(*s, p).unwrap();
-            predecessors[index] = Some((*s, cost, t));
+    /// Points that can have an edge to or from `p`, including diagonal ones if they're allowed
+    pub fn neighbors(&self, p: Point) -> Vec<Point> {
+        let mut neighbors = get_neighbors(p, self.width, self.height);
+        if self.rules.diagonal {
+            neighbors.extend(get_diagonal_neighbors(p, self.width, self.height));
+        }
+        return neighbors;
+    }
+
+    /// Returns a list of predecessors and the cost to move from each
+    fn get_predecessors(&self, p: Point) -> Edges {
+        // Edges can be one way, so need to check each neighbor for an edge to `p`
+        let mut predecessors = [None; 8];
+
+        for n in self.neighbors(p) {
+            if let Some((cost, t)) = self.get_edge(n, p) {
+                let index = CostMap::get_index(n, p).unwrap();
+                predecessors[index] = Some((n, cost, t));
+            }
         }
         return predecessors;
     }
@@ -175,6 +313,10 @@ impl CostMap {
             (-1, 0) => Some(1),
             (0, -1) => Some(2),
             (0, 1) => Some(3),
+            (1, 1) => Some(4),
+            (-1, -1) => Some(5),
+            (1, -1) => Some(6),
+            (-1, 1) => Some(7),
             _ => None,
         };
     }
@@ -182,9 +324,21 @@ impl CostMap {
 
 /// Return positions that are neighbors to a givne position
 pub fn get_neighbors(point: Point, width: usize, height: usize) -> Vec<Point> {
-    // Allow adjacent and diagonal movement
-    // let directions = [(-1, 0), (1, 0), (0, -1), (0, 1), (1, 1), (-1, -1), (1, -1), (-1, 1)];
-    let directions = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+    return get_points_offset(point, width, height, &[(-1, 0), (1, 0), (0, -1), (0, 1)]);
+}
+
+/// Return positions diagonal to a given position, only neighbors when
+/// `MovementRules::diagonal` is set
+pub fn get_diagonal_neighbors(point: Point, width: usize, height: usize) -> Vec<Point> {
+    return get_points_offset(point, width, height, &[(1, 1), (-1, -1), (1, -1), (-1, 1)]);
+}
+
+fn get_points_offset(
+    point: Point,
+    width: usize,
+    height: usize,
+    directions: &[(i32, i32)],
+) -> Vec<Point> {
     let mut neighbors: Vec<Point> = Vec::new();
 
     for d in directions {
@@ -237,7 +391,7 @@ impl<'a> CostMapView<'a> {
         return self.filter_edges(predecessors);
     }
 
-    fn filter_edges(&self, edges: [Option<(Point, i32, EdgeType)>; 4]) -> Vec<(Point, i32)> {
+    fn filter_edges(&self, edges: Edges) -> Vec<(Point, i32)> {
         let results = edges
             .iter()
             .filter_map(|x| x.as_ref())
@@ -247,6 +401,14 @@ impl<'a> CostMapView<'a> {
         return results;
     }
 
+    pub fn rules(&self) -> MovementRules {
+        return self.cost_map.rules;
+    }
+
+    pub fn neighbors(&self, p: Point) -> Vec<Point> {
+        return self.cost_map.neighbors(p);
+    }
+
     pub fn get_cost(&self, from: Point, to: Point) -> Option<i32> {
         let successors = self.get_successors(from);
         let (_, cost) = successors.iter().find(|(p, _)| *p == to)?;
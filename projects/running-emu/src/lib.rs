@@ -6,18 +6,32 @@ use crossterm::{
     execute,
     style::{Color, ResetColor, SetBackgroundColor},
 };
-use hecs::{Entity, World};
+use events::{
+    publish, read_events, system_clear_events, AttackEvent, DeathEvent, DespawnEvent, DiscoverEvent,
+};
+use hecs::World;
 use spatial::system_update_spatial_cache;
 
 use crate::{
     ai_pathing::{system_ai_action, system_exploration, system_path_highlight},
-    spatial::Point,
+    benchmark::timed,
+    graph::{get_movement_rules, set_movement_rules, MovementRules},
+    policy::{AttackerPolicy, ExplorationPolicy},
+    replay::{system_record_replay, system_start_replay},
+    spatial::{entities_in_rect, get_entities, Direction, Point},
 };
 
 pub mod ai;
 pub mod ai_pathing;
+pub mod benchmark;
+pub mod editor;
+pub mod events;
 pub mod graph;
+pub mod policy;
 pub mod render;
+pub mod replay;
+pub mod save;
+pub mod snapshot;
 pub mod spatial;
 
 /// Position of the entity in the game world
@@ -31,15 +45,31 @@ pub struct Vision(pub usize);
 pub struct TargetLocation(pub Option<Point>);
 pub struct AttackerAgent;
 pub struct Health(pub i32);
-pub struct Damage {
-    pub amount: i32,
-    pub from: Entity,
-}
 /// Damage a unit can do
 pub struct Attack {
     pub damage: i32,
     pub range: usize,
 }
+/// Ticks a visible unit has been out of sight of the agents
+pub struct Memory(pub usize);
+/// Cost of moving onto the tile in place of `MOVE_COST`
+pub struct TerrainCost(pub i32);
+/// The tile can only be entered and left moving in this direction
+pub struct OneWay(pub Direction);
+/// Picked up by an agent moving onto the tile
+pub struct Key;
+/// Blocks movement until an agent carrying a key moves through it, using up the key
+pub struct LockedDoor;
+/// Number of keys an agent is carrying
+pub struct Keys(pub usize);
+
+/// Cost of moving onto a plain tile. Costs are in half moves so roads can be cheaper than plain
+/// tiles, each point of damage costs as much as a plain move
+pub const MOVE_COST: i32 = 2;
+/// Cheapest cost of moving onto any tile, used by the pathing heuristics
+pub const MIN_MOVE_COST: i32 = ROAD_COST;
+pub const ROAD_COST: i32 = 1;
+pub const MUD_COST: i32 = 3 * MOVE_COST;
 
 #[derive(Clone, Copy)]
 pub struct FeatureFlags {
@@ -54,12 +84,27 @@ pub struct FeatureFlags {
     pub write_agent_visible_map: bool,
     pub pathing_algorithm: PathingAlgorithm,
     pub print_tile_costs: bool,
+    /// Forget units and tiles that have been out of sight for more than this many ticks, so
+    /// they must be scouted again. `None` remembers everything that was seen. Too short a
+    /// decay and the agent can pace between tiles it keeps forgetting
+    pub memory_decay: Option<usize>,
+    /// Record how long each system takes in `benchmark::SystemTimings`
+    pub time_systems: bool,
+    /// Record every step in a `replay::Replay` so the run can be played back afterwards
+    pub record_replay: bool,
+    /// Allow agents to step diagonally, see `graph::MovementRules`. Vision range is counted in
+    /// the same steps, so agents see their diagonal neighbors
+    pub diagonal_movement: bool,
+    /// Straighten agent paths after they're found, see `ai_pathing::smooth_path`
+    pub path_smoothing: bool,
 }
 
 #[derive(Clone, Copy)]
 pub enum PathingAlgorithm {
     Astar,
     LpaStar,
+    /// LPA* that follows the agent and only searches until the exploration candidates are costed
+    DStarLite,
 }
 
 impl FeatureFlags {
@@ -71,6 +116,18 @@ impl FeatureFlags {
             write_agent_visible_map: false,
             pathing_algorithm: PathingAlgorithm::LpaStar,
             print_tile_costs: false,
+            memory_decay: None,
+            time_systems: false,
+            record_replay: false,
+            diagonal_movement: false,
+            path_smoothing: false,
+        };
+    }
+
+    pub fn movement_rules(&self) -> MovementRules {
+        return MovementRules {
+            diagonal: self.diagonal_movement,
+            smooth_paths: self.path_smoothing,
         };
     }
 }
@@ -79,62 +136,153 @@ impl FeatureFlags {
 ///
 /// Main entry point for running a simulation
 pub fn run_sim_from_map(map: &str, features: FeatureFlags) -> i32 {
+    return run_sim_with_stats(map, features).steps;
+}
+
+/// Work done by a simulation run, for comparing pathing algorithms
+#[derive(Debug, Clone, Copy)]
+pub struct SimStats {
+    pub steps: i32,
+    /// Nodes expanded by the start and goal pathers while replanning, always 0 for
+    /// `PathingAlgorithm::Astar`
+    pub expansions: usize,
+}
+
+pub fn run_sim_with_stats(map: &str, features: FeatureFlags) -> SimStats {
     let mut world = hecs::World::new();
     parse_map(&mut world, map);
     return run_sim(&mut world, features);
 }
 
-fn run_sim(world: &mut World, features: FeatureFlags) -> i32 {
+fn run_sim(world: &mut World, features: FeatureFlags) -> SimStats {
     let mut num_steps = 0;
+    set_movement_rules(world, features.movement_rules());
     let mut start_pather = get_start_lpapather(&world);
     let mut goal_pather = get_goal_lpapather(&world);
 
     // Bootstrap
     system_vision(world);
 
+    let initial_expansions = start_pather.expansions() + goal_pather.expansions();
+
     loop {
         num_steps += 1;
         if step_game_world(world, features, &mut start_pather, &mut goal_pather) {
             break;
         }
     }
+    return SimStats {
+        steps: num_steps,
+        expansions: start_pather.expansions() + goal_pather.expansions() - initial_expansions,
+    };
+}
+
+/// Run the simulation on a map with a different attacker, see `policy`
+pub fn run_sim_with_policy(
+    map: &str,
+    features: FeatureFlags,
+    policy: &mut dyn AttackerPolicy,
+) -> i32 {
+    let mut world = hecs::World::new();
+    parse_map(&mut world, map);
+    set_movement_rules(&mut world, features.movement_rules());
+    system_vision(&mut world);
+
+    let mut num_steps = 0;
+    loop {
+        num_steps += 1;
+        if step_game_world_with_policy(&mut world, features, policy) {
+            break;
+        }
+    }
     return num_steps;
 }
 
+/// Advance the simulation a step with the default `ExplorationPolicy`, returns true once the
+/// goal is reached
 pub fn step_game_world(
     world: &mut World,
     features: FeatureFlags,
     start_pather: &mut LpaStarPather,
     goal_pather: &mut LpaStarPather,
 ) -> bool {
+    let mut policy = ExplorationPolicy {
+        pathing_algorithm: features.pathing_algorithm,
+        start_pather: start_pather,
+        goal_pather: goal_pather,
+    };
+    return step_game_world_with_policy(world, features, &mut policy);
+}
+
+pub fn step_game_world_with_policy(
+    world: &mut World,
+    features: FeatureFlags,
+    policy: &mut dyn AttackerPolicy,
+) -> bool {
+    // Catches up on the previous tick's moves, so must run before its events are cleared
     if features.entity_spatial_cache {
-        system_update_spatial_cache(world);
+        timed(
+            world,
+            features,
+            "spatial cache",
+            system_update_spatial_cache,
+        );
     }
 
-    if system_exploration(world, features, start_pather, goal_pather) {
-        return true;
+    timed(world, features, "clear events", system_clear_events);
+
+    set_movement_rules(world, features.movement_rules());
+
+    if features.record_replay {
+        timed(world, features, "replay", system_start_replay);
     }
 
-    system_path_highlight(world);
-    let char_buffer = build_char_output(&world);
-    let highlight_buffer = build_highlight_output(world);
-    if features.render {
-        system_render(&char_buffer, &highlight_buffer);
+    let done = timed(world, features, "exploration", |world| {
+        system_exploration(world, policy)
+    });
+    if done {
+        return true;
     }
 
+    timed(world, features, "path highlight", system_path_highlight);
+    let (char_buffer, highlight_buffer) = timed(world, features, "render", |world| {
+        let char_buffer = build_char_output(&world);
+        let highlight_buffer = build_highlight_output(world);
+        if features.render {
+            system_render(&char_buffer, &highlight_buffer);
+        }
+        (char_buffer, highlight_buffer)
+    });
+
     if features.print_tile_costs {
         system_print_tile_costs(world);
     }
 
-    system_ai_action(world);
-    system_defense_ai(world);
-    system_vision(world);
-    system_health(world); // Can despawn enemies so, should be run last
+    // Systems communicate through events published earlier in the tick
+    timed(world, features, "ai action", system_ai_action);
+    timed(world, features, "keys", system_keys);
+    timed(world, features, "defense ai", system_defense_ai);
+    timed(world, features, "vision", system_vision);
+    if let Some(decay) = features.memory_decay {
+        timed(world, features, "memory decay", |world| {
+            system_memory_decay(world, decay)
+        });
+    }
+    timed(world, features, "health", system_health); // Can despawn enemies so, should be run last
+
+    if features.record_replay {
+        timed(world, features, "replay", |world| {
+            system_record_replay(world, char_buffer, highlight_buffer)
+        });
+    }
 
     return false;
 }
 
 /// Populate a world from a string map
+///
+/// Tiles are `@` start, `G` goal, `.` empty, `W`/`D` walls with 50/25 health, `T` tower, `O` pit,
+/// `~` mud, `=` road, `^`/`v`/`<`/`>` one way, `K` key and `L` locked door.
 pub fn parse_map(world: &mut World, map: &str) {
     let mut x = 0;
     let mut y = 0;
@@ -181,6 +329,7 @@ pub fn parse_map(world: &mut World, map: &str) {
                             range: 1,
                         },
                         Health(500),
+                        Keys(0),
                     ));
                     world.spawn((Position(p), Sprite('S'), Visibility(true)))
                 }
@@ -214,6 +363,35 @@ pub fn parse_map(world: &mut World, map: &str) {
                         damage: 100,
                     },
                 )),
+                '~' => world.spawn((
+                    Position(p),
+                    Sprite(c),
+                    Visibility(false),
+                    TerrainCost(MUD_COST),
+                )),
+                '=' => world.spawn((
+                    Position(p),
+                    Sprite(c),
+                    Visibility(false),
+                    TerrainCost(ROAD_COST),
+                )),
+                '^' | 'v' | '<' | '>' => {
+                    let direction = match c {
+                        '^' => Direction::Up,
+                        'v' => Direction::Down,
+                        '<' => Direction::Left,
+                        _ => Direction::Right,
+                    };
+                    world.spawn((Position(p), Sprite(c), Visibility(false), OneWay(direction)))
+                }
+                'K' => {
+                    world.spawn((Position(p), Sprite(c), Visibility(false), Key));
+                    world.spawn((Position(p), Sprite('.'), Visibility(false))) // Spawn empty tile underneath
+                }
+                'L' => {
+                    world.spawn((Position(p), Sprite(c), Visibility(false), LockedDoor));
+                    world.spawn((Position(p), Sprite('.'), Visibility(false))) // Spawn empty tile underneath
+                }
                 '.' => world.spawn((Position(p), Sprite(c), Visibility(false))), // All others must be found
                 _ => panic!("Error spawning entities, unknown tile: {}", c),
             };
@@ -280,7 +458,14 @@ pub fn system_render(output_char: &Vec<Vec<char>>, output_highlight: &Vec<Vec<Op
     println!("");
 }
 
+/// Reveal entities in sight of an agent or that attacked this tick
+///
+/// Publishes a `DiscoverEvent` for each entity that wasn't already visible.
 pub fn system_vision(world: &mut World) {
+    let mut discovered = Vec::new();
+    // Sight is measured in steps, so reaches further along diagonals when they can be walked
+    let diagonal = get_movement_rules(world).diagonal;
+
     // Make entities visible based on line of sight
     let mut ids = Vec::new();
     for (id, (_, _)) in world.query_mut::<(&Position, &Vision)>() {
@@ -290,21 +475,90 @@ pub fn system_vision(world: &mut World) {
     for id in ids {
         let agent_pos = world.get::<Position>(id).unwrap().0;
         let agent_sight = world.get::<Vision>(id).unwrap().0;
-        for (_, (position, visibility)) in world.query_mut::<(&Position, &mut Visibility)>() {
-            if agent_pos.dist(&position.0) <= agent_sight as i32 {
+        let min = Point {
+            x: agent_pos.x.saturating_sub(agent_sight),
+            y: agent_pos.y.saturating_sub(agent_sight),
+        };
+        let max = Point {
+            x: agent_pos.x + agent_sight,
+            y: agent_pos.y + agent_sight,
+        };
+        for e in entities_in_rect(world, min, max) {
+            // The cache catches up at the start of the next tick, so can still hold entities
+            // despawned earlier in this one
+            let (position, mut visibility) =
+                match (world.get::<Position>(e), world.get_mut::<Visibility>(e)) {
+                    (Ok(p), Ok(v)) => (p, v),
+                    _ => continue,
+                };
+            let dist = match diagonal {
+                true => agent_pos.diagonal_dist(&position.0),
+                false => agent_pos.dist(&position.0),
+            };
+            if !visibility.0 && dist <= agent_sight as i32 {
                 visibility.0 = true;
+                discovered.push(e);
             }
         }
     }
 
     // Make entities visible based on attacking
-    let mut attackers = Vec::new();
-    for (_, dmg) in world.query_mut::<&Damage>() {
-        attackers.push(dmg.from);
+    for attack in read_events::<AttackEvent>(world) {
+        let visible = world.get::<Visibility>(attack.from).map_or(false, |v| v.0);
+        if !visible {
+            world.insert_one(attack.from, Visibility(true)).unwrap();
+            discovered.push(attack.from);
+        }
+    }
+
+    for entity in discovered {
+        publish(world, DiscoverEvent { entity });
+    }
+}
+
+/// Forget units and tiles that have been out of sight for more than `decay` ticks
+///
+/// A forgotten tile's cost is unknown until it's seen again by `system_vision`, so it's explored
+/// again like any other tile in the fog. The goal is known from the start and is never forgotten.
+pub fn system_memory_decay(world: &mut World, decay: usize) {
+    let mut agents = Vec::new();
+    for (_, (p, v)) in world.query_mut::<(&Position, &Vision)>() {
+        agents.push((p.0, v.0));
+    }
+
+    let mut first_seen = Vec::new();
+    for (e, (position, visibility, memory, sprite)) in world
+        .query::<(
+            &Position,
+            &mut Visibility,
+            Option<&mut Memory>,
+            Option<&Sprite>,
+        )>()
+        .without::<AttackerAgent>()
+        .iter()
+    {
+        if !visibility.0 || sprite.map_or(false, |s| s.0 == 'G') {
+            continue;
+        }
+
+        let in_sight = agents
+            .iter()
+            .any(|(p, sight)| p.dist(&position.0) <= *sight as i32);
+        match memory {
+            None => first_seen.push(e),
+            Some(m) if in_sight => m.0 = 0,
+            Some(m) => {
+                m.0 += 1;
+                if m.0 > decay {
+                    visibility.0 = false;
+                    m.0 = 0;
+                }
+            }
+        }
     }
 
-    for attacker in attackers {
-        world.insert_one(attacker, Visibility(true)).unwrap();
+    for e in first_seen {
+        world.insert_one(e, Memory(0)).unwrap();
     }
 }
 
@@ -365,26 +619,89 @@ pub fn create_map(size: usize) -> String {
     return map;
 }
 
-pub fn system_health(world: &mut World) {
-    let mut damage_removal = Vec::new();
-    let mut entity_despawn = Vec::new();
+/// Create a map with walls scattered over `wall_percent` of the tiles.
+///
+/// Uses a small xorshift generator so the same `seed` always gives the same map. Agents can attack
+/// through walls so there's always a route to the goal.
+pub fn create_random_map(size: usize, wall_percent: u64, seed: u64) -> String {
+    let mut state = seed.max(1);
+    let mut map = String::from("");
+
+    for y in 0..size {
+        for x in 0..size {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let c = match (x, y) {
+                (0, 0) => '@',
+                (x, y) if x == size - 1 && y == size - 1 => 'G',
+                _ if state % 100 < wall_percent => 'W',
+                _ => '.',
+            };
+            map.push(c);
+        }
+        if y < size - 1 {
+            map.push('\n')
+        }
+    }
 
-    for (e, (health, dmg)) in world.query_mut::<(&mut Health, &Damage)>() {
-        health.0 = health.0 - dmg.amount;
+    return map;
+}
+
+/// Pick up keys agents are standing on and unlock doors they've moved into
+///
+/// Agents only path through locked doors while carrying a key, so an agent on a door always has
+/// one to use. Publishes a `DespawnEvent` for each key and door used.
+pub fn system_keys(world: &mut World) {
+    let mut agents = Vec::new();
+    for (e, (p, _)) in world.query_mut::<(&Position, &Keys)>() {
+        agents.push((e, p.0));
+    }
 
-        if health.0 <= 0 {
-            entity_despawn.push(e);
-        } else {
-            damage_removal.push(e);
+    let mut despawn = Vec::new();
+    for (agent, p) in agents {
+        for e in get_entities(world, p) {
+            let mut keys = world.get_mut::<Keys>(agent).unwrap();
+            if world.get::<Key>(e).is_ok() {
+                keys.0 += 1;
+            } else if world.get::<LockedDoor>(e).is_ok() && keys.0 > 0 {
+                keys.0 -= 1;
+            } else {
+                continue;
+            }
+            despawn.push((e, p));
         }
     }
 
-    for e in damage_removal {
-        world.remove_one::<Damage>(e).unwrap();
+    for (e, p) in despawn {
+        world.despawn(e).unwrap();
+        publish(world, DespawnEvent { entity: e, at: p });
+    }
+}
+
+/// Apply this tick's attacks and despawn entities that run out of health
+///
+/// Publishes a `DeathEvent` for each despawned entity, and a `DespawnEvent` if it had a position.
+pub fn system_health(world: &mut World) {
+    let mut entity_despawn = Vec::new();
+
+    for attack in read_events::<AttackEvent>(world) {
+        if let Ok(mut health) = world.get_mut::<Health>(attack.target) {
+            health.0 = health.0 - attack.amount;
+
+            if health.0 <= 0 && !entity_despawn.contains(&attack.target) {
+                entity_despawn.push(attack.target);
+            }
+        }
     }
 
     for e in entity_despawn {
+        let at = world.get::<Position>(e).map(|p| p.0).ok();
         world.despawn(e).unwrap();
+        publish(world, DeathEvent { entity: e });
+        if let Some(at) = at {
+            publish(world, DespawnEvent { entity: e, at: at });
+        }
     }
 }
 
@@ -393,6 +710,8 @@ mod test {
     use super::*;
     #[allow(unused_imports)]
     use hecs::World;
+    #[allow(unused_imports)]
+    use policy::Observation;
 
     #[test]
     fn create_map_empty() {
@@ -400,6 +719,15 @@ mod test {
         assert_eq!(map, "@..\n...\n..G")
     }
 
+    #[test]
+    fn create_random_map_repeatable() {
+        let map = create_random_map(10, 20, 7);
+        assert_eq!(map, create_random_map(10, 20, 7));
+        assert_ne!(map, create_random_map(10, 20, 8));
+        assert!(map.starts_with('@') && map.ends_with('G'));
+        assert!(map.contains('W'));
+    }
+
     #[test]
     fn test_simple_map_parse() {
         let map = "@..
@@ -432,6 +760,32 @@ mod test {
         assert_eq!(num_steps, 375)
     }
 
+    #[test]
+    fn test_d_star_lite() {
+        let spiral = "@..............
+                        .OOOOOOOOOOOOO.
+                        .O...........O.
+                        .O.OOOOOOOOO.O.
+                        .O.O.......O.O.
+                        .O.OOOOOOO.O.O.
+                        .O......GO.O.O.
+                        .OOOOOOOOO.O.O.
+                        ...........O...";
+
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        for map in [spiral, &create_random_map(20, 25, 3)] {
+            features.pathing_algorithm = PathingAlgorithm::LpaStar;
+            let lpa = run_sim_with_stats(map, features);
+            features.pathing_algorithm = PathingAlgorithm::DStarLite;
+            let d_star = run_sim_with_stats(map, features);
+
+            // Same costs for the candidates, so the agent takes the same route
+            assert_eq!(d_star.steps, lpa.steps);
+            assert!(d_star.expansions <= lpa.expansions);
+        }
+    }
+
     #[test]
     fn test_empty_map() {
         let map = &&create_map(10);
@@ -442,36 +796,193 @@ mod test {
         assert_eq!(num_steps, 19)
     }
 
+    #[test]
+    fn test_diagonal_movement() {
+        let maps = [
+            (create_map(10), 19, 10),
+            ("@...T..G\n........\n........".to_string(), 12, 9),
+            ("@...O..G\n........".to_string(), 10, 8),
+            ("K.@LG".to_string(), 7, 7),
+        ];
+
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        for (map, steps, diagonal_steps) in maps.iter() {
+            for algorithm in [
+                PathingAlgorithm::Astar,
+                PathingAlgorithm::LpaStar,
+                PathingAlgorithm::DStarLite,
+            ] {
+                features.pathing_algorithm = algorithm;
+                for smoothing in [false, true] {
+                    features.path_smoothing = smoothing;
+                    features.diagonal_movement = false;
+                    assert_eq!(run_sim_from_map(map, features), *steps);
+                    features.diagonal_movement = true;
+                    assert_eq!(run_sim_from_map(map, features), *diagonal_steps);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_health_system() {
         let mut world = hecs::World::new();
         let attacker = world.spawn(());
 
-        let e = world.spawn((
-            Health(10),
-            Damage {
-                amount: 5,
+        let e = world.spawn((Health(10),));
+        publish(
+            &mut world,
+            AttackEvent {
+                target: e,
                 from: attacker,
+                amount: 5,
             },
-        ));
-        assert_eq!(world.len(), 2);
+        );
+        // attacker, target and the attack event queue
+        assert_eq!(world.len(), 3);
         system_health(&mut world);
         assert_eq!(world.get::<Health>(e).unwrap().0, 5);
+        system_clear_events(&mut world);
         system_health(&mut world);
-        // No more damage done, it was consumed
+        // No more damage done, the event was cleared with the tick
         assert_eq!(world.get::<Health>(e).unwrap().0, 5);
-        world
-            .insert_one(
-                e,
-                Damage {
-                    amount: 10,
+
+        // Attacks in the same tick add up
+        for _ in 0..2 {
+            publish(
+                &mut world,
+                AttackEvent {
+                    target: e,
                     from: attacker,
+                    amount: 3,
                 },
-            )
+            );
+        }
+        system_health(&mut world);
+        assert_eq!(world.len(), 3);
+        assert_eq!(
+            read_events::<DeathEvent>(&world),
+            vec![DeathEvent { entity: e }]
+        );
+        assert!(world.get::<Health>(e).is_err());
+    }
+
+    #[test]
+    fn test_vision_system() {
+        // Map of:
+        // @.T
+        let mut world = hecs::World::new();
+        world.spawn((Position(Point { x: 0, y: 0 }), Visibility(true), Vision(1)));
+        let near = world.spawn((Position(Point { x: 1, y: 0 }), Visibility(false)));
+        let tower = world.spawn((Position(Point { x: 2, y: 0 }), Visibility(false)));
+
+        system_vision(&mut world);
+        assert!(world.get::<Visibility>(near).unwrap().0);
+        assert!(!world.get::<Visibility>(tower).unwrap().0);
+        assert_eq!(
+            read_events::<DiscoverEvent>(&world),
+            vec![DiscoverEvent { entity: near }]
+        );
+
+        // Attacking gives away the tower's position
+        system_clear_events(&mut world);
+        publish(
+            &mut world,
+            AttackEvent {
+                target: near,
+                from: tower,
+                amount: 1,
+            },
+        );
+        system_vision(&mut world);
+        assert!(world.get::<Visibility>(tower).unwrap().0);
+        assert_eq!(
+            read_events::<DiscoverEvent>(&world),
+            vec![DiscoverEvent { entity: tower }]
+        );
+    }
+
+    #[test]
+    fn test_memory_decay_system() {
+        // Map of:
+        // @T
+        let mut world = hecs::World::new();
+        let agent = world.spawn((Position(Point { x: 0, y: 0 }), Visibility(true), Vision(1)));
+        let tower = world.spawn((Position(Point { x: 1, y: 0 }), Visibility(false), Health(5)));
+        let floor = world.spawn((Position(Point { x: 1, y: 0 }), Visibility(false)));
+
+        system_vision(&mut world);
+        system_memory_decay(&mut world, 1);
+        assert!(world.get::<Visibility>(tower).unwrap().0);
+
+        // Remembered for 1 tick after moving out of sight
+        world.get_mut::<Position>(agent).unwrap().0 = Point { x: 5, y: 0 };
+        system_memory_decay(&mut world, 1);
+        assert!(world.get::<Visibility>(tower).unwrap().0);
+        system_memory_decay(&mut world, 1);
+        assert!(!world.get::<Visibility>(tower).unwrap().0);
+        assert!(!world.get::<Visibility>(floor).unwrap().0);
+
+        world.get_mut::<Position>(agent).unwrap().0 = Point { x: 0, y: 0 };
+        system_vision(&mut world);
+        assert!(world.get::<Visibility>(tower).unwrap().0);
+        assert!(world.get::<Visibility>(floor).unwrap().0);
+    }
+
+    #[test]
+    fn test_forgotten_tiles_explored_again() {
+        struct Recorder(Vec<Point>);
+        impl AttackerPolicy for Recorder {
+            fn choose_target(&mut self, observation: &Observation) -> Option<Point> {
+                self.0 = observation.candidates.clone();
+                return Some(observation.goal);
+            }
+        }
+
+        let mut world = World::new();
+        parse_map(&mut world, "@...G");
+        system_vision(&mut world);
+        let (agent, _) = world
+            .query_mut::<&AttackerAgent>()
+            .into_iter()
+            .next()
             .unwrap();
+        world.get_mut::<Position>(agent).unwrap().0 = Point { x: 3, y: 0 };
+        system_vision(&mut world);
+        for _ in 0..3 {
+            system_memory_decay(&mut world, 1);
+        }
 
-        system_health(&mut world);
-        assert_eq!(world.len(), 1);
+        // The tiles behind the agent are back in the fog, so the edge of what it remembers is
+        // worth exploring again
+        let mut policy = Recorder(Vec::new());
+        system_exploration(&mut world, &mut policy);
+        assert!(policy.0.contains(&Point { x: 2, y: 0 }));
+
+        world.get_mut::<Position>(agent).unwrap().0 = Point { x: 1, y: 0 };
+        system_vision(&mut world);
+        assert!(world
+            .query::<(&Position, &Visibility)>()
+            .iter()
+            .all(|(_, (p, v))| p.0.x > 2 || v.0));
+    }
+
+    #[test]
+    fn test_memory_decay() {
+        let map = "@...T..G
+        ........
+        ........";
+
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        features.memory_decay = Some(2);
+        // Forgets the tower and the tiles around it, so explores back into its range
+        assert_eq!(run_sim_from_map(map, features), 14);
+        features.memory_decay = Some(3);
+        assert_eq!(run_sim_from_map(map, features), 12);
+        features.memory_decay = None;
+        assert_eq!(run_sim_from_map(map, features), 12);
     }
 
     #[test]
@@ -505,20 +1016,48 @@ mod test {
         let mut features = FeatureFlags::new();
         features.render = false;
         features.write_agent_visible_map = true;
-        let num_steps = run_sim(&mut world, features);
+        let num_steps = run_sim(&mut world, features).steps;
         assert_eq!(num_steps, 13)
     }
 
     #[test]
     fn test_damage_avoidance() {
+        // Every tile in the tower's column is in its range on a three row map, so the fourth row
+        // leaves a way past it
         let map = "@...T..G
-        ........                 
+        ........
+        ........
         ........";
 
         let mut features = FeatureFlags::new();
         features.render = false;
-        let num_steps = run_sim_from_map(map, features);
-        assert_eq!(num_steps, 16)
+        let mut world = World::new();
+        parse_map(&mut world, map);
+        let num_steps = run_sim(&mut world, features).steps;
+        assert_eq!(num_steps, 14);
+
+        // The tower is hidden until it fires, after that the route stays out of its range
+        assert_eq!(agent_health(&mut world), vec![495]);
+
+        // A tower that is already known is never in range
+        let mut world = World::new();
+        parse_map(&mut world, map);
+        for (_, (visibility, _)) in world
+            .query_mut::<(&mut Visibility, &Attack)>()
+            .without::<AttackerAgent>()
+        {
+            visibility.0 = true;
+        }
+        run_sim(&mut world, features);
+        assert_eq!(agent_health(&mut world), vec![500]);
+    }
+
+    fn agent_health(world: &mut World) -> Vec<i32> {
+        return world
+            .query_mut::<(&Health, &AttackerAgent)>()
+            .into_iter()
+            .map(|(_, (h, _))| h.0)
+            .collect::<Vec<i32>>();
     }
 
     #[test]
@@ -531,4 +1070,90 @@ mod test {
         let num_steps = run_sim_from_map(map, features);
         assert_eq!(num_steps, 10)
     }
+
+    /// Runs the map to the end and returns every point the agent stood on
+    #[allow(dead_code)]
+    fn agent_route(map: &str) -> Vec<Point> {
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        let mut world = World::new();
+        parse_map(&mut world, map);
+        let mut start_pather = get_start_lpapather(&world);
+        let mut goal_pather = get_goal_lpapather(&world);
+        system_vision(&mut world);
+
+        let mut route = Vec::new();
+        while !step_game_world(&mut world, features, &mut start_pather, &mut goal_pather) {
+            for (_, (p, _)) in world.query::<(&Position, &AttackerAgent)>().iter() {
+                route.push(p.0);
+            }
+        }
+        return route;
+    }
+
+    #[test]
+    fn test_parse_map_tiles() {
+        let map = "@~=^
+        v<>.
+        KL.G";
+
+        let mut world = World::new();
+        parse_map(&mut world, map);
+        assert_eq!(world.query::<&TerrainCost>().iter().count(), 2);
+        assert_eq!(world.query::<&OneWay>().iter().count(), 4);
+        assert_eq!(world.query::<&Key>().iter().count(), 1);
+        assert_eq!(world.query::<&LockedDoor>().iter().count(), 1);
+        assert_eq!(get_max_point(&world), Point { x: 4, y: 3 });
+    }
+
+    #[test]
+    fn test_keys_system() {
+        // Map of:
+        // KL
+        let mut world = World::new();
+        let agent = world.spawn((Position(Point { x: 0, y: 0 }), Keys(0)));
+        let key = world.spawn((Position(Point { x: 0, y: 0 }), Key));
+        let door = world.spawn((Position(Point { x: 1, y: 0 }), LockedDoor));
+
+        system_keys(&mut world);
+        assert_eq!(world.get::<Keys>(agent).unwrap().0, 1);
+        assert!(world.get::<Key>(key).is_err());
+
+        world.get_mut::<Position>(agent).unwrap().0 = Point { x: 1, y: 0 };
+        system_keys(&mut world);
+        assert_eq!(world.get::<Keys>(agent).unwrap().0, 0);
+        assert!(world.get::<LockedDoor>(door).is_err());
+    }
+
+    #[test]
+    fn test_terrain_costs() {
+        // The road around is cheaper than wading through the mud
+        let map = "@~~~G
+        =====";
+
+        let route = agent_route(map);
+        assert!(route.iter().all(|p| p.y == 1 || p.x == 0 || p.x == 4));
+        assert_eq!(route.len(), 6);
+    }
+
+    #[test]
+    fn test_one_way_tiles() {
+        // The shortcut can only be taken right to left
+        let map = "@.<.G
+        .....";
+
+        let route = agent_route(map);
+        assert!(!route.contains(&Point { x: 2, y: 0 }));
+        assert_eq!(route.len(), 6);
+    }
+
+    #[test]
+    fn test_locked_door() {
+        // Has to fetch the key before it can get through the door
+        let map = "K.@LG";
+
+        let route = agent_route(map);
+        assert!(route.contains(&Point { x: 0, y: 0 }));
+        assert_eq!(route.len(), 6);
+    }
 }
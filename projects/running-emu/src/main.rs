@@ -1,21 +1,58 @@
 use core::time;
 use std::{
-    thread,
+    env, fs, thread,
     time::{Duration, Instant},
 };
 
 use hecs::World;
 use log::info;
 use running_emu::{
-    ai_pathing::{get_goal_lpapather, get_start_lpapather},
-    create_map, parse_map,
-    render::system_render,
-    run_sim_from_map, step_game_world, system_vision, FeatureFlags,
+    ai_pathing::{get_goal_lpapather, get_start_lpapather, LpaStarPather},
+    create_map,
+    editor::LevelEditor,
+    graph::set_movement_rules,
+    parse_map,
+    render::{point_at, render_chars, system_render},
+    replay::{play_in_terminal, take_replay, Replay},
+    run_sim_from_map,
+    save::{load_from_file, save_to_file},
+    step_game_world, system_vision, FeatureFlags,
+};
+use sdl2::{
+    event::Event, keyboard::Keycode, mouse::MouseButton, pixels, render::Canvas, video::Window,
+    EventPump,
 };
-use sdl2::{event::Event, keyboard::Keycode, pixels, render::Canvas, video::Window, EventPump};
 
 const FRAME_TIME_MILLI: Duration = time::Duration::from_millis(500);
+const EDITOR_FRAME_TIME: Duration = time::Duration::from_millis(16);
+/// Size of the blank map the editor starts with when the file doesn't exist yet
+const EDITOR_MAP_SIZE: usize = 20;
+
+/// `--record <file>` saves a replay of the run on quit, `--replay <file>` plays one back instead
+/// of running the simulation. Add `--terminal` to play it back in the terminal.
+///
+/// `--save <file>` saves the world on quit and `--resume <file>` continues from a save rather
+/// than starting a new map.
+///
+/// `--edit <file>` opens the map in the level editor instead.
+///
+/// `--diagonal` lets the agent step diagonally and `--smooth` straightens its paths.
 fn main() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+    if let Some(path) = arg_value(&args, "--edit") {
+        return edit_level(&path);
+    }
+    if let Some(path) = arg_value(&args, "--replay") {
+        let replay = Replay::load(&path)?;
+        if args.iter().any(|a| a == "--terminal") {
+            play_in_terminal(&replay);
+            return Ok(());
+        }
+        return play_replay(&replay);
+    }
+    let record_path = arg_value(&args, "--record");
+    let save_path = arg_value(&args, "--save");
+
     let (mut canvas, mut events) = init_sdl()?;
 
     // let _map = "@...O..G
@@ -35,17 +72,31 @@ fn main() -> Result<(), String> {
 
     let mut features = FeatureFlags::new();
     features.write_agent_visible_map = false;
+    features.record_replay = record_path.is_some();
+    features.diagonal_movement = args.iter().any(|a| a == "--diagonal");
+    features.path_smoothing = args.iter().any(|a| a == "--smooth");
     // features.print_tile_costs = true;
     // let num_steps = run_sim_from_map(_map, features);
     // println!("Completed in {} steps", num_steps);
-    let mut world = World::new();
-    parse_map(&mut world, _map);
+    let (mut world, mut start_pather, mut goal_pather) = match arg_value(&args, "--resume") {
+        Some(path) => {
+            let state = load_from_file(&path)?;
+            info!("resumed from {}", path);
+            (state.world, state.start_pather, state.goal_pather)
+        }
+        None => {
+            let mut world = World::new();
+            parse_map(&mut world, _map);
+            set_movement_rules(&mut world, features.movement_rules());
 
-    let mut start_pather = get_start_lpapather(&world);
-    let mut goal_pather = get_goal_lpapather(&world);
+            let start_pather = get_start_lpapather(&world);
+            let goal_pather = get_goal_lpapather(&world);
 
-    // Bootstrap
-    system_vision(&mut world);
+            // Bootstrap
+            system_vision(&mut world);
+            (world, start_pather, goal_pather)
+        }
+    };
 
     loop {
         let start = Instant::now();
@@ -67,9 +118,174 @@ fn main() -> Result<(), String> {
         }
     }
 
+    if let Some(path) = save_path {
+        save_to_file(&path, &world, &start_pather, &goal_pather)?;
+        info!("saved world to {}", path);
+    }
+
+    if let (Some(path), Some(replay)) = (record_path, take_replay(&mut world)) {
+        replay.save(&path)?;
+        info!("saved {} step replay to {}", replay.steps.len(), path);
+    }
+
     Ok(())
 }
 
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    return args.get(i + 1).cloned();
+}
+
+/// Step through a replay, left and right arrows move a step at a time and space plays or pauses
+fn play_replay(replay: &Replay) -> Result<(), String> {
+    if replay.steps.is_empty() {
+        return Err("replay has no steps".to_string());
+    }
+
+    let (mut canvas, mut events) = init_sdl()?;
+    let last_step = replay.steps.len() - 1;
+    let mut i = 0;
+    let mut playing = false;
+    let mut shown = None;
+
+    loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => match keycode {
+                    Keycode::Escape => return Ok(()),
+                    Keycode::Space => playing = !playing,
+                    Keycode::Right => i = (i + 1).min(last_step),
+                    Keycode::Left => i = i.saturating_sub(1),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        if shown != Some(i) {
+            let step = &replay.steps[i];
+            canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+            canvas.clear();
+            render_chars(&mut canvas, &step.chars)?;
+            canvas.present();
+
+            println!("step {} of {}", i, last_step);
+            for action in step.actions.iter() {
+                println!("  {}", action);
+            }
+            shown = Some(i);
+        }
+
+        if playing {
+            thread::sleep(FRAME_TIME_MILLI);
+            i = (i + 1).min(last_step);
+            playing = i < last_step;
+        } else {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Paint a map with the mouse and try it out.
+///
+/// Typing a map character picks the tile to paint. Enter saves the map and runs the simulation
+/// on it, tab goes back to editing.
+fn edit_level(path: &str) -> Result<(), String> {
+    let mut editor = match fs::read_to_string(path) {
+        Ok(map) => LevelEditor::from_map(&map)?,
+        Err(_) => LevelEditor::from_map(&create_map(EDITOR_MAP_SIZE))?,
+    };
+    let (mut canvas, mut events) = init_sdl()?;
+    let features = FeatureFlags::new();
+    let mut sim: Option<(World, LpaStarPather, LpaStarPather)> = None;
+
+    loop {
+        let start = Instant::now();
+
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => match keycode {
+                    Keycode::Escape => return Ok(()),
+                    Keycode::Tab => sim = None,
+                    Keycode::Return if sim.is_none() => {
+                        if let Err(e) = editor.validate() {
+                            println!("can't run map: {}", e);
+                            continue;
+                        }
+                        fs::write(path, editor.to_map()).map_err(|e| e.to_string())?;
+                        info!("saved map to {}", path);
+
+                        let mut world = World::new();
+                        parse_map(&mut world, &editor.to_map());
+                        let start_pather = get_start_lpapather(&world);
+                        let goal_pather = get_goal_lpapather(&world);
+                        system_vision(&mut world);
+                        sim = Some((world, start_pather, goal_pather));
+                    }
+                    _ => {}
+                },
+                Event::TextInput { text, .. } if sim.is_none() => {
+                    for c in text.chars() {
+                        editor.set_brush(c);
+                    }
+                }
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } if sim.is_none() => paint(&mut editor, &canvas, x, y),
+                Event::MouseMotion {
+                    mousestate, x, y, ..
+                } if sim.is_none() && mousestate.left() => paint(&mut editor, &canvas, x, y),
+                _ => {}
+            }
+        }
+
+        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        canvas.clear();
+        let title = match &mut sim {
+            Some((world, start_pather, goal_pather)) => {
+                step_game_world(world, features, start_pather, goal_pather);
+                system_render(world, &mut canvas)?;
+                "Running Emu - tab to edit".to_string()
+            }
+            None => {
+                render_chars(&mut canvas, editor.tiles())?;
+                format!("Running Emu - painting {}", editor.brush())
+            }
+        };
+        canvas
+            .window_mut()
+            .set_title(&title)
+            .map_err(|e| e.to_string())?;
+        canvas.present();
+
+        let frame_time = match sim {
+            Some(_) => FRAME_TIME_MILLI,
+            None => EDITOR_FRAME_TIME,
+        };
+        while start.elapsed() < frame_time {
+            thread::sleep(frame_time - start.elapsed())
+        }
+    }
+}
+
+fn paint(editor: &mut LevelEditor, canvas: &Canvas<Window>, x: i32, y: i32) {
+    let tiles = editor.tiles();
+    if let Some(p) = point_at(canvas, tiles[0].len(), tiles.len(), x, y) {
+        editor.paint(p);
+    }
+}
+
 fn init_sdl() -> Result<(Canvas<Window>, EventPump), String> {
     let sdl_context = sdl2::init()?;
     let video_subsys = sdl_context.video()?;
@@ -0,0 +1,260 @@
+use itertools::Itertools;
+
+use crate::{
+    ai_pathing::{get_travel_costs, LpaStarPather},
+    graph::{CostMap, CostMapView, EdgeType},
+    spatial::Point,
+    PathingAlgorithm,
+};
+
+/// What an attacker knows when choosing where to head next
+pub struct Observation {
+    pub agent: Point,
+    pub start: Point,
+    pub goal: Point,
+    /// Costs of the tiles seen so far
+    pub costs: CostMap,
+    /// Visible points next to unexplored tiles, the goal once it's been seen, and visible keys
+    pub candidates: Vec<Point>,
+}
+
+impl Observation {
+    /// Candidates the agent has a path to through visible tiles
+    pub fn reachable_candidates(&self) -> Vec<Point> {
+        let view = CostMapView::new(&self.costs, vec![EdgeType::Visible]);
+        let travel_costs = get_travel_costs(self.agent, &view, false);
+        return self
+            .candidates
+            .iter()
+            .copied()
+            .filter(|p| travel_costs[p.y][p.x] != i32::MAX)
+            .collect_vec();
+    }
+}
+
+/// Decides where an attacker goes next.
+///
+/// Called by `system_exploration` whenever the agent has reached its target. Moving and
+/// attacking along the way to the target are handled by `system_ai_action`.
+pub trait AttackerPolicy {
+    /// Returns the next target, which must be reachable through visible tiles
+    fn choose_target(&mut self, observation: &Observation) -> Option<Point>;
+}
+
+/// The default policy, explores the candidate on the cheapest known route from start to goal.
+///
+/// Each candidate is scored by the cost to get there from start plus the cost to get to goal
+/// assuming un-explored tiles only have travel cost. Ties go to the candidate closer to the goal,
+/// then to the one closer to the agent. Exploring the lowest cost tile first rather than breadth
+/// first means tile costs always represent the 'cheapest' way to reach them.
+pub struct ExplorationPolicy<'a> {
+    pub pathing_algorithm: PathingAlgorithm,
+    pub start_pather: &'a mut LpaStarPather,
+    pub goal_pather: &'a mut LpaStarPather,
+}
+
+impl<'a> AttackerPolicy for ExplorationPolicy<'a> {
+    fn choose_target(&mut self, observation: &Observation) -> Option<Point> {
+        let costs = &observation.costs;
+        let start_view = CostMapView::new(costs, vec![EdgeType::Visible]);
+        let goal_view = CostMapView::new(costs, vec![EdgeType::Visible, EdgeType::Fog]);
+        let candidate_points = &observation.candidates;
+
+        let v;
+        let start_travel_costs = match self.pathing_algorithm {
+            PathingAlgorithm::Astar => {
+                v = get_travel_costs(observation.start, &start_view, false);
+                &v
+            }
+            PathingAlgorithm::LpaStar => {
+                self.start_pather.update_tile_costs(&start_view);
+                self.start_pather.get_travel_costs()
+            }
+            PathingAlgorithm::DStarLite => {
+                self.start_pather.move_goal(observation.agent);
+                self.start_pather
+                    .update_tile_costs_for(&start_view, candidate_points);
+                self.start_pather.get_travel_costs()
+            }
+        };
+
+        // Tile costs are paid on entering a tile, so costs from the goal would charge the candidate
+        // twice and the goal never. Searching towards the goal instead makes the two halves add up
+        // to the route's cost.
+        let v;
+        let goal_travel_costs = match self.pathing_algorithm {
+            PathingAlgorithm::Astar => {
+                v = get_travel_costs(observation.goal, &goal_view, true);
+                &v
+            }
+            PathingAlgorithm::LpaStar => {
+                self.goal_pather.update_tile_costs(&goal_view);
+                self.goal_pather.get_travel_costs()
+            }
+            PathingAlgorithm::DStarLite => {
+                self.goal_pather.move_goal(observation.agent);
+                self.goal_pather
+                    .update_tile_costs_for(&goal_view, candidate_points);
+                self.goal_pather.get_travel_costs()
+            }
+        };
+
+        // The way back to the start can be forgotten with `FeatureFlags::memory_decay`, then
+        // routes are costed from the agent instead
+        let from_agent;
+        let agent = observation.agent;
+        let start_travel_costs = if start_travel_costs[agent.y][agent.x] == i32::MAX {
+            from_agent = get_travel_costs(agent, &start_view, false);
+            &from_agent
+        } else {
+            start_travel_costs
+        };
+
+        // Points past locked doors or one way tiles can be seen without being reachable
+        let candidate_points = candidate_points
+            .iter()
+            .copied()
+            .filter(|p| start_travel_costs[p.y][p.x] != i32::MAX)
+            .collect_vec();
+
+        let mut candidate_scores = Vec::with_capacity(candidate_points.len());
+        for p in candidate_points.iter() {
+            let score = CandidateScore {
+                dist_to_start: start_travel_costs[p.y][p.x],
+                dist_to_goal: goal_travel_costs[p.y][p.x],
+                dist_to_agent: p.dist(&observation.agent),
+            };
+            candidate_scores.push(score);
+        }
+
+        let min_val = *candidate_scores.iter().min()?;
+        let min_index = candidate_scores.iter().position(|x| *x == min_val).unwrap();
+        return Some(candidate_points[min_index]);
+    }
+}
+
+/// Helper for sorting candidate locations.
+#[derive(PartialEq, Eq, Clone, Copy)]
+struct CandidateScore {
+    dist_to_start: i32,
+    dist_to_goal: i32,
+    dist_to_agent: i32,
+}
+
+impl Ord for CandidateScore {
+    /// Compare on dist to goal + dist to start, if a tiebreaker, use goal dist as secondary sort, if still tied, used agent dist
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let self_total = self
+            .dist_to_start
+            .checked_add(self.dist_to_goal)
+            .unwrap_or(i32::MAX);
+        let other_total = other
+            .dist_to_start
+            .checked_add(other.dist_to_goal)
+            .unwrap_or(i32::MAX);
+        if self_total != other_total {
+            return self_total.cmp(&other_total);
+        } else if self.dist_to_goal != other.dist_to_goal {
+            return self.dist_to_goal.cmp(&other.dist_to_goal);
+        } else {
+            return self.dist_to_agent.cmp(&other.dist_to_agent);
+        }
+    }
+}
+
+impl PartialOrd for CandidateScore {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Baseline that heads for the reachable candidate closest to the goal as the crow flies,
+/// ignoring tile costs. Ties go to the candidate closer to the agent.
+pub struct GreedyPolicy;
+
+impl AttackerPolicy for GreedyPolicy {
+    fn choose_target(&mut self, observation: &Observation) -> Option<Point> {
+        return observation
+            .reachable_candidates()
+            .into_iter()
+            .min_by_key(|p| (p.dist(&observation.goal), p.dist(&observation.agent)));
+    }
+}
+
+/// Baseline that heads for a random reachable candidate, the same seed always makes the same
+/// choices
+pub struct RandomPolicy {
+    state: u64,
+}
+
+impl RandomPolicy {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at 0
+        return Self { state: seed.max(1) };
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        return self.state;
+    }
+}
+
+impl AttackerPolicy for RandomPolicy {
+    fn choose_target(&mut self, observation: &Observation) -> Option<Point> {
+        let candidates = observation.reachable_candidates();
+        if candidates.is_empty() {
+            return None;
+        }
+        let i = self.next() % candidates.len() as u64;
+        return Some(candidates[i as usize]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_map, run_sim_from_map, run_sim_with_policy, system_vision, FeatureFlags};
+    use hecs::World;
+
+    #[test]
+    fn test_baseline_policies() {
+        let map = "@...O....
+        .W.......
+        ...T.....
+        ....O...G";
+        let mut features = FeatureFlags::new();
+        features.render = false;
+
+        let explore = run_sim_from_map(map, features);
+        let greedy = run_sim_with_policy(map, features, &mut GreedyPolicy);
+        let random = run_sim_with_policy(map, features, &mut RandomPolicy::new(7));
+        assert!(explore > 0);
+        assert!(greedy > 0);
+        assert!(random > 0);
+        assert_eq!(
+            random,
+            run_sim_with_policy(map, features, &mut RandomPolicy::new(7))
+        );
+    }
+
+    #[test]
+    fn test_greedy_policy() {
+        let mut world = World::new();
+        parse_map(&mut world, "@..\n...\n..G");
+        system_vision(&mut world);
+        let observation = Observation {
+            agent: Point { x: 0, y: 0 },
+            start: Point { x: 0, y: 0 },
+            goal: Point { x: 2, y: 2 },
+            costs: CostMap::from_world(&world),
+            candidates: vec![Point { x: 1, y: 0 }, Point { x: 0, y: 1 }],
+        };
+        // Both are as close to the goal, (1, 0) is first
+        assert_eq!(
+            GreedyPolicy.choose_target(&observation),
+            Some(Point { x: 1, y: 0 })
+        );
+    }
+}
@@ -8,19 +8,32 @@ use sdl2::{
     VideoSubsystem,
 };
 
-use crate::{build_char_output, get_max_point, spatial::Point};
+use crate::{build_char_output, spatial::Point};
 
 const SCREEN_WIDTH: i16 = 1280;
 const SCREEN_HEIGHT: i16 = 720;
 
 pub fn system_render(world: &World, canvas: &mut Canvas<Window>) -> Result<(), String> {
+    let char_buffer = build_char_output(world);
+    return render_chars(canvas, &char_buffer);
+}
+
+/// Draw a rendered map, like the ones saved in a replay
+pub fn render_chars(
+    canvas: &mut Canvas<Window>,
+    char_buffer: &Vec<Vec<char>>,
+) -> Result<(), String> {
     // Draw grid
-    let max_p = get_max_point(world);
+    let max_p = Point {
+        x: char_buffer.first().map_or(0, |row| row.len()),
+        y: char_buffer.len(),
+    };
+    if max_p.x == 0 || max_p.y == 0 {
+        return Ok(());
+    }
     let width = max_p.x as i16;
     let height = max_p.y as i16;
 
-    let char_buffer = build_char_output(world);
-
     for y in 0..max_p.y {
         for x in 0..max_p.x {
             let p = Point { x: x, y: y };
@@ -33,6 +46,31 @@ pub fn system_render(world: &World, canvas: &mut Canvas<Window>) -> Result<(), S
     Ok(())
 }
 
+/// Returns the tile under a pixel of the canvas, for a map `width` by `height` tiles
+pub fn point_at(
+    canvas: &Canvas<Window>,
+    width: usize,
+    height: usize,
+    x: i32,
+    y: i32,
+) -> Option<Point> {
+    let (canvas_width, canvas_height) = canvas.window().drawable_size();
+    let box_width = canvas_width as i32 / width as i32;
+    let box_height = canvas_height as i32 / height as i32;
+    if x < 0 || y < 0 || box_width == 0 || box_height == 0 {
+        return None;
+    }
+
+    let p = Point {
+        x: (x / box_width) as usize,
+        y: (y / box_height) as usize,
+    };
+    if p.x >= width || p.y >= height {
+        return None;
+    }
+    return Some(p);
+}
+
 fn draw_char_center(
     canvas: &mut Canvas<Window>,
     p: Point,
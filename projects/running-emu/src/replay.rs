@@ -0,0 +1,496 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    io::{stdin, BufRead},
+    str::FromStr,
+};
+
+use crossterm::style::Color;
+use hecs::{Entity, World};
+
+use crate::{
+    events::{read_events, AttackEvent, DeathEvent},
+    spatial::Point,
+    system_render, Position, Sprite, TargetLocation, Visibility,
+};
+
+/// Something a system did during a step, located by position since entity ids aren't stable
+/// between runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayAction {
+    /// An agent picked a new location to explore
+    Target {
+        at: Point,
+        target: Point,
+    },
+    Move {
+        from: Point,
+        to: Point,
+    },
+    Attack {
+        from: Point,
+        target: Point,
+        amount: i32,
+    },
+    Death {
+        at: Point,
+        sprite: char,
+    },
+    /// Despawned without dying, like a key being picked up or a door being unlocked
+    Remove {
+        at: Point,
+        sprite: char,
+    },
+    Discover {
+        at: Point,
+        sprite: char,
+    },
+    /// Out of sight for long enough to be forgotten
+    Forget {
+        at: Point,
+        sprite: char,
+    },
+}
+
+impl ReplayAction {
+    /// Order actions are listed in within a step, roughly the order the systems run
+    fn rank(&self) -> u8 {
+        match self {
+            ReplayAction::Target { .. } => 0,
+            ReplayAction::Move { .. } => 1,
+            ReplayAction::Attack { .. } => 2,
+            ReplayAction::Death { .. } => 3,
+            ReplayAction::Remove { .. } => 4,
+            ReplayAction::Discover { .. } => 5,
+            ReplayAction::Forget { .. } => 6,
+        }
+    }
+}
+
+impl Display for ReplayAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let p = |p: &Point| format!("{},{}", p.x, p.y);
+        match self {
+            ReplayAction::Target { at, target } => write!(f, "target {} {}", p(at), p(target)),
+            ReplayAction::Move { from, to } => write!(f, "move {} {}", p(from), p(to)),
+            ReplayAction::Attack {
+                from,
+                target,
+                amount,
+            } => write!(f, "attack {} {} {}", p(from), p(target), amount),
+            ReplayAction::Death { at, sprite } => write!(f, "death {} {}", p(at), sprite),
+            ReplayAction::Remove { at, sprite } => write!(f, "remove {} {}", p(at), sprite),
+            ReplayAction::Discover { at, sprite } => write!(f, "discover {} {}", p(at), sprite),
+            ReplayAction::Forget { at, sprite } => write!(f, "forget {} {}", p(at), sprite),
+        }
+    }
+}
+
+impl FromStr for ReplayAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let err = || format!("invalid replay action: {}", s);
+        let point = |i: usize| -> Result<Point, String> {
+            let (x, y) = parts
+                .get(i)
+                .and_then(|p| p.split_once(','))
+                .ok_or_else(err)?;
+            Ok(Point {
+                x: x.parse().map_err(|_| err())?,
+                y: y.parse().map_err(|_| err())?,
+            })
+        };
+        let sprite = || -> Result<char, String> {
+            let mut chars = parts.get(2).ok_or_else(err)?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(err()),
+            }
+        };
+
+        let action = match parts.first() {
+            Some(&"target") => ReplayAction::Target {
+                at: point(1)?,
+                target: point(2)?,
+            },
+            Some(&"move") => ReplayAction::Move {
+                from: point(1)?,
+                to: point(2)?,
+            },
+            Some(&"attack") => ReplayAction::Attack {
+                from: point(1)?,
+                target: point(2)?,
+                amount: parts.get(3).ok_or_else(err)?.parse().map_err(|_| err())?,
+            },
+            Some(&"death") => ReplayAction::Death {
+                at: point(1)?,
+                sprite: sprite()?,
+            },
+            Some(&"remove") => ReplayAction::Remove {
+                at: point(1)?,
+                sprite: sprite()?,
+            },
+            Some(&"discover") => ReplayAction::Discover {
+                at: point(1)?,
+                sprite: sprite()?,
+            },
+            Some(&"forget") => ReplayAction::Forget {
+                at: point(1)?,
+                sprite: sprite()?,
+            },
+            _ => return Err(err()),
+        };
+        return Ok(action);
+    }
+}
+
+/// What was rendered during a step, then what each system did in it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayStep {
+    pub chars: Vec<Vec<char>>,
+    /// Only the colors used by the path highlight are kept, others are dropped
+    pub highlights: Vec<Vec<Option<Color>>>,
+    pub actions: Vec<ReplayAction>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct EntityState {
+    pos: Point,
+    sprite: char,
+    visible: bool,
+    target: Option<Point>,
+}
+
+/// Every step of a run, recorded when `FeatureFlags::record_replay` is set.
+///
+/// Stored on its own entity, like the event queues. Actions are found by comparing entities
+/// against the previous step, so nothing needs to be added to the systems themselves.
+#[derive(Default)]
+pub struct Replay {
+    pub steps: Vec<ReplayStep>,
+    last: HashMap<Entity, EntityState>,
+}
+
+impl Replay {
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        return fs::write(path, self.to_string()).map_err(|e| e.to_string());
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        return fs::read_to_string(path).map_err(|e| e.to_string())?.parse();
+    }
+}
+
+/// One `step` line per step, followed by `chars` and `highlight` rows and then the actions
+impl Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(f, "step {}", i)?;
+            for row in step.chars.iter() {
+                writeln!(f, "chars {}", row.iter().collect::<String>())?;
+            }
+            for row in step.highlights.iter() {
+                let row: String = row
+                    .iter()
+                    .map(|h| match h {
+                        Some(Color::Green) => 'g',
+                        Some(Color::Blue) => 'b',
+                        Some(Color::Black) => 'k',
+                        _ => '.',
+                    })
+                    .collect();
+                writeln!(f, "highlight {}", row)?;
+            }
+            for action in step.actions.iter() {
+                writeln!(f, "{}", action)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut replay = Replay::default();
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            if line.starts_with("step ") {
+                replay.steps.push(ReplayStep {
+                    chars: Vec::new(),
+                    highlights: Vec::new(),
+                    actions: Vec::new(),
+                });
+                continue;
+            }
+
+            let step = replay
+                .steps
+                .last_mut()
+                .ok_or_else(|| format!("replay line before the first step: {}", line))?;
+            if let Some(row) = line.strip_prefix("chars ") {
+                step.chars.push(row.chars().collect());
+            } else if let Some(row) = line.strip_prefix("highlight ") {
+                step.highlights.push(
+                    row.chars()
+                        .map(|c| match c {
+                            'g' => Some(Color::Green),
+                            'b' => Some(Color::Blue),
+                            'k' => Some(Color::Black),
+                            _ => None,
+                        })
+                        .collect(),
+                );
+            } else {
+                step.actions.push(line.parse()?);
+            }
+        }
+        return Ok(replay);
+    }
+}
+
+fn entity_states(world: &World) -> HashMap<Entity, EntityState> {
+    let mut states = HashMap::new();
+    for (e, (p, s, v, t)) in world
+        .query::<(&Position, &Sprite, &Visibility, Option<&TargetLocation>)>()
+        .iter()
+    {
+        let state = EntityState {
+            pos: p.0,
+            sprite: s.0,
+            visible: v.0,
+            target: t.and_then(|t| t.0),
+        };
+        states.insert(e, state);
+    }
+    return states;
+}
+
+/// Create the replay from the state of the world before the first step, does nothing once the
+/// replay exists
+pub fn system_start_replay(world: &mut World) {
+    if world.query::<&Replay>().iter().next().is_some() {
+        return;
+    }
+    let replay = Replay {
+        steps: Vec::new(),
+        last: entity_states(world),
+    };
+    world.spawn((replay,));
+}
+
+/// Add a step to the replay.
+///
+/// Should be run at the end of a step, after every system that changes the world. `chars` and
+/// `highlights` are what was rendered during the step.
+pub fn system_record_replay(
+    world: &mut World,
+    chars: Vec<Vec<char>>,
+    highlights: Vec<Vec<Option<Color>>>,
+) {
+    system_start_replay(world);
+    let current = entity_states(world);
+    let attacks = read_events::<AttackEvent>(world);
+    let deaths: Vec<Entity> = read_events::<DeathEvent>(world)
+        .iter()
+        .map(|d| d.entity)
+        .collect();
+
+    let replay = match world.query_mut::<&mut Replay>().into_iter().next() {
+        Some((_, replay)) => replay,
+        None => return,
+    };
+    let last = &replay.last;
+    let pos = |e: &Entity| current.get(e).or_else(|| last.get(e)).map(|s| s.pos);
+    let mut actions = Vec::new();
+
+    for attack in attacks.iter() {
+        if let (Some(from), Some(target)) = (pos(&attack.from), pos(&attack.target)) {
+            actions.push(ReplayAction::Attack {
+                from: from,
+                target: target,
+                amount: attack.amount,
+            });
+        }
+    }
+
+    // Entity ids are handed out in spawn order, so sorting them keeps the actions in the
+    // same order every run
+    let mut ids: Vec<&Entity> = last.keys().chain(current.keys()).collect();
+    ids.sort();
+    ids.dedup();
+    for e in ids {
+        match (last.get(e), current.get(e)) {
+            (Some(before), None) if deaths.contains(e) => actions.push(ReplayAction::Death {
+                at: before.pos,
+                sprite: before.sprite,
+            }),
+            (Some(before), None) => actions.push(ReplayAction::Remove {
+                at: before.pos,
+                sprite: before.sprite,
+            }),
+            (Some(before), Some(after)) => {
+                if let (Some(target), true) = (after.target, after.target != before.target) {
+                    actions.push(ReplayAction::Target {
+                        at: before.pos,
+                        target: target,
+                    });
+                }
+                if before.pos != after.pos {
+                    actions.push(ReplayAction::Move {
+                        from: before.pos,
+                        to: after.pos,
+                    });
+                }
+                if !before.visible && after.visible {
+                    actions.push(ReplayAction::Discover {
+                        at: after.pos,
+                        sprite: after.sprite,
+                    });
+                }
+                if before.visible && !after.visible {
+                    actions.push(ReplayAction::Forget {
+                        at: after.pos,
+                        sprite: after.sprite,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    actions.sort_by_key(|a| a.rank());
+
+    replay.steps.push(ReplayStep {
+        chars: chars,
+        highlights: highlights,
+        actions: actions,
+    });
+    replay.last = current;
+}
+
+/// Removes the replay from the world, if one was recorded
+pub fn take_replay(world: &mut World) -> Option<Replay> {
+    let e = world.query::<&Replay>().iter().next().map(|(e, _)| e)?;
+    return world.remove_one::<Replay>(e).ok();
+}
+
+/// Scrub through a replay in the terminal.
+///
+/// Reads commands from stdin: enter or `n` for the next step, `p` for the previous one, a step
+/// number to jump to it and `q` to quit.
+pub fn play_in_terminal(replay: &Replay) {
+    if replay.steps.is_empty() {
+        println!("replay has no steps");
+        return;
+    }
+
+    let mut i = 0;
+    let mut lines = stdin().lock().lines();
+    loop {
+        let step = &replay.steps[i];
+        println!("step {} of {}", i, replay.steps.len() - 1);
+        system_render(&step.chars, &step.highlights);
+        for action in step.actions.iter() {
+            println!("  {}", action);
+        }
+
+        let command = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return,
+        };
+        match command.trim() {
+            "" | "n" => i = (i + 1).min(replay.steps.len() - 1),
+            "p" => i = i.saturating_sub(1),
+            "q" => return,
+            n => match n.parse::<usize>() {
+                Ok(n) => i = n.min(replay.steps.len() - 1),
+                Err(_) => println!("unknown command: {}", n),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai_pathing::{get_goal_lpapather, get_start_lpapather},
+        parse_map, step_game_world, system_vision, FeatureFlags,
+    };
+
+    fn record(map: &str) -> Replay {
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        features.record_replay = true;
+
+        let mut world = World::new();
+        parse_map(&mut world, map);
+        let mut start_pather = get_start_lpapather(&world);
+        let mut goal_pather = get_goal_lpapather(&world);
+        system_vision(&mut world);
+        while !step_game_world(&mut world, features, &mut start_pather, &mut goal_pather) {}
+
+        return take_replay(&mut world).unwrap();
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let map = "@.D.
+        .OO.
+        ...G";
+        let replay = record(map);
+
+        let first = &replay.steps[0];
+        assert_eq!(first.chars[0][0], 'S');
+        assert!(first.actions.contains(&ReplayAction::Move {
+            from: Point { x: 0, y: 0 },
+            to: Point { x: 1, y: 0 },
+        }));
+        assert!(replay.steps[1].actions.contains(&ReplayAction::Target {
+            at: Point { x: 1, y: 0 },
+            target: Point { x: 2, y: 0 },
+        }));
+
+        let actions: Vec<ReplayAction> = replay
+            .steps
+            .iter()
+            .flat_map(|s| s.actions.iter().copied())
+            .collect();
+        assert!(actions
+            .iter()
+            .any(|a| matches!(a, ReplayAction::Attack { .. })));
+        assert!(actions.contains(&ReplayAction::Death {
+            at: Point { x: 2, y: 0 },
+            sprite: 'D',
+        }));
+        assert!(actions.contains(&ReplayAction::Discover {
+            at: Point { x: 1, y: 1 },
+            sprite: 'O',
+        }));
+
+        // Runs are deterministic
+        assert_eq!(replay.steps, record(map).steps);
+    }
+
+    #[test]
+    fn test_replay_round_trip() {
+        let replay = record(
+            "@.K.
+            ..L.
+            ...G",
+        );
+        let parsed: Replay = replay.to_string().parse().unwrap();
+        assert_eq!(parsed.steps, replay.steps);
+        assert!(parsed.steps.iter().any(|s| s
+            .actions
+            .iter()
+            .any(|a| matches!(a, ReplayAction::Remove { .. }))));
+
+        assert!("move 1,2".parse::<ReplayAction>().is_err());
+        assert!("death 1,2 ab".parse::<ReplayAction>().is_err());
+        assert!("chars ..".parse::<Replay>().is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use std::fs;
+
+use crossterm::style::Color;
+use hecs::{Entity, EntityBuilder, EntityRef, World};
+
+use crate::{
+    ai_pathing::LpaStarPather, spatial::Direction, Attack, AttackerAgent, BackgroundHighlight,
+    Health, Key, Keys, LockedDoor, Memory, OneWay, Position, Sprite, TargetLocation, TerrainCost,
+    Visibility, Vision,
+};
+
+const START_PATHER_HEADER: &str = "[start pather]";
+const GOAL_PATHER_HEADER: &str = "[goal pather]";
+
+/// A world and the pathers for its agent, everything needed to continue a run
+pub struct SaveState {
+    pub world: World,
+    pub start_pather: LpaStarPather,
+    pub goal_pather: LpaStarPather,
+}
+
+/// Returns a text form of the world and pathers, read back with `load`.
+///
+/// Each entity is a line of its components, in the same `name=value` style as the snapshots.
+/// Entities are written in id order so they are spawned in the same order when loaded. Only
+/// entities with a position are saved, the spatial cache and event queues are rebuilt each step.
+pub fn save(world: &World, start_pather: &LpaStarPather, goal_pather: &LpaStarPather) -> String {
+    let mut entities: Vec<Entity> = world.query::<&Position>().iter().map(|(e, _)| e).collect();
+    entities.sort();
+
+    let mut s = String::new();
+    for e in entities {
+        s.push_str(&entity_line(&world.entity(e).unwrap()));
+        s.push('\n');
+    }
+    s.push_str(START_PATHER_HEADER);
+    s.push('\n');
+    s.push_str(&start_pather.save());
+    s.push_str(GOAL_PATHER_HEADER);
+    s.push('\n');
+    s.push_str(&goal_pather.save());
+    return s;
+}
+
+pub fn load(s: &str) -> Result<SaveState, String> {
+    let start = s
+        .find(START_PATHER_HEADER)
+        .ok_or_else(|| "save is missing the start pather".to_string())?;
+    let goal = s
+        .find(GOAL_PATHER_HEADER)
+        .ok_or_else(|| "save is missing the goal pather".to_string())?;
+    if goal < start {
+        return Err("goal pather saved before the start pather".to_string());
+    }
+
+    let mut world = World::new();
+    for line in s[..start].lines().filter(|l| !l.trim().is_empty()) {
+        let mut builder = parse_entity(line)?;
+        world.spawn(builder.build());
+    }
+
+    return Ok(SaveState {
+        world: world,
+        start_pather: LpaStarPather::load(&s[start + START_PATHER_HEADER.len()..goal])?,
+        goal_pather: LpaStarPather::load(&s[goal + GOAL_PATHER_HEADER.len()..])?,
+    });
+}
+
+pub fn save_to_file(
+    path: &str,
+    world: &World,
+    start_pather: &LpaStarPather,
+    goal_pather: &LpaStarPather,
+) -> Result<(), String> {
+    return fs::write(path, save(world, start_pather, goal_pather)).map_err(|e| e.to_string());
+}
+
+pub fn load_from_file(path: &str) -> Result<SaveState, String> {
+    return load(&fs::read_to_string(path).map_err(|e| e.to_string())?);
+}
+
+fn entity_line(e: &EntityRef) -> String {
+    let mut parts = Vec::new();
+
+    if let Some(p) = e.get::<Position>() {
+        parts.push(format!("pos={},{}", p.0.x, p.0.y));
+    }
+    if let Some(s) = e.get::<Sprite>() {
+        parts.push(format!("sprite={}", s.0));
+    }
+    if let Some(v) = e.get::<Visibility>() {
+        parts.push(format!("vis={}", v.0));
+    }
+    if let Some(bg) = e.get::<BackgroundHighlight>() {
+        parts.push(format!("bg={:?}", bg.0));
+    }
+    if let Some(v) = e.get::<Vision>() {
+        parts.push(format!("vision={}", v.0));
+    }
+    if let Some(t) = e.get::<TargetLocation>() {
+        match t.0 {
+            Some(p) => parts.push(format!("target={},{}", p.x, p.y)),
+            None => parts.push("target=none".to_string()),
+        }
+    }
+    if e.get::<AttackerAgent>().is_some() {
+        parts.push("attacker".to_string());
+    }
+    if let Some(h) = e.get::<Health>() {
+        parts.push(format!("health={}", h.0));
+    }
+    if let Some(a) = e.get::<Attack>() {
+        parts.push(format!("attack={}/{}", a.damage, a.range));
+    }
+    if let Some(m) = e.get::<Memory>() {
+        parts.push(format!("memory={}", m.0));
+    }
+    if let Some(t) = e.get::<TerrainCost>() {
+        parts.push(format!("terrain={}", t.0));
+    }
+    if let Some(d) = e.get::<OneWay>() {
+        parts.push(format!("oneway={:?}", d.0));
+    }
+    if e.get::<Key>().is_some() {
+        parts.push("key".to_string());
+    }
+    if e.get::<LockedDoor>().is_some() {
+        parts.push("door".to_string());
+    }
+    if let Some(k) = e.get::<Keys>() {
+        parts.push(format!("keys={}", k.0));
+    }
+
+    return parts.join(" ");
+}
+
+fn parse_entity(line: &str) -> Result<EntityBuilder, String> {
+    let err = || format!("invalid entity: {}", line);
+    let mut builder = EntityBuilder::new();
+
+    for part in line.split(' ') {
+        let (name, value) = part.split_once('=').unwrap_or((part, ""));
+        match name {
+            "pos" => builder.add(Position(value.parse()?)),
+            "sprite" => {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => builder.add(Sprite(c)),
+                    _ => return Err(err()),
+                }
+            }
+            "vis" => builder.add(Visibility(value.parse().map_err(|_| err())?)),
+            "bg" => builder.add(BackgroundHighlight(match value {
+                "Green" => Color::Green,
+                "Blue" => Color::Blue,
+                "Black" => Color::Black,
+                _ => return Err(err()),
+            })),
+            "vision" => builder.add(Vision(value.parse().map_err(|_| err())?)),
+            "target" => builder.add(TargetLocation(match value {
+                "none" => None,
+                p => Some(p.parse()?),
+            })),
+            "attacker" => builder.add(AttackerAgent),
+            "health" => builder.add(Health(value.parse().map_err(|_| err())?)),
+            "attack" => {
+                let (damage, range) = value.split_once('/').ok_or_else(err)?;
+                builder.add(Attack {
+                    damage: damage.parse().map_err(|_| err())?,
+                    range: range.parse().map_err(|_| err())?,
+                })
+            }
+            "memory" => builder.add(Memory(value.parse().map_err(|_| err())?)),
+            "terrain" => builder.add(TerrainCost(value.parse().map_err(|_| err())?)),
+            "oneway" => builder.add(OneWay(match value {
+                "Up" => Direction::Up,
+                "Down" => Direction::Down,
+                "Left" => Direction::Left,
+                "Right" => Direction::Right,
+                _ => return Err(err()),
+            })),
+            "key" => builder.add(Key),
+            "door" => builder.add(LockedDoor),
+            "keys" => builder.add(Keys(value.parse().map_err(|_| err())?)),
+            _ => return Err(err()),
+        };
+    }
+
+    return Ok(builder);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai_pathing::{get_goal_lpapather, get_start_lpapather},
+        parse_map,
+        snapshot::snapshot,
+        step_game_world, system_vision, FeatureFlags,
+    };
+
+    #[test]
+    fn test_save_resume() {
+        let map = "@..~..
+        .WW.T.
+        .=K..L
+        >.O..G";
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        features.memory_decay = Some(2);
+
+        let mut world = World::new();
+        parse_map(&mut world, map);
+        let mut start_pather = get_start_lpapather(&world);
+        let mut goal_pather = get_goal_lpapather(&world);
+        system_vision(&mut world);
+        for _ in 0..4 {
+            assert!(!step_game_world(
+                &mut world,
+                features,
+                &mut start_pather,
+                &mut goal_pather
+            ));
+        }
+
+        let saved = save(&world, &start_pather, &goal_pather);
+        let mut resumed = load(&saved).unwrap();
+        assert_eq!(snapshot(&resumed.world), snapshot(&world));
+        assert_eq!(
+            save(&resumed.world, &resumed.start_pather, &resumed.goal_pather),
+            saved
+        );
+
+        // Picks up where the original run left off
+        let mut steps = 0;
+        while !step_game_world(&mut world, features, &mut start_pather, &mut goal_pather) {
+            steps += 1;
+        }
+        let mut resumed_steps = 0;
+        while !step_game_world(
+            &mut resumed.world,
+            features,
+            &mut resumed.start_pather,
+            &mut resumed.goal_pather,
+        ) {
+            resumed_steps += 1;
+        }
+        assert_eq!(resumed_steps, steps);
+        assert_eq!(snapshot(&resumed.world), snapshot(&world));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        assert!(load("pos=0,0 sprite=@").is_err());
+
+        let mut world = World::new();
+        parse_map(&mut world, "@.G");
+        let saved = save(
+            &world,
+            &get_start_lpapather(&world),
+            &get_goal_lpapather(&world),
+        );
+        assert!(load(&saved).is_ok());
+        assert!(load(&saved.replace("sprite=@", "sprite=@@")).is_err());
+        assert!(load(&saved.replace("pos=0,0", "pos=0")).is_err());
+        assert!(load(&format!("unknown\n{}", saved)).is_err());
+        assert!(load(&saved.replace("size 3 1", "size 4 1")).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use std::{env, fs, path::PathBuf};
+
+use hecs::{EntityRef, World};
+
+use crate::{
+    ai_pathing::{get_goal_lpapather, get_start_lpapather},
+    parse_map, step_game_world, system_vision, Attack, AttackerAgent, BackgroundHighlight,
+    FeatureFlags, Health, Position, Sprite, TargetLocation, Visibility, Vision,
+};
+
+/// Set to regenerate golden files rather than comparing against them
+const UPDATE_ENV_VAR: &str = "UPDATE_SNAPSHOTS";
+const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Returns a canonical text representation of the world.
+///
+/// Each line is one entity with its components in a fixed order. Lines are sorted
+/// since entity ids depend on spawn order. Entities without any snapshot components,
+/// like the spatial cache, are skipped.
+pub fn snapshot(world: &World) -> String {
+    let mut lines: Vec<String> = world
+        .iter()
+        .map(|e| entity_line(&e))
+        .filter(|l| !l.is_empty())
+        .collect();
+    lines.sort();
+
+    let mut s = lines.join("\n");
+    s.push('\n');
+    return s;
+}
+
+fn entity_line(e: &EntityRef) -> String {
+    let mut parts = Vec::new();
+
+    if let Some(p) = e.get::<Position>() {
+        parts.push(format!("pos=({},{})", p.0.x, p.0.y));
+    }
+    if let Some(s) = e.get::<Sprite>() {
+        parts.push(format!("sprite={}", s.0));
+    }
+    if let Some(v) = e.get::<Visibility>() {
+        parts.push(format!("vis={}", v.0));
+    }
+    if let Some(bg) = e.get::<BackgroundHighlight>() {
+        parts.push(format!("bg={:?}", bg.0));
+    }
+    if let Some(v) = e.get::<Vision>() {
+        parts.push(format!("vision={}", v.0));
+    }
+    if let Some(t) = e.get::<TargetLocation>() {
+        match t.0 {
+            Some(p) => parts.push(format!("target=({},{})", p.x, p.y)),
+            None => parts.push("target=none".to_string()),
+        }
+    }
+    if e.get::<AttackerAgent>().is_some() {
+        parts.push("attacker".to_string());
+    }
+    if let Some(h) = e.get::<Health>() {
+        parts.push(format!("health={}", h.0));
+    }
+    if let Some(a) = e.get::<Attack>() {
+        parts.push(format!("attack={}/{}", a.damage, a.range));
+    }
+
+    return parts.join(" ");
+}
+
+/// Runs the simulation for `map` and returns a snapshot of the world at each of `ticks`.
+///
+/// Tick 0 is the world after the initial vision update, before the first step.
+/// Stops early if the goal is reached, so ticks past the end of the run are not captured.
+pub fn capture_snapshots(
+    map: &str,
+    features: FeatureFlags,
+    ticks: &[usize],
+) -> Vec<(usize, String)> {
+    let mut world = World::new();
+    parse_map(&mut world, map);
+    let mut start_pather = get_start_lpapather(&world);
+    let mut goal_pather = get_goal_lpapather(&world);
+    system_vision(&mut world);
+
+    let last_tick = ticks.iter().copied().max().unwrap_or(0);
+    let mut snapshots = Vec::new();
+    for tick in 0..=last_tick {
+        if ticks.contains(&tick) {
+            snapshots.push((tick, snapshot(&world)));
+        }
+
+        if step_game_world(&mut world, features, &mut start_pather, &mut goal_pather) {
+            break;
+        }
+    }
+
+    return snapshots;
+}
+
+/// Compare snapshots against the golden files in `snapshots/{name}_tick_{tick}.snap`.
+///
+/// Run with `UPDATE_SNAPSHOTS=1` to write new golden files.
+pub fn assert_snapshots(name: &str, snapshots: &[(usize, String)]) {
+    let update = env::var(UPDATE_ENV_VAR).is_ok();
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(SNAPSHOT_DIR);
+
+    for (tick, actual) in snapshots {
+        let path = dir.join(format!("{}_tick_{}.snap", name, tick));
+
+        if update {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&path, actual).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "missing golden file {:?}, run with {}=1 to create it",
+                path, UPDATE_ENV_VAR
+            )
+        });
+
+        if &expected != actual {
+            panic!(
+                "snapshot {} differs at tick {}\n{}",
+                name,
+                tick,
+                diff(&expected, actual)
+            );
+        }
+    }
+}
+
+/// Lists the entity lines only present in one of the snapshots
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let mut out = String::new();
+    for l in expected.iter().filter(|l| !actual.contains(l)) {
+        out.push_str(&format!("- {}\n", l));
+    }
+    for l in actual.iter().filter(|l| !expected.contains(l)) {
+        out.push_str(&format!("+ {}\n", l));
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_map, FeatureFlags};
+
+    use super::{assert_snapshots, capture_snapshots, diff};
+
+    #[test]
+    fn test_snapshot_start() {
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        let snapshots = capture_snapshots("@.G", features, &[0]);
+        assert_snapshots("start", &snapshots);
+    }
+
+    #[test]
+    fn test_snapshot_tower() {
+        let map = "@...T..G
+        ........
+        ........";
+
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        let ticks = [0, 4, 8, 11];
+        let snapshots = capture_snapshots(map, features, &ticks);
+        assert_eq!(snapshots.len(), ticks.len());
+        assert_snapshots("tower", &snapshots);
+    }
+
+    #[test]
+    fn test_snapshot_spiral() {
+        let map = "@..............
+        .OOOOOOOOOOOOO.
+        .O...........O.
+        .O.OOOOOOOOO.O.
+        .O.O.......O.O.
+        .O.OOOOOOO.O.O.
+        .O......GO.O.O.
+        .OOOOOOOOO.O.O.
+        ...........O...";
+
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        let ticks = [0, 100, 250, 374];
+        let snapshots = capture_snapshots(map, features, &ticks);
+        assert_eq!(snapshots.len(), ticks.len());
+        assert_snapshots("spiral", &snapshots);
+    }
+
+    #[test]
+    fn test_snapshot_deterministic() {
+        let mut features = FeatureFlags::new();
+        features.render = false;
+        let map = &create_map(5);
+        let ticks = [0, 1, 3, 5];
+
+        let first = capture_snapshots(map, features, &ticks);
+        let second = capture_snapshots(map, features, &ticks);
+        assert_eq!(first.len(), ticks.len());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let d = diff("a\nb\n", "a\nc\n");
+        assert_eq!(d, "- b\n+ c\n");
+    }
+}
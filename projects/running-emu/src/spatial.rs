@@ -1,9 +1,16 @@
 use hecs::{Entity, EntityBuilder, World};
-use std::hash::Hash;
+use std::{cmp::max, hash::Hash, str::FromStr};
 
-use crate::{get_max_point, Position};
+use crate::{
+    events::{read_events, DespawnEvent, MoveEvent},
+    get_max_point, Position,
+};
 
-/// Read only cache for spatial based lookups.
+/// Index of the entities at each point for spatial lookups.
+///
+/// Built once, then kept up to date from `MoveEvent`s and `DespawnEvent`s rather than rebuilt
+/// each tick. Entities at a point are kept in id order so lookups don't depend on how the world
+/// stores them. Entities spawned with a position after the cache is built aren't indexed.
 pub struct SpatialCache {
     entity_lookup: Vec<Vec<Vec<Entity>>>,
 }
@@ -22,37 +29,70 @@ impl SpatialCache {
         for (id, pos) in world.query::<&Position>().into_iter() {
             self.entity_lookup[pos.0.y][pos.0.x].push(id);
         }
+        for row in self.entity_lookup.iter_mut() {
+            for entities in row.iter_mut() {
+                entities.sort();
+            }
+        }
     }
 
     /// Returns the tile at a given location
     pub fn get_entities(&self, point: Point) -> Vec<Entity> {
         return self.entity_lookup[point.y][point.x].clone();
     }
-}
 
-pub fn system_update_spatial_cache(world: &mut World) {
-    let cache;
-
-    for (_, c) in world.query::<&mut SpatialCache>().iter() {
-        cache = c;
-        // Clear the chache
-        let width = cache.entity_lookup[0].len();
-        let height = cache.entity_lookup.len();
-        for y in 0..height {
-            for x in 0..width {
-                cache.entity_lookup[y][x].clear();
+    /// Returns the entities between `min` and `max` inclusive, row by row. Points past the edge
+    /// of the map are skipped.
+    pub fn entities_in_rect(&self, min: Point, max: Point) -> Vec<Entity> {
+        let mut results = Vec::new();
+        for row in self.entity_lookup.iter().take(max.y + 1).skip(min.y) {
+            for entities in row.iter().take(max.x + 1).skip(min.x) {
+                results.extend(entities.iter());
             }
         }
-        cache.populate_entity_lookup(world);
+        return results;
+    }
+
+    fn insert(&mut self, e: Entity, p: Point) {
+        let entities = &mut self.entity_lookup[p.y][p.x];
+        if let Err(i) = entities.binary_search(&e) {
+            entities.insert(i, e);
+        }
+    }
+
+    fn remove(&mut self, e: Entity, p: Point) {
+        let entities = &mut self.entity_lookup[p.y][p.x];
+        if let Ok(i) = entities.binary_search(&e) {
+            entities.remove(i);
+        }
+    }
+}
 
+/// Apply the moves and despawns of the previous tick to the cache, creating it if it doesn't
+/// exist.
+///
+/// Must run before `system_clear_events` drops the previous tick's events.
+pub fn system_update_spatial_cache(world: &mut World) {
+    if world.query::<&SpatialCache>().iter().next().is_none() {
+        let cache = SpatialCache::new(world);
+        let mut builder = EntityBuilder::new();
+        builder.add(cache);
+        world.spawn(builder.build());
         return;
     }
 
-    let cache = SpatialCache::new(world);
-    let mut builder = EntityBuilder::new();
-    builder.add(cache);
-    // Otherwise we create one if it doesn't exist
-    world.spawn(builder.build());
+    // An entity can move then be despawned in the same tick, so moves are applied first
+    let moves = read_events::<MoveEvent>(world);
+    let despawns = read_events::<DespawnEvent>(world);
+    for (_, cache) in world.query_mut::<&mut SpatialCache>() {
+        for m in moves.iter() {
+            cache.remove(m.entity, m.from);
+            cache.insert(m.entity, m.to);
+        }
+        for d in despawns.iter() {
+            cache.remove(d.entity, d.at);
+        }
+    }
 }
 
 pub fn get_entities(world: &World, p: Point) -> Vec<Entity> {
@@ -68,9 +108,29 @@ pub fn get_entities(world: &World, p: Point) -> Vec<Entity> {
             results.push(id)
         }
     }
+    results.sort();
     return results;
 }
 
+/// Returns the entities between `min` and `max` inclusive, row by row and in id order within a
+/// point, like `SpatialCache::entities_in_rect`
+pub fn entities_in_rect(world: &World, min: Point, max: Point) -> Vec<Entity> {
+    if let Some((_, cache)) = world.query::<&SpatialCache>().iter().next() {
+        return cache.entities_in_rect(min, max);
+    }
+
+    // If no cache, need to iterate and find
+    let mut results = Vec::new();
+    for (id, p) in world.query::<&Position>().iter() {
+        let p = p.0;
+        if p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y {
+            results.push((p.y, p.x, id));
+        }
+    }
+    results.sort();
+    return results.into_iter().map(|(_, _, id)| id).collect();
+}
+
 /// Point in the game world
 #[derive(PartialEq, Clone, Copy, Hash, Eq, Debug)]
 pub struct Point {
@@ -83,6 +143,48 @@ impl Point {
     pub fn dist(&self, p: &Point) -> i32 {
         return (self.x as i32 - p.x as i32).abs() + (self.y as i32 - p.y as i32).abs();
     }
+
+    /// Returns the number of steps between points when diagonal steps are allowed
+    pub fn diagonal_dist(&self, p: &Point) -> i32 {
+        return max(
+            (self.x as i32 - p.x as i32).abs(),
+            (self.y as i32 - p.y as i32).abs(),
+        );
+    }
+
+    /// Returns the direction of a single step to `p`, or `None` if `p` isn't a neighbor
+    pub fn direction_to(&self, p: &Point) -> Option<Direction> {
+        return match (p.x as i32 - self.x as i32, p.y as i32 - self.y as i32) {
+            (1, 0) => Some(Direction::Right),
+            (-1, 0) => Some(Direction::Left),
+            (0, -1) => Some(Direction::Up),
+            (0, 1) => Some(Direction::Down),
+            _ => None,
+        };
+    }
+}
+
+/// Parses points written as `x,y`
+impl FromStr for Point {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid point: {}", s);
+        let (x, y) = s.split_once(',').ok_or_else(err)?;
+        return Ok(Point {
+            x: x.parse().map_err(|_| err())?,
+            y: y.parse().map_err(|_| err())?,
+        });
+    }
+}
+
+/// Direction of a step between neighboring points, up is towards the first row of the map
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
 }
 
 pub fn print_path(path: &Vec<Point>, world: &World) {
@@ -99,3 +201,79 @@ pub fn print_path(path: &Vec<Point>, world: &World) {
         println!("")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai_pathing::{get_goal_lpapather, get_start_lpapather},
+        parse_map, step_game_world, system_vision, FeatureFlags,
+    };
+
+    fn cache_lookup(world: &World) -> Vec<Vec<Vec<Entity>>> {
+        let mut query = world.query::<&SpatialCache>();
+        let (_, cache) = query.iter().next().expect("no spatial cache");
+        return cache.entity_lookup.clone();
+    }
+
+    #[test]
+    fn test_incremental_spatial_cache() {
+        let mut features = FeatureFlags::new();
+        features.render = false;
+
+        let mut world = World::new();
+        parse_map(
+            &mut world,
+            "@.D.K
+            .OO.L
+            ..T.G",
+        );
+        let mut start_pather = get_start_lpapather(&world);
+        let mut goal_pather = get_goal_lpapather(&world);
+        system_vision(&mut world);
+
+        let mut despawns = 0;
+        loop {
+            let done = step_game_world(&mut world, features, &mut start_pather, &mut goal_pather);
+            despawns += read_events::<DespawnEvent>(&world).len();
+            // Catch up on the last tick, then compare against a full rebuild
+            system_update_spatial_cache(&mut world);
+            assert_eq!(
+                cache_lookup(&world),
+                SpatialCache::new(&world).entity_lookup
+            );
+            if done {
+                break;
+            }
+        }
+        assert!(despawns > 0);
+    }
+
+    #[test]
+    fn test_entities_in_rect() {
+        let mut world = World::new();
+        parse_map(
+            &mut world,
+            "@.W
+            ...
+            ..G",
+        );
+        let min = Point { x: 1, y: 1 };
+        let max = Point { x: 5, y: 5 };
+
+        let uncached = entities_in_rect(&world, min, max);
+        let sprites: String = uncached
+            .iter()
+            .map(|e| world.get::<crate::Sprite>(*e).unwrap().0)
+            .collect();
+        assert_eq!(sprites, "...G");
+
+        system_update_spatial_cache(&mut world);
+        assert_eq!(entities_in_rect(&world, min, max), uncached);
+        assert_eq!(
+            entities_in_rect(&world, Point { x: 2, y: 0 }, Point { x: 2, y: 0 }).len(),
+            2
+        );
+        assert!(entities_in_rect(&world, Point { x: 3, y: 0 }, Point { x: 5, y: 5 }).is_empty());
+    }
+}
@@ -0,0 +1,390 @@
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use log::{debug, info};
+use rayon::prelude::*;
+
+use crate::encode::save_wav;
+
+/// Power iterations used to find the rank 1 approximation when updating an atom
+const POWER_ITERATIONS: usize = 5;
+/// Chunks quieter than this are skipped when learning, they don't say anything about the corpus
+const MIN_CHUNK_ENERGY: f32 = 1e-6;
+
+/// Settings for [`Dictionary::learn`]
+#[derive(Debug, Clone)]
+pub struct LearnOptions {
+    pub num_atoms: usize,
+    /// Number of atoms used to code each chunk of the corpus
+    pub sparsity: usize,
+    /// Rounds of alternating between coding the corpus and updating the atoms
+    pub iterations: usize,
+}
+
+impl Default for LearnOptions {
+    fn default() -> Self {
+        Self {
+            num_atoms: 32,
+            sparsity: 3,
+            iterations: 10,
+        }
+    }
+}
+
+/// Atoms learned from a corpus rather than recorded, so material that isn't
+/// made of piano notes can still be reconstructed
+///
+/// Learning is k-SVD style: each chunk of the corpus is coded with a few atoms
+/// using matching pursuit, then each atom is replaced by the rank 1
+/// approximation of what's left of the chunks using it.
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+    /// Unit norm atoms, each one chunk long
+    atoms: Vec<Vec<f32>>,
+    /// Mean magnitude of each atom's coefficients over the corpus
+    gains: Vec<f32>,
+}
+
+/// Sparse code of a single chunk
+#[derive(Debug, Clone)]
+struct Code {
+    /// Atom index and coefficient of each atom used
+    coefs: Vec<(usize, f32)>,
+    /// Part of the chunk the atoms don't explain
+    residual: Vec<f32>,
+}
+
+impl Dictionary {
+    /// Learns a dictionary of `chunk_len` long atoms from the chunks of `corpus`
+    pub fn learn(corpus: &[Vec<f32>], chunk_len: usize, options: &LearnOptions) -> Self {
+        let chunks = corpus
+            .iter()
+            .flat_map(|samples| samples.chunks(chunk_len))
+            .map(|chunk| {
+                let mut chunk = chunk.to_vec();
+                chunk.resize(chunk_len, 0.0);
+                chunk
+            })
+            .filter(|chunk| energy(chunk) > MIN_CHUNK_ENERGY)
+            .collect_vec();
+        assert!(!chunks.is_empty(), "corpus has no audible chunks");
+        info!("learning dictionary from {} chunks", chunks.len());
+
+        // start from evenly spaced chunks of the corpus
+        let num_atoms = options.num_atoms.min(chunks.len());
+        let mut atoms = (0..num_atoms)
+            .map(|i| normalized(&chunks[i * chunks.len() / num_atoms]))
+            .collect_vec();
+
+        let total_energy: f32 = chunks.iter().map(|c| energy(c)).sum();
+        let mut codes = Vec::new();
+        for iteration in 0..options.iterations {
+            codes = code_chunks(&atoms, &chunks, options.sparsity);
+            for k in 0..atoms.len() {
+                update_atom(&mut atoms, k, &mut codes);
+            }
+
+            let residual_energy: f32 = codes.iter().map(|c| energy(&c.residual)).sum();
+            info!(
+                "iteration {}: {:.1}% of the corpus energy is left in the residual",
+                iteration,
+                100.0 * residual_energy / total_energy
+            );
+        }
+
+        // the gains come from the final atoms
+        let codes = if options.iterations == 0 {
+            code_chunks(&atoms, &chunks, options.sparsity)
+        } else {
+            codes
+        };
+        let gains = (0..atoms.len())
+            .map(|k| {
+                let coefs = codes
+                    .iter()
+                    .flat_map(|c| c.coefs.iter())
+                    .filter(|(j, _)| *j == k)
+                    .map(|(_, x)| x.abs())
+                    .collect_vec();
+                coefs.iter().sum::<f32>() / coefs.len().max(1) as f32
+            })
+            .collect_vec();
+
+        Self { atoms, gains }
+    }
+
+    /// Returns the atoms scaled to how loud they were in the corpus, for the
+    /// optimizers
+    ///
+    /// The optimizers only add atoms, so each atom is included with both
+    /// polarities. Atoms that no chunk used are left out.
+    pub fn atoms(&self) -> Vec<Vec<f32>> {
+        self.atoms
+            .iter()
+            .zip(self.gains.iter())
+            .filter(|(_, &gain)| gain > 0.0)
+            .flat_map(|(atom, &gain)| {
+                [
+                    atom.iter().map(|x| x * gain).collect_vec(),
+                    atom.iter().map(|x| -x * gain).collect_vec(),
+                ]
+            })
+            .collect()
+    }
+
+    /// Number of learned atoms
+    pub fn len(&self) -> usize {
+        self.atoms.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Saves the scaled atoms back to back as a wav file, so the dictionary
+    /// can be listened to as well as reused
+    pub fn save(&self, filename: &str) -> anyhow::Result<()> {
+        let samples = self
+            .atoms
+            .iter()
+            .zip(self.gains.iter())
+            .flat_map(|(atom, &gain)| atom.iter().map(move |x| x * gain))
+            .collect_vec();
+        save_wav(filename, &samples)
+    }
+
+    /// Loads a dictionary saved with [`Dictionary::save`]
+    pub fn load(filename: &str, chunk_len: usize) -> anyhow::Result<Self> {
+        let samples = hound::WavReader::open(filename)
+            .context("failed to open dictionary")?
+            .samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to read dictionary samples")?;
+        if samples.len() % chunk_len != 0 {
+            bail!(
+                "dictionary has {} samples, not a multiple of the chunk length {}",
+                samples.len(),
+                chunk_len
+            );
+        }
+
+        let (atoms, gains) = samples
+            .chunks(chunk_len)
+            .map(|scaled| (normalized(scaled), energy(scaled).sqrt()))
+            .unzip();
+        Ok(Self { atoms, gains })
+    }
+}
+
+/// Codes each chunk with `sparsity` atoms using matching pursuit
+fn code_chunks(atoms: &[Vec<f32>], chunks: &[Vec<f32>], sparsity: usize) -> Vec<Code> {
+    chunks
+        .par_iter()
+        .map(|chunk| {
+            let mut residual = chunk.clone();
+            let mut coefs: Vec<(usize, f32)> = Vec::new();
+            for _ in 0..sparsity {
+                let (k, x) = atoms
+                    .iter()
+                    .map(|atom| dot(atom, &residual))
+                    .enumerate()
+                    .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+                    .unwrap();
+                if x == 0.0 {
+                    break;
+                }
+
+                add_scaled(&mut residual, &atoms[k], -x);
+                match coefs.iter_mut().find(|(j, _)| *j == k) {
+                    Some((_, coef)) => *coef += x,
+                    None => coefs.push((k, x)),
+                }
+            }
+            Code { coefs, residual }
+        })
+        .collect()
+}
+
+/// Replaces atom `k` with the rank 1 approximation of the chunks using it once
+/// its own contribution is added back, updating their coefficients to match
+///
+/// An atom no chunk uses is replaced with the worst coded chunk instead.
+fn update_atom(atoms: &mut [Vec<f32>], k: usize, codes: &mut [Code]) {
+    let users = codes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| c.coefs.iter().find(|(j, _)| *j == k).map(|(_, x)| (i, *x)))
+        .collect_vec();
+
+    if users.is_empty() {
+        let worst = codes
+            .iter()
+            .max_by(|a, b| energy(&a.residual).total_cmp(&energy(&b.residual)))
+            .unwrap();
+        debug!(
+            "atom {} is unused, replacing it with the worst coded chunk",
+            k
+        );
+        atoms[k] = normalized(&worst.residual);
+        return;
+    }
+
+    // what's left of each user's chunk without atom k
+    let errors = users
+        .iter()
+        .map(|&(i, x)| {
+            let mut error = codes[i].residual.clone();
+            add_scaled(&mut error, &atoms[k], x);
+            error
+        })
+        .collect_vec();
+
+    let mut atom = atoms[k].clone();
+    for _ in 0..POWER_ITERATIONS {
+        let mut next = vec![0.0; atom.len()];
+        for error in errors.iter() {
+            add_scaled(&mut next, error, dot(error, &atom));
+        }
+        if energy(&next) == 0.0 {
+            break;
+        }
+        atom = normalized(&next);
+    }
+
+    for (&(i, _), error) in users.iter().zip(errors) {
+        let x = dot(&error, &atom);
+        let mut residual = error;
+        add_scaled(&mut residual, &atom, -x);
+        let code = &mut codes[i];
+        code.residual = residual;
+        code.coefs.iter_mut().find(|(j, _)| *j == k).unwrap().1 = x;
+    }
+    atoms[k] = atom;
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn energy(a: &[f32]) -> f32 {
+    dot(a, a)
+}
+
+/// `a += scale * b`
+fn add_scaled(a: &mut [f32], b: &[f32], scale: f32) {
+    a.iter_mut().zip(b).for_each(|(x, y)| *x += scale * y);
+}
+
+/// Returns `a` scaled to unit norm, or unchanged if it's silent
+fn normalized(a: &[f32]) -> Vec<f32> {
+    let norm = energy(a).sqrt();
+    if norm == 0.0 {
+        return a.to_vec();
+    }
+    a.iter().map(|x| x / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use itertools::Itertools;
+
+    use super::{code_chunks, dot, energy, Dictionary, LearnOptions};
+
+    const CHUNK_LEN: usize = 64;
+
+    fn tone(freq: f32, phase: f32) -> Vec<f32> {
+        (0..CHUNK_LEN)
+            .map(|i| (2.0 * PI * freq * i as f32 / CHUNK_LEN as f32 + phase).sin())
+            .collect()
+    }
+
+    /// Chunks that each mix two of three tones at different volumes
+    fn corpus() -> Vec<Vec<f32>> {
+        let tones = [tone(3.0, 0.0), tone(7.0, 1.0), tone(13.0, 2.0)];
+        let tones = &tones;
+        let samples = (0..30)
+            .flat_map(|i| {
+                let (a, b) = (i % 3, (i + 1) % 3);
+                let (x, y) = (1.0 + (i % 5) as f32 * 0.1, 0.5 - (i % 4) as f32 * 0.1);
+                (0..CHUNK_LEN).map(move |j| x * tones[a][j] + y * tones[b][j])
+            })
+            .collect_vec();
+        vec![samples]
+    }
+
+    /// Fraction of the corpus energy left after coding it with `dictionary`
+    fn residual_fraction(dictionary: &Dictionary, corpus: &[Vec<f32>]) -> f32 {
+        let chunks = corpus[0]
+            .chunks(CHUNK_LEN)
+            .map(|c| c.to_vec())
+            .collect_vec();
+        let codes = code_chunks(&dictionary.atoms, &chunks, 2);
+        let residual: f32 = codes.iter().map(|c| energy(&c.residual)).sum();
+        let total: f32 = chunks.iter().map(|c| energy(c)).sum();
+        residual / total
+    }
+
+    #[test]
+    fn test_learn_dictionary() {
+        let corpus = corpus();
+        let mut options = LearnOptions {
+            num_atoms: 3,
+            sparsity: 2,
+            iterations: 0,
+        };
+        let initial = Dictionary::learn(&corpus, CHUNK_LEN, &options);
+        options.iterations = 10;
+        let dictionary = Dictionary::learn(&corpus, CHUNK_LEN, &options);
+        assert_eq!(dictionary.len(), 3);
+        for atom in dictionary.atoms.iter() {
+            assert!((energy(atom) - 1.0).abs() < 1e-3);
+        }
+
+        // learning codes the corpus better than the chunks it started from
+        let initial_residual = residual_fraction(&initial, &corpus);
+        let learned_residual = residual_fraction(&dictionary, &corpus);
+        assert!(
+            learned_residual < initial_residual / 4.0,
+            "residual went from {} to {}",
+            initial_residual,
+            learned_residual
+        );
+
+        // and moves the atoms towards the tones the corpus is made of
+        for t in [tone(3.0, 0.0), tone(7.0, 1.0), tone(13.0, 2.0)] {
+            let t_norm = energy(&t).sqrt();
+            let best = dictionary
+                .atoms
+                .iter()
+                .map(|a| dot(a, &t).abs() / t_norm)
+                .fold(0.0, f32::max);
+            assert!(best > 0.95, "tone not learned, best match {}", best);
+        }
+
+        // both polarities of each used atom are available to the optimizers
+        assert_eq!(dictionary.atoms().len(), 6);
+    }
+
+    #[test]
+    fn test_save_load_dictionary() {
+        let options = LearnOptions {
+            num_atoms: 3,
+            sparsity: 2,
+            iterations: 2,
+        };
+        let dictionary = Dictionary::learn(&corpus(), CHUNK_LEN, &options);
+
+        let path = std::env::temp_dir().join("whale_singer_test_dictionary.wav");
+        let path = path.to_str().unwrap();
+        dictionary.save(path).unwrap();
+        let loaded = Dictionary::load(path, CHUNK_LEN).unwrap();
+        assert_eq!(loaded.len(), dictionary.len());
+        for (a, b) in loaded.atoms().iter().zip(dictionary.atoms()) {
+            assert!(a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-5));
+        }
+        assert!(Dictionary::load(path, CHUNK_LEN + 1).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
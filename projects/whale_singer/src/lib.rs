@@ -1,6 +1,8 @@
 pub mod app;
 pub mod decode;
+pub mod dictionary;
 pub mod encode;
+pub mod live;
 pub mod metrics;
 pub mod optimization;
 pub mod samples;
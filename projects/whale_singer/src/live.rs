@@ -0,0 +1,181 @@
+use std::{
+    collections::VecDeque,
+    io::stdin,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    SampleFormat, StreamConfig, SupportedStreamConfig,
+};
+use log::{error, info, warn};
+
+use crate::{encode::SAMPLE_RATE, optimization::StreamOptimizer};
+
+/// Delay between the microphone input and playing back the reconstruction
+const LATENCY: Duration = Duration::from_secs(2);
+/// Fraction of a chunk's duration the optimizer can spend on it, the rest is
+/// headroom so playback doesn't fall behind
+const CHUNK_BUDGET: f64 = 0.8;
+
+/// Reconstructs the microphone input from the atoms and plays it back in real
+/// time, until enter is pressed
+pub fn run_live(atoms: &[Vec<f32>]) -> anyhow::Result<()> {
+    let host = cpal::default_host();
+    let input = host
+        .default_input_device()
+        .context("no input device available")?;
+    let output = host
+        .default_output_device()
+        .context("no output device available")?;
+
+    let input_config = f32_config(&input, input.default_input_config()?)?;
+    let output_config = f32_config(&output, output.default_output_config()?)?;
+    info!(
+        "live mode with input: {:?}, output: {:?}",
+        input.name()?,
+        output.name()?
+    );
+
+    // mono samples at the input device's sample rate
+    let (tx, rx) = mpsc::channel::<Vec<f32>>();
+    let in_channels = input_config.channels as usize;
+    let input_stream = input.build_input_stream(
+        &input_config,
+        move |data: &[f32], _: &cpal::InputCallbackInfo| {
+            let mono = data
+                .chunks(in_channels)
+                .map(|frame| frame.iter().sum::<f32>() / in_channels as f32)
+                .collect();
+            // the optimizer thread has exited if this fails
+            let _ = tx.send(mono);
+        },
+        |err| error!("input stream error: {}", err),
+        None,
+    )?;
+
+    // mono samples at the output device's sample rate, pre-filled with
+    // silence for the latency
+    let out_rate = output_config.sample_rate.0 as usize;
+    let latency_samples = (LATENCY.as_secs_f64() * out_rate as f64) as usize;
+    let playback = Arc::new(Mutex::new(VecDeque::from(vec![0.0; latency_samples])));
+    let out_channels = output_config.channels as usize;
+    let out_queue = playback.clone();
+    let output_stream = output.build_output_stream(
+        &output_config,
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            let mut queue = out_queue.lock().unwrap();
+            let mut underrun = false;
+            for frame in data.chunks_mut(out_channels) {
+                let sample = queue.pop_front().unwrap_or_else(|| {
+                    underrun = true;
+                    0.0
+                });
+                frame.iter_mut().for_each(|s| *s = sample);
+            }
+            if underrun {
+                warn!("playback underrun, reconstruction is falling behind");
+            }
+        },
+        |err| error!("output stream error: {}", err),
+        None,
+    )?;
+
+    let optimizer = StreamOptimizer::new(atoms);
+    let in_rate = input_config.sample_rate.0 as usize;
+    let worker =
+        thread::spawn(move || reconstruct_stream(optimizer, rx, in_rate, out_rate, playback));
+
+    input_stream.play()?;
+    output_stream.play()?;
+
+    info!("reconstructing microphone input, press enter to stop");
+    let mut line = String::new();
+    stdin().read_line(&mut line)?;
+
+    // dropping the input stream closes the channel and stops the worker
+    drop(input_stream);
+    drop(output_stream);
+    match worker.join() {
+        Ok(result) => result,
+        Err(_) => bail!("reconstruction thread panicked"),
+    }
+}
+
+/// Reconstructs the input a chunk at a time and queues the result for playback
+fn reconstruct_stream(
+    mut optimizer: StreamOptimizer,
+    rx: Receiver<Vec<f32>>,
+    in_rate: usize,
+    out_rate: usize,
+    playback: Arc<Mutex<VecDeque<f32>>>,
+) -> anyhow::Result<()> {
+    let chunk_len = optimizer.chunk_len();
+    let chunk_duration = Duration::from_secs_f64(chunk_len as f64 / SAMPLE_RATE as f64);
+    let budget = chunk_duration.mul_f64(CHUNK_BUDGET);
+    // number of input samples that make up a chunk at the optimizer's sample rate
+    let in_chunk_len = chunk_len * in_rate / SAMPLE_RATE;
+
+    let mut pending = Vec::new();
+    while let Ok(samples) = rx.recv() {
+        pending.extend(samples);
+
+        while pending.len() >= in_chunk_len {
+            let start = Instant::now();
+            let raw: Vec<f32> = pending.drain(..in_chunk_len).collect();
+            let target = resample(&raw, chunk_len);
+            let reconstructed = optimizer.reconstruct(&target, start + budget)?;
+            let out = resample(&reconstructed, chunk_len * out_rate / SAMPLE_RATE);
+            playback.lock().unwrap().extend(out);
+
+            let elapsed = start.elapsed();
+            if elapsed > chunk_duration {
+                warn!(
+                    "chunk took {:?}, longer than its {:?} duration",
+                    elapsed, chunk_duration
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the f32 stream config for a device's default config
+fn f32_config(
+    device: &cpal::Device,
+    config: SupportedStreamConfig,
+) -> anyhow::Result<StreamConfig> {
+    if config.sample_format() != SampleFormat::F32 {
+        bail!(
+            "unsupported sample format for {:?}: {:?}",
+            device.name()?,
+            config.sample_format()
+        );
+    }
+    Ok(config.config())
+}
+
+/// Linearly interpolates `samples` to `len` samples
+fn resample(samples: &[f32], len: usize) -> Vec<f32> {
+    if samples.len() == len || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let step = (samples.len() - 1) as f64 / (len - 1).max(1) as f64;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let idx = pos as usize;
+            let frac = (pos - idx as f64) as f32;
+            let next = samples[(idx + 1).min(samples.len() - 1)];
+            samples[idx] * (1.0 - frac) + next * frac
+        })
+        .collect()
+}
@@ -4,8 +4,10 @@ use log::info;
 use whale_singer::{
     app::run_app,
     decode::extract_samples,
+    dictionary::{Dictionary, LearnOptions},
     encode::{save_wav, SAMPLE_RATE},
-    optimization::{AtomOptimizer, AtomSearchResult},
+    live::run_live,
+    optimization::{AtomOptimizer, AtomSearchResult, CHUNK_LEN},
 };
 
 use color_eyre::Result;
@@ -17,14 +19,21 @@ fn main() -> Result<()> {
     if args.len() == 1 {
         return run_app();
     } else if args[1] == "scratch" {
-        run_scratch();
+        run_scratch(&load_atoms_or_dictionary(args.get(2)));
+        return color_eyre::Result::Ok(());
+    } else if args[1] == "live" {
+        run_live(&load_atoms_or_dictionary(args.get(2))).unwrap();
+        return color_eyre::Result::Ok(());
+    } else if args[1] == "learn" && args.len() > 3 {
+        // learn <dictionary> <corpus files>...
+        run_learn(&args[2], &args[3..]);
         return color_eyre::Result::Ok(());
     }
 
     panic!("invalid arguments: {:?}", args);
 }
 
-fn run_scratch() {
+fn run_scratch(atoms: &[Vec<f32>]) {
     let src = std::fs::File::open("im_different_sample.wav").expect("failed to open media");
     // let src = std::fs::File::open("happy_birthday.mp3").expect("failed to open media");
     let mut target_samples = extract_samples(src).unwrap();
@@ -32,24 +41,7 @@ fn run_scratch() {
 
     let target = target_samples;
 
-    // let paths = fs::read_dir("../../../piano-mp3/piano-mp3/").unwrap();
-
-    let atom_files = ["A4", "B4", "C4", "D4", "E4", "F4", "G4"];
-    let mut atoms = Vec::new();
-    for atom_id in atom_files {
-        let path_name = format!("../../../piano-mp3/piano-mp3/{}.mp3", atom_id);
-        // let src =
-        //     std::fs::File::open(atom_file.unwrap().path()).expect("failed to open media");
-        let src = std::fs::File::open(path_name).expect("failed to open media");
-        let key_samples = extract_samples(src).unwrap();
-        // only the the middle 1 second
-        // key_samples = key_samples[SAMPLE_RATE * 3 / 4..SAMPLE_RATE * 5 / 4].to_vec();
-        atoms.push(key_samples);
-    }
-
-    info!("loaded {} atoms", atoms.len());
-
-    let mut atom_finder = AtomOptimizer::new(&target, &atoms);
+    let mut atom_finder = AtomOptimizer::new(&target, atoms);
 
     loop {
         match atom_finder.add_best_chunk().unwrap() {
@@ -73,3 +65,51 @@ fn run_scratch() {
 
     info!("finished searching");
 }
+
+/// Learns a dictionary from the corpus files and saves it to `dictionary`
+fn run_learn(dictionary: &str, corpus_files: &[String]) {
+    let corpus = corpus_files
+        .iter()
+        .map(|path| {
+            let src = std::fs::File::open(path).expect("failed to open media");
+            extract_samples(src).unwrap()
+        })
+        .collect::<Vec<_>>();
+    info!("loaded {} corpus files", corpus.len());
+
+    let learned = Dictionary::learn(&corpus, CHUNK_LEN, &LearnOptions::default());
+    learned.save(dictionary).unwrap();
+    info!("saved {} atoms to {}", learned.len(), dictionary);
+}
+
+/// Loads the learned dictionary at `dictionary` if given, otherwise the piano notes
+fn load_atoms_or_dictionary(dictionary: Option<&String>) -> Vec<Vec<f32>> {
+    match dictionary {
+        Some(path) => {
+            let atoms = Dictionary::load(path, CHUNK_LEN).unwrap().atoms();
+            info!("loaded {} atoms from {}", atoms.len(), path);
+            atoms
+        }
+        None => load_atoms(),
+    }
+}
+
+fn load_atoms() -> Vec<Vec<f32>> {
+    // let paths = fs::read_dir("../../../piano-mp3/piano-mp3/").unwrap();
+
+    let atom_files = ["A4", "B4", "C4", "D4", "E4", "F4", "G4"];
+    let mut atoms = Vec::new();
+    for atom_id in atom_files {
+        let path_name = format!("../../../piano-mp3/piano-mp3/{}.mp3", atom_id);
+        // let src =
+        //     std::fs::File::open(atom_file.unwrap().path()).expect("failed to open media");
+        let src = std::fs::File::open(path_name).expect("failed to open media");
+        let key_samples = extract_samples(src).unwrap();
+        // only the the middle 1 second
+        // key_samples = key_samples[SAMPLE_RATE * 3 / 4..SAMPLE_RATE * 5 / 4].to_vec();
+        atoms.push(key_samples);
+    }
+
+    info!("loaded {} atoms", atoms.len());
+    atoms
+}
@@ -30,6 +30,13 @@ impl Default for ErrorCalculator {
 }
 
 impl ErrorCalculator {
+    /// Clears the cached autocorrelations, needed when the reference chunks
+    /// change but can reuse chunk ids
+    pub(super) fn clear_caches(&mut self) {
+        self.autocor_time_cache.clear();
+        self.autocor_freq_cache.clear();
+    }
+
     fn get_inverse(&mut self, len: usize) -> Arc<dyn Fft<f32>> {
         get_or_insert(len, &mut self.inverse, || {
             self.planner.plan_fft_inverse(len)
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use anyhow::{Context, Ok};
 
 use itertools::Itertools;
@@ -13,6 +15,9 @@ use self::{
 mod chunks;
 pub mod error;
 
+/// Number of samples in a chunk, atoms are matched to the target a chunk at a time
+pub const CHUNK_LEN: usize = SAMPLE_RATE / 10;
+
 /// Find the next best atom
 pub struct AtomOptimizer {
     /// Number of samples in a chunk
@@ -48,7 +53,7 @@ impl ImprovementDetails {
 
 impl AtomOptimizer {
     pub fn new(target: &[f32], atoms: &[Vec<f32>]) -> Self {
-        let chunk_len = SAMPLE_RATE / 10;
+        let chunk_len = CHUNK_LEN;
 
         let sample_chunks = to_chunks(&[target.to_vec()], chunk_len);
         let atom_chunks = to_chunks(atoms, chunk_len);
@@ -174,3 +179,96 @@ impl AtomOptimizer {
         Ok(())
     }
 }
+
+/// Reconstructs a stream of target chunks one at a time, e.g. from a microphone
+///
+/// Unlike [`AtomOptimizer`] each chunk is optimized on its own as it arrives,
+/// greedily adding atom chunks until there is no improvement or the deadline
+/// for the chunk passes.
+pub struct StreamOptimizer {
+    chunk_len: usize,
+    error_calc: ErrorCalculator,
+    atom_chunks: Vec<Chunk>,
+}
+
+impl StreamOptimizer {
+    pub fn new(atoms: &[Vec<f32>]) -> Self {
+        let chunk_len = CHUNK_LEN;
+        let atom_chunks = to_chunks(atoms, chunk_len);
+        debug!("converted atoms into {} atom chunks", atom_chunks.len());
+
+        Self {
+            chunk_len,
+            error_calc: ErrorCalculator::default(),
+            atom_chunks,
+        }
+    }
+
+    /// Number of samples in each target chunk
+    pub fn chunk_len(&self) -> usize {
+        self.chunk_len
+    }
+
+    /// Returns the reconstruction of `target` using the best atom chunks found
+    /// before `deadline`
+    ///
+    /// The deadline is checked between atom chunks, so this returns shortly
+    /// after it even if the search isn't finished.
+    pub fn reconstruct(&mut self, target: &[f32], deadline: Instant) -> anyhow::Result<Vec<f32>> {
+        assert_eq!(target.len(), self.chunk_len);
+
+        // every target chunk has the same id, so the cached values are stale
+        self.error_calc.clear_caches();
+        let target = Chunk {
+            atom_id: 0,
+            chunk_id: 0,
+            samples: target.to_vec().into(),
+        };
+
+        let mut constructed = ConstructedSample::new(self.chunk_len, 1);
+        let mut buffer = constructed.chunk_samples(0);
+        let mut cur_error = self.error_calc.weighted_error(&target, &buffer)?;
+
+        while Instant::now() < deadline {
+            let mut best_error = cur_error;
+            let mut best_atom_chunk = None;
+
+            for atom in self.atom_chunks.iter() {
+                if Instant::now() >= deadline {
+                    break;
+                }
+
+                // only allow a single part of an atom in each target chunk
+                if constructed
+                    .atoms(0)
+                    .iter()
+                    .any(|c| c.atom_id == atom.atom_id)
+                {
+                    continue;
+                }
+
+                buffer.add(&atom.samples);
+                let error = self
+                    .error_calc
+                    .weighted_error(&target, &buffer)
+                    .context("failed to calculate error")?;
+                if error < best_error {
+                    best_error = error;
+                    best_atom_chunk = Some(atom);
+                }
+                buffer.subtract(&atom.samples);
+            }
+
+            match best_atom_chunk {
+                Some(chunk) => {
+                    buffer.add(&chunk.samples);
+                    constructed.add_atom(0, chunk.clone());
+                    cur_error = best_error;
+                }
+                None => break,
+            }
+        }
+
+        Ok(buffer.to_vec())
+    }
+}
@@ -0,0 +1,30 @@
+use std::collections::HashSet;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use wordle_bot::{absurdle::play_adversarial, load_word_list, ScoreMatrix};
+
+const ANSWER_FILE: &str = "data/wordle-answers-alphabetical.txt";
+const GUESS_FILE: &str = "data/wordle-allowed-guesses.txt";
+
+/// Plays full Absurdle games, every guess is searched over the full lists
+/// with the most answers left, so it's the worst case for each heuristic
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut answers = HashSet::new();
+    load_word_list(ANSWER_FILE, &mut answers);
+    let mut guesses = HashSet::new();
+    load_word_list(GUESS_FILE, &mut guesses);
+    let matrix = ScoreMatrix::<5>::new(&guesses, &answers);
+
+    let mut group = c.benchmark_group("absurdle");
+    group.sample_size(10);
+    group.bench_function("expected remaining", |b| {
+        b.iter(|| play_adversarial(&matrix, ScoreMatrix::find_best_guess, 20))
+    });
+    group.bench_function("minimax", |b| {
+        b.iter(|| play_adversarial(&matrix, ScoreMatrix::find_minimax_guess, 20))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
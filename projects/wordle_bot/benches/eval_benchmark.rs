@@ -1,17 +1,27 @@
 use std::collections::HashSet;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use wordle_bot::{evaluate_guess, load_word_list};
+use wordle_bot::{evaluate_guess, load_word_list, ScoreMatrix};
 
 const ANSWER_FILE: &str = "data/wordle-answers-alphabetical.txt";
+const GUESS_FILE: &str = "data/wordle-allowed-guesses.txt";
 
 fn criterion_benchmark(c: &mut Criterion) {
     let mut answers = HashSet::new();
     load_word_list(ANSWER_FILE, &mut answers);
+    let mut guesses = HashSet::new();
+    load_word_list(GUESS_FILE, &mut guesses);
 
     c.bench_function("eval crane", |b| {
         b.iter(|| evaluate_guess(black_box(&['c', 'r', 'a', 'n', 'e']), &answers))
     });
+
+    let matrix = ScoreMatrix::new(&guesses, &answers);
+    let crane = matrix.guess_index(&['c', 'r', 'a', 'n', 'e']).unwrap();
+    let all_answers = matrix.all_answers();
+    c.bench_function("eval crane matrix", |b| {
+        b.iter(|| matrix.evaluate_guess(black_box(crane), &all_answers))
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
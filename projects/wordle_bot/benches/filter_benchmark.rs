@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use wordle_bot::{
+    filter_answers_hashset, filter_answers_vec, get_all_scores, load_word_list, LetterState,
+};
+
+const ANSWER_FILE: &str = "data/wordle-answers-alphabetical.txt";
+
+/// Sizes of the answer set from the first guess to the last few
+const SIZES: [usize; 6] = [5, 20, 50, 100, 500, 2315];
+
+type Filter = fn(&[char; 5], [LetterState; 5], &HashSet<[char; 5]>) -> HashSet<[char; 5]>;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut all_answers = HashSet::new();
+    load_word_list(ANSWER_FILE, &mut all_answers);
+    let mut sorted = all_answers.into_iter().collect::<Vec<_>>();
+    sorted.sort();
+
+    let guess = ['c', 'r', 'a', 'n', 'e'];
+    let scores = get_all_scores();
+    let filters: [(&str, Filter); 2] = [
+        ("vec", filter_answers_vec),
+        ("hashset", filter_answers_hashset),
+    ];
+
+    let mut group = c.benchmark_group("filter answers");
+    for size in SIZES {
+        // spread over the alphabet, so the subset isn't all one starting letter
+        let step = (sorted.len() / size).max(1);
+        let answers = sorted
+            .iter()
+            .step_by(step)
+            .take(size)
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        // evaluate_guess filters with every score, so time all of them
+        for (name, filter) in filters {
+            group.bench_with_input(BenchmarkId::new(name, size), &answers, |b, answers| {
+                b.iter(|| {
+                    for score in &scores {
+                        black_box(filter(black_box(&guess), *score, answers));
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
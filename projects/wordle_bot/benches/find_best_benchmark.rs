@@ -1,7 +1,9 @@
 use std::collections::HashSet;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use wordle_bot::{filter_answers, find_best_guess, load_word_list, LetterState};
+use wordle_bot::{
+    filter_answers, find_best_guess, load_word_list, AnswerSet, LetterState, ScoreMatrix,
+};
 
 const ANSWER_FILE: &str = "data/wordle-answers-alphabetical.txt";
 const GUESS_FILE: &str = "data/wordle-allowed-guesses.txt";
@@ -29,6 +31,15 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("find best guess", |b| {
         b.iter(|| find_best_guess(black_box(&answers), &guesses))
     });
+
+    let matrix = ScoreMatrix::new(&guesses, &answers);
+    let mut answer_set = AnswerSet::new(matrix.answers().len());
+    for answer in &answers {
+        answer_set.insert(matrix.answer_index(answer).unwrap());
+    }
+    c.bench_function("find best guess matrix", |b| {
+        b.iter(|| matrix.find_best_guess(black_box(&answer_set)))
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,192 @@
+use std::cmp::Reverse;
+
+use crate::{
+    num_scores, pack_score, unpack_score, AnswerSet, Heuristic, LetterState, PackedScore,
+    ScoreMatrix,
+};
+
+/// An Absurdle game, where the answer isn't picked until it has to be
+///
+/// Each guess gets whichever score leaves the most answers, so the answer
+/// keeps moving away from the guesses. Ties go to the score with the fewest
+/// greens, then the fewest yellows. The only way to win is to guess the
+/// last answer left.
+pub struct AdversarialGame<'a, const N: usize> {
+    matrix: &'a ScoreMatrix<N>,
+    answers: AnswerSet,
+    played: Vec<(usize, PackedScore)>,
+}
+
+impl<'a, const N: usize> AdversarialGame<'a, N> {
+    /// Starts with every answer in the matrix possible
+    pub fn new(matrix: &'a ScoreMatrix<N>) -> Self {
+        return Self {
+            matrix,
+            answers: matrix.all_answers(),
+            played: Vec::new(),
+        };
+    }
+
+    /// Answers that are consistent with every score so far
+    pub fn answers(&self) -> &AnswerSet {
+        &self.answers
+    }
+
+    /// Guess indexes and the scores they got, in order
+    pub fn played(&self) -> &[(usize, PackedScore)] {
+        &self.played
+    }
+
+    pub fn is_solved(&self) -> bool {
+        return self.played.last().map(|(_, score)| *score)
+            == Some(pack_score(&[LetterState::Green; N]));
+    }
+
+    /// Scores a guess index, keeping the answers that get the chosen score
+    pub fn guess(&mut self, guess: usize) -> PackedScore {
+        let score = adversarial_score(self.matrix, guess, &self.answers);
+        self.answers = self.matrix.filter_answers(guess, score, &self.answers);
+        self.played.push((guess, score));
+        return score;
+    }
+}
+
+/// Returns the score the adversary gives `guess`, see [`AdversarialGame`]
+pub fn adversarial_score<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    guess: usize,
+    answers: &AnswerSet,
+) -> PackedScore {
+    let mut counts = vec![0; num_scores::<N>()];
+    for answer in answers.iter() {
+        counts[matrix.score(guess, answer) as usize] += 1;
+    }
+
+    let (score, _) = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .max_by_key(|(score, count)| {
+            let score = unpack_score::<N>(*score as PackedScore);
+            let greens = score.iter().filter(|s| **s == LetterState::Green).count();
+            let yellows = score.iter().filter(|s| **s == LetterState::Yellow).count();
+            (**count, Reverse(greens), Reverse(yellows))
+        })
+        .unwrap();
+    return score as PackedScore;
+}
+
+/// Plays an adversarial game with guesses from `heuristic`, returning the
+/// guesses and their scores
+///
+/// Stops after `max_guesses` if it hasn't been solved, a heuristic that
+/// guesses words that can't split the answers would never finish.
+pub fn play_adversarial<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    heuristic: Heuristic<N>,
+    max_guesses: usize,
+) -> Vec<(usize, PackedScore)> {
+    let mut game = AdversarialGame::new(matrix);
+    while !game.is_solved() && game.played().len() < max_guesses {
+        let guess = heuristic(matrix, game.answers());
+        game.guess(guess);
+    }
+    return game.played;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        absurdle::{adversarial_score, play_adversarial, AdversarialGame},
+        pack_score, score_guess, LetterState, ScoreMatrix,
+    };
+
+    /// Returns char array from str
+    fn to_chars(s: &str) -> [char; 5] {
+        let mut chars = ['a'; 5];
+        for i in 0..5 {
+            chars[i] = s.chars().nth(i).unwrap();
+        }
+
+        return chars;
+    }
+
+    const ANSWERS: [&str; 8] = [
+        "warby", "wordy", "wormy", "wryly", "crane", "nodes", "foods", "robin",
+    ];
+
+    fn matrix() -> ScoreMatrix<5> {
+        let answers = HashSet::from_iter(ANSWERS.map(to_chars));
+        let guesses = HashSet::from_iter(["weary", "digit", "xxxxx"].map(to_chars));
+        return ScoreMatrix::new(&guesses, &answers);
+    }
+
+    #[test]
+    fn test_adversarial_score() {
+        let matrix = matrix();
+        let all_answers = matrix.all_answers();
+
+        // nothing in common with any answer
+        let xxxxx = matrix.guess_index(&to_chars("xxxxx")).unwrap();
+        assert_eq!(
+            adversarial_score(&matrix, xxxxx, &all_answers),
+            pack_score(&[LetterState::Gray; 5])
+        );
+
+        // The biggest group of answers with the same score is kept
+        let weary = matrix.guess_index(&to_chars("weary")).unwrap();
+        let score = adversarial_score(&matrix, weary, &all_answers);
+        let kept = matrix.filter_answers(weary, score, &all_answers).len();
+        for answer in matrix.answers() {
+            let other = pack_score(&score_guess(&to_chars("weary"), answer));
+            assert!(matrix.filter_answers(weary, other, &all_answers).len() <= kept);
+        }
+    }
+
+    #[test]
+    fn test_adversarial_game() {
+        let matrix = matrix();
+        let mut game = AdversarialGame::new(&matrix);
+
+        // Guessing an answer while others are left never wins
+        let crane = matrix.guess_index(&to_chars("crane")).unwrap();
+        assert_ne!(game.guess(crane), pack_score(&[LetterState::Green; 5]));
+        assert!(!game.is_solved());
+        assert!(game.answers().len() < ANSWERS.len());
+
+        while game.answers().len() > 1 {
+            let guess = matrix.find_best_guess(game.answers());
+            game.guess(guess);
+        }
+        assert!(!game.is_solved());
+        let last = matrix.answer_guess(game.answers().iter().next().unwrap());
+        game.guess(last);
+        assert!(game.is_solved());
+    }
+
+    #[test]
+    fn test_play_adversarial() {
+        let matrix = matrix();
+        let expected = play_adversarial(&matrix, ScoreMatrix::find_best_guess, 20);
+        let minimax = play_adversarial(&matrix, ScoreMatrix::find_minimax_guess, 20);
+        for played in [&expected, &minimax] {
+            assert_eq!(
+                played.last().unwrap().1,
+                pack_score(&[LetterState::Green; 5])
+            );
+        }
+        // Same lists, same game
+        assert_eq!(
+            minimax,
+            play_adversarial(&matrix, ScoreMatrix::find_minimax_guess, 20)
+        );
+
+        // Not enough guesses to finish
+        assert_eq!(
+            play_adversarial(&matrix, ScoreMatrix::find_best_guess, 1).len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::{collections::HashSet, fs};
+
+use crate::MAX_WORD_LENGTH;
+
+const ANSWER_FILE: &str = "data/wordle-answers-alphabetical.txt";
+const GUESS_FILE: &str = "data/wordle-allowed-guesses.txt";
+
+type Words<const N: usize> = HashSet<[char; N]>;
+
+/// Letters that can appear in words
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+    letters: Vec<char>,
+}
+
+impl Alphabet {
+    /// Letters are lowercased and repeats are ignored
+    pub fn new(letters: &str) -> Self {
+        let mut unique = Vec::new();
+        for c in letters.to_lowercase().chars() {
+            if !c.is_whitespace() && !unique.contains(&c) {
+                unique.push(c);
+            }
+        }
+        return Self { letters: unique };
+    }
+
+    /// `a` to `z`
+    pub fn english() -> Self {
+        return Self::new("abcdefghijklmnopqrstuvwxyz");
+    }
+
+    /// `0` to `9`, for number puzzles like Primel
+    pub fn digits() -> Self {
+        return Self::new("0123456789");
+    }
+
+    pub fn letters(&self) -> &[char] {
+        &self.letters
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        return self.letters.contains(&c);
+    }
+
+    /// Returns the lowercased word, or `None` if it isn't `N` letters from
+    /// the alphabet
+    pub fn parse_word<const N: usize>(&self, s: &str) -> Option<[char; N]> {
+        let chars = s.trim().to_lowercase().chars().collect::<Vec<char>>();
+        if !chars.iter().all(|c| self.contains(*c)) {
+            return None;
+        }
+        return <[char; N]>::try_from(chars).ok();
+    }
+}
+
+/// Word length, alphabet and word lists of a puzzle
+///
+/// Read from files of `key = value` lines, where `#` starts a comment:
+///
+/// ```text
+/// # Primel, guess a 5 digit prime
+/// length = 5
+/// alphabet = 0123456789
+/// answers = data/primel-primes.txt
+/// ```
+///
+/// `guesses` is an optional list of words that can be guessed but are never
+/// the answer and `alphabet` defaults to `a` to `z`. Paths are relative to
+/// the working directory, like the default Wordle lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PuzzleConfig {
+    pub length: usize,
+    pub alphabet: Alphabet,
+    pub answers: String,
+    pub guesses: Option<String>,
+}
+
+impl PuzzleConfig {
+    /// The original 5 letter game with the lists in `data/`
+    pub fn wordle() -> Self {
+        return Self {
+            length: 5,
+            alphabet: Alphabet::english(),
+            answers: ANSWER_FILE.to_string(),
+            guesses: Some(GUESS_FILE.to_string()),
+        };
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut length = None;
+        let mut alphabet = Alphabet::english();
+        let mut answers = None;
+        let mut guesses = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", i + 1))?;
+            let value = value.trim();
+            match key.trim() {
+                "length" => {
+                    length = Some(
+                        value
+                            .parse::<usize>()
+                            .map_err(|_| format!("line {}: invalid length {}", i + 1, value))?,
+                    )
+                }
+                "alphabet" => alphabet = Alphabet::new(value),
+                "answers" => answers = Some(value.to_string()),
+                "guesses" => guesses = Some(value.to_string()),
+                key => return Err(format!("line {}: unknown key {}", i + 1, key)),
+            }
+        }
+
+        let length = length.ok_or("missing length")?;
+        if length == 0 || length > MAX_WORD_LENGTH {
+            return Err(format!(
+                "length must be between 1 and {}, got {}",
+                MAX_WORD_LENGTH, length
+            ));
+        }
+        if alphabet.letters().is_empty() {
+            return Err("alphabet is empty".to_string());
+        }
+
+        return Ok(Self {
+            length,
+            alphabet,
+            answers: answers.ok_or("missing answers")?,
+            guesses,
+        });
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        return Self::parse(&s).map_err(|e| format!("{}: {}", path, e));
+    }
+
+    /// Returns the answers and the words that can be guessed, which always
+    /// include the answers
+    ///
+    /// Unlike [`crate::load_word_list`], words that are the wrong length or
+    /// use letters outside the alphabet are an error, since they usually
+    /// mean the config doesn't match its lists.
+    pub fn load_words<const N: usize>(&self) -> Result<(Words<N>, Words<N>), String> {
+        if N != self.length {
+            return Err(format!(
+                "puzzle has {} letter words, not {}",
+                self.length, N
+            ));
+        }
+
+        let answers = read_words(&self.answers, &self.alphabet)?;
+        let mut guesses = match &self.guesses {
+            Some(path) => read_words(path, &self.alphabet)?,
+            None => HashSet::new(),
+        };
+        guesses.extend(answers.iter());
+        return Ok((answers, guesses));
+    }
+}
+
+fn read_words<const N: usize>(path: &str, alphabet: &Alphabet) -> Result<Words<N>, String> {
+    let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut words = HashSet::new();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let word = alphabet.parse_word(line).ok_or_else(|| {
+            format!(
+                "{} line {}: {} isn't {} letters from the alphabet",
+                path,
+                i + 1,
+                line.trim(),
+                N
+            )
+        })?;
+        words.insert(word);
+    }
+    return Ok(words);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, env, fs};
+
+    use crate::{
+        config::{Alphabet, PuzzleConfig},
+        score_guess, LetterState, ScoreMatrix,
+    };
+
+    #[test]
+    fn test_alphabet() {
+        let alphabet = Alphabet::new("abcABC ä");
+        assert_eq!(alphabet.letters(), &['a', 'b', 'c', 'ä']);
+        assert_eq!(alphabet.parse_word(" CäB\n"), Some(['c', 'ä', 'b']));
+        assert_eq!(alphabet.parse_word::<3>("cab d"), None);
+        assert_eq!(alphabet.parse_word::<3>("abcd"), None);
+        assert_eq!(alphabet.parse_word::<3>("abx"), None);
+        assert_eq!(
+            Alphabet::digits().parse_word("10007"),
+            Some(['1', '0', '0', '0', '7'])
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = PuzzleConfig::parse(
+            "# Primel
+            length = 5
+            alphabet = 0123456789 # digits
+            answers = primes.txt",
+        )
+        .unwrap();
+        assert_eq!(config.length, 5);
+        assert_eq!(config.alphabet, Alphabet::digits());
+        assert_eq!(config.answers, "primes.txt");
+        assert_eq!(config.guesses, None);
+
+        let config = PuzzleConfig::parse("length=6\nanswers=a.txt\nguesses=b.txt").unwrap();
+        assert_eq!(config.alphabet, Alphabet::english());
+        assert_eq!(config.guesses.as_deref(), Some("b.txt"));
+
+        for invalid in [
+            "answers = a.txt",
+            "length = 5",
+            "length = five\nanswers = a.txt",
+            "length = 11\nanswers = a.txt",
+            "length = 5\nanswers = a.txt\ncolor = red",
+            "length = 5\nanswers = a.txt\nalphabet =",
+            "length 5",
+        ] {
+            assert!(PuzzleConfig::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_load_words() {
+        let dir = env::temp_dir();
+        let answers_path = dir.join(format!("wordle-answers-{}.txt", std::process::id()));
+        let guesses_path = dir.join(format!("wordle-guesses-{}.txt", std::process::id()));
+        fs::write(&answers_path, "skönt\nsyster\n\n").unwrap();
+        fs::write(&guesses_path, "sväng\nsylten\n").unwrap();
+
+        let mut config = PuzzleConfig {
+            length: 6,
+            alphabet: Alphabet::new("abcdefghijklmnopqrstuvwxyzåäö"),
+            answers: answers_path.to_str().unwrap().to_string(),
+            guesses: Some(guesses_path.to_str().unwrap().to_string()),
+        };
+        // skönt and sväng are 5 letters
+        assert!(config.load_words::<6>().is_err());
+        assert!(config.load_words::<5>().is_err());
+
+        config.length = 5;
+        fs::write(&answers_path, "skönt\nsväng\n").unwrap();
+        fs::write(&guesses_path, "SKÄLL\nsvamp\n").unwrap();
+        let (answers, guesses) = config.load_words::<5>().unwrap();
+        assert_eq!(answers.len(), 2);
+        assert_eq!(guesses.len(), 4);
+        assert!(guesses.contains(&['s', 'k', 'ä', 'l', 'l']));
+
+        config.alphabet = Alphabet::english();
+        assert!(config.load_words::<5>().is_err());
+
+        fs::remove_file(answers_path).unwrap();
+        fs::remove_file(guesses_path).unwrap();
+    }
+
+    #[test]
+    fn test_primel() {
+        let primes = [
+            "10007", "10009", "10037", "10039", "11939", "13297", "99991",
+        ];
+        let answers = primes
+            .iter()
+            .map(|p| Alphabet::digits().parse_word(p).unwrap())
+            .collect::<HashSet<[char; 5]>>();
+
+        assert_eq!(
+            score_guess(&['1', '0', '0', '0', '9'], &['1', '0', '0', '3', '9']),
+            [
+                LetterState::Green,
+                LetterState::Green,
+                LetterState::Green,
+                LetterState::Gray,
+                LetterState::Green
+            ]
+        );
+
+        let matrix = ScoreMatrix::new(&answers, &answers);
+        let all_answers = matrix.all_answers();
+        let best = matrix.find_best_guess(&all_answers);
+        assert!(matrix.evaluate_guess(best, &all_answers) < answers.len());
+    }
+
+    #[test]
+    fn test_six_letters() {
+        let answers = ["planet", "plants", "planes", "plains", "glance", "cancel"]
+            .iter()
+            .map(|w| Alphabet::english().parse_word(w).unwrap())
+            .collect::<HashSet<[char; 6]>>();
+        let matrix = ScoreMatrix::new(&answers, &answers);
+        assert_eq!(matrix.answers().len(), 6);
+
+        // 3^6 scores don't fit in a byte
+        let glance = matrix.guess_index(&['g', 'l', 'a', 'n', 'c', 'e']).unwrap();
+        for a in 0..matrix.answers().len() {
+            assert_eq!(
+                crate::unpack_score(matrix.score(glance, a)),
+                score_guess(&matrix.guesses()[glance], &matrix.answers()[a])
+            );
+        }
+    }
+}
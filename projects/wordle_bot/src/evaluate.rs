@@ -0,0 +1,204 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+
+use crate::{
+    get_all_scores, pack_score, play_game, tree::score_to_string, unpack_score, Heuristic,
+    LetterState, ScoreMatrix,
+};
+
+/// Guesses and scores that solved one answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult<const N: usize> {
+    pub answer: [char; N],
+    pub played: Vec<([char; N], [LetterState; N])>,
+}
+
+/// Every answer played with one heuristic, see [`evaluate`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation<const N: usize> {
+    pub heuristic: String,
+    pub games: Vec<GameResult<N>>,
+}
+
+/// Plays every answer in the matrix, in order, with guesses from `heuristic`
+///
+/// The first guess and the second guess for each score are the same in
+/// every game, so they're worked out once up front.
+pub fn evaluate<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    name: &str,
+    heuristic: Heuristic<N>,
+) -> Evaluation<N> {
+    let all_answers = matrix.all_answers();
+    let starting_guess = heuristic(matrix, &all_answers);
+
+    let mut second_guess_lookup = HashMap::new();
+    for score in get_all_scores::<N>() {
+        let score = pack_score(&score);
+        let filtered_answers = matrix.filter_answers(starting_guess, score, &all_answers);
+
+        if filtered_answers.is_empty() {
+            // impossible state, don't need to pre-compute
+            continue;
+        }
+
+        second_guess_lookup.insert(score, heuristic(matrix, &filtered_answers));
+    }
+
+    let mut games = Vec::new();
+    for answer in all_answers.iter() {
+        let played = play_game(
+            answer,
+            starting_guess,
+            matrix,
+            &second_guess_lookup,
+            heuristic,
+        );
+        games.push(GameResult {
+            answer: matrix.answers()[answer],
+            played: played
+                .into_iter()
+                .map(|(guess, score)| (matrix.guesses()[guess], unpack_score(score)))
+                .collect(),
+        });
+    }
+
+    return Evaluation {
+        heuristic: name.to_string(),
+        games,
+    };
+}
+
+impl<const N: usize> Evaluation<N> {
+    /// Number of answers solved in each number of guesses
+    pub fn histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for game in &self.games {
+            *histogram.entry(game.played.len()).or_insert(0) += 1;
+        }
+        return histogram;
+    }
+
+    pub fn average_guesses(&self) -> f64 {
+        let total: usize = self.games.iter().map(|g| g.played.len()).sum();
+        return total as f64 / self.games.len() as f64;
+    }
+
+    /// Writes the results as JSON for plotting
+    ///
+    /// ```text
+    /// {"heuristic":"entropy","average":3.5,"histogram":{"3":2,"4":1},
+    ///  "games":[{"answer":"wordy","guesses":["weary","wordy"],"scores":["GBBYG","GGGGG"]}]}
+    /// ```
+    ///
+    /// Scores are written like [`score_to_string`]. Words are written as is,
+    /// like in [`crate::tree::DecisionTree::to_json`].
+    pub fn to_json(&self) -> String {
+        let mut s = String::new();
+        s.push_str(&format!(
+            "{{\"heuristic\":\"{}\",\"average\":{},\"histogram\":{{",
+            self.heuristic,
+            self.average_guesses()
+        ));
+        let histogram = self
+            .histogram()
+            .iter()
+            .map(|(guesses, count)| format!("\"{}\":{}", guesses, count))
+            .collect::<Vec<_>>();
+        s.push_str(&histogram.join(","));
+        s.push_str("},\"games\":[");
+
+        for (i, game) in self.games.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
+            }
+            let guesses = game
+                .played
+                .iter()
+                .map(|(guess, _)| format!("\"{}\"", guess.iter().collect::<String>()))
+                .collect::<Vec<_>>();
+            let scores = game
+                .played
+                .iter()
+                .map(|(_, score)| format!("\"{}\"", score_to_string(score)))
+                .collect::<Vec<_>>();
+            s.push_str(&format!(
+                "{{\"answer\":\"{}\",\"guesses\":[{}],\"scores\":[{}]}}",
+                game.answer.iter().collect::<String>(),
+                guesses.join(","),
+                scores.join(",")
+            ));
+        }
+        s.push_str("]}");
+        return s;
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        return fs::write(path, self.to_json()).map_err(|e| e.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{evaluate::evaluate, LetterState, ScoreMatrix};
+
+    /// Returns char array from str
+    fn to_chars(s: &str) -> [char; 5] {
+        let mut chars = ['a'; 5];
+        for i in 0..5 {
+            chars[i] = s.chars().nth(i).unwrap();
+        }
+
+        return chars;
+    }
+
+    const ANSWERS: [&str; 8] = [
+        "warby", "wordy", "wormy", "wryly", "crane", "nodes", "foods", "robin",
+    ];
+
+    #[test]
+    fn test_evaluate() {
+        let answers = HashSet::from_iter(ANSWERS.map(to_chars));
+        let guesses = HashSet::from_iter(["weary", "digit", "xxxxx"].map(to_chars));
+        let matrix = ScoreMatrix::new(&guesses, &answers);
+
+        for heuristic in [
+            ScoreMatrix::find_best_guess,
+            ScoreMatrix::find_max_entropy_guess,
+            ScoreMatrix::find_minimax_guess,
+        ] {
+            let evaluation = evaluate(&matrix, "test", heuristic);
+            assert_eq!(evaluation.games.len(), ANSWERS.len());
+            for game in &evaluation.games {
+                let (guess, score) = game.played.last().unwrap();
+                assert_eq!(*guess, game.answer);
+                assert_eq!(*score, [LetterState::Green; 5]);
+                // Every game starts the same
+                assert_eq!(game.played[0].0, evaluation.games[0].played[0].0);
+            }
+            assert_eq!(
+                evaluation.histogram().values().sum::<usize>(),
+                ANSWERS.len()
+            );
+            assert!(evaluation.average_guesses() >= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_evaluation_json() {
+        let answers = HashSet::from_iter(["wordy", "wormy"].map(to_chars));
+        let matrix = ScoreMatrix::new(&answers, &answers);
+        let evaluation = evaluate(&matrix, "expected", ScoreMatrix::find_best_guess);
+        // With 2 answers the first one is guessed
+        assert_eq!(
+            evaluation.to_json(),
+            "{\"heuristic\":\"expected\",\"average\":1.5,\"histogram\":{\"1\":1,\"2\":1},\
+            \"games\":[{\"answer\":\"wordy\",\"guesses\":[\"wordy\"],\"scores\":[\"GGGGG\"]},\
+            {\"answer\":\"wormy\",\"guesses\":[\"wordy\",\"wormy\"],\"scores\":[\"GGGBG\",\"GGGGG\"]}]}"
+        );
+    }
+}
@@ -1,10 +1,17 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
     fs::File,
     io::{self, BufRead},
     path::Path,
 };
 
+pub mod absurdle;
+pub mod config;
+pub mod evaluate;
+pub mod multi;
+pub mod tree;
+
 #[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
 pub enum LetterState {
     /// Letter is in the right position
@@ -16,72 +23,116 @@ pub enum LetterState {
 }
 
 /// Returns Green, Yellow, Gray for a given guess and answer
-pub fn score_guess(guess: &[char; 5], answer: &[char; 5]) -> [LetterState; 5] {
-    let mut score = [LetterState::Gray; 5];
-    let mut unmatch_chars = HashMap::new();
+///
+/// Works for any word length and alphabet. Each answer letter that isn't
+/// matched by a green can make one guess letter yellow, earliest first.
+pub fn score_guess<const N: usize>(guess: &[char; N], answer: &[char; N]) -> [LetterState; N] {
+    let mut score = [LetterState::Gray; N];
+    let mut unmatched = [true; N];
 
-    for i in 0..5 {
-        let answer_char = answer[i];
-        if guess[i] == answer_char {
+    for i in 0..N {
+        if guess[i] == answer[i] {
             score[i] = LetterState::Green;
-        } else {
-            let count = *unmatch_chars.get(&answer_char).unwrap_or(&0);
-            unmatch_chars.insert(answer_char, count + 1);
+            unmatched[i] = false;
         }
     }
 
-    for i in 0..5 {
+    for i in 0..N {
         if score[i] == LetterState::Green {
             continue; // skip already matched chars
         }
 
-        let guess_char = guess[i];
-        let count = *unmatch_chars.get(&guess_char).unwrap_or(&0);
-        if count > 0 {
+        if let Some(j) = (0..N).find(|&j| unmatched[j] && answer[j] == guess[i]) {
             score[i] = LetterState::Yellow;
-            unmatch_chars.insert(guess_char, count - 1);
+            unmatched[j] = false;
         }
     }
 
     return score;
 }
 
-/// Returns the number of guesses to get the word
-pub fn play_game(
-    answer: &[char; 5],
-    start_guess: &[char; 5],
-    answers: &HashSet<[char; 5]>,
-    guesses: &HashSet<[char; 5]>,
-    second_guess_lookup: &HashMap<[LetterState; 5], [char; 5]>,
-) -> u32 {
-    let mut answers = answers.clone();
-    let mut num_rounds = 0;
-    let mut score = [LetterState::Gray; 5];
-
-    while score != [LetterState::Green; 5] {
-        let guess = match num_rounds {
-            0 => *start_guess,
-            1 => second_guess_lookup.get(&score).unwrap().clone(),
-            _ => find_best_guess(&answers, guesses),
+/// Longest word that can be scored, packed scores need to fit in a
+/// [`PackedScore`]
+pub const MAX_WORD_LENGTH: usize = 10;
+
+/// Score packed into a base 3 number by [`pack_score`]
+pub type PackedScore = u16;
+
+/// Number of possible scores for words of length `N`, 3^N
+pub fn num_scores<const N: usize>() -> usize {
+    return 3usize.pow(N as u32);
+}
+
+/// Packs a score into a [`PackedScore`], treating it as a base 3 number
+///
+/// All green packs to 0. Every packed score is less than [`num_scores`].
+pub fn pack_score<const N: usize>(score: &[LetterState; N]) -> PackedScore {
+    let mut packed = 0;
+    for s in score {
+        packed = packed * 3
+            + match s {
+                LetterState::Green => 0,
+                LetterState::Yellow => 1,
+                LetterState::Gray => 2,
+            };
+    }
+    return packed;
+}
+
+pub fn unpack_score<const N: usize>(packed: PackedScore) -> [LetterState; N] {
+    let mut score = [LetterState::Green; N];
+    let mut packed = packed;
+    for i in (0..N).rev() {
+        score[i] = match packed % 3 {
+            0 => LetterState::Green,
+            1 => LetterState::Yellow,
+            _ => LetterState::Gray,
         };
-        score = score_guess(&guess, answer);
-        answers = filter_answers(&guess, score, &answers);
-        println!(
-            "{}: {:?}, {} answers remain",
-            guess.into_iter().collect::<String>(),
-            &score,
-            answers.len()
-        );
-        num_rounds += 1;
+        packed /= 3;
+    }
+    return score;
+}
+
+/// Plays a game, returning each guess and the packed score it got
+///
+/// `answer` indexes the matrix answers, `start_guess` and the lookup values
+/// index its guesses and the lookup is keyed by packed scores. Guesses after
+/// the second, or a second guess missing from the lookup, come from
+/// `heuristic`.
+pub fn play_game<const N: usize>(
+    answer: usize,
+    start_guess: usize,
+    matrix: &ScoreMatrix<N>,
+    second_guess_lookup: &HashMap<PackedScore, usize>,
+    heuristic: Heuristic<N>,
+) -> Vec<(usize, PackedScore)> {
+    let mut answers = matrix.all_answers();
+    let mut played = Vec::new();
+    let mut score = PackedScore::MAX;
+
+    while score != pack_score(&[LetterState::Green; N]) {
+        let guess = match played.len() {
+            0 => start_guess,
+            1 => match second_guess_lookup.get(&score) {
+                Some(guess) => *guess,
+                None => heuristic(matrix, &answers),
+            },
+            _ => heuristic(matrix, &answers),
+        };
+        score = matrix.score(guess, answer);
+        answers = matrix.filter_answers(guess, score, &answers);
+        played.push((guess, score));
     }
 
-    return num_rounds;
+    return played;
 }
 
-pub fn find_best_guess(answers: &HashSet<[char; 5]>, guesses: &HashSet<[char; 5]>) -> [char; 5] {
+pub fn find_best_guess<const N: usize>(
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) -> [char; N] {
     let mut best_guess_score = usize::MAX;
-    let mut best_guess = ['x'; 5];
-
+    let mut best_guess = ['x'; N];
     // Early exit if only 2 or fewer possible answers
     // Randomly choose 1
     if answers.len() <= 2 {
@@ -103,25 +154,29 @@ pub fn find_best_guess(answers: &HashSet<[char; 5]>, guesses: &HashSet<[char; 5]
     return best_guess;
 }
 
-pub fn filter_answers(
-    guess: &[char; 5],
-    score: [LetterState; 5],
-    answers: &HashSet<[char; 5]>,
-) -> HashSet<[char; 5]> {
-    // return filter_answers_hashset(guess, score, answers);
+/// Returns the answers that are still possible after `guess` got `score`
+///
+/// Uses [`filter_answers_vec`], in `benches/filter_benchmark.rs` it's 5-10x
+/// faster than [`filter_answers_hashset`] at every answer set size from 5 words
+/// to the full list, so there's no size where switching pays off.
+pub fn filter_answers<const N: usize>(
+    guess: &[char; N],
+    score: [LetterState; N],
+    answers: &HashSet<[char; N]>,
+) -> HashSet<[char; N]> {
     return filter_answers_vec(guess, score, answers);
 }
 
-fn filter_green<'a>(
-    guess: &[char; 5],
-    score: [LetterState; 5],
-    answers: &'a HashSet<[char; 5]>,
-) -> Vec<&'a [char; 5]> {
+fn filter_green<'a, const N: usize>(
+    guess: &[char; N],
+    score: [LetterState; N],
+    answers: &'a HashSet<[char; N]>,
+) -> Vec<&'a [char; N]> {
     let mut filtered = Vec::with_capacity(answers.len());
     // Filter letters where they should be, e.g. Green
     for answer in answers {
         let mut is_match = true;
-        for i in 0..5 {
+        for i in 0..N {
             if score[i] == LetterState::Green {
                 let g = guess[i];
                 let a = answer[i];
@@ -139,7 +194,7 @@ fn filter_green<'a>(
     // Filter letters where they shouldn't be, e.g. Gray and not Green
     for answer in filtered {
         let mut is_match = true;
-        for i in 0..5 {
+        for i in 0..N {
             if score[i] == LetterState::Yellow {
                 let g = guess[i];
                 let a = answer[i];
@@ -155,45 +210,21 @@ fn filter_green<'a>(
     return new_filtered;
 }
 
-fn filter_answers_vec(
-    guess: &[char; 5],
-    score: [LetterState; 5],
-    answers: &HashSet<[char; 5]>,
-) -> HashSet<[char; 5]> {
+pub fn filter_answers_vec<const N: usize>(
+    guess: &[char; N],
+    score: [LetterState; N],
+    answers: &HashSet<[char; N]>,
+) -> HashSet<[char; N]> {
     let filtered = filter_green(guess, score, answers);
     let mut new_filtered = Vec::with_capacity(filtered.len());
 
     // Filter by char counts
-    let mut known_char_counts = [0; 26];
-    let mut is_absent = [false; 26];
-    for i in 0..5 {
-        let g = guess[i];
-        let index = get_index(g);
-        match score[i] {
-            LetterState::Yellow | LetterState::Green => known_char_counts[index] += 1,
-            LetterState::Gray => is_absent[index] = true,
-        }
-    }
-
+    let letter_counts = get_letter_counts(guess, &score);
     for answer in filtered {
-        let mut char_count = [0; 26];
-        for &a in answer {
-            increment_count(a, &mut char_count);
-        }
-
-        let mut is_match = true;
-        for i in 0..26 {
-            is_match = is_match
-                && !((char_count[i] < known_char_counts[i])
-                // Need to check for known_char_counts being zero to handle double letters
-                || (char_count[i] > 0 && is_absent[i] && known_char_counts[i] == 0));
-
-            if !is_match {
-                break;
-            }
-        }
-
-        if is_match {
+        if letter_counts
+            .iter()
+            .all(|(&c, &(known, is_absent))| is_count_match(answer, c, known, is_absent))
+        {
             new_filtered.push(answer)
         }
     }
@@ -201,18 +232,18 @@ fn filter_answers_vec(
     return HashSet::from_iter(new_filtered.into_iter().map(|s| s.clone()));
 }
 
-fn filter_answers_hashset(
-    guess: &str,
-    score: [LetterState; 5],
-    answers: &HashSet<[char; 5]>,
-) -> HashSet<[char; 5]> {
+pub fn filter_answers_hashset<const N: usize>(
+    guess: &[char; N],
+    score: [LetterState; N],
+    answers: &HashSet<[char; N]>,
+) -> HashSet<[char; N]> {
     // Start with a copy of all answers and remove ones that can't match
     let mut filtered = answers.clone();
 
     // Filter letters where they should be, e.g. Green
-    for i in 0..5 {
+    for i in 0..N {
         if score[i] == LetterState::Green {
-            let g = guess.chars().nth(i).unwrap();
+            let g = guess[i];
             for answer in answers {
                 if answer[i] != g {
                     filtered.remove(answer);
@@ -222,9 +253,9 @@ fn filter_answers_hashset(
     }
 
     // Filter letters where they shouldn't be, e.g. Gray and not Green
-    for i in 0..5 {
+    for i in 0..N {
         if score[i] == LetterState::Yellow {
-            let g = guess.chars().nth(i).unwrap();
+            let g = guess[i];
             for answer in answers {
                 if answer[i] == g {
                     filtered.remove(answer);
@@ -235,28 +266,10 @@ fn filter_answers_hashset(
 
     // Filter by char counts
     // TODO: need to account for double letters, do an optional for known char counts
-    let mut known_char_counts = [0; 26];
-    let mut is_absent = [false; 26];
-    for i in 0..5 {
-        let g = guess.chars().nth(i).unwrap();
-        let index = get_index(g);
-        match score[i] {
-            LetterState::Yellow | LetterState::Green => known_char_counts[index] += 1,
-            LetterState::Gray => is_absent[index] = true,
-        }
-    }
-
+    let letter_counts = get_letter_counts(guess, &score);
     for answer in &filtered.clone() {
-        let mut char_count = [0; 26];
-        for &a in answer {
-            increment_count(a, &mut char_count);
-        }
-
-        for i in 0..26 {
-            if (char_count[i] < known_char_counts[i])
-                // Need to check for known_char_counts being zero to handle double letters
-                || (char_count[i] > 0 && is_absent[i] && known_char_counts[i] == 0)
-            {
+        for (&c, &(known, is_absent)) in &letter_counts {
+            if !is_count_match(answer, c, known, is_absent) {
                 filtered.remove(answer);
             }
         }
@@ -265,25 +278,45 @@ fn filter_answers_hashset(
     return filtered;
 }
 
-fn increment_count(c: char, counts: &mut [usize; 26]) {
-    let index = get_index(c);
-    counts[index] += 1;
+/// Returns each letter in the guess, the number of times it was scored
+/// green or yellow and whether it was ever scored gray
+///
+/// Letters that weren't guessed don't constrain the answer, so only the
+/// guess letters are counted and any alphabet works.
+fn get_letter_counts<const N: usize>(
+    guess: &[char; N],
+    score: &[LetterState; N],
+) -> BTreeMap<char, (usize, bool)> {
+    let mut counts = BTreeMap::new();
+    for i in 0..N {
+        let (known, is_absent) = counts.entry(guess[i]).or_insert((0, false));
+        match score[i] {
+            LetterState::Yellow | LetterState::Green => *known += 1,
+            LetterState::Gray => *is_absent = true,
+        }
+    }
+    return counts;
 }
 
-fn get_index(c: char) -> usize {
-    const A_DECIMAL: usize = 97;
-    let index = c.to_ascii_lowercase() as usize - A_DECIMAL;
-    return index;
+fn is_count_match<const N: usize>(
+    answer: &[char; N],
+    c: char,
+    known: usize,
+    is_absent: bool,
+) -> bool {
+    let count = answer.iter().filter(|&&a| a == c).count();
+    // Need to check for known being zero to handle double letters
+    return count >= known && !(count > 0 && is_absent && known == 0);
 }
 
 /// Returns all possible scores
-pub fn get_all_scores() -> Vec<[LetterState; 5]> {
-    let mut cur = [LetterState::Green; 5];
+pub fn get_all_scores<const N: usize>() -> Vec<[LetterState; N]> {
+    let mut cur = [LetterState::Green; N];
     let mut scores = Vec::new();
     scores.push(cur);
 
-    while cur != [LetterState::Gray; 5] {
-        for i in 0..5 {
+    while cur != [LetterState::Gray; N] {
+        for i in 0..N {
             match cur[i] {
                 LetterState::Green => {
                     cur[i] = LetterState::Yellow;
@@ -307,9 +340,12 @@ pub fn get_all_scores() -> Vec<[LetterState; 5]> {
 ///
 /// This method can be used to iterate overall all possible guesses. The guess with the
 /// lowest expected value of remaining answers is the best guess
-pub fn evaluate_guess(guess: &[char; 5], answers: &HashSet<[char; 5]>) -> usize {
+///
+/// Works for any word, [`ScoreMatrix::evaluate_guess`] is much faster for
+/// words in its lists.
+pub fn evaluate_guess<const N: usize>(guess: &[char; N], answers: &HashSet<[char; N]>) -> usize {
     let mut expected_remaining_answers = 0;
-    for score in get_all_scores() {
+    for score in get_all_scores::<N>() {
         let remaining_answers = filter_answers(guess, score, answers);
 
         // E[] = Sum( P(# answers) * # answers )
@@ -320,17 +356,553 @@ pub fn evaluate_guess(guess: &[char; 5], answers: &HashSet<[char; 5]>) -> usize
     return (expected_remaining_answers as f64 / answers.len() as f64) as usize;
 }
 
-pub fn load_word_list(path: &str, set: &mut HashSet<[char; 5]>) {
+/// Set of answers, stored as a bit per index into [`ScoreMatrix::answers`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnswerSet {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl AnswerSet {
+    /// Returns an empty set that can hold indexes up to `capacity`
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            bits: vec![0; capacity.div_ceil(64)],
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, i: usize) {
+        if !self.contains(i) {
+            self.bits[i / 64] |= 1 << (i % 64);
+            self.len += 1;
+        }
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        return self.bits[i / 64] & (1 << (i % 64)) != 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the indexes in the set in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.bits.iter().enumerate().flat_map(|(word, bits)| {
+            let mut bits = *bits;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let i = bits.trailing_zeros() as usize;
+                bits &= bits - 1; // clear the lowest bit
+                Some(word * 64 + i)
+            })
+        });
+    }
+}
+
+/// Picks the next guess index from the answers still possible, like
+/// [`ScoreMatrix::find_best_guess`]
+pub type Heuristic<const N: usize> = fn(&ScoreMatrix<N>, &AnswerSet) -> usize;
+
+/// Packed score of every guess against every answer
+///
+/// Scoring a guess is the slow part of filtering and evaluating, so it's
+/// done once for every pair up front. Words are sorted, so indexes are
+/// stable for the same lists, and answers are always added to the guesses.
+/// Words can be up to [`MAX_WORD_LENGTH`] letters.
+pub struct ScoreMatrix<const N: usize> {
+    guesses: Vec<[char; N]>,
+    answers: Vec<[char; N]>,
+    /// Index of each answer in `guesses`
+    answer_guesses: Vec<usize>,
+    /// `scores[guess * answers.len() + answer]`
+    scores: Vec<PackedScore>,
+}
+
+impl<const N: usize> ScoreMatrix<N> {
+    pub fn new(guesses: &HashSet<[char; N]>, answers: &HashSet<[char; N]>) -> Self {
+        assert!(
+            N <= MAX_WORD_LENGTH,
+            "words can be at most {} letters",
+            MAX_WORD_LENGTH
+        );
+        let mut answers = answers.iter().copied().collect::<Vec<_>>();
+        answers.sort();
+        let mut guesses = guesses.iter().chain(&answers).copied().collect::<Vec<_>>();
+        guesses.sort();
+        guesses.dedup();
+
+        let answer_guesses = answers
+            .iter()
+            .map(|a| guesses.binary_search(a).unwrap())
+            .collect();
+        let mut scores = Vec::with_capacity(guesses.len() * answers.len());
+        for guess in &guesses {
+            for answer in &answers {
+                scores.push(pack_score(&score_guess(guess, answer)));
+            }
+        }
+
+        return Self {
+            guesses,
+            answers,
+            answer_guesses,
+            scores,
+        };
+    }
+
+    pub fn guesses(&self) -> &[[char; N]] {
+        &self.guesses
+    }
+
+    pub fn answers(&self) -> &[[char; N]] {
+        &self.answers
+    }
+
+    pub fn guess_index(&self, guess: &[char; N]) -> Option<usize> {
+        return self.guesses.binary_search(guess).ok();
+    }
+
+    pub fn answer_index(&self, answer: &[char; N]) -> Option<usize> {
+        return self.answers.binary_search(answer).ok();
+    }
+
+    /// Returns the index in the guesses of an answer
+    pub fn answer_guess(&self, answer: usize) -> usize {
+        return self.answer_guesses[answer];
+    }
+
+    /// Returns the packed score of a guess against an answer
+    #[inline]
+    pub fn score(&self, guess: usize, answer: usize) -> PackedScore {
+        return self.scores[guess * self.answers.len() + answer];
+    }
+
+    pub fn all_answers(&self) -> AnswerSet {
+        let mut set = AnswerSet::new(self.answers.len());
+        for i in 0..self.answers.len() {
+            set.insert(i);
+        }
+        return set;
+    }
+
+    /// Returns the answers that are still possible after `guess` got the
+    /// packed `score`
+    pub fn filter_answers(
+        &self,
+        guess: usize,
+        score: PackedScore,
+        answers: &AnswerSet,
+    ) -> AnswerSet {
+        let row = &self.scores[guess * self.answers.len()..(guess + 1) * self.answers.len()];
+        let mut filtered = AnswerSet::new(self.answers.len());
+        for a in answers.iter() {
+            if row[a] == score {
+                filtered.insert(a);
+            }
+        }
+        return filtered;
+    }
+
+    /// Same as [`evaluate_guess`], but counts the answers left for each score
+    /// in one pass rather than filtering once per score
+    pub fn evaluate_guess(&self, guess: usize, answers: &AnswerSet) -> usize {
+        let row = &self.scores[guess * self.answers.len()..(guess + 1) * self.answers.len()];
+        let mut counts = vec![0; num_scores::<N>()];
+        // Adding an answer to a score with c answers adds 2c + 1 to the sum
+        let mut expected_remaining_answers = 0;
+        for a in answers.iter() {
+            let count = &mut counts[row[a] as usize];
+            expected_remaining_answers += 2 * *count + 1;
+            *count += 1;
+        }
+
+        return (expected_remaining_answers as f64 / answers.len() as f64) as usize;
+    }
+
+    /// Same as [`find_best_guess`], returning a guess index. Ties go to the
+    /// first guess alphabetically.
+    pub fn find_best_guess(&self, answers: &AnswerSet) -> usize {
+        // Early exit if only 2 or fewer possible answers
+        if answers.len() <= 2 {
+            return self.answer_guess(answers.iter().next().unwrap());
+        }
+
+        let mut best_guess_score = usize::MAX;
+        let mut best_guess = 0;
+        for guess in 0..self.guesses.len() {
+            let expected_answers = self.evaluate_guess(guess, answers);
+            if expected_answers < best_guess_score {
+                best_guess_score = expected_answers;
+                best_guess = guess;
+                // Can't get better than 1, can return early
+                if expected_answers == 1 {
+                    return best_guess;
+                }
+            }
+        }
+
+        return best_guess;
+    }
+
+    /// Returns up to `count` guesses with the fewest expected answers left,
+    /// best first, along with the expected answers left
+    ///
+    /// Ordered like [`ScoreMatrix::find_best_guess`] but without rounding
+    /// the expected answers, ties go to guesses that could be the answer.
+    /// Guesses that can't be the answer and can't tell the answers apart are
+    /// skipped.
+    pub fn suggest_guesses(&self, answers: &AnswerSet, count: usize) -> Vec<(usize, f64)> {
+        let all_green = pack_score(&[LetterState::Green; N]) as usize;
+        let mut counts = vec![0; num_scores::<N>()];
+        let mut ranked = Vec::new();
+        for guess in 0..self.guesses.len() {
+            self.count_scores(guess, answers, &mut counts);
+            let is_answer = counts[all_green] > 0;
+            if !is_answer && counts.contains(&answers.len()) {
+                continue;
+            }
+            let sum_squares: usize = counts.iter().map(|c| c * c).sum();
+            ranked.push((sum_squares, !is_answer, guess));
+        }
+
+        ranked.sort();
+        return ranked
+            .into_iter()
+            .take(count)
+            .map(|(sum_squares, _, guess)| (guess, sum_squares as f64 / answers.len() as f64))
+            .collect();
+    }
+
+    /// Returns the expected information from the score of a guess, in bits
+    pub fn entropy(&self, guess: usize, answers: &AnswerSet) -> f64 {
+        let mut counts = vec![0; num_scores::<N>()];
+        self.count_scores(guess, answers, &mut counts);
+        return entropy(&counts, answers.len());
+    }
+
+    /// Returns the guess whose score gives the most information. Ties go to
+    /// guesses that could be the answer, then to the first alphabetically.
+    pub fn find_max_entropy_guess(&self, answers: &AnswerSet) -> usize {
+        if answers.len() <= 2 {
+            return self.answer_guess(answers.iter().next().unwrap());
+        }
+
+        let all_green = pack_score(&[LetterState::Green; N]) as usize;
+        let mut counts = vec![0; num_scores::<N>()];
+        let mut best = (f64::MIN, false, 0);
+        for guess in 0..self.guesses.len() {
+            self.count_scores(guess, answers, &mut counts);
+            let bits = entropy(&counts, answers.len());
+            let is_answer = counts[all_green] > 0;
+            // Scores are summed in a different order for each guess, so
+            // equal entropies can differ in the last few bits
+            let is_tie = (bits - best.0).abs() < 1e-9;
+            if (!is_tie && bits > best.0) || (is_tie && is_answer && !best.1) {
+                best = (bits, is_answer, guess);
+            }
+        }
+
+        return best.2;
+    }
+
+    /// Sets `counts` to the number of answers that get each packed score
+    fn count_scores(&self, guess: usize, answers: &AnswerSet, counts: &mut [usize]) {
+        let row = &self.scores[guess * self.answers.len()..(guess + 1) * self.answers.len()];
+        counts.fill(0);
+        for a in answers.iter() {
+            counts[row[a] as usize] += 1;
+        }
+    }
+
+    /// Returns the guess that leaves the fewest answers in the worst case,
+    /// which is what [`absurdle::AdversarialGame`] always leaves. Ties go to
+    /// guesses that could be the answer, then to the first alphabetically.
+    pub fn find_minimax_guess(&self, answers: &AnswerSet) -> usize {
+        if answers.len() <= 2 {
+            return self.answer_guess(answers.iter().next().unwrap());
+        }
+
+        let all_green = pack_score(&[LetterState::Green; N]) as usize;
+        let mut counts = vec![0; num_scores::<N>()];
+        let mut best = (usize::MAX, true, 0);
+        for guess in 0..self.guesses.len() {
+            self.count_scores(guess, answers, &mut counts);
+            let largest = *counts.iter().max().unwrap();
+            let candidate = (largest, counts[all_green] == 0, guess);
+            if candidate < best {
+                best = candidate;
+            }
+        }
+
+        return best.2;
+    }
+
+    /// Same as [`ScoreMatrix::find_best_guess`] for several boards that share
+    /// each guess, like Quordle, `boards` are the answers still possible on
+    /// each unsolved board
+    ///
+    /// Sums the expected fraction of each board left rather than the expected
+    /// answers, so a board with a few answers left counts as much as one with
+    /// hundreds and no board is starved of information. A board with one
+    /// answer left is always guessed first, it has to be guessed eventually
+    /// and the score still splits the other boards. Ties go to guesses that
+    /// could be an answer, then to the first alphabetically.
+    pub fn find_best_multi_guess(&self, boards: &[AnswerSet]) -> usize {
+        if let Some(board) = boards.iter().find(|b| b.len() == 1) {
+            return self.answer_guess(board.iter().next().unwrap());
+        }
+
+        let all_green = pack_score(&[LetterState::Green; N]) as usize;
+        let mut counts = vec![0; num_scores::<N>()];
+        let mut best = (f64::MAX, false, 0);
+        for guess in 0..self.guesses.len() {
+            let mut expected_fraction = 0.0;
+            let mut is_answer = false;
+            for answers in boards {
+                self.count_scores(guess, answers, &mut counts);
+                let sum_squares: usize = counts.iter().map(|c| c * c).sum();
+                let total = answers.len() as f64;
+                expected_fraction += sum_squares as f64 / (total * total);
+                is_answer |= counts[all_green] > 0;
+            }
+            // Same as the entropy, sums can differ in the last few bits
+            let is_tie = (expected_fraction - best.0).abs() < 1e-9;
+            if (!is_tie && expected_fraction < best.0) || (is_tie && is_answer && !best.1) {
+                best = (expected_fraction, is_answer, guess);
+            }
+        }
+
+        return best.2;
+    }
+}
+
+/// Entropy in bits of `total` answers split into groups of `counts`
+fn entropy(counts: &[usize], total: usize) -> f64 {
+    let total = total as f64;
+    return counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+}
+
+/// Letter constraints implied by the guesses and scores entered so far
+///
+/// Used to explain why a score leaves no possible answers. Guesses are
+/// numbered from 1 in the order they were added.
+#[derive(Debug, Clone)]
+pub struct Constraints<const N: usize> {
+    /// Letter known to be at each position and the guess that showed it
+    green: [Option<(char, usize)>; N],
+    /// Letters known not to be at each position and the guess that showed it
+    not_at: [Vec<(char, usize)>; N],
+    /// Minimum number of times each letter appears and the guess that showed it
+    min_count: BTreeMap<char, (usize, usize)>,
+    /// Exact number of times each letter appears, known once a letter is scored gray
+    max_count: BTreeMap<char, (usize, usize)>,
+    num_guesses: usize,
+}
+
+impl<const N: usize> Default for Constraints<N> {
+    fn default() -> Self {
+        Self {
+            green: [None; N],
+            not_at: std::array::from_fn(|_| Vec::new()),
+            min_count: BTreeMap::new(),
+            max_count: BTreeMap::new(),
+            num_guesses: 0,
+        }
+    }
+}
+
+/// A score that can't be true given the previous guesses
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Contradiction {
+    /// Two guesses have different green letters at the same position
+    Green {
+        position: usize,
+        letter: char,
+        guess: usize,
+        other: char,
+        other_guess: usize,
+    },
+    /// A letter is green at a position where a previous guess ruled it out
+    Position {
+        position: usize,
+        letter: char,
+        guess: usize,
+        other_guess: usize,
+    },
+    /// A letter must appear more times than another guess allows
+    Count {
+        letter: char,
+        min: usize,
+        min_guess: usize,
+        max: usize,
+        max_guess: usize,
+    },
+    /// The constraints are consistent, but no answer matches them
+    NoAnswers,
+}
+
+impl fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Contradiction::Green {
+                position,
+                letter,
+                guess,
+                other,
+                other_guess,
+            } => write!(
+                f,
+                "position {} is '{}' in guess {} but '{}' in guess {}",
+                position + 1,
+                letter,
+                guess,
+                other,
+                other_guess
+            ),
+            Contradiction::Position {
+                position,
+                letter,
+                guess,
+                other_guess,
+            } => write!(
+                f,
+                "'{}' is green at position {} in guess {} but guess {} ruled it out there",
+                letter,
+                position + 1,
+                guess,
+                other_guess
+            ),
+            Contradiction::Count {
+                letter,
+                min,
+                min_guess,
+                max,
+                max_guess,
+            } => write!(
+                f,
+                "'{}' appears at least {} time(s) in guess {} but at most {} time(s) in guess {}",
+                letter, min, min_guess, max, max_guess
+            ),
+            Contradiction::NoAnswers => write!(f, "no answer matches all of the scores"),
+        }
+    }
+}
+
+impl<const N: usize> Constraints<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the constraints from a scored guess
+    ///
+    /// Returns the first constraint the score conflicts with, in which case
+    /// `self` is left unchanged.
+    pub fn add(&mut self, guess: &[char; N], score: [LetterState; N]) -> Result<(), Contradiction> {
+        let mut next = self.clone();
+        next.num_guesses += 1;
+        let n = next.num_guesses;
+
+        for i in 0..N {
+            let g = guess[i];
+            match score[i] {
+                LetterState::Green => match next.green[i] {
+                    Some((other, other_guess)) if other != g => {
+                        return Err(Contradiction::Green {
+                            position: i,
+                            letter: g,
+                            guess: n,
+                            other,
+                            other_guess,
+                        });
+                    }
+                    Some(_) => {}
+                    None => next.green[i] = Some((g, n)),
+                },
+                LetterState::Yellow | LetterState::Gray => next.not_at[i].push((g, n)),
+            }
+        }
+
+        for i in 0..N {
+            if let Some((g, guess)) = next.green[i] {
+                if let Some((_, other_guess)) = next.not_at[i].iter().find(|(c, _)| *c == g) {
+                    return Err(Contradiction::Position {
+                        position: i,
+                        letter: g,
+                        guess,
+                        other_guess: *other_guess,
+                    });
+                }
+            }
+        }
+
+        // Only the guessed letters can change, in alphabetical order
+        for (letter, (known, is_absent)) in get_letter_counts(guess, &score) {
+            let min_count = next.min_count.entry(letter).or_insert((0, 0));
+            if known > min_count.0 {
+                *min_count = (known, n);
+            }
+            let (min, min_guess) = *min_count;
+            if is_absent
+                && next
+                    .max_count
+                    .get(&letter)
+                    .map_or(true, |(m, _)| known < *m)
+            {
+                next.max_count.insert(letter, (known, n));
+            }
+
+            if let Some(&(max, max_guess)) = next.max_count.get(&letter) {
+                if min > max {
+                    return Err(Contradiction::Count {
+                        letter,
+                        min,
+                        min_guess,
+                        max,
+                        max_guess,
+                    });
+                }
+            }
+        }
+
+        *self = next;
+        Ok(())
+    }
+
+    /// Number of guesses added so far
+    pub fn len(&self) -> usize {
+        self.num_guesses
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_guesses == 0
+    }
+}
+
+/// Adds the words in a file, one per line, skipping lines that aren't `N`
+/// letters long
+pub fn load_word_list<const N: usize>(path: &str, set: &mut HashSet<[char; N]>) {
     if let Ok(lines) = read_lines(path) {
         for line in lines {
             if let Ok(word) = line {
-                let mut chars = ['a'; 5];
-                let mut i = 0;
-                for c in word.chars() {
-                    chars[i] = c;
-                    i += 1;
+                let chars = word.trim().chars().collect::<Vec<char>>();
+                if let Ok(chars) = <[char; N]>::try_from(chars) {
+                    set.insert(chars);
                 }
-                set.insert(chars);
             }
         }
     }
@@ -349,7 +921,11 @@ where
 mod tests {
     use std::collections::HashSet;
 
-    use crate::{filter_answers, get_all_scores, score_guess, LetterState};
+    use crate::{
+        evaluate_guess, filter_answers, filter_answers_hashset, filter_answers_vec,
+        find_best_guess, get_all_scores, num_scores, pack_score, score_guess, unpack_score,
+        AnswerSet, Constraints, Contradiction, LetterState, ScoreMatrix, MAX_WORD_LENGTH,
+    };
 
     /// Returns char array from str
     fn to_chars(s: &str) -> [char; 5] {
@@ -425,6 +1001,24 @@ mod tests {
         assert_eq!(filtered.len(), 1);
     }
 
+    #[test]
+    fn test_filter_implementations_match() {
+        let answers = HashSet::from_iter(
+            [
+                "warby", "wordy", "wormy", "wryly", "crane", "nodes", "foods",
+            ]
+            .map(to_chars),
+        );
+        for guess in ["weary", "crane", "foods"].map(to_chars) {
+            for score in get_all_scores() {
+                assert_eq!(
+                    filter_answers_vec(&guess, score, &answers),
+                    filter_answers_hashset(&guess, score, &answers)
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_filter_weary() {
         let answers = HashSet::from_iter(vec![
@@ -473,7 +1067,231 @@ mod tests {
 
     #[test]
     fn test_get_all_scores() {
-        let scores = get_all_scores();
+        let scores = get_all_scores::<5>();
         assert_eq!(scores.len(), 243); //3^5 options
     }
+
+    #[test]
+    fn test_pack_score() {
+        let mut packed = HashSet::new();
+        for score in get_all_scores::<5>() {
+            let p = pack_score(&score);
+            assert!((p as usize) < num_scores::<5>());
+            assert_eq!(unpack_score(p), score);
+            packed.insert(p);
+        }
+        assert_eq!(packed.len(), num_scores::<5>());
+        assert_eq!(pack_score(&[LetterState::Green; 5]), 0);
+
+        // Longer words have more scores than fit in a byte
+        let scores = get_all_scores::<MAX_WORD_LENGTH>();
+        assert_eq!(scores.len(), num_scores::<MAX_WORD_LENGTH>());
+        let packed = scores.iter().map(pack_score).collect::<HashSet<_>>();
+        assert_eq!(packed.len(), scores.len());
+        assert_eq!(
+            unpack_score(pack_score(&[LetterState::Gray; MAX_WORD_LENGTH])),
+            [LetterState::Gray; MAX_WORD_LENGTH]
+        );
+    }
+
+    #[test]
+    fn test_answer_set() {
+        let mut set = AnswerSet::new(130);
+        assert!(set.is_empty());
+        for i in [129, 0, 64, 63, 0] {
+            set.insert(i);
+        }
+        assert_eq!(set.len(), 4);
+        assert!(set.contains(64));
+        assert!(!set.contains(65));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 63, 64, 129]);
+    }
+
+    #[test]
+    fn test_score_matrix_matches() {
+        let answers = HashSet::from_iter(
+            [
+                "warby", "wordy", "wormy", "wryly", "crane", "nodes", "foods", "robin",
+            ]
+            .map(to_chars),
+        );
+        let guesses = HashSet::from_iter(["weary", "digit", "xxxxx"].map(to_chars));
+        let matrix = ScoreMatrix::new(&guesses, &answers);
+        // answers are added to the guesses
+        assert_eq!(matrix.guesses().len(), 11);
+        assert_eq!(matrix.answers().len(), 8);
+
+        let all_answers = matrix.all_answers();
+        let to_words = |set: &AnswerSet| {
+            set.iter()
+                .map(|a| matrix.answers()[a])
+                .collect::<HashSet<_>>()
+        };
+        for (g, guess) in matrix.guesses().iter().enumerate() {
+            assert_eq!(matrix.guess_index(guess), Some(g));
+            for (a, answer) in matrix.answers().iter().enumerate() {
+                assert_eq!(unpack_score(matrix.score(g, a)), score_guess(guess, answer));
+            }
+
+            // The filters only agree without repeated letters, otherwise
+            // `filter_answers` also keeps answers that would score differently
+            let repeats = (1..5).any(|i| guess[..i].contains(&guess[i]));
+            for score in get_all_scores() {
+                let filtered =
+                    to_words(&matrix.filter_answers(g, pack_score(&score), &all_answers));
+                let expected = answers
+                    .iter()
+                    .filter(|a| score_guess(guess, a) == score)
+                    .copied()
+                    .collect::<HashSet<_>>();
+                assert_eq!(filtered, expected);
+                let loose = filter_answers(guess, score, &answers);
+                assert!(filtered.is_subset(&loose));
+                assert!(repeats || filtered == loose);
+            }
+            if !repeats {
+                assert_eq!(
+                    matrix.evaluate_guess(g, &all_answers),
+                    evaluate_guess(guess, &answers)
+                );
+            }
+        }
+
+        let best = matrix.guesses()[matrix.find_best_guess(&all_answers)];
+        let best_without_matrix = find_best_guess(&answers, &guesses);
+        assert_eq!(
+            matrix.evaluate_guess(matrix.guess_index(&best).unwrap(), &all_answers),
+            matrix.evaluate_guess(
+                matrix.guess_index(&best_without_matrix).unwrap(),
+                &all_answers
+            )
+        );
+    }
+
+    #[test]
+    fn test_suggest_guesses() {
+        let answers = HashSet::from_iter(
+            [
+                "warby", "wordy", "wormy", "wryly", "crane", "nodes", "foods", "robin",
+            ]
+            .map(to_chars),
+        );
+        let guesses = HashSet::from_iter(["weary", "digit", "xxxxx"].map(to_chars));
+        let matrix = ScoreMatrix::new(&guesses, &answers);
+        let all_answers = matrix.all_answers();
+
+        let suggestions = matrix.suggest_guesses(&all_answers, 10);
+        assert_eq!(suggestions.len(), 10);
+        let best = matrix.find_best_guess(&all_answers);
+        assert_eq!(
+            suggestions[0].1 as usize,
+            matrix.evaluate_guess(best, &all_answers)
+        );
+        for pair in suggestions.windows(2) {
+            assert!(pair[0].1 <= pair[1].1);
+        }
+        for (guess, expected) in &suggestions {
+            assert_eq!(
+                *expected as usize,
+                matrix.evaluate_guess(*guess, &all_answers)
+            );
+        }
+        // Nothing in common with any answer, so nothing is learned
+        let xxxxx = matrix.guess_index(&to_chars("xxxxx")).unwrap();
+        let all = matrix.suggest_guesses(&all_answers, 11);
+        assert_eq!(all.len(), 10);
+        assert!(all.iter().all(|(guess, _)| *guess != xxxxx));
+
+        // With one answer left it's the only suggestion
+        let wordy = matrix.answer_index(&to_chars("wordy")).unwrap();
+        let mut one = AnswerSet::new(answers.len());
+        one.insert(wordy);
+        assert_eq!(
+            matrix.suggest_guesses(&one, 10),
+            vec![(matrix.answer_guess(wordy), 1.0)]
+        );
+    }
+
+    /// Parses a score string like "GYXXG"
+    fn to_score(s: &str) -> [LetterState; 5] {
+        let mut score = [LetterState::Gray; 5];
+        for (i, c) in s.chars().enumerate() {
+            score[i] = match c {
+                'G' => LetterState::Green,
+                'Y' => LetterState::Yellow,
+                _ => LetterState::Gray,
+            };
+        }
+        return score;
+    }
+
+    #[test]
+    fn test_constraints_consistent() {
+        let mut constraints = Constraints::new();
+        for answer in ["nodes", "crane", "weary"] {
+            let guess = to_chars(answer);
+            let score = score_guess(&guess, &to_chars("wordy"));
+            assert_eq!(constraints.add(&guess, score), Ok(()));
+        }
+        assert_eq!(constraints.len(), 3);
+    }
+
+    #[test]
+    fn test_constraints_green_conflict() {
+        let mut constraints = Constraints::new();
+        constraints
+            .add(&to_chars("crane"), to_score("GXXXX"))
+            .unwrap();
+        let result = constraints.add(&to_chars("boats"), to_score("GXXXX"));
+        assert_eq!(
+            result,
+            Err(Contradiction::Green {
+                position: 0,
+                letter: 'b',
+                guess: 2,
+                other: 'c',
+                other_guess: 1
+            })
+        );
+        // failed guesses aren't added
+        assert_eq!(constraints.len(), 1);
+    }
+
+    #[test]
+    fn test_constraints_position_conflict() {
+        let mut constraints = Constraints::new();
+        constraints
+            .add(&to_chars("crane"), to_score("XXYXX"))
+            .unwrap();
+        let result = constraints.add(&to_chars("toast"), to_score("XXGXX"));
+        assert_eq!(
+            result,
+            Err(Contradiction::Position {
+                position: 2,
+                letter: 'a',
+                guess: 2,
+                other_guess: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_constraints_count_conflict() {
+        let mut constraints = Constraints::new();
+        // a single e
+        constraints
+            .add(&to_chars("geese"), to_score("XGXXX"))
+            .unwrap();
+        let result = constraints.add(&to_chars("eerie"), to_score("YGXXX"));
+        assert_eq!(
+            result,
+            Err(Contradiction::Count {
+                letter: 'e',
+                min: 2,
+                min_guess: 2,
+                max: 1,
+                max_guess: 1
+            })
+        );
+    }
 }
@@ -1,115 +1,655 @@
 use std::{
     collections::{HashMap, HashSet},
     io::{self, BufRead},
+    time::Instant,
 };
 
+use clap::{Parser, Subcommand, ValueEnum};
 use wordle_bot::{
-    filter_answers, find_best_guess, get_all_scores, load_word_list, play_game, LetterState,
+    absurdle::{play_adversarial, AdversarialGame},
+    config::{Alphabet, PuzzleConfig},
+    evaluate::evaluate,
+    filter_answers,
+    multi::play_multi_game,
+    pack_score, play_game,
+    tree::{load_cached_tree, load_or_build_tree, score_to_string, TreeSearch},
+    unpack_score, AnswerSet, Constraints, Contradiction, Heuristic, LetterState, PackedScore,
+    ScoreMatrix,
 };
 
-const ANSWER_FILE: &str = "data/wordle-answers-alphabetical.txt";
-const GUESS_FILE: &str = "data/wordle-allowed-guesses.txt";
+/// Decision tree cache, written by `greedy-tree` and `optimal-tree` modes and
+/// used by interactive mode
+const TREE_FILE: &str = "data/decision-tree.json";
+/// Number of answers in the official Wordle list
+const WORDLE_ANSWERS: usize = 2315;
+/// Total guesses of the optimal tree for the official Wordle answers with
+/// every allowed guess, starting with salet for 3.4212 guesses on average
+const OPTIMAL_WORDLE_TOTAL: usize = 7920;
+/// Guesses before `absurdle-bench` gives up on a heuristic
+const MAX_ADVERSARIAL_GUESSES: usize = 20;
+/// Guesses allowed on top of one per board, Quordle allows 9 for 4 boards
+/// and Octordle 13 for 8
+const EXTRA_MULTI_GUESSES: usize = 5;
+/// Guesses suggested after each score in interactive mode
+const SUGGESTIONS: usize = 10;
+/// Interactive mode lists the answers left once there are this few
+const LISTED_ANSWERS: usize = 20;
 
-fn main() {
-    evaluate();
-    // interactive_mode();
+#[derive(Debug, Subcommand, Clone)]
+enum Commands {
+    /// Plays one answer, printing each guess
+    Solve {
+        answer: String,
+        #[clap(long, value_enum, default_value_t = HeuristicType::Expected)]
+        heuristic: HeuristicType,
+    },
+    /// Suggests guesses while you play, enter each guess and its score
+    Interactive,
+    /// Plays every answer and prints how many guesses each took
+    Evaluate {
+        #[clap(long, value_enum, default_value_t = HeuristicType::Expected)]
+        heuristic: HeuristicType,
+        /// Writes every game as JSON, for plotting
+        #[clap(long)]
+        out: Option<String>,
+    },
+    /// Builds a decision tree by always taking the guess that leaves the
+    /// fewest expected answers, or loads it from `data/decision-tree.json`,
+    /// and prints how many guesses it takes. The tree is a heuristic, for the
+    /// official answers its total is compared to the optimal tree's.
+    GreedyTree {
+        /// Guesses searched at each state, more never does worse but the
+        /// time grows exponentially
+        #[clap(long, default_value_t = 1)]
+        candidates: usize,
+    },
+    /// Builds the optimal decision tree with a branch and bound search, or
+    /// loads it from `data/decision-tree.json`, and prints how many guesses
+    /// it takes. Only finishes for a couple of hundred answers, not the full
+    /// Wordle list.
+    OptimalTree,
+    /// Plays Absurdle, where the answer dodges every guess until it can't
+    Absurdle,
+    /// Plays Absurdle with every heuristic
+    AbsurdleBench,
+    /// Plays one board for each answer with the same guesses, like Quordle
+    Multi {
+        #[clap(required = true)]
+        words: Vec<String>,
+    },
+    /// Plays many multi-board games and prints how many guesses they took
+    MultiBench {
+        /// 4 for Quordle, 8 for Octordle
+        #[clap(long, default_value_t = 4)]
+        boards: usize,
+        #[clap(long, default_value_t = 100)]
+        games: usize,
+    },
 }
 
-fn evaluate() {
-    let mut answers = HashSet::new();
-    load_word_list(ANSWER_FILE, &mut answers);
-    let mut guesses = HashSet::new();
-    load_word_list(GUESS_FILE, &mut guesses);
-    load_word_list(ANSWER_FILE, &mut guesses);
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum HeuristicType {
+    /// Fewest expected answers left
+    Expected,
+    /// Most information from the score
+    Entropy,
+    /// Fewest answers left in the worst case
+    Minimax,
+}
 
-    println!("calculating starting guess...");
-    let starting_guess = find_best_guess(&answers, &guesses);
+impl HeuristicType {
+    fn get<const N: usize>(self) -> Heuristic<N> {
+        match self {
+            HeuristicType::Expected => ScoreMatrix::find_best_guess,
+            HeuristicType::Entropy => ScoreMatrix::find_max_entropy_guess,
+            HeuristicType::Minimax => ScoreMatrix::find_minimax_guess,
+        }
+    }
 
-    // Build lookup table for second guess
-    println!("building second guess lookup...");
-    let mut second_guess_lookup = HashMap::new();
-    for score in get_all_scores() {
-        let filtered_answers = filter_answers(&starting_guess, score, &answers);
+    fn name(self) -> String {
+        return self.to_possible_value().unwrap().get_name().to_string();
+    }
+}
 
-        if filtered_answers.len() == 0 {
-            // impossible state, don't need to pre-compute
-            continue;
+#[derive(Parser, Debug, Clone)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Commands,
+    /// Puzzle with the word length, alphabet and word lists, see
+    /// `data/primel.conf`. Plays Wordle by default.
+    #[clap(long, global = true)]
+    config: Option<String>,
+    /// Answer list, one word per line, instead of the config's
+    #[clap(long, global = true)]
+    answers: Option<String>,
+    /// Words that can be guessed but are never the answer, instead of the
+    /// config's
+    #[clap(long, global = true)]
+    guesses: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut config = match &args.config {
+        Some(path) => match PuzzleConfig::load(path) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("Could not load config: {}", e);
+                return;
+            }
+        },
+        None => PuzzleConfig::wordle(),
+    };
+    if let Some(answers) = &args.answers {
+        config.answers = answers.clone();
+    }
+    if let Some(guesses) = &args.guesses {
+        config.guesses = Some(guesses.clone());
+    }
+
+    // Word length is a const generic, so each supported length is built in
+    match config.length {
+        4 => run::<4>(&args.command, &config),
+        5 => run::<5>(&args.command, &config),
+        6 => run::<6>(&args.command, &config),
+        7 => run::<7>(&args.command, &config),
+        8 => run::<8>(&args.command, &config),
+        n => println!("{} letter words aren't supported, use 4 to 8", n),
+    }
+}
+
+fn run<const N: usize>(command: &Commands, config: &PuzzleConfig) {
+    let (answers, guesses) = match config.load_words::<N>() {
+        Ok(words) => words,
+        Err(e) => {
+            println!("Could not load words: {}", e);
+            return;
         }
+    };
 
-        let best_guess = find_best_guess(&filtered_answers, &guesses);
-        second_guess_lookup.insert(score, best_guess);
+    match command {
+        Commands::Solve { answer, heuristic } => {
+            solve(&config.alphabet, answer, *heuristic, &answers, &guesses)
+        }
+        Commands::Interactive => interactive_mode(&config.alphabet, answers, &guesses),
+        Commands::Evaluate { heuristic, out } => {
+            evaluate_mode(*heuristic, out.as_deref(), &answers, &guesses)
+        }
+        Commands::GreedyTree { candidates } => {
+            tree_mode(TreeSearch::Candidates(*candidates), &answers, &guesses)
+        }
+        Commands::OptimalTree => tree_mode(TreeSearch::Optimal, &answers, &guesses),
+        Commands::Absurdle => absurdle_mode(&config.alphabet, &answers, &guesses),
+        Commands::AbsurdleBench => absurdle_benchmark(&answers, &guesses),
+        Commands::Multi { words } => multi_mode(&config.alphabet, words, &answers, &guesses),
+        Commands::MultiBench { boards, games } => {
+            multi_benchmark(*boards, *games, &answers, &guesses)
+        }
     }
+}
 
-    let mut histogram = HashMap::new();
+fn solve<const N: usize>(
+    alphabet: &Alphabet,
+    answer: &str,
+    heuristic: HeuristicType,
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) {
+    let matrix = ScoreMatrix::new(guesses, answers);
+    let answer = match alphabet
+        .parse_word(answer)
+        .and_then(|a| matrix.answer_index(&a))
+    {
+        Some(answer) => answer,
+        None => {
+            println!("{} isn't in the answer list", answer);
+            return;
+        }
+    };
 
-    for answer in &answers {
-        let turns = play_game(
-            answer,
-            &starting_guess,
-            &answers,
-            &guesses,
-            &second_guess_lookup,
-        );
+    let heuristic = heuristic.get::<N>();
+    let start_guess = heuristic(&matrix, &matrix.all_answers());
+    let played = play_game(answer, start_guess, &matrix, &HashMap::new(), heuristic);
+    for (guess, score) in &played {
         println!(
-            "Solved {} in {}",
-            answer.into_iter().collect::<String>(),
-            turns
+            "{} {}",
+            matrix.guesses()[*guess].iter().collect::<String>(),
+            score_to_string(&unpack_score::<N>(*score))
         );
-        let count = *histogram.get(&turns).unwrap_or(&0);
-        histogram.insert(turns, count + 1);
+    }
+    println!("Solved in {} guesses", played.len());
+}
 
-        for i in 0..8 {
-            let count = histogram.get(&i).unwrap_or(&0);
-            println!("{}: {}", i, &count);
+/// Builds the decision tree, or loads it from `TREE_FILE`, and prints how
+/// many guesses it takes
+fn tree_mode<const N: usize>(
+    search: TreeSearch,
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) {
+    println!("loading decision tree...");
+    let tree = match load_or_build_tree(TREE_FILE, answers, guesses, search) {
+        Ok(tree) => tree,
+        Err(e) => {
+            println!("Could not cache decision tree: {}", e);
+            return;
+        }
+    };
+
+    let depths = tree.answer_depths();
+    let mut histogram = HashMap::new();
+    for (_, depth) in &depths {
+        *histogram.entry(*depth).or_insert(0) += 1;
+    }
+    println!(
+        "Starting guess: {}",
+        tree.guess().unwrap().into_iter().collect::<String>()
+    );
+    println!(
+        "Average guesses: {:.4}",
+        tree.total_guesses() as f64 / depths.len() as f64
+    );
+    match search {
+        TreeSearch::Optimal => println!("Total guesses: {} (optimal)", tree.total_guesses()),
+        TreeSearch::Candidates(_) if N == 5 && answers.len() == WORDLE_ANSWERS => println!(
+            "Total guesses: {} (heuristic, the optimal tree with every allowed guess takes {})",
+            tree.total_guesses(),
+            OPTIMAL_WORDLE_TOTAL
+        ),
+        TreeSearch::Candidates(_) => {
+            println!("Total guesses: {} (heuristic)", tree.total_guesses())
         }
     }
+    for i in 1..8 {
+        let count = histogram.get(&i).unwrap_or(&0);
+        println!("{}: {}", i, &count);
+    }
 }
 
-fn interactive_mode() {
-    let mut answers = HashSet::new();
-    load_word_list(ANSWER_FILE, &mut answers);
-    let mut guesses = HashSet::new();
-    load_word_list(GUESS_FILE, &mut guesses);
-    load_word_list(ANSWER_FILE, &mut guesses);
+/// Plays Absurdle, where the answer dodges every guess until it can't
+fn absurdle_mode<const N: usize>(
+    alphabet: &Alphabet,
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) {
+    println!("scoring every guess against every answer...");
+    let matrix = ScoreMatrix::new(guesses, answers);
+    let mut game = AdversarialGame::new(&matrix);
+    println!("{} answers possible", game.answers().len());
 
-    println!("Loaded {} answers", answers.len());
-    println!("Loaded {} guesses", guesses.len());
     let stdin = io::stdin();
-    loop {
+    while !game.is_solved() {
         println!("Enter guess:");
         let mut guess_string = String::new();
-        stdin
+        let read = stdin
             .lock()
             .read_line(&mut guess_string)
             .expect("Could not read line");
+        if read == 0 {
+            // end of input
+            return;
+        }
 
-        println!("Enter score (GYX):");
-        let mut score_str = String::new();
-        stdin
-            .lock()
-            .read_line(&mut score_str)
-            .expect("Could not read line");
+        let guess = match alphabet
+            .parse_word(&guess_string)
+            .and_then(|g| matrix.guess_index(&g))
+        {
+            Some(guess) => guess,
+            None => {
+                println!("Not in the guess list");
+                continue;
+            }
+        };
+        let score = game.guess(guess);
+        println!(
+            "{}, {} answers remain",
+            score_to_string(&unpack_score::<N>(score)),
+            game.answers().len()
+        );
+    }
+    println!("Solved in {} guesses", game.played().len());
+}
+
+/// Plays Absurdle with each guess heuristic. The adversary always leaves
+/// the most answers, so this is each heuristic's worst case.
+fn absurdle_benchmark<const N: usize>(answers: &HashSet<[char; N]>, guesses: &HashSet<[char; N]>) {
+    println!("scoring every guess against every answer...");
+    let matrix = ScoreMatrix::new(guesses, answers);
+
+    for heuristic in HeuristicType::value_variants() {
+        let name = heuristic.name();
+        let start = Instant::now();
+        let played = play_adversarial(&matrix, heuristic.get(), MAX_ADVERSARIAL_GUESSES);
+        let elapsed = start.elapsed();
+
+        let solved = played.last().map(|(_, s)| *s) == Some(pack_score(&[LetterState::Green; N]));
+        match solved {
+            true => println!(
+                "{}: solved in {} guesses, {:.2?}",
+                name,
+                played.len(),
+                elapsed
+            ),
+            false => println!("{}: unsolved after {} guesses", name, played.len()),
+        }
+        for (guess, score) in played {
+            println!(
+                "  {} {}",
+                matrix.guesses()[guess].iter().collect::<String>(),
+                score_to_string(&unpack_score::<N>(score))
+            );
+        }
+    }
+}
+
+/// Plays one board for each of `words`, printing each guess and its scores
+fn multi_mode<const N: usize>(
+    alphabet: &Alphabet,
+    words: &[String],
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) {
+    println!("scoring every guess against every answer...");
+    let matrix = ScoreMatrix::new(guesses, answers);
+    let mut boards = Vec::new();
+    for word in words {
+        match alphabet
+            .parse_word(word)
+            .and_then(|a| matrix.answer_index(&a))
+        {
+            Some(answer) => boards.push(answer),
+            None => {
+                println!("{} isn't in the answer list", word);
+                return;
+            }
+        }
+    }
+
+    let max_guesses = boards.len() + EXTRA_MULTI_GUESSES;
+    let played = play_multi_game(&boards, &matrix, max_guesses);
+    for (guess, scores) in &played {
+        let scores = scores
+            .iter()
+            .map(|s| match s {
+                Some(s) => score_to_string(&unpack_score::<N>(*s)),
+                None => " ".repeat(N),
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{} {}",
+            matrix.guesses()[*guess].iter().collect::<String>(),
+            scores.join(" ")
+        );
+    }
+    match is_multi_solved::<N>(&played) {
+        true => println!("Solved in {} guesses", played.len()),
+        false => println!("Unsolved after {} guesses", played.len()),
+    }
+}
+
+/// Plays `games` multi-board games and prints how many guesses they took
+///
+/// Game `g` plays answers `g`, `g + n / boards`, `g + 2n / boards` and so
+/// on, where `n` is the number of answers, so the boards are spread over the
+/// answer list and runs are repeatable.
+fn multi_benchmark<const N: usize>(
+    boards: usize,
+    games: usize,
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) {
+    println!("scoring every guess against every answer...");
+    let matrix = ScoreMatrix::new(guesses, answers);
+    let n = matrix.answers().len();
+    if boards == 0 || boards > n {
+        println!("boards must be between 1 and {}", n);
+        return;
+    }
+
+    let max_guesses = boards + EXTRA_MULTI_GUESSES;
+    let mut histogram = HashMap::new();
+    let mut unsolved = 0;
+    for g in 0..games {
+        let answers = (0..boards)
+            .map(|b| (g + b * n / boards) % n)
+            .collect::<Vec<_>>();
+        let played = play_multi_game(&answers, &matrix, max_guesses);
+        match is_multi_solved::<N>(&played) {
+            true => *histogram.entry(played.len()).or_insert(0) += 1,
+            false => unsolved += 1,
+        }
+    }
+
+    for i in boards..=max_guesses {
+        let count = histogram.get(&i).unwrap_or(&0);
+        println!("{}: {}", i, &count);
+    }
+    println!("Unsolved: {}", unsolved);
+    let solved: usize = histogram.values().sum();
+    if solved > 0 {
+        let total: usize = histogram.iter().map(|(i, c)| i * c).sum();
+        println!("Average guesses: {:.4}", total as f64 / solved as f64);
+    }
+}
+
+/// Whether the last guess of a multi-board game solved its last board
+fn is_multi_solved<const N: usize>(played: &[(usize, Vec<Option<PackedScore>>)]) -> bool {
+    let all_green = Some(pack_score(&[LetterState::Green; N]));
+    return played
+        .last()
+        .is_some_and(|(_, scores)| scores.iter().all(|s| s.is_none() || *s == all_green));
+}
+
+/// Plays every answer, printing the number of guesses for each and writing
+/// the games to `out` as JSON
+fn evaluate_mode<const N: usize>(
+    heuristic: HeuristicType,
+    out: Option<&str>,
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) {
+    println!("scoring every guess against every answer...");
+    let matrix = ScoreMatrix::new(guesses, answers);
+
+    println!("playing every answer...");
+    let evaluation = evaluate(&matrix, &heuristic.name(), heuristic.get());
+    for game in &evaluation.games {
+        println!(
+            "Solved {} in {}",
+            game.answer.iter().collect::<String>(),
+            game.played.len()
+        );
+    }
+
+    let histogram = evaluation.histogram();
+    for i in 0..8 {
+        let count = histogram.get(&i).unwrap_or(&0);
+        println!("{}: {}", i, &count);
+    }
+    println!("Average guesses: {:.4}", evaluation.average_guesses());
+
+    if let Some(path) = out {
+        match evaluation.save(path) {
+            Ok(()) => println!("Wrote results to {}", path),
+            Err(e) => println!("Could not write results: {}", e),
+        }
+    }
+}
+
+/// Line entered at the guess prompt of interactive mode
+enum Entry<const N: usize> {
+    Guess([char; N]),
+    Undo,
+}
+
+fn interactive_mode<const N: usize>(
+    alphabet: &Alphabet,
+    mut answers: HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) {
+    // Guesses are looked up while they follow the tree, so only use one
+    // that's already been built
+    let tree = load_cached_tree(TREE_FILE, &answers).map(|(tree, _)| tree);
+    println!("scoring every guess against every answer...");
+    let matrix = ScoreMatrix::new(guesses, &answers);
+
+    println!("Loaded {} answers", answers.len());
+    println!("Loaded {} guesses", guesses.len());
+    if tree.is_some() {
+        println!("Loaded decision tree");
+    }
+    println!("Enter `undo` to revert the last guess");
+
+    let mut constraints = Constraints::new();
+    // state before each guess, used to undo
+    let mut history: Vec<(Constraints<N>, HashSet<[char; N]>)> = Vec::new();
+    let mut played = Vec::new();
+    loop {
+        let tree_guess = tree
+            .as_ref()
+            .and_then(|t| t.follow(&played))
+            .and_then(|t| t.guess());
+        print_suggestions(&matrix, tree_guess, &answers);
 
-        let mut score = [LetterState::Gray; 5];
-        for i in 0..5 {
-            let c = score_str.chars().nth(i).unwrap();
-            match c {
-                'G' => score[i] = LetterState::Green,
-                'Y' => score[i] = LetterState::Yellow,
-                _ => score[i] = LetterState::Gray,
+        let guess = match prompt("Enter guess:", |s| parse_entry(alphabet, s)) {
+            Some(Entry::Guess(guess)) => guess,
+            Some(Entry::Undo) => {
+                match history.pop() {
+                    Some((c, a)) => {
+                        constraints = c;
+                        answers = a;
+                        played.pop();
+                        println!("Reverted last guess");
+                    }
+                    None => println!("Nothing to undo"),
+                }
+                continue;
             }
+            // end of input
+            None => return,
+        };
+        let score = match prompt("Enter score (G green, Y yellow, X gray):", parse_score) {
+            Some(score) => score,
+            None => return,
+        };
+
+        let mut next_constraints = constraints.clone();
+        if let Err(e) = next_constraints.add(&guess, score) {
+            println!("Score contradicts previous guesses: {}", e);
+            continue;
+        }
+
+        let filtered = filter_answers(&guess, score, &answers);
+        if filtered.is_empty() {
+            println!(
+                "Score contradicts previous guesses: {}",
+                Contradiction::NoAnswers
+            );
+            continue;
         }
 
-        let mut guess = ['a'; 5];
-        for i in 0..5 {
-            guess[i] = guess_string.chars().nth(i).unwrap();
+        history.push((constraints, answers));
+        played.push((guess, score));
+        constraints = next_constraints;
+        answers = filtered;
+        if score == [LetterState::Green; N] {
+            println!("Solved in {} guesses", played.len());
+            return;
         }
+    }
+}
 
-        answers = filter_answers(&guess, score, &answers);
-        println!("{} answers remain", answers.len());
-        println!("{:?}", answers);
+/// Prints the answers left if there are only a few, the decision tree's
+/// guess and the guesses that leave the fewest answers
+fn print_suggestions<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    tree_guess: Option<[char; N]>,
+    answers: &HashSet<[char; N]>,
+) {
+    println!("{} answers remain", answers.len());
+    if answers.len() <= LISTED_ANSWERS {
+        let mut words = answers
+            .iter()
+            .map(|a| a.iter().collect::<String>())
+            .collect::<Vec<_>>();
+        words.sort();
+        println!("{}", words.join(" "));
+    }
+
+    if let Some(guess) = tree_guess {
+        println!("Decision tree guess: {}", guess.iter().collect::<String>());
+    }
+
+    let mut set = AnswerSet::new(matrix.answers().len());
+    for answer in answers {
+        set.insert(matrix.answer_index(answer).unwrap());
+    }
+    println!("Best guesses, with the expected answers left:");
+    for (i, (guess, expected)) in matrix
+        .suggest_guesses(&set, SUGGESTIONS)
+        .into_iter()
+        .enumerate()
+    {
+        println!(
+            "{:>2}. {} {:.2}",
+            i + 1,
+            matrix.guesses()[guess].iter().collect::<String>(),
+            expected
+        );
+    }
+}
+
+/// Prints `message` and reads lines until `parse` accepts one, printing why
+/// it didn't for each line it rejects. Returns `None` at the end of input.
+fn prompt<T>(message: &str, parse: impl Fn(&str) -> Result<T, String>) -> Option<T> {
+    let stdin = io::stdin();
+    loop {
+        println!("{}", message);
+        let mut line = String::new();
+        let read = stdin
+            .lock()
+            .read_line(&mut line)
+            .expect("Could not read line");
+        if read == 0 {
+            return None;
+        }
+        match parse(&line) {
+            Ok(value) => return Some(value),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+fn parse_entry<const N: usize>(alphabet: &Alphabet, s: &str) -> Result<Entry<N>, String> {
+    if s.trim() == "undo" {
+        return Ok(Entry::Undo);
+    }
+    return alphabet.parse_word(s).map(Entry::Guess).ok_or_else(|| {
+        format!(
+            "Guesses must be {} characters from {}",
+            N,
+            alphabet.letters().iter().collect::<String>()
+        )
+    });
+}
+
+/// Parses a score like `GYXXG`, `B` and `.` are also gray and lowercase is
+/// fine
+fn parse_score<const N: usize>(s: &str) -> Result<[LetterState; N], String> {
+    let s = s.trim();
+    if s.chars().count() != N {
+        return Err(format!("Scores must be {} characters", N));
+    }
 
-        let best_guess = find_best_guess(&answers, &guesses);
-        println!("Best guess: {}", best_guess.into_iter().collect::<String>())
+    let mut score = [LetterState::Gray; N];
+    for (i, c) in s.chars().enumerate() {
+        score[i] = match c.to_ascii_uppercase() {
+            'G' => LetterState::Green,
+            'Y' => LetterState::Yellow,
+            'X' | 'B' | '.' => LetterState::Gray,
+            _ => return Err(format!("{} isn't G, Y or X", c)),
+        };
     }
+    return Ok(score);
 }
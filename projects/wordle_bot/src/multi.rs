@@ -0,0 +1,186 @@
+use crate::{pack_score, AnswerSet, LetterState, PackedScore, ScoreMatrix};
+
+/// Several boards played at once with the same guesses, like Quordle (4
+/// boards) or Octordle (8 boards)
+///
+/// Every guess is scored on each board that isn't solved yet. A board is
+/// solved once a guess gets all greens on it, after that it's not scored.
+pub struct MultiGame<'a, const N: usize> {
+    matrix: &'a ScoreMatrix<N>,
+    /// Answer index of each board
+    answers: Vec<usize>,
+    /// Answers still possible on each board
+    boards: Vec<AnswerSet>,
+    solved: Vec<bool>,
+    played: Vec<(usize, Vec<Option<PackedScore>>)>,
+}
+
+impl<'a, const N: usize> MultiGame<'a, N> {
+    /// One board for each answer index, every answer in the matrix is
+    /// possible on each
+    pub fn new(matrix: &'a ScoreMatrix<N>, answers: &[usize]) -> Self {
+        return Self {
+            matrix,
+            answers: answers.to_vec(),
+            boards: vec![matrix.all_answers(); answers.len()],
+            solved: vec![false; answers.len()],
+            played: Vec::new(),
+        };
+    }
+
+    /// Answers still possible on each board that isn't solved
+    pub fn unsolved(&self) -> Vec<AnswerSet> {
+        return self
+            .boards
+            .iter()
+            .zip(&self.solved)
+            .filter(|(_, solved)| !**solved)
+            .map(|(board, _)| board.clone())
+            .collect();
+    }
+
+    /// Guess indexes and the score on each board, `None` for boards that
+    /// were already solved
+    pub fn played(&self) -> &[(usize, Vec<Option<PackedScore>>)] {
+        &self.played
+    }
+
+    pub fn is_solved(&self) -> bool {
+        return self.solved.iter().all(|s| *s);
+    }
+
+    /// Scores a guess index on every unsolved board, returning the scores
+    /// like [`MultiGame::played`]
+    pub fn guess(&mut self, guess: usize) -> Vec<Option<PackedScore>> {
+        let all_green = pack_score(&[LetterState::Green; N]);
+        let mut scores = Vec::with_capacity(self.boards.len());
+        for i in 0..self.boards.len() {
+            if self.solved[i] {
+                scores.push(None);
+                continue;
+            }
+            let score = self.matrix.score(guess, self.answers[i]);
+            self.boards[i] = self.matrix.filter_answers(guess, score, &self.boards[i]);
+            self.solved[i] = score == all_green;
+            scores.push(Some(score));
+        }
+        self.played.push((guess, scores.clone()));
+        return scores;
+    }
+}
+
+/// Plays one board for each answer index with guesses from
+/// [`ScoreMatrix::find_best_multi_guess`], returning the guesses and their
+/// scores like [`MultiGame::played`]
+///
+/// Stops after `max_guesses` if it hasn't been solved, Quordle allows 9
+/// guesses and Octordle 13.
+pub fn play_multi_game<const N: usize>(
+    answers: &[usize],
+    matrix: &ScoreMatrix<N>,
+    max_guesses: usize,
+) -> Vec<(usize, Vec<Option<PackedScore>>)> {
+    let mut game = MultiGame::new(matrix, answers);
+    while !game.is_solved() && game.played().len() < max_guesses {
+        let guess = matrix.find_best_multi_guess(&game.unsolved());
+        game.guess(guess);
+    }
+    return game.played;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        multi::{play_multi_game, MultiGame},
+        pack_score, LetterState, ScoreMatrix,
+    };
+
+    /// Returns char array from str
+    fn to_chars(s: &str) -> [char; 5] {
+        let mut chars = ['a'; 5];
+        for i in 0..5 {
+            chars[i] = s.chars().nth(i).unwrap();
+        }
+
+        return chars;
+    }
+
+    const ANSWERS: [&str; 8] = [
+        "warby", "wordy", "wormy", "wryly", "crane", "nodes", "foods", "robin",
+    ];
+
+    fn matrix() -> ScoreMatrix<5> {
+        let answers = HashSet::from_iter(ANSWERS.map(to_chars));
+        let guesses = HashSet::from_iter(["weary", "digit", "xxxxx"].map(to_chars));
+        return ScoreMatrix::new(&guesses, &answers);
+    }
+
+    #[test]
+    fn test_multi_game() {
+        let matrix = matrix();
+        let wordy = matrix.answer_index(&to_chars("wordy")).unwrap();
+        let crane = matrix.answer_index(&to_chars("crane")).unwrap();
+        let mut game = MultiGame::new(&matrix, &[wordy, crane]);
+        assert_eq!(game.unsolved().len(), 2);
+
+        let scores = game.guess(matrix.answer_guess(crane));
+        assert_eq!(scores[1], Some(pack_score(&[LetterState::Green; 5])));
+        assert!(!game.is_solved());
+        assert_eq!(game.unsolved().len(), 1);
+
+        // The solved board isn't scored again
+        let scores = game.guess(matrix.answer_guess(wordy));
+        assert_eq!(scores[1], None);
+        assert!(game.is_solved());
+        assert_eq!(game.played().len(), 2);
+    }
+
+    #[test]
+    fn test_find_best_multi_guess() {
+        let matrix = matrix();
+        let all_answers = matrix.all_answers();
+        let robin = matrix.answer_index(&to_chars("robin")).unwrap();
+        let weary = matrix.guess_index(&to_chars("weary")).unwrap();
+
+        // A board with one answer left is guessed first
+        let solved = matrix.filter_answers(weary, matrix.score(weary, robin), &all_answers);
+        assert_eq!(solved.len(), 1);
+        assert_eq!(
+            matrix.find_best_multi_guess(&[all_answers.clone(), solved]),
+            matrix.answer_guess(robin)
+        );
+
+        // Same boards, same guess as one board
+        let guess = matrix.find_best_multi_guess(std::slice::from_ref(&all_answers));
+        assert_eq!(
+            guess,
+            matrix.find_best_multi_guess(&[all_answers.clone(), all_answers.clone()])
+        );
+        assert!(matrix.evaluate_guess(guess, &all_answers) < ANSWERS.len());
+    }
+
+    #[test]
+    fn test_play_multi_game() {
+        let matrix = matrix();
+        for boards in [1, 2, 4, 8] {
+            let answers = (0..boards).collect::<Vec<_>>();
+            let played = play_multi_game(&answers, &matrix, 20);
+            // Every board gets all greens once
+            for board in 0..boards {
+                let greens = played
+                    .iter()
+                    .filter(|(_, scores)| {
+                        scores[board] == Some(pack_score(&[LetterState::Green; 5]))
+                    })
+                    .count();
+                assert_eq!(greens, 1);
+            }
+            assert!(played.len() >= boards);
+        }
+
+        // Not enough guesses to finish
+        assert_eq!(play_multi_game(&[0, 1, 2, 3], &matrix, 2).len(), 2);
+    }
+}
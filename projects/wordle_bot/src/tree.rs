@@ -0,0 +1,839 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+use crate::{
+    num_scores, pack_score, unpack_score, AnswerSet, LetterState, PackedScore, ScoreMatrix,
+};
+
+/// What to guess in every state of a game, built by [`build_tree`]
+///
+/// Each guess maps every score it can get to the subtree to play next. An
+/// all green score maps to [`DecisionTree::Solved`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecisionTree<const N: usize> {
+    /// The previous guess was the answer
+    Solved,
+    Guess {
+        guess: [char; N],
+        children: HashMap<[LetterState; N], DecisionTree<N>>,
+    },
+}
+
+impl<const N: usize> DecisionTree<N> {
+    /// Returns the guess to make, or `None` once solved
+    pub fn guess(&self) -> Option<[char; N]> {
+        match self {
+            DecisionTree::Solved => None,
+            DecisionTree::Guess { guess, .. } => Some(*guess),
+        }
+    }
+
+    /// Returns the subtree to play after the guess gets `score`
+    pub fn next(&self, score: &[LetterState; N]) -> Option<&DecisionTree<N>> {
+        match self {
+            DecisionTree::Solved => None,
+            DecisionTree::Guess { children, .. } => children.get(score),
+        }
+    }
+
+    /// Returns the subtree for a game so far, or `None` if a guess wasn't the
+    /// one the tree makes or a score isn't possible
+    ///
+    /// Takes one lookup per guess, and games last at most a handful of
+    /// guesses, so any state is answered in constant time.
+    pub fn follow(&self, played: &[([char; N], [LetterState; N])]) -> Option<&DecisionTree<N>> {
+        let mut node = self;
+        for (guess, score) in played {
+            if node.guess() != Some(*guess) {
+                return None;
+            }
+            node = node.next(score)?;
+        }
+        return Some(node);
+    }
+
+    /// Returns every answer in the tree and the number of guesses to solve it
+    pub fn answer_depths(&self) -> Vec<([char; N], usize)> {
+        let mut depths = Vec::new();
+        self.collect_depths(1, &mut depths);
+        depths.sort();
+        return depths;
+    }
+
+    fn collect_depths(&self, depth: usize, depths: &mut Vec<([char; N], usize)>) {
+        if let DecisionTree::Guess { guess, children } = self {
+            for (score, child) in children {
+                if *score == [LetterState::Green; N] {
+                    depths.push((*guess, depth));
+                } else {
+                    child.collect_depths(depth + 1, depths);
+                }
+            }
+        }
+    }
+
+    /// Sum of the guesses needed to solve every answer, divide by the number
+    /// of answers for the average
+    pub fn total_guesses(&self) -> usize {
+        return self.answer_depths().iter().map(|(_, d)| d).sum();
+    }
+
+    /// Writes the tree as nested JSON objects
+    ///
+    /// Each guess is `{"guess":"salet","map":{...}}`, with the map keyed by
+    /// score strings of `B` (gray), `Y` (yellow) and `G` (green) in
+    /// alphabetical order. Solved states are `null`, e.g. `"GGGGG":null`.
+    /// Letters are written as is, so any alphabet without `"` or `\\` works.
+    pub fn to_json(&self) -> String {
+        let mut s = String::new();
+        self.write_json(&mut s, None);
+        return s;
+    }
+
+    /// Writes how the tree was searched in the root object too, when set
+    fn write_json(&self, s: &mut String, search: Option<TreeSearch>) {
+        match self {
+            DecisionTree::Solved => s.push_str("null"),
+            DecisionTree::Guess { guess, children } => {
+                s.push('{');
+                if let Some(search) = search {
+                    s.push_str(&format!("\"candidates\":{},", search.candidates()));
+                }
+                s.push_str("\"guess\":\"");
+                s.extend(guess.iter());
+                s.push_str("\",\"map\":{");
+                let mut scores = children.keys().collect::<Vec<_>>();
+                scores.sort_by_key(|score| score_to_string(score));
+                for (i, score) in scores.into_iter().enumerate() {
+                    if i > 0 {
+                        s.push(',');
+                    }
+                    s.push('"');
+                    s.push_str(&score_to_string(score));
+                    s.push_str("\":");
+                    children[score].write_json(s, None);
+                }
+                s.push_str("}}");
+            }
+        }
+    }
+
+    /// Reads a tree written by [`DecisionTree::to_json`]
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        return Self::from_cache_json(s).map(|(tree, _)| tree);
+    }
+
+    /// Reads a tree and how it was searched, trees without a `candidates`
+    /// number were written by [`DecisionTree::to_json`]
+    fn from_cache_json(s: &str) -> Result<(Self, Option<TreeSearch>), String> {
+        let mut parser = JsonParser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let mut candidates = None;
+        let tree = parser.parse_node(Some(&mut candidates))?;
+        parser.skip_whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err(parser.error("expected end of input"));
+        }
+        return Ok((tree, candidates.map(TreeSearch::from_candidates)));
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        return fs::write(path, self.to_json()).map_err(|e| e.to_string());
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        return Self::from_json(&fs::read_to_string(path).map_err(|e| e.to_string())?);
+    }
+}
+
+/// How a decision tree is searched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeSearch {
+    /// [`build_tree`] with this many candidates, a heuristic tree
+    Candidates(usize),
+    /// [`build_optimal_tree`]
+    Optimal,
+}
+
+impl TreeSearch {
+    /// Number written to the cache, every guess is searched for the optimal
+    /// tree so it's written as 0 for no limit
+    fn candidates(self) -> usize {
+        match self {
+            TreeSearch::Candidates(candidates) => candidates.max(1),
+            TreeSearch::Optimal => 0,
+        }
+    }
+
+    fn from_candidates(candidates: usize) -> Self {
+        match candidates {
+            0 => TreeSearch::Optimal,
+            n => TreeSearch::Candidates(n),
+        }
+    }
+}
+
+/// Returns the score as a string of `B` (gray), `Y` (yellow) and `G` (green)
+pub fn score_to_string<const N: usize>(score: &[LetterState; N]) -> String {
+    return score
+        .iter()
+        .map(|s| match s {
+            LetterState::Green => 'G',
+            LetterState::Yellow => 'Y',
+            LetterState::Gray => 'B',
+        })
+        .collect();
+}
+
+/// Parses a score written by [`score_to_string`], `X` is also accepted for
+/// gray like in interactive mode
+pub fn score_from_string<const N: usize>(s: &str) -> Option<[LetterState; N]> {
+    if s.chars().count() != N {
+        return None;
+    }
+
+    let mut score = [LetterState::Gray; N];
+    for (i, c) in s.chars().enumerate() {
+        score[i] = match c {
+            'G' => LetterState::Green,
+            'Y' => LetterState::Yellow,
+            'B' | 'X' => LetterState::Gray,
+            _ => return None,
+        };
+    }
+    return Some(score);
+}
+
+/// Only handles what [`DecisionTree::to_json`] writes: objects, strings
+/// without escapes, `null` and the cache's `candidates` number
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonParser {
+    fn error(&self, message: &str) -> String {
+        return format!("invalid tree at character {}: {}", self.pos, message);
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        return self.chars.get(self.pos).copied();
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("expected '{}'", c)));
+        }
+        self.pos += 1;
+        return Ok(());
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.get(self.pos) {
+                Some('"') => break,
+                Some('\\') => return Err(self.error("escapes aren't supported")),
+                Some(c) => s.push(*c),
+                None => return Err(self.error("unterminated string")),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        return Ok(s);
+    }
+
+    fn parse_usize(&mut self) -> Result<usize, String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        return self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .map_err(|_| self.error("expected a number"));
+    }
+
+    /// Calls `f` with each key of an object, `f` parses the value
+    fn parse_object(
+        &mut self,
+        mut f: impl FnMut(&mut Self, String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.expect('{')?;
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            f(self, key)?;
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    /// Reads a `candidates` key into `candidates` if it's given, only the
+    /// root of a cached tree has one
+    fn parse_node<const N: usize>(
+        &mut self,
+        mut candidates: Option<&mut Option<usize>>,
+    ) -> Result<DecisionTree<N>, String> {
+        if self.peek() == Some('n') {
+            if self.chars[self.pos..].starts_with(&['n', 'u', 'l', 'l']) {
+                self.pos += 4;
+                return Ok(DecisionTree::Solved);
+            }
+            return Err(self.error("expected null"));
+        }
+
+        let mut guess = None;
+        let mut children = HashMap::new();
+        self.parse_object(|parser, key| {
+            match key.as_str() {
+                "guess" => {
+                    let word = parser.parse_string()?;
+                    let chars = word.chars().collect::<Vec<char>>();
+                    guess =
+                        Some(<[char; N]>::try_from(chars).map_err(|_| {
+                            parser.error(&format!("guesses must be {} letters", N))
+                        })?);
+                }
+                "map" => parser.parse_object(|parser, key| {
+                    let score = score_from_string(&key)
+                        .ok_or_else(|| parser.error(&format!("invalid score {}", key)))?;
+                    children.insert(score, parser.parse_node(None)?);
+                    Ok(())
+                })?,
+                "candidates" if candidates.is_some() => {
+                    *candidates.as_deref_mut().unwrap() = Some(parser.parse_usize()?)
+                }
+                _ => return Err(parser.error(&format!("unknown key {}", key))),
+            }
+            Ok(())
+        })?;
+
+        let guess = guess.ok_or_else(|| self.error("missing guess"))?;
+        return Ok(DecisionTree::Guess { guess, children });
+    }
+}
+
+/// Builds a decision tree that solves every answer
+///
+/// At each state the `candidates` guesses that leave the fewest expected
+/// answers, the same measure as [`crate::evaluate_guess`], are each built
+/// out and the one with the fewest total guesses is kept. With 1 candidate
+/// this is the greedy tree. More candidates search more of the tree and get
+/// closer to the optimal one, at a cost that grows exponentially with depth.
+/// The tree is only a heuristic, see [`build_optimal_tree`] for the best one.
+///
+/// Ties go to guesses that could be the answer, then to the first
+/// alphabetically, so the same words always build the same tree. Answers can
+/// always be guessed, even if they aren't in `guesses`.
+pub fn build_tree<const N: usize>(
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+    candidates: usize,
+) -> DecisionTree<N> {
+    let matrix = ScoreMatrix::new(guesses, answers);
+    let (tree, _) = build_node(&matrix, &matrix.all_answers(), candidates.max(1));
+    return tree;
+}
+
+/// Returns the tree for `answers` and its total guesses
+fn build_node<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    answers: &AnswerSet,
+    candidates: usize,
+) -> (DecisionTree<N>, usize) {
+    let mut best: Option<(DecisionTree<N>, usize)> = None;
+
+    for guess in rank_guesses(matrix, answers, candidates) {
+        // Every answer takes this guess, then whatever its subtree needs
+        let mut total = answers.len();
+        let mut children = HashMap::new();
+        for (score, partition) in partition(matrix, guess, answers) {
+            let score = unpack_score(score);
+            if score == [LetterState::Green; N] {
+                children.insert(score, DecisionTree::Solved);
+                continue;
+            }
+            let (child, child_total) = build_node(matrix, &partition, candidates);
+            total += child_total;
+            children.insert(score, child);
+        }
+
+        if best.as_ref().map_or(true, |(_, t)| total < *t) {
+            let guess = matrix.guesses()[guess];
+            best = Some((DecisionTree::Guess { guess, children }, total));
+        }
+    }
+
+    return best.unwrap();
+}
+
+/// Builds the decision tree with the fewest total guesses, the optimal tree
+///
+/// A branch and bound search over every guess. Each partition of `n` answers
+/// takes at least `2n - 1` more guesses, since only one of them can be the
+/// next guess, so a guess is only built out while that bound is below the
+/// best tree found so far, and its subtrees only get what's left of the
+/// best total. Subtrees are cached by their answers, as different guesses
+/// often leave the same ones.
+///
+/// The search is exponential. With every allowed Wordle guess it takes
+/// seconds for a couple of hundred answers, but far too long for the full
+/// list. Ties go to the guess with the lowest bound, then to guesses that
+/// could be the answer, then to the first alphabetically.
+pub fn build_optimal_tree<const N: usize>(
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+) -> DecisionTree<N> {
+    let matrix = ScoreMatrix::new(guesses, answers);
+    let mut solved = HashMap::new();
+    let (tree, _) = solve_node(&matrix, &matrix.all_answers(), usize::MAX, &mut solved)
+        .expect("every answer can be guessed");
+    return tree;
+}
+
+/// Fewest total guesses that could solve `n` answers, one of them can be
+/// guessed next and the rest need at least two guesses
+fn min_total(n: usize) -> usize {
+    return (2 * n).saturating_sub(1);
+}
+
+/// Returns the optimal tree for `answers` and its total guesses, or `None`
+/// if it takes `limit` guesses or more
+fn solve_node<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    answers: &AnswerSet,
+    mut limit: usize,
+    solved: &mut HashMap<AnswerSet, (DecisionTree<N>, usize)>,
+) -> Option<(DecisionTree<N>, usize)> {
+    if let Some((tree, total)) = solved.get(answers) {
+        return (*total < limit).then(|| (tree.clone(), *total));
+    }
+    if min_total(answers.len()) >= limit {
+        return None;
+    }
+    if answers.len() == 1 {
+        let guess = matrix.guesses()[matrix.answer_guess(answers.iter().next().unwrap())];
+        let mut children = HashMap::new();
+        children.insert([LetterState::Green; N], DecisionTree::Solved);
+        return Some((DecisionTree::Guess { guess, children }, 1));
+    }
+
+    let all_green = pack_score(&[LetterState::Green; N]);
+    let mut options = Vec::new();
+    for guess in 0..matrix.guesses().len() {
+        let partitions = partition(matrix, guess, answers);
+        let is_answer = partitions.contains_key(&all_green);
+        if !is_answer && partitions.len() == 1 {
+            continue; // Every answer scores the same, so nothing is learned
+        }
+        let bound = answers.len()
+            + partitions
+                .iter()
+                .filter(|(score, _)| **score != all_green)
+                .map(|(_, p)| min_total(p.len()))
+                .sum::<usize>();
+        options.push((bound, !is_answer, guess, partitions));
+    }
+    options.sort_by_key(|(bound, not_answer, guess, _)| (*bound, *not_answer, *guess));
+
+    let mut best = None;
+    for (bound, _, guess, partitions) in options {
+        if bound >= limit {
+            break; // Sorted, so no other guess can do better
+        }
+
+        // Solve the largest partitions first, they're the most likely to
+        // go over the limit
+        let mut partitions = partitions.into_iter().collect::<Vec<_>>();
+        partitions.sort_by_key(|(score, p)| (usize::MAX - p.len(), *score));
+
+        let mut total = bound;
+        let mut children = HashMap::new();
+        for (score, partition) in partitions {
+            if score == all_green {
+                children.insert(unpack_score(score), DecisionTree::Solved);
+                continue;
+            }
+            // The bound already counts this partition's minimum
+            let child_min = min_total(partition.len());
+            match solve_node(matrix, &partition, limit - total + child_min, solved) {
+                Some((child, child_total)) => {
+                    total += child_total - child_min;
+                    children.insert(unpack_score(score), child);
+                }
+                None => {
+                    total = limit;
+                    break;
+                }
+            }
+        }
+
+        if total < limit {
+            limit = total;
+            let guess = matrix.guesses()[guess];
+            best = Some((DecisionTree::Guess { guess, children }, total));
+        }
+    }
+
+    if let Some(best) = &best {
+        solved.insert(answers.clone(), best.clone());
+    }
+    return best;
+}
+
+/// Splits `answers` by the score they give `guess`
+fn partition<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    guess: usize,
+    answers: &AnswerSet,
+) -> HashMap<PackedScore, AnswerSet> {
+    let mut partitions: HashMap<PackedScore, AnswerSet> = HashMap::new();
+    for answer in answers.iter() {
+        partitions
+            .entry(matrix.score(guess, answer))
+            .or_insert_with(|| AnswerSet::new(matrix.answers().len()))
+            .insert(answer);
+    }
+    return partitions;
+}
+
+/// Returns the guesses with the fewest expected answers left, skipping ones
+/// that can't tell the answers apart
+fn rank_guesses<const N: usize>(
+    matrix: &ScoreMatrix<N>,
+    answers: &AnswerSet,
+    candidates: usize,
+) -> Vec<usize> {
+    if answers.len() == 1 {
+        return vec![matrix.answer_guess(answers.iter().next().unwrap())];
+    }
+
+    let all_green = pack_score(&[LetterState::Green; N]) as usize;
+    let mut ranked = Vec::new();
+    let mut counts = vec![0; num_scores::<N>()];
+    for guess in 0..matrix.guesses().len() {
+        counts.fill(0);
+        for answer in answers.iter() {
+            counts[matrix.score(guess, answer) as usize] += 1;
+        }
+
+        let is_answer = counts[all_green] > 0;
+        if !is_answer && counts.contains(&answers.len()) {
+            continue; // Every answer scores the same, so nothing is learned
+        }
+        // Proportional to the expected number of answers left
+        let sum_of_squares: usize = counts.iter().map(|c| c * c).sum();
+        ranked.push((sum_of_squares, !is_answer, guess));
+    }
+
+    ranked.sort();
+    return ranked
+        .into_iter()
+        .take(candidates)
+        .map(|(_, _, guess)| guess)
+        .collect();
+}
+
+/// Loads the tree cached at `path` if it solves exactly these answers, a
+/// tree for other answers or another puzzle gives the wrong guesses. Also
+/// returns how it was searched, if the cache says.
+pub fn load_cached_tree<const N: usize>(
+    path: &str,
+    answers: &HashSet<[char; N]>,
+) -> Option<(DecisionTree<N>, Option<TreeSearch>)> {
+    let json = fs::read_to_string(path).ok()?;
+    let (tree, candidates) = DecisionTree::from_cache_json(&json).ok()?;
+    let tree_answers = tree
+        .answer_depths()
+        .into_iter()
+        .map(|(answer, _)| answer)
+        .collect::<HashSet<_>>();
+    if tree_answers != *answers {
+        return None;
+    }
+    return Some((tree, candidates));
+}
+
+/// Loads the tree cached at `path`, building and caching a new one if there
+/// isn't a readable tree for exactly these answers searched the same way
+///
+/// The cache is the [`DecisionTree::to_json`] format with a `candidates`
+/// number added to the root object. It's 0 for the optimal tree, any other
+/// number means the tree is a heuristic one from [`build_tree`].
+pub fn load_or_build_tree<const N: usize>(
+    path: &str,
+    answers: &HashSet<[char; N]>,
+    guesses: &HashSet<[char; N]>,
+    search: TreeSearch,
+) -> Result<DecisionTree<N>, String> {
+    let search = TreeSearch::from_candidates(search.candidates());
+    if let Some((tree, cached)) = load_cached_tree(path, answers) {
+        if cached == Some(search) {
+            return Ok(tree);
+        }
+    }
+
+    let tree = match search {
+        TreeSearch::Candidates(candidates) => build_tree(answers, guesses, candidates),
+        TreeSearch::Optimal => build_optimal_tree(answers, guesses),
+    };
+    let mut json = String::new();
+    tree.write_json(&mut json, Some(search));
+    fs::write(path, json).map_err(|e| e.to_string())?;
+    return Ok(tree);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, env};
+
+    use crate::{
+        score_guess,
+        tree::{
+            build_optimal_tree, build_tree, load_cached_tree, load_or_build_tree,
+            score_from_string, DecisionTree, TreeSearch,
+        },
+        LetterState,
+    };
+
+    /// Returns char array from str
+    fn to_chars(s: &str) -> [char; 5] {
+        let mut chars = ['a'; 5];
+        for i in 0..5 {
+            chars[i] = s.chars().nth(i).unwrap();
+        }
+
+        return chars;
+    }
+
+    fn word_set(words: &[&str]) -> HashSet<[char; 5]> {
+        return words.iter().map(|w| to_chars(w)).collect();
+    }
+
+    const ANSWERS: [&str; 8] = [
+        "warby", "wordy", "wormy", "wryly", "crane", "nodes", "foods", "robin",
+    ];
+
+    /// Plays `answer` following the tree, returning the number of guesses
+    fn play(tree: &DecisionTree<5>, answer: &[char; 5]) -> usize {
+        let mut node = tree;
+        let mut num_guesses = 0;
+        while let Some(guess) = node.guess() {
+            num_guesses += 1;
+            node = node.next(&score_guess(&guess, answer)).unwrap();
+        }
+        return num_guesses;
+    }
+
+    #[test]
+    fn test_build_tree() {
+        let answers = word_set(&ANSWERS);
+        let mut guesses = word_set(&["weary", "digit", "xxxxx"]);
+        guesses.extend(answers.iter());
+
+        let tree = build_tree(&answers, &guesses, 1);
+        let depths = tree.answer_depths();
+        assert_eq!(depths.len(), answers.len());
+        for (answer, depth) in depths {
+            assert!(answers.contains(&answer));
+            assert_eq!(play(&tree, &answer), depth);
+        }
+
+        // Same words, same tree
+        assert_eq!(tree, build_tree(&answers, &guesses, 1));
+        // Searching more candidates never does worse
+        let searched = build_tree(&answers, &guesses, 4);
+        assert!(searched.total_guesses() <= tree.total_guesses());
+    }
+
+    #[test]
+    fn test_optimal_tree() {
+        let answers = word_set(&ANSWERS);
+        let mut guesses = word_set(&["weary", "digit", "xxxxx"]);
+        guesses.extend(answers.iter());
+
+        let tree = build_optimal_tree(&answers, &guesses);
+        for (answer, depth) in tree.answer_depths() {
+            assert_eq!(play(&tree, &answer), depth);
+        }
+        assert_eq!(tree.answer_depths().len(), answers.len());
+
+        // Searching every guess at every state finds the same total, the
+        // bounds only skip trees that can't be better
+        let exhaustive = build_tree(&answers, &guesses, usize::MAX);
+        assert_eq!(tree.total_guesses(), exhaustive.total_guesses());
+        assert!(tree.total_guesses() <= build_tree(&answers, &guesses, 1).total_guesses());
+        assert_eq!(tree, build_optimal_tree(&answers, &guesses));
+
+        // Two answers take 3 guesses, only one can be guessed first
+        let pair = word_set(&["crane", "robin"]);
+        assert_eq!(build_optimal_tree(&pair, &pair).total_guesses(), 3);
+    }
+
+    #[test]
+    fn test_single_answer() {
+        let answers = word_set(&["crane"]);
+        let tree = build_tree(&answers, &word_set(&["weary"]), 1);
+        assert_eq!(tree.guess(), Some(to_chars("crane")));
+        assert_eq!(tree.total_guesses(), 1);
+    }
+
+    #[test]
+    fn test_follow() {
+        let answers = word_set(&ANSWERS);
+        let tree = build_tree(&answers, &answers, 1);
+
+        let answer = to_chars("wormy");
+        let first = tree.guess().unwrap();
+        let score = score_guess(&first, &answer);
+        let node = tree.follow(&[(first, score)]).unwrap();
+        assert_eq!(Some(node), tree.next(&score));
+
+        // Guessing something the tree wouldn't
+        assert_eq!(tree.follow(&[(to_chars("xxxxx"), score)]), None);
+        assert_eq!(tree.follow(&[]), Some(&tree));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let answers = word_set(&ANSWERS);
+        let tree = build_tree(&answers, &answers, 1);
+        let json = tree.to_json();
+        assert!(json.starts_with("{\"guess\":\""));
+        assert!(json.contains("\"GGGGG\":null"));
+        assert_eq!(DecisionTree::from_json(&json), Ok(tree.clone()));
+
+        // Whitespace is ignored
+        let pretty = json.replace(",", ",\n  ").replace(":", ": ");
+        assert_eq!(DecisionTree::from_json(&pretty), Ok(tree));
+
+        let leaf = "{\"guess\": \"crane\", \"map\": {\"GGGGG\": null}}";
+        let tree = DecisionTree::from_json(leaf).unwrap();
+        assert_eq!(tree.answer_depths(), vec![(to_chars("crane"), 1)]);
+        // Cached trees say how many candidates they searched
+        let cached = "{\"candidates\": 12, \"guess\": \"crane\", \"map\": {\"GGGGG\": null}}";
+        assert_eq!(DecisionTree::from_json(cached), Ok(tree.clone()));
+        assert_eq!(
+            DecisionTree::from_cache_json(cached),
+            Ok((tree, Some(TreeSearch::Candidates(12))))
+        );
+
+        for invalid in [
+            "",
+            "{\"guess\":\"cran\",\"map\":{}}",
+            "{\"guess\":\"crane\",\"map\":{\"GGGG\":null}}",
+            "{\"guess\":\"crane\",\"map\":{\"GGGGG\":nul}}",
+            "{\"guess\":\"crane\",\"map\":{}} extra",
+            "{\"map\":{}}",
+            "{\"guess\":\"crane\",\"map\":{\"GGGGG\":{\"candidates\":1}}}",
+            "{\"candidates\":x,\"guess\":\"crane\",\"map\":{}}",
+        ] {
+            assert!(
+                DecisionTree::<5>::from_json(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_score_strings() {
+        assert_eq!(
+            score_from_string("GYBXB"),
+            Some([
+                LetterState::Green,
+                LetterState::Yellow,
+                LetterState::Gray,
+                LetterState::Gray,
+                LetterState::Gray
+            ])
+        );
+        assert_eq!(score_from_string::<5>("GYB"), None);
+        assert_eq!(score_from_string::<5>("GYBZB"), None);
+    }
+
+    #[test]
+    fn test_tree_cache() {
+        let path = env::temp_dir().join(format!("wordle-tree-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let answers = word_set(&ANSWERS);
+
+        let tree = load_or_build_tree(path, &answers, &answers, TreeSearch::Candidates(1)).unwrap();
+        assert_eq!(DecisionTree::load(path), Ok(tree.clone()));
+        assert_eq!(
+            load_cached_tree(path, &answers),
+            Some((tree.clone(), Some(TreeSearch::Candidates(1))))
+        );
+        // Cached tree is used, even with other guesses
+        assert_eq!(
+            load_or_build_tree(
+                path,
+                &answers,
+                &word_set(&["weary"]),
+                TreeSearch::Candidates(1)
+            ),
+            Ok(tree.clone())
+        );
+
+        // A wider search rebuilds it
+        let searched =
+            load_or_build_tree(path, &answers, &answers, TreeSearch::Candidates(4)).unwrap();
+        assert_eq!(searched, build_tree(&answers, &answers, 4));
+        assert_eq!(
+            load_cached_tree(path, &answers),
+            Some((searched, Some(TreeSearch::Candidates(4))))
+        );
+
+        // Exported trees don't say how they were built, so they're rebuilt
+        tree.save(path).unwrap();
+        assert_eq!(load_cached_tree(path, &answers), Some((tree.clone(), None)));
+        assert_eq!(
+            load_or_build_tree(path, &answers, &answers, TreeSearch::Candidates(1)),
+            Ok(tree)
+        );
+        assert_eq!(
+            load_cached_tree(path, &answers).unwrap().1,
+            Some(TreeSearch::Candidates(1))
+        );
+
+        // The optimal tree is cached apart from the heuristic ones
+        let optimal = load_or_build_tree(path, &answers, &answers, TreeSearch::Optimal).unwrap();
+        assert_eq!(
+            load_cached_tree(path, &answers),
+            Some((optimal, Some(TreeSearch::Optimal)))
+        );
+
+        // Different answers rebuild the tree
+        let fewer = word_set(&ANSWERS[..4]);
+        let rebuilt = load_or_build_tree(path, &fewer, &fewer, TreeSearch::Candidates(1)).unwrap();
+        assert_eq!(rebuilt.answer_depths().len(), 4);
+        assert_eq!(DecisionTree::load(path), Ok(rebuilt));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}